@@ -47,6 +47,10 @@ extern crate failure;
 #[macro_use]
 extern crate failure_derive;
 
+mod throttle;
+
+pub use throttle::Throttled;
+
 #[derive(Fail, Debug)]
 #[fail(display = "HTTP failure ({})", code)]
 pub(crate) struct HttpError {
@@ -64,7 +68,117 @@ cfg_if! {
 }
 
 use std::fs::File;
+use std::io;
 use std::path::Path;
+use std::time::Duration;
+
+use reqwest::header::{Authorization, Basic};
+use reqwest::{RequestBuilder, Response, StatusCode};
+
+/// Basic auth credentials for the server an archive is fetched from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Credentials {
+    pub login: String,
+    pub password: Option<String>,
+}
+
+/// How to connect to the server an archive is fetched from.
+#[derive(Clone, Default)]
+pub struct Connection {
+    /// Basic auth credentials for the server, if it requires them.
+    pub credentials: Option<Credentials>,
+    /// DER-encoded root certificates to trust besides the system's, such as that of
+    /// a proxy that intercepts TLS.
+    pub root_certificates: Vec<Vec<u8>>,
+    /// A client certificate to present, as a DER-encoded PKCS #12 archive and the
+    /// password it is encrypted with.
+    pub identity: Option<(Vec<u8>, String)>,
+    /// Whether the server's certificate is accepted without verifying its hostname.
+    pub insecure: bool,
+    /// The most bytes per second the archive is downloaded at, if it is throttled.
+    pub max_rate: Option<u64>,
+    /// How long to wait for the server to send data, if not the HTTP client's default.
+    pub read_timeout: Option<Duration>,
+    /// The HTTP proxy the archive is requested through, if any, which also carries
+    /// requests for any URL the server redirects to.
+    pub proxy: Option<String>,
+}
+
+impl Connection {
+    /// Produces an HTTP client with the TLS settings of the connection.
+    pub(crate) fn client(&self) -> Result<reqwest::Client, failure::Error> {
+        let mut builder = reqwest::ClientBuilder::new()?;
+        for der in &self.root_certificates {
+            builder.add_root_certificate(reqwest::Certificate::from_der(der)?)?;
+        }
+        if let Some((ref der, ref password)) = self.identity {
+            builder.identity(reqwest::Pkcs12::from_der(der, password)?)?;
+        }
+        if self.insecure {
+            builder.danger_disable_hostname_verification();
+        }
+        if let Some(timeout) = self.read_timeout {
+            builder.timeout(timeout);
+        }
+        if let Some(ref proxy) = self.proxy {
+            builder.proxy(reqwest::Proxy::all(proxy.as_str())?);
+        }
+        Ok(builder.build()?)
+    }
+
+    /// Adds basic auth to a request, if there are credentials for the server.
+    pub(crate) fn authorize(&self, request: &mut RequestBuilder) {
+        if let Some(ref credentials) = self.credentials {
+            request.header(Authorization(Basic {
+                username: credentials.login.clone(),
+                password: credentials.password.clone(),
+            }));
+        }
+    }
+
+    /// Fetches a URL.
+    pub(crate) fn get(&self, url: &str) -> Result<Response, failure::Error> {
+        let client = self.client()?;
+        let mut request = client.get(url)?;
+        self.authorize(&mut request);
+        Ok(request.send()?)
+    }
+}
+
+/// Returns true if a failure to fetch an archive is worth retrying: a server error, a
+/// request timeout, too many requests, or a failure to connect.
+pub fn is_transient(error: &failure::Error) -> bool {
+    if let Some(error) = error.downcast_ref::<HttpError>() {
+        return error.code.is_server_error()
+            || error.code == StatusCode::RequestTimeout
+            || error.code == StatusCode::TooManyRequests;
+    }
+    match error.downcast_ref::<reqwest::Error>() {
+        Some(error) => error.is_http() || error.is_server_error(),
+        None => false,
+    }
+}
+
+/// Returns true if a failure to fetch or unpack an archive was the server taking longer
+/// than the read timeout of the connection to send data.
+pub fn is_timeout(error: &failure::Error) -> bool {
+    if let Some(error) = error.downcast_ref::<io::Error>() {
+        return timed_out(error);
+    }
+    match error.downcast_ref::<reqwest::Error>() {
+        Some(error) => error
+            .get_ref()
+            .and_then(|cause| cause.downcast_ref::<io::Error>())
+            .map_or(false, timed_out),
+        None => false,
+    }
+}
+
+/// Returns true if an I/O error is a read that timed out, which is reported as
+/// `WouldBlock` on some platforms.
+fn timed_out(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::TimedOut || error.kind() == io::ErrorKind::WouldBlock
+}
 
 pub trait Archive {
     fn compressed_size(&self) -> u64;
@@ -90,13 +204,31 @@ cfg_if! {
         pub fn fetch(url: &str, cache_file: &Path) -> Result<Box<Archive>, failure::Error> {
             unimplemented!()
         }
+
+        /// Fetch a remote Node archive from the given URL over the given connection
+        /// and cache its results at the given file path.
+        pub fn fetch_with(
+            url: &str,
+            cache_file: &Path,
+            connection: &Connection,
+        ) -> Result<Box<Archive>, failure::Error> {
+            unimplemented!()
+        }
     } else if #[cfg(unix)] {
         pub fn load(source: File) -> Result<Box<Archive>, failure::Error> {
             Ok(Box::new(Tarball::load(source)?))
         }
 
         pub fn fetch(url: &str, cache_file: &Path) -> Result<Box<Archive>, failure::Error> {
-            Ok(Box::new(Tarball::fetch(url, cache_file)?))
+            fetch_with(url, cache_file, &Connection::default())
+        }
+
+        pub fn fetch_with(
+            url: &str,
+            cache_file: &Path,
+            connection: &Connection,
+        ) -> Result<Box<Archive>, failure::Error> {
+            Ok(Box::new(Tarball::fetch(url, cache_file, connection)?))
         }
     } else if #[cfg(windows)] {
         pub fn load(source: File) -> Result<Box<Archive>, failure::Error> {
@@ -104,7 +236,15 @@ cfg_if! {
         }
 
         pub fn fetch(url: &str, cache_file: &Path) -> Result<Box<Archive>, failure::Error> {
-            Ok(Box::new(Zip::fetch(url, cache_file)?))
+            fetch_with(url, cache_file, &Connection::default())
+        }
+
+        pub fn fetch_with(
+            url: &str,
+            cache_file: &Path,
+            connection: &Connection,
+        ) -> Result<Box<Archive>, failure::Error> {
+            Ok(Box::new(Zip::fetch(url, cache_file, connection)?))
         }
     } else {
         compile_error!("Unsupported OS (expected 'unix' or 'windows').");
@@ -1,7 +1,7 @@
 //! Provides types and functions for fetching and unpacking a Node installation
 //! tarball in Unix operating systems.
 
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{self, Read, Seek, SeekFrom};
 use std::path::Path;
 use std::fs::File;
 
@@ -14,12 +14,12 @@ use tee::TeeReader;
 use progress_read::ProgressRead;
 use failure;
 
-use super::Archive;
+use super::{Archive, Connection, Throttled};
 
 /// A Node installation tarball.
 pub struct Tarball<S: Read> {
     compressed_size: u64,
-    uncompressed_size: u64,
+    uncompressed_size: Option<u64>,
     data: S
 }
 
@@ -27,7 +27,7 @@ impl Tarball<File> {
 
     /// Loads a cached Node tarball from the specified file.
     pub fn load(mut source: File) -> Result<Self, failure::Error> {
-        let uncompressed_size = load_uncompressed_size(&mut source)?;
+        let uncompressed_size = Some(load_uncompressed_size(&mut source)?);
         let compressed_size = source.metadata()?.len();
         Ok(Tarball {
             uncompressed_size,
@@ -55,14 +55,26 @@ fn content_length(response: &Response) -> Result<u64, failure::Error> {
     })
 }
 
-impl Tarball<TeeReader<reqwest::Response, File>> {
+impl Tarball<TeeReader<Throttled<reqwest::Response>, File>> {
 
     /// Initiate fetching of a Node tarball from the given URL, returning
     /// a tarball that can be streamed (and that tees its data to a cache
-    /// file as it streams).
-    pub fn fetch(url: &str, cache_file: &Path) -> Result<Self, failure::Error> {
-        let uncompressed_size = fetch_uncompressed_size(url)?;
-        let response = reqwest::get(url)?;
+    /// file as it streams). Each request is made over the given connection.
+    ///
+    /// The uncompressed size is unknown if the server does not accept byte
+    /// range requests.
+    pub fn fetch(
+        url: &str,
+        cache_file: &Path,
+        connection: &Connection,
+    ) -> Result<Self, failure::Error> {
+        let uncompressed_size = match fetch_uncompressed_size(url, connection) {
+            Err(ref error) if error.downcast_ref::<ByteRangesNotAcceptedError>().is_some() => {
+                None
+            }
+            result => Some(result?),
+        };
+        let response = connection.get(url)?;
 
         if !response.status().is_success() {
             Err(super::HttpError { code: response.status() })?;
@@ -70,7 +82,7 @@ impl Tarball<TeeReader<reqwest::Response, File>> {
 
         let compressed_size = content_length(&response)?;
         let file = File::create(cache_file)?;
-        let data = TeeReader::new(response, file);
+        let data = TeeReader::new(Throttled::new(response, connection.max_rate), file);
 
         Ok(Tarball {
             uncompressed_size,
@@ -83,19 +95,27 @@ impl Tarball<TeeReader<reqwest::Response, File>> {
 
 impl<S: Read> Archive for Tarball<S> {
     fn compressed_size(&self) -> u64 { self.compressed_size }
-    fn uncompressed_size(&self) -> Option<u64> { Some(self.uncompressed_size) }
+    fn uncompressed_size(&self) -> Option<u64> { self.uncompressed_size }
     fn unpack(self: Box<Self>, dest: &Path, progress: &mut FnMut(&(), usize)) -> Result<(), failure::Error> {
         let decoded = GzDecoder::new(self.data);
         let mut tarball = tar::Archive::new(ProgressRead::new(decoded, (), progress));
         tarball.unpack(dest)?;
+
+        // The tar reader stops at the end-of-archive marker, which can leave the
+        // padding after it and the gzip trailer unread. They are read through so that
+        // a streamed tarball is teed to its cache file in full.
+        let mut rest = tarball.into_inner().into_inner().into_inner();
+        io::copy(&mut rest, &mut io::sink())?;
         Ok(())
     }
 }
 
 /// Fetches just the headers of a URL.
-fn headers_only(url: &str) -> Result<Response, failure::Error> {
-    let client = reqwest::Client::new()?;
-    let response = client.head(url)?.send()?;
+fn headers_only(url: &str, connection: &Connection) -> Result<Response, failure::Error> {
+    let client = connection.client()?;
+    let mut request = client.head(url)?;
+    connection.authorize(&mut request);
+    let response = request.send()?;
     if !response.status().is_success() {
         Err(super::HttpError { code: response.status() })?;
     }
@@ -133,13 +153,14 @@ struct UnexpectedContentLengthError {
 /// of a gzip file from a URL. This makes two round-trips to the server but avoids
 /// downloading the entire gzip file. For very small files it's unlikely to be
 /// more efficient than simply downloading the entire file up front.
-fn fetch_isize(url: &str, len: u64) -> Result<[u8; 4], failure::Error> {
-    let client = reqwest::Client::new()?;
-    let mut response = client.get(url)?
-        .header(Range::Bytes(
-            vec![ByteRangeSpec::FromTo(len - 4, len - 1)]
-        ))
-        .send()?;
+fn fetch_isize(url: &str, len: u64, connection: &Connection) -> Result<[u8; 4], failure::Error> {
+    let client = connection.client()?;
+    let mut request = client.get(url)?;
+    request.header(Range::Bytes(
+        vec![ByteRangeSpec::FromTo(len - 4, len - 1)]
+    ));
+    connection.authorize(&mut request);
+    let mut response = request.send()?;
 
     if !response.status().is_success() {
         Err(super::HttpError { code: response.status() })?;
@@ -175,8 +196,8 @@ struct ByteRangesNotAcceptedError;
 /// two round-trips to the server, so it is only more efficient than simply
 /// downloading the file if the file is large enough that downloading it is
 /// slower than the extra round trips.
-fn fetch_uncompressed_size(url: &str) -> Result<u64, failure::Error> {
-    let response = headers_only(url)?;
+fn fetch_uncompressed_size(url: &str, connection: &Connection) -> Result<u64, failure::Error> {
+    let response = headers_only(url, connection)?;
 
     if !response.headers().get::<AcceptRanges>()
         .map(|v| v.iter().any(|unit| *unit == RangeUnit::Bytes))
@@ -185,7 +206,7 @@ fn fetch_uncompressed_size(url: &str) -> Result<u64, failure::Error> {
     }
 
     let len = content_length(&response)?;
-    let packed = fetch_isize(url, len)?;
+    let packed = fetch_isize(url, len, connection)?;
     Ok(unpack_isize(packed))
 }
 
@@ -217,7 +238,7 @@ pub mod tests {
         let test_file = File::open(test_file_path).expect("Couldn't open test file");
         let tarball = Tarball::load(test_file).expect("Failed to load tarball");
 
-        assert_eq!(tarball.uncompressed_size, 10240);
+        assert_eq!(tarball.uncompressed_size, Some(10240));
         assert_eq!(tarball.compressed_size, 402);
     }
 }
@@ -0,0 +1,134 @@
+//! Provides a throttle on the rate that archives are downloaded at.
+//!
+//! Every throttled download in the process draws from the same bucket, so that
+//! downloads made at the same time are limited collectively rather than each
+//! getting the whole rate. The bucket holds a second's worth of the rate, which lets
+//! short bursts go faster.
+
+use std::cmp;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The number of milliseconds of downloading the bucket holds.
+const BURST_MILLIS: u64 = 1000;
+
+/// The time the clock of the bucket starts at, in seconds since the Unix epoch, or 0
+/// before the first throttled read.
+static EPOCH_SECS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// The time at which the bucket will be full again, in milliseconds on the clock of
+/// the bucket. Each read pushes it further out by the time its bytes take at the
+/// maximum rate.
+static FULL_AT: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// A reader that reads no faster than a maximum rate, in bytes per second.
+pub struct Throttled<R: Read> {
+    inner: R,
+    max_rate: Option<u64>,
+    /// The bytes read too recently to have been taken from the bucket, which only
+    /// deals in whole milliseconds.
+    unpaid: u64,
+}
+
+impl<R: Read> Throttled<R> {
+    /// Wraps a reader, limiting it to `max_rate` bytes per second, if given.
+    pub fn new(inner: R, max_rate: Option<u64>) -> Self {
+        Throttled {
+            inner,
+            max_rate,
+            unpaid: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for Throttled<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let rate = match self.max_rate {
+            Some(rate) if rate > 0 => rate,
+            _ => return self.inner.read(buf),
+        };
+
+        // reading a tenth of a second's worth at a time keeps the rate smooth
+        let chunk = cmp::max(rate / 10, 1) as usize;
+        let len = cmp::min(buf.len(), chunk);
+        let read = self.inner.read(&mut buf[..len])?;
+
+        self.unpaid += read as u64;
+        let cost = self.unpaid * 1000 / rate;
+        self.unpaid -= cost * rate / 1000;
+        consume(cost);
+        Ok(read)
+    }
+}
+
+/// Produces the current time on the clock of the bucket, in milliseconds.
+fn now_millis() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0));
+    let epoch = match EPOCH_SECS.compare_and_swap(0, now.as_secs() as usize, Ordering::SeqCst) {
+        0 => now.as_secs(),
+        epoch => epoch as u64,
+    };
+    now.as_secs().saturating_sub(epoch) * 1000 + (now.subsec_nanos() / 1_000_000) as u64
+}
+
+/// Takes `cost` milliseconds of downloading from the bucket, waiting for as long as
+/// the bucket is overdrawn.
+fn consume(cost: u64) {
+    if cost == 0 {
+        return;
+    }
+    loop {
+        let now = now_millis();
+        let full_at = FULL_AT.load(Ordering::SeqCst);
+        let next = cmp::max(full_at as u64, now) + cost;
+        if FULL_AT.compare_and_swap(full_at, next as usize, Ordering::SeqCst) == full_at {
+            if let Some(wait) = overdrawn(next, now) {
+                thread::sleep(wait);
+            }
+            return;
+        }
+    }
+}
+
+/// Produces how long to wait before reading more, once the bucket will next be full at
+/// `full_at`, if it holds more than `BURST_MILLIS` of downloading.
+fn overdrawn(full_at: u64, now: u64) -> Option<Duration> {
+    if full_at > now + BURST_MILLIS {
+        Some(Duration::from_millis(full_at - now - BURST_MILLIS))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{overdrawn, Throttled};
+    use std::io::Read;
+    use std::time::Duration;
+
+    #[test]
+    fn test_overdrawn() {
+        assert_eq!(overdrawn(1500, 1000), None);
+        assert_eq!(overdrawn(2000, 1000), None);
+        assert_eq!(overdrawn(2250, 1000), Some(Duration::from_millis(250)));
+    }
+
+    #[test]
+    fn test_unthrottled() {
+        let mut data = Vec::new();
+        Throttled::new(&[1u8, 2, 3][..], None).read_to_end(&mut data).unwrap();
+        assert_eq!(data, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_throttled_reads_in_chunks() {
+        let mut throttled = Throttled::new(&[0u8; 100][..], Some(200));
+        let mut buf = [0; 64];
+        assert_eq!(throttled.read(&mut buf).unwrap(), 20);
+    }
+}
@@ -5,14 +5,13 @@ use std::io::{self, Read, Seek, copy};
 use std::path::Path;
 use std::fs::{File, create_dir_all};
 
-use reqwest;
 use progress_read::ProgressRead;
 use zip_rs::ZipArchive;
 use verbatim::PathExt;
 
 use failure;
 
-use super::Archive;
+use super::{Archive, Connection, Throttled};
 
 pub struct Zip<S: Read + Seek> {
     compressed_size: u64,
@@ -32,9 +31,13 @@ impl Zip<File> {
     }
 
     /// Initiate fetching of a Node zip archive from the given URL, returning
-    /// a `Remote` data source.
-    pub fn fetch(url: &str, cache_file: &Path) -> Result<Self, failure::Error> {
-        let mut response = reqwest::get(url)?;
+    /// a `Remote` data source. The request is made over the given connection.
+    pub fn fetch(
+        url: &str,
+        cache_file: &Path,
+        connection: &Connection,
+    ) -> Result<Self, failure::Error> {
+        let response = connection.get(url)?;
 
         if !response.status().is_success() {
             Err(super::HttpError { code: response.status() })?;
@@ -42,7 +45,7 @@ impl Zip<File> {
 
         {
             let mut file = File::create(cache_file)?;
-            copy(&mut response, &mut file)?;
+            copy(&mut Throttled::new(response, connection.max_rate), &mut file)?;
         }
 
         let file = File::create(cache_file)?;
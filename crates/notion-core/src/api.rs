@@ -0,0 +1,183 @@
+//! The stable API of notion-core, for tools that want to know which Node a directory
+//! uses without running the `notion` executable, such as editor plugins.
+//!
+//! This module follows semantic versioning: nothing in it is removed or changed
+//! incompatibly without a breaking release of notion-core (a new minor version while
+//! it is 0.x), although it may grow in any release, including new variants of
+//! `Source`. The rest of this crate is the implementation of Notion itself, and may
+//! change in any release.
+//!
+//! ```no_run
+//! # extern crate notion_core;
+//! # use std::path::Path;
+//! # fn main() { example().unwrap() }
+//! # fn example() -> notion_core::api::Fallible<()> {
+//! use notion_core::api;
+//!
+//! if let Some(platform) = api::platform(Path::new("/projects/app"))? {
+//!     println!("node {} (from {})", platform.node().version(), platform.node().source());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Resolution reads the same files and settings that `notion` does, including the
+//! `NOTION_NODE_VERSION`, `NOTION_NPM_VERSION` and `NOTION_YARN_VERSION` variables of
+//! the calling process. It never downloads tools or asks the user anything: the pins
+//! of a project the user hasn't trusted are ignored, just as they are by
+//! `notion status`.
+
+use std::path::{Path, PathBuf};
+
+use semver::Version;
+
+use catalog::LazyCatalog;
+use project;
+use prompt;
+use session::Session;
+
+pub use notion_fail::{ExitCode, Fallible, NotionError};
+pub use prompt::Source;
+
+/// A Node project: a directory with a `package.json` file that isn't a dependency of
+/// another project.
+#[derive(Debug, Clone)]
+pub struct Project {
+    root: PathBuf,
+    pin_file: PathBuf,
+    pinned: bool,
+}
+
+impl Project {
+    /// The directory containing the project's `package.json` file.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The file the project's toolchain is pinned in: its `notion.toml` file if it
+    /// has one, or else its `package.json` file.
+    pub fn pin_file(&self) -> &Path {
+        &self.pin_file
+    }
+
+    /// Returns true if the project pins a Node version.
+    pub fn is_pinned(&self) -> bool {
+        self.pinned
+    }
+}
+
+/// Finds the Node project containing a directory, if any.
+pub fn project(dir: &Path) -> Fallible<Option<Project>> {
+    Ok(project::Project::for_dir(dir)?.map(|project| Project {
+        root: project.root().to_path_buf(),
+        pin_file: project.toolchain_source(),
+        pinned: project.is_pinned(),
+    }))
+}
+
+/// The version of a tool in effect, and where it was selected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolved {
+    version: Version,
+    source: Source,
+}
+
+impl Resolved {
+    /// The version in effect.
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+
+    /// Where the version was selected.
+    pub fn source(&self) -> Source {
+        self.source
+    }
+}
+
+/// The tools in effect in a directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Platform {
+    node: Resolved,
+    yarn: Option<Resolved>,
+}
+
+impl Platform {
+    /// The Node version in effect.
+    pub fn node(&self) -> &Resolved {
+        &self.node
+    }
+
+    /// The Yarn version in effect, if any.
+    pub fn yarn(&self) -> Option<&Resolved> {
+        self.yarn.as_ref()
+    }
+}
+
+/// Resolves the tools in effect in a directory, which is `None` if no Node version is
+/// selected there. A version may not be installed yet, if it would be installed when
+/// it is first run (see `is_node_installed`).
+pub fn platform(dir: &Path) -> Fallible<Option<Platform>> {
+    let mut session = Session::for_dir(dir)?;
+    let mut node = None;
+    let mut yarn = None;
+    for selection in prompt::selections(&mut session)? {
+        let resolved = Resolved {
+            version: selection.version,
+            source: selection.source,
+        };
+        match selection.tool {
+            "node" => node = Some(resolved),
+            "yarn" => yarn = Some(resolved),
+            _ => {}
+        }
+    }
+    Ok(node.map(|node| Platform { node, yarn }))
+}
+
+/// Lists the installed Node versions, oldest first.
+pub fn installed_node_versions() -> Fallible<Vec<Version>> {
+    let catalog = LazyCatalog::new();
+    let versions = catalog.get()?.node.versions.iter().cloned().collect();
+    Ok(versions)
+}
+
+/// Lists the installed Yarn versions, oldest first.
+pub fn installed_yarn_versions() -> Fallible<Vec<Version>> {
+    let catalog = LazyCatalog::new();
+    let versions = catalog.get()?.yarn.versions.iter().cloned().collect();
+    Ok(versions)
+}
+
+/// Returns true if a Node version is installed for this machine's architecture.
+pub fn is_node_installed(version: &Version) -> Fallible<bool> {
+    Ok(LazyCatalog::new().get()?.node.contains(version))
+}
+
+/// Returns true if a Yarn version is installed.
+pub fn is_yarn_installed(version: &Version) -> Fallible<bool> {
+    Ok(LazyCatalog::new().get()?.yarn.contains(version))
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    fn fixture_path(fixture_dir: &str) -> PathBuf {
+        let mut cargo_manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        cargo_manifest_dir.push("fixtures");
+        cargo_manifest_dir.push(fixture_dir);
+        cargo_manifest_dir
+    }
+
+    #[test]
+    fn test_project() {
+        let pinned = project(&fixture_path("basic")).unwrap().unwrap();
+        assert_eq!(pinned.root(), fixture_path("basic").as_path());
+        assert_eq!(pinned.pin_file(), fixture_path("basic").join("package.json").as_path());
+        assert!(pinned.is_pinned());
+
+        let unpinned = project(&fixture_path("no_toolchain")).unwrap().unwrap();
+        assert!(!unpinned.is_pinned());
+    }
+}
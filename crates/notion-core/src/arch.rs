@@ -0,0 +1,194 @@
+//! Provides the `Arch` type, which represents a CPU architecture that Node
+//! distributions are published for, and the `Libc` type, which represents the
+//! C library they are linked against, along with detection of both.
+
+use std::env;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use notion_fail::{ExitCode, NotionError, NotionFail};
+
+/// The environment variable that overrides architecture detection.
+const NOTION_ARCH: &'static str = "NOTION_ARCH";
+
+/// The environment variable that overrides C library detection.
+const NOTION_LIBC: &'static str = "NOTION_LIBC";
+
+/// Thrown when an architecture name is not recognized.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Unrecognized architecture '{}' (expected one of x86, x64, arm64, armv7l)", name)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct UnknownArchError {
+    name: String,
+}
+
+/// A CPU architecture, named the way Node distribution file names name it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Arch {
+    X86,
+    X64,
+    Arm64,
+    Armv7l,
+}
+
+cfg_if! {
+    if #[cfg(target_arch = "x86")] {
+        const COMPILED: Arch = Arch::X86;
+    } else if #[cfg(target_arch = "x86_64")] {
+        const COMPILED: Arch = Arch::X64;
+    } else if #[cfg(target_arch = "aarch64")] {
+        const COMPILED: Arch = Arch::Arm64;
+    } else if #[cfg(target_arch = "arm")] {
+        const COMPILED: Arch = Arch::Armv7l;
+    } else {
+        compile_error!("Unsupported target_arch (expected 'x86', 'x86_64', 'aarch64', or 'arm').");
+    }
+}
+
+cfg_if! {
+    if #[cfg(target_os = "macos")] {
+        /// Returns true if this process is an x64 binary being translated by Rosetta 2
+        /// on Apple Silicon.
+        fn is_translated() -> bool {
+            use std::process::Command;
+
+            Command::new("sysctl")
+                .args(&["-n", "sysctl.proc_translated"])
+                .output()
+                .map(|output| String::from_utf8_lossy(&output.stdout).trim() == "1")
+                .unwrap_or(false)
+        }
+    } else {
+        fn is_translated() -> bool {
+            false
+        }
+    }
+}
+
+impl Arch {
+    /// The architecture this copy of Notion was compiled for.
+    pub fn compiled() -> Arch {
+        COMPILED
+    }
+
+    /// The architecture of the machine, which is the architecture of the Node builds
+    /// Notion installs by default. This can be overridden with the `NOTION_ARCH`
+    /// environment variable, and sees through Rosetta 2 translation, so that an x64
+    /// build of Notion on Apple Silicon still installs arm64 builds of Node.
+    pub fn native() -> Arch {
+        if let Ok(name) = env::var(NOTION_ARCH) {
+            if let Ok(arch) = name.parse() {
+                return arch;
+            }
+        }
+
+        if COMPILED == Arch::X64 && is_translated() {
+            return Arch::Arm64;
+        }
+
+        COMPILED
+    }
+}
+
+impl Display for Arch {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let s = match self {
+            &Arch::X86 => "x86",
+            &Arch::X64 => "x64",
+            &Arch::Arm64 => "arm64",
+            &Arch::Armv7l => "armv7l",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for Arch {
+    type Err = NotionError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        Ok(match src {
+            "x86" | "ia32" | "i686" => Arch::X86,
+            "x64" | "x86_64" | "amd64" => Arch::X64,
+            "arm64" | "aarch64" => Arch::Arm64,
+            "armv7l" | "arm" => Arch::Armv7l,
+            _ => throw!(UnknownArchError {
+                name: src.to_string(),
+            }),
+        })
+    }
+}
+
+/// The C library a Node build is linked against. The official Node builds for Linux
+/// are linked against glibc and do not run on musl-based systems such as Alpine, which
+/// need the musl builds published by the Node unofficial-builds project instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Libc {
+    Glibc,
+    Musl,
+}
+
+cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        /// Returns true if the system's dynamic loader is musl's.
+        fn has_musl_loader() -> bool {
+            use std::fs::read_dir;
+
+            read_dir("/lib")
+                .map(|entries| {
+                    entries.filter_map(|entry| entry.ok()).any(|entry| {
+                        entry.file_name().to_string_lossy().starts_with("ld-musl-")
+                    })
+                })
+                .unwrap_or(false)
+        }
+    } else {
+        fn has_musl_loader() -> bool {
+            false
+        }
+    }
+}
+
+impl Libc {
+    /// The C library of the machine, which can be overridden with the `NOTION_LIBC`
+    /// environment variable (`glibc` or `musl`).
+    pub fn native() -> Libc {
+        match env::var(NOTION_LIBC).as_ref().map(|name| &name[..]) {
+            Ok("musl") => Libc::Musl,
+            Ok("glibc") => Libc::Glibc,
+            _ => if has_musl_loader() {
+                Libc::Musl
+            } else {
+                Libc::Glibc
+            },
+        }
+    }
+
+    /// Produces the suffix Node distribution file names use for builds against this
+    /// C library after the architecture.
+    pub fn archive_suffix(&self) -> &'static str {
+        match self {
+            &Libc::Glibc => "",
+            &Libc::Musl => "-musl",
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{Arch, Libc};
+
+    #[test]
+    fn test_parse_arch() {
+        assert_eq!("x64".parse::<Arch>().unwrap(), Arch::X64);
+        assert_eq!("aarch64".parse::<Arch>().unwrap(), Arch::Arm64);
+        assert_eq!(Arch::Armv7l.to_string(), "armv7l");
+        assert!("sparc".parse::<Arch>().is_err());
+    }
+
+    #[test]
+    fn test_libc_archive_suffix() {
+        assert_eq!(Libc::Glibc.archive_suffix(), "");
+        assert_eq!(Libc::Musl.archive_suffix(), "-musl");
+    }
+}
@@ -0,0 +1,292 @@
+//! Provides the record of the binaries each project has had shimmed automatically, so
+//! that autoshimming only touches the shims of binaries that were added or removed
+//! since it last ran, and so that the shims a project created can be cleaned up once
+//! nothing provides their binaries anymore.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use catalog::Catalog;
+use fs::ensure_containing_dir_exists;
+use notion_fail::{Fallible, NotionError, ResultExt};
+use path;
+use plan::{Action, Plan};
+use shim;
+
+/// Where the shim for a project's binary came from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Origin {
+    /// Autoshimming the project created the shim.
+    Created,
+    /// The shim already existed, so it belongs to whoever created it.
+    Existing,
+}
+
+/// The binaries shimmed for a project, by where their shims came from.
+pub type Record = BTreeMap<String, Origin>;
+
+/// The change in a project's binaries since it was last autoshimmed.
+#[derive(Debug, PartialEq)]
+pub struct Diff {
+    /// The binaries that need new shims.
+    pub added: Vec<String>,
+    /// The binaries whose shims may no longer be needed.
+    pub removed: Vec<String>,
+}
+
+impl Diff {
+    /// Compares the binaries recorded for a project with its current binaries.
+    pub fn between(recorded: &Record, current: &BTreeSet<String>) -> Self {
+        Diff {
+            added: current
+                .iter()
+                .filter(|name| !recorded.contains_key(*name))
+                .cloned()
+                .collect(),
+            removed: recorded
+                .keys()
+                .filter(|name| !current.contains(*name))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// Reads the binaries recorded for the project rooted at `root`, which is empty if
+/// the project has never been autoshimmed.
+pub fn recorded(root: &Path) -> Fallible<Record> {
+    let file = path::autoshim_file(&path::project_key(root))?;
+    match fs::read_to_string(&file) {
+        Ok(src) => Ok(match parse_record(&src) {
+            Some((ref recorded_root, ref record)) if recorded_root == root => record.clone(),
+            _ => Record::new(),
+        }),
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(Record::new()),
+        Err(error) => Err(error).unknown(),
+    }
+}
+
+/// Records the binaries that are shimmed for the project rooted at `root`.
+pub fn record(root: &Path, record: &Record) -> Fallible<()> {
+    let file = path::autoshim_file(&path::project_key(root))?;
+    if record.is_empty() {
+        if file.exists() {
+            fs::remove_file(&file).unknown()?;
+        }
+        return Ok(());
+    }
+
+    ensure_containing_dir_exists(&file)?;
+    let mut file = File::create(&file).unknown()?;
+    file.write_all(format_record(root, record).as_bytes()).unknown()
+}
+
+/// Tests whether the shim for a binary is still needed after the project rooted at
+/// `root` stops providing it: because it is one of Notion's own tools, because another
+/// project's record lists it, because it was created to run a target of the user's
+/// choosing, or because an installed package provides it.
+pub fn needed_elsewhere(root: &Path, name: &str) -> Fallible<bool> {
+    if !shim::is_3p_shim(name) {
+        return Ok(true);
+    }
+
+    let own_key = path::project_key(root);
+    let autoshim_dir = path::autoshim_dir()?;
+    if autoshim_dir.is_dir() {
+        for entry in fs::read_dir(&autoshim_dir).unknown()? {
+            let entry = entry.unknown()?;
+            if entry.file_name().to_string_lossy() == own_key {
+                continue;
+            }
+            let src = fs::read_to_string(entry.path()).unknown()?;
+            if let Some((_, record)) = parse_record(&src) {
+                if record.contains_key(name) {
+                    return Ok(true);
+                }
+            }
+        }
+    }
+
+    if Catalog::current()?.targets.contains_key(name) {
+        return Ok(true);
+    }
+
+    let packages_dir = path::packages_dir()?;
+    if packages_dir.is_dir() {
+        for entry in fs::read_dir(&packages_dir).unknown()? {
+            let package = entry.unknown()?.file_name();
+            let bin = path::package_bin_dir(&package.to_string_lossy())?.join(name);
+            if fs::symlink_metadata(bin).is_ok() {
+                return Ok(true);
+            }
+        }
+    }
+
+    Ok(false)
+}
+
+/// Tests whether the shim for a binary the project rooted at `root` no longer
+/// provides should be deleted: only shims the project created are, and only when
+/// nothing else needs them.
+pub fn should_delete(root: &Path, name: &str, origin: Origin) -> Fallible<bool> {
+    match origin {
+        Origin::Created => Ok(!needed_elsewhere(root, name)?),
+        Origin::Existing => Ok(false),
+    }
+}
+
+/// Finds the records of projects that no longer exist, whose shims are orphans.
+fn orphaned_projects() -> Fallible<Vec<(PathBuf, Record)>> {
+    let autoshim_dir = path::autoshim_dir()?;
+    let mut orphaned = Vec::new();
+    if !autoshim_dir.is_dir() {
+        return Ok(orphaned);
+    }
+
+    for entry in fs::read_dir(&autoshim_dir).unknown()? {
+        let src = fs::read_to_string(entry.unknown()?.path()).unknown()?;
+        if let Some((root, record)) = parse_record(&src) {
+            if !root.join("package.json").is_file() {
+                orphaned.push((root, record));
+            }
+        }
+    }
+    Ok(orphaned)
+}
+
+/// Deletes the shims created for projects that no longer exist, unless something
+/// else still needs them, and forgets those projects. Returns any errors that
+/// occurred while doing so.
+pub fn clean_orphans() -> Vec<NotionError> {
+    let orphaned = match orphaned_projects() {
+        Ok(orphaned) => orphaned,
+        Err(error) => return vec![error],
+    };
+
+    let mut errors = Vec::new();
+    for (root, orphans) in orphaned {
+        let mut kept = Record::new();
+        for (name, origin) in orphans {
+            let result = should_delete(&root, &name, origin).and_then(|delete| {
+                if delete {
+                    shim::delete(&name)?;
+                }
+                Ok(())
+            });
+            if let Err(error) = result {
+                kept.insert(name, origin);
+                errors.push(error);
+            }
+        }
+        if let Err(error) = record(&root, &kept) {
+            errors.push(error);
+        }
+    }
+    errors
+}
+
+/// Plans cleaning up the shims of projects that no longer exist, without touching
+/// the filesystem.
+pub fn plan_clean_orphans() -> Fallible<Plan> {
+    let mut plan = Plan::new();
+    for (root, orphans) in orphaned_projects()? {
+        for (name, origin) in orphans {
+            if should_delete(&root, &name, origin)? {
+                plan.extend(shim::plan_delete(&name)?);
+            }
+        }
+        plan.push(Action::Remove(path::autoshim_file(&path::project_key(&root))?));
+    }
+    Ok(plan)
+}
+
+/// Formats a record: the project root on the first line, so that records can be
+/// traced back to their projects, and then a binary per line, prefixed with where
+/// its shim came from.
+fn format_record(root: &Path, record: &Record) -> String {
+    let mut src = format!("{}\n", root.display());
+    for (name, origin) in record {
+        let origin = match origin {
+            &Origin::Created => "created",
+            &Origin::Existing => "existing",
+        };
+        src.push_str(&format!("{} {}\n", origin, name));
+    }
+    src
+}
+
+/// Parses a record into its project root and binaries. Binaries without an origin
+/// were recorded before origins were, when autoshimming assumed it created them all.
+fn parse_record(src: &str) -> Option<(PathBuf, Record)> {
+    let mut lines = src.lines();
+    let root = PathBuf::from(lines.next()?);
+    let mut record = Record::new();
+    for line in lines.filter(|line| !line.is_empty()) {
+        let mut parts = line.splitn(2, ' ');
+        let (origin, name) = match (parts.next(), parts.next()) {
+            (Some("created"), Some(name)) => (Origin::Created, name),
+            (Some("existing"), Some(name)) => (Origin::Existing, name),
+            _ => (Origin::Created, line),
+        };
+        record.insert(name.to_string(), origin);
+    }
+    Some((root, record))
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    fn record_of(bins: &[(&str, Origin)]) -> Record {
+        bins.iter()
+            .map(|&(name, origin)| (name.to_string(), origin))
+            .collect()
+    }
+
+    fn set(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let recorded = record_of(&[
+            ("eslint", Origin::Created),
+            ("tsc", Origin::Existing),
+            ("mocha", Origin::Created),
+        ]);
+        let diff = Diff::between(&recorded, &set(&["tsc", "jest"]));
+        assert_eq!(
+            diff,
+            Diff {
+                added: vec!["jest".to_string()],
+                removed: vec!["eslint".to_string(), "mocha".to_string()],
+            }
+        );
+
+        let unchanged = Diff::between(&record_of(&[("tsc", Origin::Created)]), &set(&["tsc"]));
+        assert!(unchanged.added.is_empty() && unchanged.removed.is_empty());
+    }
+
+    #[test]
+    fn test_record_format() {
+        let root = PathBuf::from("/projects/app");
+        let bins = record_of(&[("tsc", Origin::Created), ("eslint", Origin::Existing)]);
+        let src = format_record(&root, &bins);
+        assert_eq!(src, "/projects/app\nexisting eslint\ncreated tsc\n");
+        assert_eq!(parse_record(&src), Some((root, bins)));
+    }
+
+    #[test]
+    fn test_parse_record_without_origins() {
+        assert_eq!(
+            parse_record("/projects/app\neslint\ntsc\n"),
+            Some((
+                PathBuf::from("/projects/app"),
+                record_of(&[("eslint", Origin::Created), ("tsc", Origin::Created)])
+            ))
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! Provides support for backing up the user's Notion settings into a small archive
+//! and restoring them again, for recovering from a damaged Notion installation or
+//! replicating a setup on another machine.
+//!
+//! A backup is a gzipped tarball with the following layout:
+//!
+//! ```text
+//! backup.toml                     the backup manifest
+//! catalog.toml                    the user catalog (if any)
+//! config.toml                     the user configuration file (if any)
+//! hooks.toml                      the user hooks file (if any)
+//! ```
+//!
+//! Unlike a bundle, a backup does not contain any installed tool versions. Shims for
+//! 3rd-party executables are recorded by name in the manifest and recreated on restore.
+
+use std::fs::{read_to_string, rename, File};
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use semver::Version;
+use tar;
+use toml;
+
+use bundle::installed_shims;
+use catalog::Catalog;
+use distro::staging_dir;
+use fs::ensure_containing_dir_exists;
+use lock::FileLock;
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+use path;
+use shim;
+
+/// The name of the manifest file inside a backup.
+const MANIFEST_FILE: &'static str = "backup.toml";
+
+/// The names of the catalog, configuration, and hooks files inside a backup.
+const CATALOG_FILE: &'static str = "catalog.toml";
+const CONFIG_FILE: &'static str = "config.toml";
+const HOOKS_FILE: &'static str = "hooks.toml";
+
+/// Thrown when a backup file is missing its manifest or its contents are malformed.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} is not a valid Notion backup: {}", file, error)]
+#[notion_fail(code = "FileSystemError")]
+pub(crate) struct InvalidBackupError {
+    file: String,
+    error: String,
+}
+
+/// The serialized form of the backup manifest.
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    shims: Vec<String>,
+}
+
+/// The contents of a backup.
+pub struct Contents {
+    /// The default Node version in the backed up catalog.
+    pub node: Option<Version>,
+    /// The default Yarn version in the backed up catalog.
+    pub yarn: Option<Version>,
+    /// The names of the backed up settings files.
+    pub files: Vec<&'static str>,
+    /// The names of the backed up shims for 3rd-party executables.
+    pub shims: Vec<String>,
+}
+
+/// The settings files included in a backup, along with where they live.
+fn settings_files() -> Fallible<Vec<(&'static str, PathBuf)>> {
+    Ok(vec![
+        (CATALOG_FILE, path::user_catalog_file()?),
+        (CONFIG_FILE, path::user_config_file()?),
+        (HOOKS_FILE, path::user_hooks_file()?),
+    ])
+}
+
+/// Backs up the user catalog, configuration and hooks files, and the names of the
+/// installed shims into a backup file.
+pub fn create(catalog: &Catalog, dest: &Path) -> Fallible<Contents> {
+    let mut contents = Contents {
+        node: catalog.node.default.clone(),
+        yarn: catalog.yarn.default.clone(),
+        files: Vec::new(),
+        shims: installed_shims()?,
+    };
+
+    let file = File::create(dest).unknown()?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    let manifest = Manifest {
+        shims: contents.shims.clone(),
+    };
+    let manifest = toml::to_string(&manifest).unknown()?;
+    let mut header = tar::Header::new_gnu();
+    header.set_path(MANIFEST_FILE).unknown()?;
+    header.set_size(manifest.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append(&header, manifest.as_bytes()).unknown()?;
+
+    {
+        // Hold the catalog lock so that the catalog file isn't copied mid-write.
+        let _lock = FileLock::shared(&path::catalog_lock_file()?)?;
+        for (name, file) in settings_files()? {
+            if file.is_file() {
+                builder.append_path_with_name(&file, name).unknown()?;
+                contents.files.push(name);
+            }
+        }
+    }
+
+    builder.into_inner().unknown()?.finish().unknown()?;
+    Ok(contents)
+}
+
+/// Drops the versions from a restored catalog that are not installed on this machine,
+/// since a backup does not contain them. Default versions are kept, and installed the
+/// first time they are needed.
+fn prune_uninstalled(catalog: &mut Catalog) -> Fallible<()> {
+    let mut node = Vec::new();
+    for version in &catalog.node.versions {
+        if path::node_version_dir(&version.to_string())?.is_dir() {
+            node.push(version.clone());
+        }
+    }
+    catalog.node.versions = node.into_iter().collect();
+
+    let mut arches = Vec::new();
+    for (version, version_arches) in &catalog.node.arches {
+        let mut installed = Vec::new();
+        for arch in version_arches {
+            let key = path::node_version_key(&version.to_string(), *arch);
+            if path::node_version_dir(&key)?.is_dir() {
+                installed.push(*arch);
+            }
+        }
+        if !installed.is_empty() {
+            arches.push((version.clone(), installed.into_iter().collect()));
+        }
+    }
+    catalog.node.arches = arches.into_iter().collect();
+
+    let mut yarn = Vec::new();
+    for version in &catalog.yarn.versions {
+        if path::yarn_version_dir(&version.to_string())?.is_dir() {
+            yarn.push(version.clone());
+        }
+    }
+    catalog.yarn.versions = yarn.into_iter().collect();
+
+    Ok(())
+}
+
+/// Restores the settings files and shims in a backup file, replacing the current
+/// settings files. This does not read the current catalog, so that it can recover
+/// from a catalog file that can no longer be parsed.
+pub fn restore(src: &Path) -> Fallible<Contents> {
+    let invalid = |error: String| InvalidBackupError {
+        file: src.to_string_lossy().to_string(),
+        error,
+    };
+
+    let staging = staging_dir()?;
+    let file = File::open(src).unknown()?;
+    tar::Archive::new(GzDecoder::new(file))
+        .unpack(staging.path())
+        .with_context(|error| invalid(error.to_string()))?;
+
+    let manifest_file = staging.path().join(MANIFEST_FILE);
+    if !manifest_file.is_file() {
+        throw!(invalid(format!("missing {}", MANIFEST_FILE)));
+    }
+    let manifest: Manifest = toml::from_str(&read_to_string(manifest_file).unknown()?)
+        .with_context(|error| invalid(error.to_string()))?;
+
+    let mut contents = Contents {
+        node: None,
+        yarn: None,
+        files: Vec::new(),
+        shims: manifest.shims,
+    };
+
+    let staged_catalog = staging.path().join(CATALOG_FILE);
+    let restored = if staged_catalog.is_file() {
+        let src = read_to_string(&staged_catalog).unknown()?;
+        let mut catalog: Catalog = src.parse()
+            .with_context(|error| invalid(format!("{}: {}", CATALOG_FILE, error)))?;
+        prune_uninstalled(&mut catalog)?;
+        contents.node = catalog.node.default.clone();
+        contents.yarn = catalog.yarn.default.clone();
+        Some(catalog)
+    } else {
+        None
+    };
+
+    {
+        let _lock = FileLock::exclusive(&path::catalog_lock_file()?)?;
+
+        if let Some(catalog) = restored {
+            catalog.write()?;
+            contents.files.push(CATALOG_FILE);
+        }
+
+        for (name, file) in settings_files()? {
+            let staged = staging.path().join(name);
+            if name != CATALOG_FILE && staged.is_file() {
+                ensure_containing_dir_exists(&file)?;
+                rename(staged, file).unknown()?;
+                contents.files.push(name);
+            }
+        }
+    }
+
+    for name in &contents.shims {
+        shim::create(name)?;
+    }
+
+    Ok(contents)
+}
+
@@ -0,0 +1,146 @@
+//! Provides the shim latency benchmark, for `notion bench shim`.
+
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use image::Image;
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+use path;
+
+/// The arguments the benchmarked tool runs with, which should do as little work as
+/// possible so that the time is dominated by starting the process.
+pub const ARGS: [&'static str; 1] = ["--version"];
+
+/// Thrown when a run of the benchmarked command could not be started or failed.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not benchmark '{}': {}", command, error)]
+#[notion_fail(code = "ExecutionFailure")]
+pub(crate) struct BenchRunError {
+    command: String,
+    error: String,
+}
+
+/// The latency percentiles of a set of runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Latency {
+    pub p50: Duration,
+    pub p95: Duration,
+}
+
+impl Latency {
+    /// Computes the percentiles of a set of samples.
+    pub fn of(samples: &[Duration]) -> Self {
+        let mut sorted = samples.to_vec();
+        sorted.sort();
+        Latency {
+            p50: percentile(&sorted, 50),
+            p95: percentile(&sorted, 95),
+        }
+    }
+
+    /// The latency added on top of a baseline, which is zero where this is faster.
+    pub fn over(&self, baseline: &Latency) -> Latency {
+        Latency {
+            p50: self.p50.checked_sub(baseline.p50).unwrap_or_default(),
+            p95: self.p95.checked_sub(baseline.p95).unwrap_or_default(),
+        }
+    }
+}
+
+/// The results of the shim benchmark.
+pub struct ShimBench {
+    /// The number of times each command ran.
+    pub iterations: usize,
+    /// The latency of running Node directly.
+    pub direct: Latency,
+    /// The latency of running Node through its shim.
+    pub shim: Latency,
+}
+
+impl ShimBench {
+    /// The latency that dispatching through the shim adds.
+    pub fn overhead(&self) -> Latency {
+        self.shim.over(&self.direct)
+    }
+}
+
+/// Runs `node --version` `iterations` times directly from a platform image and
+/// `iterations` times through the `node` shim, measuring the wall-clock time of each
+/// run. Each command runs once beforehand, so that caches are warm and the shim
+/// doesn't install anything while it is being measured.
+pub fn bench_shim(image: &Image, iterations: usize) -> Fallible<ShimBench> {
+    let mut direct = Command::new("node");
+    direct.args(&ARGS).env("PATH", image.path()?);
+    let mut shim = Command::new(path::shim_file("node")?);
+    shim.args(&ARGS);
+
+    let direct_samples = sample(&mut direct, iterations)?;
+    let shim_samples = sample(&mut shim, iterations)?;
+
+    Ok(ShimBench {
+        iterations,
+        direct: Latency::of(&direct_samples),
+        shim: Latency::of(&shim_samples),
+    })
+}
+
+fn sample(command: &mut Command, iterations: usize) -> Fallible<Vec<Duration>> {
+    command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+    run_once(command)?;
+
+    let mut samples = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        run_once(command)?;
+        samples.push(start.elapsed());
+    }
+    Ok(samples)
+}
+
+fn run_once(command: &mut Command) -> Fallible<()> {
+    let status = command.status().with_context(|error| BenchRunError {
+        command: format!("{:?}", command),
+        error: error.to_string(),
+    })?;
+    if !status.success() {
+        throw!(BenchRunError {
+            command: format!("{:?}", command),
+            error: format!("exited with {}", status),
+        });
+    }
+    Ok(())
+}
+
+/// Returns the `p`th percentile of sorted samples, by the nearest-rank method.
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::from_secs(0);
+    }
+    let rank = (p * sorted.len() + 99) / 100;
+    sorted[rank.max(1) - 1]
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    fn millis(values: &[u64]) -> Vec<Duration> {
+        values.iter().map(|ms| Duration::from_millis(*ms)).collect()
+    }
+
+    #[test]
+    fn test_latency() {
+        let samples = millis(&[30, 10, 20, 50, 40, 60, 70, 80, 90, 100]);
+        let latency = Latency::of(&samples);
+        assert_eq!(latency.p50, Duration::from_millis(50));
+        assert_eq!(latency.p95, Duration::from_millis(100));
+
+        let baseline = Latency::of(&millis(&[20, 120]));
+        let overhead = latency.over(&baseline);
+        assert_eq!(overhead.p50, Duration::from_millis(30));
+        assert_eq!(overhead.p95, Duration::from_millis(0));
+
+        assert_eq!(Latency::of(&[]).p50, Duration::from_secs(0));
+    }
+}
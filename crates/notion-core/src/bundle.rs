@@ -0,0 +1,320 @@
+//! Provides support for exporting installed tools into a single bundle file and
+//! importing them again, for provisioning machines without network access.
+//!
+//! A bundle is a gzipped tarball with the following layout:
+//!
+//! ```text
+//! bundle.toml                     the bundle manifest
+//! versions/node/<version>/        installed Node versions
+//! versions/yarn/<version>/        installed Yarn versions
+//! cache/node/index.json           the cached public Node index (if any)
+//! cache/node/index.json.expires
+//! ```
+//!
+//! Shims for 3rd-party executables are recorded by name in the manifest and
+//! recreated on import.
+
+use std::collections::BTreeSet;
+use std::fs::{read_dir, read_to_string, rename, symlink_metadata, File};
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use semver::Version;
+use tar;
+use toml;
+
+use catalog::{node_install_lock, yarn_install_lock, Catalog};
+use distro::staging_dir;
+use fs::ensure_containing_dir_exists;
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+use path;
+use shim;
+use store;
+
+/// The name of the manifest file inside a bundle.
+const MANIFEST_FILE: &'static str = "bundle.toml";
+
+/// Thrown when a version selected for export is not installed.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} version {} is not installed", tool, version)]
+#[notion_fail(code = "NoVersionMatch")]
+pub(crate) struct NotInstalledError {
+    tool: String,
+    version: String,
+}
+
+/// Thrown when a bundle file is missing its manifest or the manifest is malformed.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} is not a valid Notion bundle: {}", file, error)]
+#[notion_fail(code = "FileSystemError")]
+pub(crate) struct InvalidBundleError {
+    file: String,
+    error: String,
+}
+
+/// The serialized form of the bundle manifest.
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    node: Vec<String>,
+    #[serde(default)]
+    yarn: Vec<String>,
+    #[serde(default)]
+    shims: Vec<String>,
+    #[serde(default)]
+    musl: Vec<String>,
+}
+
+/// The contents of a bundle.
+pub struct Contents {
+    /// The bundled Node versions.
+    pub node: Vec<Version>,
+    /// The bundled Yarn versions.
+    pub yarn: Vec<Version>,
+    /// The names of the bundled shims for 3rd-party executables.
+    pub shims: Vec<String>,
+    /// The bundled Node versions whose builds are linked against musl.
+    pub musl: Vec<Version>,
+}
+
+impl Manifest {
+    fn into_contents(self) -> Fallible<Contents> {
+        let node: Result<Vec<Version>, _> = self.node.iter().map(|v| Version::parse(v)).collect();
+        let yarn: Result<Vec<Version>, _> = self.yarn.iter().map(|v| Version::parse(v)).collect();
+        let musl: Result<Vec<Version>, _> = self.musl.iter().map(|v| Version::parse(v)).collect();
+        Ok(Contents {
+            node: node.unknown()?,
+            yarn: yarn.unknown()?,
+            shims: self.shims,
+            musl: musl.unknown()?,
+        })
+    }
+}
+
+impl Contents {
+    fn to_manifest(&self) -> Manifest {
+        Manifest {
+            node: self.node.iter().map(|v| v.to_string()).collect(),
+            yarn: self.yarn.iter().map(|v| v.to_string()).collect(),
+            shims: self.shims.clone(),
+            musl: self.musl.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+}
+
+/// Selects the versions to export: the requested versions, each of which must be
+/// installed, or every installed version if none were requested.
+fn select(
+    tool: &str,
+    installed: &BTreeSet<Version>,
+    requested: Option<Vec<Version>>,
+) -> Fallible<Vec<Version>> {
+    match requested {
+        Some(versions) => {
+            for version in &versions {
+                if !installed.contains(version) {
+                    throw!(NotInstalledError {
+                        tool: tool.to_string(),
+                        version: version.to_string(),
+                    });
+                }
+            }
+            Ok(versions)
+        }
+        None => Ok(installed.iter().cloned().collect()),
+    }
+}
+
+/// Returns the names of the installed shims for 3rd-party executables.
+pub(crate) fn installed_shims() -> Fallible<Vec<String>> {
+    let shim_dir = path::shim_dir()?;
+    let mut shims = Vec::new();
+
+    if !shim_dir.is_dir() {
+        return Ok(shims);
+    }
+
+    for entry in read_dir(shim_dir).unknown()? {
+        let entry = entry.unknown()?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let is_symlink = symlink_metadata(entry.path())
+            .unknown()?
+            .file_type()
+            .is_symlink();
+        if is_symlink && shim::is_3p_shim(&name) {
+            shims.push(name);
+        }
+    }
+
+    shims.sort();
+    Ok(shims)
+}
+
+/// Exports the selected installed versions (or all installed versions), the cached
+/// public Node index, and the installed shims into a bundle file.
+pub fn export(
+    catalog: &Catalog,
+    node: Option<Vec<Version>>,
+    yarn: Option<Vec<Version>>,
+    dest: &Path,
+) -> Fallible<Contents> {
+    let node = select("Node", &catalog.node.versions, node)?;
+    let musl = node.iter()
+        .filter(|version| catalog.node.musl.contains(version))
+        .cloned()
+        .collect();
+    let contents = Contents {
+        node,
+        yarn: select("Yarn", &catalog.yarn.versions, yarn)?,
+        shims: installed_shims()?,
+        musl,
+    };
+
+    let file = File::create(dest).unknown()?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    // Installed packages contain relative symlinks (e.g. `bin/npm`), which must be
+    // preserved rather than copied.
+    builder.follow_symlinks(false);
+
+    let manifest = toml::to_string(&contents.to_manifest()).unknown()?;
+    let mut header = tar::Header::new_gnu();
+    header.set_path(MANIFEST_FILE).unknown()?;
+    header.set_size(manifest.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append(&header, manifest.as_bytes()).unknown()?;
+
+    for version in &contents.node {
+        let version = version.to_string();
+        builder
+            .append_dir_all(
+                format!("versions/node/{}", version),
+                path::node_version_dir(&version)?,
+            )
+            .unknown()?;
+    }
+
+    for version in &contents.yarn {
+        let version = version.to_string();
+        builder
+            .append_dir_all(
+                format!("versions/yarn/{}", version),
+                path::yarn_version_dir(&version)?,
+            )
+            .unknown()?;
+    }
+
+    let index_file = path::node_index_file()?;
+    let expiry_file = path::node_index_expiry_file()?;
+    if index_file.is_file() && expiry_file.is_file() {
+        builder
+            .append_path_with_name(&index_file, "cache/node/index.json")
+            .unknown()?;
+        builder
+            .append_path_with_name(&expiry_file, "cache/node/index.json.expires")
+            .unknown()?;
+    }
+
+    builder.into_inner().unknown()?.finish().unknown()?;
+    Ok(contents)
+}
+
+/// Imports the contents of a bundle file, installing its versions into the catalog,
+/// restoring the cached public Node index, and recreating its shims.
+pub fn import(catalog: &mut Catalog, src: &Path) -> Fallible<Contents> {
+    let invalid = |error: String| InvalidBundleError {
+        file: src.to_string_lossy().to_string(),
+        error,
+    };
+
+    let staging = staging_dir()?;
+    let file = File::open(src).unknown()?;
+    tar::Archive::new(GzDecoder::new(file))
+        .unpack(staging.path())
+        .with_context(|error| invalid(error.to_string()))?;
+
+    let manifest_file = staging.path().join(MANIFEST_FILE);
+    if !manifest_file.is_file() {
+        throw!(invalid(format!("missing {}", MANIFEST_FILE)));
+    }
+    let manifest: Manifest = toml::from_str(&read_to_string(manifest_file).unknown()?)
+        .with_context(|error| invalid(error.to_string()))?;
+    let contents = manifest.into_contents()?;
+
+    {
+        // Versions are only installed while holding their install locks, and those are
+        // taken before the catalog lock, in the same order as installs take them.
+        let mut _install_locks = Vec::new();
+        for version in &contents.node {
+            _install_locks.push(node_install_lock(version)?);
+        }
+        for version in &contents.yarn {
+            _install_locks.push(yarn_install_lock(version)?);
+        }
+        let _lock = catalog.lock()?;
+
+        for version in &contents.node {
+            if !catalog.node.contains(version) {
+                let version_str = version.to_string();
+                let staged = staging.path().join("versions").join("node").join(&version_str);
+                let dir = path::node_version_dir(&version_str)?;
+                // Anything left at the version's paths is moved into a staging
+                // directory of its own and removed along with it.
+                let replaced = staging_dir()?;
+                store::install(
+                    &staged,
+                    replaced.path(),
+                    &path::node_farm_dir(&version_str)?,
+                    &dir,
+                    &path::node_manifest_file(&version_str)?,
+                )?;
+                catalog.node.versions.insert(version.clone());
+                catalog.record_installed("node", version.clone(), dir);
+                if contents.musl.contains(version) {
+                    catalog.node.musl.insert(version.clone());
+                } else {
+                    catalog.node.musl.remove(version);
+                }
+            }
+        }
+
+        for version in &contents.yarn {
+            if !catalog.yarn.contains(version) {
+                let version_str = version.to_string();
+                let staged = staging.path().join("versions").join("yarn").join(&version_str);
+                let dir = path::yarn_version_dir(&version_str)?;
+                let replaced = staging_dir()?;
+                store::install(
+                    &staged,
+                    replaced.path(),
+                    &path::yarn_farm_dir(&version_str)?,
+                    &dir,
+                    &path::yarn_manifest_file(&version_str)?,
+                )?;
+                catalog.yarn.versions.insert(version.clone());
+                catalog.record_installed("yarn", version.clone(), dir);
+            }
+        }
+
+        catalog.write()?;
+    }
+
+    let staged_cache = staging.path().join("cache").join("node");
+    if staged_cache.join("index.json").is_file() {
+        let index_file = path::node_index_file()?;
+        ensure_containing_dir_exists(&index_file)?;
+        rename(staged_cache.join("index.json"), index_file).unknown()?;
+        rename(
+            staged_cache.join("index.json.expires"),
+            path::node_index_expiry_file()?,
+        ).unknown()?;
+    }
+
+    for name in &contents.shims {
+        shim::create(name)?;
+    }
+
+    Ok(contents)
+}
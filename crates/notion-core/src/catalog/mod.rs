@@ -1,10 +1,13 @@
 //! Provides types for working with Notion's local _catalog_, the local repository
 //! of available tool versions.
 
-use std::collections::{BTreeSet, HashSet};
-use std::fs::{remove_dir_all, File};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::env;
+use std::fs::{self, remove_file, File};
 use std::io::{self, Write};
 use std::marker::PhantomData;
+use std::mem;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::string::ToString;
 use std::time::{Duration, SystemTime};
@@ -12,21 +15,38 @@ use std::time::{Duration, SystemTime};
 use lazycell::LazyCell;
 use readext::ReadExt;
 use reqwest;
-use reqwest::header::{CacheControl, CacheDirective, Expires, HttpDate};
+use reqwest::StatusCode;
+use reqwest::header::{CacheControl, CacheDirective, ETag, EntityTag, Expires, HttpDate,
+                      IfNoneMatch};
 use serde_json;
 use tempfile::NamedTempFile;
 use toml;
 
-use config::{Config, ToolConfig};
+use arch::{Arch, Libc};
+use config::{Config, LazyConfig, ToolConfig};
+use dedupe;
 use distro::node::NodeDistro;
 use distro::yarn::YarnDistro;
-use distro::{Distro, Fetched};
+use checksum::{Checksum, ChecksumMismatchError, PinnedChecksumError, UnverifiedPinError};
+use compress::{Compressed, VersionPaths};
+use distro::{url_archive_version, Distro, Fetched, Location};
 use fs::{ensure_containing_dir_exists, read_file_opt, touch};
+use http;
+use lock::FileLock;
+use lockfile::Archive;
 use notion_fail::{ExitCode, Fallible, NotionError, NotionFail, ResultExt};
-use path::{self, user_catalog_file};
+use package::PackageTool;
+use path::{self, catalog_lock_file, user_catalog_file};
+use plan::{self, Action, Plan};
+use provenance::{self, Provenance, Source};
 use semver::{Version, VersionReq};
+use shim::Target;
+use store;
 use style::progress_spinner;
+use timing::{self, Phase};
+use usage;
 use version::VersionSpec;
+use webhook::Notification;
 
 pub(crate) mod serial;
 
@@ -83,6 +103,12 @@ impl LazyCatalog {
     pub fn get_mut(&mut self) -> Fallible<&mut Catalog> {
         self.catalog.try_borrow_mut_with(|| Catalog::current())
     }
+
+    /// Returns a mutable reference to the catalog if it has been loaded, without
+    /// loading it otherwise.
+    pub fn loaded_mut(&mut self) -> Option<&mut Catalog> {
+        self.catalog.borrow_mut()
+    }
 }
 
 pub struct Collection<D: Distro> {
@@ -92,6 +118,32 @@ pub struct Collection<D: Distro> {
     // A sorted collection of the available versions in the catalog.
     pub versions: BTreeSet<Version>,
 
+    /// The URLs that versions installed from outside the usual distributor were
+    /// downloaded from, recorded for provenance.
+    pub origins: BTreeMap<Version, String>,
+
+    /// The provenance of the archive each installed build was unpacked from, by the
+    /// name it is installed under.
+    pub provenance: BTreeMap<String, Provenance>,
+
+    /// The architecture of the default version, if it is not the native architecture.
+    pub default_arch: Option<Arch>,
+
+    /// The builds for architectures other than the native architecture that are
+    /// installed alongside (or instead of) the native build of each version.
+    pub arches: BTreeMap<Version, BTreeSet<Arch>>,
+
+    /// The versions whose installed build is linked against musl rather than glibc.
+    pub musl: BTreeSet<Version>,
+
+    /// The names defined for versions with `notion alias`, which can be used wherever
+    /// a version is requested.
+    pub aliases: BTreeMap<String, Version>,
+
+    /// The versions that only the system layer provides, which are available like any
+    /// other but are never written to the user's catalog or changed by Notion.
+    pub system: BTreeSet<Version>,
+
     pub phantom: PhantomData<D>,
 }
 
@@ -102,14 +154,121 @@ pub type YarnCollection = Collection<YarnDistro>;
 pub struct Catalog {
     pub node: NodeCollection,
     pub yarn: YarnCollection,
+
+    /// The installed package tools, by package name.
+    pub packages: BTreeMap<String, PackageTool>,
+
+    /// The executables that shims created with `notion shim create --target` run, by
+    /// shim name.
+    pub targets: BTreeMap<String, Target>,
+
+    /// The tool versions installed by this process whose install hooks have not yet run.
+    installed: Vec<Installed>,
+
+    /// The installs and uninstalls by this process that the webhook has not yet been
+    /// notified of.
+    notifications: Vec<Notification>,
+}
+
+/// A tool version that has just been installed.
+#[derive(Debug, Clone)]
+pub struct Installed {
+    /// The name of the tool (`node` or `yarn`).
+    pub tool: &'static str,
+    /// The installed version.
+    pub version: Version,
+    /// The directory the version was installed into.
+    pub dir: PathBuf,
 }
 
 impl Catalog {
     /// Returns the current tool catalog.
-    fn current() -> Fallible<Catalog> {
+    pub(crate) fn current() -> Fallible<Catalog> {
+        // A dry run doesn't create the lock file; without one, no other process can be
+        // writing the catalog.
+        let lock_file = catalog_lock_file()?;
+        let _lock = if plan::is_dry_run() && !lock_file.exists() {
+            None
+        } else {
+            Some(FileLock::shared(&lock_file)?)
+        };
+        Catalog::read()
+    }
+
+    /// Reads the catalog file without acquiring the catalog lock. Callers must
+    /// already hold the lock. A dry run reads a missing catalog as empty rather
+    /// than creating it.
+    fn read() -> Fallible<Catalog> {
         let path = user_catalog_file()?;
-        let src = touch(&path)?.read_into_string().unknown()?;
-        src.parse()
+        let src = if plan::is_dry_run() {
+            read_file_opt(&path).unknown()?.unwrap_or_default()
+        } else {
+            touch(&path)?.read_into_string().unknown()?
+        };
+        let mut catalog: Catalog = src.parse()?;
+        catalog.overlay_system()?;
+        Ok(catalog)
+    }
+
+    /// Adds the versions installed in the system layer, if there is one, to those the
+    /// user installed.
+    fn overlay_system(&mut self) -> Fallible<()> {
+        let file = match path::system_catalog_file()? {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        let src = match read_file_opt(&file).with_context(SystemCatalogError::for_file(&file))? {
+            Some(src) => src,
+            None => return Ok(()),
+        };
+        let system = src.parse::<Catalog>()
+            .with_context(SystemCatalogError::for_file(&file))?;
+        self.node.overlay(system.node);
+        self.yarn.overlay(system.yarn);
+        Ok(())
+    }
+
+    /// Acquires an exclusive lock on the catalog and reloads its contents from disk,
+    /// so that changes made by other Notion processes are not clobbered.
+    pub(crate) fn lock(&mut self) -> Fallible<FileLock> {
+        let lock = FileLock::exclusive(&catalog_lock_file()?)?;
+        self.reread()?;
+        Ok(lock)
+    }
+
+    /// Reloads the catalog's contents from disk under a shared lock, to see the
+    /// versions other Notion processes have installed since it was read.
+    fn reload(&mut self) -> Fallible<()> {
+        let _lock = FileLock::shared(&catalog_lock_file()?)?;
+        self.reread()
+    }
+
+    /// Rereads the catalog file, keeping the installs this process has yet to report.
+    /// Callers must already hold the lock.
+    fn reread(&mut self) -> Fallible<()> {
+        let installed = mem::replace(&mut self.installed, Vec::new());
+        let notifications = mem::replace(&mut self.notifications, Vec::new());
+        *self = Catalog::read()?;
+        self.installed = installed;
+        self.notifications = notifications;
+        Ok(())
+    }
+
+    /// Records that a tool version has just been installed, so that its install hooks
+    /// can run once the catalog lock is released.
+    pub(crate) fn record_installed(&mut self, tool: &'static str, version: Version, dir: PathBuf) {
+        self.notifications.push(Notification::install(tool, &version, true));
+        self.installed.push(Installed { tool, version, dir });
+    }
+
+    /// Takes the tool versions installed since this was last called.
+    pub fn take_installed(&mut self) -> Vec<Installed> {
+        mem::replace(&mut self.installed, Vec::new())
+    }
+
+    /// Takes the webhook notifications recorded since this was last called.
+    pub fn take_notifications(&mut self) -> Vec<Notification> {
+        mem::replace(&mut self.notifications, Vec::new())
     }
 
     /// Returns a pretty-printed TOML representation of the contents of the catalog.
@@ -119,76 +278,676 @@ impl Catalog {
 
     /// Saves the contents of the catalog to the user's catalog file.
     pub fn save(&self) -> Fallible<()> {
+        let _lock = FileLock::exclusive(&catalog_lock_file()?)?;
+        self.write()
+    }
+
+    /// Writes the contents of the catalog to the user's catalog file without
+    /// acquiring the catalog lock. Callers must already hold the lock.
+    pub(crate) fn write(&self) -> Fallible<()> {
         let path = user_catalog_file()?;
         let mut file = File::create(&path).unknown()?;
         file.write_all(self.to_string().as_bytes()).unknown()?;
-        Ok(())
+        bump_generation()
     }
 
     /// Sets the Node version in the user toolchain to one matching the specified semantic versioning requirements.
     pub fn set_user_node(&mut self, matching: &VersionSpec, config: &Config) -> Fallible<()> {
-        let fetched = self.fetch_node(matching, config)?;
-        let version = Some(fetched.into_version());
+        let version = Some(self.fetch_node(matching, config)?.into_version());
 
-        if self.node.default != version {
+        let _lock = self.lock()?;
+        if self.node.default != version || self.node.default_arch.is_some() {
             self.node.default = version;
-            self.save()?;
+            self.node.default_arch = None;
+            self.write()?;
         }
 
         Ok(())
     }
 
+    /// Sets the Node version in the user toolchain to a build for the specified
+    /// architecture of one matching the specified semantic versioning requirements.
+    pub fn set_user_node_for_arch(
+        &mut self,
+        matching: &VersionSpec,
+        config: &Config,
+        arch: Arch,
+    ) -> Fallible<Version> {
+        let location = self.node.locate_remote(matching, config.node.as_ref())?;
+        let _install_lock = node_install_lock(&location.version)?;
+        let distro = NodeDistro::public_for(location.version, arch)?;
+        let version = self.install_node(distro)?.into_version();
+        let default_arch = if arch == Arch::native() { None } else { Some(arch) };
+
+        let _lock = self.lock()?;
+        if self.node.default.as_ref() != Some(&version) || self.node.default_arch != default_arch {
+            self.node.default = Some(version.clone());
+            self.node.default_arch = default_arch;
+            self.write()?;
+        }
+
+        Ok(version)
+    }
+
+    /// Plans setting the Node version in the user toolchain to a build for the specified
+    /// architecture, without touching the filesystem.
+    pub fn plan_set_user_node_for_arch(
+        &self,
+        matching: &VersionSpec,
+        config: &Config,
+        arch: Arch,
+    ) -> Fallible<(Version, Plan)> {
+        let location = self.node.locate_remote(matching, config.node.as_ref())?;
+        let version_str = location.version.to_string();
+        let archive = path::node_cache_dir()?.join(path::node_archive_file_for(&version_str, arch));
+        let dir = path::node_version_dir(&path::node_version_key(&version_str, arch))?;
+
+        let mut plan = Plan::new();
+        if !self.node.contains_arch(&location.version, arch) {
+            if !archive.is_file() {
+                plan.push(Action::Download {
+                    url: NodeDistro::public_url_for(&location.version, arch),
+                    file: archive.clone(),
+                });
+            }
+            plan.push(Action::Unpack { archive, dir });
+        }
+        plan.push(Action::Write(user_catalog_file()?));
+        Ok((location.version, plan))
+    }
+
     /// Fetches a Node version matching the specified semantic versioning requirements.
     pub fn fetch_node(&mut self, matching: &VersionSpec, config: &Config) -> Fallible<Fetched> {
         let distro = self.node.resolve_remote(matching, config.node.as_ref())?;
-        let fetched = distro.fetch(&self.node).unknown()?;
+        let _install_lock = node_install_lock(distro.version())?;
+        self.install_node(distro)
+    }
+
+    /// Installs a specific Node version if it is not installed yet, as shims do for
+    /// pinned versions. Only one process installs a given version at a time: others
+    /// that need it wait for that install to finish and then find it installed,
+    /// rather than downloading and unpacking it again. Installs of other versions, and
+    /// processes that only read the catalog, don't wait for it.
+    pub fn ensure_node(&mut self, version: &Version, config: &Config) -> Fallible<()> {
+        if self.node.contains(version) {
+            return Ok(());
+        }
+
+        let _install_lock = node_install_lock(version)?;
+        self.reload()?;
+        if !self.node.contains(version) {
+            let exact = VersionSpec::exact(version);
+            let distro = self.node.resolve_remote(&exact, config.node.as_ref())?;
+            self.install_node(distro)?;
+        }
+        Ok(())
+    }
+
+    /// Installs a specific Node version from the archive a project's lockfile records
+    /// for it, if it is not installed yet, with the same single-flight guarantee as
+    /// `ensure_node`. The archive is verified against its locked checksum, and so is the
+    /// archive an installed version was unpacked from, as for a pinned checksum.
+    pub fn ensure_locked_node(&mut self, version: &Version, archive: &Archive) -> Fallible<()> {
+        if !self.node.contains(version) {
+            let _install_lock = node_install_lock(version)?;
+            self.reload()?;
+            if !self.node.contains(version) {
+                let distro = NodeDistro::from_url(&archive.url, Some(&archive.checksum))?;
+                self.install_node(distro)?;
+            }
+        }
+
+        let key = path::node_version_key(&version.to_string(), Arch::native());
+        check_pinned("node", version, &archive.checksum, self.node.provenance.get(&key))
+    }
+
+    /// Installs a specific Node version if it is not installed yet, as `ensure_node`
+    /// does, for a project that pins the checksum of its archive. The archive is
+    /// verified against the pinned checksum, and so is the archive an installed version
+    /// was unpacked from.
+    pub fn ensure_pinned_node(
+        &mut self,
+        version: &Version,
+        checksum: &Checksum,
+        config: &Config,
+    ) -> Fallible<()> {
+        if !self.node.contains(version) {
+            let _install_lock = node_install_lock(version)?;
+            self.reload()?;
+            if !self.node.contains(version) {
+                let distro =
+                    self.node.resolve_pinned("node", version, checksum, config.node.as_ref())?;
+                self.install_node(distro)?;
+            }
+        }
+
+        let key = path::node_version_key(&version.to_string(), Arch::native());
+        check_pinned("node", version, checksum, self.node.provenance.get(&key))
+    }
+
+    /// Installs a Node distro, if it isn't installed yet. Callers must hold the install
+    /// lock of its version, but not the catalog lock: the download and unpacking can
+    /// take minutes, so the catalog is only locked to record the version afterwards.
+    fn install_node(&mut self, distro: NodeDistro) -> Fallible<Fetched> {
+        let arch = distro.arch();
+        let version = distro.version().clone();
+        let source = distro.source().cloned();
+        self.reload()?;
+        let fetched = match distro.fetch(&self.node).unknown() {
+            Ok(fetched) => fetched,
+            Err(error) => {
+                self.notifications.push(Notification::install("node", &version, false));
+                return Err(error);
+            }
+        };
 
         if let &Fetched::Now(ref version) = &fetched {
-            self.node.versions.insert(version.clone());
-            self.save()?;
+            let key = path::node_version_key(&version.to_string(), arch);
+            let provenance = match source {
+                Some(ref source) => match checked_provenance(source) {
+                    Ok(provenance) => Some(provenance),
+                    Err(error) => {
+                        store::remove(&path::node_version_dir(&key)?, &path::node_farm_dir(&key)?)?;
+                        remove_manifest(path::node_manifest_file(&key)?)?;
+                        self.notifications.push(Notification::install("node", version, false));
+                        return Err(error);
+                    }
+                },
+                None => None,
+            };
+
+            let _lock = self.lock()?;
+            if let Some(provenance) = provenance {
+                self.node.provenance.insert(key.clone(), provenance);
+            }
+            if arch == Arch::native() {
+                self.node.versions.insert(version.clone());
+                self.node.tag_libc(version);
+            } else {
+                self.node
+                    .arches
+                    .entry(version.clone())
+                    .or_insert_with(BTreeSet::new)
+                    .insert(arch);
+            }
+            self.write()?;
+
+            self.record_installed("node", version.clone(), path::node_version_dir(&key)?);
         }
 
         Ok(fetched)
     }
 
+    /// Sets the Node version in the user toolchain to the one in a local archive file,
+    /// without resolving it against a remote index.
+    pub fn set_user_node_from_archive(&mut self, archive: &Path) -> Fallible<Version> {
+        let distro = NodeDistro::local(archive)?;
+        let _install_lock = node_install_lock(distro.version())?;
+        let version = self.install_node(distro)?.into_version();
+
+        let _lock = self.lock()?;
+        if self.node.default.as_ref() != Some(&version) || self.node.default_arch.is_some() {
+            self.node.default = Some(version.clone());
+            self.node.default_arch = None;
+            self.write()?;
+        }
+
+        Ok(version)
+    }
+
+    /// Sets the Node version in the user toolchain to the one downloaded from an arbitrary
+    /// URL, recording the URL in the catalog. If the version is already installed, it
+    /// must have been installed from the same URL, and nothing is downloaded; a
+    /// checksum is then verified against the archive it was unpacked from instead.
+    pub fn set_user_node_from_url(&mut self, url: &str, checksum: Option<&Checksum>) -> Fallible<Version> {
+        let version = url_archive_version(url, NodeDistro::ARCHIVE_PREFIX)?;
+        let _install_lock = node_install_lock(&version)?;
+        self.reload()?;
+        if self.node.contains(&version) {
+            check_origin("node", &version, url, self.node.origins.get(&version))?;
+            if let Some(checksum) = checksum {
+                let key = path::node_version_key(&version.to_string(), Arch::native());
+                check_pinned("node", &version, checksum, self.node.provenance.get(&key))?;
+            }
+        } else {
+            self.install_node(NodeDistro::from_url(url, checksum)?)?;
+        }
+
+        let _lock = self.lock()?;
+        self.node.origins.insert(version.clone(), url.to_string());
+        self.node.default = Some(version.clone());
+        self.node.default_arch = None;
+        self.write()?;
+
+        Ok(version)
+    }
+
+    /// Plans setting the Node version in the user toolchain from an arbitrary URL,
+    /// without touching the filesystem.
+    pub fn plan_set_user_node_from_url(&self, url: &str) -> Fallible<(Version, Plan)> {
+        let version = url_archive_version(url, NodeDistro::ARCHIVE_PREFIX)?;
+        let archive = env::temp_dir().join(url.rsplit('/').next().unwrap_or(url));
+        let dir = path::node_version_dir(&version.to_string())?;
+
+        let mut plan = Plan::new();
+        if !self.node.contains(&version) {
+            plan.push(Action::Download {
+                url: url.to_string(),
+                file: archive.clone(),
+            });
+        }
+        plan.extend(self.node.plan_unpack(&version, archive, dir)?);
+        plan.push(Action::Write(user_catalog_file()?));
+        Ok((version, plan))
+    }
+
+    /// Plans setting the Node version in the user toolchain from a local archive file,
+    /// without touching the filesystem.
+    pub fn plan_set_user_node_from_archive(&self, archive: &Path) -> Fallible<(Version, Plan)> {
+        let distro = NodeDistro::local(archive)?;
+        let version = distro.version().clone();
+        let dir = path::node_version_dir(&version.to_string())?;
+        let mut plan = self.node.plan_unpack(&version, archive.to_path_buf(), dir)?;
+        if self.node.default.as_ref() != Some(&version) {
+            plan.push(Action::Write(user_catalog_file()?));
+        }
+        Ok((version, plan))
+    }
+
     /// Resolves a Node version matching the specified semantic versioning requirements.
     pub fn resolve_node(&self, matching: &VersionSpec, config: &Config) -> Fallible<Version> {
-        let distro = self.node.resolve_remote(&matching, config.node.as_ref())?;
-        Ok(distro.version().clone())
+        let location = self.node.locate_remote(&matching, config.node.as_ref())?;
+        Ok(location.version)
+    }
+
+    /// Resolves a range pinned by a project to the newest installed Node version that
+    /// satisfies it or, if none does, the newest satisfying version available to install.
+    pub fn resolve_node_pin(&self, range: &VersionReq, config: &Config) -> Fallible<Version> {
+        let installed = self.node.versions.iter().rev().find(|version| {
+            range.matches(version) && self.node.contains(version)
+        });
+        match installed {
+            Some(version) => Ok(version.clone()),
+            None => self.resolve_node(&VersionSpec::Semver(range.clone()), config),
+        }
+    }
+
+    /// Resolves the Node version to use for a version of npm, which is the newest
+    /// installed Node version bundling a matching npm or, if none is installed, the
+    /// newest one available to install. The Node versions considered can be further
+    /// restricted to a range.
+    pub fn resolve_node_for_npm(&self, npm: &VersionReq, node: Option<&VersionReq>) -> Fallible<Version> {
+        let candidates: Vec<Version> = public_node_versions()?
+            .into_iter()
+            .filter(|available| {
+                available.npm.as_ref().map_or(false, |bundled| npm.matches(bundled))
+                    && node.map_or(true, |range| range.matches(&available.version))
+            })
+            .map(|available| available.version)
+            .collect();
+
+        if let Some(installed) = candidates.iter().find(|version| self.node.contains(version)) {
+            return Ok(installed.clone());
+        }
+
+        match candidates.into_iter().next() {
+            Some(version) => Ok(version),
+            None => throw!(NoNodeForNpmError {
+                npm: npm.to_string(),
+            }),
+        }
+    }
+
+    /// Plans fetching a Node version matching the specified semantic versioning
+    /// requirements, without touching the filesystem.
+    pub fn plan_fetch_node(&self, matching: &VersionSpec, config: &Config) -> Fallible<(Version, Plan)> {
+        let location = self.node.locate_remote(matching, config.node.as_ref())?;
+        let dir = path::node_version_dir(&location.version.to_string())?;
+        let plan = self.node.plan_fetch(&location, dir)?;
+        Ok((location.version, plan))
+    }
+
+    /// Plans setting the Node version in the user toolchain, without touching the filesystem.
+    pub fn plan_set_user_node(&self, matching: &VersionSpec, config: &Config) -> Fallible<(Version, Plan)> {
+        let (version, mut plan) = self.plan_fetch_node(matching, config)?;
+        if self.node.default.as_ref() != Some(&version) {
+            plan.push(Action::Write(user_catalog_file()?));
+        }
+        Ok((version, plan))
     }
 
     /// Uninstalls a specific Node version from the local catalog.
     pub fn uninstall_node(&mut self, version: &Version) -> Fallible<()> {
-        if self.node.contains(version) {
+        let _lock = self.lock()?;
+
+        let installed =
+            self.node.versions.contains(version) || self.node.arches.contains_key(version);
+        let result = self.uninstall_node_locked(version);
+        if installed {
+            self.notifications.push(Notification::uninstall("node", version, result.is_ok()));
+        }
+        result
+    }
+
+    fn uninstall_node_locked(&mut self, version: &Version) -> Fallible<()> {
+        if self.node.system.contains(version) {
+            let dir = path::node_version_dir(&version.to_string())?;
+            throw!(SystemLayerError::new("node", version, &dir));
+        }
+
+        if self.node.versions.contains(version) {
             let home = path::node_version_dir(&version.to_string())?;
+            let paths = VersionPaths::node(&version.to_string())?;
 
-            if !home.is_dir() {
+            if !home.is_dir() && !paths.is_compressed() {
                 Err(io::Error::new(
                     io::ErrorKind::NotFound,
                     format!("{} is not a directory", home.to_string_lossy()),
                 )).unknown()?;
             }
 
-            remove_dir_all(home).unknown()?;
+            store::remove(&home, &path::node_farm_dir(&version.to_string())?)?;
+            remove_manifest(path::node_manifest_file(&version.to_string())?)?;
+            paths.forget()?;
+
+            self.node.versions.remove(version);
+            self.node.origins.remove(version);
+            self.node.provenance.remove(&version.to_string());
+            self.node.musl.remove(version);
+
+            self.write()?;
+        }
+
+        if let Some(arches) = self.node.arches.remove(version) {
+            for arch in arches {
+                let key = path::node_version_key(&version.to_string(), arch);
+                let home = path::node_version_dir(&key)?;
+                store::remove(&home, &path::node_farm_dir(&key)?)?;
+                remove_manifest(path::node_manifest_file(&key)?)?;
+                VersionPaths::node(&key)?.forget()?;
+                self.node.provenance.remove(&key);
+            }
+
+            self.write()?;
+        }
+
+        Ok(())
+    }
+
+    /// Reinstalls the build of a Node version for the specified architecture, replacing
+    /// its directory with a freshly unpacked one. The archive is taken from the cache if
+    /// it is there, and downloaded again otherwise. The catalog's record of the version,
+    /// and of the default version, is left as it is. Like an install, the version is
+    /// fetched under its install lock, and the catalog is only locked to record it.
+    pub fn repair_node(&mut self, version: &Version, arch: Arch, config: &Config) -> Fallible<()> {
+        let _install_lock = node_install_lock(version)?;
+        self.reload()?;
+
+        if !self.node.contains_arch(version, arch) {
+            let key = path::node_version_key(&version.to_string(), arch);
+            throw!(NotInstalledError::for_key("node", key));
+        }
+        if arch == Arch::native() && self.node.system.contains(version) {
+            let dir = path::node_version_dir(&version.to_string())?;
+            throw!(SystemLayerError::new("node", version, &dir));
+        }
+
+        let distro = if arch != Arch::native() {
+            NodeDistro::public_for(version.clone(), arch)?
+        } else if let Some(url) = self.node.origins.get(version) {
+            NodeDistro::from_url(url, None)?
+        } else {
+            self.node.resolve_remote(&VersionSpec::exact(version), config.node.as_ref())?
+        };
 
+        // The version is forgotten in memory only while it is fetched, so that it is
+        // unpacked again rather than found to be installed already.
+        if arch == Arch::native() {
             self.node.versions.remove(version);
+        } else if let Some(arches) = self.node.arches.get_mut(version) {
+            arches.remove(&arch);
+        }
+
+        let source = distro.source().cloned();
+        let result = distro.fetch(&self.node);
+
+        if arch == Arch::native() {
+            self.node.versions.insert(version.clone());
+        } else if let Some(arches) = self.node.arches.get_mut(version) {
+            arches.insert(arch);
+        }
+
+        result.unknown()?;
+        if let Some(source) = source {
+            let provenance = checked_provenance(&source)?;
+            let _lock = self.lock()?;
+            if self.node.contains_arch(version, arch) {
+                let key = path::node_version_key(&version.to_string(), arch);
+                self.node.provenance.insert(key, provenance);
+                self.write()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Plans reinstalling the build of a Node version for the specified architecture,
+    /// without touching the filesystem.
+    pub fn plan_repair_node(&self, version: &Version, arch: Arch) -> Fallible<Plan> {
+        if !self.node.contains_arch(version, arch) {
+            let key = path::node_version_key(&version.to_string(), arch);
+            throw!(NotInstalledError::for_key("node", key));
+        }
+
+        let version_str = version.to_string();
+        let archive = path::node_cache_dir()?.join(path::node_archive_file_for(&version_str, arch));
+        let url = match self.node.origins.get(version) {
+            Some(url) if arch == Arch::native() => url.clone(),
+            _ => NodeDistro::public_url_for(version, arch),
+        };
+        let dir = path::node_version_dir(&path::node_version_key(&version_str, arch))?;
+        Ok(plan_repair(archive, url, dir))
+    }
+
+    /// Replaces files that are identical across the installed Node versions with
+    /// hardlinks to one copy. If `apply` is false, nothing is changed and the summary
+    /// reports what deduplicating would do. The catalog is only read to list the
+    /// versions, rather than locked while they are hashed, so versions installed or
+    /// removed in the meantime are deduplicated next time or skipped.
+    pub fn dedupe_node(&mut self, apply: bool) -> Fallible<dedupe::Summary> {
+        self.reload()?;
+
+        // versions in the system layer are left as they are
+        let mut dirs = Vec::new();
+        for version in self.node.versions.difference(&self.node.system) {
+            dirs.push(path::node_version_dir(&version.to_string())?);
+        }
+        for (version, arches) in self.node.arches.iter() {
+            for arch in arches {
+                let key = path::node_version_key(&version.to_string(), *arch);
+                dirs.push(path::node_version_dir(&key)?);
+            }
+        }
+
+        dedupe::dedupe_dirs(&dirs, apply)
+    }
+
+    /// Compresses the installed versions that have gone unused for more than `days`
+    /// days, other than the default Node and Yarn versions. If `apply` is false,
+    /// nothing is changed and the versions that would be compressed are produced.
+    /// The catalog is only read to pick the candidates; each version is compressed
+    /// while holding its install lock, and versions whose lock is held by another
+    /// process (being installed, repaired, or unpacked for use) are left for next time.
+    pub fn compress_unused(&mut self, days: u64, apply: bool) -> Fallible<Vec<Compressed>> {
+        self.reload()?;
+
+        let default_node = self.node.default.as_ref().map(|version| {
+            path::node_version_key(&version.to_string(), self.node.default_arch())
+        });
+        let default_yarn = self.yarn.default.as_ref().map(Version::to_string);
+
+        // versions in the system layer are left as they are
+        let mut candidates = Vec::new();
+        for version in self.node.versions.difference(&self.node.system) {
+            candidates.push(("node", version.clone(), version.to_string()));
+        }
+        for (version, arches) in self.node.arches.iter() {
+            for arch in arches {
+                let key = path::node_version_key(&version.to_string(), *arch);
+                candidates.push(("node", version.clone(), key));
+            }
+        }
+        for version in self.yarn.versions.difference(&self.yarn.system) {
+            candidates.push(("yarn", version.clone(), version.to_string()));
+        }
+
+        let mut compressed = Vec::new();
+        for (tool, version, key) in candidates {
+            let (paths, default) = if tool == "node" {
+                (VersionPaths::node(&key)?, &default_node)
+            } else {
+                (VersionPaths::yarn(&key)?, &default_yarn)
+            };
+            if default.as_ref() == Some(&key)
+                || paths.is_compressed()
+                || !paths.dir.is_dir()
+                || !paths.unused_for(days)
+            {
+                continue;
+            }
+
+            if !apply {
+                let bytes = usage::dir_size(&paths.dir)?;
+                compressed.push(Compressed { tool, key, bytes });
+                continue;
+            }
 
-            self.save()?;
+            let lock_file = install_lock_file(tool, &version)?;
+            let _install_lock = match FileLock::try_exclusive(&lock_file)? {
+                Some(lock) => lock,
+                None => continue,
+            };
+            // Another process may have compressed or removed it in the meantime.
+            if paths.is_compressed() || !paths.dir.is_dir() {
+                continue;
+            }
+            let bytes = usage::dir_size(&paths.dir)?;
+            paths.compress()?;
+            compressed.push(Compressed { tool, key, bytes });
         }
 
+        Ok(compressed)
+    }
+
+    /// Defines an alias for a Node version, replacing any alias of the same name.
+    pub fn set_node_alias(&mut self, name: &str, version: Version) -> Fallible<()> {
+        check_alias_name(name)?;
+        let _lock = self.lock()?;
+        self.node.aliases.insert(name.to_string(), version);
+        self.write()
+    }
+
+    /// Removes an alias for a Node version, producing the version it stood for.
+    pub fn remove_node_alias(&mut self, name: &str) -> Fallible<Version> {
+        let _lock = self.lock()?;
+        let version = self.node.remove_alias("Node", name)?;
+        self.write()?;
+        Ok(version)
+    }
+
+    /// Defines an alias for a Yarn version, replacing any alias of the same name.
+    pub fn set_yarn_alias(&mut self, name: &str, version: Version) -> Fallible<()> {
+        check_alias_name(name)?;
+        let _lock = self.lock()?;
+        self.yarn.aliases.insert(name.to_string(), version);
+        self.write()
+    }
+
+    /// Removes an alias for a Yarn version, producing the version it stood for.
+    pub fn remove_yarn_alias(&mut self, name: &str) -> Fallible<Version> {
+        let _lock = self.lock()?;
+        let version = self.yarn.remove_alias("Yarn", name)?;
+        self.write()?;
+        Ok(version)
+    }
+
+    /// Records an installed package tool, replacing any previous record of the package.
+    pub fn record_package(&mut self, name: &str, tool: PackageTool) -> Fallible<()> {
+        let _lock = self.lock()?;
+        self.packages.insert(name.to_string(), tool);
+        self.write()
+    }
+
+    /// Records the executable a shim runs, replacing any previous target of the shim.
+    pub fn record_target(&mut self, shim_name: &str, target: Target) -> Fallible<()> {
+        let _lock = self.lock()?;
+        self.targets.insert(shim_name.to_string(), target);
+        self.write()
+    }
+
+    /// Forgets the executable a shim runs, if it has a target.
+    pub fn forget_target(&mut self, shim_name: &str) -> Fallible<()> {
+        let _lock = self.lock()?;
+        if self.targets.remove(shim_name).is_some() {
+            self.write()?;
+        }
         Ok(())
     }
 
+    /// Finds the installed package tool that provides an executable, producing the
+    /// package's name along with it.
+    pub fn package_for_bin(&self, bin: &str) -> Option<(&String, &PackageTool)> {
+        self.packages
+            .iter()
+            .find(|&(_, tool)| tool.bins.iter().any(|name| name == bin))
+    }
+
+    /// Unpacks the Node build of a version for an architecture if it has been
+    /// compressed, and records that it is being used.
+    pub fn use_node(&mut self, version: &Version, arch: Arch) -> Fallible<()> {
+        let key = path::node_version_key(&version.to_string(), arch);
+        let paths = VersionPaths::node(&key)?;
+        self.use_version("node", version, &key, paths)
+    }
+
+    /// Unpacks a Yarn version if it has been compressed, and records that it is
+    /// being used.
+    pub fn use_yarn(&mut self, version: &Version) -> Fallible<()> {
+        let key = version.to_string();
+        self.use_version("yarn", version, &key, VersionPaths::yarn(&key)?)
+    }
+
+    /// Unpacks a version if it has been compressed, holding its install lock, which
+    /// `compress_unused` compresses it under.
+    fn use_version(
+        &mut self,
+        tool: &'static str,
+        version: &Version,
+        key: &str,
+        paths: VersionPaths,
+    ) -> Fallible<()> {
+        if paths.is_compressed() {
+            let _install_lock = install_lock(tool, version, &install_lock_file(tool, version)?)?;
+
+            // Another process may have unpacked it while waiting for the lock.
+            if paths.is_compressed() {
+                let spinner = progress_spinner(&format!("Unpacking compressed {} v{}", tool, key));
+                paths.restore()?;
+                spinner.finish_and_clear();
+            }
+        }
+        paths.record_use()
+    }
+
     // ISSUE (#87) Abstract Catalog's activate, install and uninstall methods
     // And potentially share code between node and yarn
     /// Sets the Yarn version in the user toolchain to one matching the specified semantic versioning requirements.
     pub fn set_user_yarn(&mut self, matching: &VersionSpec, config: &Config) -> Fallible<()> {
-        let fetched = self.fetch_yarn(matching, config)?;
-        let version = Some(fetched.into_version());
+        let version = Some(self.fetch_yarn(matching, config)?.into_version());
 
+        let _lock = self.lock()?;
         if self.yarn.default != version {
             self.yarn.default = version;
-            self.save()?;
+            self.write()?;
         }
 
         Ok(())
@@ -196,44 +955,368 @@ impl Catalog {
 
     /// Fetches a Yarn version matching the specified semantic versioning requirements.
     pub fn fetch_yarn(&mut self, matching: &VersionSpec, config: &Config) -> Fallible<Fetched> {
-        let distro = self.yarn.resolve_remote(&matching, config.yarn.as_ref())?;
-        let fetched = distro.fetch(&self.yarn).unknown()?;
+        let distro = self.yarn.resolve_remote(matching, config.yarn.as_ref())?;
+        let _install_lock = yarn_install_lock(distro.version())?;
+        self.install_yarn(distro)
+    }
+
+    /// Installs a specific Yarn version if it is not installed yet, with the same
+    /// single-flight guarantee as `ensure_node`.
+    pub fn ensure_yarn(&mut self, version: &Version, config: &Config) -> Fallible<()> {
+        if self.yarn.contains(version) {
+            return Ok(());
+        }
+
+        let _install_lock = yarn_install_lock(version)?;
+        self.reload()?;
+        if !self.yarn.contains(version) {
+            let exact = VersionSpec::exact(version);
+            let distro = self.yarn.resolve_remote(&exact, config.yarn.as_ref())?;
+            self.install_yarn(distro)?;
+        }
+        Ok(())
+    }
+
+    /// Installs a specific Yarn version from the archive a project's lockfile records
+    /// for it, as `ensure_locked_node` does for Node.
+    pub fn ensure_locked_yarn(&mut self, version: &Version, archive: &Archive) -> Fallible<()> {
+        if !self.yarn.contains(version) {
+            let _install_lock = yarn_install_lock(version)?;
+            self.reload()?;
+            if !self.yarn.contains(version) {
+                let distro = YarnDistro::from_url(&archive.url, Some(&archive.checksum))?;
+                self.install_yarn(distro)?;
+            }
+        }
+
+        let key = version.to_string();
+        check_pinned("yarn", version, &archive.checksum, self.yarn.provenance.get(&key))
+    }
+
+    /// Installs a specific Yarn version for a project that pins the checksum of its
+    /// archive, as `ensure_pinned_node` does for Node.
+    pub fn ensure_pinned_yarn(
+        &mut self,
+        version: &Version,
+        checksum: &Checksum,
+        config: &Config,
+    ) -> Fallible<()> {
+        if !self.yarn.contains(version) {
+            let _install_lock = yarn_install_lock(version)?;
+            self.reload()?;
+            if !self.yarn.contains(version) {
+                let distro =
+                    self.yarn.resolve_pinned("yarn", version, checksum, config.yarn.as_ref())?;
+                self.install_yarn(distro)?;
+            }
+        }
+
+        let key = version.to_string();
+        check_pinned("yarn", version, checksum, self.yarn.provenance.get(&key))
+    }
+
+    /// Installs a Yarn distro, if it isn't installed yet, as `install_node` does for
+    /// Node.
+    fn install_yarn(&mut self, distro: YarnDistro) -> Fallible<Fetched> {
+        let version = distro.version().clone();
+        let source = distro.source().cloned();
+        self.reload()?;
+        let fetched = match distro.fetch(&self.yarn).unknown() {
+            Ok(fetched) => fetched,
+            Err(error) => {
+                self.notifications.push(Notification::install("yarn", &version, false));
+                return Err(error);
+            }
+        };
 
         if let &Fetched::Now(ref version) = &fetched {
+            let key = version.to_string();
+            let provenance = match source {
+                Some(ref source) => match checked_provenance(source) {
+                    Ok(provenance) => Some(provenance),
+                    Err(error) => {
+                        store::remove(&path::yarn_version_dir(&key)?, &path::yarn_farm_dir(&key)?)?;
+                        remove_manifest(path::yarn_manifest_file(&key)?)?;
+                        self.notifications.push(Notification::install("yarn", version, false));
+                        return Err(error);
+                    }
+                },
+                None => None,
+            };
+
+            let _lock = self.lock()?;
+            if let Some(provenance) = provenance {
+                self.yarn.provenance.insert(key.clone(), provenance);
+            }
             self.yarn.versions.insert(version.clone());
-            self.save()?;
+            self.write()?;
+
+            let dir = path::yarn_version_dir(&key)?;
+            self.record_installed("yarn", version.clone(), dir);
         }
 
         Ok(fetched)
     }
 
+    /// Sets the Yarn version in the user toolchain to the one in a local archive file,
+    /// without resolving it against a remote index.
+    pub fn set_user_yarn_from_archive(&mut self, archive: &Path) -> Fallible<Version> {
+        let distro = YarnDistro::local(archive)?;
+        let _install_lock = yarn_install_lock(distro.version())?;
+        let version = self.install_yarn(distro)?.into_version();
+
+        let _lock = self.lock()?;
+        if self.yarn.default.as_ref() != Some(&version) {
+            self.yarn.default = Some(version.clone());
+            self.write()?;
+        }
+
+        Ok(version)
+    }
+
+    /// Sets the Yarn version in the user toolchain to the one downloaded from an arbitrary
+    /// URL, recording the URL in the catalog. If the version is already installed, it
+    /// must have been installed from the same URL, as for Node.
+    pub fn set_user_yarn_from_url(&mut self, url: &str, checksum: Option<&Checksum>) -> Fallible<Version> {
+        let version = url_archive_version(url, YarnDistro::ARCHIVE_PREFIX)?;
+        let _install_lock = yarn_install_lock(&version)?;
+        self.reload()?;
+        if self.yarn.contains(&version) {
+            check_origin("yarn", &version, url, self.yarn.origins.get(&version))?;
+            if let Some(checksum) = checksum {
+                let key = version.to_string();
+                check_pinned("yarn", &version, checksum, self.yarn.provenance.get(&key))?;
+            }
+        } else {
+            self.install_yarn(YarnDistro::from_url(url, checksum)?)?;
+        }
+
+        let _lock = self.lock()?;
+        self.yarn.origins.insert(version.clone(), url.to_string());
+        self.yarn.default = Some(version.clone());
+        self.write()?;
+
+        Ok(version)
+    }
+
+    /// Plans setting the Yarn version in the user toolchain from an arbitrary URL,
+    /// without touching the filesystem.
+    pub fn plan_set_user_yarn_from_url(&self, url: &str) -> Fallible<(Version, Plan)> {
+        let version = url_archive_version(url, YarnDistro::ARCHIVE_PREFIX)?;
+        let archive = env::temp_dir().join(url.rsplit('/').next().unwrap_or(url));
+        let dir = path::yarn_version_dir(&version.to_string())?;
+
+        let mut plan = Plan::new();
+        if !self.yarn.contains(&version) {
+            plan.push(Action::Download {
+                url: url.to_string(),
+                file: archive.clone(),
+            });
+        }
+        plan.extend(self.yarn.plan_unpack(&version, archive, dir)?);
+        plan.push(Action::Write(user_catalog_file()?));
+        Ok((version, plan))
+    }
+
+    /// Plans setting the Yarn version in the user toolchain from a local archive file,
+    /// without touching the filesystem.
+    pub fn plan_set_user_yarn_from_archive(&self, archive: &Path) -> Fallible<(Version, Plan)> {
+        let distro = YarnDistro::local(archive)?;
+        let version = distro.version().clone();
+        let dir = path::yarn_version_dir(&version.to_string())?;
+        let mut plan = self.yarn.plan_unpack(&version, archive.to_path_buf(), dir)?;
+        if self.yarn.default.as_ref() != Some(&version) {
+            plan.push(Action::Write(user_catalog_file()?));
+        }
+        Ok((version, plan))
+    }
+
+    /// Resolves a range pinned by a project to the newest installed Yarn version that
+    /// satisfies it or, if none does, the newest satisfying version available to install.
+    pub fn resolve_yarn_pin(&self, range: &VersionReq, config: &Config) -> Fallible<Version> {
+        let installed = self.yarn.versions.iter().rev().find(|version| {
+            range.matches(version) && self.yarn.contains(version)
+        });
+        match installed {
+            Some(version) => Ok(version.clone()),
+            None => self.resolve_yarn(&VersionSpec::Semver(range.clone()), config),
+        }
+    }
+
     /// Resolves a Yarn version matching the specified semantic versioning requirements.
     pub fn resolve_yarn(&self, matching: &VersionSpec, config: &Config) -> Fallible<Version> {
-        let distro = self.yarn.resolve_remote(&matching, config.yarn.as_ref())?;
-        Ok(distro.version().clone())
+        let location = self.yarn.locate_remote(&matching, config.yarn.as_ref())?;
+        Ok(location.version)
+    }
+
+    /// Plans fetching a Yarn version matching the specified semantic versioning
+    /// requirements, without touching the filesystem.
+    pub fn plan_fetch_yarn(&self, matching: &VersionSpec, config: &Config) -> Fallible<(Version, Plan)> {
+        let location = self.yarn.locate_remote(matching, config.yarn.as_ref())?;
+        let dir = path::yarn_version_dir(&location.version.to_string())?;
+        let plan = self.yarn.plan_fetch(&location, dir)?;
+        Ok((location.version, plan))
+    }
+
+    /// Plans setting the Yarn version in the user toolchain, without touching the filesystem.
+    pub fn plan_set_user_yarn(&self, matching: &VersionSpec, config: &Config) -> Fallible<(Version, Plan)> {
+        let (version, mut plan) = self.plan_fetch_yarn(matching, config)?;
+        if self.yarn.default.as_ref() != Some(&version) {
+            plan.push(Action::Write(user_catalog_file()?));
+        }
+        Ok((version, plan))
     }
 
     /// Uninstalls a specific Yarn version from the local catalog.
     pub fn uninstall_yarn(&mut self, version: &Version) -> Fallible<()> {
+        let _lock = self.lock()?;
+
+        let installed = self.yarn.contains(version);
+        let result = self.uninstall_yarn_locked(version);
+        if installed {
+            self.notifications.push(Notification::uninstall("yarn", version, result.is_ok()));
+        }
+        result
+    }
+
+    fn uninstall_yarn_locked(&mut self, version: &Version) -> Fallible<()> {
+        if self.yarn.system.contains(version) {
+            let dir = path::yarn_version_dir(&version.to_string())?;
+            throw!(SystemLayerError::new("yarn", version, &dir));
+        }
+
         if self.yarn.contains(version) {
             let home = path::yarn_version_dir(&version.to_string())?;
+            let paths = VersionPaths::yarn(&version.to_string())?;
 
-            if !home.is_dir() {
+            if !home.is_dir() && !paths.is_compressed() {
                 Err(io::Error::new(
                     io::ErrorKind::NotFound,
                     format!("{} is not a directory", home.to_string_lossy()),
                 )).unknown()?;
             }
 
-            remove_dir_all(home).unknown()?;
+            store::remove(&home, &path::yarn_farm_dir(&version.to_string())?)?;
+            remove_manifest(path::yarn_manifest_file(&version.to_string())?)?;
+            paths.forget()?;
 
             self.yarn.versions.remove(version);
+            self.yarn.origins.remove(version);
+            self.yarn.provenance.remove(&version.to_string());
+
+            self.write()?;
+        }
+
+        Ok(())
+    }
+
+    /// Reinstalls a Yarn version, replacing its directory with a freshly unpacked one.
+    /// The archive is taken from the cache if it is there, and downloaded again
+    /// otherwise. The catalog's record of the version, and of the default version, is
+    /// left as it is. The catalog is only locked to record the version, as for Node.
+    pub fn repair_yarn(&mut self, version: &Version, config: &Config) -> Fallible<()> {
+        let _install_lock = yarn_install_lock(version)?;
+        self.reload()?;
 
-            self.save()?;
+        if !self.yarn.contains(version) {
+            throw!(NotInstalledError::for_key("yarn", version.to_string()));
         }
+        if self.yarn.system.contains(version) {
+            let dir = path::yarn_version_dir(&version.to_string())?;
+            throw!(SystemLayerError::new("yarn", version, &dir));
+        }
+
+        let distro = match self.yarn.origins.get(version) {
+            Some(url) => YarnDistro::from_url(url, None)?,
+            None => self.yarn.resolve_remote(&VersionSpec::exact(version), config.yarn.as_ref())?,
+        };
 
+        // The version is forgotten in memory only while it is fetched, so that it is
+        // unpacked again rather than found to be installed already.
+        self.yarn.versions.remove(version);
+        let source = distro.source().cloned();
+        let result = distro.fetch(&self.yarn);
+        self.yarn.versions.insert(version.clone());
+
+        result.unknown()?;
+        if let Some(source) = source {
+            let provenance = checked_provenance(&source)?;
+            let _lock = self.lock()?;
+            if self.yarn.contains(version) {
+                self.yarn.provenance.insert(version.to_string(), provenance);
+                self.write()?;
+            }
+        }
         Ok(())
     }
+
+    /// Plans reinstalling a Yarn version, without touching the filesystem.
+    pub fn plan_repair_yarn(&self, version: &Version) -> Fallible<Plan> {
+        if !self.yarn.contains(version) {
+            throw!(NotInstalledError::for_key("yarn", version.to_string()));
+        }
+
+        let url = match self.yarn.origins.get(version) {
+            Some(url) => url.clone(),
+            None => YarnDistro::public_url(version),
+        };
+        let dir = path::yarn_version_dir(&version.to_string())?;
+        Ok(plan_repair(YarnDistro::cache_file(version)?, url, dir))
+    }
+}
+
+/// The number of seconds to wait for another process to finish installing a version.
+/// This is much longer than the catalog lock's timeout, since it covers a download,
+/// which is made without holding the catalog lock.
+const INSTALL_LOCK_TIMEOUT_SECS: u64 = 600;
+
+/// Acquires the lock that lets only one process install a Node version at a time.
+pub(crate) fn node_install_lock(version: &Version) -> Fallible<FileLock> {
+    install_lock("node", version, &path::node_install_lock_file(&version.to_string())?)
+}
+
+/// Acquires the lock that lets only one process install a Yarn version at a time.
+pub(crate) fn yarn_install_lock(version: &Version) -> Fallible<FileLock> {
+    install_lock("yarn", version, &path::yarn_install_lock_file(&version.to_string())?)
+}
+
+/// Produces the file a tool version's install lock is taken on.
+fn install_lock_file(tool: &str, version: &Version) -> Fallible<PathBuf> {
+    if tool == "node" {
+        path::node_install_lock_file(&version.to_string())
+    } else {
+        path::yarn_install_lock_file(&version.to_string())
+    }
+}
+
+/// Acquires the lock that lets only one process install a tool version at a time,
+/// showing a spinner while waiting for another process that holds it.
+fn install_lock(tool: &str, version: &Version, file: &Path) -> Fallible<FileLock> {
+    if let Some(lock) = FileLock::try_exclusive(file)? {
+        return Ok(lock);
+    }
+
+    let spinner = progress_spinner(&format!(
+        "Waiting for another process to install {} v{}",
+        tool, version
+    ));
+    let lock = FileLock::exclusive_with_timeout(file, INSTALL_LOCK_TIMEOUT_SECS);
+    spinner.finish_and_clear();
+    lock
+}
+
+/// Plans unpacking a cached archive (downloading it first if it is not cached) over
+/// an installed version's directory.
+fn plan_repair(archive: PathBuf, url: String, dir: PathBuf) -> Plan {
+    let mut plan = Plan::new();
+    if !archive.is_file() {
+        plan.push(Action::Download {
+            url,
+            file: archive.clone(),
+        });
+    }
+    plan.push(Action::Unpack { archive, dir });
+    plan
 }
 
 /// Thrown when there is no Node version matching a requested semver specifier.
@@ -244,6 +1327,129 @@ struct NoNodeVersionFoundError {
     matching: VersionSpec,
 }
 
+/// Thrown when no Node version bundles an npm version matching a requested semver specifier.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "No Node version found bundling npm {}", npm)]
+#[notion_fail(code = "NoVersionMatch")]
+struct NoNodeForNpmError {
+    npm: String,
+}
+
+/// Thrown when the catalog of the system layer could not be read.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not read the system catalog {}
+
+Ask an administrator of this machine to check the system-wide Notion install.", file)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct SystemCatalogError {
+    file: String,
+}
+
+impl SystemCatalogError {
+    fn for_file<E>(file: &Path) -> impl FnOnce(&E) -> SystemCatalogError {
+        let file = file.to_string_lossy().to_string();
+        move |_| SystemCatalogError { file }
+    }
+}
+
+/// Thrown when a change would write to a version installed in the system layer.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} v{} is installed system-wide in {}, which Notion doesn't change
+
+Ask an administrator of this machine to change it.", tool, version, dir)]
+#[notion_fail(code = "FileSystemError")]
+pub(crate) struct SystemLayerError {
+    tool: String,
+    version: String,
+    dir: String,
+}
+
+impl SystemLayerError {
+    fn new(tool: &str, version: &Version, dir: &Path) -> Self {
+        SystemLayerError {
+            tool: tool.to_string(),
+            version: version.to_string(),
+            dir: dir.to_string_lossy().to_string(),
+        }
+    }
+}
+
+/// Thrown when a version to be repaired is not installed.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} v{} is not installed", tool, version)]
+#[notion_fail(code = "NoVersionMatch")]
+struct NotInstalledError {
+    tool: String,
+    version: String,
+}
+
+impl NotInstalledError {
+    fn for_key(tool: &str, version: String) -> Self {
+        NotInstalledError {
+            tool: tool.to_string(),
+            version,
+        }
+    }
+}
+
+/// Thrown when a version is installed from a URL while it is installed from elsewhere.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} v{} is already installed from {}
+
+Uninstall it first to install it from another URL.", tool, version, origin)]
+#[notion_fail(code = "InvalidArguments")]
+struct OtherOriginError {
+    tool: String,
+    version: String,
+    origin: String,
+}
+
+/// Thrown when a requested alias has not been defined.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "No {} alias named '{}' ({})", tool, name, defined)]
+#[notion_fail(code = "NoVersionMatch")]
+struct UnknownAliasError {
+    tool: &'static str,
+    name: String,
+    defined: String,
+}
+
+impl UnknownAliasError {
+    fn new(tool: &'static str, name: &str, aliases: &BTreeMap<String, Version>) -> Self {
+        let defined = if aliases.is_empty() {
+            "see `notion help alias` for defining aliases".to_string()
+        } else {
+            let names: Vec<&str> = aliases.keys().map(|name| name.as_str()).collect();
+            format!("defined aliases: {}", names.join(", "))
+        };
+        UnknownAliasError {
+            tool,
+            name: name.to_string(),
+            defined,
+        }
+    }
+}
+
+/// Thrown when an alias is given a name that could be mistaken for a version.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Invalid alias name '{}': aliases start with a letter and contain only \
+                  letters, digits, '-', and '_'", name)]
+#[notion_fail(code = "InvalidArguments")]
+struct InvalidAliasNameError {
+    name: String,
+}
+
+/// Checks that a name can be given to an alias, which is never the case for a name that
+/// parses as a version requirement.
+fn check_alias_name(name: &str) -> Fallible<()> {
+    match name.parse::<VersionSpec>() {
+        Ok(VersionSpec::Alias(_)) => Ok(()),
+        _ => throw!(InvalidAliasNameError {
+            name: name.to_string(),
+        }),
+    }
+}
+
 /// Thrown when there is no Yarn version matching a requested semver specifier.
 #[derive(Debug, Fail, NotionFail)]
 #[fail(display = "No Yarn version found for {}", matching)]
@@ -253,9 +1459,109 @@ struct NoYarnVersionFoundError {
 }
 
 impl<D: Distro> Collection<D> {
+    /// Replaces an alias with the exact version it stands for, leaving any other
+    /// requirement as it is.
+    fn expand_alias_of(
+        &self,
+        tool: &'static str,
+        matching: &VersionSpec,
+    ) -> Fallible<VersionSpec> {
+        match *matching {
+            VersionSpec::Alias(ref name) => match self.aliases.get(name) {
+                Some(version) => Ok(VersionSpec::exact(version)),
+                None => throw!(UnknownAliasError::new(tool, name, &self.aliases)),
+            },
+            _ => Ok(matching.clone()),
+        }
+    }
+
+    fn remove_alias(&mut self, tool: &'static str, name: &str) -> Fallible<Version> {
+        match self.aliases.remove(name) {
+            Some(version) => Ok(version),
+            None => throw!(UnknownAliasError::new(tool, name, &self.aliases)),
+        }
+    }
+
+    /// Adds the versions of the system layer's collection that this one doesn't have.
+    fn overlay(&mut self, system: Collection<D>) {
+        for version in system.versions {
+            if self.versions.insert(version.clone()) {
+                if system.musl.contains(&version) {
+                    self.musl.insert(version.clone());
+                }
+                self.system.insert(version);
+            }
+        }
+    }
+
     /// Tests whether this Collection contains the specified Tool version.
     pub fn contains(&self, version: &Version) -> bool {
-        self.versions.contains(version)
+        self.versions.contains(version) && self.matches_libc(version)
+    }
+
+    /// Tests whether the installed build of the specified version is linked against
+    /// the C library of this machine, so that a glibc build is never used on a musl
+    /// system (or vice versa).
+    fn matches_libc(&self, version: &Version) -> bool {
+        match D::libc() {
+            Some(libc) => self.musl.contains(version) == (libc == Libc::Musl),
+            None => true,
+        }
+    }
+
+    /// Records the C library that the newly installed build of a version is linked
+    /// against.
+    pub(crate) fn tag_libc(&mut self, version: &Version) {
+        if D::libc() == Some(Libc::Musl) {
+            self.musl.insert(version.clone());
+        } else {
+            self.musl.remove(version);
+        }
+    }
+
+    /// Tests whether this Collection contains a build of the specified version for the
+    /// specified architecture.
+    pub fn contains_arch(&self, version: &Version, arch: Arch) -> bool {
+        if arch == Arch::native() {
+            return self.contains(version);
+        }
+        self.arches
+            .get(version)
+            .map(|arches| arches.contains(&arch))
+            .unwrap_or(false)
+    }
+
+    /// Produces the architecture of the default version.
+    pub fn default_arch(&self) -> Arch {
+        self.default_arch.unwrap_or_else(Arch::native)
+    }
+
+    /// Plans fetching the distribution at the specified location into a directory.
+    fn plan_fetch(&self, location: &Location, dir: PathBuf) -> Fallible<Plan> {
+        let mut plan = Plan::new();
+        let archive = D::cache_file(&location.version)?;
+
+        if !self.contains(&location.version) && !archive.is_file() {
+            plan.push(Action::Download {
+                url: location.url.clone(),
+                file: archive.clone(),
+            });
+        }
+
+        plan.extend(self.plan_unpack(&location.version, archive, dir)?);
+        Ok(plan)
+    }
+
+    /// Plans unpacking the archive for the specified version into a directory.
+    fn plan_unpack(&self, version: &Version, archive: PathBuf, dir: PathBuf) -> Fallible<Plan> {
+        let mut plan = Plan::new();
+
+        if !self.contains(version) {
+            plan.push(Action::Unpack { archive, dir });
+            plan.push(Action::Write(user_catalog_file()?));
+        }
+
+        Ok(plan)
     }
 }
 
@@ -266,17 +1572,54 @@ pub trait Resolve<D: Distro> {
         matching: &VersionSpec,
         config: Option<&ToolConfig<D>>,
     ) -> Fallible<D> {
-        match config {
+        let location = self.locate_remote(matching, config)?;
+        timing::time(Phase::Download, || D::remote(location.version, &location.url))
+    }
+
+    /// Provisions a distribution of a specific version from the location it resolves
+    /// to, verifying its archive against the checksum a project pins for it.
+    fn resolve_pinned(
+        &self,
+        tool: &str,
+        version: &Version,
+        checksum: &Checksum,
+        config: Option<&ToolConfig<D>>,
+    ) -> Fallible<D> {
+        let location = self.locate_remote(&VersionSpec::exact(version), config)?;
+        D::from_url(&location.url, Some(checksum)).map_err(|error| {
+            let actual = error
+                .downcast_ref::<ChecksumMismatchError>()
+                .map(|mismatch| mismatch.actual.clone());
+            match actual {
+                Some(actual) => PinnedChecksumError::new(tool, version, checksum, &actual).into(),
+                None => error,
+            }
+        })
+    }
+
+    /// Resolves the specified semantic versioning requirements to the location of a
+    /// distribution, without provisioning it.
+    fn locate_remote(
+        &self,
+        matching: &VersionSpec,
+        config: Option<&ToolConfig<D>>,
+    ) -> Fallible<Location> {
+        let matching = self.expand_alias(matching)?;
+        timing::time(Phase::Resolution, || match config {
             Some(ToolConfig {
                 resolve: Some(ref plugin),
                 ..
-            }) => plugin.resolve(matching),
-            _ => self.resolve_public(matching),
-        }
+            }) => plugin.locate(&matching),
+            _ => self.locate_public(&matching),
+        })
     }
 
-    /// Resolves the specified semantic versioning requirements from the public distributor (e.g. `https://nodejs.org`).
-    fn resolve_public(&self, matching: &VersionSpec) -> Fallible<D>;
+    /// Replaces an alias with the exact version it stands for.
+    fn expand_alias(&self, matching: &VersionSpec) -> Fallible<VersionSpec>;
+
+    /// Resolves the specified semantic versioning requirements to the location of a
+    /// distribution on the public distributor (e.g. `https://nodejs.org`).
+    fn locate_public(&self, matching: &VersionSpec) -> Fallible<Location>;
 }
 
 /// Thrown when the public registry for Node or Yarn could not be downloaded.
@@ -296,9 +1639,13 @@ impl RegistryFetchError {
 }
 
 impl Resolve<NodeDistro> for NodeCollection {
-    fn resolve_public(&self, matching: &VersionSpec) -> Fallible<NodeDistro> {
+    fn expand_alias(&self, matching: &VersionSpec) -> Fallible<VersionSpec> {
+        self.expand_alias_of("Node", matching)
+    }
+
+    fn locate_public(&self, matching: &VersionSpec) -> Fallible<Location> {
         let version_opt = {
-            let index: Index = resolve_node_versions()?.into_index()?;
+            let index: Index = timing::time(Phase::IndexFetch, resolve_node_versions)?.into_index()?;
             let mut entries = index.entries.into_iter();
             let entry = match *matching {
                 VersionSpec::Latest => {
@@ -311,12 +1658,18 @@ impl Resolve<NodeDistro> for NodeCollection {
                     // ISSUE #34: also make sure this OS is available for this version
                     entries.find(|&(ref k, _)| matching.matches(k))
                 }
+                VersionSpec::Alias(ref name) => {
+                    throw!(UnknownAliasError::new("Node", name, &self.aliases))
+                }
             };
             entry.map(|(k, _)| k)
         };
 
         if let Some(version) = version_opt {
-            NodeDistro::public(version)
+            Ok(Location {
+                url: NodeDistro::public_url(&version),
+                version,
+            })
         } else {
             throw!(NoNodeVersionFoundError {
                 matching: matching.clone()
@@ -326,25 +1679,21 @@ impl Resolve<NodeDistro> for NodeCollection {
 }
 
 impl Resolve<YarnDistro> for YarnCollection {
+    fn expand_alias(&self, matching: &VersionSpec) -> Fallible<VersionSpec> {
+        self.expand_alias_of("Yarn", matching)
+    }
+
     /// Resolves the specified semantic versioning requirements from the public distributor.
-    fn resolve_public(&self, matching: &VersionSpec) -> Fallible<YarnDistro> {
+    fn locate_public(&self, matching: &VersionSpec) -> Fallible<Location> {
         let version = match *matching {
             VersionSpec::Latest => {
                 let mut response: reqwest::Response =
-                    reqwest::get(public_yarn_latest_version().as_str())
+                    http::get(public_yarn_latest_version().as_str())?
                         .with_context(RegistryFetchError::from_error)?;
                 response.text().unknown()?
             }
             VersionSpec::Semver(ref matching) => {
-                let spinner = progress_spinner(&format!(
-                    "Fetching public registry: {}",
-                    public_yarn_version_index()
-                ));
-                let releases: Vec<String> = reqwest::get(public_yarn_version_index().as_str())
-                    .with_context(RegistryFetchError::from_error)?
-                    .json()
-                    .unknown()?;
-                spinner.finish_and_clear();
+                let releases = timing::time(Phase::IndexFetch, fetch_yarn_releases)?;
                 let version = releases.into_iter().find(|v| {
                     let v = Version::parse(v).unwrap();
                     matching.matches(&v)
@@ -358,8 +1707,15 @@ impl Resolve<YarnDistro> for YarnCollection {
                     });
                 }
             }
+            VersionSpec::Alias(ref name) => {
+                throw!(UnknownAliasError::new("Yarn", name, &self.aliases))
+            }
         };
-        YarnDistro::public(Version::parse(&version).unknown()?)
+        let version = Version::parse(&version).unknown()?;
+        Ok(Location {
+            url: YarnDistro::public_url(&version),
+            version,
+        })
     }
 }
 
@@ -371,6 +1727,73 @@ pub struct Index {
 /// The set of available files on the public Node server for a given Node version.
 pub struct VersionData {
     pub files: HashSet<String>,
+    /// The codename of the LTS line the version belongs to, if any.
+    pub lts: Option<String>,
+    /// The version of npm bundled with the Node version, if known.
+    pub npm: Option<Version>,
+}
+
+/// A Node version available from the public Node server.
+pub struct PublicNodeVersion {
+    pub version: Version,
+    /// The codename of the LTS line the version belongs to, if any.
+    pub lts: Option<String>,
+    /// The version of npm bundled with the Node version, if known.
+    pub npm: Option<Version>,
+}
+
+/// Lists the Node versions available from the public Node server, newest first. The
+/// cached index is used until it expires.
+pub fn public_node_versions() -> Fallible<Vec<PublicNodeVersion>> {
+    let index: Index = timing::time(Phase::IndexFetch, resolve_node_versions)?.into_index()?;
+    Ok(index
+        .entries
+        .into_iter()
+        .map(|(version, data)| PublicNodeVersion {
+            version,
+            lts: data.lts,
+            npm: data.npm,
+        })
+        .collect())
+}
+
+/// Lists the Node versions in the cached public index, newest first, whether or not
+/// it has expired. Nothing is fetched, so this is empty if the index was never cached.
+pub fn cached_node_versions() -> Fallible<Vec<Version>> {
+    let cached: Option<String> = read_file_opt(&path::node_index_file()?).unknown()?;
+    let serial: serial::Index = match cached {
+        Some(string) => serde_json::de::from_str(&string).unknown()?,
+        None => return Ok(Vec::new()),
+    };
+    Ok(serial
+        .into_index()?
+        .entries
+        .into_iter()
+        .map(|(version, _)| version)
+        .collect())
+}
+
+/// Lists the Yarn versions available from the public Yarn registry, newest first.
+pub fn public_yarn_versions() -> Fallible<Vec<Version>> {
+    let mut versions = Vec::new();
+    for release in timing::time(Phase::IndexFetch, fetch_yarn_releases)? {
+        versions.push(Version::parse(&release).unknown()?);
+    }
+    Ok(versions)
+}
+
+/// Fetches the list of Yarn releases from the public Yarn registry.
+fn fetch_yarn_releases() -> Fallible<Vec<String>> {
+    let spinner = progress_spinner(&format!(
+        "Fetching public registry: {}",
+        public_yarn_version_index()
+    ));
+    let releases: Vec<String> = http::get(public_yarn_version_index().as_str())?
+        .with_context(RegistryFetchError::from_error)?
+        .json()
+        .unknown()?;
+    spinner.finish_and_clear();
+    Ok(releases)
 }
 
 impl FromStr for Catalog {
@@ -402,6 +1825,136 @@ fn read_cached_opt() -> Fallible<Option<serial::Index>> {
     Ok(None)
 }
 
+/// Reads a public index from the Node cache along with its entity tag, whether or not
+/// it has expired. A cache that can't be read is ignored, and fetched again in full.
+fn read_stale_opt() -> Fallible<Option<(serial::Index, EntityTag)>> {
+    let etag: Option<String> = read_file_opt(&path::node_index_etag_file()?).unknown()?;
+    let etag = match etag.and_then(|etag| etag.trim().parse::<EntityTag>().ok()) {
+        Some(etag) => etag,
+        None => return Ok(None),
+    };
+
+    let cached: Option<String> = read_file_opt(&path::node_index_file()?).unknown()?;
+    Ok(cached
+        .and_then(|string| serde_json::de::from_str(&string).ok())
+        .map(|index| (index, etag)))
+}
+
+/// Produces the provenance of the archive a version was just unpacked from, checked
+/// against the attestation published alongside it if attestations are verified.
+fn checked_provenance(source: &Source) -> Fallible<Provenance> {
+    let mut provenance = source.provenance()?;
+    if LazyConfig::new().get()?.verify_attestations() {
+        provenance::attest(&mut provenance)?;
+    }
+    Ok(provenance)
+}
+
+/// Checks that a version installed from a URL isn't already installed from elsewhere,
+/// since only one build of a version can be installed at a time.
+fn check_origin(tool: &str, version: &Version, url: &str, origin: Option<&String>) -> Fallible<()> {
+    match origin {
+        Some(origin) if origin == url => Ok(()),
+        origin => throw!(OtherOriginError {
+            tool: tool.to_string(),
+            version: version.to_string(),
+            origin: origin.cloned().unwrap_or_else(|| format!("the public {} index", tool)),
+        }),
+    }
+}
+
+/// Checks that an installed version was unpacked from the archive a project pins the
+/// checksum of.
+fn check_pinned(
+    tool: &str,
+    version: &Version,
+    checksum: &Checksum,
+    provenance: Option<&Provenance>,
+) -> Fallible<()> {
+    match provenance {
+        Some(provenance) if checksum.matches_digest(&provenance.sha256) => Ok(()),
+        Some(provenance) => throw!(PinnedChecksumError::new(
+            tool,
+            version,
+            checksum,
+            &format!("sha256:{}", provenance.sha256)
+        )),
+        None => throw!(UnverifiedPinError::new(tool, version)),
+    }
+}
+
+/// Removes the install manifest of an uninstalled version, if one was recorded.
+fn remove_manifest(file: PathBuf) -> Fallible<()> {
+    if file.is_file() {
+        remove_file(file).unknown()?;
+    }
+    Ok(())
+}
+
+/// Produces the catalog's generation, which is bumped every time the catalog is
+/// written, so that caches derived from the catalog can tell whether it has changed
+/// by reading a single number rather than the whole file. A catalog that has never
+/// been written is generation 0.
+pub fn generation() -> Fallible<u64> {
+    match fs::read(path::catalog_generation_file()?) {
+        Ok(bytes) => Ok(decode_generation(&bytes)),
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(error) => Err(error).unknown(),
+    }
+}
+
+/// Decodes a generation stored as 8 little-endian bytes. Anything else counts as
+/// generation 0, so that a damaged file is replaced on the next write.
+fn decode_generation(bytes: &[u8]) -> u64 {
+    if bytes.len() != 8 {
+        return 0;
+    }
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |generation, byte| (generation << 8) | *byte as u64)
+}
+
+fn encode_generation(generation: u64) -> [u8; 8] {
+    let mut bytes = [0; 8];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (generation >> (i * 8)) as u8;
+    }
+    bytes
+}
+
+/// Bumps the catalog's generation. Callers must hold the catalog lock.
+fn bump_generation() -> Fallible<()> {
+    let next = generation()?.wrapping_add(1);
+    let file = path::catalog_generation_file()?;
+    let temp = NamedTempFile::new_in(file.parent().unwrap()).unknown()?;
+    {
+        let mut temp_file: &File = temp.as_file();
+        temp_file.write_all(&encode_generation(next)).unknown()?;
+    }
+    temp.persist(file).unknown()?;
+    Ok(())
+}
+
+/// Atomically replaces a file in the Node cache. A dry run leaves the cache as it is.
+fn write_cache_file(file: &Path, contents: &str) -> Fallible<()> {
+    if plan::is_dry_run() {
+        return Ok(());
+    }
+
+    ensure_containing_dir_exists(&file)?;
+    let temp = NamedTempFile::new_in(file.parent().unwrap()).unknown()?;
+
+    // Block to borrow temp for temp_file.
+    {
+        let mut temp_file: &File = temp.as_file();
+        temp_file.write_all(contents.as_bytes()).unknown()?;
+    }
+
+    temp.persist(file).unknown()?;
+    Ok(())
+}
+
 /// Get the cache max-age of an HTTP reponse.
 fn max_age(response: &reqwest::Response) -> u32 {
     if let Some(cache_control_header) = response.headers().get::<CacheControl>() {
@@ -417,53 +1970,63 @@ fn max_age(response: &reqwest::Response) -> u32 {
 }
 
 fn resolve_node_versions() -> Result<serial::Index, NotionError> {
-    match read_cached_opt().unknown()? {
-        Some(serial) => Ok(serial),
-        None => {
-            let spinner = progress_spinner(&format!(
-                "Fetching public registry: {}",
-                public_node_version_index()
-            ));
-            let mut response: reqwest::Response = reqwest::get(
-                public_node_version_index().as_str(),
-            ).with_context(RegistryFetchError::from_error)?;
-            let response_text: String = response.text().unknown()?;
-            let cached: NamedTempFile = NamedTempFile::new().unknown()?;
-
-            // Block to borrow cached for cached_file.
-            {
-                let mut cached_file: &File = cached.as_file();
-                cached_file.write(response_text.as_bytes()).unknown()?;
-            }
-
-            let index_cache_file = path::node_index_file()?;
-            ensure_containing_dir_exists(&index_cache_file)?;
-            cached.persist(index_cache_file).unknown()?;
-
-            let expiry: NamedTempFile = NamedTempFile::new().unknown()?;
-
-            // Block to borrow expiry for expiry_file.
-            {
-                let mut expiry_file: &File = expiry.as_file();
+    if let Some(serial) = read_cached_opt().unknown()? {
+        return Ok(serial);
+    }
 
-                if let Some(expires_header) = response.headers().get::<Expires>() {
-                    write!(expiry_file, "{}", expires_header).unknown()?;
-                } else {
-                    let expiry_date =
-                        SystemTime::now() + Duration::from_secs(max_age(&response).into());
+    // If the index hasn't changed since it was cached, a conditional request lets the
+    // cached copy be reused without downloading the whole index again.
+    let stale = read_stale_opt()?;
+
+    let spinner = progress_spinner(&format!(
+        "Fetching public registry: {}",
+        public_node_version_index()
+    ));
+    let prepare = |request: &mut reqwest::RequestBuilder| {
+        if let Some((_, ref etag)) = stale {
+            request.header(IfNoneMatch::Items(vec![etag.clone()]));
+        }
+    };
+    let mut response: reqwest::Response =
+        http::get_with(public_node_version_index().as_str(), prepare)?
+            .with_context(RegistryFetchError::from_error)?;
+
+    let serial = match stale {
+        Some((cached, _)) if response.status() == StatusCode::NotModified => cached,
+        _ => {
+            let response_text: String = response.text().unknown()?;
+            let serial: serial::Index = serde_json::de::from_str(&response_text).unknown()?;
 
-                    write!(expiry_file, "{}", HttpDate::from(expiry_date)).unknown()?;
+            // Only the fields Notion uses are cached, which keeps the cached index a
+            // fraction of the size of the original.
+            write_cache_file(
+                &path::node_index_file()?,
+                &serde_json::to_string(&serial).unknown()?,
+            )?;
+
+            let etag_file = path::node_index_etag_file()?;
+            match response.headers().get::<ETag>() {
+                Some(&ETag(ref etag)) => write_cache_file(&etag_file, &etag.to_string())?,
+                // the cache is left as it is in dry-run mode, as write_cache_file does
+                None => {
+                    if !plan::is_dry_run() {
+                        let _ = remove_file(&etag_file);
+                    }
                 }
             }
 
-            let index_expiry_file = path::node_index_expiry_file()?;
-            ensure_containing_dir_exists(&index_expiry_file)?;
-            expiry.persist(index_expiry_file).unknown()?;
+            serial
+        }
+    };
 
-            let serial: serial::Index = serde_json::de::from_str(&response_text).unknown()?;
+    let expiry = if let Some(expires_header) = response.headers().get::<Expires>() {
+        expires_header.to_string()
+    } else {
+        let expiry_date = SystemTime::now() + Duration::from_secs(max_age(&response).into());
+        HttpDate::from(expiry_date).to_string()
+    };
+    write_cache_file(&path::node_index_expiry_file()?, &expiry)?;
 
-            spinner.finish_and_clear();
-            Ok(serial)
-        }
-    }
+    spinner.finish_and_clear();
+    Ok(serial)
 }
@@ -1,10 +1,17 @@
-use std::collections::{BTreeSet, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::default::Default;
 use std::iter::FromIterator;
 use std::marker::PhantomData;
+use std::path::PathBuf;
 use std::string::ToString;
 
+use arch::Arch;
+use distro::Distro;
 use notion_fail::{Fallible, ResultExt};
+use package::PackageTool;
+use path;
+use provenance;
+use shim;
 
 use semver::{SemVerError, Version};
 
@@ -14,13 +21,29 @@ pub struct Catalog {
     node: NodeCollection,
     #[serde(default)]
     yarn: YarnCollection,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    packages: BTreeMap<String, Package>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    targets: BTreeMap<String, Target>,
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename = "node")]
 pub struct NodeCollection {
     default: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default_arch: Option<String>,
     versions: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    origins: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    provenance: BTreeMap<String, Provenance>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    arches: BTreeMap<String, Vec<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    musl: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    aliases: BTreeMap<String, String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -28,13 +51,53 @@ pub struct NodeCollection {
 pub struct YarnCollection {
     default: Option<String>,
     versions: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    origins: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    provenance: BTreeMap<String, Provenance>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    aliases: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Provenance {
+    url: String,
+    fetched: u64,
+    sha256: String,
+    verification: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Package {
+    version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    requested: Option<String>,
+    node: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    npm: Option<String>,
+    bins: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Target {
+    file: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    node: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    interpreter: Vec<String>,
 }
 
 impl Default for NodeCollection {
     fn default() -> Self {
         NodeCollection {
             default: None,
+            default_arch: None,
             versions: vec![],
+            origins: BTreeMap::new(),
+            provenance: BTreeMap::new(),
+            arches: BTreeMap::new(),
+            musl: vec![],
+            aliases: BTreeMap::new(),
         }
     }
 }
@@ -44,6 +107,9 @@ impl Default for YarnCollection {
         YarnCollection {
             default: None,
             versions: vec![],
+            origins: BTreeMap::new(),
+            provenance: BTreeMap::new(),
+            aliases: BTreeMap::new(),
         }
     }
 }
@@ -53,6 +119,10 @@ impl Catalog {
         Ok(super::Catalog {
             node: self.node.into_node_collection().unknown()?,
             yarn: self.yarn.into_yarn_collection().unknown()?,
+            packages: into_packages(self.packages)?,
+            targets: into_targets(self.targets)?,
+            installed: Vec::new(),
+            notifications: Vec::new(),
         })
     }
 }
@@ -69,9 +139,26 @@ impl NodeCollection {
             .map(|s| Ok(Version::parse(&s[..])?))
             .collect();
 
+        let musl: Result<Vec<Version>, SemVerError> = self.musl
+            .into_iter()
+            .map(|s| Ok(Version::parse(&s[..])?))
+            .collect();
+
+        let default_arch = match self.default_arch {
+            Some(arch) => Some(arch.parse()?),
+            None => None,
+        };
+
         Ok(super::NodeCollection {
             default,
+            default_arch,
             versions: BTreeSet::from_iter(versions.unknown()?),
+            origins: into_origins(self.origins)?,
+            provenance: into_provenance(self.provenance)?,
+            arches: into_arches(self.arches)?,
+            musl: BTreeSet::from_iter(musl.unknown()?),
+            system: BTreeSet::new(),
+            aliases: into_aliases(self.aliases)?,
             phantom: PhantomData,
         })
     }
@@ -91,25 +178,148 @@ impl YarnCollection {
 
         Ok(super::YarnCollection {
             default,
+            default_arch: None,
             versions: BTreeSet::from_iter(versions.unknown()?),
+            origins: into_origins(self.origins)?,
+            provenance: into_provenance(self.provenance)?,
+            arches: BTreeMap::new(),
+            musl: BTreeSet::new(),
+            system: BTreeSet::new(),
+            aliases: into_aliases(self.aliases)?,
             phantom: PhantomData,
         })
     }
 }
 
+fn into_origins(origins: BTreeMap<String, String>) -> Fallible<BTreeMap<Version, String>> {
+    let mut result = BTreeMap::new();
+    for (version, url) in origins {
+        result.insert(Version::parse(&version[..]).unknown()?, url);
+    }
+    Ok(result)
+}
+
+fn into_provenance(
+    provenance: BTreeMap<String, Provenance>,
+) -> Fallible<BTreeMap<String, provenance::Provenance>> {
+    let mut result = BTreeMap::new();
+    for (key, record) in provenance {
+        result.insert(
+            key,
+            provenance::Provenance {
+                url: record.url,
+                fetched: record.fetched,
+                sha256: record.sha256,
+                verification: record.verification.parse()?,
+            },
+        );
+    }
+    Ok(result)
+}
+
+fn into_aliases(aliases: BTreeMap<String, String>) -> Fallible<BTreeMap<String, Version>> {
+    let mut result = BTreeMap::new();
+    for (name, version) in aliases {
+        result.insert(name, Version::parse(&version[..]).unknown()?);
+    }
+    Ok(result)
+}
+
+fn into_packages(packages: BTreeMap<String, Package>) -> Fallible<BTreeMap<String, PackageTool>> {
+    let mut result = BTreeMap::new();
+    for (name, package) in packages {
+        result.insert(
+            name,
+            PackageTool {
+                version: Version::parse(&package.version[..]).unknown()?,
+                requested: package.requested,
+                node: Version::parse(&package.node[..]).unknown()?,
+                npm: match package.npm {
+                    Some(npm) => Some(Version::parse(&npm[..]).unknown()?),
+                    None => None,
+                },
+                bins: package.bins,
+            },
+        );
+    }
+    Ok(result)
+}
+
+fn into_targets(targets: BTreeMap<String, Target>) -> Fallible<BTreeMap<String, shim::Target>> {
+    let mut result = BTreeMap::new();
+    for (name, target) in targets {
+        result.insert(
+            name,
+            shim::Target {
+                file: path::from_portable(PathBuf::from(target.file))?,
+                node: match target.node {
+                    Some(node) => Some(Version::parse(&node[..]).unknown()?),
+                    None => None,
+                },
+                interpreter: target.interpreter,
+            },
+        );
+    }
+    Ok(result)
+}
+
+fn into_arches(
+    arches: BTreeMap<String, Vec<String>>,
+) -> Fallible<BTreeMap<Version, BTreeSet<Arch>>> {
+    let mut result = BTreeMap::new();
+    for (version, names) in arches {
+        let mut set = BTreeSet::new();
+        for name in names {
+            set.insert(name.parse()?);
+        }
+        result.insert(Version::parse(&version[..]).unknown()?, set);
+    }
+    Ok(result)
+}
+
 impl super::Catalog {
     pub fn to_serial(&self) -> Catalog {
         Catalog {
             node: self.node.to_serial(),
             yarn: self.yarn.to_serial(),
+            packages: self.packages
+                .iter()
+                .map(|(name, tool)| (name.clone(), Package::from_tool(tool)))
+                .collect(),
+            targets: self.targets
+                .iter()
+                .map(|(name, target)| (name.clone(), Target::from_target(target)))
+                .collect(),
         }
     }
 }
+impl<D: Distro> super::Collection<D> {
+    /// Serializes a set of versions, leaving out those of the system layer.
+    fn user_versions(&self, versions: &BTreeSet<Version>) -> Vec<String> {
+        versions
+            .difference(&self.system)
+            .map(|v| v.to_string())
+            .collect()
+    }
+}
+
 impl super::NodeCollection {
     fn to_serial(&self) -> NodeCollection {
         NodeCollection {
             default: self.default.clone().map(|v| v.to_string()),
-            versions: self.versions.iter().map(|v| v.to_string()).collect(),
+            default_arch: self.default_arch.map(|arch| arch.to_string()),
+            versions: self.user_versions(&self.versions),
+            origins: self.origins
+                .iter()
+                .map(|(v, url)| (v.to_string(), url.clone()))
+                .collect(),
+            provenance: to_provenance(&self.provenance),
+            arches: self.arches
+                .iter()
+                .map(|(v, set)| (v.to_string(), set.iter().map(|a| a.to_string()).collect()))
+                .collect(),
+            musl: self.user_versions(&self.musl),
+            aliases: to_aliases(&self.aliases),
         }
     }
 }
@@ -118,11 +328,65 @@ impl super::YarnCollection {
     fn to_serial(&self) -> YarnCollection {
         YarnCollection {
             default: self.default.clone().map(|v| v.to_string()),
-            versions: self.versions.iter().map(|v| v.to_string()).collect(),
+            versions: self.user_versions(&self.versions),
+            origins: self.origins
+                .iter()
+                .map(|(v, url)| (v.to_string(), url.clone()))
+                .collect(),
+            provenance: to_provenance(&self.provenance),
+            aliases: to_aliases(&self.aliases),
+        }
+    }
+}
+
+fn to_provenance(
+    provenance: &BTreeMap<String, provenance::Provenance>,
+) -> BTreeMap<String, Provenance> {
+    provenance
+        .iter()
+        .map(|(key, record)| {
+            (
+                key.clone(),
+                Provenance {
+                    url: record.url.clone(),
+                    fetched: record.fetched,
+                    sha256: record.sha256.clone(),
+                    verification: record.verification.to_string(),
+                },
+            )
+        })
+        .collect()
+}
+
+impl Package {
+    fn from_tool(tool: &PackageTool) -> Package {
+        Package {
+            version: tool.version.to_string(),
+            requested: tool.requested.clone(),
+            node: tool.node.to_string(),
+            npm: tool.npm.as_ref().map(|v| v.to_string()),
+            bins: tool.bins.clone(),
         }
     }
 }
 
+impl Target {
+    fn from_target(target: &shim::Target) -> Target {
+        Target {
+            file: path::to_portable(&target.file).to_string_lossy().to_string(),
+            node: target.node.as_ref().map(|v| v.to_string()),
+            interpreter: target.interpreter.clone(),
+        }
+    }
+}
+
+fn to_aliases(aliases: &BTreeMap<String, Version>) -> BTreeMap<String, String> {
+    aliases
+        .iter()
+        .map(|(name, v)| (name.clone(), v.to_string()))
+        .collect()
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Index(Vec<Entry>);
 
@@ -130,6 +394,28 @@ pub struct Index(Vec<Entry>);
 pub struct Entry {
     pub version: String,
     pub files: Vec<String>,
+    #[serde(default)]
+    pub lts: Option<Lts>,
+    #[serde(default)]
+    pub npm: Option<String>,
+}
+
+/// The `lts` field of an index entry, which is `false` for releases that aren't
+/// part of an LTS line, and the line's codename for releases that are.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Lts {
+    Codename(String),
+    Flag(bool),
+}
+
+impl Lts {
+    fn into_codename(self) -> Option<String> {
+        match self {
+            Lts::Codename(codename) => Some(codename),
+            Lts::Flag(_) => None,
+        }
+    }
 }
 
 impl Index {
@@ -138,6 +424,8 @@ impl Index {
         for entry in self.0 {
             let data = super::VersionData {
                 files: HashSet::from_iter(entry.files.into_iter()),
+                lts: entry.lts.and_then(Lts::into_codename),
+                npm: entry.npm.and_then(|npm| Version::parse(&npm).ok()),
             };
             let mut version = &entry.version[..];
             version = version.trim();
@@ -0,0 +1,173 @@
+//! Provides the `Checksum` type, which represents an expected digest of a
+//! downloaded file.
+
+use std::fmt::{self, Display, Formatter};
+use std::io::Read;
+use std::str::FromStr;
+
+use semver::Version;
+use sha2::{Digest, Sha256};
+
+use notion_fail::{ExitCode, Fallible, NotionError, NotionFail, ResultExt};
+
+/// Thrown when a checksum could not be parsed.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Invalid checksum '{}' (expected sha256:<hex digest>)", checksum)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct ChecksumParseError {
+    checksum: String,
+}
+
+/// Thrown when a file does not match its expected checksum.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Checksum mismatch for {}\nexpected: {}\n  actual: {}", file, expected, actual)]
+#[notion_fail(code = "NetworkError")]
+pub(crate) struct ChecksumMismatchError {
+    file: String,
+    expected: String,
+    pub(crate) actual: String,
+}
+
+/// Thrown when the archive of a version does not match the checksum a project pins for
+/// it, which is the case even when the archive was re-published upstream.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} v{} does not match the checksum pinned for it\n\
+                  expected: {}\n  actual: {}\n\
+                  (the archive may have been re-published upstream)",
+       tool, version, expected, actual)]
+#[notion_fail(code = "NetworkError")]
+pub(crate) struct PinnedChecksumError {
+    tool: String,
+    version: String,
+    expected: String,
+    actual: String,
+}
+
+impl PinnedChecksumError {
+    pub(crate) fn new(tool: &str, version: &Version, expected: &Checksum, actual: &str) -> Self {
+        PinnedChecksumError {
+            tool: tool.to_string(),
+            version: version.to_string(),
+            expected: expected.to_string(),
+            actual: actual.to_string(),
+        }
+    }
+}
+
+/// Thrown when a version that a project pins the checksum of was installed without a
+/// record of the archive it came from, so the checksum can't be checked.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} v{} has a pinned checksum, but was installed without recording its archive\n\
+                  Run `notion repair {}@{}` to reinstall it.",
+       tool, version, tool, version)]
+#[notion_fail(code = "NetworkError")]
+pub(crate) struct UnverifiedPinError {
+    tool: String,
+    version: String,
+}
+
+impl UnverifiedPinError {
+    pub(crate) fn new(tool: &str, version: &Version) -> Self {
+        UnverifiedPinError {
+            tool: tool.to_string(),
+            version: version.to_string(),
+        }
+    }
+}
+
+/// An expected SHA-256 digest, written as `sha256:<hex digest>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checksum {
+    digest: String,
+}
+
+impl Checksum {
+    /// Verifies that the contents of a reader match this checksum. The `name`
+    /// identifies the data in error messages.
+    pub fn verify<R: Read>(&self, name: &str, reader: &mut R) -> Fallible<()> {
+        let actual = sha256_hex(reader)?;
+        if actual != self.digest {
+            throw!(ChecksumMismatchError {
+                file: name.to_string(),
+                expected: self.to_string(),
+                actual: format!("sha256:{}", actual),
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns true if a hex-encoded SHA-256 digest is the one this checksum expects.
+    pub fn matches_digest(&self, digest: &str) -> bool {
+        self.digest == digest.to_lowercase()
+    }
+}
+
+/// Computes the hex-encoded SHA-256 digest of the contents of a reader.
+pub fn sha256_hex<R: Read>(reader: &mut R) -> Fallible<String> {
+    let mut hasher = Sha256::default();
+    let mut buffer = [0u8; 8192];
+
+    loop {
+        let read = reader.read(&mut buffer).unknown()?;
+        if read == 0 {
+            break;
+        }
+        hasher.input(&buffer[..read]);
+    }
+
+    Ok(hasher
+        .result()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+impl FromStr for Checksum {
+    type Err = NotionError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let digest = if src.starts_with("sha256:") {
+            src["sha256:".len()..].to_lowercase()
+        } else {
+            String::new()
+        };
+
+        if digest.len() != 64 || !digest.chars().all(|c| c.is_digit(16)) {
+            throw!(ChecksumParseError {
+                checksum: src.to_string(),
+            });
+        }
+
+        Ok(Checksum { digest })
+    }
+}
+
+impl Display for Checksum {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "sha256:{}", self.digest)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::Checksum;
+
+    const HELLO_SHA256: &'static str =
+        "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn test_parse_checksum() {
+        let checksum: Checksum = HELLO_SHA256.parse().unwrap();
+        assert_eq!(checksum.to_string(), HELLO_SHA256);
+        assert!("md5:abcdef".parse::<Checksum>().is_err());
+        assert!("sha256:xyz".parse::<Checksum>().is_err());
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        let checksum: Checksum = HELLO_SHA256.parse().unwrap();
+        assert!(checksum.verify("hello", &mut "hello".as_bytes()).is_ok());
+        assert!(checksum.verify("goodbye", &mut "goodbye".as_bytes()).is_err());
+    }
+}
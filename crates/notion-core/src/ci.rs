@@ -0,0 +1,124 @@
+//! Provides support for setting up CI jobs with the current toolchain, for
+//! `notion ci setup`.
+
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use image::Image;
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+use path;
+
+/// Thrown when a variable GitHub Actions sets for each step is missing.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} is not set: `notion ci setup --github` must run in a GitHub Actions step",
+       variable)]
+#[notion_fail(code = "EnvironmentError")]
+pub(crate) struct GitHubEnvError {
+    variable: String,
+}
+
+/// What `notion ci setup --github` passes on to the later steps of a GitHub Actions job.
+pub struct GitHubSetup {
+    /// The directories added to the `PATH` of later steps, in order of precedence.
+    pub path: Vec<PathBuf>,
+    /// The outputs of the step, like `node-version`.
+    pub outputs: Vec<(String, String)>,
+}
+
+impl GitHubSetup {
+    /// Produces the setup for a platform image: its Node and Yarn directories, followed
+    /// by the shim directory so that package tools still run, and its tool versions.
+    pub fn for_image(image: &Image) -> Fallible<Self> {
+        let mut dirs = image.bins()?;
+        dirs.push(path::shim_dir()?);
+
+        let mut outputs = vec![("node-version".to_string(), image.node.to_string())];
+        if let Some(ref yarn) = image.yarn {
+            outputs.push(("yarn-version".to_string(), yarn.to_string()));
+        }
+
+        Ok(GitHubSetup {
+            path: dirs,
+            outputs,
+        })
+    }
+
+    /// Appends the setup to the files named by `GITHUB_PATH` and `GITHUB_OUTPUT`.
+    pub fn write(&self) -> Fallible<()> {
+        let path_file = github_file("GITHUB_PATH")?;
+        let output_file = github_file("GITHUB_OUTPUT")?;
+
+        // Each line of GITHUB_PATH is put in front of the PATH, so the directory that
+        // takes precedence comes last.
+        let path_lines: Vec<String> = self.path
+            .iter()
+            .rev()
+            .map(|dir| dir.to_string_lossy().into_owned())
+            .collect();
+        let output_lines: Vec<String> = self.outputs
+            .iter()
+            .map(|&(ref name, ref value)| format!("{}={}", name, value))
+            .collect();
+
+        append_lines(&path_file, &path_lines)?;
+        append_lines(&output_file, &output_lines)
+    }
+}
+
+/// Produces the file named by one of the variables GitHub Actions sets for each step.
+pub fn github_file(variable: &str) -> Fallible<PathBuf> {
+    match env::var_os(variable) {
+        Some(ref file) if !file.is_empty() => Ok(PathBuf::from(file)),
+        _ => throw!(GitHubEnvError {
+            variable: variable.to_string(),
+        }),
+    }
+}
+
+fn append_lines(file: &Path, lines: &[String]) -> Fallible<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file)
+        .unknown()?;
+    for line in lines {
+        writeln!(file, "{}", line).unknown()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_write() {
+        let dir = tempdir().unwrap();
+        let path_file = dir.path().join("path");
+        let output_file = dir.path().join("output");
+        fs::write(&output_file, "previous=1\n").unwrap();
+        env::set_var("GITHUB_PATH", &path_file);
+        env::set_var("GITHUB_OUTPUT", &output_file);
+
+        let setup = GitHubSetup {
+            path: vec![PathBuf::from("/node/bin"), PathBuf::from("/shims")],
+            outputs: vec![("node-version".to_string(), "18.17.1".to_string())],
+        };
+        setup.write().unwrap();
+
+        assert_eq!(fs::read_to_string(&path_file).unwrap(), "/shims\n/node/bin\n");
+        assert_eq!(
+            fs::read_to_string(&output_file).unwrap(),
+            "previous=1\nnode-version=18.17.1\n"
+        );
+
+        env::remove_var("GITHUB_OUTPUT");
+        assert!(setup.write().is_err());
+        env::remove_var("GITHUB_PATH");
+    }
+}
@@ -0,0 +1,123 @@
+//! Provides the tab completion scripts printed by `notion completions`.
+//!
+//! Commands are completed from the scripts themselves, but everything after the
+//! command is completed dynamically, by running the hidden `notion __complete`
+//! command when Tab is pressed: shim names, installed versions, and versions
+//! available to install.
+
+use std::path::Path;
+
+use shell::fish::quote;
+use shell::nu;
+
+/// Produces the fish completions for Notion, given the name and description of
+/// each command and the path of the Notion executable that completes their
+/// arguments.
+pub fn fish(notion: &Path, commands: &[(&str, &str)]) -> String {
+    let mut script = format!(
+        r#"# Completions for Notion, generated by `notion completions fish`.
+
+complete -c notion -f
+
+function __notion_complete
+    set -l words (commandline -opc)
+    set -l current (commandline -ct)
+    {notion} __complete -- $words[2..-1] "$current" 2>/dev/null
+end
+
+"#,
+        notion = quote(&notion.to_string_lossy())
+    );
+
+    for &(name, description) in commands {
+        script.push_str(&format!(
+            "complete -c notion -n __fish_use_subcommand -a {} -d {}\n",
+            quote(name),
+            quote(description)
+        ));
+    }
+
+    script.push_str(
+        "\ncomplete -c notion -n 'not __fish_use_subcommand' -a '(__notion_complete)'\n",
+    );
+    script
+}
+
+/// Produces the Nushell completions for Notion: an external completer for `notion`
+/// that hands any other command to the external completer configured before it.
+pub fn nu(notion: &Path, commands: &[(&str, &str)]) -> String {
+    let commands = commands
+        .iter()
+        .map(|&(name, description)| {
+            format!(
+                "        {{value: {}, description: {}}}\n",
+                nu::quote(name),
+                nu::quote(description)
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        r#"# Completions for Notion, generated by `notion completions nu`.
+
+let notion_completer = {{|spans|
+    let commands = [
+{commands}    ]
+    if ($spans | length) == 2 {{
+        $commands | where value starts-with ($spans | last)
+    }} else {{
+        ^{notion} __complete -- ...($spans | skip 1) | complete | get stdout | lines
+            | each {{|value| {{value: $value}}}}
+    }}
+}}
+
+let notion_previous_completer = ($env.config.completions.external.completer? | default null)
+$env.config.completions.external.enable = true
+$env.config.completions.external.completer = {{|spans|
+    if $spans.0 == "notion" {{
+        do $notion_completer $spans
+    }} else if $notion_previous_completer != null {{
+        do $notion_previous_completer $spans
+    }}
+}}
+"#,
+        notion = nu::quote(&notion.to_string_lossy()),
+        commands = commands
+    )
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{fish, nu};
+    use std::path::Path;
+
+    #[test]
+    fn test_fish() {
+        let script = fish(
+            Path::new("/home/user/.notion/notion"),
+            &[("fetch", "Fetch a tool"), ("why", "Explain a tool's version")],
+        );
+        assert!(script.contains(
+            "    '/home/user/.notion/notion' __complete -- $words[2..-1] \"$current\" 2>/dev/null"
+        ));
+        assert!(script.contains(
+            "complete -c notion -n __fish_use_subcommand -a 'fetch' -d 'Fetch a tool'\n"
+        ));
+        assert!(script.contains("-a 'why' -d 'Explain a tool\\'s version'\n"));
+        assert!(script.contains("-n 'not __fish_use_subcommand' -a '(__notion_complete)'\n"));
+    }
+
+    #[test]
+    fn test_nu() {
+        let script = nu(
+            Path::new("/home/user/.notion/notion"),
+            &[("fetch", "Fetch a tool"), ("why", "Explain \"why\"")],
+        );
+        assert!(script.contains(
+            "        ^\"/home/user/.notion/notion\" __complete -- ...($spans | skip 1)"
+        ));
+        assert!(script.contains("        {value: \"fetch\", description: \"Fetch a tool\"}\n"));
+        assert!(script.contains("{value: \"why\", description: \"Explain \\\"why\\\"\"}\n"));
+    }
+}
@@ -0,0 +1,240 @@
+//! Provides compression of installed versions that have not been used for a while.
+//!
+//! Every time a version is prepared for a shim (or for `notion env`), the time is
+//! recorded in its usage file. Compressing a version packs its directory back into an
+//! archive and removes the directory, along with any files in the store that no other
+//! version shares. The version stays in the catalog, and the next time it is used it
+//! is transparently unpacked again, at the cost of a slower first run.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use tar;
+
+use distro::staging_dir;
+use fs::ensure_containing_dir_exists;
+use notion_fail::{Fallible, ResultExt};
+use path;
+use store;
+
+/// The name of the root directory inside the archive of a compressed version.
+const ROOT_DIR: &'static str = "version";
+
+/// The name the manifest of a restored version's files is written to within the
+/// staging directory. The manifest recorded when the version was first installed is
+/// kept instead, so that changes made since then can still be detected.
+const RESTORED_MANIFEST: &'static str = "restored.sha256";
+
+/// An installed version that was (or would be) compressed.
+pub struct Compressed {
+    /// The name of the tool, e.g. `node`.
+    pub tool: &'static str,
+    /// The name the version is installed under, which includes the architecture
+    /// for Node builds other than the native one.
+    pub key: String,
+    /// The size of the version's directory before it was compressed, in bytes.
+    pub bytes: u64,
+}
+
+/// The paths involved in compressing an installed version.
+pub(crate) struct VersionPaths {
+    /// The version directory, which is normally a symlink to `farm`.
+    pub(crate) dir: PathBuf,
+    /// The version's link farm in the store.
+    pub(crate) farm: PathBuf,
+    /// The archive the version is packed into while it is compressed.
+    pub(crate) archive: PathBuf,
+    /// The file recording when the version was last used.
+    pub(crate) used: PathBuf,
+}
+
+impl VersionPaths {
+    /// Produces the paths of the Node build installed under `key`.
+    pub(crate) fn node(key: &str) -> Fallible<Self> {
+        Ok(VersionPaths {
+            dir: path::node_version_dir(key)?,
+            farm: path::node_farm_dir(key)?,
+            archive: path::node_compressed_file(key)?,
+            used: path::node_used_file(key)?,
+        })
+    }
+
+    /// Produces the paths of an installed Yarn version.
+    pub(crate) fn yarn(version: &str) -> Fallible<Self> {
+        Ok(VersionPaths {
+            dir: path::yarn_version_dir(version)?,
+            farm: path::yarn_farm_dir(version)?,
+            archive: path::yarn_compressed_file(version)?,
+            used: path::yarn_used_file(version)?,
+        })
+    }
+
+    /// Tests whether the version is currently compressed.
+    pub(crate) fn is_compressed(&self) -> bool {
+        self.archive.is_file()
+    }
+
+    /// Records that the version is being used now.
+    pub(crate) fn record_use(&self) -> Fallible<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        ensure_containing_dir_exists(&self.used)?;
+        write!(File::create(&self.used).unknown()?, "{}", now).unknown()
+    }
+
+    /// Produces the time the version was last used. Versions that have not been used
+    /// since usage started being recorded count as last used when they were installed.
+    pub(crate) fn last_used(&self) -> Option<SystemTime> {
+        let recorded = fs::read_to_string(&self.used)
+            .ok()
+            .and_then(|contents| contents.trim().parse::<u64>().ok())
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+
+        recorded.or_else(|| {
+            fs::symlink_metadata(&self.dir)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+    }
+
+    /// Tests whether the version has gone unused for longer than `days` days.
+    pub(crate) fn unused_for(&self, days: u64) -> bool {
+        let cutoff = Duration::from_secs(days * 24 * 60 * 60);
+        match self.last_used() {
+            Some(last_used) => SystemTime::now()
+                .duration_since(last_used)
+                .map(|elapsed| elapsed > cutoff)
+                .unwrap_or(false),
+            None => false,
+        }
+    }
+
+    /// Packs the version directory into its archive and removes the directory.
+    pub(crate) fn compress(&self) -> Fallible<()> {
+        // The farm is packed directly when there is one, since the version directory
+        // itself is only a symlink to it.
+        let src = if self.farm.is_dir() { &self.farm } else { &self.dir };
+
+        let staging = staging_dir()?;
+        let packed = staging.path().join("packed.tar.gz");
+        pack(src, &packed)?;
+
+        ensure_containing_dir_exists(&self.archive)?;
+        fs::rename(&packed, &self.archive).unknown()?;
+        store::remove(&self.dir, &self.farm)
+    }
+
+    /// Unpacks the version from its archive back into place and removes the archive.
+    /// An archive left behind by a version that has since been reinstalled (for
+    /// instance by `notion repair`) is simply removed.
+    pub(crate) fn restore(&self) -> Fallible<()> {
+        if !self.dir.is_dir() {
+            let staging = staging_dir()?;
+            let unpacked = unpack(&self.archive, staging.path())?;
+            store::install(
+                &unpacked,
+                staging.path(),
+                &self.farm,
+                &self.dir,
+                &staging.path().join(RESTORED_MANIFEST),
+            )?;
+        }
+        fs::remove_file(&self.archive).unknown()
+    }
+
+    /// Removes the archive and usage record of a version being uninstalled.
+    pub(crate) fn forget(&self) -> Fallible<()> {
+        for file in &[&self.archive, &self.used] {
+            if file.is_file() {
+                fs::remove_file(file).unknown()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Packs a directory into a gzipped tarball, under a single root directory.
+/// Symlinks inside the directory (e.g. `bin/npm`) are preserved rather than copied.
+fn pack(src: &Path, archive: &Path) -> Fallible<()> {
+    let file = File::create(archive).unknown()?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    builder.follow_symlinks(false);
+    builder.append_dir_all(ROOT_DIR, src).unknown()?;
+    builder.into_inner().unknown()?.finish().unknown()?;
+    Ok(())
+}
+
+/// Unpacks an archive made by `pack` into a staging directory and produces the path
+/// of the unpacked directory.
+fn unpack(archive: &Path, staging: &Path) -> Fallible<PathBuf> {
+    let file = File::open(archive).unknown()?;
+    tar::Archive::new(GzDecoder::new(file))
+        .unpack(staging)
+        .unknown()?;
+    Ok(staging.join(ROOT_DIR))
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{pack, unpack, VersionPaths};
+    use std::fs::{create_dir_all, read_to_string, File};
+    use std::io::Write;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn write_file(file: &Path, contents: &str) {
+        create_dir_all(file.parent().unwrap()).unwrap();
+        File::create(file).unwrap().write_all(contents.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_pack_unpack() {
+        let root = tempdir().expect("Could not create temporary directory");
+        let src = root.path().join("8.6.0");
+        write_file(&src.join("bin").join("node"), "#!/bin/sh");
+        write_file(&src.join("include").join("node.h"), "header");
+        let archive = root.path().join("8.6.0.tar.gz");
+        let staging = root.path().join("staging");
+
+        pack(&src, &archive).unwrap();
+        let unpacked = unpack(&archive, &staging).unwrap();
+        assert_eq!(
+            read_to_string(unpacked.join("bin").join("node")).unwrap(),
+            "#!/bin/sh"
+        );
+        assert_eq!(
+            read_to_string(unpacked.join("include").join("node.h")).unwrap(),
+            "header"
+        );
+    }
+
+    #[test]
+    fn test_unused_for() {
+        let root = tempdir().expect("Could not create temporary directory");
+        let paths = VersionPaths {
+            dir: root.path().join("versions").join("8.6.0"),
+            farm: root.path().join("farms").join("8.6.0"),
+            archive: root.path().join("compressed").join("8.6.0.tar.gz"),
+            used: root.path().join("used").join("8.6.0"),
+        };
+
+        // Neither used nor installed.
+        assert_eq!(paths.last_used(), None);
+        assert!(!paths.unused_for(0));
+
+        paths.record_use().unwrap();
+        assert!(!paths.unused_for(1));
+
+        // Last used at the start of the epoch.
+        write_file(&paths.used, "0");
+        assert!(paths.unused_for(10));
+    }
+}
@@ -1,34 +1,467 @@
 use super::super::config;
 use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::time::Duration;
 
 use distro::Distro;
 use distro::node::NodeDistro;
 use distro::yarn::YarnDistro;
+use http::Rate;
+use locale::Locale;
+use manifest::PinStyle;
 use plugin::serial::Plugin;
+use sandbox::Restriction;
+use shim::Strategy;
+use style::ProgressDisplay;
 
-use notion_fail::Fallible;
+use notion_fail::{ExitCode, Fallible, NotionFail};
 
 #[derive(Serialize, Deserialize)]
 pub struct Config {
+    pub color: Option<String>,
+    pub locale: Option<String>,
     pub node: Option<ToolConfig<NodeDistro>>,
     pub yarn: Option<ToolConfig<YarnDistro>>,
+    pub download: Option<DownloadConfig>,
     pub events: Option<EventsConfig>,
+    pub layout: Option<LayoutConfig>,
+    pub log: Option<LogConfig>,
+    pub network: Option<NetworkConfig>,
+    pub overrides: Option<OverridesConfig>,
+    pub pin: Option<PinConfig>,
+    pub progress: Option<ProgressConfig>,
+    pub provenance: Option<ProvenanceConfig>,
+    pub registries: Option<Vec<RegistryConfig>>,
+    pub retry: Option<RetryConfig>,
+    pub sandbox: Option<SandboxConfig>,
+    pub security: Option<SecurityConfig>,
+    pub shim: Option<ShimConfig>,
+    pub storage: Option<StorageConfig>,
+    pub telemetry: Option<TelemetryConfig>,
+    pub tls: Option<TlsConfig>,
+    pub trust: Option<TrustConfig>,
+    pub webhook: Option<WebhookConfig>,
+}
+
+/// The `[layout]` section, which is read by `path::layout` before the rest of the
+/// configuration.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "layout")]
+pub struct LayoutConfig {
+    #[serde(rename = "data-dir")]
+    pub data_dir: Option<PathBuf>,
+
+    #[serde(rename = "shim-dir")]
+    pub shim_dir: Option<PathBuf>,
+
+    #[serde(rename = "system-dir")]
+    pub system_dir: Option<PathBuf>,
+
+    /// The layout version: 1 for the single-directory layout, 2 for the XDG base
+    /// directories on Linux.
+    pub version: Option<u32>,
+}
+
+/// The `[download]` section, which controls how archives are downloaded.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "download")]
+pub struct DownloadConfig {
+    pub netrc: Option<bool>,
+    pub stream: Option<bool>,
+}
+
+impl DownloadConfig {
+    pub fn into_download_config(self) -> config::DownloadConfig {
+        config::DownloadConfig {
+            netrc: self.netrc.unwrap_or(true),
+            stream: self.stream.unwrap_or(true),
+        }
+    }
+}
+
+/// The `[log]` section, which configures the log sink for warnings and errors.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "log")]
+pub struct LogConfig {
+    pub sink: String,
+}
+
+impl LogConfig {
+    pub fn into_log_config(self) -> Fallible<config::LogConfig> {
+        Ok(config::LogConfig {
+            sink: self.sink.parse()?,
+        })
+    }
+}
+
+/// The `[network]` section, which controls how much of the network downloads use and
+/// how long network operations wait.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "network")]
+pub struct NetworkConfig {
+    #[serde(rename = "max-rate")]
+    pub max_rate: Option<RateValue>,
+
+    #[serde(rename = "connect-timeout")]
+    pub connect_timeout: Option<u64>,
+
+    #[serde(rename = "read-timeout")]
+    pub read_timeout: Option<u64>,
+}
+
+/// A download rate, as a number of bytes per second or a string like `"500K"`.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RateValue {
+    Bytes(u64),
+    Text(String),
+}
+
+impl NetworkConfig {
+    pub fn into_network_config(self) -> Fallible<config::NetworkConfig> {
+        Ok(config::NetworkConfig {
+            max_rate: match self.max_rate {
+                Some(RateValue::Bytes(bytes)) => Some(bytes.to_string().parse::<Rate>()?.0),
+                Some(RateValue::Text(text)) => Some(text.parse::<Rate>()?.0),
+                None => None,
+            },
+            connect_timeout: Duration::from_secs(
+                self.connect_timeout.unwrap_or(config::DEFAULT_CONNECT_TIMEOUT_SECS),
+            ),
+            read_timeout: Duration::from_secs(
+                self.read_timeout.unwrap_or(config::DEFAULT_READ_TIMEOUT_SECS),
+            ),
+        })
+    }
+}
+
+/// The `[overrides]` section, which controls the `NOTION_<TOOL>_VERSION` overrides.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "overrides")]
+pub struct OverridesConfig {
+    #[serde(rename = "auto-install")]
+    pub auto_install: Option<bool>,
+}
+
+impl OverridesConfig {
+    pub fn into_overrides_config(self) -> config::OverridesConfig {
+        config::OverridesConfig {
+            auto_install: self.auto_install.unwrap_or(true),
+        }
+    }
+}
+
+/// The `[pin]` section, which controls how versions are pinned in a project's toolchain.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "pin")]
+pub struct PinConfig {
+    pub style: Option<String>,
+}
+
+impl PinConfig {
+    pub fn into_pin_config(self) -> Fallible<config::PinConfig> {
+        Ok(config::PinConfig {
+            style: match self.style {
+                Some(style) => style.parse()?,
+                None => PinStyle::Exact,
+            },
+        })
+    }
+}
+
+/// The `[progress]` section, which controls how progress is displayed.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "progress")]
+pub struct ProgressConfig {
+    pub style: Option<String>,
+}
+
+impl ProgressConfig {
+    pub fn into_progress_config(self) -> Fallible<config::ProgressConfig> {
+        Ok(config::ProgressConfig {
+            style: match self.style {
+                Some(style) => style.parse()?,
+                None => ProgressDisplay::Bar,
+            },
+        })
+    }
+}
+
+/// The `[provenance]` section, which controls how downloaded archives are verified.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "provenance")]
+pub struct ProvenanceConfig {
+    pub attestations: Option<bool>,
+}
+
+impl ProvenanceConfig {
+    pub fn into_provenance_config(self) -> config::ProvenanceConfig {
+        config::ProvenanceConfig {
+            attestations: self.attestations.unwrap_or(false),
+        }
+    }
+}
+
+/// The `[sandbox]` section, which controls how project executables are sandboxed.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "sandbox")]
+pub struct SandboxConfig {
+    pub untrusted: Option<String>,
+    pub trusted: Option<String>,
+    pub allowed: Option<String>,
+    #[serde(rename = "keep-env")]
+    pub keep_env: Option<Vec<String>>,
+}
+
+impl SandboxConfig {
+    pub fn into_sandbox_config(self) -> Fallible<config::SandboxConfig> {
+        let restriction = |setting: Option<String>| -> Fallible<Restriction> {
+            match setting {
+                Some(setting) => setting.parse(),
+                None => Ok(Restriction::None),
+            }
+        };
+        Ok(config::SandboxConfig {
+            untrusted: restriction(self.untrusted)?,
+            trusted: restriction(self.trusted)?,
+            allowed: restriction(self.allowed)?,
+            keep_env: self.keep_env.unwrap_or_default(),
+        })
+    }
+}
+
+/// A `[[registries]]` section, which configures an npm registry for the packages of
+/// some scopes, or for every package if it has no scopes.
+#[derive(Serialize, Deserialize)]
+pub struct RegistryConfig {
+    pub url: String,
+    pub scopes: Option<Vec<String>>,
+    pub token: Option<String>,
+}
+
+impl RegistryConfig {
+    pub fn into_registry_config(self) -> config::RegistryConfig {
+        config::RegistryConfig {
+            url: self.url,
+            scopes: self.scopes.unwrap_or_default(),
+            token: self.token,
+        }
+    }
+}
+
+/// The `[security]` section, which controls how unpacked versions are trusted.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "security")]
+pub struct SecurityConfig {
+    #[serde(rename = "clear-quarantine")]
+    pub clear_quarantine: Option<bool>,
+    #[serde(rename = "verify-codesign")]
+    pub verify_codesign: Option<bool>,
+}
+
+impl SecurityConfig {
+    pub fn into_security_config(self) -> config::SecurityConfig {
+        config::SecurityConfig {
+            clear_quarantine: self.clear_quarantine.unwrap_or(true),
+            verify_codesign: self.verify_codesign.unwrap_or(false),
+        }
+    }
+}
+
+/// The `[trust]` section, which controls which projects' pins are honored.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "trust")]
+pub struct TrustConfig {
+    pub required: Option<bool>,
+    pub allow: Option<Vec<String>>,
+}
+
+impl TrustConfig {
+    pub fn into_trust_config(self) -> config::TrustConfig {
+        config::TrustConfig {
+            required: self.required.unwrap_or(true),
+            allow: self.allow.unwrap_or_default(),
+        }
+    }
+}
+
+/// The `[shim]` section, which controls how shims are made.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "shim")]
+pub struct ShimConfig {
+    pub strategy: Option<String>,
+}
+
+impl ShimConfig {
+    pub fn into_shim_config(self) -> Fallible<config::ShimConfig> {
+        Ok(config::ShimConfig {
+            strategy: match self.strategy {
+                Some(strategy) => strategy.parse()?,
+                None => Strategy::Symlink,
+            },
+        })
+    }
+}
+
+/// The `[storage]` section, which controls how installed versions are stored.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "storage")]
+pub struct StorageConfig {
+    pub dedupe: Option<bool>,
+    #[serde(rename = "compress-after")]
+    pub compress_after: Option<u64>,
+}
+
+impl StorageConfig {
+    pub fn into_storage_config(self) -> config::StorageConfig {
+        config::StorageConfig {
+            dedupe: self.dedupe.unwrap_or(false),
+            compress_after: self.compress_after,
+        }
+    }
+}
+
+/// The `[telemetry]` section, which controls what is reported to the events plugin.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "telemetry")]
+pub struct TelemetryConfig {
+    pub enabled: Option<bool>,
+    pub events: Option<Vec<String>>,
+    pub paths: Option<String>,
+}
+
+impl TelemetryConfig {
+    pub fn into_telemetry_config(self) -> Fallible<config::TelemetryConfig> {
+        Ok(config::TelemetryConfig {
+            enabled: self.enabled.unwrap_or(true),
+            events: self.events,
+            paths: match self.paths {
+                Some(paths) => paths.parse()?,
+                None => config::PathsPolicy::Keep,
+            },
+        })
+    }
+}
+
+/// The `[tls]` section, which controls how HTTPS connections are verified.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "tls")]
+pub struct TlsConfig {
+    #[serde(rename = "ca-bundle")]
+    pub ca_bundle: Option<PathBuf>,
+
+    #[serde(rename = "client-cert")]
+    pub client_cert: Option<PathBuf>,
+
+    #[serde(rename = "client-cert-password")]
+    pub client_cert_password: Option<String>,
+
+    pub insecure: Option<bool>,
+}
+
+impl TlsConfig {
+    pub fn into_tls_config(self) -> config::TlsConfig {
+        config::TlsConfig {
+            ca_bundle: self.ca_bundle,
+            client_cert: self.client_cert,
+            client_cert_password: self.client_cert_password,
+            insecure: self.insecure.unwrap_or(false),
+        }
+    }
+}
+
+/// The `[retry]` section, which controls how failed network operations are retried.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "retry")]
+pub struct RetryConfig {
+    pub attempts: Option<u32>,
+
+    #[serde(rename = "max-delay")]
+    pub max_delay: Option<u64>,
+}
+
+impl RetryConfig {
+    pub fn into_retry_config(self) -> config::RetryConfig {
+        let max_delay = self.max_delay.unwrap_or(config::DEFAULT_RETRY_MAX_DELAY_SECS);
+        config::RetryConfig {
+            attempts: self.attempts.unwrap_or(config::DEFAULT_RETRY_ATTEMPTS),
+            max_delay: Duration::from_secs(max_delay),
+        }
+    }
+}
+
+/// The `[webhook]` section, which configures notifications of installs and uninstalls.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "webhook")]
+pub struct WebhookConfig {
+    pub url: String,
+    pub timeout: Option<u64>,
+    pub retries: Option<u32>,
+}
+
+impl WebhookConfig {
+    pub fn into_webhook_config(self) -> config::WebhookConfig {
+        let timeout = self.timeout.unwrap_or(config::DEFAULT_WEBHOOK_TIMEOUT_SECS);
+        config::WebhookConfig {
+            url: self.url,
+            timeout: Duration::from_secs(timeout),
+            retries: self.retries.unwrap_or(config::DEFAULT_WEBHOOK_RETRIES),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename = "events")]
 pub struct EventsConfig {
     pub publish: Option<Plugin>,
+    pub subscribers: Option<Vec<SubscriberConfig>>,
 }
 
 impl EventsConfig {
     pub fn into_events_config(self) -> Fallible<config::EventsConfig> {
+        let mut subscribers = Vec::new();
+        for subscriber in self.subscribers.unwrap_or_default() {
+            subscribers.push(subscriber.into_subscriber()?);
+        }
+
         Ok(config::EventsConfig {
             publish: if let Some(p) = self.publish {
                 Some(p.into_publish()?)
             } else {
                 None
             },
+            subscribers,
+        })
+    }
+}
+
+/// An `[[events.subscribers]]` section, which must have exactly one of `command`,
+/// `file`, or `url`.
+#[derive(Serialize, Deserialize)]
+pub struct SubscriberConfig {
+    pub command: Option<String>,
+    pub file: Option<PathBuf>,
+    pub url: Option<String>,
+    pub events: Option<Vec<String>>,
+}
+
+/// Thrown when an events subscriber does not have exactly one delivery mode.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Each events subscriber needs exactly one of 'command', 'file', or 'url'")]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct SubscriberDeliveryError;
+
+impl SubscriberConfig {
+    pub fn into_subscriber(self) -> Fallible<config::Subscriber> {
+        let delivery = match (self.command, self.file, self.url) {
+            (Some(command), None, None) => config::Delivery::Command(command),
+            (None, Some(file), None) => config::Delivery::File(file),
+            (None, None, Some(url)) => config::Delivery::Post(url),
+            _ => throw!(SubscriberDeliveryError),
+        };
+        Ok(config::Subscriber {
+            delivery,
+            events: self.events,
         })
     }
 }
@@ -58,11 +491,72 @@ impl Config {
             } else {
                 None
             },
+            download: self.download.map(DownloadConfig::into_download_config),
             events: if let Some(e) = self.events {
                 Some(e.into_events_config()?)
             } else {
                 None
             },
+            color: match self.color {
+                Some(color) => Some(color.parse()?),
+                None => None,
+            },
+            locale: match self.locale {
+                Some(value) => match Locale::parse(&value) {
+                    Some(locale) => Some(locale),
+                    None => throw!(config::LocaleError { value }),
+                },
+                None => None,
+            },
+            log: if let Some(l) = self.log {
+                Some(l.into_log_config()?)
+            } else {
+                None
+            },
+            network: if let Some(n) = self.network {
+                Some(n.into_network_config()?)
+            } else {
+                None
+            },
+            overrides: self.overrides.map(OverridesConfig::into_overrides_config),
+            pin: if let Some(p) = self.pin {
+                Some(p.into_pin_config()?)
+            } else {
+                None
+            },
+            progress: if let Some(p) = self.progress {
+                Some(p.into_progress_config()?)
+            } else {
+                None
+            },
+            provenance: self.provenance.map(ProvenanceConfig::into_provenance_config),
+            registries: self.registries.map(|registries| {
+                registries
+                    .into_iter()
+                    .map(RegistryConfig::into_registry_config)
+                    .collect()
+            }),
+            retry: self.retry.map(RetryConfig::into_retry_config),
+            sandbox: if let Some(s) = self.sandbox {
+                Some(s.into_sandbox_config()?)
+            } else {
+                None
+            },
+            security: self.security.map(SecurityConfig::into_security_config),
+            shim: if let Some(s) = self.shim {
+                Some(s.into_shim_config()?)
+            } else {
+                None
+            },
+            storage: self.storage.map(StorageConfig::into_storage_config),
+            telemetry: if let Some(t) = self.telemetry {
+                Some(t.into_telemetry_config()?)
+            } else {
+                None
+            },
+            tls: self.tls.map(TlsConfig::into_tls_config),
+            trust: self.trust.map(TrustConfig::into_trust_config),
+            webhook: self.webhook.map(WebhookConfig::into_webhook_config),
         })
     }
 }
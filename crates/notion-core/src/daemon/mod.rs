@@ -0,0 +1,626 @@
+//! Provides `notion daemon`, which answers queries from editors and language servers
+//! over JSON-RPC 2.0, so that they don't start a process for every query.
+//!
+//! The daemon listens on a Unix socket or, on Windows, a named pipe. Each request is a
+//! JSON-RPC request object on a line of its own, and each response is written on a line
+//! of its own. The methods are:
+//!
+//! | method    | params               | result                                     |
+//! |-----------|----------------------|--------------------------------------------|
+//! | `resolve` | `{"dir"}`            | `{"node": {"version", "source"}, "yarn"}`  |
+//! | `list`    | `{"tool", "remote"}` | versions, oldest first                     |
+//! | `which`   | `{"dir", "tool"}`    | `{"path", "bypassed"}`                     |
+//!
+//! where `resolve` produces `null` if no Node version is in effect, `yarn` is `null`
+//! if no Yarn version is, and `tool` is `node` or `yarn` for `list` (`remote` is
+//! optional) and any executable for `which`. A
+//! Notion error is a JSON-RPC error with the code -32000 and the exit code it would
+//! exit `notion` with in its data, as `{"exit_code", "name"}`.
+//!
+//! The catalog stays loaded between requests until it changes, and the public indexes
+//! `list` reads with `"remote": true` are kept for five minutes. The answers to
+//! `resolve` and `which` are kept too, each until one of the files it was resolved from
+//! changes (see `watch`), so they stay consistent with what other `notion` processes
+//! write. As with `notion status`, the pins of a project that hasn't been trusted are
+//! ignored rather than asked about.
+
+mod watch;
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use semver::Version;
+use serde_json::{self, Value};
+
+use catalog::{self, public_node_versions, public_yarn_versions, LazyCatalog};
+use notion_fail::{ExitCode, Fallible, NotionError, NotionFail, ResultExt};
+use prompt;
+use resolve;
+use session::Session;
+
+use self::watch::Watch;
+
+/// How long the public indexes are kept in memory, in seconds.
+const INDEX_TTL_SECS: u64 = 5 * 60;
+
+/// How many answers are kept before they are all dropped.
+const MAX_ANSWERS: usize = 256;
+
+/// The JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const NOTION_ERROR: i64 = -32000;
+
+/// Thrown when another daemon is already listening on the socket.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "A Notion daemon is already listening on {}", socket)]
+#[notion_fail(code = "FileSystemError")]
+pub(crate) struct DaemonRunningError {
+    socket: String,
+}
+
+/// Thrown when the daemon can't listen on its socket.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not listen on {}: {}", socket, error)]
+#[notion_fail(code = "FileSystemError")]
+pub(crate) struct DaemonListenError {
+    socket: String,
+    error: String,
+}
+
+impl DaemonListenError {
+    fn for_socket<E: ToString>(socket: &Path) -> impl FnOnce(&E) -> DaemonListenError {
+        let socket = socket.display().to_string();
+        move |error| DaemonListenError {
+            socket: socket,
+            error: error.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<ErrorData>,
+}
+
+#[derive(Serialize)]
+struct ErrorData {
+    exit_code: i32,
+    name: &'static str,
+}
+
+impl RpcError {
+    fn new(code: i64, message: String) -> Self {
+        RpcError {
+            code,
+            message,
+            data: None,
+        }
+    }
+
+    fn from_notion(error: &NotionError) -> Self {
+        let exit_code = error.exit_code();
+        RpcError {
+            code: NOTION_ERROR,
+            message: error.to_string(),
+            data: Some(ErrorData {
+                exit_code: exit_code.code(),
+                name: exit_code.name(),
+            }),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ResolveParams {
+    dir: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct ListParams {
+    tool: String,
+    #[serde(default)]
+    remote: bool,
+}
+
+#[derive(Deserialize)]
+struct WhichParams {
+    dir: PathBuf,
+    tool: String,
+}
+
+#[derive(Serialize)]
+struct Resolved {
+    version: String,
+    source: String,
+}
+
+#[derive(Serialize)]
+struct Platform {
+    node: Resolved,
+    yarn: Option<Resolved>,
+}
+
+#[derive(Serialize)]
+struct Which {
+    path: Option<String>,
+    bypassed: bool,
+}
+
+/// The queries whose answers are kept until the files they depend on change.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Query {
+    Resolve(PathBuf),
+    Which(PathBuf, String),
+}
+
+/// The state the daemon keeps between requests.
+pub struct Daemon {
+    /// The catalog, and the generation it was loaded at.
+    catalog: Option<(u64, LazyCatalog)>,
+    node_index: Option<(Instant, Vec<Version>)>,
+    yarn_index: Option<(Instant, Vec<Version>)>,
+    answers: HashMap<Query, (Watch, Value)>,
+}
+
+impl Daemon {
+    pub fn new() -> Self {
+        Daemon {
+            catalog: None,
+            node_index: None,
+            yarn_index: None,
+            answers: HashMap::new(),
+        }
+    }
+
+    /// Answers a request, producing the response, or `None` if the request is a
+    /// notification, which has no response.
+    pub fn handle(&mut self, message: &str) -> Option<String> {
+        let request: Request = match serde_json::from_str(message) {
+            Ok(request) => request,
+            Err(error) => {
+                let code = if serde_json::from_str::<Value>(message).is_ok() {
+                    INVALID_REQUEST
+                } else {
+                    PARSE_ERROR
+                };
+                let error = RpcError::new(code, error.to_string());
+                return Some(respond(Value::Null, Err(error)));
+            }
+        };
+
+        let result = self.call(&request.method, request.params);
+        request.id.map(|id| respond(id, result))
+    }
+
+    fn call(&mut self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "resolve" => {
+                let params: ResolveParams = parse_params(params)?;
+                let query = Query::Resolve(params.dir);
+                self.answer(query).map_err(|error| RpcError::from_notion(&error))
+            }
+            "list" => {
+                let params: ListParams = parse_params(params)?;
+                let versions = match (&params.tool[..], params.remote) {
+                    ("node", false) | ("yarn", false) => self.installed(&params.tool),
+                    ("node", true) => self.remote_node(),
+                    ("yarn", true) => self.remote_yarn(),
+                    (tool, _) => {
+                        return Err(RpcError::new(
+                            INVALID_PARAMS,
+                            format!("Unknown tool '{}': expected node or yarn", tool),
+                        ))
+                    }
+                };
+                versions
+                    .map(|versions| {
+                        to_value(versions.iter().map(Version::to_string).collect::<Vec<_>>())
+                    })
+                    .map_err(|error| RpcError::from_notion(&error))
+            }
+            "which" => {
+                let params: WhichParams = parse_params(params)?;
+                let query = Query::Which(params.dir, params.tool);
+                self.answer(query).map_err(|error| RpcError::from_notion(&error))
+            }
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method '{}'", method),
+            )),
+        }
+    }
+
+    /// Answers a query from memory if none of the files it was answered from have
+    /// changed since, or else resolves it again. The watch starts before resolving, so
+    /// that a file changed while resolving invalidates the answer.
+    fn answer(&mut self, query: Query) -> Fallible<Value> {
+        if let Some(&(ref watch, ref value)) = self.answers.get(&query) {
+            if watch.is_fresh() {
+                return Ok(value.clone());
+            }
+        }
+
+        let (watch, value) = match query {
+            Query::Resolve(ref dir) => {
+                let watch = Watch::of(dir)?;
+                (watch, self.resolve(dir)?)
+            }
+            Query::Which(ref dir, ref tool) => {
+                let watch = Watch::of(dir)?;
+                (watch, self.which(dir, tool)?)
+            }
+        };
+        if self.answers.len() >= MAX_ANSWERS {
+            self.answers.clear();
+        }
+        self.answers.insert(query, (watch, value.clone()));
+        Ok(value)
+    }
+
+    /// Runs a query in a session for a directory, with the catalog kept from earlier
+    /// queries unless it has changed since.
+    fn with_session<T, F>(&mut self, dir: &Path, query: F) -> Fallible<T>
+    where
+        F: FnOnce(&mut Session) -> Fallible<T>,
+    {
+        let generation = catalog::generation()?;
+        let catalog = match self.catalog.take() {
+            Some((loaded, catalog)) if loaded == generation => catalog,
+            _ => LazyCatalog::new(),
+        };
+        let mut session = Session::for_dir_with_catalog(dir, catalog)?;
+        session.trust_without_asking()?;
+        let result = query(&mut session);
+        self.catalog = Some((generation, session.into_catalog()));
+        result
+    }
+
+    fn resolve(&mut self, dir: &Path) -> Fallible<Value> {
+        let selections = self.with_session(dir, prompt::selections)?;
+        let mut node = None;
+        let mut yarn = None;
+        for selection in selections {
+            let resolved = Resolved {
+                version: selection.version.to_string(),
+                source: selection.source.to_string(),
+            };
+            match selection.tool {
+                "node" => node = Some(resolved),
+                "yarn" => yarn = Some(resolved),
+                _ => {}
+            }
+        }
+        Ok(to_value(node.map(|node| Platform { node, yarn })))
+    }
+
+    fn which(&mut self, dir: &Path, tool: &str) -> Fallible<Value> {
+        let target = self.with_session(dir, |session| resolve::which(session, tool))?;
+        Ok(to_value(Which {
+            path: target.path.map(|path| path.display().to_string()),
+            bypassed: target.bypassed,
+        }))
+    }
+
+    fn installed(&mut self, tool: &str) -> Fallible<Vec<Version>> {
+        // the installed versions are the same in every directory
+        let dir = PathBuf::from("/");
+        self.with_session(&dir, |session| {
+            let catalog = session.catalog()?;
+            Ok(match tool {
+                "node" => catalog.node.versions.iter().cloned().collect(),
+                _ => catalog.yarn.versions.iter().cloned().collect(),
+            })
+        })
+    }
+
+    fn remote_node(&mut self) -> Fallible<Vec<Version>> {
+        warm(&mut self.node_index, || {
+            Ok(public_node_versions()?
+                .into_iter()
+                .map(|available| available.version)
+                .collect())
+        })
+    }
+
+    fn remote_yarn(&mut self) -> Fallible<Vec<Version>> {
+        warm(&mut self.yarn_index, public_yarn_versions)
+    }
+}
+
+/// Produces a public index from memory, or fetches it again if it has expired. The
+/// versions are sorted oldest first.
+fn warm<F>(index: &mut Option<(Instant, Vec<Version>)>, fetch: F) -> Fallible<Vec<Version>>
+where
+    F: FnOnce() -> Fallible<Vec<Version>>,
+{
+    if let Some((ref fetched, ref versions)) = *index {
+        if fetched.elapsed() < Duration::from_secs(INDEX_TTL_SECS) {
+            return Ok(versions.clone());
+        }
+    }
+    let mut versions = fetch()?;
+    versions.sort();
+    *index = Some((Instant::now(), versions.clone()));
+    Ok(versions)
+}
+
+fn parse_params<T>(params: Value) -> Result<T, RpcError>
+where
+    T: ::serde::de::DeserializeOwned,
+{
+    serde_json::from_value(params)
+        .map_err(|error| RpcError::new(INVALID_PARAMS, error.to_string()))
+}
+
+fn to_value<T: ::serde::Serialize>(value: T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+fn respond(id: Value, result: Result<Value, RpcError>) -> String {
+    let (result, error) = match result {
+        Ok(result) => (Some(result), None),
+        Err(error) => (None, Some(error)),
+    };
+    let response = Response {
+        jsonrpc: "2.0",
+        id,
+        result,
+        error,
+    };
+    serde_json::to_string(&response).unwrap_or_default()
+}
+
+/// A request line, and where to send the response to it.
+type Pending = (String, Sender<Option<String>>);
+
+/// Reads the requests of a connection, one per line, and writes their responses.
+fn converse<R: Read, W: Write>(
+    reader: R,
+    mut writer: W,
+    queries: Sender<Pending>,
+) -> Fallible<()> {
+    for line in BufReader::new(reader).lines() {
+        let line = line.unknown()?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (reply, response) = mpsc::channel();
+        if queries.send((line, reply)).is_err() {
+            break;
+        }
+        if let Ok(Some(response)) = response.recv() {
+            writeln!(writer, "{}", response).unknown()?;
+        }
+    }
+    Ok(())
+}
+
+/// Answers the requests of every connection one at a time, in the order they arrive,
+/// until no connection can be made anymore.
+fn answer(queue: Receiver<Pending>) {
+    let mut daemon = Daemon::new();
+    for (query, reply) in queue {
+        let _ = reply.send(daemon.handle(&query));
+    }
+}
+
+/// Answers requests on a Unix socket until the process is killed. Each connection is
+/// read on a thread of its own, but the requests are answered one at a time, in the
+/// order they arrive.
+#[cfg(unix)]
+pub fn serve(socket: &Path) -> Fallible<()> {
+    use std::fs::{self, Permissions};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    use fs::ensure_containing_dir_exists;
+
+    // a socket that can't be connected to was left behind by a daemon that was killed
+    if UnixStream::connect(socket).is_ok() {
+        throw!(DaemonRunningError {
+            socket: socket.display().to_string(),
+        });
+    }
+    let _ = fs::remove_file(socket);
+    ensure_containing_dir_exists(&socket)?;
+    let listener =
+        UnixListener::bind(socket).with_context(DaemonListenError::for_socket(socket))?;
+    fs::set_permissions(socket, Permissions::from_mode(0o600)).unknown()?;
+
+    let (queries, queue) = mpsc::channel::<Pending>();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            if let Ok(stream) = stream {
+                let queries = queries.clone();
+                thread::spawn(move || {
+                    if let Ok(writer) = stream.try_clone() {
+                        let _ = converse(stream, writer, queries);
+                    }
+                });
+            }
+        }
+    });
+
+    answer(queue);
+    Ok(())
+}
+
+/// Answers requests on a named pipe until the process is killed. Each connection is
+/// made to an instance of the pipe of its own and read on a thread of its own, but
+/// the requests are answered one at a time, in the order they arrive.
+#[cfg(windows)]
+pub fn serve(pipe: &Path) -> Fallible<()> {
+    use std::fs::File;
+    use std::io;
+    use std::iter;
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::io::{AsRawHandle, FromRawHandle, RawHandle};
+    use std::ptr;
+
+    use winapi::shared::winerror::{ERROR_ACCESS_DENIED, ERROR_PIPE_CONNECTED};
+    use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+    use winapi::um::namedpipeapi::{ConnectNamedPipe, CreateNamedPipeW};
+    use winapi::um::winbase::{FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX,
+                              PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE,
+                              PIPE_UNLIMITED_INSTANCES, PIPE_WAIT};
+    use winapi::um::winnt::HANDLE;
+
+    const BUFFER_SIZE: u32 = 4096;
+
+    /// Creates an instance of the pipe. Only the first instance claims the name, so
+    /// creating it fails if another daemon already has.
+    fn create(name: &[u16], first: bool) -> io::Result<File> {
+        let mut open_mode = PIPE_ACCESS_DUPLEX;
+        if first {
+            open_mode |= FILE_FLAG_FIRST_PIPE_INSTANCE;
+        }
+        let pipe_mode =
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS;
+        unsafe {
+            let handle = CreateNamedPipeW(
+                name.as_ptr(),
+                open_mode,
+                pipe_mode,
+                PIPE_UNLIMITED_INSTANCES,
+                BUFFER_SIZE,
+                BUFFER_SIZE,
+                0,
+                ptr::null_mut(),
+            );
+            if handle == INVALID_HANDLE_VALUE {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(File::from_raw_handle(handle as RawHandle))
+        }
+    }
+
+    /// Waits for a client to connect to an instance of the pipe.
+    fn connect(instance: &File) -> io::Result<()> {
+        // a client that connected before this was called is connected all the same
+        let handle = instance.as_raw_handle() as HANDLE;
+        if unsafe { ConnectNamedPipe(handle, ptr::null_mut()) } == 0 {
+            let error = io::Error::last_os_error();
+            if error.raw_os_error() != Some(ERROR_PIPE_CONNECTED as i32) {
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    let name: Vec<u16> = pipe.as_os_str().encode_wide().chain(iter::once(0)).collect();
+    let mut instance = match create(&name, true) {
+        Ok(instance) => instance,
+        Err(ref error) if error.raw_os_error() == Some(ERROR_ACCESS_DENIED as i32) => {
+            throw!(DaemonRunningError {
+                socket: pipe.display().to_string(),
+            });
+        }
+        Err(error) => {
+            return Err(error).with_context(DaemonListenError::for_socket(pipe));
+        }
+    };
+
+    let (queries, queue) = mpsc::channel::<Pending>();
+    thread::spawn(move || loop {
+        if connect(&instance).is_ok() {
+            let queries = queries.clone();
+            let stream = instance;
+            thread::spawn(move || {
+                if let Ok(writer) = stream.try_clone() {
+                    let _ = converse(&stream, writer, queries);
+                }
+            });
+        }
+        instance = match create(&name, false) {
+            Ok(instance) => instance,
+            Err(_) => break,
+        };
+    });
+
+    answer(queue);
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    fn error_code(response: &str) -> Value {
+        let response: Value = serde_json::from_str(response).unwrap();
+        response["error"]["code"].clone()
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let response = Daemon::new().handle("{\"method\":").unwrap();
+        assert_eq!(error_code(&response), PARSE_ERROR);
+    }
+
+    #[test]
+    fn test_invalid_request() {
+        let response = Daemon::new().handle("{\"jsonrpc\":\"2.0\",\"id\":1}").unwrap();
+        assert_eq!(error_code(&response), INVALID_REQUEST);
+    }
+
+    #[test]
+    fn test_unknown_method() {
+        let response = Daemon::new()
+            .handle("{\"jsonrpc\":\"2.0\",\"id\":7,\"method\":\"install\"}")
+            .unwrap();
+        assert_eq!(error_code(&response), METHOD_NOT_FOUND);
+        let response: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["jsonrpc"], "2.0");
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        let response = Daemon::new()
+            .handle("{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"list\",\"params\":{}}")
+            .unwrap();
+        assert_eq!(error_code(&response), INVALID_PARAMS);
+
+        let response = Daemon::new()
+            .handle(r#"{"jsonrpc":"2.0","id":1,"method":"list","params":{"tool":"npm"}}"#)
+            .unwrap();
+        assert_eq!(error_code(&response), INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_notification() {
+        assert_eq!(
+            Daemon::new().handle("{\"jsonrpc\":\"2.0\",\"method\":\"install\"}"),
+            None
+        );
+    }
+}
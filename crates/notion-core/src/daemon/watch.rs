@@ -0,0 +1,99 @@
+//! Provides the watches that keep the daemon's answers consistent with the files they
+//! were resolved from.
+//!
+//! A watch records the catalog's generation and the sizes and modification times of
+//! every file an answer for a directory can depend on: the user configuration and
+//! trusted projects files, and in the directory and each of its ancestors, the files
+//! that make it a project or pin a toolchain, and the `node_modules` directories its
+//! binaries are found in. It is checked before an answer is reused, rather than by a
+//! thread in the background, so that an answer is never served after another `notion`
+//! process has changed one of them.
+
+use std::path::{Path, PathBuf};
+
+use catalog;
+use image::cache::stamp;
+use lockfile::LOCK_FILE;
+use notion_fail::Fallible;
+use path;
+use toolchain::{TOOLCHAIN_FILE, VERSION_FILE};
+
+/// The state of the files an answer was resolved from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watch {
+    generation: u64,
+    files: Vec<(PathBuf, [u64; 3])>,
+}
+
+impl Watch {
+    /// Starts watching the files that answers for `dir` depend on.
+    pub fn of(dir: &Path) -> Fallible<Watch> {
+        let files = watched_files(dir)?
+            .into_iter()
+            .map(|file| {
+                let stamp = stamp(&file);
+                (file, stamp)
+            })
+            .collect();
+        Ok(Watch {
+            generation: catalog::generation()?,
+            files,
+        })
+    }
+
+    /// Returns true if none of the watched files have changed since the watch started.
+    pub fn is_fresh(&self) -> bool {
+        match catalog::generation() {
+            Ok(generation) if generation == self.generation => {}
+            _ => return false,
+        }
+        self.files
+            .iter()
+            .all(|&(ref file, ref recorded)| stamp(file) == *recorded)
+    }
+}
+
+fn watched_files(dir: &Path) -> Fallible<Vec<PathBuf>> {
+    let mut files = vec![path::user_config_file()?, path::trusted_projects_file()?];
+    for ancestor in dir.ancestors() {
+        files.push(ancestor.join("package.json"));
+        files.push(ancestor.join(TOOLCHAIN_FILE));
+        files.push(ancestor.join(VERSION_FILE));
+        files.push(ancestor.join(LOCK_FILE));
+        files.push(ancestor.join("node_modules"));
+        files.push(ancestor.join("node_modules").join(".bin"));
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    use std::fs::File;
+    use std::io::Write;
+
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_watched_files() {
+        let dir = PathBuf::from("/projects/app");
+        let files = watched_files(&dir).unwrap();
+        assert!(files.contains(&dir.join("package.json")));
+        assert!(files.contains(&PathBuf::from("/projects").join(VERSION_FILE)));
+        assert!(files.contains(&PathBuf::from("/").join(TOOLCHAIN_FILE)));
+        assert!(files.contains(&dir.join("node_modules").join(".bin")));
+    }
+
+    #[test]
+    fn test_fresh_until_changed() {
+        let dir = tempdir().unwrap();
+        let watch = Watch::of(dir.path()).unwrap();
+        assert!(watch.is_fresh());
+
+        let mut file = File::create(dir.path().join("package.json")).unwrap();
+        file.write_all(b"{}").unwrap();
+        assert!(!watch.is_fresh());
+    }
+}
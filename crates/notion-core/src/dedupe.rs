@@ -0,0 +1,213 @@
+//! Provides deduplication of identical files across installed Node versions.
+//!
+//! Adjacent Node versions share many identical files (such as much of the bundled
+//! npm and the C headers). Deduplicating replaces every copy of such a file with a
+//! hardlink to a single one, so that its contents are only stored once.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use checksum::sha256_hex;
+use fs::collect_regular_files;
+use notion_fail::{Fallible, ResultExt};
+
+/// The outcome of deduplicating a set of directories.
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    /// The number of files replaced by hardlinks.
+    pub linked: usize,
+    /// The disk space freed by replacing them, in bytes.
+    pub reclaimed: u64,
+}
+
+/// Replaces files with identical contents (and permissions) across a set of
+/// directories with hardlinks to one copy. If `apply` is false, nothing is changed
+/// and the summary reports what deduplicating would do. Files that are removed while
+/// this runs, such as those of a version that is uninstalled, are skipped.
+pub fn dedupe_dirs(dirs: &[PathBuf], apply: bool) -> Fallible<Summary> {
+    let mut files = Vec::new();
+    for dir in dirs {
+        if dir.is_dir() {
+            match collect_regular_files(dir, &mut files) {
+                Err(_) if !dir.is_dir() => {}
+                result => result?,
+            }
+        }
+    }
+    files.sort();
+
+    // Only files of the same size and permissions can be linked, so they are grouped
+    // by those first and only hashed if there is more than one in a group.
+    let mut candidates: BTreeMap<(u64, u32), Vec<(PathBuf, Metadata)>> = BTreeMap::new();
+    for file in files {
+        let metadata = match unless_missing(fs::metadata(&file))? {
+            Some(metadata) => metadata,
+            None => continue,
+        };
+        if metadata.len() > 0 {
+            candidates
+                .entry((metadata.len(), permissions_key(&metadata)))
+                .or_insert_with(Vec::new)
+                .push((file, metadata));
+        }
+    }
+
+    let mut summary = Summary::default();
+    for (_, group) in candidates {
+        if group.len() < 2 {
+            continue;
+        }
+
+        let mut by_digest: BTreeMap<String, Vec<(PathBuf, Metadata)>> = BTreeMap::new();
+        for (file, metadata) in group {
+            let digest = match unless_missing(File::open(&file))? {
+                Some(mut opened) => sha256_hex(&mut opened)?,
+                None => continue,
+            };
+            by_digest.entry(digest).or_insert_with(Vec::new).push((file, metadata));
+        }
+
+        for (_, mut copies) in by_digest {
+            let (original, original_metadata) = copies.remove(0);
+            for (copy, metadata) in copies {
+                if is_same_file(&original_metadata, &metadata) {
+                    continue;
+                }
+                if apply && !link(&original, &copy)? {
+                    continue;
+                }
+                summary.linked += 1;
+                if is_last_link(&metadata) {
+                    summary.reclaimed += metadata.len();
+                }
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Replaces `copy` with a hardlink to `original`. The link is created next to the
+/// copy and then renamed over it, so the copy is never missing. Returns false if the
+/// link could not be created (for instance because the files are on different
+/// filesystems), in which case the copy is left as it is, or if the copy was removed.
+fn link(original: &Path, copy: &Path) -> Fallible<bool> {
+    let name = copy
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let temp = copy.with_file_name(format!(".{}.notion-dedupe", name));
+
+    if fs::hard_link(original, &temp).is_err() {
+        return Ok(false);
+    }
+    let renamed = fs::rename(&temp, copy);
+    if renamed.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    Ok(unless_missing(renamed)?.is_some())
+}
+
+/// Produces the outcome of a filesystem operation, or `None` if the file it was on has
+/// been removed.
+fn unless_missing<T>(result: io::Result<T>) -> Fallible<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error).unknown(),
+    }
+}
+
+#[cfg(unix)]
+fn permissions_key(metadata: &Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode()
+}
+
+#[cfg(windows)]
+fn permissions_key(metadata: &Metadata) -> u32 {
+    metadata.permissions().readonly() as u32
+}
+
+/// Tests whether two files are already links to the same contents.
+#[cfg(unix)]
+fn is_same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+// NOTE: The file index that identifies a file on Windows is not yet available from
+//       the standard library, so files that are already linked are linked again
+//       (which is harmless, but counted again in the summary).
+#[cfg(windows)]
+fn is_same_file(_a: &Metadata, _b: &Metadata) -> bool {
+    false
+}
+
+/// Tests whether removing a file frees its contents, because no other hardlink to
+/// them exists.
+#[cfg(unix)]
+fn is_last_link(metadata: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    metadata.nlink() == 1
+}
+
+#[cfg(windows)]
+fn is_last_link(_metadata: &Metadata) -> bool {
+    true
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{dedupe_dirs, Summary};
+    use std::fs::{create_dir_all, read_to_string, File};
+    use std::io::Write;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn write_file(file: &Path, contents: &str) {
+        create_dir_all(file.parent().unwrap()).unwrap();
+        File::create(file).unwrap().write_all(contents.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_dedupe_dirs() {
+        let root = tempdir().expect("Could not create temporary directory");
+        let a = root.path().join("8.0.0");
+        let b = root.path().join("8.1.0");
+        write_file(&a.join("include").join("node.h"), "same header");
+        write_file(&b.join("include").join("node.h"), "same header");
+        write_file(&a.join("CHANGELOG.md"), "eight zero");
+        write_file(&b.join("CHANGELOG.md"), "eight one!");
+        let dirs = vec![a.clone(), b.clone()];
+
+        let expected = Summary {
+            linked: 1,
+            reclaimed: "same header".len() as u64,
+        };
+        assert_eq!(dedupe_dirs(&dirs, false).unwrap(), expected);
+        assert_eq!(dedupe_dirs(&dirs, true).unwrap(), expected);
+        assert_eq!(
+            read_to_string(b.join("include").join("node.h")).unwrap(),
+            "same header"
+        );
+        assert_eq!(read_to_string(b.join("CHANGELOG.md")).unwrap(), "eight one!");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dedupe_dirs_is_idempotent() {
+        let root = tempdir().expect("Could not create temporary directory");
+        let a = root.path().join("8.0.0");
+        let b = root.path().join("8.1.0");
+        write_file(&a.join("node.h"), "same header");
+        write_file(&b.join("node.h"), "same header");
+        let dirs = vec![a, b];
+
+        dedupe_dirs(&dirs, true).unwrap();
+        assert_eq!(dedupe_dirs(&dirs, true).unwrap(), Summary::default());
+    }
+}
@@ -20,3 +20,44 @@ impl DownloadError {
         }
     }
 }
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Archive for version {} did not contain the expected directory '{}'", version, root_dir)]
+#[notion_fail(code = "FileSystemError")]
+pub(crate) struct UnpackVerificationError {
+    pub(crate) version: String,
+    pub(crate) root_dir: String,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Archive file {} does not exist", file)]
+#[notion_fail(code = "FileSystemError")]
+pub(crate) struct ArchiveNotFoundError {
+    pub(crate) file: String,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Unexpected archive file name {} (expected {})", file, expected)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct ArchiveNameError {
+    pub(crate) file: String,
+    pub(crate) expected: String,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Failed to download {}\n{}", url, error)]
+#[notion_fail(code = "NetworkError")]
+pub(crate) struct UrlDownloadError {
+    pub(crate) url: String,
+    pub(crate) error: String,
+}
+
+impl UrlDownloadError {
+    pub(crate) fn for_url<E: ToString>(url: &str) -> impl FnOnce(&E) -> UrlDownloadError {
+        let url = url.to_string();
+        move |error| UrlDownloadError {
+            url: url,
+            error: error.to_string(),
+        }
+    }
+}
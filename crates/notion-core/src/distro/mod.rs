@@ -4,10 +4,34 @@ mod error;
 pub mod node;
 pub mod yarn;
 
+use std::ffi::OsStr;
+use std::fs::{create_dir_all, read_dir, remove_dir_all, remove_file, File};
+use std::io::{copy, ErrorKind, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use indicatif::ProgressBar;
+use node_archive::{self, Archive, Throttled};
+use reqwest::RequestBuilder;
+use tempfile::{tempfile, Builder, NamedTempFile, TempDir};
+
+use arch::Libc;
 use catalog::Collection;
-use notion_fail::Fallible;
+use checksum::{sha256_hex, Checksum};
+use config::LazyConfig;
+use distro::error::{ArchiveNameError, ArchiveNotFoundError, DownloadError,
+                    UnpackVerificationError, UrlDownloadError};
+use fs::CreateDirError;
+use gatekeeper;
+use http::{self, ReadTimeoutError};
+use lock::FileLock;
+use netrc;
+use notion_fail::{Fallible, ResultExt};
+use path;
+use provenance::{Source, Verification};
+use retry::{self, Policy};
 use semver::Version;
-use std::fs::File;
+use store;
+use timing::{self, Phase};
 
 /// The result of a requested installation.
 pub enum Fetched {
@@ -33,9 +57,98 @@ impl Fetched {
     }
 }
 
+/// The location of a resolved distribution, which has not yet been provisioned.
+pub struct Location {
+    /// The resolved version.
+    pub version: Version,
+    /// The URL the distribution can be downloaded from.
+    pub url: String,
+}
+
 pub trait Distro: Sized {
+    /// Produces the URL of a version's archive on the public distributor.
+    fn public_url(version: &Version) -> String;
+
     /// Provision a distribution from the public distributor (e.g. `https://nodejs.org`).
-    fn public(version: Version) -> Fallible<Self>;
+    fn public(version: Version) -> Fallible<Self> {
+        let url = Self::public_url(&version);
+        Self::remote(version, &url)
+    }
+
+    /// Produces the path the archive for a version is cached at after downloading.
+    fn cache_file(version: &Version) -> Fallible<PathBuf>;
+
+    /// The prefix of archive file names, which is followed by the version.
+    const ARCHIVE_PREFIX: &'static str;
+
+    /// Produces the file name of the archive for a version.
+    fn archive_file(version: &Version) -> String;
+
+    /// Produces the C library that builds from this distributor are linked against on
+    /// this machine, or `None` if builds do not depend on the C library.
+    fn libc() -> Option<Libc> {
+        None
+    }
+
+    /// Provision a distribution from a local archive file, such as one copied onto an
+    /// air-gapped machine. The version is read from the file name, which must be the
+    /// name the distributor publishes for that version and platform.
+    fn local(file: &Path) -> Fallible<Self> {
+        if !file.is_file() {
+            throw!(ArchiveNotFoundError {
+                file: file.to_string_lossy().to_string(),
+            });
+        }
+
+        let version = local_archive_version(file, Self::ARCHIVE_PREFIX)?;
+        let expected = Self::archive_file(&version);
+        if file.file_name() != Some(OsStr::new(&expected)) {
+            throw!(ArchiveNameError {
+                file: file.to_string_lossy().to_string(),
+                expected,
+            });
+        }
+
+        let distro = Self::cached(version, File::open(file).unknown()?)?;
+        Ok(distro.with_source(Source::local(file)))
+    }
+
+    /// Provision a distribution from an arbitrary URL, such as a custom build. The
+    /// version is read from the last segment of the URL.
+    ///
+    /// By default the archive is unpacked as it downloads, and written to a temporary
+    /// file along the way. If a checksum is given, the file is verified against it once
+    /// the archive has been unpacked, and the version is only installed if it matches.
+    /// With `download.stream = false`, the archive is downloaded in full and verified
+    /// before it is unpacked.
+    fn from_url(url: &str, checksum: Option<&Checksum>) -> Fallible<Self> {
+        let version = url_archive_version(url, Self::ARCHIVE_PREFIX)?;
+
+        if LazyConfig::new().get()?.stream_downloads() {
+            let file = NamedTempFile::new().unknown()?;
+            let archive = fetch_archive(url, file.path(), &version)?;
+            let distro = Self::streaming(version, archive);
+            return Ok(distro.with_source(Source::streamed(url, file, checksum)));
+        }
+
+        let mut file = timing::time(Phase::Download, || download(url))?;
+
+        let verification = match checksum {
+            Some(checksum) => {
+                file.seek(SeekFrom::Start(0)).unknown()?;
+                timing::time(Phase::Checksum, || checksum.verify(url, &mut file))?;
+                Verification::Checksum
+            }
+            None => Verification::Unverified,
+        };
+
+        file.seek(SeekFrom::Start(0)).unknown()?;
+        let digest = sha256_hex(&mut file)?;
+
+        file.seek(SeekFrom::Start(0)).unknown()?;
+        let distro = Self::cached(version, file)?;
+        Ok(distro.with_source(Source::downloaded(url, digest, verification)))
+    }
 
     /// Provision a distribution from a remote distributor.
     fn remote(version: Version, url: &str) -> Fallible<Self>;
@@ -43,6 +156,15 @@ pub trait Distro: Sized {
     /// Provision a distribution from the filesystem.
     fn cached(version: Version, file: File) -> Fallible<Self>;
 
+    /// Provision a distribution from an archive that is still being downloaded.
+    fn streaming(version: Version, archive: Box<Archive>) -> Self;
+
+    /// Produces where this distribution's archive came from, if it is known.
+    fn source(&self) -> Option<&Source>;
+
+    /// Records where this distribution's archive came from.
+    fn with_source(self, source: Source) -> Self;
+
     /// Produces a reference to this distro's Tool version.
     fn version(&self) -> &Version;
 
@@ -50,3 +172,238 @@ pub trait Distro: Sized {
     /// to update its state after fetching succeeds.)
     fn fetch(self, catalog: &Collection<Self>) -> Fallible<Fetched>;
 }
+
+/// Reads the version from the name of an archive file, e.g. `10.2.1` from
+/// `node-v10.2.1-linux-x64.tar.gz` (with the prefix `node-v`).
+fn local_archive_version(file: &Path, prefix: &str) -> Fallible<Version> {
+    let name = file
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let version = if name.starts_with(prefix) {
+        let rest = &name[prefix.len()..];
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        Version::parse(rest[..end].trim_right_matches('.')).ok()
+    } else {
+        None
+    };
+
+    match version {
+        Some(version) => Ok(version),
+        None => throw!(ArchiveNameError {
+            file: file.to_string_lossy().to_string(),
+            expected: format!("{}<version>...", prefix),
+        }),
+    }
+}
+
+/// Reads the version from the last segment of an archive URL.
+pub(crate) fn url_archive_version(url: &str, prefix: &str) -> Fallible<Version> {
+    let name = url.rsplit('/').next().unwrap_or(url);
+    local_archive_version(Path::new(name), prefix)
+}
+
+/// Starts downloading an archive into a cache file, with the connection for its URL
+/// (see `http`), retrying transient failures (see `retry`).
+pub(crate) fn fetch_archive(
+    url: &str,
+    cache_file: &Path,
+    version: &Version,
+) -> Fallible<Box<Archive>> {
+    http::check_connect(url)?;
+    let connection = http::connection(url)?;
+    let result = Policy::current()?.run(
+        url,
+        || node_archive::fetch_with(url, cache_file, &connection),
+        |result| retry::transient_fetch(result, node_archive::is_transient),
+    );
+    if let Err(ref error) = result {
+        if node_archive::is_timeout(error) {
+            throw!(ReadTimeoutError::new(url, http::read_timeout()?));
+        }
+    }
+    let archive = result.with_context(DownloadError::for_version(version.to_string()))?;
+    Ok(archive)
+}
+
+/// Downloads a file in full to a temporary file, with the TLS settings, maximum rate,
+/// and timeouts (see `http`) and basic auth if there are credentials for the URL (see
+/// `netrc`), retrying transient failures (see `retry`).
+fn download(url: &str) -> Fallible<File> {
+    let credentials = netrc::credentials_for(url)?;
+    let authorize = |request: &mut RequestBuilder| netrc::authorize(request, credentials.as_ref());
+    let mut response = http::get_with(url, authorize)?
+        .with_context(UrlDownloadError::for_url(url))?;
+    if !response.status().is_success() {
+        throw!(UrlDownloadError {
+            url: url.to_string(),
+            error: format!("server responded with {}", response.status()),
+        });
+    }
+
+    let mut file = tempfile().unknown()?;
+    if let Err(error) = copy(&mut Throttled::new(response, http::max_rate()?), &mut file) {
+        if error.kind() == ErrorKind::TimedOut || error.kind() == ErrorKind::WouldBlock {
+            throw!(ReadTimeoutError::new(url, http::read_timeout()?));
+        }
+        throw!(UrlDownloadError {
+            url: url.to_string(),
+            error: error.to_string(),
+        });
+    }
+    Ok(file)
+}
+
+/// The filename prefix of the temporary directories that archives are unpacked into.
+const STAGING_PREFIX: &'static str = "staging-";
+
+/// The filename suffix of the lock file next to each staging directory, which is
+/// locked for as long as the directory is in use.
+const STAGING_LOCK_SUFFIX: &'static str = ".lock";
+
+/// A staging directory inside the Notion home. Since it is on the same filesystem as
+/// the versions directory, its contents can be atomically renamed into place. It is
+/// locked for as long as it exists, so that `cleanup_staging` in another Notion
+/// process leaves it alone, and it is removed along with anything left inside it when
+/// dropped.
+pub(crate) struct Staging {
+    // fields are dropped in order, so the directory is removed before it is unlocked
+    dir: TempDir,
+    _lock: FileLock,
+    _lock_file: NamedTempFile,
+}
+
+impl Staging {
+    pub(crate) fn path(&self) -> &Path {
+        self.dir.path()
+    }
+}
+
+/// Creates a fresh staging directory. Its lock file is created and locked before the
+/// directory itself, so a staging directory is never in use without being locked.
+pub(crate) fn staging_dir() -> Fallible<Staging> {
+    let root = path::staging_dir()?;
+    create_dir_all(&root).with_context(CreateDirError::for_dir(root.to_string_lossy().to_string()))?;
+    let lock_file = Builder::new()
+        .prefix(STAGING_PREFIX)
+        .suffix(STAGING_LOCK_SUFFIX)
+        .tempfile_in(&root)
+        .unknown()?;
+    let lock = FileLock::exclusive(lock_file.path())?;
+    let name = lock_file.path().file_stem().unwrap_or_default().to_os_string();
+    let dir = Builder::new()
+        .prefix(&name)
+        .rand_bytes(0)
+        .tempdir_in(&root)
+        .unknown()?;
+    Ok(Staging {
+        dir,
+        _lock: lock,
+        _lock_file: lock_file,
+    })
+}
+
+/// Unpacks an archive into a staging directory, verifies that it contains the expected
+/// root directory, and only then adds its files to the store as the link farm `farm`
+/// and points `dest` at it. If any step fails, the staging directory is discarded and
+/// `dest` is left untouched. A manifest of the unpacked files is recorded at `manifest`
+/// so the install can be verified later. An archive that was streamed from its `source`
+/// is verified once it has been unpacked.
+pub(crate) fn unpack_staged(
+    archive: Box<Archive>,
+    source: Option<&Source>,
+    version: &Version,
+    root_dir: &str,
+    dest: &Path,
+    farm: &Path,
+    manifest: &Path,
+    bar: &ProgressBar,
+) -> Fallible<()> {
+    timing::time(Phase::Unpack, || {
+        let staging = staging_dir()?;
+
+        let unpacked = archive.unpack(staging.path(), &mut |_, read| {
+            bar.inc(read as u64);
+        });
+        // a streamed archive is still downloading while it is unpacked
+        if let (&Err(ref error), Some(source)) = (&unpacked, source) {
+            if node_archive::is_timeout(error) {
+                throw!(ReadTimeoutError::new(source.url(), http::read_timeout()?));
+            }
+        }
+        unpacked.unknown()?;
+
+        if let Some(source) = source {
+            source.verify()?;
+        }
+
+        let unpacked = staging.path().join(root_dir);
+        if !unpacked.is_dir() {
+            throw!(UnpackVerificationError {
+                version: version.to_string(),
+                root_dir: root_dir.to_string(),
+            });
+        }
+
+        gatekeeper::prepare(&unpacked)?;
+
+        // A version can already be at `dest` if a previous install was interrupted after
+        // moving it into place but before recording it in the catalog, or if a damaged
+        // install is being repaired. It is replaced, and removed along with the staging
+        // directory.
+        store::install(&unpacked, staging.path(), farm, dest, manifest)
+    })
+}
+
+/// Removes staging directories left behind by installs that were interrupted (for
+/// instance by a crash or a killed process). A staging directory whose lock is held is
+/// still in use by another Notion process, such as a long-running package install,
+/// and is left alone. Lock files without a directory are left too, since one may have
+/// just been created for a directory that doesn't exist yet.
+pub fn cleanup_staging() -> Fallible<()> {
+    let root = path::staging_dir()?;
+
+    let entries = match read_dir(&root) {
+        Ok(entries) => entries.collect::<Result<Vec<_>, _>>().unknown()?,
+        Err(_) => return Ok(()),
+    };
+
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with(STAGING_PREFIX) || !entry.path().is_dir() {
+            continue;
+        }
+        let lock_file = root.join(format!("{}{}", name, STAGING_LOCK_SUFFIX));
+        if let Some(_lock) = FileLock::try_exclusive(&lock_file)? {
+            remove_dir_all(entry.path()).unknown()?;
+            let _ = remove_file(&lock_file);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::local_archive_version;
+    use semver::Version;
+    use std::path::Path;
+
+    #[test]
+    fn test_local_archive_version() {
+        assert_eq!(
+            local_archive_version(Path::new("/usb/node-v10.2.1-linux-x64.tar.gz"), "node-v").unwrap(),
+            Version::parse("10.2.1").unwrap()
+        );
+        assert_eq!(
+            local_archive_version(Path::new("yarn-v1.9.4.tar.gz"), "yarn-v").unwrap(),
+            Version::parse("1.9.4").unwrap()
+        );
+        assert!(local_archive_version(Path::new("node-latest.tar.gz"), "node-v").is_err());
+        assert!(local_archive_version(Path::new("yarn-v1.9.4.tar.gz"), "node-v").is_err());
+    }
+}
@@ -1,15 +1,17 @@
 //! Provides the `Installer` type, which represents a provisioned Node installer.
 
-use std::fs::{rename, File};
+use std::env;
+use std::fs::File;
 use std::path::PathBuf;
 use std::string::ToString;
 
-use super::{Distro, Fetched};
+use super::{fetch_archive, unpack_staged, Distro, Fetched};
+use arch::{Arch, Libc};
 use catalog::NodeCollection;
-use distro::error::DownloadError;
 use fs::ensure_containing_dir_exists;
 use node_archive::{self, Archive};
 use path;
+use provenance::Source;
 use style::{progress_bar, Action};
 
 use notion_fail::{Fallible, ResultExt};
@@ -18,15 +20,37 @@ use semver::Version;
 #[cfg(feature = "mock-network")]
 use mockito;
 
+/// The environment variable that overrides the server musl builds of Node are
+/// downloaded from.
+const NOTION_NODE_MUSL_MIRROR: &'static str = "NOTION_NODE_MUSL_MIRROR";
+
 cfg_if! {
     if #[cfg(feature = "mock-network")] {
         fn public_node_server_root() -> String {
             mockito::SERVER_URL.to_string()
         }
+        fn public_node_musl_server_root() -> String {
+            mockito::SERVER_URL.to_string()
+        }
     } else {
         fn public_node_server_root() -> String {
             "https://nodejs.org/dist".to_string()
         }
+        /// The official Node server does not publish musl builds, which are published
+        /// by the Node unofficial-builds project instead.
+        fn public_node_musl_server_root() -> String {
+            "https://unofficial-builds.nodejs.org/download/release".to_string()
+        }
+    }
+}
+
+/// Produces the root URL of the server Node builds for this machine's C library are
+/// downloaded from.
+fn node_server_root() -> String {
+    match Libc::native() {
+        Libc::Glibc => public_node_server_root(),
+        Libc::Musl => env::var(NOTION_NODE_MUSL_MIRROR)
+            .unwrap_or_else(|_| public_node_musl_server_root()),
     }
 }
 
@@ -34,6 +58,8 @@ cfg_if! {
 pub struct NodeDistro {
     archive: Box<Archive>,
     version: Version,
+    arch: Arch,
+    source: Option<Source>,
 }
 
 /// Check if the cached file is valid. It may have been corrupted or interrupted in the middle of
@@ -51,33 +77,99 @@ fn cache_is_valid(cache_file: &PathBuf) -> bool {
     false
 }
 
+impl NodeDistro {
+    /// Produces the URL of a Node archive for the specified architecture on the public
+    /// Node distributor.
+    pub fn public_url_for(version: &Version, arch: Arch) -> String {
+        let archive_file = path::node_archive_file_for(&version.to_string(), arch);
+        format!(
+            "{}/v{}/{}",
+            node_server_root(),
+            version,
+            &archive_file
+        )
+    }
+
+    /// Provision a Node distribution built for the specified architecture from the
+    /// public Node distributor.
+    pub fn public_for(version: Version, arch: Arch) -> Fallible<Self> {
+        if arch == Arch::native() {
+            return NodeDistro::public(version);
+        }
+
+        let url = NodeDistro::public_url_for(&version, arch);
+        let archive_file = path::node_archive_file_for(&version.to_string(), arch);
+        let cache_file = path::node_cache_dir()?.join(&archive_file);
+
+        if cache_is_valid(&cache_file) {
+            return Ok(NodeDistro {
+                archive: node_archive::load(File::open(&cache_file).unknown()?).unknown()?,
+                version,
+                arch,
+                source: Some(Source::cached(&url, cache_file)),
+            });
+        }
+
+        ensure_containing_dir_exists(&cache_file)?;
+        Ok(NodeDistro {
+            archive: fetch_archive(&url, &cache_file, &version)?,
+            version,
+            arch,
+            source: Some(Source::cached(&url, cache_file)),
+        })
+    }
+
+    /// Produces the architecture this distribution was built for.
+    pub fn arch(&self) -> Arch {
+        self.arch
+    }
+}
+
 impl Distro for NodeDistro {
-    /// Provision a Node distribution from the public Node distributor (`https://nodejs.org`).
-    fn public(version: Version) -> Fallible<Self> {
+    /// Produces the URL of a Node archive on the public Node distributor (`https://nodejs.org`).
+    fn public_url(version: &Version) -> String {
         let archive_file = path::node_archive_file(&version.to_string());
-        let url = format!(
+        format!(
             "{}/v{}/{}",
-            public_node_server_root(),
+            node_server_root(),
             version,
             &archive_file
-        );
-        NodeDistro::remote(version, &url)
+        )
+    }
+
+    /// Produces the path a Node archive is cached at after downloading.
+    fn cache_file(version: &Version) -> Fallible<PathBuf> {
+        let archive_file = path::node_archive_file(&version.to_string());
+        Ok(path::node_cache_dir()?.join(&archive_file))
+    }
+
+    const ARCHIVE_PREFIX: &'static str = "node-v";
+
+    /// Produces the file name of the Node archive for this platform.
+    fn archive_file(version: &Version) -> String {
+        path::node_archive_file(&version.to_string())
+    }
+
+    /// Node builds for Linux are linked against either glibc or musl.
+    fn libc() -> Option<Libc> {
+        Some(Libc::native())
     }
 
     /// Provision a Node distribution from a remote distributor.
     fn remote(version: Version, url: &str) -> Fallible<Self> {
-        let archive_file = path::node_archive_file(&version.to_string());
-        let cache_file = path::node_cache_dir()?.join(&archive_file);
+        let cache_file = NodeDistro::cache_file(&version)?;
 
         if cache_is_valid(&cache_file) {
-            return NodeDistro::cached(version, File::open(cache_file).unknown()?);
+            let distro = NodeDistro::cached(version, File::open(&cache_file).unknown()?)?;
+            return Ok(distro.with_source(Source::cached(url, cache_file)));
         }
 
         ensure_containing_dir_exists(&cache_file)?;
         Ok(NodeDistro {
-            archive: node_archive::fetch(url, &cache_file)
-                .with_context(DownloadError::for_version(version.to_string()))?,
+            archive: fetch_archive(url, &cache_file, &version)?,
             version: version,
+            arch: Arch::native(),
+            source: Some(Source::cached(url, cache_file)),
         })
     }
 
@@ -86,9 +178,34 @@ impl Distro for NodeDistro {
         Ok(NodeDistro {
             archive: node_archive::load(file).unknown()?,
             version: version,
+            arch: Arch::native(),
+            source: None,
         })
     }
 
+    /// Provision a distribution from an archive that is still being downloaded.
+    fn streaming(version: Version, archive: Box<Archive>) -> Self {
+        NodeDistro {
+            archive,
+            version,
+            arch: Arch::native(),
+            source: None,
+        }
+    }
+
+    /// Produces where this distribution's archive came from, if it is known.
+    fn source(&self) -> Option<&Source> {
+        self.source.as_ref()
+    }
+
+    /// Records where this distribution's archive came from.
+    fn with_source(self, source: Source) -> Self {
+        NodeDistro {
+            source: Some(source),
+            ..self
+        }
+    }
+
     /// Produces a reference to this distribution's Node version.
     fn version(&self) -> &Version {
         &self.version
@@ -97,11 +214,10 @@ impl Distro for NodeDistro {
     /// Fetches this version of Node. (It is left to the responsibility of the `NodeCollection`
     /// to update its state after fetching succeeds.)
     fn fetch(self, collection: &NodeCollection) -> Fallible<Fetched> {
-        if collection.contains(&self.version) {
+        if collection.contains_arch(&self.version, self.arch) {
             return Ok(Fetched::Already(self.version));
         }
 
-        let dest = path::node_versions_dir()?;
         let bar = progress_bar(
             Action::Fetching,
             &format!("v{}", self.version),
@@ -110,17 +226,19 @@ impl Distro for NodeDistro {
                 .unwrap_or(self.archive.compressed_size()),
         );
 
-        self.archive
-            .unpack(&dest, &mut |_, read| {
-                bar.inc(read as u64);
-            })
-            .unknown()?;
-
         let version_string = self.version.to_string();
-        rename(
-            dest.join(path::node_archive_root_dir(&version_string)),
-            path::node_version_dir(&version_string)?,
-        ).unknown()?;
+        let key = path::node_version_key(&version_string, self.arch);
+        // versions are installed in the user's own directory, never the system layer
+        unpack_staged(
+            self.archive,
+            self.source.as_ref(),
+            &self.version,
+            &path::node_archive_root_dir_for(&version_string, self.arch),
+            &path::node_versions_dir()?.join(&key),
+            &path::node_farm_dir(&key)?,
+            &path::node_manifest_file(&key)?,
+            &bar,
+        )?;
 
         bar.finish_and_clear();
         Ok(Fetched::Now(self.version))
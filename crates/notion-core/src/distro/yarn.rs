@@ -1,15 +1,15 @@
 //! Provides the `Installer` type, which represents a provisioned Node installer.
 
-use std::fs::{rename, File};
+use std::fs::File;
 use std::path::PathBuf;
 use std::string::ToString;
 
-use super::{Distro, Fetched};
+use super::{fetch_archive, unpack_staged, Distro, Fetched};
 use catalog::YarnCollection;
-use distro::error::DownloadError;
 use fs::ensure_containing_dir_exists;
 use node_archive::{self, Archive};
 use path;
+use provenance::Source;
 use style::{progress_bar, Action};
 
 use notion_fail::{Fallible, ResultExt};
@@ -34,6 +34,7 @@ cfg_if! {
 pub struct YarnDistro {
     archive: Box<Archive>,
     version: Version,
+    source: Option<Source>,
 }
 
 /// Check if the cached file is valid. It may have been corrupted or interrupted in the middle of
@@ -52,27 +53,39 @@ fn cache_is_valid(cache_file: &PathBuf) -> bool {
 }
 
 impl Distro for YarnDistro {
-    /// Provision a distribution from the public Yarn distributor (`https://yarnpkg.com`).
-    fn public(version: Version) -> Fallible<Self> {
+    /// Produces the URL of a Yarn archive on the public Yarn distributor (`https://yarnpkg.com`).
+    fn public_url(version: &Version) -> String {
         let archive_file = path::yarn_archive_file(&version.to_string());
-        let url = format!("{}/{}", public_yarn_server_root(), archive_file);
-        YarnDistro::remote(version, &url)
+        format!("{}/{}", public_yarn_server_root(), archive_file)
+    }
+
+    /// Produces the path a Yarn archive is cached at after downloading.
+    fn cache_file(version: &Version) -> Fallible<PathBuf> {
+        let archive_file = path::yarn_archive_file(&version.to_string());
+        Ok(path::yarn_cache_dir()?.join(&archive_file))
+    }
+
+    const ARCHIVE_PREFIX: &'static str = "yarn-v";
+
+    /// Produces the file name of the Yarn archive for this platform.
+    fn archive_file(version: &Version) -> String {
+        path::yarn_archive_file(&version.to_string())
     }
 
     /// Provision a distribution from a remote distributor.
     fn remote(version: Version, url: &str) -> Fallible<Self> {
-        let archive_file = path::yarn_archive_file(&version.to_string());
-        let cache_file = path::yarn_cache_dir()?.join(&archive_file);
+        let cache_file = YarnDistro::cache_file(&version)?;
 
         if cache_is_valid(&cache_file) {
-            return YarnDistro::cached(version, File::open(cache_file).unknown()?);
+            let distro = YarnDistro::cached(version, File::open(&cache_file).unknown()?)?;
+            return Ok(distro.with_source(Source::cached(url, cache_file)));
         }
 
         ensure_containing_dir_exists(&cache_file)?;
         Ok(YarnDistro {
-            archive: node_archive::fetch(url, &cache_file)
-                .with_context(DownloadError::for_version(version.to_string()))?,
+            archive: fetch_archive(url, &cache_file, &version)?,
             version: version,
+            source: Some(Source::cached(url, cache_file)),
         })
     }
 
@@ -81,9 +94,32 @@ impl Distro for YarnDistro {
         Ok(YarnDistro {
             archive: node_archive::load(file).unknown()?,
             version: version,
+            source: None,
         })
     }
 
+    /// Provision a distribution from an archive that is still being downloaded.
+    fn streaming(version: Version, archive: Box<Archive>) -> Self {
+        YarnDistro {
+            archive,
+            version,
+            source: None,
+        }
+    }
+
+    /// Produces where this distribution's archive came from, if it is known.
+    fn source(&self) -> Option<&Source> {
+        self.source.as_ref()
+    }
+
+    /// Records where this distribution's archive came from.
+    fn with_source(self, source: Source) -> Self {
+        YarnDistro {
+            source: Some(source),
+            ..self
+        }
+    }
+
     /// Produces a reference to this distro's Yarn version.
     fn version(&self) -> &Version {
         &self.version
@@ -96,7 +132,6 @@ impl Distro for YarnDistro {
             return Ok(Fetched::Already(self.version));
         }
 
-        let dest = path::yarn_versions_dir()?;
         let bar = progress_bar(
             Action::Fetching,
             &format!("v{}", self.version),
@@ -105,17 +140,18 @@ impl Distro for YarnDistro {
                 .unwrap_or(self.archive.compressed_size()),
         );
 
-        self.archive
-            .unpack(&dest, &mut |_, read| {
-                bar.inc(read as u64);
-            })
-            .unknown()?;
-
         let version_string = self.version.to_string();
-        rename(
-            dest.join(path::yarn_archive_root_dir(&version_string)),
-            path::yarn_version_dir(&version_string)?,
-        ).unknown()?;
+        // versions are installed in the user's own directory, never the system layer
+        unpack_staged(
+            self.archive,
+            self.source.as_ref(),
+            &self.version,
+            &path::yarn_archive_root_dir(&version_string),
+            &path::yarn_versions_dir()?.join(&version_string),
+            &path::yarn_farm_dir(&version_string)?,
+            &path::yarn_manifest_file(&version_string)?,
+            &bar,
+        )?;
 
         bar.finish_and_clear();
         Ok(Fetched::Now(self.version))
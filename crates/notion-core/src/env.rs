@@ -13,6 +13,39 @@ pub fn postscript_path() -> Option<PathBuf> {
         .map(|ref s| Path::new(s).to_path_buf())
 }
 
+/// Returns true if `NOTION_BYPASS` is set (to anything other than `0` or the empty
+/// string), in which case shims skip all toolchain resolution and run the next
+/// executable of the same name on the `PATH`.
+pub fn bypass() -> bool {
+    match env::var_os("NOTION_BYPASS") {
+        Some(value) => !value.is_empty() && value != "0",
+        None => false,
+    }
+}
+
+/// Returns true if Notion is in CI mode: `NOTION_CI` is set (to anything other than
+/// `0` or the empty string), as `notion --ci` does, or `CI` is `true` or `1`, as CI
+/// services set it.
+pub fn ci() -> bool {
+    match env::var_os("NOTION_CI") {
+        Some(value) => !value.is_empty() && value != "0",
+        None => match env::var("CI") {
+            Ok(value) => value == "true" || value == "1",
+            Err(_) => false,
+        },
+    }
+}
+
+/// Returns the version of a tool selected for the current shell session by
+/// `notion use --session`, from the `NOTION_<TOOL>_VERSION` variable, if any.
+pub fn version_override(tool: &str) -> Option<String> {
+    match env::var(format!("NOTION_{}_VERSION", tool.to_ascii_uppercase())) {
+        Ok(ref value) if value.is_empty() => None,
+        Ok(value) => Some(value),
+        Err(_) => None,
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
 
@@ -32,4 +65,41 @@ pub mod tests {
         assert_eq!(postscript_path().unwrap(), PathBuf::from("/some/path"));
     }
 
+    #[test]
+    fn test_bypass() {
+        env::set_var("NOTION_BYPASS", "1");
+        assert!(bypass());
+        env::set_var("NOTION_BYPASS", "0");
+        assert!(!bypass());
+        env::remove_var("NOTION_BYPASS");
+        assert!(!bypass());
+    }
+
+    #[test]
+    fn test_ci() {
+        env::remove_var("NOTION_CI");
+        env::set_var("CI", "true");
+        assert!(ci());
+        env::set_var("CI", "false");
+        assert!(!ci());
+        env::set_var("NOTION_CI", "1");
+        assert!(ci());
+        env::set_var("NOTION_CI", "0");
+        env::set_var("CI", "true");
+        assert!(!ci());
+        env::remove_var("NOTION_CI");
+        env::remove_var("CI");
+        assert!(!ci());
+    }
+
+    #[test]
+    fn test_version_override() {
+        env::set_var("NOTION_TEST_VERSION", "2.4.5");
+        assert_eq!(version_override("test"), Some("2.4.5".to_string()));
+        env::set_var("NOTION_TEST_VERSION", "");
+        assert_eq!(version_override("test"), None);
+        env::remove_var("NOTION_TEST_VERSION");
+        assert_eq!(version_override("test"), None);
+    }
+
 }
@@ -1,52 +1,119 @@
 //! Events for the sessions in executables and shims and everything
+//!
+//! Each session sends its events to the events plugin (see `[events]` in
+//! `config.toml`) as a JSON array of `Event` objects on the plugin's stdin. The
+//! types in this module are the schema of that payload, and every event carries
+//! the `SCHEMA_VERSION` it was written with.
+//!
+//! Compatibility policy: adding a new field, a new event kind, or a new activity
+//! name is a compatible change and keeps the schema version, so consumers should
+//! ignore fields and kinds they do not recognize. Removing or renaming a field,
+//! or changing its type or meaning, is an incompatible change and increments the
+//! schema version. Payloads from before the schema was versioned have no
+//! `schema_version` field and are read as version 0, which is otherwise identical
+//! to version 1.
 
 extern crate os_info;
 
 use std::env;
+use std::mem;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use monitor::LazyMonitor;
+use checksum::sha256_hex;
+use config::{Delivery, PathsPolicy, Subscriber, TelemetryConfig};
+use monitor;
 use notion_fail::{ExitCode, Fallible, NotionError};
-use plugin::Publish;
+use serde_json;
 use session::ActivityKind;
+use timing::PhaseTiming;
 
-// the Event data that is serialized to JSON and sent the plugin
-#[derive(Serialize)]
+/// The version of the event schema written by this version of Notion.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// An event, as serialized to JSON and sent to the events plugin.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Event {
-    timestamp: u64,
-    name: String,
-    event: EventKind,
+    /// The version of the schema the event was written with.
+    #[serde(default)]
+    pub schema_version: u32,
+    /// The time of the event, in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The name of the activity the event belongs to, e.g. `install`.
+    pub name: String,
+    pub event: EventKind,
 }
 
-#[derive(Serialize)]
+/// The environment an error occurred in.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct ErrorEnv {
-    argv: String,
-    exec_path: String,
-    path: String,
-    platform: String,
-    platform_version: String,
+    /// The command line, with arguments separated by spaces.
+    pub argv: String,
+    /// The path of the running executable.
+    pub exec_path: String,
+    /// The value of the `PATH` environment variable.
+    pub path: String,
+    /// The name of the operating system, e.g. `Mac OS`.
+    pub platform: String,
+    /// The version of the operating system.
+    pub platform_version: String,
 }
 
-#[derive(Serialize)]
+/// What happened in an event.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 #[serde(rename_all = "lowercase")]
-enum EventKind {
+pub enum EventKind {
+    /// The activity started.
     Start,
-    End {
-        exit_code: ExitCode,
-    },
+    /// The activity finished.
+    End { exit_code: ExitCode },
+    /// The activity failed with an error.
     Error {
         exit_code: ExitCode,
         error: String,
         env: ErrorEnv,
     },
-    ToolEnd {
-        exit_code: i32,
+    /// A tool run by a shim exited with the given exit code.
+    ToolEnd { exit_code: i32 },
+    /// A phase of the activity (see `timing::Phase`) finished, after running inside of
+    /// `depth` other phases.
+    Phase {
+        phase: String,
+        depth: usize,
+        duration_ms: u64,
     },
 }
 
+impl Event {
+    /// Applies a paths policy to the fields of the event that can contain file paths
+    /// or project names.
+    fn scrub(mut self, policy: PathsPolicy) -> Event {
+        if let EventKind::Error {
+            ref mut error,
+            ref mut env,
+            ..
+        } = self.event
+        {
+            *error = scrub_field(error, policy);
+            env.argv = scrub_field(&env.argv, policy);
+            env.exec_path = scrub_field(&env.exec_path, policy);
+            env.path = scrub_field(&env.path, policy);
+        }
+        self
+    }
+}
+
+fn scrub_field(value: &str, policy: PathsPolicy) -> String {
+    match policy {
+        PathsPolicy::Keep => value.to_string(),
+        PathsPolicy::Hash => sha256_hex(&mut value.as_bytes()).unwrap_or_default(),
+        PathsPolicy::Drop => String::new(),
+    }
+}
+
 impl EventKind {
     pub fn into_event(self, activity_kind: ActivityKind) -> Event {
         Event {
+            schema_version: SCHEMA_VERSION,
             timestamp: unix_timestamp(),
             name: activity_kind.to_string(),
             event: self,
@@ -88,9 +155,14 @@ fn get_error_env() -> ErrorEnv {
     };
 }
 
+/// The most events a session buffers for its subscribers. Later events are dropped,
+/// so that a long-running session cannot grow its buffer without bound.
+const MAX_BUFFERED_EVENTS: usize = 1000;
+
 pub struct EventLog {
     events: Vec<Event>,
-    monitor: LazyMonitor,
+    /// The first activity started in the session, which phases are recorded under.
+    root: Option<ActivityKind>,
 }
 
 impl EventLog {
@@ -98,11 +170,14 @@ impl EventLog {
     pub fn new() -> Fallible<EventLog> {
         Ok(EventLog {
             events: Vec::new(),
-            monitor: LazyMonitor::new(),
+            root: None,
         })
     }
 
     pub fn add_event_start(&mut self, activity_kind: ActivityKind) {
+        if self.root.is_none() {
+            self.root = Some(activity_kind);
+        }
         self.add_event(EventKind::Start, activity_kind)
     }
     pub fn add_event_end(&mut self, activity_kind: ActivityKind, exit_code: ExitCode) {
@@ -123,29 +198,99 @@ impl EventLog {
         )
     }
 
+    /// Records the timed phases of the session under its first activity.
+    pub fn add_event_phases(&mut self, timings: &[PhaseTiming]) {
+        if let Some(root) = self.root {
+            for timing in timings {
+                let duration_ms = timing.duration.as_secs() * 1000
+                    + (timing.duration.subsec_nanos() / 1_000_000) as u64;
+                self.add_event(
+                    EventKind::Phase {
+                        phase: timing.phase.to_string(),
+                        depth: timing.depth,
+                        duration_ms,
+                    },
+                    root,
+                );
+            }
+        }
+    }
+
     fn add_event(&mut self, event_kind: EventKind, activity_kind: ActivityKind) {
-        let event = event_kind.into_event(activity_kind);
-        self.events.push(event);
+        if self.events.len() < MAX_BUFFERED_EVENTS {
+            let event = event_kind.into_event(activity_kind);
+            self.events.push(event);
+        }
     }
 
-    pub fn publish(&mut self, plugin: Option<&Publish>) {
-        match plugin {
-            Some(&Publish::Url(_)) => unimplemented!(),
-            Some(&Publish::Bin(ref command)) => {
-                self.monitor.get_mut(command).send_events(&self.events);
+    /// Publishes the recorded events to their subscribers, keeping only the events and
+    /// the details that the telemetry settings allow. Each subscriber receives the
+    /// events it subscribes to; subscribers that receive none are not contacted (and
+    /// their commands are not started).
+    pub fn publish(&mut self, subscribers: &[Subscriber], telemetry: Option<&TelemetryConfig>) {
+        if let Some(telemetry) = telemetry {
+            if !telemetry.enabled {
+                return;
+            }
+            let events = mem::replace(&mut self.events, Vec::new());
+            self.events = events
+                .into_iter()
+                .filter(|event| telemetry.publishes(&event.name))
+                .map(|event| event.scrub(telemetry.paths))
+                .collect();
+        }
+
+        let mut deliveries = Vec::new();
+        for subscriber in subscribers {
+            let events: Vec<&Event> = self.events
+                .iter()
+                .filter(|event| subscriber.receives(&event.name))
+                .collect();
+            if events.is_empty() {
+                continue;
+            }
+
+            match payload(&subscriber.delivery, &events) {
+                Ok(payload) => deliveries.push((subscriber.delivery.clone(), payload)),
+                Err(error) => {
+                    // FIXME: tighten up this error message
+                    eprintln!("There was a problem serializing the JSON data: {:?}", error);
+                }
             }
-            None => {}
         }
+
+        monitor::deliver_all(deliveries);
+    }
+}
+
+/// Serializes events for a subscriber: one JSON object per line for files, which are
+/// appended to by every session, and a JSON array otherwise.
+fn payload(delivery: &Delivery, events: &[&Event]) -> serde_json::Result<String> {
+    match delivery {
+        &Delivery::File(_) => {
+            let mut lines = String::new();
+            for event in events {
+                lines.push_str(&serde_json::to_string(event)?);
+                lines.push('\n');
+            }
+            Ok(lines)
+        }
+        _ => serde_json::to_string(events),
     }
 }
 
 #[cfg(test)]
 pub mod tests {
 
-    use super::EventLog;
+    use super::{payload, ErrorEnv, Event, EventKind, EventLog, SCHEMA_VERSION};
+    use config::{Delivery, PathsPolicy, TelemetryConfig};
     use notion_fail::{ExitCode, FailExt};
+    use serde_json;
     use session::ActivityKind;
     use std::io;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use timing::{Phase, PhaseTiming};
 
     #[test]
     fn test_adding_events() {
@@ -168,5 +313,132 @@ pub mod tests {
         event_log.add_event_error(ActivityKind::Install, &error);
         assert_eq!(event_log.events.len(), 4);
         assert_eq!(event_log.events[3].name, "install");
+
+        // phases are recorded under the first activity that started
+        event_log.add_event_phases(&[PhaseTiming {
+            phase: Phase::Download,
+            depth: 0,
+            duration: Duration::from_millis(1500),
+        }]);
+        assert_eq!(event_log.events.len(), 5);
+        assert_eq!(event_log.events[4].name, "current");
+        assert_eq!(
+            event_log.events[4].event,
+            EventKind::Phase {
+                phase: "download".to_string(),
+                depth: 0,
+                duration_ms: 1500,
+            }
+        );
+    }
+
+    #[test]
+    fn test_publish_filters_and_scrubs() {
+        let mut event_log = EventLog::new().expect("Could not create event log");
+        event_log.add_event_start(ActivityKind::Install);
+        event_log.add_event_start(ActivityKind::Use);
+        let error = io::Error::new(io::ErrorKind::Other, "/home/user/project").unknown();
+        event_log.add_event_error(ActivityKind::Install, &error);
+
+        let telemetry = TelemetryConfig {
+            enabled: true,
+            events: Some(vec!["install".to_string()]),
+            paths: PathsPolicy::Drop,
+        };
+        event_log.publish(&[], Some(&telemetry));
+        assert_eq!(event_log.events.len(), 2);
+        assert_eq!(event_log.events[0].name, "install");
+        match event_log.events[1].event {
+            EventKind::Error {
+                ref error, ref env, ..
+            } => {
+                assert_eq!(error, "");
+                assert_eq!(env.argv, "");
+                assert_eq!(env.path, "");
+            }
+            _ => panic!("expected an error event"),
+        }
+    }
+
+    #[test]
+    fn test_event_round_trip() {
+        let events = vec![
+            Event {
+                schema_version: SCHEMA_VERSION,
+                timestamp: 1539000000000,
+                name: "install".to_string(),
+                event: EventKind::End {
+                    exit_code: ExitCode::NetworkError,
+                },
+            },
+            Event {
+                schema_version: SCHEMA_VERSION,
+                timestamp: 1539000000001,
+                name: "install".to_string(),
+                event: EventKind::Error {
+                    exit_code: ExitCode::UnknownError,
+                    error: "oops".to_string(),
+                    env: ErrorEnv {
+                        argv: "notion install node".to_string(),
+                        exec_path: "/usr/bin/notion".to_string(),
+                        path: "/usr/bin".to_string(),
+                        platform: "Mac OS".to_string(),
+                        platform_version: "10.13.6".to_string(),
+                    },
+                },
+            },
+        ];
+
+        let json = serde_json::to_string(&events).unwrap();
+        let parsed: Vec<Event> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, events);
+    }
+
+    #[test]
+    fn test_event_wire_format() {
+        let json = r#"[
+            {"schema_version":1,"timestamp":1539000000000,"name":"use","event":"start"},
+            {"schema_version":1,"timestamp":1539000000002,"name":"node","event":{"toolend":{"exit_code":3}}}
+        ]"#;
+        let parsed: Vec<Event> = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed[0].event, EventKind::Start);
+        assert_eq!(parsed[1].event, EventKind::ToolEnd { exit_code: 3 });
+
+        let end = Event {
+            schema_version: SCHEMA_VERSION,
+            timestamp: 1539000000001,
+            name: "use".to_string(),
+            event: EventKind::End {
+                exit_code: ExitCode::Success,
+            },
+        };
+        assert_eq!(
+            serde_json::to_string(&end).unwrap(),
+            r#"{"schema_version":1,"timestamp":1539000000001,"name":"use","event":{"end":{"exit_code":"Success"}}}"#
+        );
+    }
+
+    #[test]
+    fn test_unversioned_event() {
+        let json = r#"{"timestamp":1539000000000,"name":"use","event":"start"}"#;
+        let parsed: Event = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed.schema_version, 0);
+    }
+
+    #[test]
+    fn test_payload() {
+        let mut event_log = EventLog::new().expect("Could not create event log");
+        event_log.add_event_start(ActivityKind::Install);
+        event_log.add_event_start(ActivityKind::Use);
+        let events: Vec<&Event> = event_log.events.iter().collect();
+
+        let lines = payload(&Delivery::File(PathBuf::from("events.jsonl")), &events).unwrap();
+        assert_eq!(lines.lines().count(), 2);
+        let first: Event = serde_json::from_str(lines.lines().next().unwrap()).unwrap();
+        assert_eq!(first.name, "install");
+
+        let array = payload(&Delivery::Command("log-events".to_string()), &events).unwrap();
+        let parsed: Vec<Event> = serde_json::from_str(&array).unwrap();
+        assert_eq!(parsed.len(), 2);
     }
 }
@@ -0,0 +1,201 @@
+//! Provides support for exporting a platform into a self-contained directory, which
+//! can be copied into a container image to run the same tools without Notion.
+//!
+//! An export has the following layout:
+//!
+//! ```text
+//! bin/                            the platform's executables
+//! versions/node/<version>/        the platform's Node version
+//! versions/yarn/<version>/        the platform's Yarn version (if any)
+//! ```
+//!
+//! The executables in `bin/` are relative symlinks into `versions/` on Unix, and
+//! `.cmd` scripts on Windows, so the directory can be copied anywhere and put on the
+//! `PATH` as it is.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use fs::copy_tree;
+use image::Image;
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+use path;
+use plan::{Action, Plan};
+
+/// Thrown when the export directory already has something in it.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Cannot export into {}, which is not empty", dir)]
+#[notion_fail(code = "FileSystemError")]
+pub(crate) struct ExportDirNotEmptyError {
+    dir: String,
+}
+
+/// What was exported.
+pub struct Exported {
+    /// The exported Node version.
+    pub node: String,
+    /// The exported Yarn version, if any.
+    pub yarn: Option<String>,
+    /// The names of the executables in `bin/`, in order.
+    pub bins: Vec<String>,
+}
+
+/// An installed version to copy into an export.
+struct Payload {
+    tool: &'static str,
+    version: String,
+    dir: PathBuf,
+    /// The directory of the version's executables, relative to `dir`.
+    bin: PathBuf,
+}
+
+impl Payload {
+    fn node(image: &Image) -> Fallible<Payload> {
+        let key = image.node_key();
+        Payload::new(
+            "node",
+            image.node_str.clone(),
+            path::node_version_dir(&key)?,
+            path::node_version_bin_dir(&key)?,
+        )
+    }
+
+    fn yarn(version: &str) -> Fallible<Payload> {
+        Payload::new(
+            "yarn",
+            version.to_string(),
+            path::yarn_version_dir(version)?,
+            path::yarn_version_bin_dir(version)?,
+        )
+    }
+
+    fn new(tool: &'static str, version: String, dir: PathBuf, bin: PathBuf) -> Fallible<Payload> {
+        let bin = bin.strip_prefix(&dir).unknown()?.to_path_buf();
+        Ok(Payload {
+            tool,
+            version,
+            dir,
+            bin,
+        })
+    }
+
+    /// The directory the version is copied to, relative to the export.
+    fn dest(&self) -> PathBuf {
+        Path::new("versions").join(self.tool).join(&self.version)
+    }
+}
+
+/// Exports a platform image, whose versions must be installed and unpacked, into
+/// `dest`, which must not exist yet or be empty.
+pub fn export(image: &Image, dest: &Path) -> Fallible<Exported> {
+    let mut payloads = vec![Payload::node(image)?];
+    if let Some(ref yarn) = image.yarn_str {
+        payloads.push(Payload::yarn(yarn)?);
+    }
+
+    Ok(Exported {
+        node: image.node_str.clone(),
+        yarn: image.yarn_str.clone(),
+        bins: export_payloads(&payloads, dest)?,
+    })
+}
+
+/// Plans exporting a platform image, without touching the filesystem.
+pub fn plan_export(dest: &Path) -> Plan {
+    let mut plan = Plan::new();
+    plan.push(Action::Write(dest.to_path_buf()));
+    plan
+}
+
+fn export_payloads(payloads: &[Payload], dest: &Path) -> Fallible<Vec<String>> {
+    if dest.is_dir() && fs::read_dir(dest).unknown()?.next().is_some() {
+        throw!(ExportDirNotEmptyError {
+            dir: dest.display().to_string(),
+        });
+    }
+
+    let bin_dir = dest.join("bin");
+    fs::create_dir_all(&bin_dir).unknown()?;
+
+    let mut bins = Vec::new();
+    for payload in payloads {
+        let version_dest = payload.dest();
+        copy_tree(&payload.dir, &dest.join(&version_dest))?;
+
+        let version_bin = payload.dir.join(&payload.bin);
+        if !version_bin.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&version_bin).unknown()? {
+            let name = entry.unknown()?.file_name();
+            let target = Path::new("..").join(&version_dest).join(&payload.bin).join(&name);
+            let name = link_bin(&bin_dir, &name.to_string_lossy(), &target)?;
+            bins.push(name);
+        }
+    }
+    bins.sort();
+    Ok(bins)
+}
+
+/// Links an executable into the export's `bin/`, where `target` is its path relative
+/// to `bin/`, producing the name it was linked under.
+#[cfg(unix)]
+fn link_bin(bin_dir: &Path, name: &str, target: &Path) -> Fallible<String> {
+    use std::os::unix;
+
+    unix::fs::symlink(target, bin_dir.join(name)).unknown()?;
+    Ok(name.to_string())
+}
+
+/// Writes a `.cmd` script into the export's `bin/` that runs an executable, where
+/// `target` is its path relative to `bin/`, producing the name of the script. Only
+/// executables Windows can run directly get scripts.
+#[cfg(windows)]
+fn link_bin(bin_dir: &Path, name: &str, target: &Path) -> Fallible<String> {
+    let target = target.to_string_lossy().replace('/', "\\");
+    let script = format!("@\"%~dp0{}\" %*\r\n", target);
+    let name = Path::new(name).with_extension("cmd");
+    fs::write(bin_dir.join(&name), script).unknown()?;
+    Ok(name.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use std::fs::File;
+    use tempfile::tempdir;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_export_payloads() {
+        let root = tempdir().expect("Could not create temporary directory");
+        let node_dir = root.path().join("installed/node/18.17.1");
+        fs::create_dir_all(node_dir.join("bin")).unwrap();
+        fs::create_dir_all(node_dir.join("lib")).unwrap();
+        File::create(node_dir.join("bin/node")).unwrap();
+        File::create(node_dir.join("lib/README.md")).unwrap();
+
+        let payload = Payload::new(
+            "node",
+            "18.17.1".to_string(),
+            node_dir.clone(),
+            node_dir.join("bin"),
+        ).unwrap();
+        let dest = root.path().join("export");
+        let bins = export_payloads(&[payload], &dest).unwrap();
+
+        assert_eq!(bins, vec!["node".to_string()]);
+        assert!(dest.join("versions/node/18.17.1/lib/README.md").is_file());
+        assert_eq!(
+            fs::read_link(dest.join("bin/node")).unwrap(),
+            PathBuf::from("../versions/node/18.17.1/bin/node")
+        );
+        assert!(dest.join("bin/node").is_file());
+
+        // exporting again into the same directory is refused
+        let payload = Payload::new("node", "18.17.1".to_string(), node_dir.clone(), node_dir)
+            .unwrap();
+        assert!(export_payloads(&[payload], &dest).is_err());
+    }
+}
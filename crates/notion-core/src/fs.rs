@@ -48,6 +48,58 @@ pub fn ensure_containing_dir_exists<P: AsRef<Path>>(path: &P) -> Fallible<()> {
     }
 }
 
+/// Collects the regular files under a directory, recursively. Symlinks are skipped
+/// rather than followed.
+pub(crate) fn collect_regular_files(dir: &Path, files: &mut Vec<PathBuf>) -> Fallible<()> {
+    for entry in fs::read_dir(dir).unknown()? {
+        let entry = entry.unknown()?;
+        let file_type = fs::symlink_metadata(entry.path()).unknown()?.file_type();
+        if file_type.is_dir() {
+            collect_regular_files(&entry.path(), files)?;
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// Copies a directory tree. Symlinks are copied as symlinks on Unix, so that the
+/// relative links in installed packages keep working, and as the files they point
+/// to elsewhere.
+pub(crate) fn copy_tree(src: &Path, dest: &Path) -> Fallible<()> {
+    fs::create_dir_all(dest)
+        .with_context(CreateDirError::for_dir(dest.to_string_lossy().to_string()))?;
+    for entry in fs::read_dir(src).unknown()? {
+        let entry = entry.unknown()?;
+        let target = dest.join(entry.file_name());
+        let file_type = fs::symlink_metadata(entry.path()).unknown()?.file_type();
+        if file_type.is_dir() {
+            copy_tree(&entry.path(), &target)?;
+        } else if file_type.is_symlink() {
+            copy_symlink(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target).unknown()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(src: &Path, dest: &Path) -> Fallible<()> {
+    use std::os::unix;
+
+    unix::fs::symlink(fs::read_link(src).unknown()?, dest).unknown()
+}
+
+#[cfg(windows)]
+fn copy_symlink(src: &Path, dest: &Path) -> Fallible<()> {
+    if src.is_dir() {
+        copy_tree(src, dest)
+    } else {
+        fs::copy(src, dest).map(|_| ()).unknown()
+    }
+}
+
 /// Reads a file, if it exists.
 pub fn read_file_opt(path: &PathBuf) -> io::Result<Option<String>> {
     let result: io::Result<String> = fs::read_to_string(path);
@@ -0,0 +1,168 @@
+//! Provides the handling that macOS needs for the versions Notion unpacks. Archives
+//! that were downloaded by a browser, or copied from a quarantined location, pass the
+//! `com.apple.quarantine` attribute on to the files unpacked from them, and Gatekeeper
+//! can then refuse to run or stall spawning those files the first time they are run.
+
+use std::path::Path;
+
+use notion_fail::Fallible;
+
+cfg_if! {
+    if #[cfg(target_os = "macos")] {
+        use std::fs::{self, File};
+        use std::io::Read;
+        use std::path::PathBuf;
+        use std::process::{Command, Stdio};
+
+        use config::LazyConfig;
+        use notion_fail::{ExitCode, NotionFail, ResultExt};
+
+        /// The extended attribute macOS marks downloaded files with.
+        const QUARANTINE: &'static str = "com.apple.quarantine";
+
+        /// Thrown when the quarantine attribute could not be cleared from a version.
+        #[derive(Debug, Fail, NotionFail)]
+        #[fail(display = "Could not clear the quarantine attribute from {}\n{}", dir, error)]
+        #[notion_fail(code = "FileSystemError")]
+        pub(crate) struct QuarantineError {
+            dir: String,
+            error: String,
+        }
+
+        /// Thrown when an executable's code signature is missing or invalid.
+        #[derive(Debug, Fail, NotionFail)]
+        #[fail(display = "Could not verify the code signature of {}\n{}", file, error)]
+        #[notion_fail(code = "FileSystemError")]
+        pub(crate) struct CodesignError {
+            file: String,
+            error: String,
+        }
+
+        /// Prepares an unpacked version to be run: clears the quarantine attribute from
+        /// its files, unless the `security.clear-quarantine` setting turns that off, and
+        /// verifies the code signatures of its native executables if the
+        /// `security.verify-codesign` setting is on.
+        pub(crate) fn prepare(dir: &Path) -> Fallible<()> {
+            let config = LazyConfig::new();
+            let config = config.get()?;
+
+            if config.clear_quarantine() {
+                clear_quarantine(dir)?;
+            }
+            if config.verify_codesign() {
+                for file in native_executables(&dir.join("bin"))? {
+                    verify_codesign(&file)?;
+                }
+            }
+            Ok(())
+        }
+
+        /// Removes the quarantine attribute from every file in a directory. Files that
+        /// don't have the attribute are left as they are.
+        fn clear_quarantine(dir: &Path) -> Fallible<()> {
+            let status = Command::new("xattr")
+                .arg("-d")
+                .arg("-r")
+                .arg(QUARANTINE)
+                .arg(dir)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+            match status {
+                Ok(_) => Ok(()),
+                Err(error) => throw!(QuarantineError {
+                    dir: dir.to_string_lossy().to_string(),
+                    error: error.to_string(),
+                }),
+            }
+        }
+
+        /// Checks the code signature of an executable with `codesign`.
+        fn verify_codesign(file: &Path) -> Fallible<()> {
+            let error = |error: String| CodesignError {
+                file: file.to_string_lossy().to_string(),
+                error,
+            };
+
+            let output = Command::new("codesign")
+                .args(&["--verify", "--strict"])
+                .arg(file)
+                .output()
+                .map_err(|e| error(e.to_string()))?;
+            if !output.status.success() {
+                throw!(error(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+            }
+            Ok(())
+        }
+
+        /// Finds the Mach-O executables in a version's `bin` directory, skipping the
+        /// symlinks and scripts (such as `npm`) that run through one of them.
+        fn native_executables(bin_dir: &Path) -> Fallible<Vec<PathBuf>> {
+            let mut executables = Vec::new();
+            if !bin_dir.is_dir() {
+                return Ok(executables);
+            }
+
+            for entry in fs::read_dir(bin_dir).unknown()? {
+                let file = entry.unknown()?.path();
+                if !fs::symlink_metadata(&file).unknown()?.is_file() {
+                    continue;
+                }
+                let mut magic = [0; 4];
+                let read = File::open(&file).and_then(|mut f| f.read_exact(&mut magic));
+                if read.is_ok() && is_mach_o(&magic) {
+                    executables.push(file);
+                }
+            }
+            executables.sort();
+            Ok(executables)
+        }
+
+        /// Tests whether a file starts with the magic number of a Mach-O binary, either
+        /// thin (32 or 64-bit, in either byte order) or universal.
+        fn is_mach_o(magic: &[u8; 4]) -> bool {
+            match magic {
+                &[0xfe, 0xed, 0xfa, 0xce]
+                | &[0xfe, 0xed, 0xfa, 0xcf]
+                | &[0xce, 0xfa, 0xed, 0xfe]
+                | &[0xcf, 0xfa, 0xed, 0xfe]
+                | &[0xca, 0xfe, 0xba, 0xbe] => true,
+                _ => false,
+            }
+        }
+    } else {
+        /// Prepares an unpacked version to be run, which needs nothing outside macOS.
+        pub(crate) fn prepare(_dir: &Path) -> Fallible<()> {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn test_native_executables() {
+        use super::*;
+        use std::io::Write;
+        use tempfile::tempdir;
+
+        let dir = tempdir().expect("Could not create temporary directory");
+        File::create(dir.path().join("node"))
+            .unwrap()
+            .write_all(&[0xcf, 0xfa, 0xed, 0xfe, 0x07, 0x00, 0x00, 0x01])
+            .unwrap();
+        File::create(dir.path().join("npm"))
+            .unwrap()
+            .write_all(b"#!/usr/bin/env node\n")
+            .unwrap();
+        File::create(dir.path().join("empty")).unwrap();
+
+        assert_eq!(
+            native_executables(dir.path()).unwrap(),
+            vec![dir.path().join("node")]
+        );
+        assert!(native_executables(&dir.path().join("missing")).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,121 @@
+//! Provides the container configuration generated by `notion generate`, which
+//! installs exactly the versions a project pins, so that containers built for the
+//! project run the same toolchain as Notion does locally.
+
+use std::collections::BTreeMap;
+
+use serde_json;
+
+use image::Image;
+use notion_fail::{Fallible, ResultExt};
+
+/// The devcontainer feature that installs Node.
+const NODE_FEATURE: &'static str = "ghcr.io/devcontainers/features/node:1";
+
+/// The Dockerfile instructions that install the Node version in `NODE_VERSION`.
+const INSTALL_NODE: [&'static str; 4] = [
+    "RUN ARCH=\"$(uname -m | sed -e 's/x86_64/x64/' -e 's/aarch64/arm64/')\" \\",
+    "    && BUILD=\"node-v${NODE_VERSION}-linux-${ARCH}\" \\",
+    "    && curl -fsSL \"https://nodejs.org/dist/v${NODE_VERSION}/${BUILD}.tar.gz\" \\",
+    "    | tar -xz -C /usr/local --strip-components=1 --no-same-owner",
+];
+
+/// Produces a Dockerfile fragment that installs the platform's Node version from the
+/// official builds, and its Yarn version with npm, on any Linux base image with `curl`.
+pub fn dockerfile(image: &Image) -> String {
+    let mut lines = vec![
+        "# Generated by `notion generate docker` from the project's toolchain.".to_string(),
+        format!("ENV NODE_VERSION={}", image.node),
+    ];
+    lines.extend(INSTALL_NODE.iter().map(|line| line.to_string()));
+    if let Some(ref yarn) = image.yarn {
+        lines.push(format!("ENV YARN_VERSION={}", yarn));
+        lines.push("RUN npm install --global \"yarn@${YARN_VERSION}\"".to_string());
+    }
+    lines.join("\n") + "\n"
+}
+
+/// The devcontainer options of the Node feature.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NodeFeature {
+    version: String,
+    install_yarn_using_apt: bool,
+}
+
+/// The part of a `devcontainer.json` that installs the toolchain.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DevContainer {
+    features: BTreeMap<&'static str, NodeFeature>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    post_create_command: Option<String>,
+}
+
+/// Produces the `devcontainer.json` settings that install the platform's Node version
+/// with the devcontainer Node feature, and its Yarn version with npm. The feature's
+/// own Yarn, which is whatever version apt has, is turned off.
+pub fn devcontainer(image: &Image) -> Fallible<String> {
+    let mut features = BTreeMap::new();
+    features.insert(
+        NODE_FEATURE,
+        NodeFeature {
+            version: image.node.to_string(),
+            install_yarn_using_apt: false,
+        },
+    );
+    let config = DevContainer {
+        features,
+        post_create_command: image
+            .yarn
+            .as_ref()
+            .map(|yarn| format!("npm install --global yarn@{}", yarn)),
+    };
+    serde_json::to_string_pretty(&config).unknown()
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use arch::Arch;
+    use semver::Version;
+
+    fn image(yarn: Option<&str>) -> Image {
+        Image {
+            node: Version::parse("18.17.1").unwrap(),
+            node_str: "18.17.1".to_string(),
+            node_arch: Arch::X64,
+            yarn: yarn.map(|yarn| Version::parse(yarn).unwrap()),
+            yarn_str: yarn.map(|yarn| yarn.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_dockerfile() {
+        let src = dockerfile(&image(Some("1.22.19")));
+        assert!(src.contains("ENV NODE_VERSION=18.17.1\n"));
+        assert!(src.contains("/dist/v${NODE_VERSION}/${BUILD}.tar.gz"));
+        assert!(src.ends_with("RUN npm install --global \"yarn@${YARN_VERSION}\"\n"));
+
+        let src = dockerfile(&image(None));
+        assert!(!src.contains("YARN_VERSION"));
+    }
+
+    #[test]
+    fn test_devcontainer() {
+        assert_eq!(
+            devcontainer(&image(Some("1.22.19"))).unwrap(),
+            r#"{
+  "features": {
+    "ghcr.io/devcontainers/features/node:1": {
+      "version": "18.17.1",
+      "installYarnUsingApt": false
+    }
+  },
+  "postCreateCommand": "npm install --global yarn@1.22.19"
+}"#
+        );
+        assert!(!devcontainer(&image(None)).unwrap().contains("postCreateCommand"));
+    }
+}
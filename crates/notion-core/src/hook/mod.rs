@@ -0,0 +1,273 @@
+//! Provides types for running hook scripts configured in a `hooks.toml` file
+//! in response to Notion events.
+
+use std::ffi::OsString;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use cmdline_words_parser::StrExt;
+use toml;
+
+use catalog::Installed;
+use logging;
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+use path::user_hooks_file;
+use semver::Version;
+
+pub(crate) mod serial;
+
+/// How long a pre-run hook may take, in milliseconds, unless its `timeout` says otherwise.
+const DEFAULT_PRE_RUN_TIMEOUT: u64 = 500;
+
+/// Thrown when the hooks file could not be parsed.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not parse hooks file {}: {}", file, error)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct HooksParseError {
+    file: String,
+    error: String,
+}
+
+/// Thrown when a hook's command is empty.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "The {} hook in {} has an empty command", event, file)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct EmptyHookError {
+    event: String,
+    file: String,
+}
+
+/// Thrown when a hook could not be started or exited unsuccessfully.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "The {} hook `{}` failed: {}", event, command, error)]
+#[notion_fail(code = "ExecutionFailure")]
+pub(crate) struct HookFailedError {
+    event: String,
+    command: String,
+    error: String,
+}
+
+/// A hook command.
+#[derive(Debug, PartialEq)]
+pub struct Hook {
+    /// The command line to run.
+    pub run: String,
+    /// How long the hook may run, in milliseconds. Only used for pre-run hooks.
+    pub timeout: Option<u64>,
+}
+
+/// The hooks configured in a `hooks.toml` file.
+#[derive(Debug, Default)]
+pub struct Hooks {
+    /// The hooks file, whose directory relative commands are resolved against.
+    file: PathBuf,
+    /// The hook run after a tool version is installed.
+    pub install: Option<Hook>,
+    /// The hook run before a shim dispatches to a tool.
+    pub pre_run: Option<Hook>,
+}
+
+impl Hooks {
+    /// Reads the user's hooks file, if there is one.
+    pub fn current() -> Fallible<Self> {
+        Hooks::from_file(&user_hooks_file()?)
+    }
+
+    /// Reads a hooks file, if it exists.
+    pub fn from_file(file: &Path) -> Fallible<Self> {
+        if !file.is_file() {
+            return Ok(Hooks::default());
+        }
+
+        let src = read_to_string(file).unknown()?;
+        Hooks::from_str(&src, file)
+    }
+
+    /// Parses the contents of a hooks file.
+    fn from_str(src: &str, file: &Path) -> Fallible<Self> {
+        let serial: serial::HooksFile = toml::from_str(src).with_context(|error| HooksParseError {
+            file: file.to_string_lossy().to_string(),
+            error: error.to_string(),
+        })?;
+        Ok(serial.into_hooks(file.to_path_buf()))
+    }
+
+    /// Runs the install hook, if any, for a newly installed tool version. The hook
+    /// receives the tool, version, and installation directory in the `NOTION_HOOK_TOOL`,
+    /// `NOTION_HOOK_VERSION`, and `NOTION_HOOK_PATH` environment variables.
+    pub fn run_install(&self, installed: &Installed) -> Fallible<()> {
+        if let Some(ref hook) = self.install {
+            let mut command = self.command("install", hook)?;
+            command
+                .env("NOTION_HOOK_EVENT", "install")
+                .env("NOTION_HOOK_TOOL", installed.tool)
+                .env("NOTION_HOOK_VERSION", installed.version.to_string())
+                .env("NOTION_HOOK_PATH", &installed.dir);
+            run("install", hook, command)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the pre-run hook, if any, before a shim runs a tool. The hook receives the
+    /// tool and its resolved version in the `NOTION_HOOK_TOOL` and `NOTION_HOOK_VERSION`
+    /// environment variables. A hook that fails stops the tool from running; a hook that
+    /// outlives its timeout is killed with a warning, and the tool runs anyway.
+    pub fn run_pre_run(&self, tool: &str, version: &Version) -> Fallible<()> {
+        if let Some(ref hook) = self.pre_run {
+            let mut command = self.command("pre-run", hook)?;
+            command
+                .env("NOTION_HOOK_EVENT", "pre-run")
+                .env("NOTION_HOOK_TOOL", tool)
+                .env("NOTION_HOOK_VERSION", version.to_string());
+            let timeout = hook.timeout.unwrap_or(DEFAULT_PRE_RUN_TIMEOUT);
+            run_with_timeout("pre-run", hook, command, timeout)?;
+        }
+        Ok(())
+    }
+
+    /// Builds the command for a hook. A program given as a relative path (starting with
+    /// `./` or `../`) is resolved against the directory of the hooks file.
+    fn command(&self, event: &str, hook: &Hook) -> Fallible<Command> {
+        let mut line = hook.run.trim().to_string();
+        let mut words = line.parse_cmdline_words();
+
+        let program = match words.next() {
+            Some(program) => program.to_string(),
+            None => throw!(EmptyHookError {
+                event: event.to_string(),
+                file: self.file.to_string_lossy().to_string(),
+            }),
+        };
+        let program = if program.starts_with("./") || program.starts_with("../") {
+            let base_dir = self.file.parent().unwrap_or(Path::new(""));
+            base_dir.join(program).into_os_string()
+        } else {
+            OsString::from(program)
+        };
+
+        let mut command = Command::new(program);
+        command.args(words);
+        Ok(command)
+    }
+}
+
+/// Runs a hook command to completion, with its output going to the terminal.
+fn run(event: &str, hook: &Hook, mut command: Command) -> Fallible<()> {
+    let failed = |error: String| HookFailedError {
+        event: event.to_string(),
+        command: hook.run.clone(),
+        error,
+    };
+
+    let status = command
+        .status()
+        .with_context(|error| failed(error.to_string()))?;
+    if !status.success() {
+        throw!(failed(match status.code() {
+            Some(code) => format!("exited with status {}", code),
+            None => "terminated by a signal".to_string(),
+        }));
+    }
+    Ok(())
+}
+
+/// Runs a hook command, killing it if it has not finished within `timeout` milliseconds.
+fn run_with_timeout(event: &str, hook: &Hook, mut command: Command, timeout: u64) -> Fallible<()> {
+    let failed = |error: String| HookFailedError {
+        event: event.to_string(),
+        command: hook.run.clone(),
+        error,
+    };
+
+    let mut child = command
+        .spawn()
+        .with_context(|error| failed(error.to_string()))?;
+    let started = Instant::now();
+
+    loop {
+        if let Some(status) = child
+            .try_wait()
+            .with_context(|error| failed(error.to_string()))?
+        {
+            if !status.success() {
+                throw!(failed(match status.code() {
+                    Some(code) => format!("exited with status {}", code),
+                    None => "terminated by a signal".to_string(),
+                }));
+            }
+            return Ok(());
+        }
+
+        if started.elapsed() >= Duration::from_millis(timeout) {
+            let _ = child.kill();
+            let _ = child.wait();
+            logging::warning(&format!(
+                "the {} hook `{}` did not finish within {}ms and was stopped",
+                event,
+                hook.run,
+                timeout
+            ));
+            return Ok(());
+        }
+
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{Hook, Hooks};
+    use std::path::Path;
+
+    #[test]
+    fn test_parse_hooks() {
+        let hooks = Hooks::from_str(
+            "[events.install]\nrun = \"./scripts/on-node-install.sh --quiet\"\n",
+            Path::new("/home/notion/hooks.toml"),
+        ).unwrap();
+        assert_eq!(
+            hooks.install,
+            Some(Hook {
+                run: "./scripts/on-node-install.sh --quiet".to_string(),
+                timeout: None,
+            })
+        );
+
+        let empty = Hooks::from_str("", Path::new("/home/notion/hooks.toml")).unwrap();
+        assert_eq!(empty.install, None);
+        assert_eq!(empty.pre_run, None);
+    }
+
+    #[test]
+    fn test_parse_pre_run_hook() {
+        let hooks = Hooks::from_str(
+            "[events.pre-run]\nrun = \"./scripts/check-node.sh\"\ntimeout = 200\n",
+            Path::new("/home/project/hooks.toml"),
+        ).unwrap();
+        assert_eq!(
+            hooks.pre_run,
+            Some(Hook {
+                run: "./scripts/check-node.sh".to_string(),
+                timeout: Some(200),
+            })
+        );
+        assert_eq!(hooks.install, None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_relative_hook_command() {
+        let hooks = Hooks::from_str(
+            "[events.install]\nrun = \"./on-install.sh\"\n",
+            Path::new("/home/notion/hooks.toml"),
+        ).unwrap();
+        let command = hooks
+            .command("install", hooks.install.as_ref().unwrap())
+            .unwrap();
+        assert!(format!("{:?}", command).contains("/home/notion/./on-install.sh"));
+    }
+}
@@ -0,0 +1,41 @@
+use std::path::PathBuf;
+
+use super::{Hook, Hooks};
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct HooksFile {
+    #[serde(default)]
+    pub events: Events,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Events {
+    pub install: Option<HookEntry>,
+    #[serde(rename = "pre-run")]
+    pub pre_run: Option<HookEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HookEntry {
+    pub run: String,
+    pub timeout: Option<u64>,
+}
+
+impl HookEntry {
+    fn into_hook(self) -> Hook {
+        Hook {
+            run: self.run,
+            timeout: self.timeout,
+        }
+    }
+}
+
+impl HooksFile {
+    pub fn into_hooks(self, file: PathBuf) -> Hooks {
+        Hooks {
+            file,
+            install: self.events.install.map(HookEntry::into_hook),
+            pre_run: self.events.pre_run.map(HookEntry::into_hook),
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! Provides the cache of resolved project platforms, so that shims don't have to
+//! resolve a project's version requirements against the catalog every time they run.
+//!
+//! Each project has a small binary entry, keyed by the sizes and modification times of
+//! the project's `package.json`, `notion.toml` and `notion.lock`, the user configuration
+//! file and the trusted projects file, and by the catalog's generation. Changing any of
+//! them invalidates the entry, so an entry can be used without checking the project's
+//! trust again (see `prompt`).
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use semver::Version;
+
+use arch::Arch;
+use catalog;
+use fs::ensure_containing_dir_exists;
+use image::Image;
+use lockfile::LOCK_FILE;
+use notion_fail::{Fallible, ResultExt};
+use path;
+use toolchain::TOOLCHAIN_FILE;
+
+/// The start of every entry, which changes whenever the format does.
+const MAGIC: &'static [u8] = b"notion-platform\x01";
+
+/// The state of the files a project's platform is resolved from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fingerprint(Vec<u64>);
+
+impl Fingerprint {
+    /// Takes the fingerprint of the files the platform of the project rooted at
+    /// `root` is resolved from.
+    pub fn of(root: &Path) -> Fallible<Fingerprint> {
+        let files = [
+            root.join("package.json"),
+            root.join(TOOLCHAIN_FILE),
+            root.join(LOCK_FILE),
+            path::user_config_file()?,
+            path::trusted_projects_file()?,
+        ];
+        let mut stamps = vec![catalog::generation()?];
+        for file in files.iter() {
+            stamps.extend_from_slice(&stamp(file));
+        }
+        Ok(Fingerprint(stamps))
+    }
+}
+
+/// Produces the modification time and size of a file. Files that don't exist are
+/// all zeroes, which an existing file never is, since its size is offset by one.
+pub(crate) fn stamp(file: &Path) -> [u64; 3] {
+    match fs::metadata(file) {
+        Ok(metadata) => {
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .unwrap_or_default();
+            [
+                modified.as_secs(),
+                modified.subsec_nanos() as u64,
+                metadata.len() + 1,
+            ]
+        }
+        Err(_) => [0, 0, 0],
+    }
+}
+
+/// Loads the cached platform of the project rooted at `root`, if there is one and the
+/// files it was resolved from haven't changed since.
+pub fn load(root: &Path, fingerprint: &Fingerprint) -> Option<Image> {
+    let bytes = fs::read(path::platform_cache_file(&path::project_key(root)).ok()?).ok()?;
+    let image = decode(&bytes, root, fingerprint)?;
+    if image.node_arch == Arch::native() {
+        Some(image)
+    } else {
+        None
+    }
+}
+
+/// Caches the resolved platform of the project rooted at `root`. Caching is best
+/// effort, since the platform can always be resolved again.
+pub fn store(root: &Path, fingerprint: &Fingerprint, image: &Image) {
+    let _ = write(root, fingerprint, image);
+}
+
+fn write(root: &Path, fingerprint: &Fingerprint, image: &Image) -> Fallible<()> {
+    let file = path::platform_cache_file(&path::project_key(root))?;
+    ensure_containing_dir_exists(&file)?;
+    let mut file = File::create(&file).unknown()?;
+    file.write_all(&encode(root, fingerprint, image)).unknown()
+}
+
+fn encode(root: &Path, fingerprint: &Fingerprint, image: &Image) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    push_str(&mut bytes, &root.to_string_lossy());
+    push_u64(&mut bytes, fingerprint.0.len() as u64);
+    for stamp in &fingerprint.0 {
+        push_u64(&mut bytes, *stamp);
+    }
+    push_str(&mut bytes, &image.node_str);
+    push_str(&mut bytes, &image.node_arch.to_string());
+    match image.yarn_str {
+        Some(ref yarn) => {
+            bytes.push(1);
+            push_str(&mut bytes, yarn);
+        }
+        None => bytes.push(0),
+    }
+    bytes
+}
+
+fn push_u64(bytes: &mut Vec<u8>, value: u64) {
+    for i in 0..8 {
+        bytes.push((value >> (i * 8)) as u8);
+    }
+}
+
+fn push_str(bytes: &mut Vec<u8>, value: &str) {
+    push_u64(bytes, value.len() as u64);
+    bytes.extend_from_slice(value.as_bytes());
+}
+
+/// Decodes an entry, producing `None` if it is malformed or stale.
+fn decode(bytes: &[u8], root: &Path, fingerprint: &Fingerprint) -> Option<Image> {
+    let mut reader = Reader(bytes);
+    if reader.take(MAGIC.len())? != MAGIC || reader.string()? != root.to_string_lossy() {
+        return None;
+    }
+
+    let len = reader.u64()? as usize;
+    if len != fingerprint.0.len() {
+        return None;
+    }
+    for stamp in &fingerprint.0 {
+        if reader.u64()? != *stamp {
+            return None;
+        }
+    }
+
+    let node_str = reader.string()?;
+    let node_arch: Arch = reader.string()?.parse().ok()?;
+    let yarn_str = match reader.take(1)?[0] {
+        0 => None,
+        1 => Some(reader.string()?),
+        _ => return None,
+    };
+    if !reader.0.is_empty() {
+        return None;
+    }
+
+    let yarn = match yarn_str {
+        Some(ref yarn) => Some(Version::parse(yarn).ok()?),
+        None => None,
+    };
+    Some(Image {
+        node: Version::parse(&node_str).ok()?,
+        node_str,
+        node_arch,
+        yarn,
+        yarn_str,
+    })
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(taken)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        let bytes = self.take(8)?;
+        Some(
+            bytes
+                .iter()
+                .enumerate()
+                .fold(0, |value, (i, byte)| value | ((*byte as u64) << (i * 8))),
+        )
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.u64()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use std::path::PathBuf;
+
+    fn image() -> Image {
+        Image {
+            node: Version::parse("18.17.1").unwrap(),
+            node_str: "18.17.1".to_string(),
+            node_arch: Arch::X64,
+            yarn: Some(Version::parse("1.22.19").unwrap()),
+            yarn_str: Some("1.22.19".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let root = PathBuf::from("/projects/app");
+        let fingerprint = Fingerprint(vec![7, 1_539_600_000, 5, 812, 0, 0, 0]);
+        let bytes = encode(&root, &fingerprint, &image());
+
+        let decoded = decode(&bytes, &root, &fingerprint).unwrap();
+        assert_eq!(decoded.node, image().node);
+        assert_eq!(decoded.node_arch, Arch::X64);
+        assert_eq!(decoded.yarn_str, Some("1.22.19".to_string()));
+    }
+
+    #[test]
+    fn test_stale() {
+        let root = PathBuf::from("/projects/app");
+        let fingerprint = Fingerprint(vec![7, 1_539_600_000, 5, 812, 0, 0, 0]);
+        let bytes = encode(&root, &fingerprint, &image());
+
+        let bumped = Fingerprint(vec![8, 1_539_600_000, 5, 812, 0, 0, 0]);
+        assert!(decode(&bytes, &root, &bumped).is_none());
+        assert!(decode(&bytes, &PathBuf::from("/projects/other"), &fingerprint).is_none());
+        assert!(decode(&bytes[..bytes.len() - 1], &root, &fingerprint).is_none());
+    }
+}
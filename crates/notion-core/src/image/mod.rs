@@ -1,18 +1,23 @@
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
 use std::path::PathBuf;
 
 use envoy;
 use semver::Version;
 
+use arch::Arch;
 use notion_fail::{Fallible, ResultExt};
 use path;
 
+pub mod cache;
+
 /// A platform image.
 pub struct Image {
     /// The pinned version of Node, under the `toolchain.node` key.
     pub node: Version,
     /// The pinned version of Node as a string.
     pub node_str: String,
+    /// The architecture of the pinned Node build.
+    pub node_arch: Arch,
     /// The pinned version of Yarn, under the `toolchain.yarn` key.
     pub yarn: Option<Version>,
     /// The pinned version of Yarn as a string.
@@ -20,8 +25,14 @@ pub struct Image {
 }
 
 impl Image {
+    /// Produces the name the pinned Node build is installed under, which accounts
+    /// for its architecture.
+    pub fn node_key(&self) -> String {
+        path::node_version_key(&self.node_str, self.node_arch)
+    }
+
     pub fn bins(&self) -> Fallible<Vec<PathBuf>> {
-        let mut bins = vec![path::node_version_bin_dir(&self.node_str)?];
+        let mut bins = vec![path::node_version_bin_dir(&self.node_key())?];
         if let Some(ref yarn_str) = self.yarn_str {
             bins.push(path::yarn_version_bin_dir(yarn_str)?);
         }
@@ -43,6 +54,26 @@ impl Image {
 
         Ok(new_path)
     }
+
+    /// Produces the environment variables that reproduce the environment shims run
+    /// tools from this image in: the modified `PATH`, the path of the Node executable,
+    /// and the selected tool versions.
+    pub fn env_vars(&self) -> Fallible<Vec<(String, String)>> {
+        let path = self.path()?;
+        let node_bin_dir = path::node_version_bin_dir(&self.node_key())?;
+        let node = path::find_executable(OsStr::new("node"), node_bin_dir.as_os_str())
+            .unwrap_or_else(|| node_bin_dir.join("node"));
+
+        let mut vars = vec![
+            ("PATH".to_string(), path.to_string_lossy().into_owned()),
+            ("NODE".to_string(), node.to_string_lossy().into_owned()),
+            ("NOTION_NODE_VERSION".to_string(), self.node_str.clone()),
+        ];
+        if let Some(ref yarn_str) = self.yarn_str {
+            vars.push(("NOTION_YARN_VERSION".to_string(), yarn_str.clone()));
+        }
+        Ok(vars)
+    }
 }
 
 /// A lightweight namespace type representing the system environment, i.e. the environment
@@ -51,6 +82,12 @@ pub struct System;
 
 impl System {
 
+    /// Finds the executable with the given name that would be run in the system
+    /// environment, if any.
+    pub fn which(exe: &OsStr) -> Fallible<Option<PathBuf>> {
+        Ok(path::find_executable(exe, &System::path()?))
+    }
+
     /// Produces a modified version of the current `PATH` environment variable that
     /// removes the Notion shims and binaries, to use for running system node and
     /// executables.
@@ -72,6 +109,7 @@ impl System {
 mod test {
 
     use super::*;
+    use arch::Arch;
     use std;
     use std::path::PathBuf;
     use semver::Version;
@@ -130,6 +168,7 @@ mod test {
         let no_yarn_image = Image {
             node: v123.clone(),
             node_str: v123.to_string(),
+            node_arch: Arch::native(),
             yarn: None,
             yarn_str: None
         };
@@ -142,6 +181,7 @@ mod test {
         let with_yarn_image = Image {
             node: v123.clone(),
             node_str: v123.to_string(),
+            node_arch: Arch::native(),
             yarn: Some(v457.clone()),
             yarn_str: Some(v457.to_string())
         };
@@ -186,6 +226,7 @@ mod test {
         let no_yarn_image = Image {
             node: v123.clone(),
             node_str: v123.to_string(),
+            node_arch: Arch::native(),
             yarn: None,
             yarn_str: None
         };
@@ -198,6 +239,7 @@ mod test {
         let with_yarn_image = Image {
             node: v123.clone(),
             node_str: v123.to_string(),
+            node_arch: Arch::native(),
             yarn: Some(v457.clone()),
             yarn_str: Some(v457.to_string())
         };
@@ -1,11 +1,15 @@
-//! The main implementation crate for the core of Notion.
+//! The main implementation crate for the core of Notion. Tools that resolve toolchains
+//! should use the `api` module, which is the only part of this crate with a stable API.
 
 #![cfg_attr(feature = "universal-docs", feature(doc_cfg))]
 
+extern crate base64;
 extern crate cmdline_words_parser;
 extern crate console;
 extern crate detect_indent;
 extern crate envoy;
+extern crate flate2;
+extern crate fs2;
 extern crate indicatif;
 extern crate lazycell;
 #[cfg(feature = "mock-network")]
@@ -15,6 +19,8 @@ extern crate readext;
 extern crate reqwest;
 extern crate semver;
 extern crate serde_json;
+extern crate sha2;
+extern crate tar;
 extern crate tempfile;
 extern crate term_size;
 extern crate toml;
@@ -23,26 +29,71 @@ extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 
+#[cfg(windows)]
+extern crate winapi;
 extern crate winfolder;
 
+pub mod api;
+pub mod arch;
+pub mod autoshim;
+pub mod backup;
+pub mod bench;
+pub mod bundle;
 pub mod catalog;
+pub mod checksum;
+pub mod ci;
+pub mod completions;
+pub mod compress;
 pub mod config;
+pub mod daemon;
+pub mod dedupe;
 mod distro;
 pub mod env;
 mod event;
+pub mod export;
+mod gatekeeper;
+pub mod generate;
+pub mod hook;
+pub mod http;
 pub(crate) mod fs;
 pub mod image;
+pub mod locale;
+pub mod lock;
+pub mod lockfile;
+pub mod logging;
 pub mod manifest;
+pub mod matrix;
 pub mod monitor;
+pub mod netrc;
+pub mod outdated;
+pub mod package;
 pub mod path;
+pub mod plan;
 mod plugin;
 pub mod project;
+pub mod prompt;
+pub mod provenance;
+pub mod proxy;
+pub mod registry;
+pub mod resolve;
+pub mod retry;
+pub mod sandbox;
+pub mod sbom;
+pub mod search;
+pub mod setup;
 pub mod session;
 pub mod shell;
 pub mod shim;
+pub mod store;
 pub mod style;
+pub mod timing;
 pub mod tool;
+pub mod toolchain;
+pub mod trust;
+pub mod usage;
+pub mod verify;
 pub mod version;
+pub mod webhook;
 
 extern crate failure;
 #[macro_use]
@@ -0,0 +1,206 @@
+//! Provides the message catalog for user-facing text, so that Notion's messages can
+//! be displayed in the user's language.
+//!
+//! Only the text displayed to the user goes through the catalog. Exit codes, error
+//! kinds, event fields, and JSON output are the same in every locale, so that scripts
+//! don't depend on the user's language.
+
+use std::env;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A language messages can be displayed in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Locale {
+    English,
+    German,
+    Spanish,
+    French,
+}
+
+/// A user-facing message in the catalog.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Message {
+    /// The prefix of errors reported by `notion`.
+    ErrorPrefix,
+    /// The prefix of errors reported by a shim.
+    ShimErrorPrefix,
+    /// The prefix of warnings.
+    WarningPrefix,
+    /// Reported in place of an internal error.
+    InternalError,
+    /// The heading of the details of an internal error.
+    Details,
+    /// How to get a backtrace of an internal error.
+    BacktraceHint,
+    /// Asks for internal errors to be reported, before the bug tracker's address.
+    /// `{}` stands for Notion's Twitter handle.
+    ReportBug,
+    /// The progress action for downloading a tool.
+    Fetching,
+}
+
+const UNSET: usize = 0;
+
+/// The locale selected for the process, as `Locale as usize + 1`, or `UNSET`.
+static SELECTED: AtomicUsize = AtomicUsize::new(UNSET);
+
+const LOCALES: [Locale; 4] = [
+    Locale::English,
+    Locale::German,
+    Locale::Spanish,
+    Locale::French,
+];
+
+impl Locale {
+    /// Parses a locale name like `es`, `fr_FR.UTF-8`, or `de-AT`, producing `None`
+    /// for languages without a catalog.
+    pub fn parse(src: &str) -> Option<Locale> {
+        let language = src
+            .split(|c| c == '_' || c == '-' || c == '.' || c == '@')
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        match &language[..] {
+            "en" | "c" | "posix" => Some(Locale::English),
+            "de" => Some(Locale::German),
+            "es" => Some(Locale::Spanish),
+            "fr" => Some(Locale::French),
+            _ => None,
+        }
+    }
+
+    /// Returns the locale messages are displayed in: the one selected with `select`,
+    /// or else the first of `NOTION_LOCALE`, `LC_ALL`, `LC_MESSAGES`, and `LANG` that
+    /// names a language with a catalog, or else English.
+    pub fn current() -> Locale {
+        match SELECTED.load(Ordering::Relaxed) {
+            UNSET => Locale::from_env(),
+            index => LOCALES[index - 1],
+        }
+    }
+
+    fn from_env() -> Locale {
+        ["NOTION_LOCALE", "LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .filter_map(|value| Locale::parse(&value))
+            .next()
+            .unwrap_or(Locale::English)
+    }
+
+    /// Selects the locale for the rest of the process, as configured by the `locale`
+    /// setting. `NOTION_LOCALE` still takes precedence.
+    pub fn select(locale: Locale) {
+        if env::var("NOTION_LOCALE").ok().and_then(|value| Locale::parse(&value)).is_some() {
+            return;
+        }
+        let index = LOCALES.iter().position(|l| *l == locale).unwrap();
+        SELECTED.store(index + 1, Ordering::Relaxed);
+    }
+
+    /// Produces a message in this locale, falling back to English for messages that
+    /// haven't been translated.
+    pub fn message(&self, message: Message) -> &'static str {
+        let translated = match self {
+            &Locale::English => None,
+            &Locale::German => german(message),
+            &Locale::Spanish => spanish(message),
+            &Locale::French => french(message),
+        };
+        translated.unwrap_or_else(|| english(message))
+    }
+}
+
+/// Produces a message in the current locale.
+pub fn message(message: Message) -> &'static str {
+    Locale::current().message(message)
+}
+
+fn english(message: Message) -> &'static str {
+    match message {
+        Message::ErrorPrefix => "error:",
+        Message::ShimErrorPrefix => "Notion error:",
+        Message::WarningPrefix => "Warning:",
+        Message::InternalError => "an internal error occurred",
+        Message::Details => "details:",
+        Message::BacktraceHint => "Run with NOTION_DEV=1 and RUST_BACKTRACE=1 for a backtrace.",
+        Message::ReportBug => {
+            "Notion is still a pre-alpha project, so we expect to run into some bugs,\n\
+             but we'd love to hear about them so we can fix them!\n\
+             \n\
+             Please feel free to reach out to us at {} on Twitter or file an issue at:"
+        }
+        Message::Fetching => "Fetching",
+    }
+}
+
+fn german(message: Message) -> Option<&'static str> {
+    Some(match message {
+        Message::ErrorPrefix => "Fehler:",
+        Message::ShimErrorPrefix => "Notion-Fehler:",
+        Message::WarningPrefix => "Warnung:",
+        Message::InternalError => "ein interner Fehler ist aufgetreten",
+        Message::Details => "Details:",
+        Message::BacktraceHint => {
+            "Mit NOTION_DEV=1 und RUST_BACKTRACE=1 ausführen, um einen Backtrace zu erhalten."
+        }
+        Message::Fetching => "Lade",
+        Message::ReportBug => return None,
+    })
+}
+
+fn spanish(message: Message) -> Option<&'static str> {
+    Some(match message {
+        Message::ErrorPrefix => "error:",
+        Message::ShimErrorPrefix => "error de Notion:",
+        Message::WarningPrefix => "Advertencia:",
+        Message::InternalError => "se produjo un error interno",
+        Message::Details => "detalles:",
+        Message::BacktraceHint => {
+            "Ejecute con NOTION_DEV=1 y RUST_BACKTRACE=1 para obtener un backtrace."
+        }
+        Message::Fetching => "Descargando",
+        Message::ReportBug => return None,
+    })
+}
+
+fn french(message: Message) -> Option<&'static str> {
+    Some(match message {
+        Message::ErrorPrefix => "erreur :",
+        Message::ShimErrorPrefix => "erreur Notion :",
+        Message::WarningPrefix => "Avertissement :",
+        Message::InternalError => "une erreur interne s'est produite",
+        Message::Details => "détails :",
+        Message::BacktraceHint => {
+            "Relancez avec NOTION_DEV=1 et RUST_BACKTRACE=1 pour obtenir un backtrace."
+        }
+        Message::Fetching => "Téléchargement",
+        Message::ReportBug => return None,
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Locale::parse("es"), Some(Locale::Spanish));
+        assert_eq!(Locale::parse("fr_FR.UTF-8"), Some(Locale::French));
+        assert_eq!(Locale::parse("de-AT"), Some(Locale::German));
+        assert_eq!(Locale::parse("C"), Some(Locale::English));
+        assert_eq!(Locale::parse("ja_JP.UTF-8"), None);
+        assert_eq!(Locale::parse(""), None);
+    }
+
+    #[test]
+    fn test_message() {
+        assert_eq!(Locale::French.message(Message::Details), "détails :");
+        // untranslated messages fall back to English
+        assert_eq!(
+            Locale::German.message(Message::ReportBug),
+            Locale::English.message(Message::ReportBug)
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! Provides advisory file locks for serializing mutations of shared Notion state
+//! (such as the catalog) across concurrently running Notion processes.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use fs2::{lock_contended_error, FileExt};
+
+use fs::ensure_containing_dir_exists;
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+
+/// The number of seconds to wait for another Notion process to release a lock.
+const LOCK_TIMEOUT_SECS: u64 = 30;
+
+/// The number of milliseconds to wait between attempts to acquire a lock.
+const LOCK_POLL_MILLIS: u64 = 50;
+
+/// Thrown when a lock could not be acquired before the timeout elapsed.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "another notion process holds the lock on {} (waited {} seconds)", path, seconds)]
+#[notion_fail(code = "FileSystemError")]
+pub(crate) struct LockTimeoutError {
+    path: String,
+    seconds: u64,
+}
+
+/// An advisory lock on a file, which is released when dropped.
+pub struct FileLock {
+    file: File,
+}
+
+fn is_contended(error: &io::Error) -> bool {
+    error.raw_os_error() == lock_contended_error().raw_os_error()
+}
+
+fn open_lock_file(path: &Path) -> Fallible<File> {
+    ensure_containing_dir_exists(&path)?;
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(path)
+        .unknown()
+}
+
+/// Repeatedly attempts to lock a file, giving up after `timeout_secs` seconds.
+fn acquire<F>(path: &Path, timeout_secs: u64, try_lock: F) -> Fallible<FileLock>
+where
+    F: Fn(&File) -> io::Result<()>,
+{
+    let file = open_lock_file(path)?;
+    let deadline = Instant::now() + Duration::from_secs(timeout_secs);
+
+    loop {
+        match try_lock(&file) {
+            Ok(()) => return Ok(FileLock { file }),
+            Err(ref error) if is_contended(error) => {
+                if Instant::now() >= deadline {
+                    throw!(LockTimeoutError {
+                        path: path.to_string_lossy().to_string(),
+                        seconds: timeout_secs,
+                    });
+                }
+                thread::sleep(Duration::from_millis(LOCK_POLL_MILLIS));
+            }
+            Err(error) => return Err(error).unknown(),
+        }
+    }
+}
+
+impl FileLock {
+    /// Acquires an exclusive lock on the specified file, waiting a bounded amount
+    /// of time for any other process holding a lock on it to release it.
+    pub fn exclusive(path: &Path) -> Fallible<FileLock> {
+        acquire(path, LOCK_TIMEOUT_SECS, |file| file.try_lock_exclusive())
+    }
+
+    /// Acquires an exclusive lock on the specified file, waiting up to `timeout_secs`
+    /// seconds for any other process holding a lock on it to release it. This is for
+    /// locks that are held for long-running work, such as downloading a tool version.
+    pub fn exclusive_with_timeout(path: &Path, timeout_secs: u64) -> Fallible<FileLock> {
+        acquire(path, timeout_secs, |file| file.try_lock_exclusive())
+    }
+
+    /// Acquires a shared lock on the specified file, waiting a bounded amount
+    /// of time for any other process holding an exclusive lock on it to release it.
+    pub fn shared(path: &Path) -> Fallible<FileLock> {
+        acquire(path, LOCK_TIMEOUT_SECS, |file| file.try_lock_shared())
+    }
+
+    /// Attempts to acquire an exclusive lock on the specified file without waiting,
+    /// returning `None` if another process currently holds a lock on it.
+    pub fn try_exclusive(path: &Path) -> Fallible<Option<FileLock>> {
+        let file = open_lock_file(path)?;
+        match file.try_lock_exclusive() {
+            Ok(()) => Ok(Some(FileLock { file })),
+            Err(ref error) if is_contended(error) => Ok(None),
+            Err(error) => Err(error).unknown(),
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::FileLock;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_exclusive_lock_blocks_other_handles() {
+        let dir = tempdir().expect("Could not create temporary directory");
+        let path = dir.path().join("test.lock");
+
+        let lock = FileLock::exclusive(&path).expect("Could not acquire lock");
+        assert!(FileLock::try_exclusive(&path).unwrap().is_none());
+
+        drop(lock);
+        assert!(FileLock::try_exclusive(&path).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_exclusive_with_timeout_gives_up() {
+        let dir = tempdir().expect("Could not create temporary directory");
+        let path = dir.path().join("test.lock");
+
+        let lock = FileLock::exclusive(&path).expect("Could not acquire lock");
+        assert!(FileLock::exclusive_with_timeout(&path, 0).is_err());
+
+        drop(lock);
+        assert!(FileLock::exclusive_with_timeout(&path, 0).is_ok());
+    }
+}
@@ -0,0 +1,482 @@
+//! Provides the `Lockfile` type, which represents a project's toolchain lockfile
+//! (`notion.lock`). It records the exact versions the project's pins resolved to,
+//! with the URL and SHA-256 checksum of the archive each version is installed from,
+//! so that every developer and CI job working on the project provisions
+//! byte-identical toolchains. `notion lock` writes it next to the project's
+//! `package.json`, to be committed along with it.
+//!
+//! Node archives differ from platform to platform, so they are recorded for each
+//! platform (like `linux-x64`) the project was locked on: running `notion lock` on
+//! another platform adds that platform's archive, as long as the versions are the
+//! same.
+//!
+//! `notion verify --locked` checks that the toolchain in use still matches the
+//! lockfile (see `deviations`), for CI jobs that must run with the locked toolchain.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{read_to_string, File};
+use std::io::Write;
+use std::path::Path;
+
+use toml;
+
+use arch::{Arch, Libc};
+use catalog::Catalog;
+use checksum::Checksum;
+use image::Image;
+use manifest::Toolchain;
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+use path;
+use provenance::Provenance;
+use semver::Version;
+
+pub(crate) mod serial;
+
+/// The name of the lockfile.
+pub const LOCK_FILE: &'static str = "notion.lock";
+
+/// The comment the lockfile starts with.
+const HEADER: &'static str =
+    "# This file is written by `notion lock`. Commit it, and don't edit it by hand.\n\n";
+
+/// Thrown when a lockfile could not be parsed.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not parse lockfile {}: {}", file, error)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct LockfileParseError {
+    file: String,
+    error: String,
+}
+
+/// Thrown when a version is locked without a record of the archive it was installed
+/// from, as for versions installed before Notion recorded provenance.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "The archive {} v{} was installed from is unknown
+Run `notion repair {}@{}` to reinstall it, then lock the project again",
+       tool, version, tool, version)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct UnknownArchiveError {
+    tool: String,
+    version: String,
+}
+
+/// Thrown when a version is locked that was installed from a local archive, which
+/// can't be downloaded anywhere else.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} v{} was installed from a local archive, which can't be locked
+Install it from a URL, then lock the project again", tool, version)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct LocalArchiveError {
+    tool: String,
+    version: String,
+}
+
+/// An archive a locked version is installed from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Archive {
+    /// The URL the archive is downloaded from.
+    pub url: String,
+    /// The checksum the archive is verified against.
+    pub checksum: Checksum,
+}
+
+impl Archive {
+    /// Produces the archive a version was installed from, according to its provenance.
+    pub(crate) fn installed(
+        tool: &str,
+        version: &Version,
+        provenance: Option<&Provenance>,
+    ) -> Fallible<Archive> {
+        let provenance = match provenance {
+            Some(provenance) => provenance,
+            None => throw!(UnknownArchiveError {
+                tool: tool.to_string(),
+                version: version.to_string(),
+            }),
+        };
+        if provenance.url.starts_with("file://") {
+            throw!(LocalArchiveError {
+                tool: tool.to_string(),
+                version: version.to_string(),
+            });
+        }
+        Ok(Archive {
+            url: provenance.url.clone(),
+            checksum: format!("sha256:{}", provenance.sha256).parse()?,
+        })
+    }
+}
+
+/// The locked Node version, with its archive for each platform it was locked on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockedNode {
+    pub version: Version,
+    /// The archives, by platform (see `platform`).
+    pub archives: BTreeMap<String, Archive>,
+}
+
+/// The locked Yarn version, whose archive is the same on every platform.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockedYarn {
+    pub version: Version,
+    pub archive: Archive,
+}
+
+/// A project's toolchain lockfile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lockfile {
+    pub node: LockedNode,
+    pub yarn: Option<LockedYarn>,
+}
+
+impl Lockfile {
+    /// Returns the lockfile of the project rooted at `root`, if it has one.
+    pub fn for_project(root: &Path) -> Fallible<Option<Lockfile>> {
+        let file = root.join(LOCK_FILE);
+        if !file.is_file() {
+            return Ok(None);
+        }
+
+        let src = read_to_string(&file).unknown()?;
+        let serial: serial::Lockfile =
+            toml::from_str(&src).with_context(|error: &toml::de::Error| LockfileParseError {
+                file: file.to_string_lossy().to_string(),
+                error: error.to_string(),
+            })?;
+        Ok(Some(serial.into_lockfile()?))
+    }
+
+    /// Writes the lockfile of the project rooted at `root`.
+    pub fn write(&self, root: &Path) -> Fallible<()> {
+        let src = toml::to_string(&serial::Lockfile::from_lockfile(self)).unknown()?;
+        let mut file = File::create(root.join(LOCK_FILE)).unknown()?;
+        file.write_all(HEADER.as_bytes()).unknown()?;
+        file.write_all(src.as_bytes()).unknown()
+    }
+
+    /// Produces the platform image of the locked versions.
+    pub fn image(&self) -> Image {
+        Image {
+            node: self.node.version.clone(),
+            node_str: self.node.version.to_string(),
+            node_arch: Arch::native(),
+            yarn: self.yarn.as_ref().map(|yarn| yarn.version.clone()),
+            yarn_str: self.yarn.as_ref().map(|yarn| yarn.version.to_string()),
+        }
+    }
+
+    /// Returns the archive of the locked Node version for this platform, if the project
+    /// was locked on it.
+    pub fn node_archive(&self) -> Option<&Archive> {
+        self.node.archives.get(&platform())
+    }
+
+    /// Tests whether the locked versions satisfy a toolchain's pins, which they stop
+    /// doing when the pins are changed without locking the project again.
+    pub fn satisfies(&self, toolchain: &Toolchain) -> bool {
+        let yarn = match (&toolchain.yarn, &self.yarn) {
+            (&Some(ref pin), &Some(ref locked)) => pin.requirements().matches(&locked.version),
+            (&None, &None) => true,
+            _ => false,
+        };
+        yarn && toolchain.node.requirements().matches(&self.node.version)
+    }
+}
+
+/// A way the toolchain in use, or the versions a project pins, deviate from the
+/// project's lockfile.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Deviation {
+    /// A tool is pinned to a version or range the locked version doesn't satisfy.
+    Pin {
+        tool: &'static str,
+        pin: String,
+        locked: Version,
+    },
+    /// A tool is pinned but not locked.
+    Unlocked { tool: &'static str },
+    /// A tool is locked but no longer pinned.
+    Unpinned { tool: &'static str },
+    /// The version of a tool in use (if any) is not the locked version, as when it is
+    /// overridden for the shell session.
+    Resolved {
+        tool: &'static str,
+        version: Option<Version>,
+        locked: Version,
+    },
+    /// No Node archive is locked for this platform.
+    NoArchive { platform: String },
+    /// An installed version was not installed from its locked archive, or it is unknown
+    /// which archive it was installed from.
+    Archive {
+        tool: &'static str,
+        version: Version,
+        expected: Checksum,
+        actual: Option<String>,
+    },
+}
+
+impl Display for Deviation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            &Deviation::Pin {
+                tool,
+                ref pin,
+                ref locked,
+            } => write!(
+                f,
+                "{} is pinned to {}, which the locked v{} doesn't match",
+                tool, pin, locked
+            ),
+            &Deviation::Unlocked { tool } => write!(f, "{} is pinned but not locked", tool),
+            &Deviation::Unpinned { tool } => write!(f, "{} is locked but no longer pinned", tool),
+            &Deviation::Resolved {
+                tool,
+                ref version,
+                ref locked,
+            } => match version {
+                &Some(ref version) => {
+                    write!(f, "{} v{} is in use rather than the locked v{}", tool, version, locked)
+                }
+                &None => write!(f, "{} is not in use, though v{} is locked", tool, locked),
+            },
+            &Deviation::NoArchive { ref platform } => {
+                write!(f, "no Node archive is locked for {}", platform)
+            }
+            &Deviation::Archive {
+                tool,
+                ref version,
+                ref expected,
+                ref actual,
+            } => match actual {
+                &Some(ref actual) => write!(
+                    f,
+                    "{} v{} was installed from an archive with checksum sha256:{}, not {}",
+                    tool, version, actual, expected
+                ),
+                &None => write!(
+                    f,
+                    "{} v{} was installed from an unknown archive, not the locked one",
+                    tool, version
+                ),
+            },
+        }
+    }
+}
+
+/// Finds the ways the versions a project pins, the platform image in use, and the
+/// installs of the locked versions deviate from the project's lockfile. Locked versions
+/// that aren't installed yet don't deviate, since they are installed from their locked
+/// archives when they are first used.
+pub fn deviations(
+    lockfile: &Lockfile,
+    toolchain: Option<&Toolchain>,
+    image: Option<&Image>,
+    catalog: &Catalog,
+) -> Vec<Deviation> {
+    let mut deviations = Vec::new();
+    let node = &lockfile.node.version;
+    let yarn = lockfile.yarn.as_ref().map(|yarn| &yarn.version);
+
+    match toolchain {
+        Some(toolchain) => {
+            if !toolchain.node.requirements().matches(node) {
+                deviations.push(Deviation::Pin {
+                    tool: "node",
+                    pin: toolchain.node_str.clone(),
+                    locked: node.clone(),
+                });
+            }
+            match (&toolchain.yarn, yarn) {
+                (&Some(ref pin), Some(yarn)) if !pin.requirements().matches(yarn) => {
+                    deviations.push(Deviation::Pin {
+                        tool: "yarn",
+                        pin: toolchain.yarn_str.clone().unwrap_or_default(),
+                        locked: yarn.clone(),
+                    });
+                }
+                (&Some(_), None) => deviations.push(Deviation::Unlocked { tool: "yarn" }),
+                (&None, Some(_)) => deviations.push(Deviation::Unpinned { tool: "yarn" }),
+                _ => {}
+            }
+        }
+        None => deviations.push(Deviation::Unpinned { tool: "node" }),
+    }
+
+    let in_use = image.filter(|image| image.node_arch == Arch::native());
+    if in_use.map(|image| &image.node) != Some(node) {
+        deviations.push(Deviation::Resolved {
+            tool: "node",
+            version: image.map(|image| image.node.clone()),
+            locked: node.clone(),
+        });
+    }
+    if let Some(yarn) = yarn {
+        let version = image.and_then(|image| image.yarn.as_ref());
+        if version != Some(yarn) {
+            deviations.push(Deviation::Resolved {
+                tool: "yarn",
+                version: version.cloned(),
+                locked: yarn.clone(),
+            });
+        }
+    }
+
+    match lockfile.node_archive() {
+        Some(archive) => if catalog.node.contains(node) {
+            let key = path::node_version_key(&node.to_string(), Arch::native());
+            let provenance = catalog.node.provenance.get(&key);
+            check_archive("node", node, archive, provenance, &mut deviations);
+        },
+        None => deviations.push(Deviation::NoArchive {
+            platform: platform(),
+        }),
+    }
+    if let Some(ref locked) = lockfile.yarn {
+        if catalog.yarn.contains(&locked.version) {
+            let provenance = catalog.yarn.provenance.get(&locked.version.to_string());
+            check_archive("yarn", &locked.version, &locked.archive, provenance, &mut deviations);
+        }
+    }
+
+    deviations
+}
+
+/// Checks that an installed version was installed from its locked archive.
+fn check_archive(
+    tool: &'static str,
+    version: &Version,
+    archive: &Archive,
+    provenance: Option<&Provenance>,
+    deviations: &mut Vec<Deviation>,
+) {
+    let actual = provenance.map(|provenance| provenance.sha256.clone());
+    let matches = actual.as_ref().map_or(false, |actual| {
+        archive.checksum.to_string() == format!("sha256:{}", actual)
+    });
+    if !matches {
+        deviations.push(Deviation::Archive {
+            tool,
+            version: version.clone(),
+            expected: archive.checksum.clone(),
+            actual,
+        });
+    }
+}
+
+/// Produces the name the Node archive for this platform is recorded under, which is the
+/// platform part of the names Node's archives are published under (like `linux-x64`).
+pub fn platform() -> String {
+    format!(
+        "{}-{}{}",
+        path::OS,
+        Arch::native(),
+        Libc::native().archive_suffix()
+    )
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    fn archive(name: &str) -> Archive {
+        Archive {
+            url: format!("https://example.com/{}", name),
+            checksum: format!("sha256:{}", "0".repeat(64)).parse().unwrap(),
+        }
+    }
+
+    fn lockfile(yarn: Option<&str>) -> Lockfile {
+        let mut archives = BTreeMap::new();
+        archives.insert(platform(), archive("node-v10.1.0.tar.gz"));
+        Lockfile {
+            node: LockedNode {
+                version: Version::parse("10.1.0").unwrap(),
+                archives,
+            },
+            yarn: yarn.map(|version| LockedYarn {
+                version: Version::parse(version).unwrap(),
+                archive: archive("yarn.tar.gz"),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_image() {
+        let image = lockfile(Some("1.7.0")).image();
+        assert_eq!(image.node_str, "10.1.0");
+        assert_eq!(image.node_arch, Arch::native());
+        assert_eq!(image.yarn_str, Some("1.7.0".to_string()));
+        assert_eq!(
+            lockfile(None).node_archive().map(|archive| &archive.url[..]),
+            Some("https://example.com/node-v10.1.0.tar.gz")
+        );
+    }
+
+    #[test]
+    fn test_satisfies() {
+        let toolchain = |node: &str, yarn: Option<&str>| {
+            Toolchain::parse(node.to_string(), yarn.map(str::to_string), None).unwrap()
+        };
+
+        assert!(lockfile(None).satisfies(&toolchain("10.1.0", None)));
+        assert!(lockfile(None).satisfies(&toolchain("^10", None)));
+        assert!(lockfile(Some("1.7.0")).satisfies(&toolchain("10", Some("^1.6"))));
+        assert!(!lockfile(None).satisfies(&toolchain("^11", None)));
+        assert!(!lockfile(None).satisfies(&toolchain("10.1.0", Some("1.7.0"))));
+        assert!(!lockfile(Some("1.7.0")).satisfies(&toolchain("10.1.0", None)));
+    }
+
+    #[test]
+    fn test_deviations() {
+        let catalog: Catalog = "".parse().unwrap();
+        let locked = lockfile(Some("1.7.0"));
+        let toolchain = Toolchain::parse("^10".to_string(), Some("1.7.0".to_string()), None)
+            .unwrap();
+        let image = locked.image();
+
+        assert!(deviations(&locked, Some(&toolchain), Some(&image), &catalog).is_empty());
+
+        let drifted = Toolchain::parse("^11".to_string(), None, None).unwrap();
+        let overridden = Image {
+            node: Version::parse("10.2.0").unwrap(),
+            node_str: "10.2.0".to_string(),
+            ..locked.image()
+        };
+        assert_eq!(
+            deviations(&locked, Some(&drifted), Some(&overridden), &catalog),
+            vec![
+                Deviation::Pin {
+                    tool: "node",
+                    pin: "^11".to_string(),
+                    locked: Version::parse("10.1.0").unwrap(),
+                },
+                Deviation::Unpinned { tool: "yarn" },
+                Deviation::Resolved {
+                    tool: "node",
+                    version: Some(Version::parse("10.2.0").unwrap()),
+                    locked: Version::parse("10.1.0").unwrap(),
+                },
+            ]
+        );
+
+        let mut elsewhere = lockfile(None);
+        elsewhere.node.archives.clear();
+        assert_eq!(
+            deviations(&elsewhere, None, None, &catalog),
+            vec![
+                Deviation::Unpinned { tool: "node" },
+                Deviation::Resolved {
+                    tool: "node",
+                    version: None,
+                    locked: Version::parse("10.1.0").unwrap(),
+                },
+                Deviation::NoArchive {
+                    platform: platform(),
+                },
+            ]
+        );
+    }
+}
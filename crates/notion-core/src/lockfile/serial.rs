@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+
+use super::super::lockfile;
+use notion_fail::{Fallible, ResultExt};
+use semver::Version;
+
+#[derive(Serialize, Deserialize)]
+pub struct Lockfile {
+    pub node: Node,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub yarn: Option<Yarn>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Node {
+    pub version: String,
+    #[serde(default)]
+    pub archives: BTreeMap<String, Archive>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Yarn {
+    pub version: String,
+    pub url: String,
+    pub checksum: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Archive {
+    pub url: String,
+    pub checksum: String,
+}
+
+impl Lockfile {
+    pub fn into_lockfile(self) -> Fallible<lockfile::Lockfile> {
+        let mut archives = BTreeMap::new();
+        for (platform, archive) in self.node.archives {
+            archives.insert(platform, archive.into_archive()?);
+        }
+        let yarn = match self.yarn {
+            Some(yarn) => Some(lockfile::LockedYarn {
+                version: Version::parse(&yarn.version).unknown()?,
+                archive: lockfile::Archive {
+                    url: yarn.url,
+                    checksum: yarn.checksum.parse()?,
+                },
+            }),
+            None => None,
+        };
+        Ok(lockfile::Lockfile {
+            node: lockfile::LockedNode {
+                version: Version::parse(&self.node.version).unknown()?,
+                archives,
+            },
+            yarn,
+        })
+    }
+
+    pub fn from_lockfile(lockfile: &lockfile::Lockfile) -> Lockfile {
+        Lockfile {
+            node: Node {
+                version: lockfile.node.version.to_string(),
+                archives: lockfile
+                    .node
+                    .archives
+                    .iter()
+                    .map(|(platform, archive)| (platform.clone(), Archive::from_archive(archive)))
+                    .collect(),
+            },
+            yarn: lockfile.yarn.as_ref().map(|yarn| Yarn {
+                version: yarn.version.to_string(),
+                url: yarn.archive.url.clone(),
+                checksum: yarn.archive.checksum.to_string(),
+            }),
+        }
+    }
+}
+
+impl Archive {
+    fn into_archive(self) -> Fallible<lockfile::Archive> {
+        Ok(lockfile::Archive {
+            url: self.url,
+            checksum: self.checksum.parse()?,
+        })
+    }
+
+    fn from_archive(archive: &lockfile::Archive) -> Archive {
+        Archive {
+            url: archive.url.clone(),
+            checksum: archive.checksum.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::Lockfile;
+    use toml;
+
+    const DIGEST: &'static str =
+        "2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae";
+
+    #[test]
+    fn test_lockfile_roundtrip() {
+        let src = format!(
+            r#"
+[node]
+version = "10.1.0"
+
+[node.archives.linux-x64]
+url = "https://nodejs.org/dist/v10.1.0/node-v10.1.0-linux-x64.tar.gz"
+checksum = "sha256:{digest}"
+
+[node.archives.darwin-x64]
+url = "https://nodejs.org/dist/v10.1.0/node-v10.1.0-darwin-x64.tar.gz"
+checksum = "sha256:{digest}"
+
+[yarn]
+version = "1.7.0"
+url = "https://github.com/yarnpkg/yarn/releases/download/v1.7.0/yarn-v1.7.0.tar.gz"
+checksum = "sha256:{digest}"
+"#,
+            digest = DIGEST
+        );
+        let serial: Lockfile = toml::from_str(&src).unwrap();
+        let lockfile = serial.into_lockfile().unwrap();
+        assert_eq!(lockfile.node.version.to_string(), "10.1.0");
+        assert_eq!(lockfile.node.archives.len(), 2);
+        assert_eq!(
+            lockfile.yarn.as_ref().map(|yarn| yarn.archive.checksum.to_string()),
+            Some(format!("sha256:{}", DIGEST))
+        );
+
+        let written = toml::to_string(&Lockfile::from_lockfile(&lockfile)).unwrap();
+        let reparsed: Lockfile = toml::from_str(&written).unwrap();
+        assert_eq!(reparsed.into_lockfile().unwrap(), lockfile);
+    }
+
+    #[test]
+    fn test_invalid_checksum() {
+        let src = r#"
+[node]
+version = "10.1.0"
+
+[node.archives.linux-x64]
+url = "https://nodejs.org/dist/v10.1.0/node-v10.1.0-linux-x64.tar.gz"
+checksum = "md5:abc"
+"#;
+        let serial: Lockfile = toml::from_str(src).unwrap();
+        assert!(serial.into_lockfile().is_err());
+    }
+}
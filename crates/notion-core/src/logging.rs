@@ -0,0 +1,378 @@
+//! Provides the optional log sink that warnings and errors are written to, alongside
+//! the usual console output, so that they can be collected on managed machines.
+//!
+//! The sink is configured in the `[log]` section of `config.toml`. Each entry carries
+//! structured fields (such as the activity and error code) in addition to its
+//! message: journald receives them as journal fields, syslog as RFC 5424 structured
+//! data, and the Windows Event Log as lines after the message, with the exit code as
+//! the event ID. Failing to write to the sink is never reported, since the entry was
+//! already shown on the console.
+
+use std::str::FromStr;
+
+use config::LazyConfig;
+use locale::{self, Message};
+use notion_fail::{NotionError, NotionFail};
+
+/// The name entries are logged under.
+const IDENTIFIER: &'static str = "notion";
+
+/// Where warnings and errors are logged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogSink {
+    /// The local syslog daemon, through `/dev/log`.
+    Syslog,
+    /// The systemd journal, through its native socket.
+    Journald,
+    /// The Windows Event Log, under the `notion` source of the Application log. Only
+    /// errors are recorded there.
+    EventLog,
+}
+
+/// Thrown when the `sink` setting of the `[log]` section is not recognized.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Invalid log.sink setting '{}' (expected syslog, journald, or eventlog)", value)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct LogSinkError {
+    value: String,
+}
+
+impl FromStr for LogSink {
+    type Err = NotionError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        match src {
+            "syslog" => Ok(LogSink::Syslog),
+            "journald" => Ok(LogSink::Journald),
+            "eventlog" => Ok(LogSink::EventLog),
+            _ => throw!(LogSinkError {
+                value: src.to_string(),
+            }),
+        }
+    }
+}
+
+/// The severity of a log entry.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Priority {
+    Error,
+    Warning,
+}
+
+impl Priority {
+    /// Produces the syslog severity level of the priority, which journald uses too.
+    fn level(&self) -> u8 {
+        match self {
+            &Priority::Error => 3,
+            &Priority::Warning => 4,
+        }
+    }
+}
+
+/// Displays a warning to stderr and writes it to the configured log sink, if any.
+///
+/// Warnings can be raised where no session is at hand (for instance while delivering
+/// events), so the configuration is read afresh here; warnings are rare enough for
+/// this not to matter.
+pub fn warning(message: &str) {
+    eprintln!("{} {}", locale::message(Message::WarningPrefix), message);
+    let config = LazyConfig::new();
+    if let Ok(Some(sink)) = config.get().map(|config| config.log_sink()) {
+        write(sink, Priority::Warning, message, &[]);
+    }
+}
+
+/// Writes an error that ended an activity to a log sink.
+pub(crate) fn error(sink: LogSink, activity: &str, error: &NotionError) {
+    let exit_code = error.exit_code();
+    let fields = [
+        ("NOTION_ACTIVITY", activity.to_string()),
+        ("NOTION_ERROR_CODE", format!("{:?}", exit_code)),
+        ("NOTION_EXIT_CODE", (exit_code as i32).to_string()),
+    ];
+    write(sink, Priority::Error, &error.to_string(), &fields);
+}
+
+/// Writes an entry to a log sink, ignoring any failure to do so.
+pub(crate) fn write(
+    sink: LogSink,
+    priority: Priority,
+    message: &str,
+    fields: &[(&str, String)],
+) {
+    let _ = match sink {
+        LogSink::Syslog | LogSink::Journald => socket::send(sink, priority, message, fields),
+        LogSink::EventLog if priority == Priority::Error => {
+            event_log::report(priority, event_id(fields), &event_log_entry(message, fields))
+        }
+        LogSink::EventLog => Ok(()),
+    };
+}
+
+#[cfg(unix)]
+mod socket {
+    use std::io;
+    use std::os::unix::net::UnixDatagram;
+    use std::process;
+
+    use super::{journal_entry, syslog_entry, LogSink, Priority};
+
+    const SYSLOG_SOCKET: &'static str = "/dev/log";
+    const JOURNAL_SOCKET: &'static str = "/run/systemd/journal/socket";
+
+    pub(super) fn send(
+        sink: LogSink,
+        priority: Priority,
+        message: &str,
+        fields: &[(&str, String)],
+    ) -> io::Result<()> {
+        let socket = UnixDatagram::unbound()?;
+        match sink {
+            LogSink::Syslog => {
+                let entry = syslog_entry(priority, process::id(), message, fields);
+                socket.send_to(entry.as_bytes(), SYSLOG_SOCKET)?;
+            }
+            LogSink::Journald => {
+                let entry = journal_entry(priority, message, fields);
+                socket.send_to(&entry, JOURNAL_SOCKET)?;
+            }
+            LogSink::EventLog => {}
+        }
+        Ok(())
+    }
+}
+
+// NOTE: Windows has neither syslog nor journald, so nothing is logged to them there.
+#[cfg(windows)]
+mod socket {
+    use std::io;
+
+    use super::{LogSink, Priority};
+
+    pub(super) fn send(
+        _sink: LogSink,
+        _priority: Priority,
+        _message: &str,
+        _fields: &[(&str, String)],
+    ) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod event_log {
+    use std::ffi::OsStr;
+    use std::io;
+    use std::iter;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr;
+
+    use winapi::um::winbase::{DeregisterEventSource, RegisterEventSourceW, ReportEventW};
+    use winapi::shared::ntdef::LPCWSTR;
+    use winapi::um::winnt::{EVENTLOG_ERROR_TYPE, EVENTLOG_WARNING_TYPE};
+
+    use super::{Priority, IDENTIFIER};
+
+    fn wide(s: &str) -> Vec<u16> {
+        OsStr::new(s).encode_wide().chain(iter::once(0)).collect()
+    }
+
+    pub(super) fn report(priority: Priority, event_id: u32, entry: &str) -> io::Result<()> {
+        let source = wide(IDENTIFIER);
+        let entry = wide(entry);
+        let event_type = match priority {
+            Priority::Error => EVENTLOG_ERROR_TYPE,
+            Priority::Warning => EVENTLOG_WARNING_TYPE,
+        };
+
+        unsafe {
+            let handle = RegisterEventSourceW(ptr::null(), source.as_ptr());
+            if handle.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            let mut strings: [LPCWSTR; 1] = [entry.as_ptr()];
+            let reported = ReportEventW(
+                handle,
+                event_type,
+                0,
+                event_id,
+                ptr::null_mut(),
+                1,
+                0,
+                strings.as_mut_ptr(),
+                ptr::null_mut(),
+            );
+            DeregisterEventSource(handle);
+            if reported == 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+// NOTE: The Windows Event Log only exists on Windows, so nothing is logged to it
+//       elsewhere.
+#[cfg(unix)]
+mod event_log {
+    use std::io;
+
+    use super::Priority;
+
+    pub(super) fn report(_priority: Priority, _event_id: u32, _entry: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Produces the ID of an Event Log event, which is its exit code.
+fn event_id(fields: &[(&str, String)]) -> u32 {
+    fields
+        .iter()
+        .find(|&&(key, _)| key == "NOTION_EXIT_CODE")
+        .and_then(|&(_, ref value)| value.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Produces the text of an Event Log event: the message, followed by one line per
+/// field.
+fn event_log_entry(message: &str, fields: &[(&str, String)]) -> String {
+    let mut entry = message.to_string();
+    if !fields.is_empty() {
+        entry.push_str("\r\n");
+        for &(key, ref value) in fields {
+            entry.push_str(&format!("\r\n{}: {}", key, value));
+        }
+    }
+    entry
+}
+
+/// Produces an RFC 5424 syslog message, with the fields as structured data. The
+/// facility is always `user`.
+fn syslog_entry(
+    priority: Priority,
+    pid: u32,
+    message: &str,
+    fields: &[(&str, String)],
+) -> String {
+    let mut data = String::new();
+    if !fields.is_empty() {
+        data.push_str("[notion@32473");
+        for &(key, ref value) in fields {
+            data.push_str(&format!(" {}=\"{}\"", key, escape_param(value)));
+        }
+        data.push(']');
+    } else {
+        data.push('-');
+    }
+
+    // The timestamp and host name are left for the daemon to fill in.
+    format!(
+        "<{}>1 - - {} {} - {} {}",
+        8 + priority.level(),
+        IDENTIFIER,
+        pid,
+        data,
+        message
+    )
+}
+
+/// Escapes the characters that RFC 5424 does not allow unescaped in a parameter value.
+fn escape_param(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c == '"' || c == '\\' || c == ']' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Produces an entry in the native journal protocol: one `KEY=value` line per field,
+/// except that values containing a newline are written with an explicit length.
+fn journal_entry(priority: Priority, message: &str, fields: &[(&str, String)]) -> Vec<u8> {
+    let mut entry = Vec::new();
+    journal_field(&mut entry, "MESSAGE", message);
+    journal_field(&mut entry, "PRIORITY", &priority.level().to_string());
+    journal_field(&mut entry, "SYSLOG_IDENTIFIER", IDENTIFIER);
+    for &(key, ref value) in fields {
+        journal_field(&mut entry, key, value);
+    }
+    entry
+}
+
+fn journal_field(entry: &mut Vec<u8>, key: &str, value: &str) {
+    entry.extend_from_slice(key.as_bytes());
+    if value.contains('\n') {
+        let len = value.len() as u64;
+        entry.push(b'\n');
+        for i in 0..8 {
+            entry.push((len >> (8 * i)) as u8);
+        }
+    } else {
+        entry.push(b'=');
+    }
+    entry.extend_from_slice(value.as_bytes());
+    entry.push(b'\n');
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{event_id, event_log_entry, journal_entry, syslog_entry, LogSink, Priority};
+
+    #[test]
+    fn test_syslog_entry() {
+        let fields = [
+            ("NOTION_ACTIVITY", "install".to_string()),
+            ("NOTION_ERROR_CODE", "Network\"Error]".to_string()),
+        ];
+        assert_eq!(
+            syslog_entry(Priority::Error, 42, "could not download node", &fields),
+            "<11>1 - - notion 42 - [notion@32473 NOTION_ACTIVITY=\"install\" \
+             NOTION_ERROR_CODE=\"Network\\\"Error\\]\"] could not download node"
+        );
+        assert_eq!(
+            syslog_entry(Priority::Warning, 42, "hook timed out", &[]),
+            "<12>1 - - notion 42 - - hook timed out"
+        );
+    }
+
+    #[test]
+    fn test_journal_entry() {
+        let fields = [("NOTION_ACTIVITY", "tool".to_string())];
+        assert_eq!(
+            journal_entry(Priority::Warning, "checksum mismatch", &fields),
+            b"MESSAGE=checksum mismatch\nPRIORITY=4\nSYSLOG_IDENTIFIER=notion\n\
+              NOTION_ACTIVITY=tool\n"
+                .to_vec()
+        );
+
+        assert_eq!(
+            journal_entry(Priority::Error, "a\nb", &[]),
+            b"MESSAGE\n\x03\0\0\0\0\0\0\0a\nb\nPRIORITY=3\nSYSLOG_IDENTIFIER=notion\n".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_event_log_entry() {
+        let fields = [
+            ("NOTION_ACTIVITY", "install".to_string()),
+            ("NOTION_EXIT_CODE", "5".to_string()),
+        ];
+        assert_eq!(
+            event_log_entry("could not download node", &fields),
+            "could not download node\r\n\r\nNOTION_ACTIVITY: install\r\nNOTION_EXIT_CODE: 5"
+        );
+        assert_eq!(event_id(&fields), 5);
+        assert_eq!(event_id(&[]), 0);
+    }
+
+    #[test]
+    fn test_log_sink_from_str() {
+        assert_eq!("syslog".parse::<LogSink>().unwrap(), LogSink::Syslog);
+        assert_eq!("journald".parse::<LogSink>().unwrap(), LogSink::Journald);
+        assert_eq!("eventlog".parse::<LogSink>().unwrap(), LogSink::EventLog);
+        assert!("console".parse::<LogSink>().is_err());
+    }
+}
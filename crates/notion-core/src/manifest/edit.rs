@@ -0,0 +1,179 @@
+//! Provides an editor for `package.json` files that changes a single top-level key
+//! without re-serializing the rest of the file, so that its formatting and the order
+//! of its keys are preserved.
+
+use detect_indent;
+use serde::Serialize;
+use serde_json::{self, Value};
+
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+
+/// Thrown when a `package.json` file to be edited does not contain a JSON object.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not update package.json: it does not contain a JSON object")]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct NotAnObjectError;
+
+/// A top-level member of the edited object, located by byte offsets into the source.
+struct Member {
+    key: String,
+    value_start: usize,
+    value_end: usize,
+}
+
+/// Sets a top-level key of the JSON object in `src` to a value, producing the edited
+/// source. A key that already exists keeps its place, and a new key is added after
+/// the last one. The value is written in the indentation and line endings that the
+/// rest of the source uses.
+pub(crate) fn set_key<T: Serialize>(src: &str, key: &str, value: &T) -> Fallible<String> {
+    // Checking that the source is a valid object first lets the scan below assume it
+    // is well-formed.
+    match serde_json::from_str::<Value>(src) {
+        Ok(Value::Object(_)) => {}
+        _ => throw!(NotAnObjectError),
+    }
+
+    let bytes = src.as_bytes();
+    let open = skip_whitespace(bytes, 0);
+    let (members, close) = scan_members(bytes, open)?;
+
+    let indent = detect_indent::detect_indent(src).indent().to_string();
+    let newline = if src.contains("\r\n") { "\r\n" } else { "\n" };
+    let rendered = render(value, &indent, newline)?;
+
+    let mut edited = String::with_capacity(src.len() + rendered.len());
+    if let Some(member) = members.iter().find(|member| member.key == key) {
+        edited.push_str(&src[..member.value_start]);
+        edited.push_str(&rendered);
+        edited.push_str(&src[member.value_end..]);
+        return Ok(edited);
+    }
+
+    let key = serde_json::to_string(key).unknown()?;
+    let entry = if indent.is_empty() {
+        format!("{}:{}", key, rendered)
+    } else {
+        format!("{}{}{}: {}", newline, indent, key, rendered)
+    };
+
+    match members.last() {
+        Some(last) => {
+            edited.push_str(&src[..last.value_end]);
+            edited.push(',');
+            edited.push_str(&entry);
+            edited.push_str(&src[last.value_end..]);
+        }
+        None => {
+            edited.push_str(&src[..open + 1]);
+            edited.push_str(&entry);
+            if !indent.is_empty() {
+                edited.push_str(newline);
+            }
+            edited.push_str(&src[close..]);
+        }
+    }
+    Ok(edited)
+}
+
+/// Serializes a value as a member of a top-level object: pretty-printed with the
+/// document's indentation and nested one level, or compactly if the document has
+/// no indentation.
+fn render<T: Serialize>(value: &T, indent: &str, newline: &str) -> Fallible<String> {
+    if indent.is_empty() {
+        return serde_json::to_string(value).unknown();
+    }
+
+    let mut buffer = Vec::new();
+    {
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+        let mut serializer = serde_json::Serializer::with_formatter(&mut buffer, formatter);
+        value.serialize(&mut serializer).unknown()?;
+    }
+    let pretty = String::from_utf8(buffer).unknown()?;
+    Ok(pretty.replace('\n', &format!("{}{}", newline, indent)))
+}
+
+/// Scans the members of the object starting at `open`, producing them along with
+/// the offset of the object's closing brace.
+fn scan_members(bytes: &[u8], open: usize) -> Fallible<(Vec<Member>, usize)> {
+    let mut members = Vec::new();
+    let mut i = skip_whitespace(bytes, open + 1);
+
+    while bytes[i] != b'}' {
+        let key_end = skip_string(bytes, i);
+        let key: String = serde_json::from_slice(&bytes[i..key_end]).unknown()?;
+
+        // skip the ':' between the key and the value
+        let value_start = skip_whitespace(bytes, skip_whitespace(bytes, key_end) + 1);
+        let value_end = skip_value(bytes, value_start);
+        members.push(Member {
+            key,
+            value_start,
+            value_end,
+        });
+
+        i = skip_whitespace(bytes, value_end);
+        if bytes[i] == b',' {
+            i = skip_whitespace(bytes, i + 1);
+        }
+    }
+    Ok((members, i))
+}
+
+fn skip_whitespace(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() && (bytes[i] as char).is_ascii_whitespace() {
+        i += 1;
+    }
+    i
+}
+
+/// Skips the string starting at `i`, producing the offset after its closing quote.
+fn skip_string(bytes: &[u8], mut i: usize) -> usize {
+    i += 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    i
+}
+
+/// Skips the value starting at `i`, producing the offset after its end.
+fn skip_value(bytes: &[u8], mut i: usize) -> usize {
+    match bytes[i] {
+        b'"' => skip_string(bytes, i),
+        b'{' | b'[' => {
+            let mut depth = 0;
+            while i < bytes.len() {
+                match bytes[i] {
+                    b'"' => {
+                        i = skip_string(bytes, i);
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return i + 1;
+                        }
+                    }
+                    _ => {}
+                }
+                i += 1;
+            }
+            i
+        }
+        _ => {
+            while i < bytes.len() && !is_scalar_end(bytes[i]) {
+                i += 1;
+            }
+            i
+        }
+    }
+}
+
+fn is_scalar_end(byte: u8) -> bool {
+    byte == b',' || byte == b'}' || byte == b']' || (byte as char).is_ascii_whitespace()
+}
@@ -2,19 +2,25 @@
 
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{self, Read};
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::str::FromStr;
 
-use detect_indent;
-use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+use arch::Arch;
+use checksum::Checksum;
+use notion_fail::{ExitCode, Fallible, NotionError, NotionFail, ResultExt};
 use image::Image;
-use semver::Version;
-use serde::Serialize;
+use toolchain::Autoshim;
+use semver::{Version, VersionReq};
 use serde_json;
+use version::VersionSpec;
 
+pub(crate) mod edit;
 pub(crate) mod serial;
 
+use self::serial::ToolPin;
+
 #[derive(Debug, Fail, NotionFail)]
 #[fail(display = "Could not read package info: {}", error)]
 #[notion_fail(code = "FileSystemError")]
@@ -30,10 +36,236 @@ impl PackageReadError {
     }
 }
 
+/// A version pinned in the `toolchain` section of a manifest.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pin {
+    /// An exact version.
+    Exact(Version),
+    /// A semver range (like `^18`), which is resolved to a version when the
+    /// toolchain is used.
+    Range(VersionReq),
+}
+
+impl Pin {
+    /// Parses a pinned version, which is exact if it is a full version number and a
+    /// range otherwise.
+    pub fn parse(src: &str) -> Fallible<Self> {
+        match Version::parse(src) {
+            Ok(version) => Ok(Pin::Exact(version)),
+            Err(_) => Ok(Pin::Range(VersionSpec::parse_requirements(src)?)),
+        }
+    }
+
+    /// Returns the versions this pin allows, as a semver requirement.
+    pub fn requirements(&self) -> VersionReq {
+        match self {
+            &Pin::Exact(ref version) => VersionReq::exact(version),
+            &Pin::Range(ref range) => range.clone(),
+        }
+    }
+}
+
+/// How precisely a resolved version is written when it is pinned, configured by the
+/// `pin.style` setting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PinStyle {
+    /// The full version, like `18.17.1`.
+    Exact,
+    /// A caret range of the major and minor version, like `^18.17`.
+    Caret,
+    /// The major version alone, like `18`.
+    Major,
+}
+
+/// Thrown when the `style` setting of the `[pin]` section is not recognized.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Invalid pin.style setting '{}' (expected exact, caret, or major)", value)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct PinStyleError {
+    value: String,
+}
+
+impl PinStyle {
+    /// Formats a resolved version for pinning in this style.
+    pub fn format(&self, version: &Version) -> String {
+        match self {
+            &PinStyle::Exact => version.to_string(),
+            &PinStyle::Caret => format!("^{}.{}", version.major, version.minor),
+            &PinStyle::Major => version.major.to_string(),
+        }
+    }
+}
+
+impl FromStr for PinStyle {
+    type Err = NotionError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        match src {
+            "exact" => Ok(PinStyle::Exact),
+            "caret" => Ok(PinStyle::Caret),
+            "major" => Ok(PinStyle::Major),
+            _ => throw!(PinStyleError {
+                value: src.to_string(),
+            }),
+        }
+    }
+}
+
+/// Thrown when a checksum is pinned along with a range of versions.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "A checksum can only be pinned with an exact version ({} is pinned to '{}')",
+       tool, pin)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct RangeChecksumError {
+    tool: String,
+    pin: String,
+}
+
+/// Parses the checksum pinned with a version, written either as a bare hex digest or
+/// as `sha256:<hex digest>`.
+fn pinned_checksum(tool: &str, pin: &Pin, src: &ToolPin) -> Fallible<Option<Checksum>> {
+    let sha256 = match src.sha256() {
+        Some(sha256) => sha256,
+        None => return Ok(None),
+    };
+    if let &Pin::Range(_) = pin {
+        throw!(RangeChecksumError {
+            tool: tool.to_string(),
+            pin: src.version().to_string(),
+        });
+    }
+    let checksum = if sha256.starts_with("sha256:") {
+        sha256.parse()?
+    } else {
+        format!("sha256:{}", sha256).parse()?
+    };
+    Ok(Some(checksum))
+}
+
+/// The `toolchain` section of a manifest, or of a `notion.toml` file.
+#[derive(Debug, Clone)]
+pub struct Toolchain {
+    /// The pinned version of Node.
+    pub node: Pin,
+    /// The pinned version of Node as it appears in the manifest.
+    pub node_str: String,
+    /// The pinned version of Yarn, if any.
+    pub yarn: Option<Pin>,
+    /// The pinned version of Yarn as it appears in the manifest.
+    pub yarn_str: Option<String>,
+    /// The pinned version of npm, if any, which selects the Node version bundling it.
+    pub npm: Option<Pin>,
+    /// The pinned version of npm as it appears in the manifest.
+    pub npm_str: Option<String>,
+    /// The checksum pinned for the archive of the pinned version of Node, if any.
+    pub node_checksum: Option<Checksum>,
+    /// The checksum pinned for the archive of the pinned version of Yarn, if any.
+    pub yarn_checksum: Option<Checksum>,
+}
+
+impl Toolchain {
+    /// Parses the pinned versions of a toolchain.
+    pub fn parse(node: String, yarn: Option<String>, npm: Option<String>) -> Fallible<Self> {
+        Ok(Toolchain {
+            node: Pin::parse(&node)?,
+            node_str: node,
+            yarn: match yarn {
+                Some(ref yarn) => Some(Pin::parse(yarn)?),
+                None => None,
+            },
+            yarn_str: yarn,
+            npm: match npm {
+                Some(ref npm) => Some(Pin::parse(npm)?),
+                None => None,
+            },
+            npm_str: npm,
+            node_checksum: None,
+            yarn_checksum: None,
+        })
+    }
+
+    /// Parses the pinned versions of a toolchain, along with any checksums pinned for
+    /// their archives, which can only be pinned with an exact version.
+    pub(crate) fn parse_pins(
+        node: &ToolPin,
+        yarn: Option<&ToolPin>,
+        npm: Option<String>,
+    ) -> Fallible<Self> {
+        let mut toolchain = Toolchain::parse(
+            node.version().to_string(),
+            yarn.map(|yarn| yarn.version().to_string()),
+            npm,
+        )?;
+        toolchain.node_checksum = pinned_checksum("node", &toolchain.node, node)?;
+        if let (Some(pin), Some(yarn)) = (toolchain.yarn.clone(), yarn) {
+            toolchain.yarn_checksum = pinned_checksum("yarn", &pin, yarn)?;
+        }
+        Ok(toolchain)
+    }
+
+    /// Returns the checksums pinned for the archives of an image's versions, for the
+    /// versions that are the ones this toolchain pins.
+    pub(crate) fn checksums_for(
+        &self,
+        image: &Image,
+    ) -> (Option<&Checksum>, Option<&Checksum>) {
+        let node = match self.node {
+            Pin::Exact(ref version) if version == &image.node => self.node_checksum.as_ref(),
+            _ => None,
+        };
+        let yarn = match (&self.yarn, &image.yarn) {
+            (&Some(Pin::Exact(ref pinned)), &Some(ref version)) if pinned == version => {
+                self.yarn_checksum.as_ref()
+            }
+            _ => None,
+        };
+        (node, yarn)
+    }
+
+    /// Returns true if any of the pinned versions is a range.
+    pub fn has_ranges(&self) -> bool {
+        match (&self.node, &self.yarn) {
+            (&Pin::Range(_), _) | (_, &Some(Pin::Range(_))) => true,
+            _ => false,
+        }
+    }
+
+    /// Produces the platform image for this toolchain if all of its pinned versions
+    /// are exact, and it doesn't pin npm.
+    pub(crate) fn exact_image(&self) -> Option<Image> {
+        if self.npm.is_some() {
+            return None;
+        }
+
+        let node = match self.node {
+            Pin::Exact(ref version) => version.clone(),
+            Pin::Range(_) => return None,
+        };
+        let yarn = match self.yarn {
+            Some(Pin::Exact(ref version)) => Some(version.clone()),
+            Some(Pin::Range(_)) => return None,
+            None => None,
+        };
+
+        Some(Image {
+            node,
+            node_str: self.node_str.clone(),
+            node_arch: Arch::native(),
+            yarn,
+            yarn_str: self.yarn_str.clone(),
+        })
+    }
+}
+
 /// A Node manifest file.
 pub struct Manifest {
-    /// The platform image specified by the `toolchain` section.
+    /// The platform image specified by the `toolchain` section, if all of its
+    /// versions are exact.
     pub platform_image: Option<Rc<Image>>,
+    /// The `toolchain` section.
+    pub toolchain: Option<Toolchain>,
+    /// The `autoshim` key of the `toolchain` section.
+    pub autoshim: Option<Autoshim>,
     /// The `dependencies` section.
     pub dependencies: HashMap<String, String>,
     /// The `devDependencies` section.
@@ -82,9 +314,9 @@ impl Manifest {
         self.platform().map(|t| t.node.clone())
     }
 
-    /// Returns the pinned verison of Node as a String, if any.
+    /// Returns the pinned verison of Node as a String, if any. This may be a range.
     pub fn node_str(&self) -> Option<String> {
-        self.platform().map(|t| t.node_str.clone())
+        self.toolchain.as_ref().map(|t| t.node_str.clone())
     }
 
     /// Returns the pinned verison of Yarn as a Version, if any.
@@ -94,41 +326,27 @@ impl Manifest {
             .unwrap_or(None)
     }
 
-    /// Returns the pinned verison of Yarn as a String, if any.
+    /// Returns the pinned verison of Yarn as a String, if any. This may be a range.
     pub fn yarn_str(&self) -> Option<String> {
-        self.platform()
-            .map(|t| t.yarn_str.clone())
-            .unwrap_or(None)
+        self.toolchain
+            .as_ref()
+            .and_then(|t| t.yarn_str.clone())
     }
 
     /// Writes the input ToolchainManifest to package.json, adding the "toolchain" key if
-    /// necessary.
+    /// necessary. The rest of the file is left as it was, including its formatting and
+    /// the order of its keys.
     pub fn update_toolchain(
         toolchain: serial::Image,
         package_file: PathBuf,
     ) -> Fallible<()> {
-        // parse the entire package.json file into a Value
-        let file = File::open(&package_file).unknown()?;
-        let mut v: serde_json::Value = serde_json::from_reader(file).unknown()?;
-
-        // detect indentation in package.json
         let mut contents = String::new();
-        let mut indent_file = File::open(&package_file).unknown()?;
-        indent_file.read_to_string(&mut contents).unknown()?;
-        let indent = detect_indent::detect_indent(&contents);
-
-        if let Some(map) = v.as_object_mut() {
-            // update the "toolchain" key
-            let toolchain_value = serde_json::to_value(toolchain).unknown()?;
-            map.insert("toolchain".to_string(), toolchain_value);
-
-            // serialize the updated contents back to package.json
-            let file = File::create(package_file).unknown()?;
-            let formatter =
-                serde_json::ser::PrettyFormatter::with_indent(indent.indent().as_bytes());
-            let mut ser = serde_json::Serializer::with_formatter(file, formatter);
-            map.serialize(&mut ser).unknown()?;
-        }
+        let mut file = File::open(&package_file).unknown()?;
+        file.read_to_string(&mut contents).unknown()?;
+
+        let edited = edit::set_key(&contents, "toolchain", &toolchain)?;
+        let mut file = File::create(package_file).unknown()?;
+        file.write_all(edited.as_bytes()).unknown()?;
         Ok(())
     }
 }
@@ -1,5 +1,5 @@
-use super::super::{image, manifest};
-use version::VersionSpec;
+use super::super::manifest;
+use toolchain;
 
 use notion_fail::Fallible;
 
@@ -61,9 +61,47 @@ pub struct Manifest {
 
 #[derive(Serialize, Deserialize)]
 pub struct Image {
-    pub node: String,
+    pub node: ToolPin,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub yarn: Option<String>,
+    pub yarn: Option<ToolPin>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub npm: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub autoshim: Option<toolchain::serial::Autoshim>,
+}
+
+/// A pinned version of a tool, which is either written as a plain version (or range),
+/// or as a table that also records the SHA-256 checksum of the archive the version is
+/// installed from, like `{ version = "18.17.1", sha256 = "..." }`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum ToolPin {
+    Version(String),
+    Checksummed { version: String, sha256: String },
+}
+
+impl ToolPin {
+    /// The pinned version, as written.
+    pub fn version(&self) -> &str {
+        match self {
+            &ToolPin::Version(ref version) => version,
+            &ToolPin::Checksummed { ref version, .. } => version,
+        }
+    }
+
+    /// The pinned checksum, as written, if there is one.
+    pub fn sha256(&self) -> Option<&str> {
+        match self {
+            &ToolPin::Version(_) => None,
+            &ToolPin::Checksummed { ref sha256, .. } => Some(sha256),
+        }
+    }
+}
+
+impl From<String> for ToolPin {
+    fn from(version: String) -> Self {
+        ToolPin::Version(version)
+    }
 }
 
 impl Manifest {
@@ -80,36 +118,43 @@ impl Manifest {
                 }
             }
         }
+        let toolchain = self.into_toolchain()?;
+        let autoshim = self
+            .toolchain
+            .and_then(|toolchain| toolchain.autoshim)
+            .map(toolchain::serial::Autoshim::into_autoshim);
         Ok(manifest::Manifest {
-            platform_image: self.into_image()?.map(Rc::new),
+            platform_image: toolchain
+                .as_ref()
+                .and_then(manifest::Toolchain::exact_image)
+                .map(Rc::new),
+            toolchain,
+            autoshim,
             dependencies: self.dependencies,
             dev_dependencies: self.dev_dependencies,
             bin: map,
         })
     }
 
-    pub fn into_image(&self) -> Fallible<Option<image::Image>> {
+    pub fn into_toolchain(&self) -> Fallible<Option<manifest::Toolchain>> {
         if let Some(toolchain) = &self.toolchain {
-            return Ok(Some(image::Image {
-                node: VersionSpec::parse_version(&toolchain.node)?,
-                node_str: toolchain.node.clone(),
-                yarn: if let Some(yarn) = &toolchain.yarn {
-                    Some(VersionSpec::parse_version(&yarn)?)
-                } else {
-                    None
-                },
-                yarn_str: toolchain.yarn.clone(),
-            }));
+            return Ok(Some(manifest::Toolchain::parse_pins(
+                &toolchain.node,
+                toolchain.yarn.as_ref(),
+                toolchain.npm.clone(),
+            )?));
         }
         Ok(None)
     }
 }
 
 impl Image {
-    pub fn new(node_version: String, yarn_version: Option<String>) -> Self {
+    pub fn new(node_version: String, yarn_version: Option<String>, npm_version: Option<String>) -> Self {
         Image {
-            node: node_version,
-            yarn: yarn_version,
+            node: ToolPin::from(node_version),
+            yarn: yarn_version.map(ToolPin::from),
+            npm: npm_version,
+            autoshim: None,
         }
     }
 }
@@ -186,6 +231,8 @@ where
 pub mod tests {
 
     use super::{BinMap, Manifest};
+    use manifest::Pin;
+    use semver::{Version, VersionReq};
     use serde_json;
     use std::collections::HashMap;
 
@@ -288,7 +335,7 @@ pub mod tests {
         }"#;
         let manifest_node_only: Manifest =
             serde_json::de::from_str(package_node_only).expect("Could not deserialize string");
-        assert_eq!(manifest_node_only.toolchain.unwrap().node, "0.10.5");
+        assert_eq!(manifest_node_only.toolchain.unwrap().node.version(), "0.10.5");
 
         let package_yarn_only = r#"{
             "toolchain": {
@@ -312,8 +359,36 @@ pub mod tests {
         let toolchain = manifest_node_and_yarn
             .toolchain
             .expect("Did not parse toolchain correctly");
-        assert_eq!(toolchain.node, "0.10.5");
-        assert_eq!(toolchain.yarn.unwrap(), "1.2.1");
+        assert_eq!(toolchain.node.version(), "0.10.5");
+        assert_eq!(toolchain.yarn.unwrap().version(), "1.2.1");
+    }
+
+    #[test]
+    fn test_package_toolchain_ranges() {
+        let package_range = r#"{
+            "toolchain": {
+                "node": "^18",
+                "yarn": "1.2.1"
+            }
+        }"#;
+        let manifest_range: Manifest =
+            serde_json::de::from_str(package_range).expect("Could not deserialize string");
+        let manifest = manifest_range
+            .into_manifest()
+            .expect("Could not convert manifest");
+        assert!(manifest.platform().is_none());
+        assert_eq!(manifest.node_str(), Some("^18".to_string()));
+
+        let toolchain = manifest.toolchain.expect("Did not parse toolchain correctly");
+        assert!(toolchain.has_ranges());
+        assert_eq!(
+            toolchain.node,
+            Pin::Range(VersionReq::parse("^18").unwrap())
+        );
+        assert_eq!(
+            toolchain.yarn,
+            Some(Pin::Exact(Version::parse("1.2.1").unwrap()))
+        );
     }
 
     #[test]
@@ -1,5 +1,8 @@
-use manifest::Manifest;
+use manifest::{Manifest, PinStyle};
+use manifest::edit::set_key;
+use manifest::serial;
 use semver::Version;
+use serde_json;
 use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 
@@ -127,3 +130,94 @@ fn handles_dep_with_no_bin() {
     let expected_bin = HashMap::new();
     assert_eq!(bin, expected_bin);
 }
+
+fn toolchain(node: &str) -> serial::Image {
+    serial::Image::new(node.to_string(), None, None)
+}
+
+#[test]
+fn edits_existing_toolchain_in_place() {
+    let src = concat!(
+        "{\n",
+        "    \"name\": \"app\",\n",
+        "    \"toolchain\": {\n",
+        "        \"node\": \"8.9.4\"\n",
+        "    },\n",
+        "    \"dependencies\": {}\n",
+        "}\n"
+    );
+    let expected = concat!(
+        "{\n",
+        "    \"name\": \"app\",\n",
+        "    \"toolchain\": {\n",
+        "        \"node\": \"18.17.0\"\n",
+        "    },\n",
+        "    \"dependencies\": {}\n",
+        "}\n"
+    );
+    assert_eq!(set_key(src, "toolchain", &toolchain("18.17.0")).unwrap(), expected);
+}
+
+#[test]
+fn adds_toolchain_after_last_key() {
+    let src = "{\r\n  \"version\": \"1.0.0\",\r\n  \"name\": \"app\"\r\n}";
+    let expected = concat!(
+        "{\r\n",
+        "  \"version\": \"1.0.0\",\r\n",
+        "  \"name\": \"app\",\r\n",
+        "  \"toolchain\": {\r\n",
+        "    \"node\": \"18.17.0\"\r\n",
+        "  }\r\n",
+        "}"
+    );
+    assert_eq!(set_key(src, "toolchain", &toolchain("18.17.0")).unwrap(), expected);
+}
+
+#[test]
+fn adds_toolchain_to_empty_or_compact_object() {
+    assert_eq!(
+        set_key("{}", "toolchain", &toolchain("18.17.0")).unwrap(),
+        "{\"toolchain\":{\"node\":\"18.17.0\"}}"
+    );
+    assert_eq!(
+        set_key("{\"name\":\"a,}\\\"b\"}", "toolchain", &toolchain("18.17.0")).unwrap(),
+        "{\"name\":\"a,}\\\"b\",\"toolchain\":{\"node\":\"18.17.0\"}}"
+    );
+}
+
+#[test]
+fn rejects_non_objects() {
+    assert!(set_key("[]", "toolchain", &toolchain("18.17.0")).is_err());
+    assert!(set_key("{ \"name\": ", "toolchain", &toolchain("18.17.0")).is_err());
+}
+
+#[test]
+fn formats_pin_styles() {
+    let version = Version::parse("18.17.1").unwrap();
+    assert_eq!(PinStyle::Exact.format(&version), "18.17.1");
+    assert_eq!(PinStyle::Caret.format(&version), "^18.17");
+    assert_eq!(PinStyle::Major.format(&version), "18");
+    assert!("tilde".parse::<PinStyle>().is_err());
+}
+
+#[test]
+fn gets_autoshim_settings() {
+    let src = r#"{
+        "name": "app",
+        "toolchain": {
+            "node": "10.2.1",
+            "autoshim": { "exclude": ["lerna-*"] }
+        }
+    }"#;
+    let manifest = serde_json::from_str::<serial::Manifest>(src)
+        .expect("Could not parse package.json")
+        .into_manifest()
+        .expect("Could not read toolchain");
+    let autoshim = manifest.autoshim.expect("Did not parse autoshim");
+    assert!(!autoshim.allows("lerna-run"));
+    assert!(autoshim.allows("tsc"));
+
+    let without: serial::Manifest = serde_json::from_str(r#"{ "toolchain": { "node": "10.2.1" } }"#)
+        .expect("Could not parse package.json");
+    assert!(without.into_manifest().unwrap().autoshim.is_none());
+}
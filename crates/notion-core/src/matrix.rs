@@ -0,0 +1,234 @@
+//! Provides support for running a command with each of several Node versions, for
+//! `notion run --node=<versions>`.
+
+use std::io::{self, Write};
+use std::process::{Command, Output, Stdio};
+use std::thread;
+
+use semver::Version;
+
+use image::Image;
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+use package::node_image;
+use plan::Plan;
+use session::Session;
+use version::VersionSpec;
+
+/// Thrown when some of the versions of a matrix run fail. The process exits with
+/// `code` rather than with this error's exit code (see `exit_code`).
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} of {} versions failed", failed, total)]
+#[notion_fail(code = "ExecutionFailure")]
+pub struct MatrixFailedError {
+    pub failed: usize,
+    pub total: usize,
+    pub code: i32,
+}
+
+/// Thrown when the command of a matrix run could not be started.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not run '{}' with Node v{}: {}", command, node, error)]
+#[notion_fail(code = "ExecutionFailure")]
+pub(crate) struct MatrixSpawnError {
+    command: String,
+    node: Version,
+    error: String,
+}
+
+/// The result of running the command with one Node version.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    /// The Node version the command ran with.
+    pub node: Version,
+    /// The exit code of the command, which is 1 if it was killed by a signal.
+    pub code: i32,
+}
+
+impl Outcome {
+    /// Tests whether the command succeeded.
+    pub fn passed(&self) -> bool {
+        self.code == 0
+    }
+}
+
+/// Produces the platform image for each entry of a matrix: the Node version matching
+/// it, fetched if necessary, with the Yarn version of the current platform, if any.
+pub fn images(session: &mut Session, versions: &[VersionSpec]) -> Fallible<Vec<Image>> {
+    let yarn = session.current_platform()?.and_then(|image| image.yarn.clone());
+
+    let mut images = Vec::new();
+    for matching in versions {
+        let node = session.fetch_node(matching)?.into_version();
+        let mut image = node_image(&node);
+        image.yarn_str = yarn.as_ref().map(|version| version.to_string());
+        image.yarn = yarn.clone();
+        session.prepare_image(&image)?;
+        images.push(image);
+    }
+    Ok(images)
+}
+
+/// Plans fetching the Node version matching each entry of a matrix, without touching
+/// the filesystem.
+pub fn plan_images(
+    session: &Session,
+    versions: &[VersionSpec],
+) -> Fallible<(Vec<Version>, Plan)> {
+    let mut nodes = Vec::new();
+    let mut plan = Plan::new();
+    for matching in versions {
+        let (node, fetch) = session.plan_fetch_node(matching)?;
+        nodes.push(node);
+        plan.extend(fetch);
+    }
+    Ok((nodes, plan))
+}
+
+/// Runs a command with each platform image in turn, sharing the console.
+pub fn run_sequential(
+    images: &[Image],
+    exe: &str,
+    args: &[String],
+) -> Fallible<Vec<Outcome>> {
+    let mut outcomes = Vec::new();
+    for image in images {
+        println!("==> Node v{}", image.node);
+        let status = command_for(image, exe, args)?
+            .status()
+            .with_context(|error| spawn_error(exe, image, error))?;
+        outcomes.push(Outcome {
+            node: image.node.clone(),
+            code: status.code().unwrap_or(1),
+        });
+    }
+    Ok(outcomes)
+}
+
+/// Runs a command with every platform image at once. The output of each run is
+/// captured, and printed once the run finishes so the runs don't interleave.
+pub fn run_parallel(
+    images: &[Image],
+    exe: &str,
+    args: &[String],
+) -> Fallible<Vec<Outcome>> {
+    let mut running = Vec::new();
+    for image in images {
+        let child = command_for(image, exe, args)?
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|error| spawn_error(exe, image, error))?;
+        let node = image.node.clone();
+        running.push((node, thread::spawn(move || child.wait_with_output())));
+    }
+
+    let mut outcomes = Vec::new();
+    for (node, handle) in running {
+        // the thread only waits for the child, so it can't panic
+        let output: Output = handle
+            .join()
+            .expect("thread waiting for a run panicked")
+            .unknown()?;
+        println!("==> Node v{}", node);
+        io::stdout().write_all(&output.stdout).unknown()?;
+        io::stderr().write_all(&output.stderr).unknown()?;
+        outcomes.push(Outcome {
+            node,
+            code: output.status.code().unwrap_or(1),
+        });
+    }
+    Ok(outcomes)
+}
+
+/// Produces the exit code of a matrix run: 0 if every version passed, the exit code
+/// the failed versions share if they all failed the same way, and 1 if they failed
+/// with different exit codes.
+pub fn exit_code(outcomes: &[Outcome]) -> i32 {
+    let mut codes = outcomes
+        .iter()
+        .filter(|outcome| !outcome.passed())
+        .map(|outcome| outcome.code);
+    match codes.next() {
+        None => 0,
+        Some(first) => {
+            if codes.all(|code| code == first) {
+                first
+            } else {
+                1
+            }
+        }
+    }
+}
+
+/// Checks the outcomes of a matrix run, failing with a `MatrixFailedError` if any
+/// version failed.
+pub fn check(outcomes: &[Outcome]) -> Fallible<()> {
+    let failed = outcomes.iter().filter(|outcome| !outcome.passed()).count();
+    if failed > 0 {
+        throw!(MatrixFailedError {
+            failed,
+            total: outcomes.len(),
+            code: exit_code(outcomes),
+        });
+    }
+    Ok(())
+}
+
+fn spawn_error(exe: &str, image: &Image, error: &io::Error) -> MatrixSpawnError {
+    MatrixSpawnError {
+        command: exe.to_string(),
+        node: image.node.clone(),
+        error: error.to_string(),
+    }
+}
+
+#[cfg(unix)]
+fn command_for(image: &Image, exe: &str, args: &[String]) -> Fallible<Command> {
+    let mut command = Command::new(exe);
+    command.args(args);
+    command.env("PATH", image.path()?);
+    Ok(command)
+}
+
+#[cfg(windows)]
+fn command_for(image: &Image, exe: &str, args: &[String]) -> Fallible<Command> {
+    // Commands like `npm` are batch scripts on Windows, which only `cmd.exe` can run
+    // (see `tool::Script`).
+    let mut command = Command::new("cmd.exe");
+    command.arg("/C");
+    command.arg(exe);
+    command.args(args);
+    command.env("PATH", image.path()?);
+    Ok(command)
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    fn outcome(node: &str, code: i32) -> Outcome {
+        Outcome {
+            node: Version::parse(node).unwrap(),
+            code,
+        }
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let passed = vec![outcome("16.20.2", 0), outcome("18.18.2", 0)];
+        assert_eq!(exit_code(&passed), 0);
+        assert!(check(&passed).is_ok());
+
+        let same = vec![outcome("16.20.2", 2), outcome("18.18.2", 0), outcome("20.9.0", 2)];
+        assert_eq!(exit_code(&same), 2);
+
+        let mixed = vec![outcome("16.20.2", 2), outcome("18.18.2", 3)];
+        assert_eq!(exit_code(&mixed), 1);
+
+        let error = check(&same).unwrap_err();
+        let failed = error.downcast_ref::<MatrixFailedError>().unwrap();
+        assert_eq!((failed.failed, failed.total, failed.code), (2, 3, 2));
+    }
+}
@@ -1,82 +1,118 @@
+//! Delivers session events to their subscribers.
+
+use std::cmp;
+use std::fs::OpenOptions;
 use std::io::Write;
 use std::process::{Child, Command, Stdio};
-use std::vec::Vec;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use lazycell::LazyCell;
-use serde_json;
+use reqwest::header::ContentType;
 
-use event::Event;
+use config::Delivery;
+use fs::ensure_containing_dir_exists;
+use http;
+use logging;
 
-pub struct Monitor {
-    monitor_process: Option<Child>,
-}
+/// The number of milliseconds to wait, on exit, for deliveries to finish. Deliveries
+/// that take longer (such as a POST to an unresponsive server) are abandoned, so that
+/// a slow subscriber never holds up the tool being run.
+const DELIVERY_TIMEOUT_MILLIS: u64 = 1000;
 
-impl Monitor {
-    /// Returns the current monitor.
-    pub fn new(command: &str) -> Monitor {
-        Monitor {
-            monitor_process: spawn_process(command),
-        }
+/// Delivers payloads to subscribers, each on a thread of its own, waiting at most
+/// `DELIVERY_TIMEOUT_MILLIS` for them all to finish.
+pub(crate) fn deliver_all(deliveries: Vec<(Delivery, String)>) {
+    let count = deliveries.len();
+    let (sender, receiver) = mpsc::channel();
+    for (delivery, payload) in deliveries {
+        let sender = sender.clone();
+        thread::spawn(move || {
+            if let Err(error) = deliver(&delivery, &payload) {
+                logging::warning(&format!(
+                    "could not deliver events to {:?}: {}",
+                    delivery, error
+                ));
+            }
+            let _ = sender.send(());
+        });
     }
 
-    /// send event to the monitor process
-    // if plugin command is not configured, this is a no-op
-    pub fn send_events(&mut self, events: &Vec<Event>) -> () {
-        if let Some(ref mut child_process) = self.monitor_process {
-            let p_stdin = child_process.stdin.as_mut().unwrap();
+    let deadline = Instant::now() + Duration::from_millis(DELIVERY_TIMEOUT_MILLIS);
+    for _ in 0..count {
+        let now = Instant::now();
+        if now >= deadline || receiver.recv_timeout(deadline - now).is_err() {
+            break;
+        }
+    }
+}
 
-            let json = serde_json::to_string(&events);
-            match json {
-                Ok(data) => {
-                    // FIXME: tighten up this error message
-                    write!(p_stdin, "{}", data).expect("Writing data to plugin failed!");
-                }
-                Err(error) => {
-                    // FIXME: tighten up this error message
-                    eprintln!("There was a problem serializing the JSON data: {:?}", error);
-                }
-            };
+fn deliver(delivery: &Delivery, payload: &str) -> Result<(), String> {
+    match delivery {
+        &Delivery::Command(ref command) => {
+            let mut child = spawn_process(command)?;
+            let stdin = child.stdin.as_mut().unwrap();
+            write!(stdin, "{}", payload).map_err(|error| error.to_string())
+        }
+        &Delivery::File(ref file) => {
+            ensure_containing_dir_exists(&file).map_err(|error| error.to_string())?;
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(file)
+                .and_then(|mut file| file.write_all(payload.as_bytes()))
+                .map_err(|error| error.to_string())
+        }
+        &Delivery::Post(ref url) => {
+            http::check_connect(url.as_str()).map_err(|error| error.to_string())?;
+            let read_timeout = http::read_timeout().map_err(|error| error.to_string())?;
+            let timeout = Duration::from_millis(DELIVERY_TIMEOUT_MILLIS);
+            let client = http::client_builder()
+                .map_err(|error| error.to_string())?
+                .timeout(cmp::min(timeout, read_timeout))
+                .build()
+                .map_err(|error| error.to_string())?;
+            let response = client
+                .post(url.as_str())
+                .header(ContentType::json())
+                .body(payload.to_string())
+                .send()
+                .map_err(|error| error.to_string())?;
+            if response.status().is_success() {
+                Ok(())
+            } else {
+                Err(format!("the server responded with {}", response.status()))
+            }
         }
     }
 }
 
-pub struct LazyMonitor {
-    monitor: LazyCell<Monitor>,
+fn spawn_process(command: &str) -> Result<Child, String> {
+    let mut words = command.split(" ");
+    let executable = words.next().unwrap_or("");
+    Command::new(executable)
+        .args(words)
+        .stdin(Stdio::piped()) // JSON data is sent over stdin
+        // .stdout(Stdio::piped()) // let the plugin write to stdout for now
+        .spawn()
+        .map_err(|error| format!("error running plugin command '{}': {}", command, error))
 }
 
-impl LazyMonitor {
-    /// Constructs a new `LazyMonitor`.
-    pub fn new() -> LazyMonitor {
-        LazyMonitor {
-            monitor: LazyCell::new(),
-        }
-    }
+#[cfg(test)]
+pub mod tests {
 
-    /// Forces creating a monitor and returns an immutable reference to it.
-    pub fn get(&self, command: &str) -> &Monitor {
-        self.monitor.borrow_with(|| Monitor::new(command))
-    }
+    use super::deliver_all;
+    use config::Delivery;
+    use std::fs::read_to_string;
+    use tempfile::tempdir;
 
-    /// Forces creating a monitor and returns a mutable reference to it.
-    pub fn get_mut(&mut self, command: &str) -> &mut Monitor {
-        self.monitor.borrow_mut_with(|| Monitor::new(command))
-    }
-}
+    #[test]
+    fn test_deliver_to_file() {
+        let dir = tempdir().expect("Could not create temporary directory");
+        let file = dir.path().join("logs").join("events.jsonl");
 
-fn spawn_process(command: &str) -> Option<Child> {
-    command.split(" ").take(1).next().and_then(|executable| {
-        let child = Command::new(executable)
-                    .args(command.split(" ").skip(1))
-                    .stdin(Stdio::piped()) // JSON data is sent over stdin
-                    // .stdout(Stdio::piped()) // let the plugin write to stdout for now
-                    .spawn();
-        match child {
-            Err(err) => {
-                eprintln!("Error running plugin command: '{}'", command);
-                eprintln!("{}", err);
-                None
-            }
-            Ok(c) => Some(c),
-        }
-    })
+        deliver_all(vec![(Delivery::File(file.clone()), "{\"a\":1}\n".to_string())]);
+        deliver_all(vec![(Delivery::File(file.clone()), "{\"b\":2}\n".to_string())]);
+        assert_eq!(read_to_string(&file).unwrap(), "{\"a\":1}\n{\"b\":2}\n");
+    }
 }
@@ -0,0 +1,275 @@
+//! Provides a comparison of the tool versions in use with the newest releases, for
+//! `notion outdated`.
+
+use std::fmt::{self, Display, Formatter};
+
+use semver::{Version, VersionReq};
+
+use catalog::{public_node_versions, public_yarn_versions, PublicNodeVersion};
+use manifest::Pin;
+use notion_fail::Fallible;
+use package::published_versions;
+use registry::Registries;
+use session::Session;
+
+/// Where a compared version comes from.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Source {
+    /// A pin in the current project's toolchain.
+    Project,
+    /// The user's default toolchain.
+    User,
+    /// A package tool installed by `notion install`.
+    Package,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let name = match self {
+            &Source::Project => "project",
+            &Source::User => "user",
+            &Source::Package => "package",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A tool version compared with the releases available.
+#[derive(Serialize, Debug)]
+pub struct Comparison {
+    /// The name of the tool: `node`, `yarn`, or the name of a package tool.
+    pub tool: String,
+    /// Where the version comes from.
+    pub source: Source,
+    /// The version in use.
+    pub current: String,
+    /// The newest release the tool's pin or range allows, or for exact versions the
+    /// newest release of the same major version.
+    pub wanted: String,
+    /// The newest release, which for Node versions in an LTS line is the newest LTS
+    /// release.
+    pub latest: String,
+}
+
+impl Comparison {
+    fn new(
+        tool: &str,
+        source: Source,
+        current: &Version,
+        wanted: &Version,
+        latest: &Version,
+    ) -> Self {
+        Comparison {
+            tool: tool.to_string(),
+            source,
+            current: current.to_string(),
+            wanted: wanted.to_string(),
+            latest: latest.to_string(),
+        }
+    }
+
+    /// Tests whether a newer version is wanted or available.
+    pub fn is_outdated(&self) -> bool {
+        self.current != self.wanted || self.current != self.latest
+    }
+}
+
+/// Compares the current project's pins, the user toolchain, and the installed package
+/// tools with the newest releases available.
+pub fn compare(session: &mut Session) -> Fallible<Vec<Comparison>> {
+    // Each entry is a source, the version in use, and the range pinned, if any.
+    let mut node: Vec<(Source, Version, Option<VersionReq>)> = Vec::new();
+    let mut yarn: Vec<(Source, Version, Option<VersionReq>)> = Vec::new();
+
+    if let Some(project) = session.project() {
+        if let Some(image) = session.project_platform()? {
+            let toolchain = project.toolchain();
+            let node_range = toolchain.and_then(|toolchain| range_of(&toolchain.node));
+            node.push((Source::Project, image.node.clone(), node_range));
+            if let Some(ref version) = image.yarn {
+                let yarn_range = toolchain
+                    .and_then(|toolchain| toolchain.yarn.as_ref())
+                    .and_then(range_of);
+                yarn.push((Source::Project, version.clone(), yarn_range));
+            }
+        }
+    }
+
+    {
+        let catalog = session.catalog()?;
+        if let Some(ref version) = catalog.node.default {
+            node.push((Source::User, version.clone(), None));
+        }
+        if let Some(ref version) = catalog.yarn.default {
+            yarn.push((Source::User, version.clone(), None));
+        }
+    }
+
+    let mut comparisons = Vec::new();
+
+    if !node.is_empty() {
+        let public = public_node_versions()?;
+        for (source, current, range) in node {
+            let (wanted, latest) = compare_node(&current, range.as_ref(), &public);
+            comparisons.push(Comparison::new("node", source, &current, &wanted, &latest));
+        }
+    }
+
+    if !yarn.is_empty() {
+        let public = public_yarn_versions()?;
+        for (source, current, range) in yarn {
+            let wanted = newest(
+                &current,
+                public.iter().filter(|v| allows(range.as_ref(), &current, v)),
+            );
+            let latest = newest(&current, public.iter());
+            comparisons.push(Comparison::new("yarn", source, &current, &wanted, &latest));
+        }
+    }
+
+    let packages: Vec<_> = session
+        .catalog()?
+        .packages
+        .iter()
+        .map(|(name, tool)| (name.clone(), tool.clone()))
+        .collect();
+    let registries = Registries::current(session.config()?)?;
+    for (name, tool) in packages {
+        let published = published_versions(&name, &registries)?;
+        let wanted = published
+            .matching(tool.requested.as_ref().map(|requested| requested.as_str()))
+            .cloned()
+            .unwrap_or_else(|| tool.version.clone());
+        let latest = newest(&tool.version, published.latest().into_iter());
+        comparisons.push(Comparison::new(
+            &name,
+            Source::Package,
+            &tool.version,
+            &wanted,
+            &latest,
+        ));
+    }
+
+    Ok(comparisons)
+}
+
+/// Produces the range a pin allows, if it is not an exact version.
+fn range_of(pin: &Pin) -> Option<VersionReq> {
+    match pin {
+        &Pin::Exact(_) => None,
+        &Pin::Range(ref range) => Some(range.clone()),
+    }
+}
+
+/// Tests whether an upgrade to a version is wanted: one the range allows, or one of
+/// the same major version as the current version if there is no range.
+fn allows(range: Option<&VersionReq>, current: &Version, version: &Version) -> bool {
+    match range {
+        Some(range) => range.matches(version),
+        None => version.major == current.major && version.pre.is_empty(),
+    }
+}
+
+/// Produces the newest of the current version and some releases.
+fn newest<'a, I>(current: &Version, releases: I) -> Version
+where
+    I: Iterator<Item = &'a Version>,
+{
+    releases
+        .filter(|version| version.pre.is_empty())
+        .fold(current.clone(), |best, version| {
+            if *version > best {
+                version.clone()
+            } else {
+                best
+            }
+        })
+}
+
+/// Produces the wanted and latest Node versions for a Node version in use. The latest
+/// version of a Node version in an LTS line is the newest LTS release, since moving to
+/// a newer current release would leave LTS.
+fn compare_node(
+    current: &Version,
+    range: Option<&VersionReq>,
+    public: &[PublicNodeVersion],
+) -> (Version, Version) {
+    let lts = public
+        .iter()
+        .any(|available| available.version == *current && available.lts.is_some());
+
+    let wanted = newest(
+        current,
+        public
+            .iter()
+            .map(|available| &available.version)
+            .filter(|version| allows(range, current, version)),
+    );
+    let latest = newest(
+        current,
+        public
+            .iter()
+            .filter(|available| !lts || available.lts.is_some())
+            .map(|available| &available.version),
+    );
+    (wanted, latest)
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    fn version(s: &str) -> Version {
+        Version::parse(s).unwrap()
+    }
+
+    fn public(versions: &[(&str, Option<&str>)]) -> Vec<PublicNodeVersion> {
+        versions
+            .iter()
+            .map(|&(v, lts)| PublicNodeVersion {
+                version: version(v),
+                lts: lts.map(|codename| codename.to_string()),
+                npm: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compare_node() {
+        let public = public(&[
+            ("21.1.0", None),
+            ("20.9.0", Some("Iron")),
+            ("20.8.0", None),
+            ("18.18.2", Some("Hydrogen")),
+            ("18.17.0", Some("Hydrogen")),
+        ]);
+
+        // an LTS version is compared with the newest LTS release
+        assert_eq!(
+            compare_node(&version("18.17.0"), None, &public),
+            (version("18.18.2"), version("20.9.0"))
+        );
+
+        // other versions are compared with the newest release
+        assert_eq!(
+            compare_node(&version("20.8.0"), None, &public),
+            (version("20.9.0"), version("21.1.0"))
+        );
+
+        // ranges are compared with the newest release they allow
+        let range = VersionReq::parse(">=18").unwrap();
+        assert_eq!(
+            compare_node(&version("18.17.0"), Some(&range), &public),
+            (version("21.1.0"), version("20.9.0"))
+        );
+    }
+
+    #[test]
+    fn test_newest() {
+        let releases = vec![version("1.22.19"), version("2.0.0-rc.1"), version("1.22.10")];
+        assert_eq!(newest(&version("1.22.10"), releases.iter()), version("1.22.19"));
+        assert_eq!(newest(&version("1.23.0"), releases.iter()), version("1.23.0"));
+    }
+}
@@ -0,0 +1,514 @@
+//! Provides types for installing npm packages as package tools: packages whose
+//! executables are installed into a directory of their own and shimmed like the
+//! tools Notion manages.
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{create_dir_all, remove_dir_all, rename, File};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+use readext::ReadExt;
+use reqwest;
+use semver::Version;
+use serde_json;
+
+use arch::Arch;
+use distro::staging_dir;
+use fs::CreateDirError;
+use http;
+use image::Image;
+use notion_fail::{Fallible, NotionError, NotionFail, ResultExt};
+use path;
+use registry::{redact_urls, Registries};
+use style::progress_spinner;
+use timing::{self, Phase};
+use version::VersionSpec;
+
+#[cfg(feature = "mock-network")]
+use mockito;
+
+cfg_if! {
+    if #[cfg(feature = "mock-network")] {
+        pub(crate) fn public_package_registry() -> String {
+            format!("{}/npm-registry", mockito::SERVER_URL)
+        }
+    } else {
+        /// Returns the URL of the public npm registry.
+        pub(crate) fn public_package_registry() -> String {
+            "https://registry.npmjs.org".to_string()
+        }
+    }
+}
+
+/// Produces the URL of a package version's tarball on the public npm registry.
+pub(crate) fn public_tarball_url(name: &str, version: &Version) -> String {
+    let basename = name.rsplit('/').next().unwrap_or(name);
+    format!(
+        "{}/{}/-/{}-{}.tgz",
+        public_package_registry(),
+        name,
+        basename,
+        version
+    )
+}
+
+/// Thrown when a package spec names no package.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "invalid package: `{}`", spec)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct InvalidPackageSpecError {
+    spec: String,
+}
+
+/// Thrown when the registry document of a package could not be fetched.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not fetch {} from the npm registry\n{}", package, error)]
+#[notion_fail(code = "NetworkError")]
+pub(crate) struct PackageRegistryError {
+    package: String,
+    error: String,
+}
+
+/// Thrown when the Node version a package is installed with has no npm.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "npm was not found for Node v{}", version)]
+#[notion_fail(code = "ExecutableNotFound")]
+pub(crate) struct NpmNotFoundError {
+    version: String,
+}
+
+/// Thrown when npm fails to install a package.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not install {}: npm {}
+
+{}", package, error, output)]
+#[notion_fail(code = "ExecutionFailure")]
+pub(crate) struct NpmInstallError {
+    package: String,
+    error: String,
+    output: String,
+}
+
+/// Thrown when an installed package's `package.json` can't be read.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not read the package.json of {}: {}", package, error)]
+#[notion_fail(code = "FileSystemError")]
+pub(crate) struct PackageManifestError {
+    package: String,
+    error: String,
+}
+
+/// Thrown when a package tool that isn't installed is named.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "No package tool named {} is installed
+See `notion list packages` for the installed package tools", name)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct UnknownPackageError {
+    name: String,
+}
+
+impl UnknownPackageError {
+    pub(crate) fn new(name: &str) -> Self {
+        UnknownPackageError {
+            name: name.to_string(),
+        }
+    }
+}
+
+/// Thrown when an installed package has no executables to shim.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} has no executables to install", package)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct NoPackageBinsError {
+    package: String,
+}
+
+/// A package to install, as named on the command line: `typescript`,
+/// `typescript@5`, or `@scope/name@^1.2`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageSpec {
+    /// The name of the package.
+    pub name: String,
+    /// The version, range, or tag to install, which npm resolves.
+    pub version: Option<String>,
+}
+
+impl FromStr for PackageSpec {
+    type Err = NotionError;
+
+    fn from_str(s: &str) -> Fallible<Self> {
+        // A scoped package name starts with '@', so the version separator is the
+        // first '@' after it.
+        let split = s.char_indices()
+            .skip(1)
+            .find(|&(_, c)| c == '@')
+            .map(|(index, _)| index);
+        let (name, version) = match split {
+            Some(index) => (&s[..index], Some(&s[index + 1..])),
+            None => (s, None),
+        };
+
+        if name.is_empty() || name == "@" || version == Some("") {
+            throw!(InvalidPackageSpecError {
+                spec: s.to_string(),
+            });
+        }
+
+        Ok(PackageSpec {
+            name: name.to_string(),
+            version: version.map(|version| version.to_string()),
+        })
+    }
+}
+
+impl Display for PackageSpec {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.version {
+            Some(ref version) => write!(f, "{}@{}", self.name, version),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// An installed package tool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageTool {
+    /// The installed version of the package.
+    pub version: Version,
+    /// The version, range, or tag the package was installed with, which upgrades
+    /// resolve again.
+    pub requested: Option<String>,
+    /// The version of Node the package was installed with, which its executables
+    /// always run with.
+    pub node: Version,
+    /// The version of npm the package was installed with, if known.
+    pub npm: Option<Version>,
+    /// The names of the package's executables.
+    pub bins: Vec<String>,
+}
+
+impl PackageTool {
+    /// Produces the platform image the package's executables run with: the Node
+    /// version it was installed with, whatever the current project selects, so that
+    /// native modules built for that version keep working.
+    pub fn image(&self) -> Image {
+        node_image(&self.node)
+    }
+
+    /// Produces the spec that installs the newest version of the package matching
+    /// the version it was installed with.
+    pub fn upgrade_spec(&self, name: &str) -> PackageSpec {
+        PackageSpec {
+            name: name.to_string(),
+            version: self.requested.clone(),
+        }
+    }
+}
+
+/// Produces the platform image package tools run with for a Node version.
+pub fn node_image(node: &Version) -> Image {
+    Image {
+        node: node.clone(),
+        node_str: node.to_string(),
+        node_arch: Arch::native(),
+        yarn: None,
+        yarn_str: None,
+    }
+}
+
+/// The versions of a package published to the npm registry.
+pub struct Published {
+    /// The published versions, newest first.
+    pub versions: Vec<Version>,
+    /// The versions the registry's dist-tags (like `latest`) point to.
+    pub tags: BTreeMap<String, Version>,
+}
+
+impl Published {
+    /// Produces the version the registry's `latest` tag points to.
+    pub fn latest(&self) -> Option<&Version> {
+        self.tags.get("latest")
+    }
+
+    /// Produces the version npm installs for the version, range, or tag a package
+    /// tool was installed with: the newest matching release, or the tagged version.
+    pub fn matching(&self, requested: Option<&str>) -> Option<&Version> {
+        match requested {
+            None => self.latest(),
+            Some(tag) if self.tags.contains_key(tag) => self.tags.get(tag),
+            Some(range) => match VersionSpec::parse_requirements(range) {
+                Ok(range) => self.versions.iter().find(|version| range.matches(version)),
+                Err(_) => None,
+            },
+        }
+    }
+}
+
+/// The parts of a registry document that are needed to list published versions.
+#[derive(Deserialize)]
+struct Packument {
+    #[serde(rename = "dist-tags", default)]
+    dist_tags: BTreeMap<String, String>,
+    #[serde(default)]
+    versions: BTreeMap<String, serde_json::Value>,
+}
+
+impl Packument {
+    fn into_published(self) -> Published {
+        let mut versions: Vec<Version> = self.versions
+            .keys()
+            .filter_map(|version| Version::parse(version).ok())
+            .collect();
+        versions.sort_by(|a, b| b.cmp(a));
+        let tags = self.dist_tags
+            .into_iter()
+            .filter_map(|(tag, version)| Version::parse(&version).ok().map(|v| (tag, v)))
+            .collect();
+        Published { versions, tags }
+    }
+}
+
+/// Fetches the versions of a package published to its npm registry, with the auth
+/// token for that registry, if there is one.
+pub fn published_versions(name: &str, registries: &Registries) -> Fallible<Published> {
+    let url = registries.packument_url(name);
+    let failed = |error: String| PackageRegistryError {
+        package: name.to_string(),
+        error: registries.redact(&error),
+    };
+
+    let spinner = progress_spinner(&format!("Fetching registry: {}", redact_urls(&url)));
+    let packument: Packument = timing::time(Phase::IndexFetch, || {
+        let mut response = http::get_with(&url, |request| registries.authorize(request, &url))?
+            .with_context(|error: &reqwest::Error| failed(error.to_string()))?;
+        if !response.status().is_success() {
+            throw!(failed(response.status().to_string()));
+        }
+        response.json().unknown()
+    })?;
+    spinner.finish_and_clear();
+    Ok(packument.into_published())
+}
+
+/// The parts of a `package.json` that are needed to record a package tool.
+#[derive(Deserialize)]
+struct Manifest {
+    version: String,
+    #[serde(default)]
+    bin: Option<Bin>,
+}
+
+/// The `bin` field of a `package.json`, which is either the path of a single
+/// executable named after the package or a map from names to paths.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Bin {
+    Single(String),
+    Named(BTreeMap<String, String>),
+}
+
+impl Bin {
+    fn names(self, package: &str) -> Vec<String> {
+        match self {
+            // A scoped package's single executable is named without the scope.
+            Bin::Single(_) => vec![package.rsplit('/').next().unwrap_or(package).to_string()],
+            Bin::Named(map) => map.into_iter().map(|(name, _)| name).collect(),
+        }
+    }
+}
+
+/// Installs a package with the npm of a platform image, which must already be
+/// prepared, into the package's own directory, replacing any previous install. npm
+/// reads the user's `.npmrc` itself, and is given the configured registries as its
+/// global configuration.
+pub fn install(
+    spec: &PackageSpec,
+    image: &Image,
+    registries: &Registries,
+) -> Fallible<PackageTool> {
+    let path_var = image.path()?;
+    let npm = match path::find_executable(OsStr::new("npm"), &path_var) {
+        Some(npm) => npm,
+        None => throw!(NpmNotFoundError {
+            version: image.node_str.clone(),
+        }),
+    };
+
+    let staging = staging_dir()?;
+    let failed = |error: String, output: String| NpmInstallError {
+        package: spec.to_string(),
+        error,
+        output: registries.redact(&output),
+    };
+
+    // The configuration is kept out of the staging directory, which becomes the
+    // package's directory.
+    let npmrc_dir = staging_dir()?;
+    let mut command = Command::new(&npm);
+    command
+        .arg("install")
+        .arg("--global")
+        .arg("--prefix")
+        .arg(staging.path())
+        .arg(spec.to_string())
+        .env("PATH", &path_var);
+    if let Some(npmrc) = registries.write_npmrc(npmrc_dir.path())? {
+        command.env("NPM_CONFIG_GLOBALCONFIG", npmrc);
+    }
+    let output = command
+        .output()
+        .with_context(|error| failed(error.to_string(), String::new()))?;
+    if !output.status.success() {
+        let error = match output.status.code() {
+            Some(code) => format!("exited with status {}", code),
+            None => "was terminated by a signal".to_string(),
+        };
+        throw!(failed(error, String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+
+    let manifest = read_manifest(&spec.name, &modules_dir(staging.path()).join(&spec.name))?;
+    let version = Version::parse(manifest.version.trim()).unknown()?;
+    let bins = match manifest.bin {
+        Some(bin) => bin.names(&spec.name),
+        None => Vec::new(),
+    };
+    if bins.is_empty() {
+        throw!(NoPackageBinsError {
+            package: spec.name.clone(),
+        });
+    }
+
+    replace_dir(staging.path(), &path::package_dir(&spec.name)?)?;
+
+    Ok(PackageTool {
+        version,
+        requested: spec.version.clone(),
+        node: image.node.clone(),
+        npm: npm_version(&npm, &path_var),
+        bins,
+    })
+}
+
+/// Asks an npm executable for its version, which may differ from the one bundled
+/// with its Node version if npm has been upgraded.
+fn npm_version(npm: &Path, path_var: &OsStr) -> Option<Version> {
+    let output = Command::new(npm)
+        .arg("--version")
+        .env("PATH", path_var)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Version::parse(String::from_utf8_lossy(&output.stdout).trim()).ok()
+}
+
+/// Moves a newly installed package directory into place, so that a package tool
+/// being replaced keeps working until the new install is complete, and is restored
+/// if the new one can't be moved into place.
+fn replace_dir(src: &Path, dest: &Path) -> Fallible<()> {
+    if let Some(parent) = dest.parent() {
+        create_dir_all(parent)
+            .with_context(CreateDirError::for_dir(parent.to_string_lossy().to_string()))?;
+    }
+
+    if !dest.exists() {
+        return rename(src, dest).unknown();
+    }
+
+    // The previous install is moved aside into a staging directory, which deletes
+    // it once the new install is in place.
+    let previous = staging_dir()?;
+    let aside = previous.path().join("previous");
+    rename(dest, &aside).unknown()?;
+    if let Err(error) = rename(src, dest) {
+        let _ = rename(&aside, dest);
+        let _ = remove_dir_all(src);
+        return Err(error).unknown();
+    }
+    Ok(())
+}
+
+/// Produces the directory npm installs global packages under for a prefix.
+fn modules_dir(prefix: &Path) -> PathBuf {
+    if cfg!(windows) {
+        prefix.join("node_modules")
+    } else {
+        prefix.join("lib").join("node_modules")
+    }
+}
+
+fn read_manifest(package: &str, dir: &Path) -> Fallible<Manifest> {
+    let failed = |error: String| PackageManifestError {
+        package: package.to_string(),
+        error,
+    };
+    let src = File::open(dir.join("package.json"))
+        .and_then(|mut file| file.read_into_string())
+        .with_context(|error| failed(error.to_string()))?;
+    serde_json::from_str(&src).with_context(|error| failed(error.to_string()))
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    fn spec(name: &str, version: Option<&str>) -> PackageSpec {
+        PackageSpec {
+            name: name.to_string(),
+            version: version.map(|version| version.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_parse_spec() {
+        assert_eq!("typescript".parse::<PackageSpec>().unwrap(), spec("typescript", None));
+        assert_eq!(
+            "typescript@5".parse::<PackageSpec>().unwrap(),
+            spec("typescript", Some("5"))
+        );
+        assert_eq!(
+            "@vue/cli@^5.0".parse::<PackageSpec>().unwrap(),
+            spec("@vue/cli", Some("^5.0"))
+        );
+        assert_eq!("@vue/cli".parse::<PackageSpec>().unwrap(), spec("@vue/cli", None));
+        assert!("".parse::<PackageSpec>().is_err());
+        assert!("@".parse::<PackageSpec>().is_err());
+        assert!("typescript@".parse::<PackageSpec>().is_err());
+    }
+
+    #[test]
+    fn test_published_matching() {
+        let packument: Packument = serde_json::from_str(
+            r#"{
+                "dist-tags": { "latest": "5.4.2", "next": "5.5.0-beta" },
+                "versions": { "4.9.5": {}, "5.1.6": {}, "5.4.2": {}, "5.5.0-beta": {} }
+            }"#,
+        ).unwrap();
+        let published = packument.into_published();
+        let version = |s: &str| Version::parse(s).unwrap();
+
+        assert_eq!(published.versions[0], version("5.5.0-beta"));
+        assert_eq!(published.matching(None), Some(&version("5.4.2")));
+        assert_eq!(published.matching(Some("next")), Some(&version("5.5.0-beta")));
+        assert_eq!(published.matching(Some("4")), Some(&version("4.9.5")));
+        assert_eq!(published.matching(Some("^5.1")), Some(&version("5.4.2")));
+        assert_eq!(published.matching(Some("6")), None);
+    }
+
+    #[test]
+    fn test_bin_names() {
+        let single: Manifest =
+            serde_json::from_str(r#"{ "version": "1.0.0", "bin": "cli.js" }"#).unwrap();
+        assert_eq!(single.bin.unwrap().names("@scope/tool"), vec!["tool"]);
+
+        let named: Manifest = serde_json::from_str(
+            r#"{ "version": "5.1.6", "bin": { "tsserver": "bin/tsserver", "tsc": "bin/tsc" } }"#,
+        ).unwrap();
+        assert_eq!(named.bin.unwrap().names("typescript"), vec!["tsc", "tsserver"]);
+    }
+}
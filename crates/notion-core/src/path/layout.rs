@@ -0,0 +1,187 @@
+//! Provides the `Layout` type, which determines the root directories that all
+//! of Notion's paths are resolved against.
+
+use std::env;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+use lazycell::AtomicLazyCell;
+use toml;
+
+use config::serial;
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+
+use super::default_layout;
+
+/// The environment variable that relocates all of Notion's files.
+const NOTION_HOME: &'static str = "NOTION_HOME";
+
+/// The environment variable that relocates the shim directory.
+const NOTION_SHIM_DIR: &'static str = "NOTION_SHIM_DIR";
+
+/// The environment variable that locates the system layer.
+const NOTION_SYSTEM_DIR: &'static str = "NOTION_SYSTEM_DIR";
+
+/// The name of the marker file that makes the directory it is in a portable
+/// installation.
+const PORTABLE_FILE: &'static str = "portable";
+
+/// The name of the user configuration file within the user root.
+pub(crate) const CONFIG_FILE: &'static str = "config.toml";
+
+static LAYOUT: AtomicLazyCell<Layout> = AtomicLazyCell::NONE;
+
+/// Thrown when the `[layout]` section of the user configuration file could not be read.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "could not read layout settings from {}: {}", file, error)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct LayoutConfigError {
+    file: String,
+    error: String,
+}
+
+/// The root directories of a Notion installation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    /// The directory downloaded archives, installed versions, and staging
+    /// directories are kept in.
+    pub data_root: PathBuf,
+    /// The directory the user configuration file and catalog are kept in.
+    pub user_root: PathBuf,
+    /// The directory downloaded archives and indexes are cached in.
+    pub cache_root: PathBuf,
+    /// The directory Notion's own executables are installed in.
+    pub install_root: PathBuf,
+    /// The directory shims are created in.
+    pub shim_dir: PathBuf,
+    /// The system layer: a directory laid out like a data root, with a catalog of its
+    /// own, that administrators install Node and Yarn versions into for every user of
+    /// the machine. Notion only ever reads from it.
+    pub system_root: Option<PathBuf>,
+    /// Whether this is a portable installation, which keeps everything next to the
+    /// `notion` executable and links its files with relative paths, so that it can be
+    /// moved (to another machine, say) as a whole.
+    pub portable: bool,
+}
+
+impl Layout {
+    /// Constructs a layout that keeps everything under a single directory.
+    pub fn in_home(home: PathBuf) -> Self {
+        Layout {
+            data_root: home.clone(),
+            user_root: home.clone(),
+            cache_root: home.join("cache"),
+            install_root: home.clone(),
+            shim_dir: home.join("bin"),
+            system_root: None,
+            portable: false,
+        }
+    }
+
+    /// Determines the current layout. `NOTION_HOME` replaces the platform's default
+    /// layout with a single directory, as does a portable installation (see
+    /// `portable_root`) when `NOTION_HOME` is not set. The `[layout]` section of the
+    /// user configuration file can relocate the data root and the shim directory, and
+    /// `NOTION_SHIM_DIR` takes precedence over the configured shim directory. The
+    /// system layer is taken from `NOTION_SYSTEM_DIR`, or else the `system-dir`
+    /// setting, or else the platform's default system directory if it exists.
+    fn current() -> Fallible<Self> {
+        let portable = match env::var_os(NOTION_HOME) {
+            Some(_) => None,
+            None => portable_root(),
+        };
+        let home = env::var_os(NOTION_HOME).map(PathBuf::from).or_else(|| portable.clone());
+
+        let mut layout = match home {
+            Some(ref home) => Layout::in_home(home.clone()),
+            None => default_layout()?,
+        };
+        layout.portable = portable.is_some();
+
+        if let Some(config) = read_layout_config(&layout.user_root.join(CONFIG_FILE))? {
+            if let (None, Some(data_dir)) = (home, config.data_dir) {
+                // the cache moves with the data root, unless it is kept apart from it
+                if layout.cache_root == layout.data_root.join("cache") {
+                    layout.cache_root = data_dir.join("cache");
+                }
+                layout.data_root = data_dir;
+            }
+            if let Some(shim_dir) = config.shim_dir {
+                layout.shim_dir = shim_dir;
+            }
+            if let Some(system_dir) = config.system_dir {
+                layout.system_root = Some(system_dir);
+            }
+        }
+
+        if let Some(shim_dir) = env::var_os(NOTION_SHIM_DIR) {
+            layout.shim_dir = PathBuf::from(shim_dir);
+        }
+        if let Some(system_dir) = env::var_os(NOTION_SYSTEM_DIR) {
+            layout.system_root = Some(PathBuf::from(system_dir));
+        }
+
+        Ok(layout)
+    }
+}
+
+/// Finds the portable installation the running executable belongs to: the directory
+/// it is in, or the one above it for the shims in `bin/`, if that directory has a
+/// `portable` marker file.
+fn portable_root() -> Option<PathBuf> {
+    let exe = env::current_exe().ok()?;
+    exe.ancestors()
+        .skip(1)
+        .take(2)
+        .find(|dir| dir.join(PORTABLE_FILE).is_file())
+        .map(Path::to_path_buf)
+}
+
+/// Reads the `[layout]` section of a user configuration file, if the file exists and
+/// has one. This reads the file directly rather than through `config::Config`, since
+/// locating the rest of the configuration depends on the layout.
+pub(crate) fn read_layout_config(file: &Path) -> Fallible<Option<serial::LayoutConfig>> {
+    if !file.is_file() {
+        return Ok(None);
+    }
+
+    let error = |error: String| LayoutConfigError {
+        file: file.to_string_lossy().to_string(),
+        error,
+    };
+
+    let src = read_to_string(file).with_context(|e| error(e.to_string()))?;
+    let config: serial::Config = toml::from_str(&src).with_context(|e| error(e.to_string()))?;
+    Ok(config.layout)
+}
+
+/// Produces the current layout, which is determined the first time it is needed and
+/// then reused for the life of the process.
+pub fn layout() -> Fallible<&'static Layout> {
+    if let Some(layout) = LAYOUT.borrow() {
+        return Ok(layout);
+    }
+
+    // If another thread got here first, its layout is kept; both were determined
+    // from the same environment.
+    let _ = LAYOUT.fill(Layout::current()?);
+    Ok(LAYOUT.borrow().unwrap())
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::Layout;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_layout_in_home() {
+        let layout = Layout::in_home(PathBuf::from("/shared/notion"));
+        assert_eq!(layout.data_root, PathBuf::from("/shared/notion"));
+        assert_eq!(layout.user_root, PathBuf::from("/shared/notion"));
+        assert_eq!(layout.cache_root, PathBuf::from("/shared/notion/cache"));
+        assert_eq!(layout.shim_dir, PathBuf::from("/shared/notion/bin"));
+        assert_eq!(layout.system_root, None);
+        assert!(!layout.portable);
+    }
+}
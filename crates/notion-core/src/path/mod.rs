@@ -1,6 +1,12 @@
 //! Provides functions for determining the paths of files and directories
 //! in a standard Notion layout.
 
+mod layout;
+#[cfg(target_os = "linux")]
+mod xdg;
+
+pub use self::layout::{layout, Layout};
+
 cfg_if! {
     if #[cfg(feature = "universal-docs")] {
         #[doc(cfg(unix))]
@@ -19,12 +25,143 @@ cfg_if! {
     }
 }
 
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::ffi::OsStr;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use arch::{Arch, Libc};
+use notion_fail::Fallible;
+
 pub fn node_archive_file(version: &str) -> String {
-    format!("{}.{}", node_archive_root_dir(version), archive_extension())
+    node_archive_file_for(version, Arch::native())
 }
 
 pub fn node_archive_root_dir(version: &str) -> String {
-    format!("node-v{}-{}-{}", version, OS, ARCH)
+    node_archive_root_dir_for(version, Arch::native())
+}
+
+pub fn node_archive_file_for(version: &str, arch: Arch) -> String {
+    format!("{}.{}", node_archive_root_dir_for(version, arch), archive_extension())
+}
+
+pub fn node_archive_root_dir_for(version: &str, arch: Arch) -> String {
+    format!(
+        "node-v{}-{}-{}{}",
+        version,
+        OS,
+        arch,
+        Libc::native().archive_suffix()
+    )
+}
+
+/// Produces the name that state kept about the project rooted at `root`, like its
+/// cached platform, is stored under.
+pub fn project_key(root: &Path) -> String {
+    let mut hasher = DefaultHasher::new();
+    root.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Produces the name a Node version is installed under in `node_versions_dir`.
+/// Builds for the native architecture use the bare version; builds for other
+/// architectures are suffixed with the architecture, so both can be installed.
+pub fn node_version_key(version: &str, arch: Arch) -> String {
+    if arch == Arch::native() {
+        version.to_string()
+    } else {
+        format!("{}-{}", version, arch)
+    }
+}
+
+/// Produces the directory of the system layer (see `Layout::system_root`), if any.
+pub fn system_root() -> Fallible<Option<PathBuf>> {
+    Ok(layout()?.system_root.clone())
+}
+
+pub fn system_catalog_file() -> Fallible<Option<PathBuf>> {
+    Ok(system_root()?.map(|root| root.join("catalog.toml")))
+}
+
+pub fn system_node_version_dir(version: &str) -> Fallible<Option<PathBuf>> {
+    Ok(system_root()?.map(|root| root.join("versions").join("node").join(version)))
+}
+
+pub fn system_yarn_version_dir(version: &str) -> Fallible<Option<PathBuf>> {
+    Ok(system_root()?.map(|root| root.join("versions").join("yarn").join(version)))
+}
+
+/// Tests whether a path is within the system layer, which Notion never writes to.
+pub fn in_system_layer(path: &Path) -> Fallible<bool> {
+    Ok(system_root()?.map_or(false, |root| path.starts_with(root)))
+}
+
+/// Chooses the directory a version is found in: the user's own, unless only the
+/// system layer has the version.
+fn overlay(user_dir: PathBuf, system_dir: Option<PathBuf>) -> PathBuf {
+    match system_dir {
+        Some(system_dir) if !user_dir.exists() && system_dir.is_dir() => system_dir,
+        _ => user_dir,
+    }
+}
+
+/// Produces the path a link at `link` should point to `target` with: the path relative
+/// to the link's directory in a portable installation, so that the installation can be
+/// moved, or else `target` itself.
+pub fn link_target(target: &Path, link: &Path) -> Fallible<PathBuf> {
+    if !layout()?.portable {
+        return Ok(target.to_path_buf());
+    }
+    Ok(match link.parent() {
+        Some(dir) => relative_path(dir, target),
+        None => target.to_path_buf(),
+    })
+}
+
+/// Produces the path a file recorded in the catalog is stored as: relative to the data
+/// root in a portable installation, if it is within the data root, and otherwise as it
+/// is. Paths that can't be made relative are also left as they are.
+pub fn to_portable(file: &Path) -> PathBuf {
+    match layout() {
+        Ok(layout) if layout.portable => match file.strip_prefix(&layout.data_root) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => file.to_path_buf(),
+        },
+        _ => file.to_path_buf(),
+    }
+}
+
+/// Resolves a file recorded in the catalog, which is relative to the data root if it
+/// was recorded by a portable installation (see `to_portable`).
+pub fn from_portable(file: PathBuf) -> Fallible<PathBuf> {
+    if file.is_relative() {
+        return Ok(layout()?.data_root.join(file));
+    }
+    Ok(file)
+}
+
+/// Produces the relative path from the directory `from` to `to`, which are both
+/// absolute. If they don't share a root (as on different Windows drives), `to` is
+/// produced as it is.
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let common = from
+        .components()
+        .zip(to.components())
+        .take_while(|&(a, b)| a == b)
+        .count();
+    if common == 0 {
+        return to.to_path_buf();
+    }
+
+    let mut path = PathBuf::new();
+    for _ in from.components().skip(common) {
+        path.push("..");
+    }
+    for component in to.components().skip(common) {
+        path.push(component.as_os_str());
+    }
+    path
 }
 
 pub fn yarn_archive_file(version: &str) -> String {
@@ -35,16 +172,67 @@ pub fn yarn_archive_root_dir(version: &str) -> String {
     format!("yarn-v{}", version)
 }
 
+/// Searches the directories of a `PATH`-style variable for an executable with the
+/// given name, the way the shell would.
+pub fn find_executable(name: &OsStr, path_var: &OsStr) -> Option<PathBuf> {
+    for dir in env::split_paths(path_var) {
+        for candidate in executable_candidates(name) {
+            let file = dir.join(candidate);
+            if file.is_file() {
+                return Some(file);
+            }
+        }
+    }
+    None
+}
+
+/// Searches the directories of a `PATH`-style variable for every executable with the
+/// given name, in the order the shell would consider them.
+pub fn find_executables(name: &OsStr, path_var: &OsStr) -> Vec<PathBuf> {
+    env::split_paths(path_var)
+        .filter_map(|dir| {
+            executable_candidates(name)
+                .into_iter()
+                .map(|candidate| dir.join(candidate))
+                .find(|file| file.is_file())
+        })
+        .collect()
+}
+
+cfg_if! {
+    if #[cfg(windows)] {
+        fn executable_candidates(name: &OsStr) -> Vec<PathBuf> {
+            let mut candidates = vec![PathBuf::from(name)];
+            for extension in &["exe", "cmd", "bat"] {
+                candidates.push(PathBuf::from(name).with_extension(extension));
+            }
+            candidates
+        }
+    } else {
+        fn executable_candidates(name: &OsStr) -> Vec<PathBuf> {
+            vec![PathBuf::from(name)]
+        }
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
 
     use super::*;
+    use std::fs::{self, File};
+    use tempfile::tempdir;
 
     #[test]
     fn test_node_archive_file() {
         assert_eq!(
             node_archive_file("1.2.3"),
-            format!("node-v1.2.3-{}-{}.{}", OS, ARCH, archive_extension())
+            format!(
+                "node-v1.2.3-{}-{}{}.{}",
+                OS,
+                Arch::native(),
+                Libc::native().archive_suffix(),
+                archive_extension()
+            )
         );
     }
 
@@ -52,7 +240,30 @@ pub mod tests {
     fn test_node_archive_root_dir() {
         assert_eq!(
             node_archive_root_dir("1.2.3"),
-            format!("node-v1.2.3-{}-{}", OS, ARCH)
+            format!(
+                "node-v1.2.3-{}-{}{}",
+                OS,
+                Arch::native(),
+                Libc::native().archive_suffix()
+            )
+        );
+    }
+
+    #[test]
+    fn test_node_archive_root_dir_for() {
+        assert_eq!(
+            node_archive_root_dir_for("1.2.3", Arch::Arm64),
+            format!("node-v1.2.3-{}-arm64{}", OS, Libc::native().archive_suffix())
+        );
+    }
+
+    #[test]
+    fn test_node_version_key() {
+        assert_eq!(node_version_key("1.2.3", Arch::native()), "1.2.3");
+        let foreign = if Arch::native() == Arch::Arm64 { Arch::X64 } else { Arch::Arm64 };
+        assert_eq!(
+            node_version_key("1.2.3", foreign),
+            format!("1.2.3-{}", foreign)
         );
     }
 
@@ -68,4 +279,41 @@ pub mod tests {
     fn yarn_node_archive_root_dir() {
         assert_eq!(yarn_archive_root_dir("1.2.3"), "yarn-v1.2.3".to_string());
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_relative_path() {
+        assert_eq!(
+            relative_path(Path::new("/usb/notion/bin"), Path::new("/usb/notion/launchbin")),
+            PathBuf::from("../launchbin")
+        );
+        assert_eq!(
+            relative_path(
+                Path::new("/usb/notion/versions/node"),
+                Path::new("/usb/notion/store/farms/node/8.6.0")
+            ),
+            PathBuf::from("../../store/farms/node/8.6.0")
+        );
+        assert_eq!(
+            relative_path(Path::new("/usb/notion"), Path::new("/usb/notion")),
+            PathBuf::new()
+        );
+    }
+
+    #[test]
+    fn test_find_executables() {
+        let root = tempdir().expect("Could not create temporary directory");
+        let dirs: Vec<PathBuf> = ["a", "b", "c"].iter().map(|dir| root.path().join(dir)).collect();
+        for dir in &dirs {
+            fs::create_dir(dir).unwrap();
+        }
+        File::create(dirs[0].join("tool")).unwrap();
+        File::create(dirs[2].join("tool")).unwrap();
+
+        let path_var = env::join_paths(&dirs).unwrap();
+        let found = find_executables(OsStr::new("tool"), &path_var);
+        assert_eq!(found, vec![dirs[0].join("tool"), dirs[2].join("tool")]);
+        assert_eq!(find_executable(OsStr::new("tool"), &path_var), Some(found[0].clone()));
+        assert!(find_executables(OsStr::new("other"), &path_var).is_empty());
+    }
 }
@@ -2,11 +2,14 @@
 //! in a standard Notion layout in Unix-based operating systems.
 
 use std::{env, io};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::os::unix;
 
 use notion_fail::{ExitCode, Fallible, NotionFail};
 
+use super::layout::{layout, Layout, CONFIG_FILE};
+use super::{overlay, system_node_version_dir, system_yarn_version_dir};
+
 #[derive(Debug, Fail, NotionFail)]
 #[fail(display = "environment variable 'HOME' is not set")]
 #[notion_fail(code = "EnvironmentError")]
@@ -28,18 +31,6 @@ cfg_if! {
     }
 }
 
-cfg_if! {
-    if #[cfg(target_arch = "x86")] {
-        /// The system architecture component of a Node distribution tarball's name.
-        pub const ARCH: &'static str = "x86";
-    } else if #[cfg(target_arch = "x86_64")] {
-        /// The system architecture component of a Node distribution tarball's name.
-        pub const ARCH: &'static str = "x64";
-    } else {
-        compile_error!("Unsupported target_arch variant of unix (expected 'x86' or 'x64').");
-    }
-}
-
 // ~/
 //     .notion/
 //         cache/                                          cache_dir
@@ -48,13 +39,53 @@ cfg_if! {
 //                 node-dist-v6.11.3-linux-x64.tar.gz
 //                 node-dist-v8.6.0-linux-x64.tar.gz
 //                 ...
+//             platforms/                                  platform_cache_dir
+//                 5f0c3a9e12d4b871                        platform_cache_file("5f0c3a9e12d4b871")
 //         versions/                                       versions_dir
 //             node/                                       node_versions_dir
-//                 4.8.4/                                  node_version_dir("4.8.4")
+//                 4.8.4 -> node_farm_dir("4.8.4")         node_version_dir("4.8.4")
 //                   bin/                                  node_version_bin_dir("4.8.4")
+//                   lib/node_modules/npm/package.json     node_npm_package_file("4.8.4")
 //                 6.11.3/
 //                 8.6.0/
+//                 8.6.0-arm64/                            node_version_dir(&node_version_key("8.6.0", Arch::Arm64))
 //                 ...
+//         manifests/                                      manifests_dir
+//             node/
+//                 8.6.0.sha256                            node_manifest_file("8.6.0")
+//             yarn/
+//                 1.7.0.sha256                            yarn_manifest_file("1.7.0")
+//         store/                                          store_dir
+//             objects/                                    store_objects_dir
+//                 3f/
+//                     a94c...-755                         store_object_file("3fa94c...-755")
+//             farms/
+//                 node/
+//                     4.8.4/                              node_farm_dir("4.8.4")
+//                 yarn/
+//                     1.7.0/                              yarn_farm_dir("1.7.0")
+//         compressed/                                     compressed_dir
+//             node/
+//                 6.11.3.tar.gz                           node_compressed_file("6.11.3")
+//             yarn/
+//                 1.6.0.tar.gz                            yarn_compressed_file("1.6.0")
+//         used/                                           used_dir
+//             node/
+//                 8.6.0                                   node_used_file("8.6.0")
+//             yarn/
+//                 1.7.0                                   yarn_used_file("1.7.0")
+//         locks/                                          locks_dir
+//             node/
+//                 8.6.0.lock                              node_install_lock_file("8.6.0")
+//             yarn/
+//                 1.7.0.lock                              yarn_install_lock_file("1.7.0")
+//         packages/                                       packages_dir
+//             typescript/                                 package_dir("typescript")
+//                 bin/                                    package_bin_dir("typescript")
+//                 lib/node_modules/typescript/
+//         staging/                                        staging_dir
+//         autoshim/                                       autoshim_dir
+//             5f0c3a9e12d4b871                            autoshim_file("5f0c3a9e12d4b871")
 //         bin/                                            shim_dir
 //             node                                        shim_file("node")
 //             npm
@@ -63,16 +94,75 @@ cfg_if! {
 //         notion                                          notion_file
 //         launchbin                                       launchbin_file
 //         launchscript                                    launchscript_file
+//         shim-version                                    shim_version_file
 //         config.toml                                     user_config_file
+//         hooks.toml                                      user_hooks_file
+//         Notion.psm1                                     powershell_module_file
+//         notion.fish                                     fish_script_file
+//         notion.nu                                       nu_script_file
 //         catalog.toml                                    user_catalog_file
+//         catalog.lock                                    catalog_lock_file
+//         catalog.generation                              catalog_generation_file
+//         trusted-projects                                trusted_projects_file
+//
+// /
+//     opt/
+//         notion/                                         system_root
+//             catalog.toml                                system_catalog_file
+//             versions/
+//                 node/
+//                     8.6.0/                              system_node_version_dir("8.6.0")
+//                 yarn/
+//                     1.7.0/                              system_yarn_version_dir("1.7.0")
+//
+// The layout can be relocated with `NOTION_HOME`, `NOTION_SHIM_DIR`,
+// `NOTION_SYSTEM_DIR`, and the `[layout]` section of `config.toml` (see
+// `path::layout`). On Linux, layout version 2 splits `~/.notion` across the XDG
+// base directories instead (see `path::xdg`). A `portable` marker file next to
+// the `notion` executable keeps the whole layout in that directory instead, with
+// relative links, so that it can be moved as a whole.
 
-fn notion_home() -> Fallible<PathBuf> {
+/// The directory the system layer is kept in by default.
+const SYSTEM_DIR: &'static str = "/opt/notion";
+
+/// Produces the default layout, which keeps everything under `~/.notion` (or in the
+/// XDG base directories, see `user_layout`), with the system layer in `/opt/notion`
+/// if an administrator has set it up there.
+pub(crate) fn default_layout() -> Fallible<Layout> {
     let home = env::home_dir().ok_or(NoHomeEnvVar)?;
-    Ok(home.join(".notion"))
+    let system_root = PathBuf::from(SYSTEM_DIR);
+    Ok(Layout {
+        system_root: if system_root.is_dir() { Some(system_root) } else { None },
+        ..user_layout(&home)?
+    })
+}
+
+cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        use super::xdg;
+
+        /// Produces the layout of the user's own files: `~/.notion`, or the XDG base
+        /// directories once layout version 2 is chosen, moving `~/.notion` into them
+        /// the first time (see `path::xdg`).
+        fn user_layout(home: &Path) -> Fallible<Layout> {
+            let legacy = Layout::in_home(home.join(".notion"));
+            let xdg = xdg::layout(home);
+            if xdg::version(&legacy, &xdg)? == xdg::XDG_VERSION {
+                xdg::migrate(&legacy, &xdg)?;
+                return Ok(xdg);
+            }
+            Ok(legacy)
+        }
+    } else {
+        /// Produces the layout of the user's own files, which are all in `~/.notion`.
+        fn user_layout(home: &Path) -> Fallible<Layout> {
+            Ok(Layout::in_home(home.join(".notion")))
+        }
+    }
 }
 
 pub fn cache_dir() -> Fallible<PathBuf> {
-    Ok(notion_home()?.join("cache"))
+    Ok(layout()?.cache_root.clone())
 }
 
 pub fn node_cache_dir() -> Fallible<PathBuf> {
@@ -90,12 +180,24 @@ pub fn node_index_expiry_file() -> Fallible<PathBuf> {
     Ok(node_cache_dir()?.join("index.json.expires"))
 }
 
+pub fn node_index_etag_file() -> Fallible<PathBuf> {
+    Ok(node_cache_dir()?.join("index.json.etag"))
+}
+
+pub fn platform_cache_dir() -> Fallible<PathBuf> {
+    Ok(cache_dir()?.join("platforms"))
+}
+
+pub fn platform_cache_file(key: &str) -> Fallible<PathBuf> {
+    Ok(platform_cache_dir()?.join(key))
+}
+
 pub fn archive_extension() -> String {
     String::from("tar.gz")
 }
 
 pub fn versions_dir() -> Fallible<PathBuf> {
-    Ok(notion_home()?.join("versions"))
+    Ok(layout()?.data_root.join("versions"))
 }
 
 pub fn node_versions_dir() -> Fallible<PathBuf> {
@@ -106,12 +208,109 @@ pub fn yarn_versions_dir() -> Fallible<PathBuf> {
     Ok(versions_dir()?.join("yarn"))
 }
 
+pub fn packages_dir() -> Fallible<PathBuf> {
+    Ok(layout()?.data_root.join("packages"))
+}
+
+pub fn package_dir(name: &str) -> Fallible<PathBuf> {
+    Ok(packages_dir()?.join(name))
+}
+
+pub fn package_bin_dir(name: &str) -> Fallible<PathBuf> {
+    Ok(package_dir(name)?.join("bin"))
+}
+
+pub fn staging_dir() -> Fallible<PathBuf> {
+    Ok(layout()?.data_root.join("staging"))
+}
+
+pub fn autoshim_dir() -> Fallible<PathBuf> {
+    Ok(layout()?.data_root.join("autoshim"))
+}
+
+pub fn autoshim_file(key: &str) -> Fallible<PathBuf> {
+    Ok(autoshim_dir()?.join(key))
+}
+
+pub fn store_dir() -> Fallible<PathBuf> {
+    Ok(layout()?.data_root.join("store"))
+}
+
+pub fn store_objects_dir() -> Fallible<PathBuf> {
+    Ok(store_dir()?.join("objects"))
+}
+
+pub fn store_object_file(name: &str) -> Fallible<PathBuf> {
+    let (prefix, rest) = name.split_at(2);
+    Ok(store_objects_dir()?.join(prefix).join(rest))
+}
+
+pub fn node_farm_dir(version: &str) -> Fallible<PathBuf> {
+    Ok(store_dir()?.join("farms").join("node").join(version))
+}
+
+pub fn yarn_farm_dir(version: &str) -> Fallible<PathBuf> {
+    Ok(store_dir()?.join("farms").join("yarn").join(version))
+}
+
+pub fn manifests_dir() -> Fallible<PathBuf> {
+    Ok(layout()?.data_root.join("manifests"))
+}
+
+pub fn node_manifest_file(version: &str) -> Fallible<PathBuf> {
+    Ok(manifests_dir()?.join("node").join(format!("{}.sha256", version)))
+}
+
+pub fn yarn_manifest_file(version: &str) -> Fallible<PathBuf> {
+    Ok(manifests_dir()?.join("yarn").join(format!("{}.sha256", version)))
+}
+
+pub fn compressed_dir() -> Fallible<PathBuf> {
+    Ok(layout()?.data_root.join("compressed"))
+}
+
+pub fn node_compressed_file(version: &str) -> Fallible<PathBuf> {
+    Ok(compressed_dir()?.join("node").join(format!("{}.tar.gz", version)))
+}
+
+pub fn yarn_compressed_file(version: &str) -> Fallible<PathBuf> {
+    Ok(compressed_dir()?.join("yarn").join(format!("{}.tar.gz", version)))
+}
+
+pub fn used_dir() -> Fallible<PathBuf> {
+    Ok(layout()?.data_root.join("used"))
+}
+
+pub fn node_used_file(version: &str) -> Fallible<PathBuf> {
+    Ok(used_dir()?.join("node").join(version))
+}
+
+pub fn yarn_used_file(version: &str) -> Fallible<PathBuf> {
+    Ok(used_dir()?.join("yarn").join(version))
+}
+
+pub fn locks_dir() -> Fallible<PathBuf> {
+    Ok(layout()?.data_root.join("locks"))
+}
+
+pub fn node_install_lock_file(version: &str) -> Fallible<PathBuf> {
+    Ok(locks_dir()?.join("node").join(format!("{}.lock", version)))
+}
+
+pub fn yarn_install_lock_file(version: &str) -> Fallible<PathBuf> {
+    Ok(locks_dir()?.join("yarn").join(format!("{}.lock", version)))
+}
+
+/// Produces the directory a version is installed in, which is in the system layer
+/// if only the system layer has the version.
 pub fn node_version_dir(version: &str) -> Fallible<PathBuf> {
-    Ok(node_versions_dir()?.join(version))
+    Ok(overlay(node_versions_dir()?.join(version), system_node_version_dir(version)?))
 }
 
+/// Produces the directory a version is installed in, which is in the system layer
+/// if only the system layer has the version.
 pub fn yarn_version_dir(version: &str) -> Fallible<PathBuf> {
-    Ok(yarn_versions_dir()?.join(version))
+    Ok(overlay(yarn_versions_dir()?.join(version), system_yarn_version_dir(version)?))
 }
 
 pub fn node_version_bin_dir(version: &str) -> Fallible<PathBuf> {
@@ -122,17 +321,22 @@ pub fn yarn_version_bin_dir(version: &str) -> Fallible<PathBuf> {
     Ok(yarn_version_dir(version)?.join("bin"))
 }
 
+// the package.json of the npm bundled with this node version
+pub fn node_npm_package_file(version: &str) -> Fallible<PathBuf> {
+    Ok(node_version_dir(version)?.join("lib/node_modules/npm/package.json"))
+}
+
 // 3rd-party binaries installed globally for this node version
 pub fn node_version_3p_bin_dir(version: &str) -> Fallible<PathBuf> {
     Ok(node_version_dir(version)?.join("lib/node_modules/.bin"))
 }
 
 pub fn notion_file() -> Fallible<PathBuf> {
-    Ok(notion_home()?.join("notion"))
+    Ok(layout()?.install_root.join("notion"))
 }
 
 pub fn shim_dir() -> Fallible<PathBuf> {
-    Ok(notion_home()?.join("bin"))
+    Ok(layout()?.shim_dir.clone())
 }
 
 pub fn shim_file(toolname: &str) -> Fallible<PathBuf> {
@@ -140,21 +344,62 @@ pub fn shim_file(toolname: &str) -> Fallible<PathBuf> {
 }
 
 pub fn launchbin_file() -> Fallible<PathBuf> {
-    Ok(notion_home()?.join("launchbin"))
+    Ok(layout()?.data_root.join("launchbin"))
 }
 
 pub fn launchscript_file() -> Fallible<PathBuf> {
-    Ok(notion_home()?.join("launchscript"))
+    Ok(layout()?.data_root.join("launchscript"))
+}
+
+pub fn shim_version_file() -> Fallible<PathBuf> {
+    Ok(layout()?.data_root.join("shim-version"))
 }
 
 pub fn user_config_file() -> Fallible<PathBuf> {
-    Ok(notion_home()?.join("config.toml"))
+    Ok(layout()?.user_root.join(CONFIG_FILE))
+}
+
+pub fn user_hooks_file() -> Fallible<PathBuf> {
+    Ok(layout()?.user_root.join("hooks.toml"))
+}
+
+pub fn powershell_module_file() -> Fallible<PathBuf> {
+    Ok(layout()?.user_root.join("Notion.psm1"))
+}
+
+pub fn fish_script_file() -> Fallible<PathBuf> {
+    Ok(layout()?.user_root.join("notion.fish"))
+}
+
+pub fn nu_script_file() -> Fallible<PathBuf> {
+    Ok(layout()?.user_root.join("notion.nu"))
 }
 
 pub fn user_catalog_file() -> Fallible<PathBuf> {
-    Ok(notion_home()?.join("catalog.toml"))
+    Ok(layout()?.user_root.join("catalog.toml"))
+}
+
+pub fn catalog_lock_file() -> Fallible<PathBuf> {
+    Ok(layout()?.user_root.join("catalog.lock"))
+}
+
+pub fn catalog_generation_file() -> Fallible<PathBuf> {
+    Ok(layout()?.user_root.join("catalog.generation"))
+}
+
+pub fn trusted_projects_file() -> Fallible<PathBuf> {
+    Ok(layout()?.user_root.join("trusted-projects"))
+}
+
+/// The Unix socket `notion daemon` listens on by default.
+pub fn daemon_socket_file() -> Fallible<PathBuf> {
+    Ok(layout()?.user_root.join("daemon.sock"))
 }
 
 pub fn create_file_symlink(src: PathBuf, dst: PathBuf) -> Result<(), io::Error> {
     unix::fs::symlink(src, dst)
 }
+
+pub fn create_dir_symlink(src: PathBuf, dst: PathBuf) -> Result<(), io::Error> {
+    unix::fs::symlink(src, dst)
+}
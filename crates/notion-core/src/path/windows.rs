@@ -11,22 +11,15 @@ use winfolder;
 
 use notion_fail::Fallible;
 
+use super::layout::{layout, Layout, CONFIG_FILE};
+use super::{overlay, system_node_version_dir, system_yarn_version_dir};
+
 // These are taken from: https://nodejs.org/dist/index.json and are used
 // by `path::archive_root_dir` to determine the root directory of the
 // contents of a Node installer archive.
 
 pub const OS: &'static str = "win";
 
-cfg_if! {
-    if #[cfg(target_arch = "x86")] {
-        pub const ARCH: &'static str = "x86";
-    } else if #[cfg(target_arch = "x86_64")] {
-        pub const ARCH: &'static str = "x64";
-    } else {
-        compile_error!("Unsupported target_arch variant of Windows (expected 'x86' or 'x64').");
-    }
-}
-
 // C:\
 //     ProgramData\
 //         Notion\
@@ -38,13 +31,49 @@ cfg_if! {
 //                     ...
 //             versions\                               versions_dir
 //                 node\                               node_versions_dir
-//                     4.8.4\                          node_version_dir("4.8.4")
+//                     4.8.4 -> node_farm_dir("4.8.4") node_version_dir("4.8.4")
 //                                                     node_version_bin_dir("4.8.4")
+//                       node_modules\npm\package.json node_npm_package_file("4.8.4")
 //                     6.11.3\
 //                     8.6.0\
 //                     ...
+//             manifests\                              manifests_dir
+//                 node\
+//                     8.6.0.sha256                    node_manifest_file("8.6.0")
+//                 yarn\
+//                     1.7.0.sha256                    yarn_manifest_file("1.7.0")
+//             store\                                  store_dir
+//                 objects\                            store_objects_dir
+//                     3f\
+//                         a94c...                     store_object_file("3fa94c...")
+//                 farms\
+//                     node\
+//                         4.8.4\                      node_farm_dir("4.8.4")
+//                     yarn\
+//                         1.7.0\                      yarn_farm_dir("1.7.0")
+//             compressed\                             compressed_dir
+//                 node\
+//                     6.11.3.tar.gz                   node_compressed_file("6.11.3")
+//                 yarn\
+//                     1.6.0.tar.gz                    yarn_compressed_file("1.6.0")
+//             used\                                   used_dir
+//                 node\
+//                     8.6.0                           node_used_file("8.6.0")
+//                 yarn\
+//                     1.7.0                           yarn_used_file("1.7.0")
+//             locks\                                  locks_dir
+//                 node\
+//                     8.6.0.lock                      node_install_lock_file("8.6.0")
+//                 yarn\
+//                     1.7.0.lock                      yarn_install_lock_file("1.7.0")
+//             packages\                               packages_dir
+//                 typescript\                         package_dir("typescript")
+//                                                     package_bin_dir("typescript")
+//                     node_modules\typescript\
+//             staging\                                staging_dir
 //             launchbin.exe                           launchbin_file
 //             launchscript.exe                        launchscript_file
+//             shim-version                            shim_version_file
 
 fn program_data_root() -> Fallible<PathBuf> {
     // if this is sandboxed in CI, use the sandboxed ProgramData directory
@@ -63,7 +92,7 @@ fn program_data_root() -> Fallible<PathBuf> {
 }
 
 pub fn cache_dir() -> Fallible<PathBuf> {
-    Ok(program_data_root()?.join("cache"))
+    Ok(layout()?.cache_root.clone())
 }
 
 pub fn node_cache_dir() -> Fallible<PathBuf> {
@@ -82,12 +111,24 @@ pub fn node_index_expiry_file() -> Fallible<PathBuf> {
     Ok(node_cache_dir()?.join("index.json.expires"))
 }
 
+pub fn node_index_etag_file() -> Fallible<PathBuf> {
+    Ok(node_cache_dir()?.join("index.json.etag"))
+}
+
+pub fn platform_cache_dir() -> Fallible<PathBuf> {
+    Ok(cache_dir()?.join("platforms"))
+}
+
+pub fn platform_cache_file(key: &str) -> Fallible<PathBuf> {
+    Ok(platform_cache_dir()?.join(key))
+}
+
 pub fn archive_extension() -> String {
     String::from("zip")
 }
 
 pub fn versions_dir() -> Fallible<PathBuf> {
-    Ok(program_data_root()?.join("versions"))
+    Ok(layout()?.data_root.join("versions"))
 }
 
 pub fn node_versions_dir() -> Fallible<PathBuf> {
@@ -98,12 +139,110 @@ pub fn yarn_versions_dir() -> Fallible<PathBuf> {
     Ok(versions_dir()?.join("yarn"))
 }
 
+pub fn packages_dir() -> Fallible<PathBuf> {
+    Ok(layout()?.data_root.join("packages"))
+}
+
+pub fn package_dir(name: &str) -> Fallible<PathBuf> {
+    Ok(packages_dir()?.join(name))
+}
+
+// npm puts the executables of global packages in the prefix directory itself on Windows
+pub fn package_bin_dir(name: &str) -> Fallible<PathBuf> {
+    package_dir(name)
+}
+
+pub fn staging_dir() -> Fallible<PathBuf> {
+    Ok(layout()?.data_root.join("staging"))
+}
+
+pub fn autoshim_dir() -> Fallible<PathBuf> {
+    Ok(layout()?.data_root.join("autoshim"))
+}
+
+pub fn autoshim_file(key: &str) -> Fallible<PathBuf> {
+    Ok(autoshim_dir()?.join(key))
+}
+
+pub fn store_dir() -> Fallible<PathBuf> {
+    Ok(layout()?.data_root.join("store"))
+}
+
+pub fn store_objects_dir() -> Fallible<PathBuf> {
+    Ok(store_dir()?.join("objects"))
+}
+
+pub fn store_object_file(name: &str) -> Fallible<PathBuf> {
+    let (prefix, rest) = name.split_at(2);
+    Ok(store_objects_dir()?.join(prefix).join(rest))
+}
+
+pub fn node_farm_dir(version: &str) -> Fallible<PathBuf> {
+    Ok(store_dir()?.join("farms").join("node").join(version))
+}
+
+pub fn yarn_farm_dir(version: &str) -> Fallible<PathBuf> {
+    Ok(store_dir()?.join("farms").join("yarn").join(version))
+}
+
+pub fn manifests_dir() -> Fallible<PathBuf> {
+    Ok(layout()?.data_root.join("manifests"))
+}
+
+pub fn node_manifest_file(version: &str) -> Fallible<PathBuf> {
+    Ok(manifests_dir()?.join("node").join(format!("{}.sha256", version)))
+}
+
+pub fn yarn_manifest_file(version: &str) -> Fallible<PathBuf> {
+    Ok(manifests_dir()?.join("yarn").join(format!("{}.sha256", version)))
+}
+
+pub fn compressed_dir() -> Fallible<PathBuf> {
+    Ok(layout()?.data_root.join("compressed"))
+}
+
+pub fn node_compressed_file(version: &str) -> Fallible<PathBuf> {
+    Ok(compressed_dir()?.join("node").join(format!("{}.tar.gz", version)))
+}
+
+pub fn yarn_compressed_file(version: &str) -> Fallible<PathBuf> {
+    Ok(compressed_dir()?.join("yarn").join(format!("{}.tar.gz", version)))
+}
+
+pub fn used_dir() -> Fallible<PathBuf> {
+    Ok(layout()?.data_root.join("used"))
+}
+
+pub fn node_used_file(version: &str) -> Fallible<PathBuf> {
+    Ok(used_dir()?.join("node").join(version))
+}
+
+pub fn yarn_used_file(version: &str) -> Fallible<PathBuf> {
+    Ok(used_dir()?.join("yarn").join(version))
+}
+
+pub fn locks_dir() -> Fallible<PathBuf> {
+    Ok(layout()?.data_root.join("locks"))
+}
+
+pub fn node_install_lock_file(version: &str) -> Fallible<PathBuf> {
+    Ok(locks_dir()?.join("node").join(format!("{}.lock", version)))
+}
+
+pub fn yarn_install_lock_file(version: &str) -> Fallible<PathBuf> {
+    Ok(locks_dir()?.join("yarn").join(format!("{}.lock", version)))
+}
+
+/// Produces the directory a version is installed in, which is in the system layer
+/// if only the system layer has the version.
 pub fn node_version_dir(version: &str) -> Fallible<PathBuf> {
-    Ok(node_versions_dir()?.join(version))
+    Ok(overlay(node_versions_dir()?.join(version), system_node_version_dir(version)?))
 }
 
+/// Produces the directory a version is installed in, which is in the system layer
+/// if only the system layer has the version.
 pub fn yarn_version_dir(version: &str) -> Fallible<PathBuf> {
-    Ok(yarn_versions_dir()?.join(version))
+    Ok(overlay(yarn_versions_dir()?.join(version), system_yarn_version_dir(version)?))
 }
 
 pub fn node_version_bin_dir(version: &str) -> Fallible<PathBuf> {
@@ -114,6 +253,14 @@ pub fn yarn_version_bin_dir(version: &str) -> Fallible<PathBuf> {
     Ok(yarn_version_dir(version)?.join("bin"))
 }
 
+// the package.json of the npm bundled with this node version
+pub fn node_npm_package_file(version: &str) -> Fallible<PathBuf> {
+    Ok(node_version_dir(version)?
+        .join("node_modules")
+        .join("npm")
+        .join("package.json"))
+}
+
 // 3rd-party binaries installed globally for this node version
 pub fn node_version_3p_bin_dir(_version: &str) -> Fallible<PathBuf> {
     // ISSUE (#90) Figure out where binaries are globally installed on Windows
@@ -121,11 +268,15 @@ pub fn node_version_3p_bin_dir(_version: &str) -> Fallible<PathBuf> {
 }
 
 pub fn launchbin_file() -> Fallible<PathBuf> {
-    Ok(program_data_root()?.join("launchbin.exe"))
+    Ok(layout()?.data_root.join("launchbin.exe"))
 }
 
 pub fn launchscript_file() -> Fallible<PathBuf> {
-    Ok(program_data_root()?.join("launchscript.exe"))
+    Ok(layout()?.data_root.join("launchscript.exe"))
+}
+
+pub fn shim_version_file() -> Fallible<PathBuf> {
+    Ok(layout()?.data_root.join("shim-version"))
 }
 
 // C:\
@@ -151,11 +302,11 @@ fn program_files_root() -> Fallible<PathBuf> {
 }
 
 pub fn notion_file() -> Fallible<PathBuf> {
-    Ok(program_files_root()?.join("notion.exe"))
+    Ok(layout()?.install_root.join("notion.exe"))
 }
 
 pub fn shim_dir() -> Fallible<PathBuf> {
-    Ok(program_files_root()?.join("bin"))
+    Ok(layout()?.shim_dir.clone())
 }
 
 pub fn shim_file(toolname: &str) -> Fallible<PathBuf> {
@@ -169,7 +320,14 @@ pub fn shim_file(toolname: &str) -> Fallible<PathBuf> {
 //                 Local\
 //                     Notion\
 //                         config.toml                 user_config_file
+//                         hooks.toml                  user_hooks_file
+//                         Notion.psm1                 powershell_module_file
+//                         notion.fish                 fish_script_file
+//                         notion.nu                   nu_script_file
 //                         catalog.toml                user_catalog_file
+//                         catalog.lock                catalog_lock_file
+//                         catalog.generation          catalog_generation_file
+//                         trusted-projects            trusted_projects_file
 
 fn local_data_root() -> Fallible<PathBuf> {
     // if this is sandboxed in CI, use the sandboxed AppData directory
@@ -187,11 +345,63 @@ fn local_data_root() -> Fallible<PathBuf> {
 }
 
 pub fn user_config_file() -> Fallible<PathBuf> {
-    Ok(local_data_root()?.join("config.toml"))
+    Ok(layout()?.user_root.join(CONFIG_FILE))
+}
+
+pub fn user_hooks_file() -> Fallible<PathBuf> {
+    Ok(layout()?.user_root.join("hooks.toml"))
+}
+
+pub fn powershell_module_file() -> Fallible<PathBuf> {
+    Ok(layout()?.user_root.join("Notion.psm1"))
+}
+
+pub fn fish_script_file() -> Fallible<PathBuf> {
+    Ok(layout()?.user_root.join("notion.fish"))
+}
+
+pub fn nu_script_file() -> Fallible<PathBuf> {
+    Ok(layout()?.user_root.join("notion.nu"))
 }
 
 pub fn user_catalog_file() -> Fallible<PathBuf> {
-    Ok(local_data_root()?.join("catalog.toml"))
+    Ok(layout()?.user_root.join("catalog.toml"))
+}
+
+pub fn catalog_lock_file() -> Fallible<PathBuf> {
+    Ok(layout()?.user_root.join("catalog.lock"))
+}
+
+pub fn catalog_generation_file() -> Fallible<PathBuf> {
+    Ok(layout()?.user_root.join("catalog.generation"))
+}
+
+pub fn trusted_projects_file() -> Fallible<PathBuf> {
+    Ok(layout()?.user_root.join("trusted-projects"))
+}
+
+/// The named pipe `notion daemon` would listen on by default.
+pub fn daemon_socket_file() -> Fallible<PathBuf> {
+    Ok(PathBuf::from(r"\\.\pipe\notion-daemon"))
+}
+
+/// Produces the default layout, which spreads Notion's files across `ProgramData`,
+/// `Program Files`, and the user's local `AppData`.
+/// There is no system layer by default, since `ProgramData` is already shared by
+/// every user of the machine; one can be set up with `NOTION_SYSTEM_DIR` or the
+/// `system-dir` setting.
+pub(crate) fn default_layout() -> Fallible<Layout> {
+    let install_root = program_files_root()?;
+    let data_root = program_data_root()?;
+    Ok(Layout {
+        cache_root: data_root.join("cache"),
+        data_root,
+        user_root: local_data_root()?,
+        shim_dir: install_root.join("bin"),
+        install_root,
+        system_root: None,
+        portable: false,
+    })
 }
 
 pub fn create_file_symlink(src: PathBuf, dst: PathBuf) -> Result<(), io::Error> {
@@ -202,3 +412,12 @@ pub fn create_file_symlink(src: PathBuf, dst: PathBuf) -> Result<(), io::Error>
     #[cfg(feature = "universal-docs")]
     unimplemented!()
 }
+
+pub fn create_dir_symlink(src: PathBuf, dst: PathBuf) -> Result<(), io::Error> {
+    #[cfg(windows)]
+    return windows::fs::symlink_dir(src, dst);
+
+    // "universal-docs" is built on a Unix machine, so we can't include Windows-specific libs
+    #[cfg(feature = "universal-docs")]
+    unimplemented!()
+}
@@ -0,0 +1,252 @@
+//! Provides the XDG Base Directory layout on Linux, which is layout version 2. It
+//! splits what the legacy layout (version 1) keeps under `~/.notion` by kind:
+//!
+//! - settings and the catalog in `$XDG_CONFIG_HOME/notion` (`~/.config/notion`)
+//! - downloaded archives and indexes in `$XDG_CACHE_HOME/notion` (`~/.cache/notion`)
+//! - installed versions, shims, and everything else in `$XDG_DATA_HOME/notion`
+//!   (`~/.local/share/notion`)
+//!
+//! The layout is chosen with `NOTION_LAYOUT_VERSION=2`, or with `version = 2` in the
+//! `[layout]` section of `~/.notion/config.toml`, and the legacy directory is then
+//! moved into place automatically. Once moved, the version is recorded in the
+//! `layout-version` file of the configuration directory, so it doesn't need to be
+//! chosen again.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::os::unix;
+use std::path::{Path, PathBuf};
+
+use logging;
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+
+use super::layout::{read_layout_config, Layout, CONFIG_FILE};
+
+/// The environment variable that selects the layout version.
+const NOTION_LAYOUT_VERSION: &'static str = "NOTION_LAYOUT_VERSION";
+
+/// The name of the file the layout version is recorded in, in the user root.
+const VERSION_FILE: &'static str = "layout-version";
+
+/// The version of the XDG layout.
+pub(crate) const XDG_VERSION: u32 = 2;
+
+/// The files of the legacy layout that belong with the settings.
+const CONFIG_FILES: &'static [&'static str] = &[
+    CONFIG_FILE,
+    "hooks.toml",
+    "catalog.toml",
+    "catalog.lock",
+    "catalog.generation",
+    "trusted-projects",
+    "Notion.psm1",
+    "notion.fish",
+    "notion.nu",
+];
+
+/// Thrown when the layout version is not one Notion knows.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Invalid layout version '{}' (expected 1 or 2)", value)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct LayoutVersionError {
+    value: String,
+}
+
+/// Thrown when the legacy layout could not be moved into the XDG directories.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not move {} to {}: {}", from, to, error)]
+#[notion_fail(code = "FileSystemError")]
+pub(crate) struct LayoutMigrationError {
+    from: String,
+    to: String,
+    error: String,
+}
+
+impl LayoutMigrationError {
+    fn for_move(from: &Path, to: &Path) -> impl FnOnce(&io::Error) -> LayoutMigrationError {
+        let from = from.to_string_lossy().to_string();
+        let to = to.to_string_lossy().to_string();
+        move |error| LayoutMigrationError {
+            from,
+            to,
+            error: error.to_string(),
+        }
+    }
+}
+
+/// Produces the XDG layout for the user whose home directory is `home`.
+pub(crate) fn layout(home: &Path) -> Layout {
+    let data_root = base_dir("XDG_DATA_HOME", home, ".local/share").join("notion");
+    Layout {
+        user_root: base_dir("XDG_CONFIG_HOME", home, ".config").join("notion"),
+        cache_root: base_dir("XDG_CACHE_HOME", home, ".cache").join("notion"),
+        install_root: data_root.clone(),
+        shim_dir: data_root.join("bin"),
+        data_root,
+        system_root: None,
+        portable: false,
+    }
+}
+
+/// Produces an XDG base directory: the one its variable names, if that is an absolute
+/// path as the specification requires, or else the default within the home directory.
+fn base_dir(var: &str, home: &Path, default: &str) -> PathBuf {
+    match env::var_os(var).map(PathBuf::from) {
+        Some(ref dir) if dir.is_absolute() => dir.clone(),
+        _ => home.join(default),
+    }
+}
+
+/// Determines which layout version is in use, given the legacy and XDG layouts.
+pub(crate) fn version(legacy: &Layout, xdg: &Layout) -> Fallible<u32> {
+    let requested = match env::var(NOTION_LAYOUT_VERSION) {
+        Ok(value) => Some(value),
+        Err(_) => fs::read_to_string(xdg.user_root.join(VERSION_FILE)).ok(),
+    };
+    if let Some(value) = requested {
+        return parse_version(value.trim());
+    }
+
+    let config = read_layout_config(&legacy.user_root.join(CONFIG_FILE))?;
+    Ok(config.and_then(|config| config.version).unwrap_or(1))
+}
+
+fn parse_version(src: &str) -> Fallible<u32> {
+    match src {
+        "1" => Ok(1),
+        "2" => Ok(XDG_VERSION),
+        _ => throw!(LayoutVersionError {
+            value: src.to_string(),
+        }),
+    }
+}
+
+/// Moves the legacy layout into the XDG directories, if it hasn't been already, and
+/// records that the XDG layout is in use.
+pub(crate) fn migrate(legacy: &Layout, xdg: &Layout) -> Fallible<()> {
+    let version_file = xdg.user_root.join(VERSION_FILE);
+    if version_file.is_file() {
+        return Ok(());
+    }
+
+    let legacy_root = &legacy.data_root;
+    let moved = legacy_root.is_dir();
+    if moved {
+        for name in CONFIG_FILES {
+            move_entry(&legacy_root.join(name), &xdg.user_root.join(name))?;
+        }
+        move_entry(&legacy_root.join("cache"), &xdg.cache_root)?;
+        for entry in fs::read_dir(legacy_root).unknown()? {
+            let entry = entry.unknown()?;
+            move_entry(&entry.path(), &xdg.data_root.join(entry.file_name()))?;
+        }
+
+        // shims and installed versions are absolute symlinks into the legacy directory
+        relink(&xdg.data_root, legacy_root, &xdg.data_root)?;
+        let _ = fs::remove_dir(legacy_root);
+    }
+
+    // the version is recorded before warning, since warnings read the configuration,
+    // which is located with the layout
+    fs::create_dir_all(&xdg.user_root).unknown()?;
+    fs::write(&version_file, format!("{}\n", XDG_VERSION)).unknown()?;
+    if moved {
+        logging::warning(&format!(
+            "moved {} to the XDG base directories; replace {} with {} in your PATH",
+            legacy_root.display(),
+            legacy.shim_dir.display(),
+            xdg.shim_dir.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Moves a file or directory, if it exists, creating the directory it is moved into.
+fn move_entry(from: &Path, to: &Path) -> Fallible<()> {
+    if fs::symlink_metadata(from).is_err() {
+        return Ok(());
+    }
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent).with_context(LayoutMigrationError::for_move(from, to))?;
+    }
+    fs::rename(from, to).with_context(LayoutMigrationError::for_move(from, to))
+}
+
+/// Points the symlinks within a directory that lead into `from` at the same place
+/// within `to` instead.
+fn relink(dir: &Path, from: &Path, to: &Path) -> Fallible<()> {
+    for entry in fs::read_dir(dir).unknown()? {
+        let file = entry.unknown()?.path();
+        let metadata = fs::symlink_metadata(&file).unknown()?;
+        if metadata.file_type().is_symlink() {
+            let target = fs::read_link(&file).unknown()?;
+            if let Ok(rest) = target.strip_prefix(from) {
+                fs::remove_file(&file).unknown()?;
+                unix::fs::symlink(to.join(rest), &file).unknown()?;
+            }
+        } else if metadata.is_dir() {
+            relink(&file, from, to)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("1").unwrap(), 1);
+        assert_eq!(parse_version("2").unwrap(), XDG_VERSION);
+        assert!(parse_version("3").is_err());
+    }
+
+    #[test]
+    fn test_base_dir() {
+        let home = Path::new("/home/user");
+        assert_eq!(
+            base_dir("NOTION_TEST_UNSET_XDG_DIR", home, ".cache"),
+            PathBuf::from("/home/user/.cache")
+        );
+    }
+
+    #[test]
+    fn test_migrate() {
+        let home = tempdir().expect("Could not create temporary directory");
+        let legacy = Layout::in_home(home.path().join(".notion"));
+        let xdg = Layout {
+            user_root: home.path().join("config"),
+            cache_root: home.path().join("cache"),
+            install_root: home.path().join("data"),
+            shim_dir: home.path().join("data").join("bin"),
+            data_root: home.path().join("data"),
+            system_root: None,
+            portable: false,
+        };
+
+        let farm = legacy.data_root.join("store").join("farms").join("node").join("8.6.0");
+        fs::create_dir_all(&farm).unwrap();
+        fs::create_dir_all(legacy.data_root.join("versions").join("node")).unwrap();
+        unix::fs::symlink(&farm, legacy.data_root.join("versions/node/8.6.0")).unwrap();
+        fs::create_dir_all(legacy.cache_root.join("node")).unwrap();
+        fs::write(legacy.data_root.join("catalog.toml"), "").unwrap();
+
+        migrate(&legacy, &xdg).unwrap();
+
+        assert!(!legacy.data_root.exists());
+        assert!(xdg.user_root.join("catalog.toml").is_file());
+        assert!(xdg.cache_root.join("node").is_dir());
+        assert_eq!(
+            fs::read_link(xdg.data_root.join("versions/node/8.6.0")).unwrap(),
+            xdg.data_root.join("store/farms/node/8.6.0")
+        );
+        assert_eq!(
+            fs::read_to_string(xdg.user_root.join(VERSION_FILE)).unwrap(),
+            "2\n"
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! Provides the `Plan` type, which describes the changes a mutating command would
+//! make to the filesystem, for reporting in dry-run mode.
+
+use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Puts the rest of the process in dry-run mode, as set by `notion --dry-run`, in
+/// which reading state never creates files and housekeeping is skipped.
+pub fn enable_dry_run() {
+    DRY_RUN.store(true, Ordering::Relaxed);
+}
+
+/// Returns true if the process is in dry-run mode.
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// A single filesystem change.
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    /// A file would be downloaded from a URL.
+    Download { url: String, file: PathBuf },
+    /// An archive would be unpacked into a directory.
+    Unpack { archive: PathBuf, dir: PathBuf },
+    /// A file would be created or overwritten.
+    Write(PathBuf),
+    /// A file or directory would be removed.
+    Remove(PathBuf),
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            &Action::Download { ref url, ref file } => {
+                write!(f, "would download {} to {}", url, file.display())
+            }
+            &Action::Unpack {
+                ref archive,
+                ref dir,
+            } => write!(f, "would unpack {} into {}", archive.display(), dir.display()),
+            &Action::Write(ref file) => write!(f, "would write {}", file.display()),
+            &Action::Remove(ref file) => write!(f, "would remove {}", file.display()),
+        }
+    }
+}
+
+/// The ordered list of changes a command would make.
+#[derive(Debug, Default)]
+pub struct Plan {
+    actions: Vec<Action>,
+}
+
+impl Plan {
+    /// Constructs an empty plan.
+    pub fn new() -> Self {
+        Plan::default()
+    }
+
+    /// Adds an action to the plan, ignoring it if an identical action is already planned.
+    pub fn push(&mut self, action: Action) {
+        if !self.actions.contains(&action) {
+            self.actions.push(action);
+        }
+    }
+
+    /// Appends all the actions of another plan to this plan.
+    pub fn extend(&mut self, other: Plan) {
+        for action in other.actions {
+            self.push(action);
+        }
+    }
+
+    /// Returns true if the plan makes no changes.
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// Produces the planned actions, in order.
+    pub fn actions(&self) -> &[Action] {
+        &self.actions
+    }
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.actions.is_empty() {
+            return write!(f, "nothing to do");
+        }
+
+        let lines: Vec<String> = self.actions.iter().map(|action| action.to_string()).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{Action, Plan};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_plan_deduplicates_actions() {
+        let mut plan = Plan::new();
+        assert_eq!(plan.to_string(), "nothing to do");
+
+        plan.push(Action::Write(PathBuf::from("catalog.toml")));
+        plan.push(Action::Remove(PathBuf::from("shim")));
+        plan.push(Action::Write(PathBuf::from("catalog.toml")));
+
+        assert_eq!(plan.actions().len(), 2);
+        assert_eq!(
+            plan.to_string(),
+            "would write catalog.toml\nwould remove shim"
+        );
+    }
+}
@@ -4,7 +4,7 @@ use std::ffi::OsString;
 use std::io::Read;
 use std::process::{Command, Stdio};
 
-use distro::Distro;
+use distro::Location;
 
 use cmdline_words_parser::StrExt;
 use notion_fail::{FailExt, Fallible, ResultExt};
@@ -34,8 +34,8 @@ pub struct InvalidCommandError {
 
 impl ResolvePlugin {
     /// Performs resolution of a Tool version based on the given semantic
-    /// versioning requirements.
-    pub fn resolve<D: Distro>(&self, _matching: &VersionSpec) -> Fallible<D> {
+    /// versioning requirements, producing the location to download it from.
+    pub fn locate(&self, _matching: &VersionSpec) -> Fallible<Location> {
         match self {
             &ResolvePlugin::Url(_) => unimplemented!(),
 
@@ -67,7 +67,7 @@ impl ResolvePlugin {
                     .unknown()?;
                 let response = ResolveResponse::from_reader(child.stdout.unwrap())?;
                 match response {
-                    ResolveResponse::Url { version, url } => D::remote(version, &url),
+                    ResolveResponse::Url { version, url } => Ok(Location { version, url }),
                     ResolveResponse::Stream { version: _version } => {
                         unimplemented!("bin plugin produced a stream")
                     }
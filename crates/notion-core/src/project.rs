@@ -1,7 +1,7 @@
 //! Provides the `Project` type, which represents a Node project tree in
 //! the filesystem.
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::env;
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
@@ -9,12 +9,31 @@ use std::rc::Rc;
 
 use lazycell::LazyCell;
 
+use autoshim::{self, Diff, Origin, Record};
 use image::Image;
-use manifest::Manifest;
+use lockfile::Lockfile;
+use logging;
+use manifest::{Manifest, PinStyle, Toolchain};
 use manifest::serial;
 use notion_fail::{ExitCode, Fallible, NotionError, NotionFail, ResultExt};
+use plan::Plan;
 use semver::Version;
-use shim;
+use shim::{self, ShimResult};
+use toolchain::{self, Autoshim, ToolchainFile, TOOLCHAIN_FILE};
+
+/// Warns that a new shim shadows executables of the same name on the `PATH`, which
+/// autoshimming can't ask about. A failure to look is not worth a warning of its own.
+fn warn_if_shadowing(shim_name: &str) {
+    if let Ok(shadowed) = shim::conflicts(shim_name) {
+        if let Some(first) = shadowed.first() {
+            logging::warning(&format!(
+                "the shim for `{}` shadows {}",
+                shim_name,
+                first.display()
+            ));
+        }
+    }
+}
 
 fn is_node_root(dir: &Path) -> bool {
     dir.join("package.json").is_file()
@@ -28,7 +47,7 @@ fn is_dependency(dir: &Path) -> bool {
     dir.parent().map_or(false, |parent| is_node_modules(parent))
 }
 
-fn is_project_root(dir: &Path) -> bool {
+pub(crate) fn is_project_root(dir: &Path) -> bool {
     is_node_root(dir) && !is_dependency(dir)
 }
 
@@ -81,6 +100,8 @@ impl NoPinnedNodeVersion {
 /// A Node project tree in the filesystem.
 pub struct Project {
     manifest: Manifest,
+    toolchain_file: Option<ToolchainFile>,
+    lockfile: Option<Lockfile>,
     project_root: PathBuf,
     dependent_bins: LazyDependentBins,
 }
@@ -107,19 +128,75 @@ impl Project {
 
         Ok(Some(Project {
             manifest: Manifest::for_dir(&dir)?,
+            toolchain_file: ToolchainFile::for_project(&dir)?,
+            lockfile: Lockfile::for_project(&dir)?,
             project_root: PathBuf::from(dir),
             dependent_bins: LazyDependentBins::new(),
         }))
     }
 
-    /// Returns the pinned platform image, if any.
+    /// Returns the `notion.toml` file next to `package.json` if it pins a toolchain,
+    /// in which case it takes precedence over the `toolchain` key in `package.json`.
+    fn pinning_toolchain_file(&self) -> Option<&ToolchainFile> {
+        match self.toolchain_file {
+            Some(ref file) if file.toolchain().is_some() => Some(file),
+            _ => None,
+        }
+    }
+
+    /// Returns the pinned toolchain, if any, which may pin ranges.
+    pub fn toolchain(&self) -> Option<&Toolchain> {
+        match self.pinning_toolchain_file() {
+            Some(file) => file.toolchain(),
+            None => self.manifest.toolchain.as_ref(),
+        }
+    }
+
+    /// Returns the pinned platform image, if the toolchain only pins exact versions.
     pub fn platform(&self) -> Option<Rc<Image>> {
-        self.manifest.platform()
+        match self.pinning_toolchain_file() {
+            Some(file) => file.platform(),
+            None => self.manifest.platform(),
+        }
     }
 
-    /// Returns true if the project manifest contains a toolchain.
+    /// Returns the project's toolchain lockfile (`notion.lock`), if it has one.
+    pub fn lockfile(&self) -> Option<&Lockfile> {
+        self.lockfile.as_ref()
+    }
+
+    /// Returns true if the project pins a toolchain.
     pub fn is_pinned(&self) -> bool {
-        self.manifest.platform().is_some()
+        self.toolchain().is_some()
+    }
+
+    /// Returns the file the project's toolchain is pinned in: its `notion.toml` if
+    /// that pins a toolchain, and `package.json` otherwise.
+    pub fn toolchain_source(&self) -> PathBuf {
+        match self.pinning_toolchain_file() {
+            Some(file) => file.file().to_path_buf(),
+            None => self.package_file(),
+        }
+    }
+
+    /// Returns the file that pins are written to: `notion.toml` if `to_file` is set or
+    /// the project already pins its toolchain there, and `package.json` otherwise.
+    pub fn pin_file(&self, to_file: bool) -> PathBuf {
+        if to_file {
+            self.project_root.join(TOOLCHAIN_FILE)
+        } else {
+            self.toolchain_source()
+        }
+    }
+
+    /// Returns the project's autoshim settings: the `[autoshim]` section of its
+    /// `notion.toml` if it has one, and the `autoshim` key of the `toolchain` section of
+    /// its `package.json` otherwise.
+    fn autoshim_settings(&self) -> Option<&Autoshim> {
+        self.toolchain_file
+            .as_ref()
+            .and_then(ToolchainFile::autoshim)
+            .or(self.manifest.autoshim.as_ref())
     }
 
     /// Returns the project manifest (`package.json`) for this project.
@@ -127,11 +204,21 @@ impl Project {
         &self.manifest
     }
 
+    /// Returns the root directory of this project, which contains its `package.json`.
+    pub fn root(&self) -> &Path {
+        &self.project_root
+    }
+
     /// Returns the path to the `package.json` file for this project.
     pub fn package_file(&self) -> PathBuf {
         self.project_root.join("package.json")
     }
 
+    /// Returns the path to the project's `hooks.toml` file, which may not exist.
+    pub fn hooks_file(&self) -> PathBuf {
+        self.project_root.join("hooks.toml")
+    }
+
     /// Returns the path to the local binary directory for this project.
     pub fn local_bin_dir(&self) -> PathBuf {
         let sub_dir: PathBuf = ["node_modules", ".bin"].iter().collect();
@@ -150,23 +237,140 @@ impl Project {
     }
 
     /// Automatically shim the binaries of all direct dependencies of this project and
-    /// return a vector of any errors which occurred while doing so.
-    pub fn autoshim(&self) -> Vec<NotionError> {
-        let dependent_binaries = self.dependent_binary_names_fault_tolerant();
+    /// return a vector of any errors which occurred while doing so. Only binaries added
+    /// since the project was last autoshimmed get new shims, which are created in
+    /// parallel (see `shim::create_all`). The shims this project
+    /// created for binaries that were removed since are deleted unless another project
+    /// or an installed package still provides them, or `keep_orphans` is set.
+    pub fn autoshim(&self, keep_orphans: bool) -> Vec<NotionError> {
+        let (recorded, current, mut errors) = self.autoshim_bins();
+        let diff = Diff::between(&recorded, &current);
+        let mut shimmed = recorded.clone();
+
+        for (name, result) in shim::create_all(diff.added) {
+            match result {
+                Ok(ShimResult::Created) => {
+                    warn_if_shadowing(&name);
+                    shimmed.insert(name, Origin::Created);
+                }
+                Ok(_) => {
+                    shimmed.insert(name, Origin::Existing);
+                }
+                Err(error) => errors.push(error),
+            }
+        }
+
+        if !keep_orphans {
+            for name in diff.removed {
+                let origin = recorded[&name];
+                let result = autoshim::should_delete(&self.project_root, &name, origin)
+                    .and_then(|delete| {
+                        if delete {
+                            shim::delete(&name)?;
+                        }
+                        Ok(())
+                    });
+                match result {
+                    Ok(()) => {
+                        shimmed.remove(&name);
+                    }
+                    Err(error) => errors.push(error),
+                }
+            }
+        }
+
+        if let Err(error) = autoshim::record(&self.project_root, &shimmed) {
+            errors.push(error);
+        }
+
+        errors
+    }
+
+    /// Plans automatically shimming the binaries of all direct dependencies, without
+    /// touching the filesystem. Returns the plan along with any errors that occurred.
+    pub fn plan_autoshim(&self, keep_orphans: bool) -> (Plan, Vec<NotionError>) {
+        let mut plan = Plan::new();
+        let (recorded, current, mut errors) = self.autoshim_bins();
+        let diff = Diff::between(&recorded, &current);
+
+        for name in diff.added {
+            match shim::plan_create(&name) {
+                Ok(shim_plan) => plan.extend(shim_plan),
+                Err(error) => errors.push(error),
+            }
+        }
+
+        if !keep_orphans {
+            for name in diff.removed {
+                let origin = recorded[&name];
+                let result = autoshim::should_delete(&self.project_root, &name, origin)
+                    .and_then(|delete| {
+                        if delete {
+                            shim::plan_delete(&name)
+                        } else {
+                            Ok(Plan::new())
+                        }
+                    });
+                match result {
+                    Ok(shim_plan) => plan.extend(shim_plan),
+                    Err(error) => errors.push(error),
+                }
+            }
+        }
+
+        (plan, errors)
+    }
+
+    /// Produces the binaries recorded the last time this project was autoshimmed and the
+    /// binaries its settings allow shimming now, along with any errors that occurred.
+    /// When some dependencies could not be read, their binaries are assumed to still be
+    /// there, so the recorded binaries are kept rather than having their shims deleted.
+    fn autoshim_bins(&self) -> (Record, BTreeSet<String>, Vec<NotionError>) {
         let mut errors = Vec::new();
+        let mut current = BTreeSet::new();
 
-        for result in dependent_binaries {
+        for result in self.dependent_binary_names_fault_tolerant() {
             match result {
                 Ok(name) => {
-                    if let Err(error) = shim::create(&name) {
-                        errors.push(error);
+                    if self.allows_autoshim(&name) {
+                        current.insert(name);
                     }
-                },
+                }
                 Err(error) => errors.push(error),
             }
         }
 
-        errors
+        let recorded = match autoshim::recorded(&self.project_root) {
+            Ok(recorded) => recorded,
+            Err(error) => {
+                errors.push(error);
+                Record::new()
+            }
+        };
+
+        if !errors.is_empty() {
+            current.extend(recorded.keys().cloned());
+        }
+
+        (recorded, current, errors)
+    }
+
+    /// Returns the names of the binaries of direct dependencies that the project's
+    /// autoshim settings keep from being shimmed, in order.
+    pub fn autoshim_excluded(&self) -> Vec<String> {
+        let excluded: BTreeSet<String> = self
+            .dependent_binary_names_fault_tolerant()
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|name| !self.allows_autoshim(name))
+            .collect();
+        excluded.into_iter().collect()
+    }
+
+    /// Returns true if the project's autoshim settings allow shimming the named binary.
+    fn allows_autoshim(&self, name: &str) -> bool {
+        self.autoshim_settings()
+            .map_or(true, |settings| settings.allows(name))
     }
 
     /// Returns a mapping of the names to paths for all the binaries installed
@@ -224,24 +428,69 @@ impl Project {
         path
     }
 
-    /// Writes the specified version of Node to the `toolchain.node` key in package.json.
-    pub fn pin_node_in_toolchain(&self, node_version: Version) -> Fallible<()> {
+    /// Writes a toolchain to the project's pin file (see `pin_file`).
+    fn write_toolchain(
+        &self,
+        node: String,
+        yarn: Option<String>,
+        npm: Option<String>,
+        to_file: bool,
+    ) -> Fallible<PathBuf> {
+        let file = self.pin_file(to_file);
+        if file == self.package_file() {
+            let mut image = serial::Image::new(node, yarn, npm);
+            image.autoshim = self.manifest.autoshim.as_ref().map(Autoshim::to_serial);
+            Manifest::update_toolchain(image, file.clone())?;
+        } else {
+            let image = toolchain::serial::Image {
+                node: node.into(),
+                yarn: yarn.map(Into::into),
+                npm,
+            };
+            toolchain::write_toolchain(&file, image)?;
+        }
+        Ok(file)
+    }
+
+    /// Writes the specified version of Node to the `toolchain.node` key in package.json,
+    /// or in notion.toml (see `pin_file`), formatted in the given style.
+    pub fn pin_node_in_toolchain(
+        &self,
+        node_version: Version,
+        style: PinStyle,
+        to_file: bool,
+    ) -> Fallible<()> {
         // update the toolchain node version
-        let toolchain =
-            serial::Image::new(node_version.to_string(), self.manifest().yarn_str().clone());
-        Manifest::update_toolchain(toolchain, self.package_file())?;
-        println!("Pinned node to version {} in package.json", node_version);
+        let pinned = style.format(&node_version);
+        let current = self.toolchain();
+        let file = self.write_toolchain(
+            pinned.clone(),
+            current.and_then(|toolchain| toolchain.yarn_str.clone()),
+            current.and_then(|toolchain| toolchain.npm_str.clone()),
+            to_file,
+        )?;
+        println!("Pinned node to version {} in {}", pinned, file_name(&file));
         Ok(())
     }
 
-    /// Writes the specified version of Yarn to the `toolchain.yarn` key in package.json.
-    pub fn pin_yarn_in_toolchain(&self, yarn_version: Version) -> Fallible<()> {
+    /// Writes the specified version of Yarn to the `toolchain.yarn` key in package.json,
+    /// or in notion.toml (see `pin_file`), formatted in the given style.
+    pub fn pin_yarn_in_toolchain(
+        &self,
+        yarn_version: Version,
+        style: PinStyle,
+        to_file: bool,
+    ) -> Fallible<()> {
         // update the toolchain yarn version
-        if let Some(node_str) = self.manifest().node_str() {
-            let toolchain =
-                serial::Image::new(node_str.clone(), Some(yarn_version.to_string()));
-            Manifest::update_toolchain(toolchain, self.package_file())?;
-            println!("Pinned yarn to version {} in package.json", yarn_version);
+        if let Some(current) = self.toolchain() {
+            let pinned = style.format(&yarn_version);
+            let file = self.write_toolchain(
+                current.node_str.clone(),
+                Some(pinned.clone()),
+                current.npm_str.clone(),
+                to_file,
+            )?;
+            println!("Pinned yarn to version {} in {}", pinned, file_name(&file));
         } else {
             throw!(NoPinnedNodeVersion::new());
         }
@@ -249,6 +498,12 @@ impl Project {
     }
 }
 
+fn file_name(file: &Path) -> String {
+    file.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
 // unit tests
 
 #[cfg(test)]
@@ -258,6 +513,7 @@ pub mod tests {
     use std::path::PathBuf;
 
     use project::Project;
+    use semver::Version;
 
     fn fixture_path(fixture_dir: &str) -> PathBuf {
         let mut cargo_manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -342,4 +598,31 @@ pub mod tests {
 
         assert!(test_project.get_dependency_path(&"foo".to_string()) == expected_path);
     }
+
+    #[test]
+    fn reads_toolchain_from_notion_toml() {
+        let project_path = fixture_path("toml_project");
+        let test_project = Project::for_dir(&project_path).unwrap().unwrap();
+
+        assert!(test_project.is_pinned());
+        assert_eq!(test_project.toolchain_source(), project_path.join("notion.toml"));
+        assert_eq!(test_project.pin_file(false), project_path.join("notion.toml"));
+
+        let platform = test_project.platform().expect("Did not read toolchain");
+        assert_eq!(platform.node, Version::parse("8.9.4").unwrap());
+        assert_eq!(platform.yarn, Some(Version::parse("1.7.0").unwrap()));
+
+        assert!(!test_project.allows_autoshim("rsvp"));
+        assert!(test_project.allows_autoshim("eslint"));
+    }
+
+    #[test]
+    fn pins_to_package_json_by_default() {
+        let project_path = fixture_path("basic");
+        let test_project = Project::for_dir(&project_path).unwrap().unwrap();
+
+        assert_eq!(test_project.toolchain_source(), project_path.join("package.json"));
+        assert_eq!(test_project.pin_file(true), project_path.join("notion.toml"));
+        assert!(test_project.allows_autoshim("rsvp"));
+    }
 }
@@ -0,0 +1,250 @@
+//! Provides the status shown by `notion prompt`, which shell prompts display on every
+//! render and so must produce in a few milliseconds, and the versions in effect with
+//! where they were selected, as listed by `notion status`.
+//!
+//! Inside a project, the status is answered from the project's entry in the platform
+//! cache (see `image::cache`), without reading the catalog or the configuration. Only
+//! when there is no entry, or when the shell session selects versions by range, is the
+//! platform resolved in full, which also caches it for the next render.
+
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+
+use semver::Version;
+
+use env;
+use image::{cache, Image};
+use notion_fail::Fallible;
+use project::is_project_root;
+use session::Session;
+
+/// The format the status is rendered in by default.
+pub const DEFAULT_FORMAT: &'static str = "⬢ {node}";
+
+/// The versions in effect, as shown in a prompt.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Status {
+    pub node: String,
+    pub yarn: Option<String>,
+}
+
+impl Status {
+    fn from_image(image: &Image) -> Self {
+        Status {
+            node: image.node_str.clone(),
+            yarn: image.yarn_str.clone(),
+        }
+    }
+
+    /// Renders the status in a format, replacing `{node}` and `{yarn}` with the Node and
+    /// Yarn versions. `{yarn}` is empty when no Yarn version is in effect.
+    pub fn render(&self, format: &str) -> String {
+        format
+            .replace("{node}", &self.node)
+            .replace("{yarn}", self.yarn.as_ref().map_or("", |yarn| yarn.as_str()))
+    }
+}
+
+/// A version selected for the shell session by `notion use --session`.
+enum Override {
+    None,
+    Exact(String),
+    /// A range, which can only be resolved against the catalog.
+    Range,
+}
+
+impl Override {
+    fn of(tool: &str) -> Self {
+        match env::version_override(tool) {
+            Some(value) => match Version::parse(value.trim().trim_left_matches('v')) {
+                Ok(version) => Override::Exact(version.to_string()),
+                Err(_) => Override::Range,
+            },
+            None => Override::None,
+        }
+    }
+}
+
+/// Answers the status of the project containing `dir` from the platform cache. Produces
+/// `None` when the status can't be answered without resolving the platform in full.
+pub fn cached_status(dir: &Path) -> Fallible<Option<Status>> {
+    // an npm version selects the Node version that bundles it, which takes the catalog
+    if env::version_override("npm").is_some() {
+        return Ok(None);
+    }
+    let (node, yarn) = match (Override::of("node"), Override::of("yarn")) {
+        (Override::Range, _) | (_, Override::Range) => return Ok(None),
+        overrides => overrides,
+    };
+
+    let mut root = dir;
+    while !is_project_root(root) {
+        root = match root.parent() {
+            Some(parent) => parent,
+            None => return Ok(None),
+        };
+    }
+
+    let fingerprint = cache::Fingerprint::of(root)?;
+    let mut status = match cache::load(root, &fingerprint) {
+        Some(image) => Status::from_image(&image),
+        None => return Ok(None),
+    };
+    if let Override::Exact(node) = node {
+        status.node = node;
+    }
+    if let Override::Exact(yarn) = yarn {
+        status.yarn = Some(yarn);
+    }
+    Ok(Some(status))
+}
+
+/// Where the version of a tool in effect was selected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    /// The shell session, by `notion use --session`.
+    Env,
+    /// The current project's toolchain.
+    Project,
+    /// A `notion.toml` or `.notion-version` file in the current directory or above it.
+    Directory,
+    /// The user toolchain.
+    User,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            &Source::Env => "env",
+            &Source::Project => "project",
+            &Source::Directory => "directory",
+            &Source::User => "user",
+        })
+    }
+}
+
+/// The version of a tool in effect, and where it was selected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection {
+    pub tool: &'static str,
+    pub version: Version,
+    pub source: Source,
+}
+
+/// Resolves the status in full, caching the current project's platform so that later
+/// prompts are answered by `cached_status`.
+pub fn status(session: &mut Session) -> Fallible<Option<Status>> {
+    let selections = selections(session)?;
+    let version = |tool: &str| {
+        selections
+            .iter()
+            .find(|selection| selection.tool == tool)
+            .map(|selection| selection.version.to_string())
+    };
+    Ok(version("node").map(|node| Status {
+        node,
+        yarn: version("yarn"),
+    }))
+}
+
+/// Resolves the versions of Node and Yarn in effect, in that order, and where each was
+/// selected. Unlike other commands, this never asks whether to trust the project, since
+/// it runs on every prompt: the pins of a project that hasn't been trusted are ignored.
+pub fn selections(session: &mut Session) -> Fallible<Vec<Selection>> {
+    let image = platform(session)?;
+    let (node, yarn) = session.shell_versions()?;
+    let mut selections = Vec::new();
+    // without a toolchain, only a Node version selected for the shell session takes effect
+    if image.is_none() && node.is_none() {
+        return Ok(selections);
+    }
+
+    match (node, &image) {
+        (Some(node), _) => selections.push(Selection {
+            tool: "node",
+            version: node,
+            source: Source::Env,
+        }),
+        (None, &Some((ref image, source))) => selections.push(Selection {
+            tool: "node",
+            version: image.node.clone(),
+            source,
+        }),
+        (None, &None) => {}
+    }
+    match (yarn, &image) {
+        (Some(yarn), _) => selections.push(Selection {
+            tool: "yarn",
+            version: yarn,
+            source: Source::Env,
+        }),
+        (None, &Some((ref image, source))) => if let Some(ref yarn) = image.yarn {
+            selections.push(Selection {
+                tool: "yarn",
+                version: yarn.clone(),
+                source,
+            });
+        },
+        (None, &None) => {}
+    }
+    Ok(selections)
+}
+
+/// Finds the platform image in effect, before the shell session's versions apply to it.
+fn platform(session: &mut Session) -> Fallible<Option<(Rc<Image>, Source)>> {
+    session.trust_without_asking()?;
+    if let Some(project) = session.trusted_project()? {
+        if let Some(image) = session.project_platform()? {
+            cache_platform(session, project.root(), &image)?;
+            return Ok(Some((image, Source::Project)));
+        }
+    }
+    if let Some(image) = session.directory_platform()? {
+        return Ok(Some((image, Source::Directory)));
+    }
+    Ok(session.user_platform()?.map(|image| (image, Source::User)))
+}
+
+/// Caches a project's platform if it is fully installed, since a version that isn't
+/// could resolve differently once the index is refreshed.
+fn cache_platform(session: &Session, root: &Path, image: &Rc<Image>) -> Fallible<()> {
+    let fingerprint = cache::Fingerprint::of(root)?;
+    if cache::load(root, &fingerprint).is_some() {
+        return Ok(());
+    }
+    let catalog = session.catalog()?;
+    let installed = catalog.node.contains(&image.node)
+        && image.yarn.as_ref().map_or(true, |yarn| catalog.yarn.contains(yarn));
+    if installed {
+        cache::store(root, &fingerprint, image);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    fn status(yarn: Option<&str>) -> Status {
+        Status {
+            node: "18.17.1".to_string(),
+            yarn: yarn.map(|yarn| yarn.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_render_default() {
+        assert_eq!(status(None).render(DEFAULT_FORMAT), "⬢ 18.17.1");
+    }
+
+    #[test]
+    fn test_render_yarn() {
+        assert_eq!(
+            status(Some("1.22.19")).render("node {node} yarn {yarn}"),
+            "node 18.17.1 yarn 1.22.19"
+        );
+        assert_eq!(status(None).render("{node}|{yarn}"), "18.17.1|");
+    }
+}
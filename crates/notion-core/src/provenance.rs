@@ -0,0 +1,424 @@
+//! Provides the provenance recorded in the catalog for every archive Notion installs a
+//! version from: the URL it was downloaded from, when, its SHA-256 digest, and how it
+//! was verified.
+//!
+//! Distributors that publish SLSA provenance attestations alongside their archives,
+//! as `<archive URL>.intoto.jsonl`, can have each archive checked against the
+//! attestation's subjects when it is installed, with the `provenance.attestations`
+//! setting. The attestation's signatures are recorded but not checked, since that
+//! requires the distributor's keys.
+
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64;
+use reqwest::{self, StatusCode};
+use serde_json;
+use tempfile::NamedTempFile;
+
+use checksum::{sha256_hex, Checksum};
+use http;
+use notion_fail::{ExitCode, Fallible, NotionError, NotionFail, ResultExt};
+use timing::{self, Phase};
+
+/// Thrown when a recorded verification is not one Notion knows.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Invalid verification '{}' (expected unverified, checksum, attested, or \
+                  attested-signed)",
+       value)]
+#[notion_fail(code = "UnknownError")]
+pub(crate) struct VerificationParseError {
+    value: String,
+}
+
+/// Thrown when an attestation published for an archive could not be downloaded.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not download attestation {}\n{}", url, error)]
+#[notion_fail(code = "NetworkError")]
+pub(crate) struct AttestationDownloadError {
+    url: String,
+    error: String,
+}
+
+impl AttestationDownloadError {
+    fn for_url(url: &str) -> impl FnOnce(&reqwest::Error) -> AttestationDownloadError {
+        let url = url.to_string();
+        move |error| AttestationDownloadError {
+            url: url,
+            error: error.to_string(),
+        }
+    }
+}
+
+/// Thrown when an archive is not a subject of the attestation published for it.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} does not match the provenance attestation published for it\n\
+                  (no subject has the SHA-256 digest {})",
+       url, digest)]
+#[notion_fail(code = "NetworkError")]
+pub(crate) struct AttestationMismatchError {
+    url: String,
+    digest: String,
+}
+
+/// Thrown when an attestation can't be read as DSSE envelopes of in-toto statements.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not read attestation {}", url)]
+#[notion_fail(code = "NetworkError")]
+pub(crate) struct AttestationParseError {
+    url: String,
+}
+
+impl AttestationParseError {
+    fn for_url<E>(url: &str) -> impl FnOnce(&E) -> AttestationParseError {
+        let url = url.to_string();
+        move |_| AttestationParseError { url: url }
+    }
+}
+
+/// How a downloaded archive was verified.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verification {
+    /// Nothing was known to check the archive against.
+    Unverified,
+    /// The archive matched the checksum given when it was installed.
+    Checksum,
+    /// The archive is a subject of the provenance attestation published alongside it,
+    /// whose signatures were not checked.
+    Attested {
+        /// Whether the attestation carried any signatures.
+        signed: bool,
+    },
+}
+
+impl FromStr for Verification {
+    type Err = NotionError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        match src {
+            "unverified" => Ok(Verification::Unverified),
+            "checksum" => Ok(Verification::Checksum),
+            "attested" => Ok(Verification::Attested { signed: false }),
+            "attested-signed" => Ok(Verification::Attested { signed: true }),
+            _ => throw!(VerificationParseError {
+                value: src.to_string(),
+            }),
+        }
+    }
+}
+
+impl Display for Verification {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(match self {
+            &Verification::Unverified => "unverified",
+            &Verification::Checksum => "checksum",
+            &Verification::Attested { signed: false } => "attested",
+            &Verification::Attested { signed: true } => "attested-signed",
+        })
+    }
+}
+
+/// The provenance of the archive an installed version was unpacked from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provenance {
+    /// The URL the archive was downloaded from, or a `file://` URL for local archives.
+    pub url: String,
+    /// When the archive was downloaded, in seconds since the Unix epoch.
+    pub fetched: u64,
+    /// The SHA-256 digest of the archive, as lowercase hex.
+    pub sha256: String,
+    pub verification: Verification,
+}
+
+/// Where a distribution's archive came from, which becomes its provenance once it
+/// has been fetched in full.
+#[derive(Debug, Clone)]
+pub struct Source {
+    url: String,
+    archive: Archive,
+    verification: Verification,
+}
+
+/// What is known of an archive before it is installed.
+#[derive(Debug, Clone)]
+enum Archive {
+    /// The file the archive is kept in, which is hashed once it is complete.
+    File(PathBuf),
+    /// The archive's digest, computed when it was downloaded into a temporary file.
+    Digest(String),
+    /// The temporary file an archive is written to as it is unpacked from the download,
+    /// and the checksum it is verified against once it is complete.
+    Streamed(Arc<NamedTempFile>, Option<Checksum>),
+}
+
+impl Source {
+    /// The source of an archive downloaded from `url` into the cache file `file`.
+    pub fn cached(url: &str, file: PathBuf) -> Source {
+        Source {
+            url: url.to_string(),
+            archive: Archive::File(file),
+            verification: Verification::Unverified,
+        }
+    }
+
+    /// The source of an archive read from a local file.
+    pub fn local(file: &Path) -> Source {
+        Source {
+            url: format!("file://{}", file.display()),
+            archive: Archive::File(file.to_path_buf()),
+            verification: Verification::Unverified,
+        }
+    }
+
+    /// The source of an archive downloaded from `url` into a temporary file, whose
+    /// digest was computed as it was downloaded.
+    pub fn downloaded(url: &str, digest: String, verification: Verification) -> Source {
+        Source {
+            url: url.to_string(),
+            archive: Archive::Digest(digest),
+            verification,
+        }
+    }
+
+    /// The source of an archive downloaded from `url` into the temporary file `file` as
+    /// it is unpacked, which is verified against `checksum`, if given, once it is
+    /// complete (see `verify`).
+    pub fn streamed(url: &str, file: NamedTempFile, checksum: Option<&Checksum>) -> Source {
+        Source {
+            url: url.to_string(),
+            archive: Archive::Streamed(Arc::new(file), checksum.cloned()),
+            verification: match checksum {
+                Some(_) => Verification::Checksum,
+                None => Verification::Unverified,
+            },
+        }
+    }
+
+    /// Produces the URL the archive was downloaded from, or a `file://` URL for local
+    /// archives.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Verifies a streamed archive against its checksum, which can only be done once it
+    /// has been fetched in full. Other archives are verified before they are unpacked.
+    pub fn verify(&self) -> Fallible<()> {
+        if let Archive::Streamed(ref file, Some(ref checksum)) = self.archive {
+            let mut file = File::open(file.path()).unknown()?;
+            timing::time(Phase::Checksum, || checksum.verify(&self.url, &mut file))?;
+        }
+        Ok(())
+    }
+
+    /// Produces the provenance of the archive, which must have been fetched in full.
+    /// Archives kept in a file are dated by the file, since a cached archive may have
+    /// been downloaded long before it is installed.
+    pub fn provenance(&self) -> Fallible<Provenance> {
+        let (sha256, fetched) = match self.archive {
+            Archive::Digest(ref digest) => (digest.clone(), now()),
+            Archive::Streamed(ref file, _) => {
+                (sha256_hex(&mut File::open(file.path()).unknown()?)?, now())
+            }
+            Archive::File(ref file) => {
+                let digest = sha256_hex(&mut File::open(file).unknown()?)?;
+                let modified = fs::metadata(file)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+                    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                    .map_or_else(now, |elapsed| elapsed.as_secs());
+                (digest, modified)
+            }
+        };
+        Ok(Provenance {
+            url: self.url.clone(),
+            fetched,
+            sha256,
+            verification: self.verification,
+        })
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+/// Produces the URL an attestation for the archive at `url` is published at.
+pub fn attestation_url(url: &str) -> String {
+    format!("{}.intoto.jsonl", url)
+}
+
+/// Checks a downloaded archive against the provenance attestation published alongside
+/// it, if there is one, recording that it was attested. Archives that are not a
+/// subject of their attestation are rejected.
+pub fn attest(provenance: &mut Provenance) -> Fallible<()> {
+    if !provenance.url.starts_with("https://") && !provenance.url.starts_with("http://") {
+        return Ok(());
+    }
+
+    let url = attestation_url(&provenance.url);
+    let src = match download_attestation(&url)? {
+        Some(src) => src,
+        None => return Ok(()),
+    };
+
+    let statements = parse_attestation(&url, &src)?;
+    let mut attested = false;
+    let mut signed = false;
+    for (digests, signatures) in statements {
+        if digests.contains(&provenance.sha256) {
+            attested = true;
+            signed = signed || signatures > 0;
+        }
+    }
+
+    if !attested {
+        throw!(AttestationMismatchError {
+            url: provenance.url.clone(),
+            digest: provenance.sha256.clone(),
+        });
+    }
+    provenance.verification = Verification::Attested { signed };
+    Ok(())
+}
+
+/// Downloads an attestation, producing `None` if the distributor doesn't publish one.
+fn download_attestation(url: &str) -> Fallible<Option<String>> {
+    let mut response = http::get(url)?.with_context(AttestationDownloadError::for_url(url))?;
+    if response.status() == StatusCode::NotFound {
+        return Ok(None);
+    }
+    if !response.status().is_success() {
+        throw!(AttestationDownloadError {
+            url: url.to_string(),
+            error: format!("server responded with {}", response.status()),
+        });
+    }
+    let src = response
+        .text()
+        .with_context(AttestationDownloadError::for_url(url))?;
+    Ok(Some(src))
+}
+
+/// A DSSE envelope (https://github.com/secure-systems-lab/dsse).
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Envelope {
+    payload_type: String,
+    payload: String,
+    #[serde(default)]
+    signatures: Vec<serde_json::Value>,
+}
+
+/// The parts of an in-toto statement (https://github.com/in-toto/attestation) that
+/// archives are checked against.
+#[derive(Deserialize)]
+struct Statement {
+    subject: Vec<Subject>,
+}
+
+#[derive(Deserialize)]
+struct Subject {
+    digest: Digests,
+}
+
+#[derive(Deserialize)]
+struct Digests {
+    sha256: Option<String>,
+}
+
+/// Parses the `.intoto.jsonl` attestation at `url`, which has a DSSE envelope per line, into the
+/// SHA-256 digests of each statement's subjects and the number of its signatures.
+fn parse_attestation(url: &str, src: &str) -> Fallible<Vec<(Vec<String>, usize)>> {
+    let mut statements = Vec::new();
+    for line in src.lines().filter(|line| !line.trim().is_empty()) {
+        let envelope: Envelope =
+            serde_json::from_str(line).with_context(AttestationParseError::for_url(url))?;
+        if envelope.payload_type != "application/vnd.in-toto+json" {
+            continue;
+        }
+        let payload =
+            base64::decode(&envelope.payload).with_context(AttestationParseError::for_url(url))?;
+        let statement: Statement =
+            serde_json::from_slice(&payload).with_context(AttestationParseError::for_url(url))?;
+        let digests = statement
+            .subject
+            .into_iter()
+            .filter_map(|subject| subject.digest.sha256)
+            .map(|digest| digest.to_lowercase())
+            .collect();
+        statements.push((digests, envelope.signatures.len()));
+    }
+    Ok(statements)
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_streamed_source() {
+        // the SHA-256 digest of "abc"
+        let digest = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let streamed = |checksum: Option<&Checksum>| {
+            let mut file = NamedTempFile::new().unwrap();
+            file.write_all(b"abc").unwrap();
+            Source::streamed("https://example.com/node.tar.gz", file, checksum)
+        };
+
+        let checksum = format!("sha256:{}", digest).parse::<Checksum>().unwrap();
+        let source = streamed(Some(&checksum));
+        assert!(source.verify().is_ok());
+        let provenance = source.provenance().unwrap();
+        assert_eq!(provenance.sha256, digest);
+        assert_eq!(provenance.verification, Verification::Checksum);
+
+        let other = format!("sha256:{}", "0".repeat(64)).parse::<Checksum>().unwrap();
+        assert!(streamed(Some(&other)).verify().is_err());
+
+        let source = streamed(None);
+        assert!(source.verify().is_ok());
+        assert_eq!(source.provenance().unwrap().verification, Verification::Unverified);
+    }
+
+    #[test]
+    fn test_verification_roundtrip() {
+        for verification in &[
+            Verification::Unverified,
+            Verification::Checksum,
+            Verification::Attested { signed: false },
+            Verification::Attested { signed: true },
+        ] {
+            assert_eq!(verification.to_string().parse::<Verification>().unwrap(), *verification);
+        }
+        assert!("signed".parse::<Verification>().is_err());
+    }
+
+    #[test]
+    fn test_parse_attestation() {
+        let statement = concat!(
+            r#"{"_type":"https://in-toto.io/Statement/v0.1","#,
+            r#""subject":[{"name":"node.tar.gz","digest":{"sha256":"ABC123"}}],"#,
+            r#""predicateType":"https://slsa.dev/provenance/v0.2"}"#
+        );
+        let src = format!(
+            "{{\"payloadType\":\"application/vnd.in-toto+json\",\"payload\":\"{}\",\
+             \"signatures\":[{{\"sig\":\"MEUCIQ\"}}]}}\n\n",
+            base64::encode(statement)
+        );
+        let url = "https://example.com/node.tar.gz.intoto.jsonl";
+        assert_eq!(
+            parse_attestation(url, &src).unwrap(),
+            vec![(vec!["abc123".to_string()], 1)]
+        );
+        assert!(parse_attestation(url, "{\"payloadType\":\"text/plain\"}").is_err());
+    }
+}
@@ -0,0 +1,395 @@
+//! Provides a structured account of the steps Notion takes to decide which version
+//! of a tool to run, for diagnostic commands like `notion why`.
+
+use std::ffi::OsStr;
+use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use env as notion_env;
+use fs::read_file_opt;
+use image::{Image, System};
+use lockfile::LOCK_FILE;
+use notion_fail::Fallible;
+use path;
+use session::Session;
+
+/// The place a platform image was selected from.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Source {
+    /// The `toolchain` key of the current project's `package.json`.
+    Project,
+    /// A `notion.toml` or `.notion-version` file in the directory tree.
+    Directory,
+    /// The user's default toolchain.
+    User,
+    /// The versions selected for the current shell session by `notion use --session`.
+    Shell,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let s = match self {
+            &Source::Project => "project",
+            &Source::Directory => "directory",
+            &Source::User => "user",
+            &Source::Shell => "shell",
+        };
+        f.write_str(s)
+    }
+}
+
+/// A single step in the decision chain that selects a tool version.
+#[derive(Serialize, Debug)]
+#[serde(tag = "step", rename_all = "kebab-case")]
+pub enum Step {
+    /// Toolchain resolution is bypassed by `NOTION_BYPASS`, and the next executable on
+    /// the `PATH` after the Notion shims is run, if any.
+    Bypassed { path: Option<PathBuf> },
+    /// The current directory is inside a Node package.
+    Project { manifest: PathBuf, pinned: bool },
+    /// The current project has a toolchain lockfile.
+    Lockfile { file: PathBuf },
+    /// The current directory is not inside a Node package.
+    NoProject,
+    /// A directory-level toolchain file applies to the current directory.
+    ToolchainFile { file: PathBuf, pinned: bool },
+    /// A tool version is overridden for the current shell session by an environment
+    /// variable.
+    EnvOverride { variable: String, value: String },
+    /// A platform image was selected.
+    Platform {
+        source: Source,
+        node: String,
+        yarn: Option<String>,
+    },
+    /// No platform image is available.
+    NoPlatform,
+    /// The selected platform does not include the requested tool.
+    NotInPlatform { tool: String },
+    /// The selected version is installed at the given location.
+    Installed { tool: String, version: String, path: PathBuf },
+    /// The selected version is not installed, and will be fetched on first use.
+    NotInstalled { tool: String, version: String },
+    /// The state of the cached public Node version index.
+    IndexCache { file: PathBuf, expires: Option<String> },
+    /// The tool is a binary installed directly by the current project.
+    ProjectBin { path: PathBuf },
+    /// The tool is a binary installed into the user's toolchain.
+    UserBin { path: PathBuf },
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            &Step::Bypassed { ref path } => match path {
+                &Some(ref path) => write!(f, "NOTION_BYPASS is set; using {}", path.display()),
+                &None => write!(f, "NOTION_BYPASS is set, but no executable was found on the PATH"),
+            },
+            &Step::Project {
+                ref manifest,
+                pinned,
+            } => write!(
+                f,
+                "found project at {} ({})",
+                manifest.display(),
+                if pinned { "pinned" } else { "not pinned" }
+            ),
+            &Step::Lockfile { ref file } => write!(f, "found lockfile at {}", file.display()),
+            &Step::NoProject => write!(f, "not in a node package"),
+            &Step::ToolchainFile { ref file, pinned } => write!(
+                f,
+                "found toolchain file at {} ({})",
+                file.display(),
+                if pinned { "pinned" } else { "not pinned" }
+            ),
+            &Step::EnvOverride {
+                ref variable,
+                ref value,
+            } => write!(f, "overridden for this shell session by {}={}", variable, value),
+            &Step::Platform {
+                source,
+                ref node,
+                ref yarn,
+            } => {
+                write!(f, "selected {} platform: node v{}", source, node)?;
+                if let &Some(ref yarn) = yarn {
+                    write!(f, ", yarn v{}", yarn)?;
+                }
+                Ok(())
+            }
+            &Step::NoPlatform => write!(f, "no project, directory, or user toolchain is selected"),
+            &Step::NotInPlatform { ref tool } => {
+                write!(f, "the selected platform does not include {}", tool)
+            }
+            &Step::Installed {
+                ref tool,
+                ref version,
+                ref path,
+            } => write!(f, "{} v{} is installed at {}", tool, version, path.display()),
+            &Step::NotInstalled {
+                ref tool,
+                ref version,
+            } => write!(f, "{} v{} is not installed and will be fetched on first use", tool, version),
+            &Step::IndexCache {
+                ref file,
+                ref expires,
+            } => match expires {
+                &Some(ref expires) => {
+                    write!(f, "node index cached at {} (expires {})", file.display(), expires)
+                }
+                &None => write!(f, "node index is not cached"),
+            },
+            &Step::ProjectBin { ref path } => {
+                write!(f, "using project dependency binary at {}", path.display())
+            }
+            &Step::UserBin { ref path } => {
+                write!(f, "using user toolchain binary at {}", path.display())
+            }
+        }
+    }
+}
+
+/// The full decision chain for a tool.
+#[derive(Serialize, Debug)]
+pub struct Explanation {
+    /// The name of the tool being explained.
+    pub tool: String,
+    /// The steps taken, in order.
+    pub steps: Vec<Step>,
+}
+
+/// Selects the platform image in effect, recording each step taken.
+fn select_platform(
+    session: &mut Session,
+    steps: &mut Vec<Step>,
+) -> Fallible<Option<(Source, Rc<Image>)>> {
+    match session.project() {
+        Some(project) => {
+            steps.push(Step::Project {
+                manifest: project.toolchain_source(),
+                pinned: project.is_pinned(),
+            });
+            if project.lockfile().is_some() {
+                steps.push(Step::Lockfile {
+                    file: project.root().join(LOCK_FILE),
+                });
+            }
+        }
+        None => steps.push(Step::NoProject),
+    }
+
+    if let Some(toolchain) = session.toolchain_file() {
+        steps.push(Step::ToolchainFile {
+            file: toolchain.file().to_path_buf(),
+            pinned: toolchain.toolchain().is_some(),
+        });
+    }
+
+    let selected = if let Some(image) = session.project_platform()? {
+        Some((Source::Project, image))
+    } else if let Some(image) = session.directory_platform()? {
+        Some((Source::Directory, image))
+    } else if let Some(image) = session.user_platform()? {
+        Some((Source::User, image))
+    } else {
+        None
+    };
+
+    for tool in &["node", "npm", "yarn"] {
+        if let Some(value) = notion_env::version_override(tool) {
+            steps.push(Step::EnvOverride {
+                variable: format!("NOTION_{}_VERSION", tool.to_ascii_uppercase()),
+                value,
+            });
+        }
+    }
+
+    let selected = match selected {
+        Some((source, image)) => Some((source, session.with_shell_override(image)?)),
+        None => session.current_platform()?.map(|image| (Source::Shell, image)),
+    };
+
+    match selected {
+        Some((source, ref image)) => steps.push(Step::Platform {
+            source,
+            node: image.node_str.clone(),
+            yarn: image.yarn_str.clone(),
+        }),
+        None => steps.push(Step::NoPlatform),
+    }
+
+    Ok(selected)
+}
+
+fn explain_node(session: &Session, image: &Image, steps: &mut Vec<Step>) -> Fallible<()> {
+    if session.catalog()?.node.contains(&image.node) {
+        steps.push(Step::Installed {
+            tool: "node".to_string(),
+            version: image.node_str.clone(),
+            path: path::node_version_dir(&image.node_key())?,
+        });
+    } else {
+        steps.push(Step::NotInstalled {
+            tool: "node".to_string(),
+            version: image.node_str.clone(),
+        });
+        let expires = read_file_opt(&path::node_index_expiry_file()?).unknown()?;
+        steps.push(Step::IndexCache {
+            file: path::node_index_file()?,
+            expires: expires.map(|expires| expires.trim().to_string()),
+        });
+    }
+    Ok(())
+}
+
+fn explain_yarn(session: &Session, image: &Image, steps: &mut Vec<Step>) -> Fallible<()> {
+    match (&image.yarn, &image.yarn_str) {
+        (&Some(ref yarn), &Some(ref yarn_str)) => {
+            if session.catalog()?.yarn.contains(yarn) {
+                steps.push(Step::Installed {
+                    tool: "yarn".to_string(),
+                    version: yarn_str.clone(),
+                    path: path::yarn_version_dir(yarn_str)?,
+                });
+            } else {
+                steps.push(Step::NotInstalled {
+                    tool: "yarn".to_string(),
+                    version: yarn_str.clone(),
+                });
+            }
+        }
+        _ => steps.push(Step::NotInPlatform {
+            tool: "yarn".to_string(),
+        }),
+    }
+    Ok(())
+}
+
+/// Explains how Notion would select the version of the named tool (`node`, `yarn`,
+/// or any other shimmed executable) when run from the current directory.
+pub fn explain(session: &mut Session, tool: &str) -> Fallible<Explanation> {
+    let mut steps = Vec::new();
+
+    if notion_env::bypass() {
+        steps.push(Step::Bypassed {
+            path: System::which(OsStr::new(tool))?,
+        });
+        return Ok(Explanation {
+            tool: tool.to_string(),
+            steps,
+        });
+    }
+
+    // Project dependency binaries take precedence over any toolchain lookup,
+    // mirroring the resolution in `tool::Binary`.
+    if tool != "node" && tool != "yarn" {
+        if let Some(project) = session.project() {
+            if project.has_direct_bin(OsStr::new(tool))? {
+                let mut bin_path = project.local_bin_dir();
+                bin_path.push(tool);
+                steps.push(Step::ProjectBin { path: bin_path });
+            }
+        }
+    }
+
+    if let Some((source, image)) = select_platform(session, &mut steps)? {
+        match tool {
+            "node" => explain_node(session, &image, &mut steps)?,
+            "yarn" => explain_yarn(session, &image, &mut steps)?,
+            _ => {
+                let is_project_bin = steps.iter().any(|step| match step {
+                    &Step::ProjectBin { .. } => true,
+                    _ => false,
+                });
+                if !is_project_bin && source == Source::User {
+                    if let Some(user) = session.user_platform()? {
+                        let mut bin_path = path::node_version_3p_bin_dir(&user.node_key())?;
+                        bin_path.push(tool);
+                        steps.push(Step::UserBin { path: bin_path });
+                    }
+                }
+                explain_node(session, &image, &mut steps)?;
+            }
+        }
+    }
+
+    Ok(Explanation {
+        tool: tool.to_string(),
+        steps,
+    })
+}
+
+/// The executable a shim would run for a tool.
+pub struct Target {
+    /// The path of the executable, if one would be found.
+    pub path: Option<PathBuf>,
+    /// Whether toolchain resolution is bypassed by `NOTION_BYPASS`.
+    pub bypassed: bool,
+}
+
+/// Finds the executable that the shim for the named tool would run from the current
+/// directory, mirroring the resolution in `tool`.
+pub fn which(session: &mut Session, tool: &str) -> Fallible<Target> {
+    let exe = OsStr::new(tool);
+
+    if notion_env::bypass() {
+        return Ok(Target {
+            path: System::which(exe)?,
+            bypassed: true,
+        });
+    }
+
+    let path = match tool {
+        "node" | "yarn" => match session.current_platform()? {
+            Some(image) => path::find_executable(exe, &image.path()?),
+            None => None,
+        },
+        _ => {
+            let project_bin = match session.project() {
+                Some(project) if project.has_direct_bin(exe)? => {
+                    Some(project.local_bin_dir().join(tool))
+                }
+                _ => None,
+            };
+
+            match project_bin {
+                Some(bin) => Some(bin),
+                None => match session.user_platform()? {
+                    Some(image) => Some(path::node_version_3p_bin_dir(&image.node_key())?.join(tool)),
+                    None => None,
+                },
+            }
+        }
+    };
+
+    Ok(Target {
+        path,
+        bypassed: false,
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{Source, Step};
+    use serde_json;
+
+    #[test]
+    fn test_step_serialization() {
+        let step = Step::Platform {
+            source: Source::Directory,
+            node: "10.2.1".to_string(),
+            yarn: None,
+        };
+        assert_eq!(
+            serde_json::to_string(&step).unwrap(),
+            r#"{"step":"platform","source":"directory","node":"10.2.1","yarn":null}"#
+        );
+        assert_eq!(
+            step.to_string(),
+            "selected directory platform: node v10.2.1"
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! Provides retries of idempotent network operations, like fetching an index or an
+//! attestation or starting the download of an archive, so that a transient failure (a
+//! server error, a timeout, or a dropped connection) doesn't fail an install outright.
+//!
+//! A failed attempt is retried after a delay that doubles with each attempt up to a
+//! ceiling, and is jittered so that clients that failed together don't retry together.
+//! The number of attempts and the ceiling are set in the `[retry]` section:
+//!
+//! ```toml
+//! [retry]
+//! attempts = 4     # including the first; 1 turns retries off
+//! max-delay = 10   # seconds
+//! ```
+//!
+//! An archive that fails partway through unpacking is not retried, since the failure is
+//! only seen once some of it has been unpacked.
+
+use std::fmt::Display;
+use std::io;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::{self, Response, StatusCode};
+
+use config::LazyConfig;
+use notion_fail::Fallible;
+use style::progress_spinner;
+
+/// The number of milliseconds to wait before the first retry.
+const INITIAL_DELAY_MILLIS: u64 = 500;
+
+/// How failed network operations are retried.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Policy {
+    /// The number of attempts made in all, including the first.
+    pub attempts: u32,
+    /// The longest delay between attempts.
+    pub max_delay: Duration,
+}
+
+impl Policy {
+    /// Reads the policy from the configuration.
+    pub fn current() -> Fallible<Self> {
+        Ok(LazyConfig::new().get()?.retry_policy())
+    }
+
+    /// Runs `operation` until it succeeds, it fails in a way `transient` says is not
+    /// worth retrying, or the attempts run out, displaying each retry. `what` describes
+    /// the operation in those messages, like a URL.
+    pub fn run<T, E, F, P>(&self, what: &str, mut operation: F, transient: P) -> Result<T, E>
+    where
+        F: FnMut() -> Result<T, E>,
+        P: Fn(&Result<T, E>) -> Option<String>,
+    {
+        let mut attempt = 1;
+        loop {
+            let result = operation();
+            if attempt >= self.attempts {
+                return result;
+            }
+            let reason = match transient(&result) {
+                Some(reason) => reason,
+                None => return result,
+            };
+
+            attempt += 1;
+            let spinner = progress_spinner(&format!(
+                "{} failed ({}), retrying ({}/{})…",
+                what, reason, attempt, self.attempts
+            ));
+            thread::sleep(self.delay(attempt - 1, jitter()));
+            spinner.finish_and_clear();
+        }
+    }
+
+    /// Produces how long to wait after `failures` failed attempts, given a `jitter`
+    /// between 0 and 1: a random time between half the backoff and all of it.
+    fn delay(&self, failures: u32, jitter: f64) -> Duration {
+        let backoff = Duration::from_millis(INITIAL_DELAY_MILLIS << (failures - 1).min(16));
+        let backoff = if backoff < self.max_delay { backoff } else { self.max_delay };
+        let millis = backoff.as_secs() * 1000 + (backoff.subsec_nanos() / 1_000_000) as u64;
+        Duration::from_millis(millis / 2 + (millis as f64 / 2.0 * jitter) as u64)
+    }
+}
+
+/// Produces a number between 0 and 1 that is random enough to spread out retries.
+fn jitter() -> f64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or(0);
+    (nanos % 1000) as f64 / 1000.0
+}
+
+/// Describes a failed HTTP request if it is worth retrying: a server error, a request
+/// timeout, too many requests, or a failure to connect or to read the response.
+pub(crate) fn transient_response(result: &reqwest::Result<Response>) -> Option<String> {
+    match result {
+        &Ok(ref response) => transient_status(response.status()),
+        &Err(ref error) => transient_error(error),
+    }
+}
+
+fn transient_status(status: StatusCode) -> Option<String> {
+    if status.is_server_error()
+        || status == StatusCode::RequestTimeout
+        || status == StatusCode::TooManyRequests
+    {
+        Some(format!("server responded with {}", status))
+    } else {
+        None
+    }
+}
+
+fn transient_error(error: &reqwest::Error) -> Option<String> {
+    let io = error
+        .get_ref()
+        .map_or(false, |cause| cause.downcast_ref::<io::Error>().is_some());
+    if error.is_http() || io {
+        Some(error.to_string())
+    } else {
+        None
+    }
+}
+
+/// Describes a failed archive fetch if it is worth retrying (see
+/// `node_archive::is_transient`).
+pub(crate) fn transient_fetch<T, E: Display>(
+    result: &Result<T, E>,
+    is_transient: fn(&E) -> bool,
+) -> Option<String> {
+    match result {
+        &Err(ref error) if is_transient(error) => Some(error.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use std::cell::Cell;
+
+    fn policy(attempts: u32) -> Policy {
+        Policy {
+            attempts,
+            max_delay: Duration::from_millis(2),
+        }
+    }
+
+    #[test]
+    fn test_delay() {
+        let policy = Policy {
+            attempts: 4,
+            max_delay: Duration::from_secs(10),
+        };
+        assert_eq!(policy.delay(1, 1.0), Duration::from_millis(500));
+        assert_eq!(policy.delay(2, 0.0), Duration::from_millis(500));
+        assert_eq!(policy.delay(3, 1.0), Duration::from_millis(2000));
+        assert_eq!(policy.delay(10, 1.0), Duration::from_secs(10));
+        assert_eq!(policy.delay(10, 0.0), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_run_retries_transient_failures() {
+        let calls = Cell::new(0);
+        let result: Result<u32, &str> = policy(4).run(
+            "test",
+            || {
+                calls.set(calls.get() + 1);
+                if calls.get() < 3 {
+                    Err("503")
+                } else {
+                    Ok(calls.get())
+                }
+            },
+            |result| result.as_ref().err().map(|error| error.to_string()),
+        );
+        assert_eq!(result, Ok(3));
+    }
+
+    #[test]
+    fn test_run_gives_up() {
+        let calls = Cell::new(0);
+        let result: Result<(), &str> = policy(3).run(
+            "test",
+            || {
+                calls.set(calls.get() + 1);
+                Err("503")
+            },
+            |result| result.as_ref().err().map(|error| error.to_string()),
+        );
+        assert_eq!(result, Err("503"));
+        assert_eq!(calls.get(), 3);
+
+        calls.set(0);
+        let result: Result<(), &str> = policy(3).run(
+            "test",
+            || {
+                calls.set(calls.get() + 1);
+                Err("404")
+            },
+            |_| None,
+        );
+        assert_eq!(result, Err("404"));
+        assert_eq!(calls.get(), 1);
+    }
+}
@@ -0,0 +1,261 @@
+//! Provides the sandbox that the executables a project provides in
+//! `node_modules/.bin` can be run in, to limit what a project's dependencies can get
+//! at when the project comes from a repository that isn't trusted.
+//!
+//! How executables are restricted is configured for each trust level (see
+//! `trust::Level`) in the `[sandbox]` section of `config.toml`. By default they run
+//! unrestricted. A scrubbed environment keeps only the variables programs need to run
+//! (and those matching the `sandbox.keep-env` patterns), so that tokens and other
+//! secrets in the environment are not passed on. A strict sandbox also makes the home
+//! directory read-only, apart from the project itself, with `bwrap` (Bubblewrap) on
+//! Linux and `sandbox-exec` on macOS.
+
+use std::env::{vars_os, ArgsOs};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+use config::Config;
+use notion_fail::{ExitCode, Fallible, NotionError, NotionFail};
+use toolchain::glob_matches;
+use trust;
+
+/// Thrown when a sandbox restriction setting is not recognized.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Invalid sandbox restriction '{}' (expected none, env, or strict)", value)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct RestrictionParseError {
+    value: String,
+}
+
+/// Thrown when the home directory can't be made read-only for an executable.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not run {} with a read-only home directory: {}
+
+Set the `[sandbox]` restriction for this project to `env` to run it with a
+scrubbed environment only.", exe, error)]
+#[notion_fail(code = "ExecutionFailure")]
+pub(crate) struct SandboxUnavailableError {
+    exe: String,
+    error: String,
+}
+
+/// How a project's executables are restricted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Restriction {
+    /// Executables run as they would without Notion.
+    None,
+    /// Executables run with a scrubbed environment.
+    Env,
+    /// Executables run with a scrubbed environment and a read-only home directory.
+    Strict,
+}
+
+impl FromStr for Restriction {
+    type Err = NotionError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        match src {
+            "none" => Ok(Restriction::None),
+            "env" => Ok(Restriction::Env),
+            "strict" => Ok(Restriction::Strict),
+            _ => throw!(RestrictionParseError {
+                value: src.to_string(),
+            }),
+        }
+    }
+}
+
+/// The environment variables kept in a scrubbed environment, as patterns matched
+/// against upper-cased names, since programs can't run properly without them.
+const KEPT_VARS: &'static [&'static str] = &[
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "SHELL",
+    "TERM",
+    "COLORTERM",
+    "LANG",
+    "LANGUAGE",
+    "LC_*",
+    "TZ",
+    "TMPDIR",
+    "TEMP",
+    "TMP",
+    "NOTION_*",
+    "SYSTEMROOT",
+    "SYSTEMDRIVE",
+    "WINDIR",
+    "COMSPEC",
+    "PATHEXT",
+    "USERPROFILE",
+    "APPDATA",
+    "LOCALAPPDATA",
+    "PROGRAMDATA",
+];
+
+/// How the executables of a project are run.
+pub struct Policy {
+    root: PathBuf,
+    restriction: Restriction,
+    keep: Vec<String>,
+}
+
+impl Policy {
+    /// Finds how the executables of the project rooted at `root` are run, from how far
+    /// the project is trusted.
+    pub fn for_project(root: &Path, config: &Config) -> Fallible<Self> {
+        let level = trust::level(root, config)?;
+        Ok(Policy {
+            root: root.to_path_buf(),
+            restriction: config.sandbox_restriction(level),
+            keep: config.sandbox_kept_env().to_vec(),
+        })
+    }
+
+    /// Returns how the project's executables are restricted.
+    pub fn restriction(&self) -> Restriction {
+        self.restriction
+    }
+
+    /// Produces the command that runs one of the project's executables.
+    pub fn command(&self, exe: &OsStr, args: ArgsOs, path_var: &OsStr) -> Fallible<Command> {
+        let mut command = match self.restriction {
+            Restriction::Strict => read_only_home(exe, &self.root)?,
+            _ => Command::new(exe),
+        };
+        command.args(args);
+
+        if self.restriction != Restriction::None {
+            command.env_clear();
+            for (name, value) in vars_os() {
+                if is_kept(&name.to_string_lossy(), &self.keep) {
+                    command.env(name, value);
+                }
+            }
+        }
+        command.env("PATH", path_var);
+        Ok(command)
+    }
+}
+
+/// Tests whether an environment variable is kept in a scrubbed environment.
+fn is_kept(name: &str, keep: &[String]) -> bool {
+    let name = name.to_uppercase();
+    KEPT_VARS
+        .iter()
+        .map(|pattern| pattern.to_string())
+        .chain(keep.iter().map(|pattern| pattern.to_uppercase()))
+        .any(|pattern| glob_matches(&pattern, &name))
+}
+
+fn sandbox_error(exe: &OsStr, error: &str) -> SandboxUnavailableError {
+    SandboxUnavailableError {
+        exe: exe.to_string_lossy().to_string(),
+        error: error.to_string(),
+    }
+}
+
+cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        use std::env;
+        use std::fs;
+
+        use path;
+
+        /// Produces a command that runs an executable under Bubblewrap, with the whole
+        /// file system as it is except for the home directory, which is mounted
+        /// read-only, and the project within it, which stays writable.
+        fn read_only_home(exe: &OsStr, root: &Path) -> Fallible<Command> {
+            let path_var = env::var_os("PATH").unwrap_or_default();
+            let bwrap = match path::find_executable(OsStr::new("bwrap"), &path_var) {
+                Some(bwrap) => bwrap,
+                None => throw!(sandbox_error(exe, "bwrap (Bubblewrap) is not installed")),
+            };
+            let home = match env::home_dir() {
+                Some(home) => home,
+                None => throw!(sandbox_error(exe, "the home directory is unknown")),
+            };
+            let home = fs::canonicalize(&home).unwrap_or(home);
+            let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+
+            let mut command = Command::new(bwrap);
+            command
+                .args(&["--dev-bind", "/", "/"])
+                .arg("--ro-bind")
+                .args(&[&home, &home])
+                .arg("--bind")
+                .args(&[&root, &root])
+                .args(&["--die-with-parent", "--"])
+                .arg(exe);
+            Ok(command)
+        }
+    } else if #[cfg(target_os = "macos")] {
+        use std::env;
+        use std::fs;
+
+        /// The profile `sandbox-exec` runs executables with: anything is allowed but
+        /// writing to the home directory, outside the project.
+        const PROFILE: &'static str = "(version 1)
+(allow default)
+(deny file-write* (subpath (param \"HOME_DIR\")))
+(allow file-write* (subpath (param \"PROJECT_DIR\")))";
+
+        /// Produces a command that runs an executable under `sandbox-exec`, with the
+        /// home directory read-only apart from the project within it.
+        fn read_only_home(exe: &OsStr, root: &Path) -> Fallible<Command> {
+            let home = match env::home_dir() {
+                Some(home) => home,
+                None => throw!(sandbox_error(exe, "the home directory is unknown")),
+            };
+            let home = fs::canonicalize(&home).unwrap_or(home);
+            let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+
+            let mut command = Command::new("/usr/bin/sandbox-exec");
+            command
+                .arg("-D")
+                .arg(format!("HOME_DIR={}", home.display()))
+                .arg("-D")
+                .arg(format!("PROJECT_DIR={}", root.display()))
+                .arg("-p")
+                .arg(PROFILE)
+                .arg(exe);
+            Ok(command)
+        }
+    } else {
+        /// Fails, since there's no sandbox to make the home directory read-only with.
+        fn read_only_home(exe: &OsStr, _root: &Path) -> Fallible<Command> {
+            throw!(sandbox_error(exe, "this platform has no supported sandbox"))
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_restriction_from_str() {
+        assert_eq!("none".parse::<Restriction>().unwrap(), Restriction::None);
+        assert_eq!("env".parse::<Restriction>().unwrap(), Restriction::Env);
+        assert_eq!("strict".parse::<Restriction>().unwrap(), Restriction::Strict);
+        assert!("readonly".parse::<Restriction>().is_err());
+    }
+
+    #[test]
+    fn test_is_kept() {
+        let keep = vec!["npm_config_*".to_string(), "CI".to_string()];
+
+        assert!(is_kept("HOME", &keep));
+        assert!(is_kept("LC_ALL", &keep));
+        assert!(is_kept("NOTION_HOME", &keep));
+        assert!(!is_kept("Path", &[]));
+        assert!(is_kept("npm_config_registry", &keep));
+        assert!(is_kept("CI", &keep));
+        assert!(!is_kept("CI", &[]));
+        assert!(!is_kept("NPM_TOKEN", &keep));
+        assert!(!is_kept("AWS_SECRET_ACCESS_KEY", &keep));
+    }
+}
@@ -0,0 +1,477 @@
+//! Provides the software bill of materials printed by `notion sbom`, which lists the
+//! tools Notion manages, with their versions, the digests of their installs, and the
+//! URLs they were downloaded from, in the CycloneDX or SPDX JSON format.
+
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use semver::Version;
+use serde_json;
+
+use arch::Arch;
+use catalog::Catalog;
+use checksum::sha256_hex;
+use distro::node::NodeDistro;
+use distro::yarn::YarnDistro;
+use distro::Distro;
+use image::Image;
+use notion_fail::{ExitCode, Fallible, NotionError, NotionFail, ResultExt};
+use package::public_tarball_url;
+use path;
+use style::format_timestamp;
+
+/// Thrown when an SBOM format is not one Notion can write.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Invalid SBOM format '{}' (expected cyclonedx or spdx)", value)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct SbomFormatError {
+    value: String,
+}
+
+/// The formats a bill of materials can be written in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// CycloneDX 1.5, as JSON.
+    CycloneDx,
+    /// SPDX 2.3, as JSON.
+    Spdx,
+}
+
+impl FromStr for Format {
+    type Err = NotionError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        match src {
+            "cyclonedx" => Ok(Format::CycloneDx),
+            "spdx" => Ok(Format::Spdx),
+            _ => throw!(SbomFormatError {
+                value: src.to_string(),
+            }),
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(match self {
+            &Format::CycloneDx => "cyclonedx",
+            &Format::Spdx => "spdx",
+        })
+    }
+}
+
+/// A tool listed in a bill of materials.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component {
+    pub name: String,
+    pub version: String,
+    /// The SHA-256 digest of the archive the version was installed from, or for
+    /// versions installed before provenance was recorded, of the manifest of file
+    /// digests recorded when it was installed.
+    pub hash: Option<String>,
+    /// The URL the version was downloaded from.
+    pub origin: Option<String>,
+    /// The package URL (https://github.com/package-url/purl-spec) of the version.
+    pub purl: String,
+}
+
+/// Lists every version installed in the catalog: Node versions and the npm each
+/// one bundles, Yarn versions, and package tools.
+pub fn components(catalog: &Catalog) -> Fallible<Vec<Component>> {
+    let mut components = Vec::new();
+    for version in catalog.node.versions.iter() {
+        let origin = match catalog.node.origins.get(version) {
+            Some(url) => url.clone(),
+            None => NodeDistro::public_url(version),
+        };
+        node_components(catalog, version, Arch::native(), origin, &mut components)?;
+    }
+    for (version, arches) in catalog.node.arches.iter() {
+        for arch in arches {
+            let origin = NodeDistro::public_url_for(version, *arch);
+            node_components(catalog, version, *arch, origin, &mut components)?;
+        }
+    }
+    for version in catalog.yarn.versions.iter() {
+        components.push(yarn_component(catalog, version)?);
+    }
+    for (name, tool) in catalog.packages.iter() {
+        components.push(Component {
+            name: name.clone(),
+            version: tool.version.to_string(),
+            hash: None,
+            origin: Some(public_tarball_url(name, &tool.version)),
+            purl: npm_purl(name, &tool.version),
+        });
+    }
+    Ok(components)
+}
+
+/// Lists the versions of a project's platform.
+pub fn image_components(catalog: &Catalog, image: &Image) -> Fallible<Vec<Component>> {
+    let mut components = Vec::new();
+    let origin = match catalog.node.origins.get(&image.node) {
+        Some(url) if image.node_arch == Arch::native() => url.clone(),
+        _ => NodeDistro::public_url_for(&image.node, image.node_arch),
+    };
+    node_components(catalog, &image.node, image.node_arch, origin, &mut components)?;
+    if let Some(ref yarn) = image.yarn {
+        components.push(yarn_component(catalog, yarn)?);
+    }
+    Ok(components)
+}
+
+fn node_components(
+    catalog: &Catalog,
+    version: &Version,
+    arch: Arch,
+    origin: String,
+    components: &mut Vec<Component>,
+) -> Fallible<()> {
+    let key = path::node_version_key(&version.to_string(), arch);
+    let (hash, origin) = match catalog.node.provenance.get(&key) {
+        Some(provenance) => (Some(provenance.sha256.clone()), provenance.url.clone()),
+        None => (manifest_digest(&path::node_manifest_file(&key)?)?, origin),
+    };
+    components.push(Component {
+        name: "node".to_string(),
+        version: version.to_string(),
+        hash,
+        origin: Some(origin),
+        purl: format!("pkg:generic/node@{}?arch={}", version, arch),
+    });
+    if let Some(npm) = bundled_npm(&key)? {
+        components.push(Component {
+            name: "npm".to_string(),
+            version: npm.to_string(),
+            hash: None,
+            origin: Some(public_tarball_url("npm", &npm)),
+            purl: npm_purl("npm", &npm),
+        });
+    }
+    Ok(())
+}
+
+fn yarn_component(catalog: &Catalog, version: &Version) -> Fallible<Component> {
+    let key = version.to_string();
+    let (hash, origin) = match catalog.yarn.provenance.get(&key) {
+        Some(provenance) => (Some(provenance.sha256.clone()), provenance.url.clone()),
+        None => {
+            let origin = match catalog.yarn.origins.get(version) {
+                Some(url) => url.clone(),
+                None => YarnDistro::public_url(version),
+            };
+            (manifest_digest(&path::yarn_manifest_file(&key)?)?, origin)
+        }
+    };
+    Ok(Component {
+        name: "yarn".to_string(),
+        version: key,
+        hash,
+        origin: Some(origin),
+        purl: npm_purl("yarn", version),
+    })
+}
+
+/// Reads the version of the npm bundled with an installed Node version, which is
+/// unknown if the version is compressed.
+fn bundled_npm(key: &str) -> Fallible<Option<Version>> {
+    #[derive(Deserialize)]
+    struct Package {
+        version: String,
+    }
+
+    let file = path::node_npm_package_file(key)?;
+    if !file.is_file() {
+        return Ok(None);
+    }
+    let package: Package = serde_json::from_reader(File::open(&file).unknown()?).unknown()?;
+    Ok(Version::parse(&package.version).ok())
+}
+
+fn manifest_digest(manifest: &Path) -> Fallible<Option<String>> {
+    if !manifest.is_file() {
+        return Ok(None);
+    }
+    Ok(Some(sha256_hex(&mut File::open(manifest).unknown()?)?))
+}
+
+/// Produces the package URL of an npm package, whose scope is percent-encoded.
+fn npm_purl(name: &str, version: &Version) -> String {
+    format!("pkg:npm/{}@{}", name.replace('@', "%40"), version)
+}
+
+/// Writes a bill of materials listing the components in the given format.
+pub fn render(format: Format, components: &[Component]) -> Fallible<String> {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    render_at(format, components, &format_timestamp(secs))
+}
+
+fn render_at(format: Format, components: &[Component], timestamp: &str) -> Fallible<String> {
+    match format {
+        Format::CycloneDx => {
+            serde_json::to_string_pretty(&cyclonedx::Bom::new(components, timestamp)).unknown()
+        }
+        Format::Spdx => {
+            serde_json::to_string_pretty(&spdx::Document::new(components, timestamp)).unknown()
+        }
+    }
+}
+
+const TOOL_NAME: &'static str = "notion";
+const TOOL_VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+mod cyclonedx {
+    use super::{Component, TOOL_NAME, TOOL_VERSION};
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Bom {
+        bom_format: &'static str,
+        spec_version: &'static str,
+        version: u32,
+        metadata: Metadata,
+        components: Vec<BomComponent>,
+    }
+
+    #[derive(Serialize)]
+    struct Metadata {
+        timestamp: String,
+        tools: Vec<Tool>,
+    }
+
+    #[derive(Serialize)]
+    struct Tool {
+        name: &'static str,
+        version: &'static str,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct BomComponent {
+        #[serde(rename = "type")]
+        kind: &'static str,
+        #[serde(rename = "bom-ref")]
+        bom_ref: String,
+        name: String,
+        version: String,
+        purl: String,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        hashes: Vec<Hash>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        external_references: Vec<Reference>,
+    }
+
+    #[derive(Serialize)]
+    struct Hash {
+        alg: &'static str,
+        content: String,
+    }
+
+    #[derive(Serialize)]
+    struct Reference {
+        #[serde(rename = "type")]
+        kind: &'static str,
+        url: String,
+    }
+
+    impl Bom {
+        pub fn new(components: &[Component], timestamp: &str) -> Self {
+            Bom {
+                bom_format: "CycloneDX",
+                spec_version: "1.5",
+                version: 1,
+                metadata: Metadata {
+                    timestamp: timestamp.to_string(),
+                    tools: vec![Tool {
+                        name: TOOL_NAME,
+                        version: TOOL_VERSION,
+                    }],
+                },
+                components: components
+                    .iter()
+                    .map(|component| BomComponent {
+                        kind: "application",
+                        bom_ref: component.purl.clone(),
+                        name: component.name.clone(),
+                        version: component.version.clone(),
+                        purl: component.purl.clone(),
+                        hashes: component
+                            .hash
+                            .iter()
+                            .map(|hash| Hash {
+                                alg: "SHA-256",
+                                content: hash.clone(),
+                            })
+                            .collect(),
+                        external_references: component
+                            .origin
+                            .iter()
+                            .map(|origin| Reference {
+                                kind: "distribution",
+                                url: origin.clone(),
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+            }
+        }
+    }
+}
+
+mod spdx {
+    use super::{Component, TOOL_NAME, TOOL_VERSION};
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Document {
+        spdx_version: &'static str,
+        data_license: &'static str,
+        #[serde(rename = "SPDXID")]
+        spdx_id: &'static str,
+        name: &'static str,
+        document_namespace: String,
+        creation_info: CreationInfo,
+        packages: Vec<Package>,
+    }
+
+    #[derive(Serialize)]
+    struct CreationInfo {
+        created: String,
+        creators: Vec<String>,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Package {
+        #[serde(rename = "SPDXID")]
+        spdx_id: String,
+        name: String,
+        version_info: String,
+        download_location: String,
+        files_analyzed: bool,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        checksums: Vec<Checksum>,
+        external_refs: Vec<ExternalRef>,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Checksum {
+        algorithm: &'static str,
+        checksum_value: String,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ExternalRef {
+        reference_category: &'static str,
+        reference_type: &'static str,
+        reference_locator: String,
+    }
+
+    impl Document {
+        pub fn new(components: &[Component], timestamp: &str) -> Self {
+            Document {
+                spdx_version: "SPDX-2.3",
+                data_license: "CC0-1.0",
+                spdx_id: "SPDXRef-DOCUMENT",
+                name: "notion-toolchain",
+                document_namespace: format!("urn:notion:sbom:{}", timestamp),
+                creation_info: CreationInfo {
+                    created: timestamp.to_string(),
+                    creators: vec![format!("Tool: {}-{}", TOOL_NAME, TOOL_VERSION)],
+                },
+                packages: components
+                    .iter()
+                    .enumerate()
+                    .map(|(i, component)| Package {
+                        spdx_id: format!("SPDXRef-Package-{}", i + 1),
+                        name: component.name.clone(),
+                        version_info: component.version.clone(),
+                        download_location: component
+                            .origin
+                            .clone()
+                            .unwrap_or_else(|| "NOASSERTION".to_string()),
+                        files_analyzed: false,
+                        checksums: component
+                            .hash
+                            .iter()
+                            .map(|hash| Checksum {
+                                algorithm: "SHA256",
+                                checksum_value: hash.clone(),
+                            })
+                            .collect(),
+                        external_refs: vec![ExternalRef {
+                            reference_category: "PACKAGE-MANAGER",
+                            reference_type: "purl",
+                            reference_locator: component.purl.clone(),
+                        }],
+                    })
+                    .collect(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    fn node() -> Component {
+        Component {
+            name: "node".to_string(),
+            version: "18.17.1".to_string(),
+            hash: Some("9f86d081884c7d65".to_string()),
+            origin: Some("https://nodejs.org/dist/v18.17.1/node.tar.gz".to_string()),
+            purl: "pkg:generic/node@18.17.1?arch=x64".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_format_from_str() {
+        assert_eq!("cyclonedx".parse::<Format>().unwrap(), Format::CycloneDx);
+        assert_eq!("spdx".parse::<Format>().unwrap(), Format::Spdx);
+        assert!("swid".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn test_npm_purl() {
+        let version = Version::parse("5.2.2").unwrap();
+        assert_eq!(npm_purl("typescript", &version), "pkg:npm/typescript@5.2.2");
+        assert_eq!(npm_purl("@vue/cli", &version), "pkg:npm/%40vue/cli@5.2.2");
+    }
+
+    #[test]
+    fn test_render_cyclonedx() {
+        let src = render_at(Format::CycloneDx, &[node()], "2023-08-09T10:00:00Z").unwrap();
+        let bom: serde_json::Value = serde_json::from_str(&src).unwrap();
+        assert_eq!(bom["bomFormat"], "CycloneDX");
+        assert_eq!(bom["metadata"]["timestamp"], "2023-08-09T10:00:00Z");
+        let component = &bom["components"][0];
+        assert_eq!(component["purl"], "pkg:generic/node@18.17.1?arch=x64");
+        assert_eq!(component["hashes"][0]["content"], "9f86d081884c7d65");
+        assert_eq!(component["externalReferences"][0]["type"], "distribution");
+    }
+
+    #[test]
+    fn test_render_spdx() {
+        let mut npm = node();
+        npm.hash = None;
+        npm.origin = None;
+        let src = render_at(Format::Spdx, &[node(), npm], "2023-08-09T10:00:00Z").unwrap();
+        let doc: serde_json::Value = serde_json::from_str(&src).unwrap();
+        assert_eq!(doc["spdxVersion"], "SPDX-2.3");
+        assert_eq!(doc["packages"][0]["SPDXID"], "SPDXRef-Package-1");
+        assert_eq!(doc["packages"][0]["checksums"][0]["algorithm"], "SHA256");
+        assert_eq!(doc["packages"][1]["downloadLocation"], "NOASSERTION");
+        assert!(doc["packages"][1].get("checksums").is_none());
+    }
+}
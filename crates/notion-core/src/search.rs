@@ -0,0 +1,135 @@
+//! Provides fuzzy searching of the tool versions available to install.
+
+use std::cmp::Ordering;
+
+use semver::Version;
+
+use catalog::public_node_versions;
+use notion_fail::Fallible;
+
+/// A Node version matching a search query.
+pub struct NodeMatch {
+    pub version: Version,
+    /// The codename of the LTS line the version belongs to, if any.
+    pub lts: Option<String>,
+    /// How well the version matched the query; higher is better.
+    pub score: u32,
+}
+
+/// Searches the Node versions available from the public Node server for versions
+/// and LTS codenames matching a query, best matches first. Equally good matches are
+/// listed newest first.
+pub fn search_node(query: &str) -> Fallible<Vec<NodeMatch>> {
+    let query = normalize(query);
+    let mut matches = Vec::new();
+    if query.is_empty() {
+        return Ok(matches);
+    }
+
+    for available in public_node_versions()? {
+        let version_score = score_version(&query, &available.version);
+        let lts_score = available
+            .lts
+            .as_ref()
+            .and_then(|codename| score_text(&query, &codename.to_lowercase()));
+
+        let score = match (version_score, lts_score) {
+            (None, None) => continue,
+            (a, b) => a.unwrap_or(0).max(b.unwrap_or(0)),
+        };
+        matches.push(NodeMatch {
+            version: available.version,
+            lts: available.lts,
+            score,
+        });
+    }
+
+    matches.sort_by(|a, b| match b.score.cmp(&a.score) {
+        Ordering::Equal => b.version.cmp(&a.version),
+        ordering => ordering,
+    });
+    Ok(matches)
+}
+
+/// Normalizes a query for matching: case is ignored, and a leading `v` (as in `v16`)
+/// is dropped.
+fn normalize(query: &str) -> String {
+    let query = query.trim().to_lowercase();
+    if query.len() > 1 && query.starts_with('v') && query[1..].starts_with(|c: char| c.is_digit(10)) {
+        query[1..].to_string()
+    } else {
+        query
+    }
+}
+
+/// Scores a version against a query. Whole version components count for more than
+/// partial ones, so `16.2` ranks 16.2.x above 16.20.x.
+fn score_version(query: &str, version: &Version) -> Option<u32> {
+    let version = version.to_string();
+    if version == query {
+        Some(100)
+    } else if version.starts_with(&format!("{}.", query)) {
+        Some(80)
+    } else if version.starts_with(query) {
+        Some(50)
+    } else {
+        None
+    }
+}
+
+/// Scores a piece of text against a query: exact matches rank highest, then prefixes,
+/// then substrings, then fuzzy matches where the query's characters appear in order.
+fn score_text(query: &str, text: &str) -> Option<u32> {
+    if query.is_empty() {
+        None
+    } else if text == query {
+        Some(90)
+    } else if text.starts_with(query) {
+        Some(70)
+    } else if text.contains(query) {
+        Some(40)
+    } else if is_subsequence(query, text) {
+        Some(20)
+    } else {
+        None
+    }
+}
+
+/// Returns true if the characters of `needle` appear in `haystack` in order.
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut haystack = haystack.chars();
+    needle.chars().all(|c| haystack.any(|h| h == c))
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{normalize, score_text, score_version};
+    use semver::Version;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(" v16.20 "), "16.20");
+        assert_eq!(normalize("Hydrogen"), "hydrogen");
+        assert_eq!(normalize("v"), "v");
+    }
+
+    #[test]
+    fn test_score_version() {
+        let v16_2 = Version::parse("16.2.0").unwrap();
+        let v16_20 = Version::parse("16.20.1").unwrap();
+        assert!(score_version("16.2", &v16_2) > score_version("16.2", &v16_20));
+        assert_eq!(score_version("16.20", &v16_20), Some(80));
+        assert_eq!(score_version("16.20.1", &v16_20), Some(100));
+        assert_eq!(score_version("18", &v16_20), None);
+    }
+
+    #[test]
+    fn test_score_text() {
+        assert_eq!(score_text("hydrogen", "hydrogen"), Some(90));
+        assert_eq!(score_text("hyd", "hydrogen"), Some(70));
+        assert_eq!(score_text("gen", "hydrogen"), Some(40));
+        assert_eq!(score_text("hdrg", "hydrogen"), Some(20));
+        assert_eq!(score_text("iron", "hydrogen"), None);
+    }
+}
@@ -2,23 +2,48 @@
 //! execution of a Notion tool, including their configuration, their current
 //! directory, and the state of the local tool catalog.
 
-use std::env::{self, VarError};
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
+use lazycell::LazyCell;
+
+use arch::Arch;
+use backup;
+use bundle;
 use catalog::{Catalog, LazyCatalog};
-use config::{Config, LazyConfig};
-use distro::Fetched;
-use image::Image;
-use plugin::Publish;
-use project::Project;
+use checksum::Checksum;
+use compress::Compressed;
+use config::{Config, LazyConfig, Subscriber, TelemetryConfig};
+use dedupe;
+use distro::{self, Fetched};
+use env as notion_env;
+use hook::Hooks;
+use image::{cache, Image};
+use locale::Locale;
+use lockfile::{self, Archive, LockedNode, LockedYarn, Lockfile, LOCK_FILE};
+use logging;
+use manifest::{Pin, PinStyle, Toolchain};
+use package::{self, PackageSpec, PackageTool, UnknownPackageError};
+use path;
+use plan::{self, Action, Plan};
+use project::{NoPinnedNodeVersion, Project};
+use registry::Registries;
+use shim::{self, ShimResult, Target};
+use style::{self, ColorChoice, ProgressDisplay};
+use timing;
+use toolchain::ToolchainFile;
+use trust;
 use version::VersionSpec;
+use webhook;
 
 use std::fmt::{self, Display, Formatter};
 use std::process::exit;
 
 use event::EventLog;
 use notion_fail::{ExitCode, Fallible, NotionError, NotionFail, ResultExt};
-use semver::Version;
+use semver::{Version, VersionReq};
 
 #[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Copy)]
 pub enum ActivityKind {
@@ -37,6 +62,37 @@ pub enum ActivityKind {
     Version,
     Binary,
     Shim,
+    ExportEnv,
+    Why,
+    Bundle,
+    Which,
+    Env,
+    Backup,
+    List,
+    Search,
+    Update,
+    Verify,
+    Repair,
+    Cache,
+    Dedupe,
+    Compress,
+    Setup,
+    Completions,
+    Complete,
+    Alias,
+    Upgrade,
+    Outdated,
+    Run,
+    Ci,
+    Explain,
+    Bench,
+    Generate,
+    Sbom,
+    Trust,
+    Lock,
+    Prompt,
+    Status,
+    Daemon,
 }
 
 impl Display for ActivityKind {
@@ -57,6 +113,37 @@ impl Display for ActivityKind {
             &ActivityKind::Version => "version",
             &ActivityKind::Binary => "binary",
             &ActivityKind::Shim => "shim",
+            &ActivityKind::ExportEnv => "export-env",
+            &ActivityKind::Why => "why",
+            &ActivityKind::Bundle => "bundle",
+            &ActivityKind::Which => "which",
+            &ActivityKind::Env => "env",
+            &ActivityKind::Backup => "backup",
+            &ActivityKind::List => "list",
+            &ActivityKind::Search => "search",
+            &ActivityKind::Update => "update",
+            &ActivityKind::Verify => "verify",
+            &ActivityKind::Repair => "repair",
+            &ActivityKind::Cache => "cache",
+            &ActivityKind::Dedupe => "dedupe",
+            &ActivityKind::Compress => "compress",
+            &ActivityKind::Setup => "setup",
+            &ActivityKind::Completions => "completions",
+            &ActivityKind::Complete => "complete",
+            &ActivityKind::Alias => "alias",
+            &ActivityKind::Upgrade => "upgrade",
+            &ActivityKind::Outdated => "outdated",
+            &ActivityKind::Run => "run",
+            &ActivityKind::Ci => "ci",
+            &ActivityKind::Explain => "explain",
+            &ActivityKind::Bench => "bench",
+            &ActivityKind::Generate => "generate",
+            &ActivityKind::Sbom => "sbom",
+            &ActivityKind::Trust => "trust",
+            &ActivityKind::Lock => "lock",
+            &ActivityKind::Prompt => "prompt",
+            &ActivityKind::Status => "status",
+            &ActivityKind::Daemon => "daemon",
         };
         f.write_str(s)
     }
@@ -74,51 +161,333 @@ impl NotInPackageError {
     }
 }
 
+/// Thrown when the user updates range pins in a project that has none.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "This project does not pin any versions as ranges")]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct NoRangePinsError;
+
+/// Thrown when a `NOTION_<TOOL>_VERSION` override is not a valid version or range.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} is not a valid version: {}", variable, value)]
+#[notion_fail(code = "EnvironmentError")]
+pub(crate) struct InvalidOverrideError {
+    variable: String,
+    value: String,
+}
+
+/// Thrown when a `NOTION_<TOOL>_VERSION` override selects a version that isn't
+/// installed, and overrides are configured not to install versions.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} v{} is selected by {} but is not installed
+Run `notion fetch {} {}` to install it, or set `auto-install = true` under [overrides] in config.toml",
+       tool, version, variable, tool, version)]
+#[notion_fail(code = "EnvironmentError")]
+pub(crate) struct OverrideNotInstalledError {
+    tool: String,
+    version: Version,
+    variable: String,
+}
+
+/// Thrown when a package tool is installed without a Node version in the user toolchain.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Installing a package requires a Node version in your toolchain
+Run `notion install node` to select one")]
+#[notion_fail(code = "NoVersionMatch")]
+pub(crate) struct NoNodeForPackageError;
+
+/// Finds the archives a project's lockfile records for the Node and Yarn versions of
+/// an image, if it locks those versions.
+fn locked_archives<'a>(
+    project: Option<&'a Rc<Project>>,
+    image: &Image,
+) -> (Option<&'a Archive>, Option<&'a Archive>) {
+    let lockfile = match project.and_then(|project| project.lockfile()) {
+        Some(lockfile) => lockfile,
+        None => return (None, None),
+    };
+    let node = if lockfile.node.version == image.node && image.node_arch == Arch::native() {
+        lockfile.node_archive()
+    } else {
+        None
+    };
+    let yarn = match (&lockfile.yarn, &image.yarn) {
+        (&Some(ref locked), &Some(ref yarn)) if locked.version == *yarn => Some(&locked.archive),
+        _ => None,
+    };
+    (node, yarn)
+}
+
+/// Finds the checksums pinned for the archives of the Node and Yarn versions of an
+/// image, by a project's toolchain or else by the toolchain file of the current
+/// directory, if they pin those versions.
+fn pinned_checksums<'a>(
+    project: Option<&'a Rc<Project>>,
+    file: Option<&'a Rc<ToolchainFile>>,
+    image: &Image,
+) -> (Option<&'a Checksum>, Option<&'a Checksum>) {
+    let toolchain = project
+        .and_then(|project| project.toolchain())
+        .or_else(|| file.and_then(|file| file.toolchain()));
+    match toolchain {
+        Some(toolchain) => toolchain.checksums_for(image),
+        None => (None, None),
+    }
+}
+
+/// The name of the environment variable that overrides a tool's version.
+fn override_var(tool: &str) -> String {
+    format!("NOTION_{}_VERSION", tool.to_ascii_uppercase())
+}
+
+/// Reads the version range selected by a tool's `NOTION_<TOOL>_VERSION` override, if any.
+fn override_requirements(tool: &str) -> Fallible<Option<VersionReq>> {
+    match notion_env::version_override(tool) {
+        Some(value) => match VersionSpec::parse_requirements(&value) {
+            Ok(requirements) => Ok(Some(requirements)),
+            Err(_) => throw!(InvalidOverrideError {
+                variable: override_var(tool),
+                value,
+            }),
+        },
+        None => Ok(None),
+    }
+}
+
+/// The directory a toolchain file pins the toolchain for, which is the one it is in.
+fn toolchain_dir(file: &ToolchainFile) -> &Path {
+    file.file().parent().unwrap_or_else(|| file.file())
+}
+
+/// A range pin re-resolved by `Session::update_range_pins`.
+pub struct PinUpdate {
+    /// The name of the pinned tool.
+    pub tool: &'static str,
+    /// The range as it appears in the manifest.
+    pub range: String,
+    /// The version the range resolved to before updating.
+    pub from: Version,
+    /// The newest version that satisfies the range.
+    pub to: Version,
+}
+
 /// Represents the user's state during an execution of a Notion tool. The session
 /// encapsulates a number of aspects of the environment in which the tool was
 /// invoked, including:
 ///     - the current directory
 ///     - the Node project tree that contains the current directory (if any)
+///     - the directory-level toolchain file that applies to the current directory (if any)
 ///     - the Notion configuration settings
 ///     - the catalog of locally-installed Notion tools
+///     - whether the current project and the directory of the toolchain file are
+///       trusted to pin the toolchain
 pub struct Session {
     config: LazyConfig,
     catalog: LazyCatalog,
     project: Option<Rc<Project>>,
+    project_trusted: LazyCell<bool>,
+    directory_trusted: LazyCell<bool>,
+    project_image: LazyCell<Option<Rc<Image>>>,
+    directory_image: LazyCell<Option<Rc<Image>>>,
+    shell_versions: LazyCell<(Option<Version>, Option<Version>)>,
+    toolchain: Option<Rc<ToolchainFile>>,
     event_log: EventLog,
 }
 
 impl Session {
     /// Constructs a new `Session`.
     pub fn new() -> Fallible<Session> {
+        Session::for_dir(&env::current_dir().unknown()?)
+    }
+
+    /// Constructs a `Session` for a directory other than the current one, which is
+    /// where its project and directory-level toolchain file are found.
+    pub fn for_dir(dir: &Path) -> Fallible<Session> {
+        Session::for_dir_with_catalog(dir, LazyCatalog::new())
+    }
+
+    /// Constructs a `Session` for a directory that uses a catalog that may already be
+    /// loaded, which `into_catalog` hands back, so that a long-running process doesn't
+    /// load the catalog for every session (see `daemon`).
+    pub fn for_dir_with_catalog(dir: &Path, catalog: LazyCatalog) -> Fallible<Session> {
         Ok(Session {
             config: LazyConfig::new(),
-            catalog: LazyCatalog::new(),
-            project: Project::for_current_dir()?.map(Rc::new),
+            catalog,
+            project: Project::for_dir(dir)?.map(Rc::new),
+            project_trusted: LazyCell::new(),
+            directory_trusted: LazyCell::new(),
+            project_image: LazyCell::new(),
+            directory_image: LazyCell::new(),
+            shell_versions: LazyCell::new(),
+            toolchain: ToolchainFile::for_dir(dir)?.map(Rc::new),
             event_log: EventLog::new()?,
         })
     }
 
+    /// Removes what interrupted installs left behind. Recovering from them is
+    /// best-effort, so it never prevents a command from running, and it is skipped in
+    /// dry-run mode.
+    pub fn recover_interrupted_installs(&self) {
+        if !plan::is_dry_run() {
+            let _ = distro::cleanup_staging();
+        }
+    }
+
+    /// Consumes the session, producing its catalog, which is loaded if the session
+    /// needed it.
+    pub fn into_catalog(self) -> LazyCatalog {
+        self.catalog
+    }
+
     /// Produces a reference to the current Node project, if any.
     pub fn project(&self) -> Option<Rc<Project>> {
         self.project.clone()
     }
 
+    /// Produces a reference to the current Node project if it is trusted to pin its
+    /// toolchain and have its binaries shimmed automatically (see `trust::check`).
+    pub fn trusted_project(&self) -> Fallible<Option<Rc<Project>>> {
+        let project = match self.project {
+            Some(ref project) => project,
+            None => return Ok(None),
+        };
+        let trusted = self.project_trusted
+            .try_borrow_with(|| trust::check(project.root(), self.config.get()?))?;
+        Ok(if *trusted { Some(project.clone()) } else { None })
+    }
+
+    /// Decides whether the current project and the directory of the toolchain file are
+    /// trusted without asking the user or warning, so that the pins of a project or
+    /// directory that hasn't been trusted are silently ignored. For commands that run too
+    /// often to ask, or without a user to ask.
+    pub fn trust_without_asking(&self) -> Fallible<()> {
+        let config = self.config.get()?;
+        let trusted = |dir: &Path| -> Fallible<bool> {
+            Ok(!config.trust_required() || trust::level(dir, config)? != trust::Level::Untrusted)
+        };
+        if let Some(ref project) = self.project {
+            let _ = self.project_trusted.fill(trusted(project.root())?);
+        }
+        if let Some(ref file) = self.toolchain {
+            let _ = self.directory_trusted.fill(trusted(toolchain_dir(file))?);
+        }
+        Ok(())
+    }
+
     pub fn current_platform(&mut self) -> Fallible<Option<Rc<Image>>> {
-        if let Some(image) = self.project_platform() {
-            return Ok(Some(image));
+        if let Some(image) = self.project_platform()? {
+            return Ok(Some(self.with_shell_override(image)?));
+        }
+
+        if let Some(image) = self.directory_platform()? {
+            return Ok(Some(self.with_shell_override(image)?));
         }
 
         if let Some(image) = self.user_platform()? {
-            return Ok(Some(image));
+            return Ok(Some(self.with_shell_override(image)?));
+        }
+
+        self.shell_platform()
+    }
+
+    /// Returns the Node and Yarn versions selected for the current shell session by the
+    /// `NOTION_NODE_VERSION`, `NOTION_NPM_VERSION` and `NOTION_YARN_VERSION` variables
+    /// (as set by `notion use --session`), which take precedence over every toolchain.
+    /// An npm version selects the Node version that bundles it.
+    pub fn shell_versions(&self) -> Fallible<(Option<Version>, Option<Version>)> {
+        let versions = self.shell_versions
+            .try_borrow_with(|| self.resolve_shell_versions())?;
+        Ok(versions.clone())
+    }
+
+    fn resolve_shell_versions(&self) -> Fallible<(Option<Version>, Option<Version>)> {
+        let node = override_requirements("node")?;
+        let npm = override_requirements("npm")?;
+        let yarn = override_requirements("yarn")?;
+        if node.is_none() && npm.is_none() && yarn.is_none() {
+            return Ok((None, None));
+        }
+
+        let catalog = self.catalog.get()?;
+        let config = self.config.get()?;
+
+        let node_var = override_var(if npm.is_some() { "npm" } else { "node" });
+        let node = match (npm, node) {
+            (Some(npm), node) => Some(catalog.resolve_node_for_npm(&npm, node.as_ref())?),
+            (None, Some(node)) => Some(catalog.resolve_node_pin(&node, config)?),
+            (None, None) => None,
+        };
+        let yarn = match yarn {
+            Some(yarn) => Some(catalog.resolve_yarn_pin(&yarn, config)?),
+            None => None,
+        };
+
+        if !config.auto_install_overrides() {
+            if let Some(ref node) = node {
+                if !catalog.node.contains(node) {
+                    throw!(OverrideNotInstalledError {
+                        tool: "node".to_string(),
+                        version: node.clone(),
+                        variable: node_var,
+                    });
+                }
+            }
+            if let Some(ref yarn) = yarn {
+                if !catalog.yarn.contains(yarn) {
+                    throw!(OverrideNotInstalledError {
+                        tool: "yarn".to_string(),
+                        version: yarn.clone(),
+                        variable: override_var("yarn"),
+                    });
+                }
+            }
         }
 
-        return Ok(None);
+        Ok((node, yarn))
+    }
+
+    /// Applies the versions selected for the current shell session, if any, to a
+    /// platform image.
+    pub fn with_shell_override(&self, image: Rc<Image>) -> Fallible<Rc<Image>> {
+        let (node, yarn) = match self.shell_versions()? {
+            (None, None) => return Ok(image),
+            versions => versions,
+        };
+
+        let (node, node_arch) = match node {
+            Some(node) => (node, Arch::native()),
+            None => (image.node.clone(), image.node_arch),
+        };
+        let yarn = yarn.or_else(|| image.yarn.clone());
+
+        Ok(Rc::new(Image {
+            node_str: node.to_string(),
+            node,
+            node_arch,
+            yarn_str: yarn.as_ref().map(|version| version.to_string()),
+            yarn,
+        }))
+    }
+
+    /// Returns the platform image selected for the current shell session when no
+    /// toolchain is in effect, if a Node version was selected.
+    fn shell_platform(&self) -> Fallible<Option<Rc<Image>>> {
+        Ok(match self.shell_versions()? {
+            (Some(node), yarn) => Some(Rc::new(Image {
+                node_str: node.to_string(),
+                node,
+                node_arch: Arch::native(),
+                yarn_str: yarn.as_ref().map(|version| version.to_string()),
+                yarn,
+            })),
+            (None, _) => None,
+        })
     }
 
     pub fn user_platform(&mut self) -> Fallible<Option<Rc<Image>>> {
         if let Some(node) = self.user_node()? {
             let node_str = node.to_string();
+            let node_arch = self.catalog.get()?.node.default_arch();
 
             if let Some(yarn) = self.user_yarn()? {
                 let yarn_str = yarn.to_string();
@@ -126,6 +495,7 @@ impl Session {
                 return Ok(Some(Rc::new(Image {
                     node,
                     node_str,
+                    node_arch,
                     yarn: Some(yarn),
                     yarn_str: Some(yarn_str)
                 })));
@@ -134,6 +504,7 @@ impl Session {
             return Ok(Some(Rc::new(Image {
                 node,
                 node_str,
+                node_arch,
                 yarn: None,
                 yarn_str: None
             })));
@@ -141,12 +512,124 @@ impl Session {
         Ok(None)
     }
 
-    /// Returns the current project's pinned platform image, if any.
-    pub fn project_platform(&self) -> Option<Rc<Image>> {
-        if let Some(ref project) = self.project {
-            return project.platform();
+    /// Returns the current project's pinned platform image, if any. Versions pinned
+    /// as ranges are resolved to the newest installed version that satisfies them, or
+    /// else the newest satisfying version available to install.
+    pub fn project_platform(&self) -> Fallible<Option<Rc<Image>>> {
+        let image = self.project_image
+            .try_borrow_with(|| self.resolve_project_platform())?;
+        Ok(image.clone())
+    }
+
+    fn resolve_project_platform(&self) -> Fallible<Option<Rc<Image>>> {
+        let project = match self.trusted_project()? {
+            Some(project) => project,
+            None => return Ok(None),
+        };
+
+        if let Some(lockfile) = project.lockfile() {
+            match project.toolchain() {
+                Some(toolchain) if !lockfile.satisfies(toolchain) => {
+                    logging::warning(&format!(
+                        "ignoring {}, which doesn't match the versions pinned in {} \
+                         (run `notion lock` to update it)",
+                        LOCK_FILE,
+                        project.toolchain_source().display()
+                    ));
+                }
+                _ => return Ok(Some(Rc::new(lockfile.image()))),
+            }
+        }
+
+        if let Some(image) = project.platform() {
+            return Ok(Some(image));
+        }
+
+        let toolchain = match project.toolchain() {
+            Some(toolchain) => toolchain,
+            None => return Ok(None),
+        };
+
+        // resolving requirements reads the catalog, so the result is cached until the
+        // project, the catalog, or the configuration changes
+        let fingerprint = cache::Fingerprint::of(project.root())?;
+        if let Some(image) = cache::load(project.root(), &fingerprint) {
+            return Ok(Some(Rc::new(image)));
+        }
+
+        let image = self.resolve_toolchain(toolchain)?;
+        // a version that isn't installed yet could resolve differently once the index
+        // is refreshed, so only fully installed platforms are cached
+        let catalog = self.catalog.get()?;
+        let installed = catalog.node.contains(&image.node)
+            && image.yarn.as_ref().map_or(true, |yarn| catalog.yarn.contains(yarn));
+        if installed {
+            cache::store(project.root(), &fingerprint, &image);
+        }
+        Ok(Some(image))
+    }
+
+    /// Resolves the versions pinned by a toolchain into a platform image.
+    fn resolve_toolchain(&self, toolchain: &Toolchain) -> Fallible<Rc<Image>> {
+        let catalog = self.catalog.get()?;
+        let config = self.config.get()?;
+
+        let node = match (&toolchain.npm, &toolchain.node) {
+            (&Some(ref npm), node) => {
+                catalog.resolve_node_for_npm(&npm.requirements(), Some(&node.requirements()))?
+            }
+            (&None, &Pin::Exact(ref version)) => version.clone(),
+            (&None, &Pin::Range(ref range)) => catalog.resolve_node_pin(range, config)?,
+        };
+        let yarn = match toolchain.yarn {
+            Some(Pin::Exact(ref version)) => Some(version.clone()),
+            Some(Pin::Range(ref range)) => Some(catalog.resolve_yarn_pin(range, config)?),
+            None => None,
+        };
+
+        Ok(Rc::new(Image {
+            node_str: node.to_string(),
+            node,
+            node_arch: Arch::native(),
+            yarn_str: yarn.as_ref().map(|version| version.to_string()),
+            yarn,
+        }))
+    }
+
+    /// Produces a reference to the directory-level toolchain file that applies
+    /// to the current directory, if any.
+    pub fn toolchain_file(&self) -> Option<Rc<ToolchainFile>> {
+        self.toolchain.clone()
+    }
+
+    /// Returns the platform image pinned by a `notion.toml` or `.notion-version`
+    /// file in the current directory or one of its ancestors, if any.
+    pub fn directory_platform(&self) -> Fallible<Option<Rc<Image>>> {
+        let image = self.directory_image
+            .try_borrow_with(|| self.resolve_directory_platform())?;
+        Ok(image.clone())
+    }
+
+    fn resolve_directory_platform(&self) -> Fallible<Option<Rc<Image>>> {
+        let file = match self.toolchain {
+            Some(ref file) => file,
+            None => return Ok(None),
+        };
+
+        let trusted = self.directory_trusted
+            .try_borrow_with(|| trust::check(toolchain_dir(file), self.config.get()?))?;
+        if !*trusted {
+            return Ok(None);
+        }
+
+        if let Some(image) = file.platform() {
+            return Ok(Some(image));
+        }
+
+        match file.toolchain() {
+            Some(toolchain) => Ok(Some(self.resolve_toolchain(toolchain)?)),
+            None => Ok(None),
         }
-        None
     }
 
     /// Produces a reference to the current tool catalog.
@@ -159,52 +642,463 @@ impl Session {
         self.catalog.get_mut()
     }
 
+    /// Exports installed tools into a bundle file for use on another machine.
+    pub fn export_bundle(
+        &self,
+        node: Option<Vec<Version>>,
+        yarn: Option<Vec<Version>>,
+        dest: &Path,
+    ) -> Fallible<bundle::Contents> {
+        bundle::export(self.catalog()?, node, yarn, dest)
+    }
+
+    /// Imports the tools in a bundle file into the catalog.
+    pub fn import_bundle(&mut self, src: &Path) -> Fallible<bundle::Contents> {
+        let contents = bundle::import(self.catalog_mut()?, src)?;
+        self.run_install_hooks()?;
+        Ok(contents)
+    }
+
+    /// Backs up the user's catalog, configuration, hooks, and shims into a backup file.
+    pub fn create_backup(&self, dest: &Path) -> Fallible<backup::Contents> {
+        backup::create(self.catalog()?, dest)
+    }
+
+    /// Restores the settings in a backup file, replacing the user's current settings.
+    pub fn restore_backup(&mut self, src: &Path) -> Fallible<backup::Contents> {
+        let contents = backup::restore(src)?;
+        // The restored settings replace anything already read from disk.
+        self.catalog = LazyCatalog::new();
+        self.config = LazyConfig::new();
+        self.project_trusted = LazyCell::new();
+        self.directory_trusted = LazyCell::new();
+        self.project_image = LazyCell::new();
+        self.directory_image = LazyCell::new();
+        self.shell_versions = LazyCell::new();
+        Ok(contents)
+    }
+
     /// Produces a reference to the configuration.
     pub fn config(&self) -> Fallible<&Config> {
         self.config.get()
     }
 
-    /// Ensures that a platform image has been fully fetched and set up.
-    pub(crate) fn prepare_image(&mut self, image: &Image) -> Fallible<()> {
-        let catalog = self.catalog.get_mut()?;
+    /// Selects the locale set by the `locale` setting, if any, for displaying messages.
+    /// A configuration that can't be loaded is ignored here, since it is reported
+    /// wherever it is used.
+    pub fn select_configured_locale(&self) {
+        if let Ok(&Config {
+            locale: Some(locale),
+            ..
+        }) = self.config.get()
+        {
+            Locale::select(locale);
+        }
+    }
+
+    /// Returns the `color` setting, if any. As with the locale, a configuration that
+    /// can't be loaded is ignored here.
+    pub fn configured_color(&self) -> Option<ColorChoice> {
+        self.config.get().ok().and_then(|config| config.color)
+    }
+
+    /// Returns how progress is displayed, according to the `progress.style` setting.
+    pub fn configured_progress_display(&self) -> ProgressDisplay {
+        self.config
+            .get()
+            .map(|config| config.progress_display())
+            .unwrap_or(ProgressDisplay::Bar)
+    }
+
+    /// Ensures that a platform image has been fully fetched and set up. Versions the
+    /// current project's lockfile records are installed from their locked archives.
+    pub fn prepare_image(&mut self, image: &Image) -> Fallible<()> {
+        {
+            let project = self.trusted_project()?;
+            let (node_archive, yarn_archive) = locked_archives(project.as_ref(), image);
+            let file = self.toolchain.clone();
+            let (node_checksum, yarn_checksum) =
+                pinned_checksums(project.as_ref(), file.as_ref(), image);
+            let catalog = self.catalog.get_mut()?;
 
-        if !catalog.node.contains(&image.node) {
             let config = self.config.get()?;
-            let _ = catalog.fetch_node(&VersionSpec::exact(&image.node), config)?;
+            match (node_archive, node_checksum) {
+                (Some(archive), _) => catalog.ensure_locked_node(&image.node, archive)?,
+                (None, Some(checksum)) => {
+                    catalog.ensure_pinned_node(&image.node, checksum, config)?
+                }
+                (None, None) => catalog.ensure_node(&image.node, config)?,
+            }
+
+            if let Some(ref yarn_version) = &image.yarn {
+                match (yarn_archive, yarn_checksum) {
+                    (Some(archive), _) => catalog.ensure_locked_yarn(yarn_version, archive)?,
+                    (None, Some(checksum)) => {
+                        catalog.ensure_pinned_yarn(yarn_version, checksum, config)?
+                    }
+                    (None, None) => catalog.ensure_yarn(yarn_version, config)?,
+                }
+            }
+
+            catalog.use_node(&image.node, image.node_arch)?;
+            if let Some(ref yarn_version) = image.yarn {
+                catalog.use_yarn(yarn_version)?;
+            }
         }
 
-        if let Some(ref yarn_version) = &image.yarn {
-            if !catalog.yarn.contains(yarn_version) {
-                let config = self.config.get()?;
-                let _ = catalog.fetch_yarn(&VersionSpec::exact(yarn_version), config)?;
+        self.run_install_hooks()
+    }
+
+    /// Installs the tool versions of the current platform that aren't installed yet, so
+    /// that shims don't install them on demand. This is done up front in CI mode, where
+    /// each version is reported as it is prepared.
+    pub fn prepare_current_platform(&mut self) -> Fallible<()> {
+        if let Some(image) = self.current_platform()? {
+            let mut tools = vec![format!("Node v{}", image.node)];
+            if let Some(ref yarn) = image.yarn {
+                tools.push(format!("Yarn v{}", yarn));
             }
+            style::ci_progress(&format!("Preparing toolchain: {}", tools.join(", ")));
+            self.prepare_image(&image)?;
+        }
+        Ok(())
+    }
+
+    /// Plans fetching the tool versions of a platform image that aren't installed yet,
+    /// without touching the filesystem.
+    pub fn plan_prepare_image(&self, image: &Image) -> Fallible<Plan> {
+        let catalog = self.catalog.get()?;
+        let config = self.config.get()?;
+
+        let mut plan = Plan::new();
+        if !catalog.node.contains(&image.node) {
+            let node = VersionSpec::exact(&image.node);
+            plan.extend(catalog.plan_fetch_node(&node, config)?.1);
+        }
+        if let Some(ref yarn) = image.yarn {
+            if !catalog.yarn.contains(yarn) {
+                let yarn = VersionSpec::exact(yarn);
+                plan.extend(catalog.plan_fetch_yarn(&yarn, config)?.1);
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Resolves the current project's pins, installs the versions they resolve to, and
+    /// records them in the project's lockfile with the archives they were installed
+    /// from. The Node archives locked on other platforms are kept if the Node version
+    /// is unchanged.
+    pub fn lock_project(&mut self) -> Fallible<Lockfile> {
+        let project = match self.project() {
+            Some(project) => project,
+            None => throw!(NotInPackageError::new()),
+        };
+        let image = match (project.platform(), project.toolchain()) {
+            (Some(image), _) => image,
+            (None, Some(toolchain)) => self.resolve_toolchain(toolchain)?,
+            (None, None) => throw!(NoPinnedNodeVersion::new()),
+        };
+        self.prepare_image(&image)?;
+
+        let catalog = self.catalog.get()?;
+        let mut archives = match project.lockfile() {
+            Some(existing) if existing.node.version == image.node => {
+                existing.node.archives.clone()
+            }
+            _ => BTreeMap::new(),
+        };
+        let node = catalog.node.provenance.get(&image.node_key());
+        archives.insert(
+            lockfile::platform(),
+            Archive::installed("node", &image.node, node)?,
+        );
+        let yarn = match image.yarn {
+            Some(ref version) => Some(LockedYarn {
+                version: version.clone(),
+                archive: Archive::installed(
+                    "yarn",
+                    version,
+                    catalog.yarn.provenance.get(&version.to_string()),
+                )?,
+            }),
+            None => None,
+        };
+
+        let lockfile = Lockfile {
+            node: LockedNode {
+                version: image.node.clone(),
+                archives,
+            },
+            yarn,
+        };
+        lockfile.write(project.root())?;
+        Ok(lockfile)
+    }
+
+    /// Runs the install hooks for the tool versions installed since they last ran.
+    /// This happens after the catalog lock is released, so that hooks may themselves
+    /// run Notion shims (for instance to install global packages with `npm`).
+    fn run_install_hooks(&mut self) -> Fallible<()> {
+        let installed = self.catalog.get_mut()?.take_installed();
+        if installed.is_empty() {
+            return Ok(());
+        }
+
+        let hooks = Hooks::current()?;
+        for installed in &installed {
+            hooks.run_install(installed)?;
         }
 
+        if self.config.get()?.dedupe_after_install()
+            && installed.iter().any(|version| version.tool == "node")
+        {
+            self.catalog.get_mut()?.dedupe_node(true)?;
+        }
+
+        if let Some(days) = self.config.get()?.compress_after() {
+            self.catalog.get_mut()?.compress_unused(days, true)?;
+        }
         Ok(())
     }
 
-    pub fn user_node(&self) -> Fallible<Option<Version>> {
-        match env::var("NOTION_NODE_VERSION") {
-            Ok(s) => Ok(Some(Version::parse(&s[..]).unknown()?)),
-            Err(VarError::NotPresent) => Ok(self.catalog()?.node.default.clone()),
-            Err(VarError::NotUnicode(_)) => unimplemented!(),
+    /// Runs the current project's pre-run hook, if it has one, before a shim dispatches
+    /// to a tool. Projects without a `hooks.toml` pay only for checking that it exists.
+    /// The hooks of a project that isn't trusted never run, since they run any command.
+    pub fn run_pre_run_hook(&self, tool: &str, version: &Version) -> Fallible<()> {
+        if let Some(project) = self.trusted_project()? {
+            Hooks::from_file(&project.hooks_file())?.run_pre_run(tool, version)?;
         }
+        Ok(())
+    }
+
+    /// Installs an npm package as a package tool with the user toolchain's Node version,
+    /// and creates shims for its executables.
+    pub fn install_package(&mut self, spec: &PackageSpec) -> Fallible<PackageTool> {
+        let image = match self.user_platform()? {
+            Some(image) => image,
+            None => throw!(NoNodeForPackageError),
+        };
+        self.install_package_with(spec, &image)
+    }
+
+    /// Plans installing an npm package as a package tool, without touching the filesystem.
+    /// The shims to create are only known once the package has been installed.
+    pub fn plan_install_package(&self, spec: &PackageSpec) -> Fallible<Plan> {
+        let mut plan = Plan::new();
+        plan.push(Action::Write(path::package_dir(&spec.name)?));
+        plan.push(Action::Write(path::user_catalog_file()?));
+        Ok(plan)
+    }
+
+    /// Reinstalls a package tool at the newest version matching the version it was
+    /// installed with, on the Node version it was installed with unless another is
+    /// chosen, and moves its shims over to the new version's executables. Produces the
+    /// package tool before and after the upgrade.
+    pub fn upgrade_package(
+        &mut self,
+        name: &str,
+        node: Option<&VersionSpec>,
+    ) -> Fallible<(PackageTool, PackageTool)> {
+        let before = self.package_tool(name)?;
+        let image = match node {
+            Some(matching) => package::node_image(&self.get_matching_node(matching)?),
+            None => before.image(),
+        };
+        let after = self.install_package_with(&before.upgrade_spec(name), &image)?;
+
+        // The shims of executables the new version no longer provides are only removed
+        // once the new ones exist, and are kept if another package tool provides them.
+        for bin in &before.bins {
+            if !after.bins.contains(bin)
+                && shim::is_3p_shim(bin)
+                && self.catalog()?.package_for_bin(bin).is_none()
+            {
+                shim::delete(bin)?;
+            }
+        }
+        Ok((before, after))
+    }
+
+    /// Plans upgrading a package tool, without touching the filesystem.
+    pub fn plan_upgrade_package(&self, name: &str) -> Fallible<Plan> {
+        let before = self.package_tool(name)?;
+        self.plan_install_package(&before.upgrade_spec(name))
+    }
+
+    /// Creates a shim that runs an arbitrary executable, through `interpreter` if it
+    /// isn't empty, with a Node version matching `node` if one is requested, and
+    /// records the shim's target. A shim that already exists is retargeted.
+    pub fn create_target_shim(
+        &mut self,
+        name: &str,
+        file: PathBuf,
+        node: Option<&VersionSpec>,
+        interpreter: Vec<String>,
+    ) -> Fallible<ShimResult> {
+        let node = match node {
+            Some(matching) => {
+                let version = self.get_matching_node(matching)?;
+                self.prepare_image(&package::node_image(&version))?;
+                Some(version)
+            }
+            None => None,
+        };
+        self.catalog_mut()?.record_target(
+            name,
+            Target {
+                file,
+                node,
+                interpreter,
+            },
+        )?;
+        shim::create(name)
+    }
+
+    /// Deletes a shim, forgetting its target if it has one.
+    pub fn delete_shim(&mut self, name: &str) -> Fallible<ShimResult> {
+        let result = shim::delete(name)?;
+        self.catalog_mut()?.forget_target(name)?;
+        Ok(result)
+    }
+
+    fn package_tool(&self, name: &str) -> Fallible<PackageTool> {
+        match self.catalog()?.packages.get(name) {
+            Some(tool) => Ok(tool.clone()),
+            None => throw!(UnknownPackageError::new(name)),
+        }
+    }
+
+    fn install_package_with(&mut self, spec: &PackageSpec, image: &Image) -> Fallible<PackageTool> {
+        self.prepare_image(image)?;
+
+        let registries = Registries::current(self.config()?)?;
+        let tool = package::install(spec, image, &registries)?;
+        self.catalog_mut()?.record_package(&spec.name, tool.clone())?;
+        for bin in &tool.bins {
+            shim::create(bin)?;
+        }
+        Ok(tool)
+    }
+
+    pub fn user_node(&self) -> Fallible<Option<Version>> {
+        Ok(self.catalog()?.node.default.clone())
     }
 
     /// Fetches a version of Node matching the specified semantic verisoning
     /// requirements.
     pub fn fetch_node(&mut self, matching: &VersionSpec) -> Fallible<Fetched> {
-        let catalog = self.catalog.get_mut()?;
+        let fetched = {
+            let catalog = self.catalog.get_mut()?;
+            let config = self.config.get()?;
+            catalog.fetch_node(matching, config)?
+        };
+        self.run_install_hooks()?;
+        Ok(fetched)
+    }
+
+    /// Plans fetching a version of Node matching the specified semantic versioning
+    /// requirements, without touching the filesystem.
+    pub fn plan_fetch_node(&self, matching: &VersionSpec) -> Fallible<(Version, Plan)> {
+        let catalog = self.catalog.get()?;
         let config = self.config.get()?;
-        catalog.fetch_node(matching, config)
+        catalog.plan_fetch_node(matching, config)
     }
 
     /// Sets the user toolchain's Node version to one matching the specified semantic versioning
     /// requirements.
     pub fn set_user_node(&mut self, matching: &VersionSpec) -> Fallible<()> {
+        {
+            let catalog = self.catalog.get_mut()?;
+            let config = self.config.get()?;
+            catalog.set_user_node(matching, config)?;
+        }
+        self.run_install_hooks()
+    }
+
+    /// Sets the user toolchain's Node version to a build for the specified architecture
+    /// of one matching the specified semantic versioning requirements.
+    pub fn set_user_node_for_arch(&mut self, matching: &VersionSpec, arch: Arch) -> Fallible<Version> {
+        let version = {
+            let catalog = self.catalog.get_mut()?;
+            let config = self.config.get()?;
+            catalog.set_user_node_for_arch(matching, config, arch)?
+        };
+        self.run_install_hooks()?;
+        Ok(version)
+    }
+
+    /// Plans setting the user toolchain's Node version to a build for the specified
+    /// architecture, without touching the filesystem.
+    pub fn plan_set_user_node_for_arch(&self, matching: &VersionSpec, arch: Arch) -> Fallible<(Version, Plan)> {
+        let catalog = self.catalog.get()?;
+        let config = self.config.get()?;
+        catalog.plan_set_user_node_for_arch(matching, config, arch)
+    }
+
+    /// Sets the user toolchain's Node version to the one in a local archive file.
+    pub fn set_user_node_from_archive(&mut self, archive: &Path) -> Fallible<Version> {
+        let version = {
+            let catalog = self.catalog.get_mut()?;
+            catalog.set_user_node_from_archive(archive)?
+        };
+        self.run_install_hooks()?;
+        Ok(version)
+    }
+
+    /// Plans setting the user toolchain's Node version from a local archive file, without
+    /// touching the filesystem.
+    pub fn plan_set_user_node_from_archive(&self, archive: &Path) -> Fallible<(Version, Plan)> {
+        let catalog = self.catalog.get()?;
+        catalog.plan_set_user_node_from_archive(archive)
+    }
+
+    /// Sets the user toolchain's Node version to the one downloaded from an arbitrary URL.
+    pub fn set_user_node_from_url(&mut self, url: &str, checksum: Option<&Checksum>) -> Fallible<Version> {
+        let version = {
+            let catalog = self.catalog.get_mut()?;
+            catalog.set_user_node_from_url(url, checksum)?
+        };
+        self.run_install_hooks()?;
+        Ok(version)
+    }
+
+    /// Plans setting the user toolchain's Node version from an arbitrary URL, without
+    /// touching the filesystem.
+    pub fn plan_set_user_node_from_url(&self, url: &str) -> Fallible<(Version, Plan)> {
+        let catalog = self.catalog.get()?;
+        catalog.plan_set_user_node_from_url(url)
+    }
+
+    /// Plans setting the user toolchain's Node version, without touching the filesystem.
+    pub fn plan_set_user_node(&self, matching: &VersionSpec) -> Fallible<(Version, Plan)> {
+        let catalog = self.catalog.get()?;
+        let config = self.config.get()?;
+        catalog.plan_set_user_node(matching, config)
+    }
+
+    /// Reinstalls an installed Node build, leaving the user toolchain as it is.
+    pub fn repair_node(&mut self, version: &Version, arch: Arch) -> Fallible<()> {
         let catalog = self.catalog.get_mut()?;
         let config = self.config.get()?;
-        catalog.set_user_node(matching, config)
+        catalog.repair_node(version, arch, config)
+    }
+
+    /// Plans reinstalling an installed Node build, without touching the filesystem.
+    pub fn plan_repair_node(&self, version: &Version, arch: Arch) -> Fallible<Plan> {
+        let catalog = self.catalog.get()?;
+        catalog.plan_repair_node(version, arch)
+    }
+
+    /// Replaces files that are identical across the installed Node versions with
+    /// hardlinks, or reports what doing so would reclaim if `apply` is false.
+    pub fn dedupe_node(&mut self, apply: bool) -> Fallible<dedupe::Summary> {
+        self.catalog.get_mut()?.dedupe_node(apply)
+    }
+
+    /// Compresses the installed versions that have gone unused for more than `days` days,
+    /// or reports which would be compressed if `apply` is false.
+    pub fn compress_unused(&mut self, days: u64, apply: bool) -> Fallible<Vec<Compressed>> {
+        self.catalog.get_mut()?.compress_unused(days, apply)
     }
 
     /// Returns the version of Node matching the specified semantic versioning requirements.
@@ -214,18 +1108,115 @@ impl Session {
         catalog.resolve_node(matching, config)
     }
 
-    /// Updates toolchain in package.json with the Node version matching the specified semantic
-    /// versioning requirements.
-    pub fn pin_node_version(&self, matching: &VersionSpec) -> Fallible<()> {
+    /// Updates toolchain in package.json (or notion.toml) with the Node version matching
+    /// the specified semantic versioning requirements, written in the given style or
+    /// else the configured `pin.style`.
+    pub fn pin_node_version(
+        &self,
+        matching: &VersionSpec,
+        style: Option<PinStyle>,
+        to_file: bool,
+    ) -> Fallible<()> {
         if let Some(ref project) = self.project() {
             let node_version = self.get_matching_node(matching)?;
-            project.pin_node_in_toolchain(node_version)?;
+            let style = self.pin_style(style)?;
+            project.pin_node_in_toolchain(node_version, style, to_file)?;
         } else {
             throw!(NotInPackageError::new());
         }
         Ok(())
     }
 
+    /// Produces the style a pinned version is written in: the style requested, if any,
+    /// or else the configured `pin.style`.
+    fn pin_style(&self, requested: Option<PinStyle>) -> Fallible<PinStyle> {
+        match requested {
+            Some(style) => Ok(style),
+            None => Ok(self.config.get()?.pin_style()),
+        }
+    }
+
+    /// Plans pinning the Node version in package.json (or notion.toml), without touching
+    /// the filesystem.
+    pub fn plan_pin_node_version(&self, matching: &VersionSpec, to_file: bool) -> Fallible<(Version, Plan)> {
+        if let Some(ref project) = self.project() {
+            let mut plan = Plan::new();
+            plan.push(Action::Write(project.pin_file(to_file)));
+            return Ok((self.get_matching_node(matching)?, plan));
+        }
+        throw!(NotInPackageError::new());
+    }
+
+    /// Plans selecting the Node version for the current shell session, without touching
+    /// the filesystem.
+    pub fn plan_use_shell_node(&self, matching: &VersionSpec) -> Fallible<(Version, Plan)> {
+        let (version, mut plan) = {
+            let catalog = self.catalog.get()?;
+            let config = self.config.get()?;
+            catalog.plan_fetch_node(matching, config)?
+        };
+        if let Some(postscript) = notion_env::postscript_path() {
+            plan.push(Action::Write(postscript));
+        }
+        Ok((version, plan))
+    }
+
+    /// Re-resolves the current project's range pins to the newest versions that satisfy
+    /// them, fetching those versions if they are not installed yet.
+    pub fn update_range_pins(&mut self) -> Fallible<Vec<PinUpdate>> {
+        let toolchain = match self.project {
+            Some(ref project) => match project.toolchain() {
+                Some(toolchain) if toolchain.has_ranges() => toolchain.clone(),
+                _ => throw!(NoRangePinsError),
+            },
+            None => throw!(NotInPackageError::new()),
+        };
+
+        let mut updates = Vec::new();
+
+        // A pinned npm version decides the Node version on its own.
+        if let (&Pin::Range(ref range), &None) = (&toolchain.node, &toolchain.npm) {
+            let (from, to) = {
+                let catalog = self.catalog.get()?;
+                let config = self.config.get()?;
+                (
+                    catalog.resolve_node_pin(range, config)?,
+                    catalog.resolve_node(&VersionSpec::Semver(range.clone()), config)?,
+                )
+            };
+            self.fetch_node(&VersionSpec::exact(&to))?;
+            updates.push(PinUpdate {
+                tool: "node",
+                range: toolchain.node_str.clone(),
+                from,
+                to,
+            });
+        }
+
+        if let Some(Pin::Range(ref range)) = toolchain.yarn {
+            let (from, to) = {
+                let catalog = self.catalog.get()?;
+                let config = self.config.get()?;
+                (
+                    catalog.resolve_yarn_pin(range, config)?,
+                    catalog.resolve_yarn(&VersionSpec::Semver(range.clone()), config)?,
+                )
+            };
+            self.fetch_yarn(&VersionSpec::exact(&to))?;
+            updates.push(PinUpdate {
+                tool: "yarn",
+                range: toolchain.yarn_str.clone().unwrap_or_default(),
+                from,
+                to,
+            });
+        }
+
+        // The project now resolves to the versions just fetched.
+        self.project_image = LazyCell::new();
+
+        Ok(updates)
+    }
+
     pub fn user_yarn(&mut self) -> Fallible<Option<Version>> {
         Ok(self.catalog()?.yarn.default.clone())
     }
@@ -233,17 +1224,78 @@ impl Session {
     /// Fetches a version of Node matching the specified semantic verisoning
     /// requirements.
     pub fn fetch_yarn(&mut self, matching: &VersionSpec) -> Fallible<Fetched> {
-        let catalog = self.catalog.get_mut()?;
-        let config = self.config.get()?;
-        catalog.fetch_yarn(matching, config)
+        let fetched = {
+            let catalog = self.catalog.get_mut()?;
+            let config = self.config.get()?;
+            catalog.fetch_yarn(matching, config)?
+        };
+        self.run_install_hooks()?;
+        Ok(fetched)
     }
 
     /// Sets the Yarn version in the user toolchain to one matching the specified semantic versioning
     /// requirements.
     pub fn set_user_yarn(&mut self, matching: &VersionSpec) -> Fallible<()> {
+        {
+            let catalog = self.catalog.get_mut()?;
+            let config = self.config.get()?;
+            catalog.set_user_yarn(matching, config)?;
+        }
+        self.run_install_hooks()
+    }
+
+    /// Sets the user toolchain's Yarn version to the one in a local archive file.
+    pub fn set_user_yarn_from_archive(&mut self, archive: &Path) -> Fallible<Version> {
+        let version = {
+            let catalog = self.catalog.get_mut()?;
+            catalog.set_user_yarn_from_archive(archive)?
+        };
+        self.run_install_hooks()?;
+        Ok(version)
+    }
+
+    /// Plans setting the user toolchain's Yarn version from a local archive file, without
+    /// touching the filesystem.
+    pub fn plan_set_user_yarn_from_archive(&self, archive: &Path) -> Fallible<(Version, Plan)> {
+        let catalog = self.catalog.get()?;
+        catalog.plan_set_user_yarn_from_archive(archive)
+    }
+
+    /// Sets the user toolchain's Yarn version to the one downloaded from an arbitrary URL.
+    pub fn set_user_yarn_from_url(&mut self, url: &str, checksum: Option<&Checksum>) -> Fallible<Version> {
+        let version = {
+            let catalog = self.catalog.get_mut()?;
+            catalog.set_user_yarn_from_url(url, checksum)?
+        };
+        self.run_install_hooks()?;
+        Ok(version)
+    }
+
+    /// Plans setting the user toolchain's Yarn version from an arbitrary URL, without
+    /// touching the filesystem.
+    pub fn plan_set_user_yarn_from_url(&self, url: &str) -> Fallible<(Version, Plan)> {
+        let catalog = self.catalog.get()?;
+        catalog.plan_set_user_yarn_from_url(url)
+    }
+
+    /// Plans setting the user toolchain's Yarn version, without touching the filesystem.
+    pub fn plan_set_user_yarn(&self, matching: &VersionSpec) -> Fallible<(Version, Plan)> {
+        let catalog = self.catalog.get()?;
+        let config = self.config.get()?;
+        catalog.plan_set_user_yarn(matching, config)
+    }
+
+    /// Reinstalls an installed Yarn version, leaving the user toolchain as it is.
+    pub fn repair_yarn(&mut self, version: &Version) -> Fallible<()> {
         let catalog = self.catalog.get_mut()?;
         let config = self.config.get()?;
-        catalog.set_user_yarn(matching, config)
+        catalog.repair_yarn(version, config)
+    }
+
+    /// Plans reinstalling an installed Yarn version, without touching the filesystem.
+    pub fn plan_repair_yarn(&self, version: &Version) -> Fallible<Plan> {
+        let catalog = self.catalog.get()?;
+        catalog.plan_repair_yarn(version)
     }
 
     /// Returns the version of Yarn matching the specified semantic versioning requirements
@@ -253,18 +1305,50 @@ impl Session {
         catalog.resolve_yarn(matching, config)
     }
 
-    /// Updates toolchain in package.json with the Yarn version matching the specified semantic
-    /// versioning requirements.
-    pub fn pin_yarn_version(&self, matching: &VersionSpec) -> Fallible<()> {
+    /// Updates toolchain in package.json (or notion.toml) with the Yarn version matching
+    /// the specified semantic versioning requirements, written in the given style or
+    /// else the configured `pin.style`.
+    pub fn pin_yarn_version(
+        &self,
+        matching: &VersionSpec,
+        style: Option<PinStyle>,
+        to_file: bool,
+    ) -> Fallible<()> {
         if let Some(ref project) = self.project() {
             let yarn_version = self.get_matching_yarn(matching)?;
-            project.pin_yarn_in_toolchain(yarn_version)?;
+            let style = self.pin_style(style)?;
+            project.pin_yarn_in_toolchain(yarn_version, style, to_file)?;
         } else {
             throw!(NotInPackageError::new());
         }
         Ok(())
     }
 
+    /// Plans pinning the Yarn version in package.json (or notion.toml), without touching
+    /// the filesystem.
+    pub fn plan_pin_yarn_version(&self, matching: &VersionSpec, to_file: bool) -> Fallible<(Version, Plan)> {
+        if let Some(ref project) = self.project() {
+            let mut plan = Plan::new();
+            plan.push(Action::Write(project.pin_file(to_file)));
+            return Ok((self.get_matching_yarn(matching)?, plan));
+        }
+        throw!(NotInPackageError::new());
+    }
+
+    /// Plans selecting the Yarn version for the current shell session, without touching
+    /// the filesystem.
+    pub fn plan_use_shell_yarn(&self, matching: &VersionSpec) -> Fallible<(Version, Plan)> {
+        let (version, mut plan) = {
+            let catalog = self.catalog.get()?;
+            let config = self.config.get()?;
+            catalog.plan_fetch_yarn(matching, config)?
+        };
+        if let Some(postscript) = notion_env::postscript_path() {
+            plan.push(Action::Write(postscript));
+        }
+        Ok((version, plan))
+    }
+
     pub fn add_event_start(&mut self, activity_kind: ActivityKind) {
         self.event_log.add_event_start(activity_kind)
     }
@@ -275,45 +1359,94 @@ impl Session {
         self.event_log.add_event_tool_end(activity_kind, exit_code)
     }
     pub fn add_event_error(&mut self, activity_kind: ActivityKind, error: &NotionError) {
+        if let Ok(Some(sink)) = self.config.get().map(|config| config.log_sink()) {
+            logging::error(sink, &activity_kind.to_string(), error);
+        }
         self.event_log.add_event_error(activity_kind, error)
     }
 
     fn publish_to_event_log(mut self) {
-        match publish_plugin(&self.config) {
-            Ok(plugin) => {
-                self.event_log.publish(plugin);
+        match event_subscribers(&self.config) {
+            Ok((subscribers, telemetry)) => {
+                self.event_log.publish(&subscribers, telemetry);
+            }
+            Err(e) => {
+                logging::warning(&format!("invalid config file ({})", e));
+            }
+        }
+    }
+
+    /// Notifies the configured webhook of the installs and uninstalls made during the
+    /// session, if there were any.
+    fn notify_webhook(&mut self) {
+        let notifications = match self.catalog.loaded_mut() {
+            Some(catalog) => catalog.take_notifications(),
+            None => return,
+        };
+        if notifications.is_empty() {
+            return;
+        }
+        match self.config.get() {
+            Ok(config) => {
+                if let Some(ref webhook) = config.webhook {
+                    webhook::notify_all(webhook, &notifications);
+                }
             }
             Err(e) => {
-                eprintln!("Warning: invalid config file ({})", e);
+                logging::warning(&format!("invalid config file ({})", e));
             }
         }
     }
 
-    pub fn exit(self, code: ExitCode) -> ! {
+    /// Records the phases timed during the session as events, and reports them if
+    /// `notion --timings` asked for them.
+    fn record_phases(&mut self) {
+        let timings = timing::take();
+        if timing::report_enabled() {
+            eprintln!();
+            eprint!("{}", timing::format_report(&timings));
+        }
+        self.event_log.add_event_phases(&timings);
+    }
+
+    pub fn exit(mut self, code: ExitCode) -> ! {
+        self.record_phases();
+        self.notify_webhook();
         self.publish_to_event_log();
         code.exit();
     }
 
-    pub fn exit_tool(self, code: i32) -> ! {
+    pub fn exit_tool(mut self, code: i32) -> ! {
+        self.record_phases();
+        self.notify_webhook();
         self.publish_to_event_log();
         exit(code);
     }
 }
 
-fn publish_plugin(config: &LazyConfig) -> Fallible<Option<&Publish>> {
+/// Produces the configured event subscribers, along with the telemetry settings that
+/// control what they receive. There are no subscribers if telemetry is disabled.
+fn event_subscribers(
+    config: &LazyConfig,
+) -> Fallible<(Vec<Subscriber>, Option<&TelemetryConfig>)> {
     let config = config.get()?;
-    Ok(config
-        .events
-        .as_ref()
-        .and_then(|events| events.publish.as_ref()))
+    let subscribers = match config.events {
+        Some(ref events) if config.telemetry_enabled() => events.all_subscribers(),
+        _ => Vec::new(),
+    };
+    Ok((subscribers, config.telemetry.as_ref()))
 }
 
 #[cfg(test)]
 pub mod tests {
 
+    use semver::Version;
     use session::Session;
     use std::env;
+    use std::fs::File;
+    use std::io::Write;
     use std::path::PathBuf;
+    use tempfile::tempdir;
 
     fn fixture_path(fixture_dir: &str) -> PathBuf {
         let mut cargo_manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -327,11 +1460,50 @@ pub mod tests {
         let project_pinned = fixture_path("basic");
         env::set_current_dir(&project_pinned).expect("Could not set current directory");
         let pinned_session = Session::new().expect("Couldn't create new Session");
-        assert_eq!(pinned_session.project_platform().is_some(), true);
+        // the fixtures are trusted here rather than in the user's trust store, which
+        // would otherwise be asked about or warned over
+        pinned_session.project_trusted.fill(true).unwrap();
+        assert_eq!(pinned_session.project_platform().unwrap().is_some(), true);
 
         let project_unpinned = fixture_path("no_toolchain");
         env::set_current_dir(&project_unpinned).expect("Could not set current directory");
         let unpinned_session = Session::new().expect("Couldn't create new Session");
-        assert_eq!(unpinned_session.project_platform().is_none(), true);
+        unpinned_session.project_trusted.fill(true).unwrap();
+        assert_eq!(unpinned_session.project_platform().unwrap().is_none(), true);
+    }
+
+    #[test]
+    fn test_untrusted_pre_run_hook() {
+        let project = tempdir().unwrap();
+        File::create(project.path().join("package.json"))
+            .and_then(|mut file| file.write_all(b"{}"))
+            .unwrap();
+        // a hook that fails to start, and so fails the run if it is run at all
+        File::create(project.path().join("hooks.toml"))
+            .and_then(|mut file| file.write_all(b"[events.pre-run]\nrun = \"./missing-hook\"\n"))
+            .unwrap();
+
+        let session = Session::for_dir(project.path()).unwrap();
+        session.trust_without_asking().unwrap();
+        assert!(session.trusted_project().unwrap().is_none());
+        assert!(
+            session
+                .run_pre_run_hook("node", &Version::parse("18.17.1").unwrap())
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_untrusted_directory_without_asking() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join(".notion-version"))
+            .and_then(|mut file| file.write_all(b"18.17.1\n"))
+            .unwrap();
+
+        let session = Session::for_dir(dir.path()).unwrap();
+        session.trust_without_asking().unwrap();
+        // decided up front, so that resolving the platform never asks
+        assert_eq!(session.directory_trusted.borrow(), Some(&false));
+        assert!(session.directory_platform().unwrap().is_none());
     }
 }
@@ -0,0 +1,397 @@
+//! Provides the shell integration that `notion setup` installs into a user's profile.
+//!
+//! For each shell, this is a small script generated into the Notion user directory
+//! and loaded from the user's profile. The script puts the shim directory on the
+//! `PATH`, wraps `notion` so that commands like `notion use` can change the
+//! environment of the running shell, and sets up tab completion for Notion's
+//! commands. For PowerShell, the script is a module imported from `$PROFILE` that
+//! also defines `Get-NotionPrompt` for use in a custom `prompt` function; for fish,
+//! it is sourced from `config.fish`, and for Nushell from `config.nu`.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use fs::ensure_containing_dir_exists;
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+use completions;
+use path;
+use plan::{Action, Plan};
+use shell::{fish, nu, powershell};
+
+/// The PowerShell executables tried, in order, to locate the user's profile.
+const POWERSHELL_EXECUTABLES: &'static [&'static str] = &["pwsh", "powershell"];
+
+/// Thrown when no PowerShell executable can be run to locate the user's profile.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not run PowerShell to locate your profile; name it with --profile")]
+#[notion_fail(code = "EnvironmentError")]
+pub(crate) struct NoPowerShellError;
+
+/// Thrown when the home directory, which holds the fish configuration, is unknown.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not locate config.fish; name it with --profile")]
+#[notion_fail(code = "EnvironmentError")]
+pub(crate) struct NoFishConfigError;
+
+/// Thrown when Nushell cannot be run to locate its configuration file.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not run Nushell to locate config.nu; name it with --profile")]
+#[notion_fail(code = "EnvironmentError")]
+pub(crate) struct NoNuError;
+
+/// The shells `notion setup` can integrate Notion with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SetupShell {
+    Fish,
+    Nu,
+    PowerShell,
+}
+
+/// The files involved in integrating Notion with a shell.
+pub struct ShellSetup {
+    /// The shell being set up.
+    pub shell: SetupShell,
+    /// The script Notion generates.
+    pub script: PathBuf,
+    /// The user's profile script, which loads the generated script.
+    pub profile: PathBuf,
+}
+
+impl ShellSetup {
+    /// Prepares to integrate Notion with a shell through the given profile script
+    /// or, if none is given, the shell's usual one: the profile PowerShell itself
+    /// reports for the current user and host, fish's `config.fish`, or the
+    /// `config.nu` Nushell reports.
+    pub fn new(shell: SetupShell, profile: Option<PathBuf>) -> Fallible<Self> {
+        let (script, default_profile): (PathBuf, fn() -> Fallible<PathBuf>) = match shell {
+            SetupShell::Fish => (path::fish_script_file()?, fish_config_file),
+            SetupShell::Nu => (path::nu_script_file()?, nu_config_file),
+            SetupShell::PowerShell => (path::powershell_module_file()?, powershell_profile),
+        };
+        Ok(ShellSetup {
+            shell,
+            script,
+            profile: match profile {
+                Some(profile) => profile,
+                None => default_profile()?,
+            },
+        })
+    }
+
+    /// Plans the setup, without touching the filesystem. The script is always
+    /// regenerated, but the profile is only written if it doesn't load it yet.
+    pub fn plan(&self) -> Plan {
+        let mut plan = Plan::new();
+        plan.push(Action::Write(self.script.clone()));
+        if !self.profile_loads_script() {
+            plan.push(Action::Write(self.profile.clone()));
+        }
+        plan
+    }
+
+    /// Generates the script, offering completion of the given commands (by name and
+    /// description), and loads it from the profile unless the profile does so
+    /// already. Produces true if the profile was changed.
+    pub fn install(&self, commands: &[(&str, &str)]) -> Fallible<bool> {
+        let notion = path::notion_file()?;
+        let shim_dir = path::shim_dir()?;
+        let source = match self.shell {
+            SetupShell::Fish => fish_source(&notion, &shim_dir),
+            SetupShell::Nu => nu_source(&notion, &shim_dir, commands),
+            SetupShell::PowerShell => module_source(&notion, &shim_dir),
+        };
+        ensure_containing_dir_exists(&self.script)?;
+        File::create(&self.script)
+            .unknown()?
+            .write_all(source.as_bytes())
+            .unknown()?;
+
+        if self.profile_loads_script() {
+            return Ok(false);
+        }
+
+        let mut profile = fs::read_to_string(&self.profile).unwrap_or_default();
+        if !profile.is_empty() && !profile.ends_with('\n') {
+            profile.push('\n');
+        }
+        profile.push_str(&self.load_line());
+
+        ensure_containing_dir_exists(&self.profile)?;
+        File::create(&self.profile)
+            .unknown()?
+            .write_all(profile.as_bytes())
+            .unknown()?;
+        Ok(true)
+    }
+
+    /// Produces the line of the profile that loads the script.
+    fn load_line(&self) -> String {
+        let script = self.script.to_string_lossy();
+        match self.shell {
+            SetupShell::Fish => format!("source {}\n", fish::quote(&script)),
+            SetupShell::Nu => format!("source {}\n", nu::quote(&script)),
+            SetupShell::PowerShell => format!(
+                "Import-Module {} -DisableNameChecking\n",
+                powershell::quote(&script)
+            ),
+        }
+    }
+
+    fn profile_loads_script(&self) -> bool {
+        fs::read_to_string(&self.profile)
+            .map(|profile| profile.contains(self.load_line().trim_right()))
+            .unwrap_or(false)
+    }
+}
+
+/// Produces the path of fish's configuration file, which is under
+/// `$XDG_CONFIG_HOME` if that is set.
+fn fish_config_file() -> Fallible<PathBuf> {
+    let config_home = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => env::home_dir().ok_or(NoFishConfigError)?.join(".config"),
+    };
+    Ok(config_home.join("fish").join("config.fish"))
+}
+
+/// Asks Nushell for the path of its configuration file, which depends on the platform.
+fn nu_config_file() -> Fallible<PathBuf> {
+    let output = Command::new("nu")
+        .args(&["--no-config-file", "--commands", "$nu.config-path"])
+        .output()
+        .map_err(|_| NoNuError)?;
+
+    let config = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || config.is_empty() {
+        throw!(NoNuError);
+    }
+    Ok(PathBuf::from(config))
+}
+
+/// Asks PowerShell for the path of the current user's profile for the current host.
+fn powershell_profile() -> Fallible<PathBuf> {
+    for executable in POWERSHELL_EXECUTABLES {
+        let output = Command::new(executable)
+            .args(&["-NoLogo", "-NoProfile", "-Command"])
+            .arg("$PROFILE.CurrentUserCurrentHost")
+            .output();
+
+        if let Ok(output) = output {
+            let profile = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if output.status.success() && !profile.is_empty() {
+                return Ok(PathBuf::from(profile));
+            }
+        }
+    }
+    throw!(NoPowerShellError);
+}
+
+/// Produces the source of the fish script. Completions come from
+/// `notion completions fish`, so that they are always those of the installed Notion.
+fn fish_source(notion: &Path, shim_dir: &Path) -> String {
+    format!(
+        r#"# Notion integration for fish, generated by `notion setup`.
+# Changes to this file are lost the next time `notion setup` is run.
+
+set -g __notion_exe {notion}
+
+# Put the shims first on the PATH, unless they are already on it.
+if not contains -- {shim_dir} $PATH
+    set -gx PATH {shim_dir} $PATH
+end
+
+# Commands like `notion use` change the environment of the shell by writing a
+# postscript, which is run here once Notion exits.
+function notion
+    set -l postscript (command mktemp)
+    env NOTION_SHELL=fish NOTION_POSTSCRIPT=$postscript $__notion_exe $argv
+    set -l exit_code $status
+    if test -s $postscript
+        source $postscript
+    end
+    command rm -f $postscript
+    return $exit_code
+end
+
+$__notion_exe completions fish | source
+"#,
+        notion = fish::quote(&notion.to_string_lossy()),
+        shim_dir = fish::quote(&shim_dir.to_string_lossy())
+    )
+}
+
+/// Produces the source of the Nushell script. Nushell only sources files when it
+/// parses a script, so completions are generated into the script itself, and the
+/// `notion` wrapper applies the postscript by reading the variables it sets.
+fn nu_source(notion: &Path, shim_dir: &Path, commands: &[(&str, &str)]) -> String {
+    format!(
+        r#"# Notion integration for Nushell, generated by `notion setup`.
+# Changes to this file are lost the next time `notion setup` is run.
+
+const notion_exe = {notion}
+const notion_shim_dir = {shim_dir}
+
+# Put the shims first on the PATH, unless they are already on it.
+if not ($notion_shim_dir in $env.PATH) {{
+    $env.PATH = ($env.PATH | prepend $notion_shim_dir)
+}}
+
+# Commands like `notion use` change the environment of the shell by writing a
+# postscript of `$env` assignments, which are applied here once Notion exits.
+def --env --wrapped notion [...args] {{
+    let postscript = (mktemp --tmpdir notion.XXXXXX)
+    with-env {{NOTION_SHELL: nu, NOTION_POSTSCRIPT: $postscript}} {{
+        ^$notion_exe ...$args
+    }}
+    let lines = (open --raw $postscript | lines)
+    rm --force $postscript
+    let vars = ($lines | parse '$env.{{name}} = {{value}}')
+    load-env ($vars | reduce --fold {{}} {{|var, acc|
+        $acc | insert $var.name ($var.value | from nuon)
+    }})
+    if ($lines | any {{|line| $line starts-with 'hide-env'}}) {{
+        hide-env --ignore-errors NOTION_HOME
+    }}
+}}
+
+{completions}"#,
+        notion = nu::quote(&notion.to_string_lossy()),
+        shim_dir = nu::quote(&shim_dir.to_string_lossy()),
+        completions = completions::nu(notion, commands)
+    )
+}
+
+/// Produces the source of the PowerShell module. Arguments are completed by
+/// `notion __complete`, including the command itself.
+fn module_source(notion: &Path, shim_dir: &Path) -> String {
+    format!(
+        r#"# Notion integration for PowerShell, generated by `notion setup`.
+# Changes to this file are lost the next time `notion setup` is run.
+
+$NotionExe = {notion}
+$NotionShimDir = {shim_dir}
+
+# Put the shims first on the PATH, unless they are already on it.
+$separator = [IO.Path]::PathSeparator
+if (-not (($env:PATH -split $separator) -contains $NotionShimDir)) {{
+    Set-Item Env:PATH -Value ($NotionShimDir + $separator + $env:PATH)
+}}
+
+# Commands like `notion use` change the environment of the shell by writing a
+# postscript, which is run here once Notion exits.
+function notion {{
+    $name = "notion_" + [guid]::NewGuid().ToString("N") + ".ps1"
+    $postscript = Join-Path ([IO.Path]::GetTempPath()) $name
+    $env:NOTION_SHELL = 'powershell'
+    $env:NOTION_POSTSCRIPT = $postscript
+    & $NotionExe @args
+    $exitCode = $LASTEXITCODE
+    Remove-Item Env:NOTION_POSTSCRIPT
+    if (Test-Path $postscript) {{
+        . $postscript
+        Remove-Item $postscript
+    }}
+    $global:LASTEXITCODE = $exitCode
+}}
+
+Register-ArgumentCompleter -Native -CommandName notion -ScriptBlock {{
+    param($wordToComplete, $commandAst, $cursorPosition)
+    $words = @($commandAst.CommandElements | Select-Object -Skip 1 |
+        Where-Object {{ $_.Extent.EndOffset -le $cursorPosition }} |
+        ForEach-Object {{ $_.ToString() }})
+    if ($wordToComplete -eq '') {{
+        # Windows PowerShell drops empty arguments to native commands, but passes
+        # quotes through as they are.
+        $words += if ($PSVersionTable.PSVersion -lt [version]'7.3') {{ '""' }} else {{ '' }}
+    }}
+    & $NotionExe __complete -- @words 2>$null | ForEach-Object {{
+        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)
+    }}
+}}
+
+# Produces the active Node version for use in a prompt, e.g.
+#
+#     function prompt {{ "$(Get-NotionPrompt)PS $PWD> " }}
+function Get-NotionPrompt {{
+    $active = & $NotionExe current 2>$null |
+        Where-Object {{ $_ -like '*(active)*' }} |
+        Select-Object -First 1
+    if ($active -match 'v\d\S*') {{
+        "[node $($Matches[0])] "
+    }}
+}}
+
+Export-ModuleMember -Function notion, Get-NotionPrompt
+"#,
+        notion = powershell::quote(&notion.to_string_lossy()),
+        shim_dir = powershell::quote(&shim_dir.to_string_lossy())
+    )
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{fish_source, module_source, nu_source, SetupShell, ShellSetup};
+    use std::fs::{read_to_string, File};
+    use std::io::Write;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_module_source() {
+        let source = module_source(
+            Path::new("C:\\Notion\\notion.exe"),
+            Path::new("C:\\Users\\o'brien\\Notion\\bin"),
+        );
+        assert!(source.contains("$NotionExe = 'C:\\Notion\\notion.exe'\n"));
+        assert!(source.contains("$NotionShimDir = 'C:\\Users\\o''brien\\Notion\\bin'\n"));
+        assert!(source.contains("    & $NotionExe __complete -- @words 2>$null"));
+    }
+
+    #[test]
+    fn test_fish_source() {
+        let source = fish_source(
+            Path::new("/home/o'brien/.notion/notion"),
+            Path::new("/home/o'brien/.notion/bin"),
+        );
+        assert!(source.contains("set -g __notion_exe '/home/o\\'brien/.notion/notion'\n"));
+        assert!(source.contains("    set -gx PATH '/home/o\\'brien/.notion/bin' $PATH\n"));
+    }
+
+    #[test]
+    fn test_nu_source() {
+        let source = nu_source(
+            Path::new("/home/user/.notion/notion"),
+            Path::new("/home/user/My \"Notion\"/bin"),
+            &[("fetch", "Fetch a tool")],
+        );
+        assert!(source.contains("const notion_exe = \"/home/user/.notion/notion\"\n"));
+        assert!(source.contains("const notion_shim_dir = \"/home/user/My \\\"Notion\\\"/bin\"\n"));
+        assert!(source.contains("let notion_completer = {|spans|\n"));
+    }
+
+    #[test]
+    fn test_profile_loads_script_once() {
+        let dir = tempdir().expect("Could not create temporary directory");
+        let setup = ShellSetup {
+            shell: SetupShell::Fish,
+            script: dir.path().join("notion.fish"),
+            profile: dir.path().join("config.fish"),
+        };
+        write!(File::create(&setup.profile).unwrap(), "set -g fish_greeting").unwrap();
+
+        assert_eq!(setup.plan().actions().len(), 2);
+        assert!(!setup.profile_loads_script());
+
+        let mut profile = read_to_string(&setup.profile).unwrap();
+        profile.push('\n');
+        profile.push_str(&setup.load_line());
+        write!(File::create(&setup.profile).unwrap(), "{}", profile).unwrap();
+
+        assert!(setup.profile_loads_script());
+        assert_eq!(setup.plan().actions().len(), 1);
+    }
+}
@@ -3,12 +3,12 @@ use std::path::{Path, PathBuf};
 use super::{Postscript, Shell};
 
 pub(crate) struct Bash {
-    pub(crate) postscript_path: PathBuf,
+    pub(crate) postscript_path: Option<PathBuf>,
 }
 
 impl Shell for Bash {
-    fn postscript_path(&self) -> &Path {
-        &self.postscript_path
+    fn postscript_path(&self) -> Option<&Path> {
+        self.postscript_path.as_ref().map(|p| p.as_path())
     }
 
     fn compile_postscript(&self, postscript: &Postscript) -> String {
@@ -17,6 +17,10 @@ impl Shell for Bash {
                 // ISSUE(#99): proper escaping
                 format!("export PATH='{}'\nunset NOTION_HOME\n", s)
             }
+            &Postscript::Path(ref s) => {
+                // ISSUE(#99): proper escaping
+                format!("export PATH='{}'\n", s)
+            }
             &Postscript::ToolVersion {
                 ref tool,
                 ref version,
@@ -25,6 +29,13 @@ impl Shell for Bash {
                 tool.to_ascii_uppercase(),
                 version
             ),
+            &Postscript::Var {
+                ref name,
+                ref value,
+            } => {
+                // ISSUE(#99): proper escaping
+                format!("export {}='{}'\n", name, value)
+            }
         }
     }
 }
@@ -0,0 +1,47 @@
+use std::path::{Path, PathBuf};
+
+use super::{Postscript, Shell};
+
+pub(crate) struct Fish {
+    pub(crate) postscript_path: Option<PathBuf>,
+}
+
+/// Quotes a string for fish, which only treats `\` and `'` specially inside single quotes.
+pub(crate) fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Compiles a `PATH` value into a fish list, since fish stores `PATH` as a list
+/// rather than a colon-separated string.
+fn quote_path(s: &str) -> String {
+    let entries: Vec<String> = s.split(':').map(quote).collect();
+    entries.join(" ")
+}
+
+impl Shell for Fish {
+    fn postscript_path(&self) -> Option<&Path> {
+        self.postscript_path.as_ref().map(|p| p.as_path())
+    }
+
+    fn compile_postscript(&self, postscript: &Postscript) -> String {
+        match postscript {
+            &Postscript::Deactivate(ref s) => format!(
+                "set -gx PATH {}\nset -e NOTION_HOME\n",
+                quote_path(s)
+            ),
+            &Postscript::Path(ref s) => format!("set -gx PATH {}\n", quote_path(s)),
+            &Postscript::ToolVersion {
+                ref tool,
+                ref version,
+            } => format!(
+                "set -gx NOTION_{}_VERSION {}\n",
+                tool.to_ascii_uppercase(),
+                version
+            ),
+            &Postscript::Var {
+                ref name,
+                ref value,
+            } => format!("set -gx {} {}\n", name, quote(value)),
+        }
+    }
+}
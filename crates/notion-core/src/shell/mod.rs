@@ -11,12 +11,20 @@ use notion_fail::{ExitCode, Fallible, NotionError, NotionFail, ResultExt};
 use env;
 
 mod bash;
+pub(crate) mod fish;
+pub(crate) mod nu;
+pub(crate) mod powershell;
 
 pub(crate) use self::bash::Bash;
+pub(crate) use self::fish::Fish;
+pub(crate) use self::nu::Nu;
+pub(crate) use self::powershell::PowerShell;
 
 pub enum Postscript {
     Deactivate(String),
+    Path(String),
     ToolVersion { tool: String, version: Version },
+    Var { name: String, value: String },
 }
 
 /// Thrown when the postscript file was not specified in the Notion environment.
@@ -26,13 +34,14 @@ pub enum Postscript {
 struct UnspecifiedPostscriptError;
 
 pub trait Shell {
-    fn postscript_path(&self) -> &Path;
+    fn postscript_path(&self) -> Option<&Path>;
 
     fn compile_postscript(&self, postscript: &Postscript) -> String;
 
     fn save_postscript(&self, postscript: &Postscript) -> Fallible<()> {
-        ensure_containing_dir_exists(&self.postscript_path())?;
-        let mut file = File::create(self.postscript_path()).unknown()?;
+        let postscript_path = self.postscript_path().ok_or(UnspecifiedPostscriptError)?;
+        ensure_containing_dir_exists(&postscript_path)?;
+        let mut file = File::create(postscript_path).unknown()?;
         file.write_all(self.compile_postscript(postscript).as_bytes())
             .unknown()?;
         Ok(())
@@ -59,7 +68,7 @@ impl CurrentShell {
 }
 
 impl Shell for CurrentShell {
-    fn postscript_path(&self) -> &Path {
+    fn postscript_path(&self) -> Option<&Path> {
         let &CurrentShell(ref shell) = self;
         shell.postscript_path()
     }
@@ -82,10 +91,13 @@ impl FromStr for CurrentShell {
     type Err = NotionError;
 
     fn from_str(src: &str) -> Result<Self, NotionError> {
-        let postscript_path = env::postscript_path().ok_or(UnspecifiedPostscriptError)?;
+        let postscript_path = env::postscript_path();
 
         Ok(CurrentShell(match src {
             "bash" => Box::new(Bash { postscript_path }),
+            "fish" => Box::new(Fish { postscript_path }),
+            "nu" => Box::new(Nu { postscript_path }),
+            "powershell" => Box::new(PowerShell { postscript_path }),
             _ => {
                 throw!(UnrecognizedShellError {
                     name: src.to_string()
@@ -119,6 +131,11 @@ pub mod tests {
             "export PATH='/path:/with:/single'quotes''\nunset NOTION_HOME\n"
         );
 
+        assert_eq!(
+            bash.compile_postscript(&Postscript::Path("/some/bin:/usr/bin".to_string())),
+            "export PATH='/some/bin:/usr/bin'\n"
+        );
+
         assert_eq!(
             bash.compile_postscript(&Postscript::ToolVersion {
                 tool: "test".to_string(),
@@ -126,5 +143,87 @@ pub mod tests {
             }),
             "export NOTION_TEST_VERSION=2.4.5\n"
         );
+
+        assert_eq!(
+            bash.compile_postscript(&Postscript::Var {
+                name: "NODE".to_string(),
+                value: "/some/bin/node".to_string()
+            }),
+            "export NODE='/some/bin/node'\n"
+        );
+    }
+
+    #[test]
+    fn test_compile_postscript_fish() {
+        let fish = CurrentShell::from_str("fish").expect("Could not create fish shell");
+
+        assert_eq!(
+            fish.compile_postscript(&Postscript::Path("/some/bin:/usr/bin".to_string())),
+            "set -gx PATH '/some/bin' '/usr/bin'\n"
+        );
+
+        assert_eq!(
+            fish.compile_postscript(&Postscript::Var {
+                name: "NODE".to_string(),
+                value: "/it's/node".to_string()
+            }),
+            "set -gx NODE '/it\\'s/node'\n"
+        );
+    }
+
+    #[test]
+    fn test_compile_postscript_nu() {
+        let nu = CurrentShell::from_str("nu").expect("Could not create nu shell");
+
+        if cfg!(unix) {
+            assert_eq!(
+                nu.compile_postscript(&Postscript::Deactivate("/some/bin:/usr/bin".to_string())),
+                "$env.PATH = [\"/some/bin\", \"/usr/bin\"]\n\
+                 hide-env --ignore-errors NOTION_HOME\n"
+            );
+        }
+
+        assert_eq!(
+            nu.compile_postscript(&Postscript::Var {
+                name: "NODE".to_string(),
+                value: "/the \"best\"/node".to_string()
+            }),
+            "$env.NODE = \"/the \\\"best\\\"/node\"\n"
+        );
+
+        assert_eq!(
+            nu.compile_postscript(&Postscript::ToolVersion {
+                tool: "node".to_string(),
+                version: Version::parse("8.9.4").unwrap()
+            }),
+            "$env.NOTION_NODE_VERSION = \"8.9.4\"\n"
+        );
+    }
+
+    #[test]
+    fn test_compile_postscript_powershell() {
+        let powershell =
+            CurrentShell::from_str("powershell").expect("Could not create powershell shell");
+
+        assert_eq!(
+            powershell.compile_postscript(&Postscript::Path("C:\\bin;D:\\bin".to_string())),
+            "Set-Item Env:PATH -Value 'C:\\bin;D:\\bin'\n"
+        );
+
+        assert_eq!(
+            powershell.compile_postscript(&Postscript::Var {
+                name: "NODE".to_string(),
+                value: "C:\\it's\\node.exe".to_string()
+            }),
+            "Set-Item Env:NODE -Value 'C:\\it''s\\node.exe'\n"
+        );
+
+        assert_eq!(
+            powershell.compile_postscript(&Postscript::ToolVersion {
+                tool: "node".to_string(),
+                version: Version::parse("8.9.4").unwrap()
+            }),
+            "Set-Item Env:NOTION_NODE_VERSION -Value '8.9.4'\n"
+        );
     }
 }
@@ -0,0 +1,49 @@
+use std::path::{Path, PathBuf};
+
+use super::{Postscript, Shell};
+
+pub(crate) struct Nu {
+    pub(crate) postscript_path: Option<PathBuf>,
+}
+
+/// Quotes a string for Nushell as a double-quoted string, which is also how NUON
+/// writes strings, so that the `notion` wrapper can read the value back.
+pub(crate) fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Compiles a `PATH` value into a Nushell list, since Nushell stores `PATH` as a
+/// list rather than a separated string.
+fn quote_path(s: &str) -> String {
+    let separator = if cfg!(windows) { ';' } else { ':' };
+    let entries: Vec<String> = s.split(separator).map(quote).collect();
+    format!("[{}]", entries.join(", "))
+}
+
+impl Shell for Nu {
+    fn postscript_path(&self) -> Option<&Path> {
+        self.postscript_path.as_ref().map(|p| p.as_path())
+    }
+
+    fn compile_postscript(&self, postscript: &Postscript) -> String {
+        match postscript {
+            &Postscript::Deactivate(ref s) => format!(
+                "$env.PATH = {}\nhide-env --ignore-errors NOTION_HOME\n",
+                quote_path(s)
+            ),
+            &Postscript::Path(ref s) => format!("$env.PATH = {}\n", quote_path(s)),
+            &Postscript::ToolVersion {
+                ref tool,
+                ref version,
+            } => format!(
+                "$env.NOTION_{}_VERSION = {}\n",
+                tool.to_ascii_uppercase(),
+                quote(&version.to_string())
+            ),
+            &Postscript::Var {
+                ref name,
+                ref value,
+            } => format!("$env.{} = {}\n", name, quote(value)),
+        }
+    }
+}
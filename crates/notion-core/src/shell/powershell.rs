@@ -0,0 +1,41 @@
+use std::path::{Path, PathBuf};
+
+use super::{Postscript, Shell};
+
+pub(crate) struct PowerShell {
+    pub(crate) postscript_path: Option<PathBuf>,
+}
+
+/// Quotes a string for PowerShell, where a `'` inside single quotes is written `''`.
+pub(crate) fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+impl Shell for PowerShell {
+    fn postscript_path(&self) -> Option<&Path> {
+        self.postscript_path.as_ref().map(|p| p.as_path())
+    }
+
+    fn compile_postscript(&self, postscript: &Postscript) -> String {
+        match postscript {
+            &Postscript::Deactivate(ref s) => format!(
+                "Set-Item Env:PATH -Value {}\n\
+                 Remove-Item Env:NOTION_HOME -ErrorAction SilentlyContinue\n",
+                quote(s)
+            ),
+            &Postscript::Path(ref s) => format!("Set-Item Env:PATH -Value {}\n", quote(s)),
+            &Postscript::ToolVersion {
+                ref tool,
+                ref version,
+            } => format!(
+                "Set-Item Env:NOTION_{}_VERSION -Value '{}'\n",
+                tool.to_ascii_uppercase(),
+                version
+            ),
+            &Postscript::Var {
+                ref name,
+                ref value,
+            } => format!("Set-Item Env:{} -Value {}\n", name, quote(value)),
+        }
+    }
+}
@@ -1,9 +1,48 @@
-//! Provides utilities for modifying shims for 3rd-party executables
+//! Provides utilities for modifying shims for 3rd-party executables, and for
+//! auditing all shims against the installed version of Notion.
+//!
+//! Shims for 3rd-party executables (and for npm and npx) are made from a shared
+//! launcher, by one of the strategies of `Strategy`: symlinks to it by default, or
+//! hardlinks or copies of it on filesystems where symlinks are unavailable or slow.
 
+use std::cmp;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsStr;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{read_to_string, write};
+use std::str::FromStr;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 use std::{fs, io};
 
-use notion_fail::{ExitCode, FailExt, Fallible, NotionFail};
+use config::LazyConfig;
+use notion_fail::{ExitCode, FailExt, Fallible, NotionError, NotionFail, ResultExt};
 use path;
+use plan::{Action, Plan};
+use semver::Version;
+use timing::{self, Phase};
+
+/// The prefix of the metadata string embedded in every shim executable.
+const METADATA_PREFIX: &'static str = "notion-shim-metadata:";
+
+/// Embeds metadata identifying the kind of shim executable and the version of Notion
+/// it was built with, so that `notion shim audit` can find shims left behind by an
+/// older Notion. This must be invoked once in each shim executable's crate root.
+#[macro_export]
+macro_rules! shim_metadata {
+    ($kind:expr) => {
+        #[used]
+        static SHIM_METADATA: &'static str = concat!(
+            "notion-shim-metadata:",
+            $kind,
+            ":",
+            env!("CARGO_PKG_VERSION"),
+            ";"
+        );
+    };
+}
 
 #[derive(Debug, Fail, NotionFail)]
 #[fail(display = "{}", error)]
@@ -26,6 +65,38 @@ impl SymlinkError {
     }
 }
 
+/// The executable that a shim created for an arbitrary executable runs, rather than
+/// a project's or a package tool's executable of the same name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    /// The absolute path of the executable.
+    pub file: PathBuf,
+    /// The version of Node the executable runs with, if one was requested. Otherwise
+    /// it runs with the current platform, if there is one.
+    pub node: Option<Version>,
+    /// The command that runs the executable, if it is a script that needs one, like
+    /// `node` or `node --loader tsx`. The executable is passed to it after its own
+    /// arguments. Scripts run this way don't depend on shebang support, which Windows
+    /// lacks.
+    pub interpreter: Vec<String>,
+}
+
+/// Chooses the interpreter for a target from its extension: Node for JavaScript, and
+/// Node with the `tsx` loader for TypeScript. Other targets run directly.
+pub fn default_interpreter(file: &Path) -> Vec<String> {
+    let extension = file.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+    let interpreter: &[&str] = match extension.as_ref().map(|ext| &ext[..]) {
+        Some("js") | Some("mjs") | Some("cjs") => &["node"],
+        Some("ts") | Some("mts") | Some("cts") => &["node", "--loader", "tsx"],
+        _ => &[],
+    };
+    interpreter.iter().map(|arg| arg.to_string()).collect()
+}
+
+/// The most threads `create_all` creates shims on, which is enough to keep a disk busy
+/// without starting hundreds of threads for a large monorepo.
+const MAX_CREATE_THREADS: usize = 8;
+
 #[derive(PartialEq)]
 pub enum ShimResult {
     Created,
@@ -34,17 +105,97 @@ pub enum ShimResult {
     DoesntExist,
 }
 
-fn is_3p_shim(name: &str) -> bool {
+/// Returns true if the named shim is for a 3rd-party executable rather than a
+/// Notion-managed tool.
+pub fn is_3p_shim(name: &str) -> bool {
     match name {
         "node" | "yarn" | "npm" | "npx" => false,
         _ => true,
     }
 }
 
+/// How shims are made from the launcher they dispatch through.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    /// A symlink to the launcher.
+    Symlink,
+    /// A hardlink to the launcher, which shares its contents without a link to follow.
+    Hardlink,
+    /// A copy of the launcher, for filesystems that support neither kind of link.
+    Copy,
+}
+
+/// Thrown when the `strategy` setting of the `[shim]` section is not recognized.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Invalid shim.strategy setting '{}' (expected symlink, hardlink, or copy)",
+       value)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct StrategyError {
+    value: String,
+}
+
+impl FromStr for Strategy {
+    type Err = NotionError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        match src {
+            "symlink" => Ok(Strategy::Symlink),
+            "hardlink" => Ok(Strategy::Hardlink),
+            "copy" => Ok(Strategy::Copy),
+            _ => throw!(StrategyError {
+                value: src.to_string(),
+            }),
+        }
+    }
+}
+
+impl Display for Strategy {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(match self {
+            &Strategy::Symlink => "symlink",
+            &Strategy::Hardlink => "hardlink",
+            &Strategy::Copy => "copy",
+        })
+    }
+}
+
+impl Strategy {
+    /// The strategy selected by the `shim.strategy` setting.
+    pub fn configured() -> Fallible<Strategy> {
+        Ok(LazyConfig::new().get()?.shim_strategy())
+    }
+
+    /// Produces the launcher file to make `shim` from: for a symlink, the path it
+    /// links to (which is relative in a portable installation).
+    fn source(&self, launcher_file: PathBuf, shim: &Path) -> Fallible<PathBuf> {
+        match self {
+            &Strategy::Symlink => path::link_target(&launcher_file, shim),
+            _ => Ok(launcher_file),
+        }
+    }
+
+    /// Makes `shim` from the launcher file, failing with `AlreadyExists` if there is
+    /// already a file at `shim`, whatever the strategy.
+    fn make(&self, launcher_file: PathBuf, shim: PathBuf) -> io::Result<()> {
+        match self {
+            &Strategy::Symlink => path::create_file_symlink(launcher_file, shim),
+            &Strategy::Hardlink => fs::hard_link(launcher_file, shim),
+            &Strategy::Copy => {
+                if fs::symlink_metadata(&shim).is_ok() {
+                    return Err(io::Error::from(io::ErrorKind::AlreadyExists));
+                }
+                fs::copy(launcher_file, shim).map(|_| ())
+            }
+        }
+    }
+}
+
 pub fn create(shim_name: &str) -> Fallible<ShimResult> {
     let launchbin = path::launchbin_file()?;
     let shim = path::shim_file(shim_name)?;
-    match path::create_file_symlink(launchbin, shim) {
+    let strategy = Strategy::configured()?;
+    let launchbin = strategy.source(launchbin, &shim)?;
+    match timing::time(Phase::ShimUpdate, || strategy.make(launchbin, shim)) {
         Ok(_) => Ok(ShimResult::Created),
         Err(err) => {
             if err.kind() == io::ErrorKind::AlreadyExists {
@@ -56,6 +207,53 @@ pub fn create(shim_name: &str) -> Fallible<ShimResult> {
     }
 }
 
+/// Creates the shims for many binaries at once, on a pool of at most
+/// `MAX_CREATE_THREADS` threads, and returns the result for each binary in order of
+/// name.
+pub fn create_all(names: Vec<String>) -> Vec<(String, Fallible<ShimResult>)> {
+    let threads = cmp::min(names.len(), MAX_CREATE_THREADS);
+    if threads <= 1 {
+        return names
+            .into_iter()
+            .map(|name| {
+                let result = create(&name);
+                (name, result)
+            })
+            .collect();
+    }
+
+    // the phases timed on the workers are lost with their threads, so the whole pool is
+    // timed here instead
+    timing::time(Phase::ShimUpdate, || {
+        let queue = Arc::new(Mutex::new(names.into_iter()));
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..threads {
+            let queue = queue.clone();
+            let sender = sender.clone();
+            thread::spawn(move || loop {
+                // a worker that panicked while holding the lock only held the queue
+                let next = match queue.lock() {
+                    Ok(mut names) => names.next(),
+                    Err(poisoned) => poisoned.into_inner().next(),
+                };
+                let name = match next {
+                    Some(name) => name,
+                    None => break,
+                };
+                let result = create(&name);
+                if sender.send((name, result)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        let mut results: Vec<(String, Fallible<ShimResult>)> = receiver.iter().collect();
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+        results
+    })
+}
+
 pub fn delete(shim_name: &str) -> Fallible<ShimResult> {
     if !is_3p_shim(shim_name) {
         throw!(SymlinkError {
@@ -74,3 +272,495 @@ pub fn delete(shim_name: &str) -> Fallible<ShimResult> {
         }
     }
 }
+
+/// Lists the names of the 3rd-party shims in the shim directory, which are the ones
+/// that can be deleted, in order.
+pub fn names_3p() -> Fallible<Vec<String>> {
+    let shim_dir = path::shim_dir()?;
+    if !shim_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut names = Vec::new();
+    for entry in fs::read_dir(shim_dir).unknown()? {
+        let name = shim_name(&entry.unknown()?);
+        if is_3p_shim(&name) {
+            names.push(name);
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// The executables on the `PATH` that a shim shadows.
+#[derive(Debug)]
+pub struct Conflict {
+    pub name: String,
+    pub shadowed: Vec<PathBuf>,
+}
+
+/// Finds the executables on the `PATH` that the named shim shadows, or would shadow
+/// if it were created: those with the same name outside the shim directory.
+pub fn conflicts(shim_name: &str) -> Fallible<Vec<PathBuf>> {
+    let shim_dir = path::shim_dir()?;
+    let path_var = env::var_os("PATH").unwrap_or_default();
+    Ok(path::find_executables(OsStr::new(shim_name), &path_var)
+        .into_iter()
+        .filter(|file| file.parent().map_or(true, |dir| !is_same_dir(dir, &shim_dir)))
+        .collect())
+}
+
+/// Finds the 3rd-party shims that shadow executables on the `PATH`, in order. Shims
+/// for Notion's own tools are left out, since shadowing the system's Node is the
+/// point of them.
+pub fn conflicts_3p() -> Fallible<Vec<Conflict>> {
+    let mut all = Vec::new();
+    for name in names_3p()? {
+        let shadowed = conflicts(&name)?;
+        if !shadowed.is_empty() {
+            all.push(Conflict { name, shadowed });
+        }
+    }
+    Ok(all)
+}
+
+fn is_same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Plans creating the named shim, without touching the filesystem.
+pub fn plan_create(shim_name: &str) -> Fallible<Plan> {
+    let mut plan = Plan::new();
+    let shim = path::shim_file(shim_name)?;
+    if fs::symlink_metadata(&shim).is_err() {
+        plan.push(Action::Write(shim));
+    }
+    Ok(plan)
+}
+
+/// Plans deleting the named shim, without touching the filesystem.
+pub fn plan_delete(shim_name: &str) -> Fallible<Plan> {
+    if !is_3p_shim(shim_name) {
+        throw!(SymlinkError {
+            error: format!("cannot delete `{}`, not a 3rd-party executable", shim_name),
+        });
+    }
+    let mut plan = Plan::new();
+    let shim = path::shim_file(shim_name)?;
+    if fs::symlink_metadata(&shim).is_ok() {
+        plan.push(Action::Remove(shim));
+    }
+    Ok(plan)
+}
+
+/// The executables that shims dispatch through.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Launcher {
+    Node,
+    Yarn,
+    Script,
+    Binary,
+}
+
+impl Launcher {
+    /// The launcher that the named shim should dispatch through.
+    pub fn for_shim(shim_name: &str) -> Self {
+        match shim_name {
+            "node" => Launcher::Node,
+            "yarn" => Launcher::Yarn,
+            "npm" | "npx" => Launcher::Script,
+            _ => Launcher::Binary,
+        }
+    }
+
+    /// The kind recorded in this launcher's embedded metadata.
+    fn kind(&self) -> &'static str {
+        match self {
+            &Launcher::Node => "node",
+            &Launcher::Yarn => "yarn",
+            &Launcher::Script => "launchscript",
+            &Launcher::Binary => "launchbin",
+        }
+    }
+
+    fn from_kind(kind: &str) -> Option<Self> {
+        match kind {
+            "node" => Some(Launcher::Node),
+            "yarn" => Some(Launcher::Yarn),
+            "launchscript" => Some(Launcher::Script),
+            "launchbin" => Some(Launcher::Binary),
+            _ => None,
+        }
+    }
+
+    /// The shared launcher file this kind of shim is a link to, if shims of this kind
+    /// are links rather than standalone executables.
+    fn file(&self) -> Fallible<Option<PathBuf>> {
+        Ok(match self {
+            &Launcher::Script => Some(path::launchscript_file()?),
+            &Launcher::Binary => Some(path::launchbin_file()?),
+            _ => None,
+        })
+    }
+}
+
+impl Display for Launcher {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(self.kind())
+    }
+}
+
+/// The metadata embedded in a shim executable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metadata {
+    pub launcher: Launcher,
+    pub version: String,
+}
+
+/// A problem found with a shim by `audit`.
+#[derive(Debug, PartialEq)]
+pub enum Mismatch {
+    /// The shim links to something other than its launcher.
+    WrongTarget { expected: PathBuf, actual: PathBuf },
+    /// The shim links to a file that no longer exists.
+    MissingTarget(PathBuf),
+    /// The shim has no embedded metadata, so it predates shim auditing.
+    NoMetadata,
+    /// The shim is a different kind of executable than its name calls for.
+    WrongLauncher { expected: Launcher, actual: Launcher },
+    /// The shim was built with a different version of Notion.
+    Version { found: String, expected: String },
+}
+
+impl Mismatch {
+    /// Returns true if `repair` can fix this mismatch. Standalone shim executables can
+    /// only be replaced by reinstalling Notion.
+    pub fn is_repairable(&self) -> bool {
+        match self {
+            &Mismatch::WrongTarget { .. } | &Mismatch::MissingTarget(_) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match self {
+            &Mismatch::WrongTarget {
+                ref expected,
+                ref actual,
+            } => write!(
+                f,
+                "links to {} instead of {}",
+                actual.display(),
+                expected.display()
+            ),
+            &Mismatch::MissingTarget(ref target) => {
+                write!(f, "links to {}, which does not exist", target.display())
+            }
+            &Mismatch::NoMetadata => f.write_str("has no version metadata (built by an older Notion)"),
+            &Mismatch::WrongLauncher {
+                ref expected,
+                ref actual,
+            } => write!(f, "is a {} executable instead of {}", actual, expected),
+            &Mismatch::Version {
+                ref found,
+                ref expected,
+            } => write!(f, "was built by Notion {} (expected {})", found, expected),
+        }
+    }
+}
+
+/// The audit result for a single shim.
+pub struct Audit {
+    pub name: String,
+    pub mismatch: Option<Mismatch>,
+}
+
+/// Audits every shim in the shim directory against the running version of Notion.
+pub fn audit_all(version: &str) -> Fallible<Vec<Audit>> {
+    let mut cache = HashMap::new();
+    let mut audits = Vec::new();
+
+    for entry in fs::read_dir(path::shim_dir()?).unknown()? {
+        let name = shim_name(&entry.unknown()?);
+        let mismatch = audit_with_cache(&name, version, &mut cache)?;
+        audits.push(Audit { name, mismatch });
+    }
+
+    audits.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(audits)
+}
+
+/// Audits the named shim against the running version of Notion.
+pub fn audit(shim_name: &str, version: &str) -> Fallible<Option<Mismatch>> {
+    audit_with_cache(shim_name, version, &mut HashMap::new())
+}
+
+fn audit_with_cache(
+    shim_name: &str,
+    version: &str,
+    cache: &mut HashMap<PathBuf, Option<Metadata>>,
+) -> Fallible<Option<Mismatch>> {
+    let shim = path::shim_file(shim_name)?;
+    let expected = Launcher::for_shim(shim_name);
+
+    let file_type = fs::symlink_metadata(&shim).unknown()?.file_type();
+    if file_type.is_symlink() {
+        let target = fs::read_link(&shim).unknown()?;
+        if let Some(launcher_file) = expected.file()? {
+            // shims in a portable installation link relatively, and are repaired if not
+            let launcher_file = path::link_target(&launcher_file, &shim)?;
+            if target != launcher_file {
+                return Ok(Some(Mismatch::WrongTarget {
+                    expected: launcher_file,
+                    actual: target,
+                }));
+            }
+        }
+        if !shim.exists() {
+            return Ok(Some(Mismatch::MissingTarget(target)));
+        }
+    }
+
+    let resolved = fs::canonicalize(&shim).unknown()?;
+    if !cache.contains_key(&resolved) {
+        let metadata = read_metadata(&resolved)?;
+        cache.insert(resolved.clone(), metadata);
+    }
+
+    Ok(match cache[&resolved] {
+        None => Some(Mismatch::NoMetadata),
+        Some(ref metadata) if metadata.launcher != expected => Some(Mismatch::WrongLauncher {
+            expected,
+            actual: metadata.launcher,
+        }),
+        Some(ref metadata) if metadata.version != version => Some(Mismatch::Version {
+            found: metadata.version.clone(),
+            expected: version.to_string(),
+        }),
+        Some(_) => None,
+    })
+}
+
+/// Reads the metadata embedded in a shim executable, if any.
+pub fn read_metadata(file: &Path) -> Fallible<Option<Metadata>> {
+    let contents = fs::read(file).unknown()?;
+    Ok(find_metadata(&contents))
+}
+
+/// Finds the embedded metadata in the contents of a shim executable. Other occurrences of
+/// the prefix (such as this module's own copy of it) are skipped over.
+fn find_metadata(contents: &[u8]) -> Option<Metadata> {
+    let prefix = METADATA_PREFIX.as_bytes();
+    let mut start = 0;
+
+    while let Some(offset) = contents[start..]
+        .windows(prefix.len())
+        .position(|window| window == prefix)
+    {
+        let rest = &contents[start + offset + prefix.len()..];
+        if let Some(metadata) = parse_metadata(rest) {
+            return Some(metadata);
+        }
+        start += offset + 1;
+    }
+
+    None
+}
+
+/// Parses `<kind>:<version>;` from the start of the input.
+fn parse_metadata(input: &[u8]) -> Option<Metadata> {
+    let end = input.iter().take(64).position(|&b| b == b';')?;
+    let text = ::std::str::from_utf8(&input[..end]).ok()?;
+    let mut parts = text.splitn(2, ':');
+    let launcher = Launcher::from_kind(parts.next()?)?;
+    let version = parts.next()?;
+
+    let valid = !version.is_empty()
+        && version
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '+');
+    if !valid {
+        return None;
+    }
+
+    Some(Metadata {
+        launcher,
+        version: version.to_string(),
+    })
+}
+
+/// Repairs a shim whose link is wrong or broken by pointing it back at its launcher.
+/// Returns false if the mismatch cannot be repaired this way.
+pub fn repair(shim_name: &str, mismatch: &Mismatch) -> Fallible<bool> {
+    if !mismatch.is_repairable() {
+        return Ok(false);
+    }
+
+    match Launcher::for_shim(shim_name).file()? {
+        Some(launcher_file) => {
+            relink(shim_name, launcher_file)?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Points the named shim at a launcher file, making it with the configured strategy.
+fn relink(shim_name: &str, launcher_file: PathBuf) -> Fallible<()> {
+    relink_with(shim_name, launcher_file, Strategy::configured()?)
+}
+
+/// Points the named shim at a launcher file. The new shim is made alongside the old
+/// one and renamed over it, so that the shim never goes missing while it is replaced.
+fn relink_with(shim_name: &str, launcher_file: PathBuf, strategy: Strategy) -> Fallible<()> {
+    let shim = path::shim_file(shim_name)?;
+    let temp = path::shim_dir()?.join(format!(".{}.relink", shim_name));
+    let _ = fs::remove_file(&temp);
+    let launcher_file = strategy.source(launcher_file, &temp)?;
+    strategy
+        .make(launcher_file, temp.clone())
+        .with_context(SymlinkError::from_io_error)?;
+    fs::rename(&temp, &shim).with_context(SymlinkError::from_io_error)?;
+    Ok(())
+}
+
+/// Regenerates every shim if the shims were last generated by a different version of
+/// Notion than `version`, and records `version` as the one that generated them. Returns
+/// true if the shims were regenerated.
+pub fn regenerate_if_outdated(version: &str) -> Fallible<bool> {
+    if !path::shim_dir()?.is_dir() || !is_outdated(version)? {
+        return Ok(false);
+    }
+
+    regenerate_all()?;
+    write(&path::shim_version_file()?, version).unknown()?;
+    Ok(true)
+}
+
+/// Returns true if the shims were last generated by a different version of Notion than
+/// `version`, or it isn't recorded which version generated them.
+pub fn is_outdated(version: &str) -> Fallible<bool> {
+    Ok(match read_to_string(&path::shim_version_file()?) {
+        Ok(recorded) => recorded.trim() != version,
+        Err(_) => true,
+    })
+}
+
+/// Regenerates every shim made from a launcher, so that each one is made from the
+/// launcher of the installed Notion. Standalone shim executables (such as `node` and
+/// `yarn`) are replaced by the Notion installer itself and are left alone.
+pub fn regenerate_all() -> Fallible<()> {
+    let strategy = Strategy::configured()?;
+    timing::time(Phase::ShimUpdate, || remake_all(strategy)).map(|_| ())
+}
+
+/// Remakes every shim made from a launcher with the given strategy, converting the
+/// shim directory from whichever strategies its shims were made with. Returns the
+/// names of the shims that were remade, in order.
+pub fn migrate(strategy: Strategy) -> Fallible<Vec<String>> {
+    timing::time(Phase::ShimUpdate, || remake_all(strategy))
+}
+
+/// Plans converting the shim directory to another strategy, without touching the
+/// filesystem.
+pub fn plan_migrate() -> Fallible<Plan> {
+    let mut plan = Plan::new();
+    for (name, _) in launcher_shims()? {
+        plan.push(Action::Write(path::shim_file(&name)?));
+    }
+    Ok(plan)
+}
+
+fn remake_all(strategy: Strategy) -> Fallible<Vec<String>> {
+    let mut remade = Vec::new();
+    for (name, launcher_file) in launcher_shims()? {
+        relink_with(&name, launcher_file, strategy)?;
+        remade.push(name);
+    }
+    Ok(remade)
+}
+
+/// Lists the shims in the shim directory that are made from a launcher file, along
+/// with that file, in order.
+fn launcher_shims() -> Fallible<Vec<(String, PathBuf)>> {
+    let mut shims = Vec::new();
+    for entry in fs::read_dir(path::shim_dir()?).unknown()? {
+        let entry = entry.unknown()?;
+        let name = shim_name(&entry);
+        // leftovers of an interrupted relink are not shims
+        if name.starts_with('.') {
+            continue;
+        }
+        if let Some(launcher_file) = Launcher::for_shim(&name).file()? {
+            shims.push((name, launcher_file));
+        }
+    }
+    shims.sort();
+    Ok(shims)
+}
+
+/// The name of the shim for an entry in the shim directory.
+#[cfg(unix)]
+fn shim_name(entry: &fs::DirEntry) -> String {
+    entry.file_name().to_string_lossy().to_string()
+}
+
+/// The name of the shim for an entry in the shim directory, without the `.exe`
+/// extension that `path::shim_file` adds.
+#[cfg(windows)]
+fn shim_name(entry: &fs::DirEntry) -> String {
+    let path = entry.path();
+    let is_exe = path
+        .extension()
+        .map_or(false, |ext| ext.to_string_lossy().eq_ignore_ascii_case("exe"));
+    match path.file_stem() {
+        Some(stem) if is_exe => stem.to_string_lossy().to_string(),
+        _ => entry.file_name().to_string_lossy().to_string(),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{default_interpreter, find_metadata, Launcher, Metadata};
+    use std::path::Path;
+
+    #[test]
+    fn test_find_metadata() {
+        let contents = b"\x7fELF...notion-shim-metadata:\x00\x01...notion-shim-metadata:launchbin:0.1.4;...";
+        assert_eq!(
+            find_metadata(contents),
+            Some(Metadata {
+                launcher: Launcher::Binary,
+                version: "0.1.4".to_string(),
+            })
+        );
+
+        assert_eq!(find_metadata(b"\x7fELF...no metadata here"), None);
+        assert_eq!(find_metadata(b"notion-shim-metadata:cargo:0.1.4;"), None);
+    }
+
+    #[test]
+    fn test_default_interpreter() {
+        assert_eq!(default_interpreter(Path::new("/opt/tool/cli.js")), vec!["node"]);
+        assert_eq!(default_interpreter(Path::new("/opt/tool/cli.MJS")), vec!["node"]);
+        assert_eq!(
+            default_interpreter(Path::new("/opt/tool/cli.ts")),
+            vec!["node", "--loader", "tsx"]
+        );
+        assert!(default_interpreter(Path::new("/opt/tool/bin/tool")).is_empty());
+        assert!(default_interpreter(Path::new("/opt/tool/tool.exe")).is_empty());
+    }
+
+    #[test]
+    fn test_launcher_for_shim() {
+        assert_eq!(Launcher::for_shim("node"), Launcher::Node);
+        assert_eq!(Launcher::for_shim("npm"), Launcher::Script);
+        assert_eq!(Launcher::for_shim("eslint"), Launcher::Binary);
+    }
+}
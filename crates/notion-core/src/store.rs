@@ -0,0 +1,243 @@
+//! Provides the content-addressed store that the files of installed tool versions
+//! live in.
+//!
+//! Each file is stored once, as an object named by the SHA-256 digest of its
+//! contents (and, on Unix, its permissions). An installed version is a *link farm*:
+//! a copy of the version's directory tree whose files are hardlinks to objects, so
+//! files shared by several versions only take up space once. The usual version
+//! directory (e.g. `versions/node/8.6.0`) is a symlink to the link farm, so that
+//! everything that expects the existing layout keeps working.
+
+use std::fs::{self, File, Metadata};
+use std::path::Path;
+
+use checksum::sha256_hex;
+use fs::{collect_regular_files, ensure_containing_dir_exists};
+use notion_fail::{Fallible, ResultExt};
+use path;
+use verify::{manifest_entries, write_manifest_entries};
+
+/// The name a link farm being replaced is moved to within a staging directory.
+const REPLACED_FARM: &'static str = "replaced-farm";
+
+/// The name a version directory being replaced is moved to within a staging directory.
+const REPLACED_DIR: &'static str = "replaced";
+
+/// Installs a directory tree that has been unpacked into `staging` as a version.
+/// Its files are moved into the store, the tree becomes a link farm at `farm`, a
+/// manifest of its files is recorded at `manifest`, and `dest` is pointed at the
+/// farm. Anything already at `farm` or `dest` is moved into `staging`, to be removed
+/// along with it.
+///
+/// Objects are added to the store with atomic renames, and the farm and version
+/// directory are only replaced once they are complete, so concurrent installs of
+/// versions that share files never see a partially written object.
+pub(crate) fn install(
+    unpacked: &Path,
+    staging: &Path,
+    farm: &Path,
+    dest: &Path,
+    manifest: &Path,
+) -> Fallible<()> {
+    let entries = manifest_entries(unpacked)?;
+    add_tree(unpacked, &entries)?;
+    write_manifest_entries(&entries, manifest)?;
+
+    if fs::symlink_metadata(farm).is_ok() {
+        fs::rename(farm, staging.join(REPLACED_FARM)).unknown()?;
+    }
+    ensure_containing_dir_exists(&farm)?;
+    fs::rename(unpacked, farm).unknown()?;
+
+    link_version_dir(farm, dest, staging)
+}
+
+/// Points a version directory at its link farm. A symlink already there is replaced
+/// atomically; a plain directory (from before the store existed) is moved aside
+/// into `staging` first.
+fn link_version_dir(farm: &Path, dest: &Path, staging: &Path) -> Fallible<()> {
+    ensure_containing_dir_exists(&dest)?;
+
+    let name = dest
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let temp = dest.with_file_name(format!(".{}.notion-link", name));
+    let _ = fs::remove_file(&temp);
+    path::create_dir_symlink(path::link_target(farm, &temp)?, temp.clone()).unknown()?;
+
+    if let Ok(metadata) = fs::symlink_metadata(dest) {
+        if metadata.is_dir() {
+            fs::rename(dest, staging.join(REPLACED_DIR)).unknown()?;
+        }
+    }
+    fs::rename(&temp, dest).unknown()?;
+    Ok(())
+}
+
+/// Moves every file listed in the manifest entries of a directory tree into the
+/// store, replacing each with a hardlink to the stored object.
+fn add_tree(dir: &Path, entries: &[(String, String)]) -> Fallible<()> {
+    for &(ref digest, ref relative) in entries {
+        let file = dir.join(relative);
+        let metadata = fs::metadata(&file).unknown()?;
+        let object = path::store_object_file(&object_name(digest, &metadata))?;
+        add_object(&file, &object, digest)?;
+    }
+    Ok(())
+}
+
+/// Stores a file as an object and replaces the file with a hardlink to it. If the
+/// object is already stored (by another version), the file is simply linked to it,
+/// unless the stored object no longer matches its digest, in which case the file
+/// takes its place.
+fn add_object(file: &Path, object: &Path, digest: &str) -> Fallible<()> {
+    let intact = object.is_file() && sha256_hex(&mut File::open(object).unknown()?)? == digest;
+
+    if !intact {
+        ensure_containing_dir_exists(&object)?;
+        fs::rename(file, object).unknown()?;
+    } else {
+        fs::remove_file(file).unknown()?;
+    }
+    fs::hard_link(object, file).unknown()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn object_name(digest: &str, metadata: &Metadata) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    // Hardlinks share their permissions, so files that differ only in permissions
+    // (such as an executable and a copy of it that is not) are stored separately.
+    format!("{}-{:o}", digest, metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(windows)]
+fn object_name(digest: &str, _metadata: &Metadata) -> String {
+    digest.to_string()
+}
+
+/// Removes an installed version: the symlink (or, for versions installed before the
+/// store existed, the directory) at `dest`, and its link farm. Objects that no other
+/// version links to are then removed from the store.
+pub(crate) fn remove(dest: &Path, farm: &Path) -> Fallible<()> {
+    if let Ok(metadata) = fs::symlink_metadata(dest) {
+        if metadata.is_dir() {
+            fs::remove_dir_all(dest).unknown()?;
+        } else {
+            remove_link(dest)?;
+        }
+    }
+    if farm.is_dir() {
+        fs::remove_dir_all(farm).unknown()?;
+    }
+    collect_garbage()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn remove_link(link: &Path) -> Fallible<()> {
+    fs::remove_file(link).unknown()
+}
+
+// Directory symlinks are removed like directories on Windows.
+#[cfg(windows)]
+fn remove_link(link: &Path) -> Fallible<()> {
+    fs::remove_dir(link).unknown()
+}
+
+/// Removes the objects in the store that no installed version links to, and
+/// produces the number of bytes freed.
+#[cfg(unix)]
+pub fn collect_garbage() -> Fallible<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    let objects_dir = path::store_objects_dir()?;
+    if !objects_dir.is_dir() {
+        return Ok(0);
+    }
+
+    let mut objects = Vec::new();
+    collect_regular_files(&objects_dir, &mut objects)?;
+
+    let mut freed = 0;
+    for object in objects {
+        let metadata = fs::metadata(&object).unknown()?;
+        if metadata.nlink() == 1 {
+            fs::remove_file(&object).unknown()?;
+            freed += metadata.len();
+        }
+    }
+    Ok(freed)
+}
+
+// NOTE: The link count of a file is not yet available from the standard library on
+//       Windows, so objects are kept until the store is cleared.
+#[cfg(windows)]
+pub fn collect_garbage() -> Fallible<u64> {
+    Ok(0)
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::add_object;
+    use checksum::sha256_hex;
+    use std::fs::{create_dir_all, read_to_string, File};
+    use std::io::Write;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn write_file(file: &Path, contents: &str) {
+        create_dir_all(file.parent().unwrap()).unwrap();
+        File::create(file).unwrap().write_all(contents.as_bytes()).unwrap();
+    }
+
+    fn digest(contents: &str) -> String {
+        sha256_hex(&mut contents.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_add_object() {
+        let root = tempdir().expect("Could not create temporary directory");
+        let object = root.path().join("objects").join("ab").join("cdef");
+        let first = root.path().join("8.0.0").join("node.h");
+        let second = root.path().join("8.1.0").join("node.h");
+        write_file(&first, "same header");
+        write_file(&second, "same header");
+
+        add_object(&first, &object, &digest("same header")).unwrap();
+        add_object(&second, &object, &digest("same header")).unwrap();
+        assert_eq!(read_to_string(&object).unwrap(), "same header");
+        assert_eq!(read_to_string(&second).unwrap(), "same header");
+
+        // A damaged object is replaced rather than linked to.
+        write_file(&object, "damaged");
+        let third = root.path().join("8.2.0").join("node.h");
+        write_file(&third, "same header");
+        add_object(&third, &object, &digest("same header")).unwrap();
+        assert_eq!(read_to_string(&object).unwrap(), "same header");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_add_object_links() {
+        use std::os::unix::fs::MetadataExt;
+
+        let root = tempdir().expect("Could not create temporary directory");
+        let object = root.path().join("objects").join("ab").join("cdef");
+        let first = root.path().join("8.0.0").join("node.h");
+        let second = root.path().join("8.1.0").join("node.h");
+        write_file(&first, "same header");
+        write_file(&second, "same header");
+
+        add_object(&first, &object, &digest("same header")).unwrap();
+        add_object(&second, &object, &digest("same header")).unwrap();
+        assert_eq!(object.metadata().unwrap().nlink(), 3);
+        assert_eq!(
+            first.metadata().unwrap().ino(),
+            second.metadata().unwrap().ino()
+        );
+    }
+}
@@ -1,13 +1,22 @@
 //! The view layer of Notion, with utilities for styling command-line output.
 
 use std::env;
+use std::cmp;
 use std::fmt::{self, Display, Formatter};
+use std::ops::Deref;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use console::style;
+use console::{self, style};
 use failure::Fail;
 use indicatif::{ProgressBar, ProgressStyle};
 use term_size;
 
+use env as notion_env;
+use locale::{message, Message};
+use notion_fail::{ExitCode, NotionError, NotionFail};
+
 /// Represents the context from which an error is being reported.
 pub enum ErrorContext {
     /// An error reported from the `notion` executable.
@@ -29,14 +38,14 @@ pub fn display_error_prefix(cx: ErrorContext) {
         ErrorContext::Notion => {
             // Since the command here was `notion`, it would be redundant to say that this was
             // a Notion error, so we are less explicit in the heading.
-            eprint!("{} ", style("error:").red().bold());
+            eprint!("{} ", style(message(Message::ErrorPrefix)).red().bold());
         }
         ErrorContext::Shim => {
             // Since a Notion error is rare case for a shim, it can be surprising to a user.
             // To make it extra clear that this was a failure that happened in Notion when
             // attempting to delegate to a shim, we are more explicit about the fact that it's
             // a Notion error.
-            eprint!("{} ", style("Notion error:").red().bold());
+            eprint!("{} ", style(message(Message::ShimErrorPrefix)).red().bold());
         }
     }
 }
@@ -44,11 +53,11 @@ pub fn display_error_prefix(cx: ErrorContext) {
 /// Displays a generic message for internal errors to stderr.
 pub fn display_unknown_error<E: Fail>(cx: ErrorContext, err: &E) {
     display_error_prefix(cx);
-    eprintln!("an internal error occurred");
+    eprintln!("{}", message(Message::InternalError));
     eprintln!();
 
     if env::var("NOTION_DEV").is_ok() {
-        eprintln!("{} {:?}", style("details:").yellow().bold(), err);
+        eprintln!("{} {:?}", style(message(Message::Details)).yellow().bold(), err);
         eprintln!();
 
         let backtrace = err.backtrace();
@@ -59,16 +68,11 @@ pub fn display_unknown_error<E: Fail>(cx: ErrorContext, err: &E) {
         if backtrace.is_some() && env::var("RUST_BACKTRACE").is_ok() {
             eprintln!("{:?}", backtrace.unwrap());
         } else {
-            eprintln!("Run with NOTION_DEV=1 and RUST_BACKTRACE=1 for a backtrace.");
+            eprintln!("{}", message(Message::BacktraceHint));
         }
     } else {
-        eprintln!("Notion is still a pre-alpha project, so we expect to run into some bugs,");
-        eprintln!("but we'd love to hear about them so we can fix them!");
-        eprintln!();
-        eprintln!(
-            "Please feel free to reach out to us at {} on Twitter or file an issue at:",
-            style("@notionjs").cyan().bold()
-        );
+        let handle = style("@notionjs").cyan().bold().to_string();
+        eprintln!("{}", message(Message::ReportBug).replace("{}", &handle));
         eprintln!();
         eprintln!(
             "    {}",
@@ -78,68 +82,377 @@ pub fn display_unknown_error<E: Fail>(cx: ErrorContext, err: &E) {
     }
 }
 
+/// Switches to CI mode if `force` is set (by `notion --ci`) or the environment asks for
+/// it (see `env::ci`), returning true if CI mode is on. In CI mode progress is reported
+/// as timestamped lines rather than progress bars and spinners, and colors are off
+/// unless they are asked for (see `init_colors`). `NOTION_CI` is set so that shims run
+/// by child processes are in CI mode too.
+pub fn init_ci_mode(force: bool) -> bool {
+    if force {
+        env::set_var("NOTION_CI", "1");
+    }
+    notion_env::ci()
+}
+
+/// When output is styled with colors, set by `notion --color` or the `color` setting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    /// Colors are used when the console supports them, unless `NO_COLOR` is set or
+    /// Notion is in CI mode.
+    Auto,
+    /// Colors are always used.
+    Always,
+    /// Colors are never used.
+    Never,
+}
+
+/// Thrown when a color setting is not recognized.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Invalid color setting '{}' (expected auto, always, or never)", value)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct ColorChoiceError {
+    value: String,
+}
+
+impl FromStr for ColorChoice {
+    type Err = NotionError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        match src {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => throw!(ColorChoiceError {
+                value: src.to_string(),
+            }),
+        }
+    }
+}
+
+/// Turns colors on or off for the rest of the process. Without a choice, colors are
+/// chosen automatically (see `ColorChoice::Auto`). This should run after `init_ci_mode`.
+pub fn init_colors(choice: Option<ColorChoice>) {
+    let enabled = match choice.unwrap_or(ColorChoice::Auto) {
+        ColorChoice::Auto => auto_colors(),
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    };
+    console::set_colors_enabled(enabled);
+}
+
+// `console` already turns colors off when stdout isn't a terminal, and honors
+// `CLICOLOR`; `NO_COLOR` is described at https://no-color.org
+fn auto_colors() -> bool {
+    let no_color = env::var_os("NO_COLOR").map_or(false, |value| !value.is_empty());
+    !no_color && !notion_env::ci() && console::colors_enabled()
+}
+
+/// Reports progress in CI mode as a single line on stderr, prefixed by the time.
+pub fn ci_progress(message: &str) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    eprintln!("[{}] {}", format_timestamp(now), message);
+}
+
+/// Formats a Unix timestamp as an RFC 3339 date and time in UTC.
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let time = secs % 86400;
+
+    // converts days since the epoch to a civil date (see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days)
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
 #[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Copy)]
 pub enum Action {
     Fetching,
 }
 
 impl Action {
-    // this is the maximum width of the displayed Action strings, used for formatting
+    // this is the width the displayed Action strings are padded to, used for formatting
+    // (translated strings may be wider)
     const MAX_WIDTH: usize = 10;
 }
 
 impl Display for Action {
     fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
         let s = match self {
-            &Action::Fetching => "Fetching",
+            &Action::Fetching => message(Message::Fetching),
         };
         f.write_str(s)
     }
 }
 
+/// How progress is displayed, set by the `progress.style` setting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressDisplay {
+    /// A progress bar for downloads, and a spinner for other work.
+    Bar,
+    /// A spinner with a percentage for downloads, and a spinner for other work.
+    Spinner,
+    /// A row of dots for downloads, and a single line for other work, for terminals
+    /// that don't redraw animations well.
+    Dots,
+    /// No progress at all.
+    None,
+}
+
+/// Thrown when the `style` setting of the `[progress]` section is not recognized.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Invalid progress.style setting '{}' (expected bar, spinner, dots, or none)",
+       value)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct ProgressDisplayError {
+    value: String,
+}
+
+impl FromStr for ProgressDisplay {
+    type Err = NotionError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        match src {
+            "bar" => Ok(ProgressDisplay::Bar),
+            "spinner" => Ok(ProgressDisplay::Spinner),
+            "dots" => Ok(ProgressDisplay::Dots),
+            "none" => Ok(ProgressDisplay::None),
+            _ => throw!(ProgressDisplayError {
+                value: src.to_string(),
+            }),
+        }
+    }
+}
+
+const PROGRESS_DISPLAYS: [ProgressDisplay; 4] = [
+    ProgressDisplay::Bar,
+    ProgressDisplay::Spinner,
+    ProgressDisplay::Dots,
+    ProgressDisplay::None,
+];
+
+/// The progress display selected for the process, as an index of `PROGRESS_DISPLAYS`.
+static PROGRESS_DISPLAY: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of progress indicators currently drawn on the console.
+static ACTIVE_PROGRESS: AtomicUsize = AtomicUsize::new(0);
+
+/// Selects how progress is displayed for the rest of the process.
+pub fn init_progress(display: ProgressDisplay) {
+    let index = PROGRESS_DISPLAYS.iter().position(|d| *d == display).unwrap();
+    PROGRESS_DISPLAY.store(index, Ordering::Relaxed);
+}
+
+fn progress_display() -> ProgressDisplay {
+    PROGRESS_DISPLAYS[PROGRESS_DISPLAY.load(Ordering::Relaxed)]
+}
+
+/// A progress indicator, which dereferences to the `ProgressBar` drawing it.
+///
+/// Only one indicator is drawn on the console at a time, since indicatif redraws the
+/// line it is on: indicators started while another one is drawn (like the downloads
+/// of parallel fetches) print a single line instead.
+pub struct Progress {
+    bar: ProgressBar,
+    drawn: bool,
+}
+
+impl Progress {
+    fn hidden() -> Self {
+        Progress {
+            bar: ProgressBar::hidden(),
+            drawn: false,
+        }
+    }
+
+    /// Claims the console for a new indicator, returning `None` if another indicator
+    /// is already drawn there.
+    fn draw<F>(make: F) -> Option<Self>
+    where
+        F: FnOnce() -> ProgressBar,
+    {
+        if ACTIVE_PROGRESS.compare_and_swap(0, 1, Ordering::SeqCst) != 0 {
+            return None;
+        }
+        Some(Progress {
+            bar: make(),
+            drawn: true,
+        })
+    }
+}
+
+impl Deref for Progress {
+    type Target = ProgressBar;
+
+    fn deref(&self) -> &ProgressBar {
+        &self.bar
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if self.drawn {
+            ACTIVE_PROGRESS.store(0, Ordering::SeqCst);
+        }
+    }
+}
+
 /// Constructs a command-line progress bar with the specified Action enum
 /// (e.g., `Action::Installing`), details string (e.g., `"v1.23.4"`), and logical
 /// length (i.e., the number of logical progress steps in the process being
-/// visualized by the progress bar).
-pub fn progress_bar(action: Action, details: &str, len: u64) -> ProgressBar {
+/// visualized by the progress bar), displayed as selected by `init_progress`. In CI
+/// mode, or if another indicator is being drawn, a line is printed instead and the
+/// bar is hidden.
+pub fn progress_bar(action: Action, details: &str, len: u64) -> Progress {
+    let display = progress_display();
+    if display == ProgressDisplay::None {
+        return Progress::hidden();
+    }
+    if notion_env::ci() {
+        ci_progress(&format!("{} {}", action, details));
+        return Progress::hidden();
+    }
+
     let display_width = term_size::dimensions().map(|(w, _)| w).unwrap_or(80);
-    let msg_width = Action::MAX_WIDTH + 1 + details.len();
+    let action_width = cmp::max(Action::MAX_WIDTH, action.to_string().chars().count());
+    let details = truncate(details, display_width.saturating_sub(action_width + 1 + 8));
+    let msg_width = action_width + 1 + details.chars().count();
+    let msg = format!(
+        "{: >width$} {}",
+        style(action.to_string()).green().bold(),
+        details,
+        width = action_width
+    );
 
     //   Installing v1.23.4  [====================>                   ]  50%
     // |----------| |-----|   |--------------------------------------|  |-|
     //    action    details                      bar                 percentage
-    let available_width = display_width - 2 - msg_width - 2 - 2 - 1 - 3 - 1;
-    let bar_width = ::std::cmp::min(available_width, 40);
-
-    let bar = ProgressBar::new(len);
+    //
+    // On terminals too narrow for a useful bar, only the percentage is displayed.
+    let available_width = display_width.saturating_sub(msg_width + 2 + 2 + 2 + 1 + 3 + 1);
+    let bar_width = cmp::min(available_width, 40);
+    let template = match display {
+        ProgressDisplay::Bar if bar_width >= 10 => format!(
+            "{{msg}}  [{{bar:{}.cyan/blue}}] {{percent:>3}}%",
+            bar_width
+        ),
+        ProgressDisplay::Dots if bar_width >= 10 => format!("{{msg}} {{bar:{}}}", bar_width),
+        ProgressDisplay::Spinner => "{spinner} {msg} {percent:>3}%".to_string(),
+        _ => "{msg} {percent:>3}%".to_string(),
+    };
 
-    bar.set_message(&format!(
-        "{: >width$} {}",
-        style(action.to_string()).green().bold(),
-        details,
-        width = Action::MAX_WIDTH
-    ));
-    bar.set_style(
-        ProgressStyle::default_bar()
-            .template(&format!(
-                "{{msg}}  [{{bar:{}.cyan/blue}}] {{percent:>3}}%",
-                bar_width
-            ))
-            .progress_chars("=> "),
-    );
+    let progress = Progress::draw(|| {
+        let bar = ProgressBar::new(len);
+        bar.set_message(&msg);
+        bar.set_style(
+            ProgressStyle::default_bar()
+                .template(&template)
+                .progress_chars(if display == ProgressDisplay::Dots { ".. " } else { "=> " }),
+        );
+        if display == ProgressDisplay::Spinner {
+            bar.enable_steady_tick(20);
+        }
+        bar
+    });
 
-    bar
+    progress.unwrap_or_else(|| {
+        eprintln!("{}", msg);
+        Progress::hidden()
+    })
 }
 
 /// Constructs a command-line progress spinner with the specified "message"
-/// string. The spinner is ticked by default every 20ms.
-pub fn progress_spinner(message: &str) -> ProgressBar {
+/// string. The spinner is ticked by default every 20ms. In CI mode, with the `dots`
+/// display, or if another indicator is being drawn, a line is printed instead and the
+/// spinner is hidden.
+pub fn progress_spinner(message: &str) -> Progress {
+    let display = progress_display();
+    if display == ProgressDisplay::None {
+        return Progress::hidden();
+    }
+    if notion_env::ci() {
+        ci_progress(message);
+        return Progress::hidden();
+    }
+
+    let display_width = term_size::dimensions().map(|(w, _)| w).unwrap_or(80);
+    let message = truncate(message, display_width.saturating_sub(3));
+    if display == ProgressDisplay::Dots {
+        eprintln!("{}...", message);
+        return Progress::hidden();
+    }
+
     // ⠋ Fetching public registry: https://nodejs.org/dist/index.json
-    let spinner = ProgressBar::new_spinner();
+    let spinner = Progress::draw(|| {
+        let spinner = ProgressBar::new_spinner();
+        spinner.set_message(&message);
+        spinner.set_style(ProgressStyle::default_spinner().template("{spinner} {msg}"));
+        spinner.enable_steady_tick(20); // tick the spinner every 20ms
+        spinner
+    });
+
+    spinner.unwrap_or_else(|| {
+        eprintln!("{}", message);
+        Progress::hidden()
+    })
+}
+
+/// Shortens a string to at most `width` characters, ending it with an ellipsis if
+/// anything was cut off.
+fn truncate(s: &str, width: usize) -> String {
+    if s.chars().count() <= width {
+        return s.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+    let mut truncated: String = s.chars().take(width - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+pub mod tests {
 
-    spinner.set_message(message);
-    spinner.set_style(ProgressStyle::default_spinner().template("{spinner} {msg}"));
-    spinner.enable_steady_tick(20); // tick the spinner every 20ms
+    use super::{format_timestamp, truncate, ProgressDisplay};
 
-    spinner
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951782400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1700000000), "2023-11-14T22:13:20Z");
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("v18.17.1", 20), "v18.17.1");
+        assert_eq!(truncate("v18.17.1", 5), "v18.…");
+        assert_eq!(truncate("v18.17.1", 0), "");
+    }
+
+    #[test]
+    fn test_from_str_progress_display() {
+        assert_eq!("dots".parse::<ProgressDisplay>().unwrap(), ProgressDisplay::Dots);
+        assert_eq!("none".parse::<ProgressDisplay>().unwrap(), ProgressDisplay::None);
+        assert!("blocks".parse::<ProgressDisplay>().is_err());
+    }
 }
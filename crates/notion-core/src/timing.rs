@@ -0,0 +1,175 @@
+//! Provides timing of the phases of a command, like downloading and unpacking a tool,
+//! for `notion --timings` and for the phase events sent to the events plugin.
+
+use std::cell::{Cell, RefCell};
+use std::fmt::{self, Display, Formatter};
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// A phase of the work Notion does to provide a tool.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    /// Fetching the index of available versions of a tool.
+    IndexFetch,
+    /// Resolving a version requirement to a version, which may fetch an index.
+    Resolution,
+    /// Downloading a tool's archive.
+    Download,
+    /// Verifying the checksum of a downloaded archive.
+    Checksum,
+    /// Unpacking an archive and adding its files to the store.
+    Unpack,
+    /// Creating or regenerating shims.
+    ShimUpdate,
+}
+
+impl Display for Phase {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let s = match self {
+            &Phase::IndexFetch => "index fetch",
+            &Phase::Resolution => "resolution",
+            &Phase::Download => "download",
+            &Phase::Checksum => "checksum",
+            &Phase::Unpack => "unpack",
+            &Phase::ShimUpdate => "shim update",
+        };
+        f.write_str(s)
+    }
+}
+
+/// How long a phase took.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhaseTiming {
+    pub phase: Phase,
+    /// The number of phases this one ran inside of, like an index fetch during
+    /// resolution.
+    pub depth: usize,
+    pub duration: Duration,
+}
+
+thread_local! {
+    static TIMINGS: RefCell<Vec<PhaseTiming>> = RefCell::new(Vec::new());
+    static DEPTH: Cell<usize> = Cell::new(0);
+}
+
+static REPORT: AtomicBool = AtomicBool::new(false);
+
+/// Runs `f` as a phase, recording how long it took. Phases run inside of `f` are
+/// recorded as nested in this one.
+pub fn time<T, F>(phase: Phase, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    let depth = DEPTH.with(|depth| depth.get());
+    // the phase is recorded before it runs, so that it is listed before its nested phases
+    let index = TIMINGS.with(|timings| {
+        let mut timings = timings.borrow_mut();
+        timings.push(PhaseTiming {
+            phase,
+            depth,
+            duration: Duration::from_secs(0),
+        });
+        timings.len() - 1
+    });
+
+    let start = Instant::now();
+    DEPTH.with(|d| d.set(depth + 1));
+    let result = f();
+    DEPTH.with(|d| d.set(depth));
+
+    let elapsed = start.elapsed();
+    TIMINGS.with(|timings| timings.borrow_mut()[index].duration = elapsed);
+    result
+}
+
+/// Removes and returns the phases recorded so far, in the order they started.
+pub fn take() -> Vec<PhaseTiming> {
+    TIMINGS.with(|timings| mem::replace(&mut *timings.borrow_mut(), Vec::new()))
+}
+
+/// Asks for the phases to be reported on stderr when the session ends, as set by
+/// `notion --timings`.
+pub fn enable_report() {
+    REPORT.store(true, Ordering::Relaxed);
+}
+
+/// Returns true if the phases are reported when the session ends.
+pub fn report_enabled() -> bool {
+    REPORT.load(Ordering::Relaxed)
+}
+
+/// Formats a breakdown of phases, with nested phases indented under the phases they
+/// ran in.
+pub fn format_report(timings: &[PhaseTiming]) -> String {
+    if timings.is_empty() {
+        return "Timings: no phases were recorded\n".to_string();
+    }
+
+    let mut report = String::from("Timings:\n");
+    for timing in timings {
+        let name = format!("{}{}", "  ".repeat(timing.depth), timing.phase);
+        report.push_str(&format!(
+            "    {:<20} {:>9}\n",
+            name,
+            format_duration(timing.duration)
+        ));
+    }
+    report
+}
+
+fn format_duration(duration: Duration) -> String {
+    let millis = duration.as_secs() * 1000 + (duration.subsec_nanos() / 1_000_000) as u64;
+    format!("{}.{:03}s", millis / 1000, millis % 1000)
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_time() {
+        take();
+        let version = time(Phase::Resolution, || {
+            time(Phase::IndexFetch, || ());
+            "18.17.1"
+        });
+        time(Phase::Download, || ());
+        assert_eq!(version, "18.17.1");
+
+        let phases: Vec<(Phase, usize)> = take()
+            .into_iter()
+            .map(|timing| (timing.phase, timing.depth))
+            .collect();
+        assert_eq!(
+            phases,
+            vec![
+                (Phase::Resolution, 0),
+                (Phase::IndexFetch, 1),
+                (Phase::Download, 0),
+            ]
+        );
+        assert!(take().is_empty());
+    }
+
+    #[test]
+    fn test_format_report() {
+        let timings = vec![
+            PhaseTiming {
+                phase: Phase::Resolution,
+                depth: 0,
+                duration: Duration::from_millis(1204),
+            },
+            PhaseTiming {
+                phase: Phase::IndexFetch,
+                depth: 1,
+                duration: Duration::from_millis(1180),
+            },
+        ];
+        assert_eq!(
+            format_report(&timings),
+            "Timings:\n    resolution              1.204s\n      index fetch           1.180s\n"
+        );
+    }
+}
@@ -4,11 +4,16 @@ use std::env::{args_os, ArgsOs};
 use std::ffi::{OsStr, OsString};
 use std::io;
 use std::marker::Sized;
-use std::path::Path;
-use std::process::{Command, ExitStatus};
-
-use notion_fail::{ExitCode, FailExt, Fallible, NotionError, NotionFail};
+use std::path::{Path, PathBuf};
+use std::process::{exit, Command, ExitStatus};
+use std::rc::Rc;
+
+use env;
+use image::System;
+use notion_fail::{ExitCode, FailExt, Fallible, NotionError, NotionFail, ResultExt};
+use package;
 use path;
+use sandbox::Policy;
 use session::{ActivityKind, Session};
 use style;
 
@@ -52,9 +57,31 @@ impl ToolUnimplementedError {
     }
 }
 
+/// Thrown when the executable a tool delegates to does not exist.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{}
+
+The executable may be missing from a damaged install. Run `notion verify` to check
+installed versions, and `notion repair <tool>@<version>` to reinstall one.", error)]
+#[notion_fail(code = "ExecutionFailure")]
+pub(crate) struct MissingExecutableError {
+    error: String,
+}
+
+impl MissingExecutableError {
+    fn from_io_error(error: &io::Error) -> Self {
+        MissingExecutableError {
+            error: BinaryExecError::from_io_error(error).error,
+        }
+    }
+}
+
 /// Represents a command-line tool that Notion shims delegate to.
 pub trait Tool: Sized {
     fn launch() -> ! {
+        if env::bypass() {
+            Self::bypass();
+        }
         let mut session = match Session::new() {
             Ok(session) => session,
             Err(err) => {
@@ -62,6 +89,11 @@ pub trait Tool: Sized {
                 ExitCode::ExecutionFailure.exit();
             }
         };
+        session.recover_interrupted_installs();
+        session.select_configured_locale();
+        style::init_ci_mode(false);
+        style::init_colors(session.configured_color());
+        style::init_progress(session.configured_progress_display());
 
         session.add_event_start(ActivityKind::Tool);
 
@@ -77,6 +109,28 @@ pub trait Tool: Sized {
         }
     }
 
+    /// Runs the next executable with the same name on the `PATH` after the Notion shims,
+    /// without resolving a project or user toolchain or recording any events.
+    fn bypass() -> ! {
+        let mut args = args_os();
+        let command = arg0(&mut args)
+            .and_then(|exe| Ok(command_for(&exe, args, &System::path()?)));
+
+        let result = command.and_then(|mut command| {
+            command
+                .status()
+                .with_context(BinaryExecError::from_io_error)
+        });
+
+        match result {
+            Ok(status) => exit(status.code().unwrap_or(1)),
+            Err(err) => {
+                display_error(&err);
+                ExitCode::ExecutionFailure.exit();
+            }
+        }
+    }
+
     /// Constructs a new instance.
     fn new(&mut Session) -> Fallible<Self>;
 
@@ -106,7 +160,11 @@ pub trait Tool: Sized {
                 session.exit_tool(code);
             }
             Err(err) => {
-                let notion_err = err.with_context(BinaryExecError::from_io_error);
+                let notion_err = if err.kind() == io::ErrorKind::NotFound {
+                    err.with_context(MissingExecutableError::from_io_error)
+                } else {
+                    err.with_context(BinaryExecError::from_io_error)
+                };
                 display_error(&notion_err);
                 session.add_event_error(ActivityKind::Tool, &notion_err);
                 session.exit(ExitCode::ExecutionFailure);
@@ -195,7 +253,38 @@ impl Tool for Binary {
         let mut args = args_os();
         let exe = arg0(&mut args)?;
 
-        // first try to use the project toolchain
+        // shims created for an arbitrary executable run it, whatever provides the name
+        let target = session.catalog()?.targets.get(&*exe.to_string_lossy()).cloned();
+        if let Some(target) = target {
+            let platform = match target.node {
+                Some(ref node) => Some(Rc::new(package::node_image(node))),
+                None => session.current_platform()?,
+            };
+            let path_var = match platform {
+                Some(platform) => {
+                    session.prepare_image(&platform)?;
+                    session.run_pre_run_hook(&exe.to_string_lossy(), &platform.node)?;
+                    platform.path()?
+                }
+                None => System::path()?,
+            };
+
+            // scripts run through their interpreter, found on the platform's path
+            let mut command = match target.interpreter.split_first() {
+                Some((program, interpreter_args)) => {
+                    let program = path::find_executable(OsStr::new(program), &path_var)
+                        .unwrap_or_else(|| PathBuf::from(program));
+                    let mut command = Command::new(program);
+                    command.args(interpreter_args).arg(&target.file);
+                    command
+                }
+                None => Command::new(&target.file),
+            };
+            command.args(args).env("PATH", &path_var);
+            return Ok(Binary(command));
+        }
+
+        // next try to use the project toolchain
         if let Some(project) = session.project() {
             // check if the executable is a direct dependency
             if project.has_direct_bin(&exe)? {
@@ -203,37 +292,60 @@ impl Tool for Binary {
                 let mut path_to_bin = project.local_bin_dir();
                 path_to_bin.push(&exe);
 
-                // if we're in a pinned project, use the project's platform.
-                if let Some(ref platform) = session.project_platform() {
-                    return Ok(Self::from_components(
-                        &path_to_bin.as_os_str(),
-                        args,
-                        &platform.path()?,
-                    ));
-                }
-
+                // if we're in a pinned project, use the project's platform, and
                 // otherwise use the user platform.
-                if let Some(ref platform) = session.user_platform()? {
-                    return Ok(Self::from_components(
-                        &path_to_bin.as_os_str(),
-                        args,
-                        &platform.path()?,
-                    ))
-                }
-
-                // if there's no user platform selected, fail.
-                throw!(NoSuchToolError {
-                    tool: "Node".to_string()
-                });
+                let platform = match session.project_platform()? {
+                    Some(platform) => platform,
+                    None => match session.user_platform()? {
+                        Some(platform) => platform,
+                        // if there's no user platform selected, fail.
+                        None => throw!(NoSuchToolError {
+                            tool: "Node".to_string()
+                        }),
+                    },
+                };
+                let platform = session.with_shell_override(platform)?;
+                session.run_pre_run_hook(&exe.to_string_lossy(), &platform.node)?;
+
+                // the project's executables run sandboxed as configured for how far
+                // the project is trusted
+                let policy = Policy::for_project(project.root(), session.config()?)?;
+                let command = policy.command(path_to_bin.as_os_str(), args, &platform.path()?)?;
+                return Ok(Binary(command));
             }
         }
 
+        // next try the package tools installed by `notion install <package>`
+        let package = session
+            .catalog()?
+            .package_for_bin(&exe.to_string_lossy())
+            .map(|(name, tool)| (name.clone(), tool.clone()));
+        if let Some((name, tool)) = package {
+            let bin_dir = path::package_bin_dir(&name)?;
+            let path_to_bin = path::find_executable(&exe, bin_dir.as_os_str())
+                .unwrap_or_else(|| bin_dir.join(&exe));
+
+            // package tools always run on the platform they were installed with,
+            // rather than the project's or the user's.
+            let platform = tool.image();
+            session.prepare_image(&platform)?;
+            session.run_pre_run_hook(&exe.to_string_lossy(), &platform.node)?;
+            return Ok(Self::from_components(
+                path_to_bin.as_os_str(),
+                args,
+                &platform.path()?,
+            ));
+        }
+
         // next try to use the user toolchain
         if let Some(ref platform) = session.user_platform()? {
             // use the full path to the binary
             // ISSUE (#160): Look up the platform image bound to the user tool.
-            let mut third_p_bin_dir = path::node_version_3p_bin_dir(&platform.node_str)?;
+            let mut third_p_bin_dir = path::node_version_3p_bin_dir(&platform.node_key())?;
             third_p_bin_dir.push(&exe);
+            // the binary stays where it was installed, but runs on the session's versions.
+            let platform = session.with_shell_override(platform.clone())?;
+            session.run_pre_run_hook(&exe.to_string_lossy(), &platform.node)?;
             return Ok(Self::from_components(
                 &third_p_bin_dir.as_os_str(),
                 args,
@@ -291,10 +403,14 @@ impl Tool for Node {
         session.add_event_start(ActivityKind::Node);
 
         let mut args = args_os();
-        let exe = arg0(&mut args)?;
+        // this runs as `launchnode` behind the minimal shim, so the tool is named
+        // explicitly rather than taken from argv[0]
+        arg0(&mut args)?;
+        let exe = OsStr::new("node");
         if let Some(ref platform) = session.current_platform()? {
             session.prepare_image(platform)?;
-            Ok(Self::from_components(&exe, args, &platform.path()?))
+            session.run_pre_run_hook("node", &platform.node)?;
+            Ok(Self::from_components(exe, args, &platform.path()?))
         } else {
             throw!(NoSuchToolError {
                 tool: "Node".to_string()
@@ -316,10 +432,16 @@ impl Tool for Yarn {
         session.add_event_start(ActivityKind::Yarn);
 
         let mut args = args_os();
-        let exe = arg0(&mut args)?;
+        // this runs as `launchyarn` behind the minimal shim, so the tool is named
+        // explicitly rather than taken from argv[0]
+        arg0(&mut args)?;
+        let exe = OsStr::new("yarn");
         if let Some(ref platform) = session.current_platform()? {
             session.prepare_image(platform)?;
-            Ok(Self::from_components(&exe, args, &platform.path()?))
+            if let Some(ref yarn) = platform.yarn {
+                session.run_pre_run_hook("yarn", yarn)?;
+            }
+            Ok(Self::from_components(exe, args, &platform.path()?))
         } else {
             throw!(NoSuchToolError {
                 tool: "Yarn".to_string()
@@ -338,8 +460,8 @@ impl Tool for Yarn {
     /// Perform any tasks which must be run after the tool runs but before exiting.
     fn finalize(session: &Session, maybe_status: &io::Result<ExitStatus>) {
         if let Ok(_) = maybe_status {
-            if let Some(project) = session.project() {
-                let errors = project.autoshim();
+            if let Ok(Some(project)) = session.trusted_project() {
+                let errors = project.autoshim(false);
 
                 for error in errors {
                     display_error(&error);
@@ -0,0 +1,288 @@
+//! Provides the `ToolchainFile` type, which represents a directory-level toolchain
+//! pin (a `notion.toml` or `.notion-version` file) that applies to a directory tree
+//! regardless of whether it contains a Node package. A `notion.toml` next to a
+//! `package.json` instead pins the toolchain of that project, for teams that would
+//! rather not add a `toolchain` key to `package.json`.
+
+use std::env;
+use std::fs::{read_to_string, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use toml;
+
+use image::Image;
+use manifest::Toolchain;
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+
+pub(crate) mod serial;
+
+/// The name of the TOML toolchain file.
+pub const TOOLCHAIN_FILE: &'static str = "notion.toml";
+
+/// The name of the plain-text Node version file.
+pub const VERSION_FILE: &'static str = ".notion-version";
+
+/// Thrown when a toolchain file could not be parsed.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not parse toolchain file {}: {}", file, error)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct ToolchainFileParseError {
+    pub(crate) file: String,
+    pub(crate) error: String,
+}
+
+/// The autoshim settings of a project, from the `[autoshim]` section of its
+/// `notion.toml` or the `autoshim` key of its `toolchain` section, which control which
+/// of its dependency binaries are shimmed automatically.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Autoshim {
+    /// Whether dependency binaries are shimmed automatically at all.
+    pub enabled: bool,
+    /// Glob patterns of the binaries that are shimmed automatically. If there are none,
+    /// every binary that isn't excluded is.
+    pub include: Vec<String>,
+    /// Glob patterns of the binaries that are never shimmed automatically, which take
+    /// precedence over `include`.
+    pub exclude: Vec<String>,
+}
+
+impl Default for Autoshim {
+    fn default() -> Self {
+        Autoshim {
+            enabled: true,
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+impl Autoshim {
+    /// Returns true if the named binary may be shimmed automatically.
+    pub fn allows(&self, name: &str) -> bool {
+        self.enabled
+            && (self.include.is_empty() || self.include.iter().any(|p| glob_matches(p, name)))
+            && !self.exclude.iter().any(|p| glob_matches(p, name))
+    }
+
+    /// Produces the serialized form of these settings, for writing them back out.
+    pub(crate) fn to_serial(&self) -> serial::Autoshim {
+        serial::Autoshim {
+            enabled: if self.enabled { None } else { Some(false) },
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+        }
+    }
+}
+
+/// Tests whether a name matches a glob pattern, in which `*` matches any run of
+/// characters and `?` matches any single character.
+pub(crate) fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // the positions to resume from if the last `*` has to match more characters
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            star = Some((star_p, star_n + 1));
+            p = star_p + 1;
+            n = star_n + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// A directory-level toolchain pin.
+pub struct ToolchainFile {
+    file: PathBuf,
+    toolchain: Option<Toolchain>,
+    platform: Option<Rc<Image>>,
+    autoshim: Option<Autoshim>,
+}
+
+impl ToolchainFile {
+    /// Returns the toolchain file that applies to the current working directory, if any.
+    pub fn for_current_dir() -> Fallible<Option<ToolchainFile>> {
+        let current_dir: &Path = &env::current_dir().unknown()?;
+        Self::for_dir(&current_dir)
+    }
+
+    /// Returns the nearest toolchain file in the input directory or any of its
+    /// ancestors, if any. A `notion.toml` takes precedence over a `.notion-version`
+    /// file in the same directory.
+    pub fn for_dir(base_dir: &Path) -> Fallible<Option<ToolchainFile>> {
+        let mut dir = Some(base_dir);
+
+        while let Some(current) = dir {
+            let toml_file = current.join(TOOLCHAIN_FILE);
+            if toml_file.is_file() {
+                return Ok(Some(ToolchainFile::from_toml(toml_file)?));
+            }
+
+            let version_file = current.join(VERSION_FILE);
+            if version_file.is_file() {
+                return Ok(Some(ToolchainFile::from_version_file(version_file)?));
+            }
+
+            dir = current.parent();
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the `notion.toml` file next to a project's `package.json`, if any.
+    pub(crate) fn for_project(project_root: &Path) -> Fallible<Option<ToolchainFile>> {
+        let toml_file = project_root.join(TOOLCHAIN_FILE);
+        if toml_file.is_file() {
+            return Ok(Some(ToolchainFile::from_toml(toml_file)?));
+        }
+        Ok(None)
+    }
+
+    fn from_toml(file: PathBuf) -> Fallible<ToolchainFile> {
+        let src = read_to_string(&file).unknown()?;
+        let serial: serial::ToolchainFile =
+            toml::from_str(&src).with_context(|error: &toml::de::Error| {
+                ToolchainFileParseError {
+                    file: file.to_string_lossy().to_string(),
+                    error: error.to_string(),
+                }
+            })?;
+        let toolchain = match serial.toolchain {
+            Some(image) => Some(image.into_toolchain()?),
+            None => None,
+        };
+        let autoshim = serial.autoshim.map(serial::Autoshim::into_autoshim);
+        Ok(ToolchainFile::new(file, toolchain, autoshim))
+    }
+
+    fn from_version_file(file: PathBuf) -> Fallible<ToolchainFile> {
+        let src = read_to_string(&file).unknown()?;
+        let toolchain = serial::parse_version_file(&src).into_toolchain()?;
+        Ok(ToolchainFile::new(file, Some(toolchain), None))
+    }
+
+    fn new(
+        file: PathBuf,
+        toolchain: Option<Toolchain>,
+        autoshim: Option<Autoshim>,
+    ) -> ToolchainFile {
+        let platform = toolchain
+            .as_ref()
+            .and_then(Toolchain::exact_image)
+            .map(Rc::new);
+        ToolchainFile {
+            file,
+            toolchain,
+            platform,
+            autoshim,
+        }
+    }
+
+    /// Returns the path to this toolchain file.
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+
+    /// Returns the pinned toolchain, if any, which may pin ranges.
+    pub fn toolchain(&self) -> Option<&Toolchain> {
+        self.toolchain.as_ref()
+    }
+
+    /// Returns the pinned platform image, if the toolchain only pins exact versions.
+    pub fn platform(&self) -> Option<Rc<Image>> {
+        self.platform.clone()
+    }
+
+    /// Returns the autoshim settings, if the file has an `[autoshim]` section.
+    pub fn autoshim(&self) -> Option<&Autoshim> {
+        self.autoshim.as_ref()
+    }
+}
+
+/// Writes the `[toolchain]` section of a `notion.toml` file, creating the file if
+/// necessary and keeping its other sections.
+pub(crate) fn write_toolchain(file: &Path, toolchain: serial::Image) -> Fallible<()> {
+    let mut value = if file.is_file() {
+        let src = read_to_string(file).unknown()?;
+        src.parse::<toml::Value>()
+            .with_context(|error: &toml::de::Error| ToolchainFileParseError {
+                file: file.to_string_lossy().to_string(),
+                error: error.to_string(),
+            })?
+    } else {
+        toml::Value::Table(toml::value::Table::new())
+    };
+
+    if let Some(table) = value.as_table_mut() {
+        table.insert(
+            "toolchain".to_string(),
+            toml::Value::try_from(toolchain).unknown()?,
+        );
+    }
+
+    let mut out = File::create(file).unknown()?;
+    out.write_all(toml::to_string(&value).unknown()?.as_bytes())
+        .unknown()?;
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use semver::Version;
+    use std::path::PathBuf;
+
+    use toolchain::{glob_matches, ToolchainFile};
+
+    fn fixture_path(fixture_dir: &str) -> PathBuf {
+        let mut cargo_manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        cargo_manifest_dir.push("fixtures");
+        cargo_manifest_dir.push(fixture_dir);
+        cargo_manifest_dir
+    }
+
+    #[test]
+    fn finds_toml_file_in_ancestor() {
+        let toolchain = ToolchainFile::for_dir(&fixture_path("toolchain_file/nested"))
+            .unwrap()
+            .expect("Did not find notion.toml");
+        let platform = toolchain.platform().unwrap();
+        assert_eq!(platform.node, Version::parse("8.9.4").unwrap());
+        assert_eq!(platform.yarn, Some(Version::parse("1.7.0").unwrap()));
+    }
+
+    #[test]
+    fn finds_version_file() {
+        let toolchain = ToolchainFile::for_dir(&fixture_path("version_file"))
+            .unwrap()
+            .expect("Did not find .notion-version");
+        let platform = toolchain.platform().unwrap();
+        assert_eq!(platform.node, Version::parse("10.2.1").unwrap());
+        assert_eq!(platform.yarn, None);
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("tsc", "tsc"));
+        assert!(!glob_matches("tsc", "tsserver"));
+        assert!(glob_matches("ts*", "tsserver"));
+        assert!(glob_matches("*-cli", "webpack-cli"));
+        assert!(glob_matches("lerna-*-*", "lerna-run-all"));
+        assert!(glob_matches("es?int", "eslint"));
+        assert!(!glob_matches("es?int", "esint"));
+        assert!(glob_matches("*", ""));
+        assert!(!glob_matches("a*b", "acbd"));
+    }
+}
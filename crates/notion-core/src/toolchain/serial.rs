@@ -0,0 +1,165 @@
+use super::super::toolchain;
+use manifest::serial::ToolPin;
+use manifest::Toolchain;
+
+use notion_fail::Fallible;
+
+#[derive(Serialize, Deserialize)]
+pub struct ToolchainFile {
+    pub toolchain: Option<Image>,
+    pub autoshim: Option<Autoshim>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Image {
+    pub node: ToolPin,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub yarn: Option<ToolPin>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub npm: Option<String>,
+}
+
+/// The `[autoshim]` section of a `notion.toml` file, or the `autoshim` key of the
+/// `toolchain` section of a `package.json` file.
+#[derive(Serialize, Deserialize)]
+pub struct Autoshim {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+}
+
+impl Image {
+    pub fn into_toolchain(self) -> Fallible<Toolchain> {
+        Toolchain::parse_pins(&self.node, self.yarn.as_ref(), self.npm)
+    }
+}
+
+impl Autoshim {
+    pub fn into_autoshim(self) -> toolchain::Autoshim {
+        toolchain::Autoshim {
+            enabled: self.enabled.unwrap_or(true),
+            include: self.include,
+            exclude: self.exclude,
+        }
+    }
+}
+
+/// Parses the contents of a `.notion-version` file, which contains nothing but
+/// a Node version (optionally prefixed with `v`).
+pub fn parse_version_file(src: &str) -> Image {
+    let version = src.trim();
+    let version = if version.starts_with('v') {
+        &version[1..]
+    } else {
+        version
+    };
+    Image {
+        node: ToolPin::from(version.to_string()),
+        yarn: None,
+        npm: None,
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{parse_version_file, ToolchainFile};
+    use checksum::Checksum;
+    use manifest::serial::ToolPin;
+    use toml;
+
+    #[test]
+    fn test_parse_version_file() {
+        assert_eq!(parse_version_file("10.2.1\n").node.version(), "10.2.1");
+        assert_eq!(parse_version_file("  v8.9.4  ").node.version(), "8.9.4");
+        assert!(parse_version_file("10.2.1").yarn.is_none());
+    }
+
+    #[test]
+    fn test_parse_toolchain_file() {
+        let src = r#"
+[toolchain]
+node = "10.2.1"
+yarn = "1.7.0"
+"#;
+        let file: ToolchainFile = toml::from_str(src).expect("Could not parse notion.toml");
+        let toolchain = file.toolchain.expect("Did not parse toolchain");
+        assert_eq!(toolchain.node.version(), "10.2.1");
+        assert_eq!(toolchain.yarn.as_ref().map(ToolPin::version), Some("1.7.0"));
+
+        let empty: ToolchainFile = toml::from_str("").expect("Could not parse empty notion.toml");
+        assert!(empty.toolchain.is_none());
+        assert!(empty.autoshim.is_none());
+    }
+
+    #[test]
+    fn test_parse_pinned_checksum() {
+        let src = r#"
+[toolchain]
+yarn = "1.7.0"
+
+[toolchain.node]
+version = "18.17.1"
+sha256 = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+"#;
+        let file: ToolchainFile = toml::from_str(src).expect("Could not parse notion.toml");
+        let toolchain = file
+            .toolchain
+            .expect("Did not parse toolchain")
+            .into_toolchain()
+            .expect("Could not parse pinned versions");
+        let expected: Checksum =
+            "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+                .parse()
+                .unwrap();
+        assert_eq!(toolchain.node_str, "18.17.1");
+        assert_eq!(toolchain.node_checksum, Some(expected));
+        assert_eq!(toolchain.yarn_checksum, None);
+
+        let range = r#"
+[toolchain.node]
+version = "^18"
+sha256 = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+"#;
+        let file: ToolchainFile = toml::from_str(range).expect("Could not parse notion.toml");
+        assert!(file.toolchain.unwrap().into_toolchain().is_err());
+    }
+
+    #[test]
+    fn test_parse_autoshim() {
+        let src = r#"
+[toolchain]
+node = "^10"
+npm = "6"
+
+[autoshim]
+exclude = ["tsc"]
+"#;
+        let file: ToolchainFile = toml::from_str(src).expect("Could not parse notion.toml");
+        let toolchain = file.toolchain.expect("Did not parse toolchain");
+        assert_eq!(toolchain.npm, Some("6".to_string()));
+
+        let autoshim = file.autoshim.expect("Did not parse autoshim").into_autoshim();
+        assert!(autoshim.enabled);
+        assert!(!autoshim.allows("tsc"));
+        assert!(autoshim.allows("eslint"));
+    }
+
+    #[test]
+    fn test_parse_autoshim_patterns() {
+        let src = r#"
+[autoshim]
+include = ["tsc", "eslint*"]
+exclude = ["*-internal"]
+"#;
+        let file: ToolchainFile = toml::from_str(src).expect("Could not parse notion.toml");
+        let autoshim = file.autoshim.expect("Did not parse autoshim").into_autoshim();
+        assert!(autoshim.allows("tsc"));
+        assert!(autoshim.allows("eslint-fix"));
+        assert!(!autoshim.allows("eslint-internal"));
+        assert!(!autoshim.allows("tsserver"));
+    }
+}
@@ -0,0 +1,277 @@
+//! Provides the trust store, which records the project directories whose pins Notion
+//! honors. Switching to the versions a cloned repository pins, and installing them,
+//! runs code chosen by whoever wrote the pins, so the pins and autoshim settings of a
+//! project only take effect once it is trusted: with `notion trust`, by answering the
+//! prompt shown the first time the project is used from an interactive terminal, or by
+//! a pattern in the `trust.allow` setting.
+//!
+//! Trusting (or declining) a directory covers the directories within it, so that a
+//! directory holding many checkouts can be trusted at once.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use console;
+
+use config::Config;
+use env;
+use fs::ensure_containing_dir_exists;
+use logging;
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+use path;
+use toolchain::glob_matches;
+
+/// Thrown when a directory to be decided on can't be recorded in the trust store.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Can't trust or decline {:?}, since its path contains a line break", dir)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct UnrecordableDirError {
+    dir: String,
+}
+
+/// Whether the pins of a directory are honored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decision {
+    /// The user trusted the directory.
+    Trusted,
+    /// The user declined to trust the directory when asked.
+    Declined,
+}
+
+/// How far a directory is trusted, which decides how its executables are sandboxed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    /// The directory is neither allowed by the configuration nor trusted by the user.
+    Untrusted,
+    /// The user trusted the directory.
+    Trusted,
+    /// The directory matches a pattern of the `trust.allow` setting.
+    Allowed,
+}
+
+/// The directories the user has decided on, by their canonical paths.
+pub type Decisions = BTreeMap<PathBuf, Decision>;
+
+/// Reads the directories the user has decided on.
+pub fn decisions() -> Fallible<Decisions> {
+    let file = path::trusted_projects_file()?;
+    match fs::read_to_string(&file) {
+        Ok(src) => Ok(parse_decisions(&src)),
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(Decisions::new()),
+        Err(error) => Err(error).unknown(),
+    }
+}
+
+fn save(decisions: &Decisions) -> Fallible<()> {
+    let file = path::trusted_projects_file()?;
+    ensure_containing_dir_exists(&file)?;
+    let mut file = File::create(&file).unknown()?;
+    file.write_all(format_decisions(decisions).as_bytes()).unknown()
+}
+
+/// Records a decision about a directory, producing the canonical path it was recorded
+/// under. A path with a line break in it is refused, since the trust store records a
+/// directory per line and the rest of the path would read as a decision of its own.
+pub fn decide(dir: &Path, decision: Decision) -> Fallible<PathBuf> {
+    let dir = canonical(dir);
+    if !recordable(&dir) {
+        throw!(UnrecordableDirError {
+            dir: dir.to_string_lossy().to_string(),
+        });
+    }
+    let mut decisions = decisions()?;
+    decisions.insert(dir.clone(), decision);
+    save(&decisions)?;
+    Ok(dir)
+}
+
+/// Forgets the decision recorded for a directory, producing false if there was none.
+pub fn forget(dir: &Path) -> Fallible<bool> {
+    let mut decisions = decisions()?;
+    let removed = decisions.remove(&canonical(dir)).is_some()
+        || decisions.remove(dir).is_some();
+    if removed {
+        save(&decisions)?;
+    }
+    Ok(removed)
+}
+
+/// Tests whether the pins found in a directory take effect. Directories that are
+/// neither allowed by the configuration nor decided on are asked about when Notion is
+/// run from an interactive terminal, and otherwise ignored with a warning.
+pub fn check(dir: &Path, config: &Config) -> Fallible<bool> {
+    if !config.trust_required() || allowed(config.trusted_patterns(), dir) {
+        return Ok(true);
+    }
+
+    match decision_for(&decisions()?, &canonical(dir)) {
+        Some(decision) => Ok(decision == Decision::Trusted),
+        None if interactive() && recordable(&canonical(dir)) => ask(dir),
+        None => {
+            logging::warning(&format!(
+                "ignoring the versions pinned in {}, which is not trusted \
+                 (run `notion trust {}` to use them)",
+                dir.display(),
+                dir.display()
+            ));
+            Ok(false)
+        }
+    }
+}
+
+/// Finds how far a directory is trusted, without asking the user about it.
+pub fn level(dir: &Path, config: &Config) -> Fallible<Level> {
+    if allowed(config.trusted_patterns(), dir) {
+        return Ok(Level::Allowed);
+    }
+    Ok(match decision_for(&decisions()?, &canonical(dir)) {
+        Some(Decision::Trusted) => Level::Trusted,
+        _ => Level::Untrusted,
+    })
+}
+
+/// Asks the user whether to trust a directory, recording the answer. No answer (at the
+/// end of the input) is not recorded, so the user is asked again next time.
+fn ask(dir: &Path) -> Fallible<bool> {
+    eprint!(
+        "{} pins its own Node toolchain. Trust it and use the versions it pins? [y/N] ",
+        dir.display()
+    );
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).unknown()? == 0 {
+        eprintln!();
+        return Ok(false);
+    }
+
+    let decision = match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Decision::Trusted,
+        _ => Decision::Declined,
+    };
+    decide(dir, decision)?;
+    Ok(decision == Decision::Trusted)
+}
+
+/// Tests whether a decision about a directory can be recorded, which it can't if its
+/// path has a line break in it.
+fn recordable(dir: &Path) -> bool {
+    !dir.to_string_lossy().contains(|c| c == '\n' || c == '\r')
+}
+
+/// Tests whether the user can be asked about a directory.
+fn interactive() -> bool {
+    !env::ci() && console::user_attended()
+}
+
+/// Finds the decision that applies to a directory: the one recorded for the directory
+/// itself or, failing that, for its nearest ancestor.
+fn decision_for(decisions: &Decisions, dir: &Path) -> Option<Decision> {
+    dir.ancestors()
+        .filter_map(|ancestor| decisions.get(ancestor))
+        .next()
+        .cloned()
+}
+
+/// Tests whether a directory, or one of its ancestors, matches one of the patterns of
+/// the `trust.allow` setting.
+fn allowed(patterns: &[String], dir: &Path) -> bool {
+    let canonical = canonical(dir);
+    patterns.iter().any(|pattern| {
+        let pattern = pattern.trim_right_matches(|c| c == '/' || c == '\\');
+        dir.ancestors()
+            .chain(canonical.ancestors())
+            .any(|ancestor| glob_matches(pattern, &ancestor.to_string_lossy()))
+    })
+}
+
+fn canonical(dir: &Path) -> PathBuf {
+    fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf())
+}
+
+/// Formats the decisions a directory per line, prefixed with the decision.
+fn format_decisions(decisions: &Decisions) -> String {
+    let mut src = String::new();
+    for (dir, decision) in decisions.iter().filter(|&(dir, _)| recordable(dir)) {
+        let decision = match decision {
+            &Decision::Trusted => "trusted",
+            &Decision::Declined => "declined",
+        };
+        src.push_str(&format!("{} {}\n", decision, dir.display()));
+    }
+    src
+}
+
+/// Parses the decisions, skipping lines that aren't understood.
+fn parse_decisions(src: &str) -> Decisions {
+    let mut decisions = Decisions::new();
+    for line in src.lines() {
+        let mut parts = line.splitn(2, ' ');
+        match (parts.next(), parts.next()) {
+            (Some("trusted"), Some(dir)) => {
+                decisions.insert(PathBuf::from(dir), Decision::Trusted);
+            }
+            (Some("declined"), Some(dir)) => {
+                decisions.insert(PathBuf::from(dir), Decision::Declined);
+            }
+            _ => {}
+        }
+    }
+    decisions
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_decisions_roundtrip() {
+        let mut decisions = Decisions::new();
+        decisions.insert(PathBuf::from("/work/my app"), Decision::Trusted);
+        decisions.insert(PathBuf::from("/tmp/clone"), Decision::Declined);
+
+        let src = format_decisions(&decisions);
+        assert_eq!(src, "declined /tmp/clone\ntrusted /work/my app\n");
+        assert_eq!(parse_decisions(&src), decisions);
+        assert!(parse_decisions("maybe /work\n\n").is_empty());
+    }
+
+    #[test]
+    fn test_unrecordable_dir() {
+        let mut decisions = Decisions::new();
+        decisions.insert(PathBuf::from("/tmp/clone\ntrusted /"), Decision::Declined);
+        decisions.insert(PathBuf::from("/tmp/clone\rtrusted /"), Decision::Declined);
+
+        assert_eq!(format_decisions(&decisions), "");
+        assert!(!recordable(Path::new("/tmp/clone\ntrusted /")));
+        assert!(recordable(Path::new("/work/my app")));
+    }
+
+    #[test]
+    fn test_decision_for() {
+        let mut decisions = Decisions::new();
+        decisions.insert(PathBuf::from("/work"), Decision::Trusted);
+        decisions.insert(PathBuf::from("/work/vendor"), Decision::Declined);
+
+        assert_eq!(decision_for(&decisions, Path::new("/work")), Some(Decision::Trusted));
+        assert_eq!(decision_for(&decisions, Path::new("/work/app")), Some(Decision::Trusted));
+        assert_eq!(
+            decision_for(&decisions, Path::new("/work/vendor/lib")),
+            Some(Decision::Declined)
+        );
+        assert_eq!(decision_for(&decisions, Path::new("/workshop")), None);
+    }
+
+    #[test]
+    fn test_allowed() {
+        let patterns = vec!["/work/acme/*".to_string(), "/src/tools/".to_string()];
+
+        assert!(allowed(&patterns, Path::new("/work/acme/app")));
+        assert!(allowed(&patterns, Path::new("/work/acme/app/packages/web")));
+        assert!(allowed(&patterns, Path::new("/src/tools")));
+        assert!(allowed(&patterns, Path::new("/src/tools/lint")));
+        assert!(!allowed(&patterns, Path::new("/work/other/app")));
+        assert!(!allowed(&patterns, Path::new("/src/toolset")));
+    }
+}
@@ -0,0 +1,205 @@
+//! Provides reporting of the disk space used by installed tool versions and by
+//! the archive cache.
+
+use std::collections::BTreeMap;
+use std::fs::{metadata, read_dir, symlink_metadata};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use semver::Version;
+
+use catalog::Catalog;
+use notion_fail::{FailExt, Fallible, ResultExt};
+use path;
+
+/// The on-disk size of each installed Node and Yarn version, in bytes.
+pub struct VersionSizes {
+    pub node: BTreeMap<Version, u64>,
+    pub yarn: BTreeMap<Version, u64>,
+}
+
+/// A file in the archive cache.
+pub struct CachedFile {
+    pub name: String,
+    pub bytes: u64,
+}
+
+/// The contents of the archive cache for each tool.
+pub struct CacheSizes {
+    pub node: Vec<CachedFile>,
+    pub yarn: Vec<CachedFile>,
+}
+
+impl CacheSizes {
+    /// The total size of the cache, in bytes.
+    pub fn total(&self) -> u64 {
+        self.node.iter().chain(self.yarn.iter()).map(|file| file.bytes).sum()
+    }
+}
+
+/// Measures the installed versions of every tool in the catalog. Builds for other
+/// architectures are counted towards the size of their version.
+pub fn version_sizes(catalog: &Catalog) -> Fallible<VersionSizes> {
+    let mut node_dirs = Vec::new();
+    for version in catalog.node.versions.iter() {
+        node_dirs.push((version.clone(), path::node_version_dir(&version.to_string())?));
+    }
+    for (version, arches) in catalog.node.arches.iter() {
+        for arch in arches {
+            let key = path::node_version_key(&version.to_string(), *arch);
+            node_dirs.push((version.clone(), path::node_version_dir(&key)?));
+        }
+    }
+
+    let mut yarn_dirs = Vec::new();
+    for version in catalog.yarn.versions.iter() {
+        yarn_dirs.push((version.clone(), path::yarn_version_dir(&version.to_string())?));
+    }
+
+    Ok(VersionSizes {
+        node: sum_by_version(node_dirs)?,
+        yarn: sum_by_version(yarn_dirs)?,
+    })
+}
+
+/// Measures the files in the archive cache of each tool, largest first.
+pub fn cache_sizes() -> Fallible<CacheSizes> {
+    Ok(CacheSizes {
+        node: cached_files(&path::node_cache_dir()?)?,
+        yarn: cached_files(&path::yarn_cache_dir()?)?,
+    })
+}
+
+fn cached_files(dir: &Path) -> Fallible<Vec<CachedFile>> {
+    let mut files = Vec::new();
+    if !dir.is_dir() {
+        return Ok(files);
+    }
+
+    for entry in read_dir(dir).unknown()? {
+        let entry = entry.unknown()?;
+        files.push(CachedFile {
+            name: entry.file_name().to_string_lossy().to_string(),
+            bytes: dir_size(&entry.path())?,
+        });
+    }
+    files.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+    Ok(files)
+}
+
+/// Measures a set of version directories in parallel and adds up the sizes of the
+/// directories that belong to the same version.
+fn sum_by_version(dirs: Vec<(Version, PathBuf)>) -> Fallible<BTreeMap<Version, u64>> {
+    let (versions, dirs): (Vec<Version>, Vec<PathBuf>) = dirs.into_iter().unzip();
+    let mut sizes = BTreeMap::new();
+    for (version, bytes) in versions.into_iter().zip(dir_sizes(dirs)?) {
+        *sizes.entry(version).or_insert(0) += bytes;
+    }
+    Ok(sizes)
+}
+
+/// Measures several directories at once, with one thread per directory, and produces
+/// their sizes in the same order.
+fn dir_sizes(dirs: Vec<PathBuf>) -> Fallible<Vec<u64>> {
+    let handles: Vec<_> = dirs
+        .into_iter()
+        .map(|dir| thread::spawn(move || dir_size(&dir)))
+        .collect();
+
+    let mut sizes = Vec::new();
+    for handle in handles {
+        match handle.join() {
+            Ok(size) => sizes.push(size?),
+            Err(_) => return Err(WalkPanicError.unknown()),
+        }
+    }
+    Ok(sizes)
+}
+
+/// Thrown when a thread measuring a directory panics.
+#[derive(Debug, Fail)]
+#[fail(display = "a thread measuring disk usage panicked")]
+struct WalkPanicError;
+
+/// Measures the total size of the files under a path, in bytes. The path itself is
+/// followed if it is a symlink (as version directories are, to their link farms), but
+/// symlinks under it are not, so a file is only counted where it actually lives. A
+/// path that does not exist has a size of zero.
+pub fn dir_size(path: &Path) -> Fallible<u64> {
+    let metadata = match metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return Ok(0),
+    };
+
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+
+    let mut total = 0;
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in read_dir(&dir).unknown()? {
+            let entry = entry.unknown()?;
+            let metadata = symlink_metadata(entry.path()).unknown()?;
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else {
+                total += metadata.len();
+            }
+        }
+    }
+    Ok(total)
+}
+
+/// Formats a number of bytes for display, e.g. `45.3 MB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&'static str; 4] = ["KB", "MB", "GB", "TB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{dir_size, dir_sizes, format_size};
+    use std::fs::{create_dir_all, File};
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(45 * 1024 * 1024 + 300 * 1024), "45.3 MB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GB");
+    }
+
+    #[test]
+    fn test_dir_size() {
+        let root = tempdir().expect("Could not create temporary directory");
+        let a = root.path().join("a");
+        let b = root.path().join("b");
+        create_dir_all(a.join("lib")).unwrap();
+        create_dir_all(&b).unwrap();
+        File::create(a.join("one")).unwrap().write_all(&[0; 100]).unwrap();
+        File::create(a.join("lib").join("two")).unwrap().write_all(&[0; 50]).unwrap();
+        File::create(b.join("three")).unwrap().write_all(&[0; 7]).unwrap();
+
+        assert_eq!(dir_size(&a).unwrap(), 150);
+        assert_eq!(dir_size(&root.path().join("missing")).unwrap(), 0);
+        assert_eq!(
+            dir_sizes(vec![a.clone(), b.clone(), a.join("one")]).unwrap(),
+            vec![150, 7, 100]
+        );
+    }
+}
@@ -0,0 +1,384 @@
+//! Provides integrity checks of the tool versions installed in the catalog.
+//!
+//! When a version is installed, a manifest of the SHA-256 digest of every file
+//! in its directory is recorded alongside it. Verifying a version recomputes
+//! those digests and checks that the version's executables are still in place.
+//! Since versions share the files they have in common through the store, each
+//! stored file is only hashed once however many versions link to it.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use arch::Arch;
+use catalog::Catalog;
+use checksum::sha256_hex;
+use compress::VersionPaths;
+use fs::{collect_regular_files, ensure_containing_dir_exists};
+use notion_fail::{Fallible, ResultExt};
+use path;
+use semver::Version;
+
+#[cfg(unix)]
+const NODE_BINARIES: &'static [&'static str] = &["node", "npm", "npx"];
+#[cfg(windows)]
+const NODE_BINARIES: &'static [&'static str] = &["node.exe", "npm.cmd", "npx.cmd"];
+
+#[cfg(unix)]
+const YARN_BINARIES: &'static [&'static str] = &["yarn"];
+#[cfg(windows)]
+const YARN_BINARIES: &'static [&'static str] = &["yarn.cmd"];
+
+/// A problem found with an installed version.
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    /// The version's directory, or a file recorded in its manifest, no longer exists.
+    Missing(String),
+    /// A file's contents no longer match the digest recorded in the manifest.
+    Modified(String),
+    /// One of the version's executables is missing or cannot be executed.
+    NotExecutable(String),
+    /// No manifest was recorded for the version (e.g. it was installed by an older
+    /// Notion), so only its executables could be checked.
+    NoManifest,
+    /// The version is compressed, so it could not be checked until it is next used.
+    Compressed,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            &Problem::Missing(ref file) => write!(f, "missing: {}", file),
+            &Problem::Modified(ref file) => write!(f, "modified: {}", file),
+            &Problem::NotExecutable(ref file) => write!(f, "not executable: {}", file),
+            &Problem::NoManifest => write!(f, "no manifest was recorded at install time"),
+            &Problem::Compressed => write!(f, "compressed until it is next used"),
+        }
+    }
+}
+
+impl Problem {
+    /// Tests whether this problem means the install is corrupted, rather than that
+    /// it could only be partially checked.
+    pub fn is_corruption(&self) -> bool {
+        match self {
+            &Problem::NoManifest | &Problem::Compressed => false,
+            _ => true,
+        }
+    }
+}
+
+/// The result of verifying one installed version.
+pub struct Report {
+    /// The name of the tool, e.g. `node`.
+    pub tool: &'static str,
+    pub version: Version,
+    /// The architecture of a Node build for an architecture other than the native one.
+    pub arch: Option<Arch>,
+    /// The name the version is installed under, which includes the architecture
+    /// for builds other than the native one.
+    pub key: String,
+    pub problems: Vec<Problem>,
+}
+
+impl Report {
+    /// Tests whether the installed version is corrupted.
+    pub fn is_corrupted(&self) -> bool {
+        self.problems.iter().any(Problem::is_corruption)
+    }
+}
+
+/// Verifies every Node and Yarn version installed in the catalog.
+pub fn verify_catalog(catalog: &Catalog) -> Fallible<Vec<Report>> {
+    let mut reports = Vec::new();
+    let mut digests = Digests::new();
+
+    for version in catalog.node.versions.iter() {
+        reports.push(verify_node(version, None, &mut digests)?);
+    }
+    for (version, arches) in catalog.node.arches.iter() {
+        for arch in arches {
+            reports.push(verify_node(version, Some(*arch), &mut digests)?);
+        }
+    }
+    for version in catalog.yarn.versions.iter() {
+        let key = version.to_string();
+        let problems = if VersionPaths::yarn(&key)?.is_compressed() {
+            vec![Problem::Compressed]
+        } else {
+            check_dir(
+                &path::yarn_version_dir(&key)?,
+                &path::yarn_manifest_file(&key)?,
+                &path::yarn_version_bin_dir(&key)?,
+                YARN_BINARIES,
+                &mut digests,
+            )?
+        };
+        reports.push(Report {
+            tool: "yarn",
+            version: version.clone(),
+            arch: None,
+            key,
+            problems,
+        });
+    }
+
+    Ok(reports)
+}
+
+fn verify_node(version: &Version, arch: Option<Arch>, digests: &mut Digests) -> Fallible<Report> {
+    let key = path::node_version_key(&version.to_string(), arch.unwrap_or_else(Arch::native));
+    let problems = if VersionPaths::node(&key)?.is_compressed() {
+        vec![Problem::Compressed]
+    } else {
+        check_dir(
+            &path::node_version_dir(&key)?,
+            &path::node_manifest_file(&key)?,
+            &path::node_version_bin_dir(&key)?,
+            NODE_BINARIES,
+            digests,
+        )?
+    };
+    Ok(Report {
+        tool: "node",
+        version: version.clone(),
+        arch,
+        key,
+        problems,
+    })
+}
+
+/// Checks an installed version directory against its manifest, and checks that the
+/// named executables in `bin_dir` exist and can be executed. Files that are not in
+/// the manifest, such as packages installed globally after the fact, are ignored.
+fn check_dir(
+    dir: &Path,
+    manifest: &Path,
+    bin_dir: &Path,
+    binaries: &[&str],
+    digests: &mut Digests,
+) -> Fallible<Vec<Problem>> {
+    if !dir.is_dir() {
+        return Ok(vec![Problem::Missing(dir.to_string_lossy().to_string())]);
+    }
+
+    let mut problems = Vec::new();
+
+    match read_manifest(manifest)? {
+        Some(entries) => {
+            for (digest, relative) in entries {
+                let file = dir.join(&relative);
+                if !file.is_file() {
+                    problems.push(Problem::Missing(relative));
+                } else if digests.digest(&file)? != digest {
+                    problems.push(Problem::Modified(relative));
+                }
+            }
+        }
+        None => problems.push(Problem::NoManifest),
+    }
+
+    for name in binaries {
+        let binary = bin_dir.join(name);
+        if !is_executable(&binary) {
+            problems.push(Problem::NotExecutable(binary.to_string_lossy().to_string()));
+        }
+    }
+
+    Ok(problems)
+}
+
+/// The digests of the files checked so far, by file identity, so that a file
+/// linked into several versions is only hashed once.
+struct Digests {
+    known: HashMap<(u64, u64), String>,
+}
+
+impl Digests {
+    fn new() -> Self {
+        Digests {
+            known: HashMap::new(),
+        }
+    }
+
+    fn digest(&mut self, file: &Path) -> Fallible<String> {
+        let id = file_id(&fs::metadata(file).unknown()?);
+        if let Some(digest) = id.and_then(|id| self.known.get(&id)) {
+            return Ok(digest.clone());
+        }
+
+        let digest = sha256_hex(&mut File::open(file).unknown()?)?;
+        if let Some(id) = id {
+            self.known.insert(id, digest.clone());
+        }
+        Ok(digest)
+    }
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+// NOTE: The file index that identifies a file on Windows is not yet available from
+//       the standard library, so every file is hashed.
+#[cfg(windows)]
+fn file_id(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(unix)]
+fn is_executable(file: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    // Follows symlinks, since the npm and npx binaries link into the npm package.
+    match file.metadata() {
+        Ok(metadata) => metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    }
+}
+
+#[cfg(windows)]
+fn is_executable(file: &Path) -> bool {
+    file.is_file()
+}
+
+/// Computes the `(digest, relative path)` manifest entries of every regular file in
+/// a directory, in order. Paths are relative to the directory, with `/` separators.
+pub(crate) fn manifest_entries(dir: &Path) -> Fallible<Vec<(String, String)>> {
+    let mut files = Vec::new();
+    collect_regular_files(dir, &mut files)?;
+    files.sort();
+
+    let mut entries = Vec::new();
+    for file in files {
+        let digest = sha256_hex(&mut File::open(&file).unknown()?)?;
+        let relative = file
+            .strip_prefix(dir)
+            .unknown()?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect::<Vec<_>>()
+            .join("/");
+        entries.push((digest, relative));
+    }
+    Ok(entries)
+}
+
+/// Records a manifest of files. Each line has the format of `sha256sum` output: the
+/// hex digest, two spaces, and the relative path.
+pub(crate) fn write_manifest_entries(
+    entries: &[(String, String)],
+    manifest: &Path,
+) -> Fallible<()> {
+    ensure_containing_dir_exists(&manifest)?;
+    let mut out = File::create(manifest).unknown()?;
+    for &(ref digest, ref relative) in entries {
+        writeln!(out, "{}  {}", digest, relative).unknown()?;
+    }
+    Ok(())
+}
+
+/// Reads the `(digest, relative path)` entries of a manifest, or `None` if no
+/// manifest exists.
+fn read_manifest(manifest: &Path) -> Fallible<Option<Vec<(String, String)>>> {
+    if !manifest.is_file() {
+        return Ok(None);
+    }
+
+    let mut entries = Vec::new();
+    for line in BufReader::new(File::open(manifest).unknown()?).lines() {
+        let line = line.unknown()?;
+        let mut parts = line.splitn(2, "  ");
+        if let (Some(digest), Some(relative)) = (parts.next(), parts.next()) {
+            entries.push((digest.to_string(), relative.to_string()));
+        }
+    }
+    Ok(Some(entries))
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{check_dir, manifest_entries, write_manifest_entries, Digests, Problem};
+    use std::fs::{create_dir_all, remove_file, File};
+    use std::io::Write;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn write_file(file: &Path, contents: &str) {
+        create_dir_all(file.parent().unwrap()).unwrap();
+        File::create(file).unwrap().write_all(contents.as_bytes()).unwrap();
+    }
+
+    fn write_manifest(dir: &Path, manifest: &Path) {
+        write_manifest_entries(&manifest_entries(dir).unwrap(), manifest).unwrap();
+    }
+
+    #[test]
+    fn test_check_dir() {
+        let root = tempdir().expect("Could not create temporary directory");
+        let dir = root.path().join("1.2.3");
+        let manifest = root.path().join("manifests").join("1.2.3.sha256");
+        write_file(&dir.join("lib").join("index.js"), "module.exports = 1;");
+        write_file(&dir.join("README.md"), "hello");
+
+        assert_eq!(
+            check_dir(&dir, &manifest, &dir, &[], &mut Digests::new()).unwrap(),
+            vec![Problem::NoManifest]
+        );
+
+        write_manifest(&dir, &manifest);
+        assert_eq!(check_dir(&dir, &manifest, &dir, &[], &mut Digests::new()).unwrap(), vec![]);
+
+        // Files added after install are not a problem.
+        write_file(&dir.join("lib").join("extra.js"), "");
+        assert_eq!(check_dir(&dir, &manifest, &dir, &[], &mut Digests::new()).unwrap(), vec![]);
+
+        write_file(&dir.join("lib").join("index.js"), "module.exports = 2;");
+        remove_file(dir.join("README.md")).unwrap();
+        assert_eq!(
+            check_dir(&dir, &manifest, &dir, &[], &mut Digests::new()).unwrap(),
+            vec![
+                Problem::Missing("README.md".to_string()),
+                Problem::Modified("lib/index.js".to_string()),
+            ]
+        );
+
+        let missing = root.path().join("4.5.6");
+        assert_eq!(
+            check_dir(&missing, &manifest, &missing, &[], &mut Digests::new()).unwrap(),
+            vec![Problem::Missing(missing.to_string_lossy().to_string())]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_check_dir_binaries() {
+        use std::fs::{metadata, set_permissions};
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = tempdir().expect("Could not create temporary directory");
+        let dir = root.path().join("1.2.3");
+        let manifest = root.path().join("1.2.3.sha256");
+        let bin = dir.join("bin");
+        write_file(&bin.join("tool"), "#!/bin/sh");
+        write_manifest(&dir, &manifest);
+
+        assert_eq!(
+            check_dir(&dir, &manifest, &bin, &["tool"], &mut Digests::new()).unwrap(),
+            vec![Problem::NotExecutable(bin.join("tool").to_string_lossy().to_string())]
+        );
+
+        let mut permissions = metadata(bin.join("tool")).unwrap().permissions();
+        permissions.set_mode(0o755);
+        set_permissions(bin.join("tool"), permissions).unwrap();
+        assert_eq!(
+            check_dir(&dir, &manifest, &bin, &["tool"], &mut Digests::new()).unwrap(),
+            vec![]
+        );
+    }
+}
@@ -13,6 +13,8 @@ use self::serial::parse_requirements;
 pub enum VersionSpec {
     Latest,
     Semver(VersionReq),
+    /// A name the user has defined for a version with `notion alias`.
+    Alias(String),
 }
 
 impl fmt::Display for VersionSpec {
@@ -20,6 +22,7 @@ impl fmt::Display for VersionSpec {
         match *self {
             VersionSpec::Latest => write!(f, "latest"),
             VersionSpec::Semver(ref req) => req.fmt(f),
+            VersionSpec::Alias(ref name) => write!(f, "{}", name),
         }
     }
 }
@@ -58,10 +61,25 @@ impl FromStr for VersionSpec {
             return Ok(VersionSpec::Latest);
         }
 
-        Ok(VersionSpec::Semver(parse_requirements(s)?))
+        match parse_requirements(s) {
+            Ok(req) => Ok(VersionSpec::Semver(req)),
+            Err(_) if is_alias_name(s) => Ok(VersionSpec::Alias(s.to_string())),
+            Err(error) => Err(error),
+        }
     }
 }
 
+/// Tests whether a name can be used as a version alias: it must start with a
+/// letter and contain only letters, digits, `-`, and `_`, so that it can never be
+/// mistaken for a version. `latest` is reserved.
+pub fn is_alias_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    let starts_with_letter = chars.next().map_or(false, |c| c.is_ascii_alphabetic());
+    starts_with_letter
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        && name != "latest"
+}
+
 #[derive(Debug, Fail, NotionFail)]
 #[fail(display = "{}", error)]
 #[notion_fail(code = "NoVersionMatch")]
@@ -82,3 +100,32 @@ impl VersionParseError {
         }
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{is_alias_name, VersionSpec};
+
+    #[test]
+    fn test_parse_alias() {
+        match VersionSpec::parse("work").unwrap() {
+            VersionSpec::Alias(name) => assert_eq!(name, "work"),
+            _ => panic!("expected an alias"),
+        }
+        match VersionSpec::parse("v14").unwrap() {
+            VersionSpec::Semver(_) => {}
+            _ => panic!("expected a version requirement"),
+        }
+        assert!(VersionSpec::parse("1.2.x.y").is_err());
+    }
+
+    #[test]
+    fn test_is_alias_name() {
+        assert!(is_alias_name("work"));
+        assert!(is_alias_name("legacy-14_lts"));
+        assert!(!is_alias_name("latest"));
+        assert!(!is_alias_name("14-legacy"));
+        assert!(!is_alias_name("my alias"));
+        assert!(!is_alias_name(""));
+    }
+}
@@ -0,0 +1,219 @@
+//! Provides notifications of installs and uninstalls sent to a configured webhook.
+//!
+//! Each notification is sent as a JSON object in the body of a POST request, once
+//! the command that caused it has finished. Failed requests are retried with
+//! exponential backoff, but every notification of a session shares a single time
+//! budget, so that a dead endpoint never holds up the tool being run by more than
+//! that.
+
+use std::cmp;
+use std::env;
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use reqwest::header::ContentType;
+use semver::Version;
+use serde_json;
+
+use config::WebhookConfig;
+use http;
+use logging;
+
+/// The number of milliseconds to wait before the first retry. Each retry after that
+/// waits twice as long as the one before.
+const INITIAL_BACKOFF_MILLIS: u64 = 100;
+
+/// The outcome of an install or uninstall, as reported to the webhook.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Notification {
+    /// The activity, either `install` or `uninstall`.
+    pub activity: &'static str,
+    /// The name of the tool, e.g. `node`.
+    pub tool: &'static str,
+    /// The version that was installed or uninstalled.
+    pub version: String,
+    /// The name of the machine the activity happened on.
+    pub host: String,
+    /// Either `success` or `failure`.
+    pub result: &'static str,
+}
+
+impl Notification {
+    /// Constructs a notification of an install.
+    pub(crate) fn install(tool: &'static str, version: &Version, succeeded: bool) -> Self {
+        Notification::new("install", tool, version, succeeded)
+    }
+
+    /// Constructs a notification of an uninstall.
+    pub(crate) fn uninstall(tool: &'static str, version: &Version, succeeded: bool) -> Self {
+        Notification::new("uninstall", tool, version, succeeded)
+    }
+
+    fn new(
+        activity: &'static str,
+        tool: &'static str,
+        version: &Version,
+        succeeded: bool,
+    ) -> Self {
+        Notification {
+            activity,
+            tool,
+            version: version.to_string(),
+            host: host_name(),
+            result: if succeeded { "success" } else { "failure" },
+        }
+    }
+}
+
+/// Produces the name of this machine, or `unknown` if it cannot be determined.
+fn host_name() -> String {
+    env::var("HOSTNAME")
+        .or_else(|_| env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| {
+            fs::read_to_string("/etc/hostname")
+                .ok()
+                .map(|contents| contents.trim().to_string())
+        })
+        .and_then(|name| if name.is_empty() { None } else { Some(name) })
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Sends notifications to the webhook one after another, giving up on any that are
+/// left once the configured time budget is spent. Failures are reported as warnings
+/// rather than errors, since the install or uninstall itself has already finished.
+pub(crate) fn notify_all(config: &WebhookConfig, notifications: &[Notification]) {
+    let deadline = Instant::now() + config.timeout;
+    for notification in notifications {
+        if let Err(error) = notify(config, notification, deadline) {
+            logging::warning(&format!(
+                "could not notify {} of the {} of {} v{}: {}",
+                config.url,
+                notification.activity,
+                notification.tool,
+                notification.version,
+                error
+            ));
+        }
+    }
+}
+
+/// Sends a notification, retrying up to the configured number of times until it
+/// succeeds or `deadline` passes. Each request only waits as long as is left of the
+/// budget.
+fn notify(
+    config: &WebhookConfig,
+    notification: &Notification,
+    deadline: Instant,
+) -> Result<(), String> {
+    let payload = serde_json::to_string(notification).map_err(|error| error.to_string())?;
+
+    let mut attempt = 0;
+    loop {
+        let left = match remaining(deadline) {
+            Some(left) => left,
+            None => return Err("ran out of time".to_string()),
+        };
+
+        let error = match post(&config.url, &payload, left) {
+            Ok(()) => return Ok(()),
+            Err(error) => error,
+        };
+
+        if attempt >= config.retries {
+            return Err(error);
+        }
+        match remaining(deadline) {
+            Some(left) if backoff(attempt) < left => thread::sleep(backoff(attempt)),
+            _ => return Err(error),
+        }
+        attempt += 1;
+    }
+}
+
+fn post(url: &str, payload: &str, timeout: Duration) -> Result<(), String> {
+    http::check_connect_within(url, timeout).map_err(|error| error.to_string())?;
+    let read_timeout = http::read_timeout().map_err(|error| error.to_string())?;
+    let client = http::client_builder()
+        .map_err(|error| error.to_string())?
+        .timeout(cmp::min(timeout, read_timeout))
+        .build()
+        .map_err(|error| error.to_string())?;
+    let response = client
+        .post(url)
+        .header(ContentType::json())
+        .body(payload.to_string())
+        .send()
+        .map_err(|error| error.to_string())?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("the server responded with {}", response.status()))
+    }
+}
+
+/// Produces the time left before `deadline`, if there is any.
+fn remaining(deadline: Instant) -> Option<Duration> {
+    let now = Instant::now();
+    if now < deadline {
+        Some(deadline - now)
+    } else {
+        None
+    }
+}
+
+/// Produces how long to wait after the failed attempt numbered `attempt` (from 0).
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_millis(INITIAL_BACKOFF_MILLIS << attempt.min(16))
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{backoff, notify_all, Notification};
+    use config::WebhookConfig;
+    use semver::Version;
+    use serde_json;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(0), Duration::from_millis(100));
+        assert_eq!(backoff(1), Duration::from_millis(200));
+        assert_eq!(backoff(3), Duration::from_millis(800));
+    }
+
+    #[test]
+    fn test_payload() {
+        let mut notification = Notification::install("node", &Version::new(8, 9, 4), true);
+        notification.host = "build-01".to_string();
+        assert_eq!(
+            serde_json::to_string(&notification).unwrap(),
+            "{\"activity\":\"install\",\"tool\":\"node\",\"version\":\"8.9.4\",\
+             \"host\":\"build-01\",\"result\":\"success\"}"
+        );
+        assert_eq!(
+            Notification::uninstall("yarn", &Version::new(1, 2, 0), false).result,
+            "failure"
+        );
+    }
+
+    #[test]
+    fn test_notify_all_keeps_to_budget() {
+        // Nothing listens on the discard port, so every attempt fails.
+        let config = WebhookConfig {
+            url: "http://127.0.0.1:9/".to_string(),
+            timeout: Duration::from_millis(500),
+            retries: 100,
+        };
+        let notifications = vec![
+            Notification::install("node", &Version::new(8, 9, 4), true),
+            Notification::install("yarn", &Version::new(1, 2, 0), true),
+        ];
+
+        let start = Instant::now();
+        notify_all(&config, &notifications);
+        assert!(start.elapsed() < Duration::from_millis(1500));
+    }
+}
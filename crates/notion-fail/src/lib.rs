@@ -22,6 +22,8 @@
 //!
 //! The `NotionFail::exit_code()` method allows each error type to indicate what the
 //! process exit code should be if the error is the reason for exiting Notion.
+//! `ExitCode` also explains each code and how to recover from it, which is what
+//! `notion explain` prints.
 //!
 //! # The `NotionError` type and `Fallible` functions
 //!
@@ -259,7 +261,7 @@ macro_rules! throw {
 }
 
 /// Exit codes supported by the NotionFail trait.
-#[derive(Copy, Clone, Debug, Serialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum ExitCode {
     /// No error occurred.
     Success = 0,
@@ -288,6 +290,12 @@ pub enum ExitCode {
     /// The command or feature is not yet implemented.
     NotYetImplemented = 9,
 
+    /// The toolchain deviates from the project's lockfile.
+    LockfileMismatch = 10,
+
+    /// A network operation timed out.
+    NetworkTimeout = 11,
+
     /// The requested executable could not be run.
     ExecutionFailure = 126,
 
@@ -296,9 +304,146 @@ pub enum ExitCode {
 }
 
 impl ExitCode {
+    /// Every exit code, in numeric order. Wrappers can rely on these values, which never
+    /// change meaning once released.
+    pub const ALL: [ExitCode; 13] = [
+        ExitCode::Success,
+        ExitCode::UnknownError,
+        ExitCode::InvalidArguments,
+        ExitCode::NoVersionMatch,
+        ExitCode::NetworkError,
+        ExitCode::EnvironmentError,
+        ExitCode::FileSystemError,
+        ExitCode::ConfigurationError,
+        ExitCode::NotYetImplemented,
+        ExitCode::LockfileMismatch,
+        ExitCode::NetworkTimeout,
+        ExitCode::ExecutionFailure,
+        ExitCode::ExecutableNotFound,
+    ];
+
     pub fn exit(self) -> ! {
         exit(self as i32);
     }
+
+    /// Returns the numeric value of the exit code.
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    /// Returns the name of the exit code, which is the error kind given by the
+    /// `#[notion_fail(code = "...")]` attribute of the errors that exit with it.
+    pub fn name(self) -> &'static str {
+        match self {
+            ExitCode::Success => "Success",
+            ExitCode::UnknownError => "UnknownError",
+            ExitCode::InvalidArguments => "InvalidArguments",
+            ExitCode::NoVersionMatch => "NoVersionMatch",
+            ExitCode::NetworkError => "NetworkError",
+            ExitCode::EnvironmentError => "EnvironmentError",
+            ExitCode::FileSystemError => "FileSystemError",
+            ExitCode::ConfigurationError => "ConfigurationError",
+            ExitCode::NotYetImplemented => "NotYetImplemented",
+            ExitCode::LockfileMismatch => "LockfileMismatch",
+            ExitCode::NetworkTimeout => "NetworkTimeout",
+            ExitCode::ExecutionFailure => "ExecutionFailure",
+            ExitCode::ExecutableNotFound => "ExecutableNotFound",
+        }
+    }
+
+    /// Explains what went wrong when Notion exits with this code.
+    pub fn explanation(self) -> &'static str {
+        match self {
+            ExitCode::Success => "No error occurred.",
+            ExitCode::UnknownError => "An internal error occurred that Notion did not expect.",
+            ExitCode::InvalidArguments => {
+                "An invalid combination of command-line arguments was supplied."
+            }
+            ExitCode::NoVersionMatch => {
+                "No version matching the requested version or range could be found, or no \
+                 toolchain selects a version of the tool."
+            }
+            ExitCode::NetworkError => "A network request failed or returned an error.",
+            ExitCode::EnvironmentError => {
+                "A required environment variable was unset or invalid."
+            }
+            ExitCode::FileSystemError => "A file or directory could not be read or written.",
+            ExitCode::ConfigurationError => {
+                "A configuration file (such as config.toml, package.json, or notion.toml) is \
+                 missing or incorrect."
+            }
+            ExitCode::NotYetImplemented => "The command or feature is not yet implemented.",
+            ExitCode::LockfileMismatch => {
+                "The toolchain in use, or the versions the project pins, no longer match the \
+                 project's notion.lock."
+            }
+            ExitCode::NetworkTimeout => {
+                "A server could not be connected to, or stopped sending data, within the \
+                 configured timeout."
+            }
+            ExitCode::ExecutionFailure => {
+                "The requested executable could not be run, or (for `notion run`) it failed."
+            }
+            ExitCode::ExecutableNotFound => "The requested executable is not available.",
+        }
+    }
+
+    /// Suggests how to recover from an exit with this code.
+    pub fn remedy(self) -> &'static str {
+        match self {
+            ExitCode::Success => "Nothing needs to be done.",
+            ExitCode::UnknownError => {
+                "Run again with NOTION_DEV=1 and RUST_BACKTRACE=1 for details, and report \
+                 the problem at https://github.com/notion-cli/notion/issues."
+            }
+            ExitCode::InvalidArguments => {
+                "Check the usage printed with the error, or run `notion help <command>`."
+            }
+            ExitCode::NoVersionMatch => {
+                "Check the version with `notion search`, or select one with `notion use` or \
+                 `notion install`."
+            }
+            ExitCode::NetworkError => {
+                "Check your connection and any proxy settings, then try again."
+            }
+            ExitCode::EnvironmentError => {
+                "Check the variable named in the error; shell integration may need to be \
+                 set up again with `notion setup`."
+            }
+            ExitCode::FileSystemError => {
+                "Check the permissions and free space of the path named in the error, and \
+                 run `notion verify` to check installed versions."
+            }
+            ExitCode::ConfigurationError => {
+                "Fix the file named in the error; `notion why` shows which files select the \
+                 current toolchain."
+            }
+            ExitCode::NotYetImplemented => "Use a workaround until a later Notion release.",
+            ExitCode::LockfileMismatch => {
+                "Run `notion lock` to lock the pinned versions again, or undo the change to \
+                 the pins or the environment that `notion verify --locked` reports."
+            }
+            ExitCode::NetworkTimeout => {
+                "Check your connection and try again, or raise network.connect-timeout or \
+                 network.read-timeout for a slow network."
+            }
+            ExitCode::ExecutionFailure => {
+                "Check that the executable exists and can be run; for `notion run`, see the \
+                 summary of the versions that failed."
+            }
+            ExitCode::ExecutableNotFound => {
+                "Install the tool, or check `notion which <tool>` for where it was expected."
+            }
+        }
+    }
+
+    /// Looks up an exit code by its numeric value or its name (ignoring case).
+    pub fn lookup(src: &str) -> Option<ExitCode> {
+        ExitCode::ALL.iter().cloned().find(|code| {
+            src.parse::<i32>().ok() == Some(code.code())
+                || code.name().eq_ignore_ascii_case(src)
+        })
+    }
 }
 
 /// The failure trait for all Notion errors.
@@ -0,0 +1,69 @@
+/*
+ * C bindings for resolving Notion toolchains in-process (libnotion_ffi).
+ *
+ * Every function but notion_abi_version and notion_string_free returns a
+ * NUL-terminated UTF-8 JSON document, which is either
+ *
+ *     {"ok": <result>}
+ *
+ * or, if the call failed,
+ *
+ *     {"error": {"code": <code>, "name": <name>, "message": <message>}}
+ *
+ * where <code> and <name> are those of the exit code the `notion` executable
+ * would exit with (see `notion explain --all`). The caller owns the document,
+ * and must free it with notion_string_free. NULL is only returned if the
+ * document could not be allocated.
+ *
+ * Functions are only ever added to this ABI, never changed; if that ever has
+ * to be broken, notion_abi_version returns a new version.
+ */
+
+#ifndef NOTION_H
+#define NOTION_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define NOTION_ABI_VERSION 1
+
+/* Returns the version of the ABI the library implements. */
+uint32_t notion_abi_version(void);
+
+/*
+ * Resolves the tools in effect in the directory `dir`. The result is
+ * {"node": {"version": ..., "source": ...}, "yarn": ...}, where "source" is
+ * "env", "project", "directory", or "user", and "yarn" is null if no Yarn
+ * version is in effect; or null if no Node version is.
+ */
+char *notion_resolve_platform(const char *dir);
+
+/*
+ * Finds the Node project containing the directory `dir`. The result is
+ * {"root": ..., "pin_file": ..., "pinned": ...}, or null if there is none.
+ */
+char *notion_project(const char *dir);
+
+/*
+ * Lists the installed versions of `tool`, which is "node" or "yarn". The
+ * result is an array of version strings, oldest first.
+ */
+char *notion_installed_versions(const char *tool);
+
+/*
+ * Tests whether `version` of `tool`, which is "node" or "yarn", is installed.
+ * The result is true or false.
+ */
+char *notion_is_installed(const char *tool, const char *version);
+
+/* Frees a document returned by any other function. Does nothing given NULL. */
+void notion_string_free(char *document);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* NOTION_H */
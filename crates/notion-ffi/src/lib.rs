@@ -0,0 +1,224 @@
+//! C bindings for resolving Notion toolchains in-process, for editor and IDE plugins
+//! written in languages other than Rust. They wrap the stable API of notion-core
+//! (`notion_core::api`), and are declared in `include/notion.h`.
+//!
+//! Every function that produces a result returns a NUL-terminated UTF-8 JSON document,
+//! which is either `{"ok": <result>}` or, if the call failed,
+//! `{"error": {"code": <code>, "name": <name>, "message": <message>}}`, where the code
+//! and name are those of the exit code the `notion` executable would exit with (see
+//! `notion explain --all`). The caller owns the document, and frees it with
+//! `notion_string_free`. A panic never unwinds into the caller, but is reported as an
+//! `UnknownError`.
+//!
+//! The ABI is stable: functions are only added, never changed, and
+//! `notion_abi_version` produces a new version if that ever has to be broken.
+
+extern crate notion_core;
+extern crate semver;
+#[macro_use]
+extern crate serde_json;
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+
+use notion_core::api::{self, ExitCode, Fallible, Resolved};
+use semver::Version;
+use serde_json::Value;
+
+/// The version of the ABI, as produced by `notion_abi_version`.
+const ABI_VERSION: u32 = 1;
+
+/// Produces the version of the ABI, which only changes if a function is changed
+/// incompatibly.
+#[no_mangle]
+pub extern "C" fn notion_abi_version() -> u32 {
+    ABI_VERSION
+}
+
+/// Resolves the tools in effect in a directory, producing
+/// `{"node": {"version": ..., "source": ...}, "yarn": ...}`, where `yarn` is `null`
+/// if no Yarn version is in effect, or `null` if no Node version is.
+#[no_mangle]
+pub unsafe extern "C" fn notion_resolve_platform(dir: *const c_char) -> *mut c_char {
+    respond(|| {
+        let dir = string_arg("dir", dir)?;
+        Ok(match notion(api::platform(Path::new(dir)))? {
+            Some(platform) => json!({
+                "node": resolved(platform.node()),
+                "yarn": platform.yarn().map(resolved),
+            }),
+            None => Value::Null,
+        })
+    })
+}
+
+/// Finds the Node project containing a directory, producing
+/// `{"root": ..., "pin_file": ..., "pinned": ...}`, or `null` if there is none.
+#[no_mangle]
+pub unsafe extern "C" fn notion_project(dir: *const c_char) -> *mut c_char {
+    respond(|| {
+        let dir = string_arg("dir", dir)?;
+        Ok(match notion(api::project(Path::new(dir)))? {
+            Some(project) => json!({
+                "root": project.root().to_string_lossy(),
+                "pin_file": project.pin_file().to_string_lossy(),
+                "pinned": project.is_pinned(),
+            }),
+            None => Value::Null,
+        })
+    })
+}
+
+/// Lists the installed versions of a tool (`node` or `yarn`), oldest first.
+#[no_mangle]
+pub unsafe extern "C" fn notion_installed_versions(tool: *const c_char) -> *mut c_char {
+    respond(|| {
+        let versions = match tool_arg(tool)? {
+            Tool::Node => notion(api::installed_node_versions())?,
+            Tool::Yarn => notion(api::installed_yarn_versions())?,
+        };
+        let versions: Vec<String> = versions.iter().map(Version::to_string).collect();
+        Ok(json!(versions))
+    })
+}
+
+/// Tests whether a version of a tool (`node` or `yarn`) is installed, producing `true`
+/// or `false`.
+#[no_mangle]
+pub unsafe extern "C" fn notion_is_installed(
+    tool: *const c_char,
+    version: *const c_char,
+) -> *mut c_char {
+    respond(|| {
+        let tool = tool_arg(tool)?;
+        let version = string_arg("version", version)?;
+        let version = match Version::parse(version.trim_left_matches('v')) {
+            Ok(version) => version,
+            Err(_) => {
+                return Err(error(
+                    ExitCode::InvalidArguments,
+                    format!("'{}' is not a version", version),
+                ))
+            }
+        };
+        let installed = match tool {
+            Tool::Node => notion(api::is_node_installed(&version))?,
+            Tool::Yarn => notion(api::is_yarn_installed(&version))?,
+        };
+        Ok(Value::Bool(installed))
+    })
+}
+
+/// Frees a document produced by any of the other functions. Does nothing if given
+/// `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn notion_string_free(document: *mut c_char) {
+    if !document.is_null() {
+        drop(CString::from_raw(document));
+    }
+}
+
+/// The tools whose versions can be queried.
+enum Tool {
+    Node,
+    Yarn,
+}
+
+/// Runs the body of a function, producing its result or error as a JSON document.
+fn respond<F>(body: F) -> *mut c_char
+where
+    F: FnOnce() -> Result<Value, Value>,
+{
+    let document = match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(result)) => json!({ "ok": result }),
+        Ok(Err(failure)) => failure,
+        Err(_) => error(ExitCode::UnknownError, "Notion panicked".to_string()),
+    };
+    // JSON escapes NUL characters, so the document never contains one
+    CString::new(document.to_string())
+        .map(CString::into_raw)
+        .unwrap_or(ptr::null_mut())
+}
+
+/// Produces the document for an error.
+fn error(code: ExitCode, message: String) -> Value {
+    json!({
+        "error": {
+            "code": code.code(),
+            "name": code.name(),
+            "message": message,
+        }
+    })
+}
+
+/// Converts a Notion failure into the document for its error.
+fn notion<T>(result: Fallible<T>) -> Result<T, Value> {
+    result.map_err(|failure| error(failure.exit_code(), failure.to_string()))
+}
+
+fn resolved(resolved: &Resolved) -> Value {
+    json!({
+        "version": resolved.version().to_string(),
+        "source": resolved.source().to_string(),
+    })
+}
+
+/// Reads a string argument, which must not be `NULL` and must be UTF-8.
+unsafe fn string_arg<'a>(name: &str, arg: *const c_char) -> Result<&'a str, Value> {
+    if arg.is_null() {
+        return Err(error(ExitCode::InvalidArguments, format!("{} is NULL", name)));
+    }
+    CStr::from_ptr(arg).to_str().map_err(|_| {
+        error(ExitCode::InvalidArguments, format!("{} is not UTF-8", name))
+    })
+}
+
+unsafe fn tool_arg(arg: *const c_char) -> Result<Tool, Value> {
+    match string_arg("tool", arg)? {
+        "node" => Ok(Tool::Node),
+        "yarn" => Ok(Tool::Yarn),
+        tool => Err(error(
+            ExitCode::InvalidArguments,
+            format!("Unknown tool '{}': expected node or yarn", tool),
+        )),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    unsafe fn document(ptr: *mut c_char) -> Value {
+        let document = serde_json::from_str(CStr::from_ptr(ptr).to_str().unwrap()).unwrap();
+        notion_string_free(ptr);
+        document
+    }
+
+    #[test]
+    fn test_null_argument() {
+        let document = unsafe { document(notion_resolve_platform(ptr::null())) };
+        assert_eq!(document["error"]["name"], "InvalidArguments");
+        assert_eq!(document["error"]["code"], ExitCode::InvalidArguments.code());
+        assert_eq!(document["error"]["message"], "dir is NULL");
+    }
+
+    #[test]
+    fn test_unknown_tool() {
+        let tool = CString::new("npm").unwrap();
+        let document = unsafe { document(notion_installed_versions(tool.as_ptr())) };
+        assert_eq!(
+            document["error"]["message"],
+            "Unknown tool 'npm': expected node or yarn"
+        );
+    }
+
+    #[test]
+    fn test_panic() {
+        let document = unsafe { document(respond(|| panic!("resolving failed"))) };
+        assert_eq!(document["error"]["name"], "UnknownError");
+    }
+}
@@ -0,0 +1,220 @@
+//! Provides a minimal JSON reader, which is just enough to find the `toolchain`
+//! section of a `package.json` file.
+
+/// A JSON value. Numbers and booleans are kept as their source text, since the
+/// dispatcher never needs their values.
+#[derive(Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Literal(String),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Returns the member of an object with the given key, if any.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            &Value::Object(ref members) => members
+                .iter()
+                .rev()
+                .find(|&&(ref name, _)| name == key)
+                .map(|&(_, ref value)| value),
+            _ => None,
+        }
+    }
+
+    /// Returns the contents of a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            &Value::String(ref s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a JSON document, producing `None` if it is malformed.
+pub fn parse(src: &str) -> Option<Value> {
+    let mut parser = Parser {
+        chars: src.chars().collect(),
+        pos: 0,
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos == parser.chars.len() {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Option<()> {
+        self.skip_whitespace();
+        if self.next()? == expected {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn value(&mut self) -> Option<Value> {
+        self.skip_whitespace();
+        match self.peek()? {
+            '{' => self.object(),
+            '[' => self.array(),
+            '"' => self.string().map(Value::String),
+            _ => self.literal(),
+        }
+    }
+
+    fn object(&mut self) -> Option<Value> {
+        self.expect('{')?;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.peek()? == '}' {
+            self.pos += 1;
+            return Some(Value::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.expect(':')?;
+            let value = self.value()?;
+            members.push((key, value));
+            self.skip_whitespace();
+            match self.next()? {
+                ',' => continue,
+                '}' => return Some(Value::Object(members)),
+                _ => return None,
+            }
+        }
+    }
+
+    fn array(&mut self) -> Option<Value> {
+        self.expect('[')?;
+        let mut elements = Vec::new();
+        self.skip_whitespace();
+        if self.peek()? == ']' {
+            self.pos += 1;
+            return Some(Value::Array(elements));
+        }
+        loop {
+            elements.push(self.value()?);
+            self.skip_whitespace();
+            match self.next()? {
+                ',' => continue,
+                ']' => return Some(Value::Array(elements)),
+                _ => return None,
+            }
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        if self.next()? != '"' {
+            return None;
+        }
+        let mut s = String::new();
+        loop {
+            match self.next()? {
+                '"' => return Some(s),
+                '\\' => match self.next()? {
+                    'b' => s.push('\u{8}'),
+                    'f' => s.push('\u{c}'),
+                    'n' => s.push('\n'),
+                    'r' => s.push('\r'),
+                    't' => s.push('\t'),
+                    'u' => {
+                        let hex: String = (0..4).filter_map(|_| self.next()).collect();
+                        let code = u32::from_str_radix(&hex, 16).ok()?;
+                        // surrogate pairs never occur in the keys and versions the
+                        // dispatcher reads, so they are replaced rather than combined
+                        s.push(::std::char::from_u32(code).unwrap_or('\u{fffd}'));
+                    }
+                    c => s.push(c),
+                },
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn literal(&mut self) -> Option<Value> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_alphanumeric() || c == '-' || c == '+' || c == '.' {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        let literal: String = self.chars[start..self.pos].iter().collect();
+        match &literal[..] {
+            "" => None,
+            "null" => Some(Value::Null),
+            _ => Some(Value::Literal(literal)),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let manifest = parse(
+            r#"{
+                "name": "app",
+                "version": "1.0.0",
+                "private": true,
+                "files": ["lib", "bin"],
+                "toolchain": { "node": "18.17.1", "yarn": "1.22.19" },
+                "description": "an \"app\" é"
+            }"#,
+        ).unwrap();
+
+        let toolchain = manifest.get("toolchain").unwrap();
+        assert_eq!(toolchain.get("node").and_then(Value::as_str), Some("18.17.1"));
+        assert_eq!(toolchain.get("yarn").and_then(Value::as_str), Some("1.22.19"));
+        assert_eq!(toolchain.get("npm"), None);
+        assert_eq!(
+            manifest.get("description").and_then(Value::as_str),
+            Some("an \"app\" \u{e9}")
+        );
+        assert_eq!(manifest.get("private"), Some(&Value::Literal("true".to_string())));
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("{\"toolchain\": }"), None);
+        assert_eq!(parse("{\"node\": \"18\"} trailing"), None);
+        assert_eq!(parse("[1, 2"), None);
+    }
+}
@@ -0,0 +1,448 @@
+//! The minimal dispatcher behind the `node` and `yarn` shims.
+//!
+//! Every invocation of `node` or `yarn` goes through a shim, so the time it takes to
+//! start one is paid over and over. This crate handles the common case, a trusted
+//! project that pins exact versions which are already installed, without any
+//! dependencies: it reads the pin, builds the `PATH`, and replaces itself with the
+//! tool. Anything else (projects that aren't trusted, version requirements, versions
+//! that need to be fetched, lockfiles whose checksums have to be verified, user
+//! configuration or hooks that could change the outcome) is handed off to the full
+//! dispatcher, `launchnode` or `launchyarn`, with the same arguments.
+
+mod json;
+
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Embeds metadata identifying the kind of shim executable and the version of Notion
+/// it was built with, in the same format as `notion_core::shim_metadata!`, so that
+/// `notion shim audit` recognizes these shims too. This must be invoked once in each
+/// shim executable's crate root.
+#[macro_export]
+macro_rules! shim_metadata {
+    ($kind:expr) => {
+        #[used]
+        static SHIM_METADATA: &'static str = concat!(
+            "notion-shim-metadata:",
+            $kind,
+            ":",
+            env!("CARGO_PKG_VERSION"),
+            ";"
+        );
+    };
+}
+
+/// The exit code for failing to start the tool, matching `ExitCode::ExecutionFailure`.
+const EXECUTION_FAILURE: i32 = 126;
+
+/// The environment variables that override the project's pinned versions.
+const VERSION_OVERRIDES: [&'static str; 3] = [
+    "NOTION_NODE_VERSION",
+    "NOTION_NPM_VERSION",
+    "NOTION_YARN_VERSION",
+];
+
+/// The name of the marker file that makes the directory it is in a portable
+/// installation, as in `notion_core::path::layout`.
+const PORTABLE_FILE: &'static str = "portable";
+
+/// The file the XDG layout's version is recorded in once `~/.notion` has been moved
+/// into it, as in `notion_core::path::xdg`.
+#[cfg(target_os = "linux")]
+const LAYOUT_VERSION_FILE: &'static str = "layout-version";
+
+/// The root directories of a Notion installation, as far as the dispatcher needs them.
+/// These mirror `notion_core::path::Layout`.
+struct Home {
+    /// The directory the full dispatchers are installed in.
+    install_root: PathBuf,
+    /// The directory installed versions and their usage records are kept in.
+    data_root: PathBuf,
+    /// The directory the configuration and hooks files are kept in.
+    user_root: PathBuf,
+    shim_dir: PathBuf,
+}
+
+impl Home {
+    /// The layout that keeps everything under a single directory.
+    fn in_home(root: PathBuf) -> Home {
+        Home {
+            install_root: root.clone(),
+            data_root: root.clone(),
+            user_root: root.clone(),
+            shim_dir: root.join("bin"),
+        }
+    }
+
+    /// Locates the installation the way `notion_core::path::layout` does: from
+    /// `NOTION_HOME`, or else the portable installation the shim belongs to, or else in
+    /// `~/.notion` or the XDG base directories it has been moved to. `NOTION_SHIM_DIR`
+    /// relocates the shim directory.
+    fn current() -> Option<Home> {
+        let mut home = match env::var_os("NOTION_HOME") {
+            Some(home) => Home::in_home(PathBuf::from(home)),
+            None => match portable_root() {
+                Some(root) => Home::in_home(root),
+                None => Home::for_user(&env::home_dir()?),
+            },
+        };
+        if let Some(dir) = env::var_os("NOTION_SHIM_DIR") {
+            home.shim_dir = PathBuf::from(dir);
+        }
+        Some(home)
+    }
+
+    /// The layout of the user's own files: the XDG layout once layout version 2 is
+    /// chosen and `~/.notion` has been moved into it, or else `~/.notion`. Until it is
+    /// moved, which the full dispatcher does, everything is still in `~/.notion`.
+    #[cfg(target_os = "linux")]
+    fn for_user(home: &Path) -> Home {
+        let user_root = xdg_dir("XDG_CONFIG_HOME", home, ".config").join("notion");
+        let recorded = fs::read_to_string(user_root.join(LAYOUT_VERSION_FILE));
+        let moved = recorded.map(|version| version.trim() == "2").unwrap_or(false);
+        let chosen = match env::var("NOTION_LAYOUT_VERSION") {
+            Ok(version) => version.trim() == "2",
+            Err(_) => moved,
+        };
+        if !(moved && chosen) {
+            return Home::in_home(home.join(".notion"));
+        }
+
+        let data_root = xdg_dir("XDG_DATA_HOME", home, ".local/share").join("notion");
+        Home {
+            install_root: data_root.clone(),
+            shim_dir: data_root.join("bin"),
+            data_root,
+            user_root,
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn for_user(home: &Path) -> Home {
+        Home::in_home(home.join(".notion"))
+    }
+
+    /// The full dispatcher for a tool.
+    fn launcher(&self, tool: &str) -> PathBuf {
+        let name = format!("launch{}{}", tool, env::consts::EXE_SUFFIX);
+        self.install_root.join(name)
+    }
+
+    fn version_bin_dir(&self, tool: &str, version: &str) -> PathBuf {
+        self.data_root.join("versions").join(tool).join(version).join("bin")
+    }
+
+    fn used_file(&self, tool: &str, version: &str) -> PathBuf {
+        self.data_root.join("used").join(tool).join(version)
+    }
+
+    /// Tests whether the user has trusted a project to pin its toolchain with `notion
+    /// trust`, as recorded for the project's directory or its nearest ancestor in the
+    /// trust store (see `notion_core::trust`). Trust can also come from the `trust`
+    /// settings, but any configuration already sends the shim to the full dispatcher.
+    fn is_trusted(&self, root: &Path) -> bool {
+        let decisions = match fs::read_to_string(self.user_root.join("trusted-projects")) {
+            Ok(src) => src,
+            Err(_) => return false,
+        };
+        let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+        root.ancestors()
+            .filter_map(|dir| decision_for(&decisions, dir))
+            .next()
+            .unwrap_or(false)
+    }
+
+    /// Tests whether the user has configuration that the full dispatcher has to take
+    /// into account. A configuration file with nothing but comments doesn't count.
+    fn is_configured(&self) -> bool {
+        if self.user_root.join("hooks.toml").exists() {
+            return true;
+        }
+        match fs::read_to_string(self.user_root.join("config.toml")) {
+            Ok(src) => src
+                .lines()
+                .map(str::trim)
+                .any(|line| !line.is_empty() && !line.starts_with('#')),
+            Err(_) => false,
+        }
+    }
+}
+
+/// The exact versions a project pins.
+#[derive(Debug, PartialEq)]
+struct Pin {
+    node: String,
+    yarn: Option<String>,
+}
+
+/// Runs `tool` with the arguments the shim was invoked with, never returning.
+pub fn dispatch(tool: &str) -> ! {
+    let home = match Home::current() {
+        Some(home) => home,
+        None => {
+            eprintln!("Notion error: could not determine the home directory");
+            process::exit(EXECUTION_FAILURE);
+        }
+    };
+    let args: Vec<OsString> = env::args_os().skip(1).collect();
+
+    if bypassed() {
+        exec(Command::new(tool).args(&args).env("PATH", system_path(&home)));
+    }
+
+    match fast_path(&home, tool) {
+        Some(bins) => {
+            let mut command = Command::new(tool);
+            command.args(&args).env("PATH", image_path(&home, bins));
+            exec(&mut command)
+        }
+        None => exec(Command::new(home.launcher(tool)).args(&args)),
+    }
+}
+
+/// Finds the portable installation the shim belongs to: the directory above the `bin/`
+/// it is in (or, as in `notion_core::path::layout`, the one it is in), if that
+/// directory has a `portable` marker file.
+fn portable_root() -> Option<PathBuf> {
+    let exe = env::current_exe().ok()?;
+    exe.ancestors()
+        .skip(1)
+        .take(2)
+        .find(|dir| dir.join(PORTABLE_FILE).is_file())
+        .map(Path::to_path_buf)
+}
+
+/// Produces an XDG base directory: the one its variable names, if that is an absolute
+/// path as the specification requires, or else the default within the home directory.
+#[cfg(target_os = "linux")]
+fn xdg_dir(var: &str, home: &Path, default: &str) -> PathBuf {
+    match env::var_os(var).map(PathBuf::from) {
+        Some(ref dir) if dir.is_absolute() => dir.clone(),
+        _ => home.join(default),
+    }
+}
+
+/// Determines the directories to run the tool from, if the project pins exact
+/// versions that are installed and nothing else could affect the choice.
+#[cfg(unix)]
+fn fast_path(home: &Home, tool: &str) -> Option<Vec<PathBuf>> {
+    if home.is_configured() || VERSION_OVERRIDES.iter().any(|name| is_set(name)) {
+        return None;
+    }
+
+    let cwd = env::current_dir().ok()?;
+    let root = project_root(&cwd)?;
+    // a project's own toolchain file, pre-run hook, and lockfile, whose checksums the
+    // installed versions are verified against, are left to the full dispatcher
+    if ["notion.toml", "hooks.toml", "notion.lock"]
+        .iter()
+        .any(|file| root.join(file).exists())
+    {
+        return None;
+    }
+    if !home.is_trusted(root) {
+        return None;
+    }
+    let pin = read_pin(&fs::read_to_string(root.join("package.json")).ok()?)?;
+    if tool == "yarn" && pin.yarn.is_none() {
+        return None;
+    }
+
+    let mut bins = vec![home.version_bin_dir("node", &pin.node)];
+    if let Some(ref yarn) = pin.yarn {
+        bins.push(home.version_bin_dir("yarn", yarn));
+    }
+    if !bins.iter().all(|bin| bin.is_dir()) {
+        return None;
+    }
+
+    record_use(&home.used_file("node", &pin.node));
+    if let Some(ref yarn) = pin.yarn {
+        record_use(&home.used_file("yarn", yarn));
+    }
+    Some(bins)
+}
+
+/// The fast path is only taken on Unix, where the shim can replace itself with the tool.
+#[cfg(not(unix))]
+fn fast_path(_home: &Home, _tool: &str) -> Option<Vec<PathBuf>> {
+    None
+}
+
+/// Finds the nearest directory containing a `package.json`, starting from `dir`.
+fn project_root(dir: &Path) -> Option<&Path> {
+    dir.ancestors().find(|dir| dir.join("package.json").is_file())
+}
+
+/// Reads the `toolchain` section of a `package.json`, producing `None` unless it pins
+/// an exact Node version and at most an exact Yarn version. Pins of npm, version
+/// requirements, and anything else are left to the full dispatcher.
+fn read_pin(manifest: &str) -> Option<Pin> {
+    let manifest = json::parse(manifest)?;
+    let toolchain = match manifest.get("toolchain")? {
+        &json::Value::Object(ref members) => members,
+        _ => return None,
+    };
+
+    let mut pin = Pin {
+        node: String::new(),
+        yarn: None,
+    };
+    for &(ref key, ref value) in toolchain {
+        let version = value.as_str().filter(|version| is_exact(version))?;
+        match &key[..] {
+            "node" => pin.node = version.to_string(),
+            "yarn" => pin.yarn = Some(version.to_string()),
+            _ => return None,
+        }
+    }
+
+    if pin.node.is_empty() {
+        None
+    } else {
+        Some(pin)
+    }
+}
+
+/// Finds the decision recorded for exactly `dir` in the trust store's lines of
+/// `trusted <dir>` and `declined <dir>`: true if it was trusted, false if declined.
+fn decision_for(decisions: &str, dir: &Path) -> Option<bool> {
+    decisions
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, ' ');
+            match (parts.next(), parts.next()) {
+                (Some("trusted"), Some(decided)) if Path::new(decided) == dir => Some(true),
+                (Some("declined"), Some(decided)) if Path::new(decided) == dir => Some(false),
+                _ => None,
+            }
+        })
+        .next()
+}
+
+/// Tests whether a version is an exact release version like `18.17.1`.
+fn is_exact(version: &str) -> bool {
+    let parts: Vec<&str> = version.split('.').collect();
+    parts.len() == 3
+        && parts
+            .iter()
+            .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Records that a version is being used now, for `notion compress`. This is best
+/// effort, since failing to record it shouldn't keep the tool from running.
+fn record_use(file: &Path) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    if let Some(dir) = file.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    if let Ok(mut file) = File::create(file) {
+        let _ = write!(file, "{}", now);
+    }
+}
+
+fn is_set(name: &str) -> bool {
+    env::var_os(name).map_or(false, |value| !value.is_empty())
+}
+
+/// Tests whether `NOTION_BYPASS` is set to anything other than `0` or the empty string.
+fn bypassed() -> bool {
+    match env::var_os("NOTION_BYPASS") {
+        Some(value) => !value.is_empty() && value != "0",
+        None => false,
+    }
+}
+
+/// The `PATH` with the shim directory removed.
+fn system_path(home: &Home) -> OsString {
+    image_path(home, Vec::new())
+}
+
+/// The `PATH` with the shim directory removed and `bins` prepended.
+fn image_path(home: &Home, bins: Vec<PathBuf>) -> OsString {
+    let old_path = env::var_os("PATH").unwrap_or_default();
+    let rest = env::split_paths(&old_path).filter(|dir| *dir != home.shim_dir);
+    env::join_paths(bins.into_iter().chain(rest)).unwrap_or(old_path)
+}
+
+#[cfg(unix)]
+fn exec(command: &mut Command) -> ! {
+    use std::os::unix::process::CommandExt;
+
+    let error = command.exec();
+    eprintln!("Notion error: could not execute {:?}: {}", command, error);
+    process::exit(EXECUTION_FAILURE)
+}
+
+#[cfg(not(unix))]
+fn exec(command: &mut Command) -> ! {
+    match command.status() {
+        Ok(status) => process::exit(status.code().unwrap_or(1)),
+        Err(error) => {
+            eprintln!("Notion error: could not execute {:?}: {}", command, error);
+            process::exit(EXECUTION_FAILURE)
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_is_exact() {
+        assert!(is_exact("18.17.1"));
+        assert!(is_exact("0.10.48"));
+        assert!(!is_exact("18.17"));
+        assert!(!is_exact("^18.17.1"));
+        assert!(!is_exact("18.17.1-rc.1"));
+        assert!(!is_exact("18..1"));
+        assert!(!is_exact("v18.17.1"));
+    }
+
+    #[test]
+    fn test_read_pin() {
+        assert_eq!(
+            read_pin(r#"{ "toolchain": { "node": "18.17.1", "yarn": "1.22.19" } }"#),
+            Some(Pin {
+                node: "18.17.1".to_string(),
+                yarn: Some("1.22.19".to_string()),
+            })
+        );
+        assert_eq!(
+            read_pin(r#"{ "name": "app", "toolchain": { "node": "18.17.1" } }"#),
+            Some(Pin {
+                node: "18.17.1".to_string(),
+                yarn: None,
+            })
+        );
+
+        // anything the full dispatcher has to resolve is not pinned
+        assert_eq!(read_pin(r#"{ "name": "app" }"#), None);
+        assert_eq!(read_pin(r#"{ "toolchain": { "node": "^18.17.1" } }"#), None);
+        assert_eq!(read_pin(r#"{ "toolchain": { "yarn": "1.22.19" } }"#), None);
+        assert_eq!(
+            read_pin(r#"{ "toolchain": { "node": "18.17.1", "npm": "9.8.1" } }"#),
+            None
+        );
+        assert_eq!(read_pin("{ \"toolchain\": "), None);
+    }
+
+    #[test]
+    fn test_decision_for() {
+        let decisions = "declined /work/vendor\ntrusted /work\nmaybe /tmp\n";
+        assert_eq!(decision_for(decisions, Path::new("/work")), Some(true));
+        assert_eq!(decision_for(decisions, Path::new("/work/vendor")), Some(false));
+        assert_eq!(decision_for(decisions, Path::new("/work/app")), None);
+        assert_eq!(decision_for(decisions, Path::new("/tmp")), None);
+    }
+}
@@ -0,0 +1,9 @@
+#[macro_use]
+extern crate notion_shim;
+
+shim_metadata!("node");
+
+/// The entry point for the `node` shim.
+pub fn main() {
+    notion_shim::dispatch("node")
+}
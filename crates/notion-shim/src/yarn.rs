@@ -0,0 +1,9 @@
+#[macro_use]
+extern crate notion_shim;
+
+shim_metadata!("yarn");
+
+/// The entry point for the `yarn` shim.
+pub fn main() {
+    notion_shim::dispatch("yarn")
+}
@@ -0,0 +1,185 @@
+use std::collections::BTreeMap;
+
+use semver::Version;
+
+use notion_core::path::user_catalog_file;
+use notion_core::plan::{Action, Plan};
+use notion_core::session::{ActivityKind, Session};
+use notion_core::version::VersionSpec;
+use notion_fail::{ExitCode, Fallible};
+
+use command::{Command, CommandName, Help};
+use {CliParseError, Notion};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_tool: Option<String>,
+    arg_name: String,
+    arg_version: String,
+    cmd_list: bool,
+    cmd_set: bool,
+    cmd_rm: bool,
+}
+
+/// The tools that versions can be aliased for.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Tool {
+    Node,
+    Yarn,
+}
+
+impl Tool {
+    fn parse(name: &str) -> Fallible<Self> {
+        match name {
+            "node" => Ok(Tool::Node),
+            "yarn" => Ok(Tool::Yarn),
+            _ => throw!(CliParseError {
+                usage: None,
+                error: format!("no such tool: `{}`", name),
+            }),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            &Tool::Node => "node",
+            &Tool::Yarn => "yarn",
+        }
+    }
+}
+
+pub(crate) enum Alias {
+    Help,
+    List { tool: Option<Tool> },
+    Set {
+        tool: Tool,
+        name: String,
+        version: Version,
+        dry_run: bool,
+    },
+    Remove {
+        tool: Tool,
+        name: String,
+        dry_run: bool,
+    },
+}
+
+impl Command for Alias {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Manage named aliases for tool versions
+
+Usage:
+    notion alias list [<tool>]
+    notion alias set <tool> <name> <version>
+    notion alias rm <tool> <name>
+    notion alias -h | --help
+
+Options:
+    -h, --help     Display this message
+
+An alias names a version of Node or Yarn, and can be used wherever a version
+is accepted:
+
+    notion alias set node work 18.17.0
+    notion alias set node legacy 14.21.3
+    notion use node work
+    notion fetch node legacy
+
+Alias names start with a letter and contain only letters, digits, '-', and
+'_'. Aliases are kept in the user catalog, and setting an alias that already
+exists points it at the new version.
+";
+
+    fn help() -> Self {
+        Alias::Help
+    }
+
+    fn parse(
+        notion: Notion,
+        Args {
+            arg_tool,
+            arg_name,
+            arg_version,
+            cmd_list,
+            cmd_set,
+            cmd_rm,
+        }: Args,
+    ) -> Fallible<Self> {
+        let dry_run = notion.dry_run();
+        let tool = match arg_tool {
+            Some(tool) => Some(Tool::parse(&tool)?),
+            None => None,
+        };
+
+        Ok(match tool {
+            _ if cmd_list => Alias::List { tool },
+            Some(tool) if cmd_set => Alias::Set {
+                tool,
+                name: arg_name,
+                version: VersionSpec::parse_version(arg_version.trim_left_matches('v'))?,
+                dry_run,
+            },
+            Some(tool) if cmd_rm => Alias::Remove {
+                tool,
+                name: arg_name,
+                dry_run,
+            },
+            _ => Alias::Help,
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Alias);
+        match self {
+            Alias::Help => Help::Command(CommandName::Alias).run(session)?,
+            Alias::List { tool } => {
+                let catalog = session.catalog()?;
+                for &listed in &[Tool::Node, Tool::Yarn] {
+                    if tool.map_or(true, |tool| tool == listed) {
+                        let aliases = match listed {
+                            Tool::Node => &catalog.node.aliases,
+                            Tool::Yarn => &catalog.yarn.aliases,
+                        };
+                        print_aliases(listed, aliases);
+                    }
+                }
+            }
+            Alias::Set { dry_run: true, .. } | Alias::Remove { dry_run: true, .. } => {
+                let mut plan = Plan::new();
+                plan.push(Action::Write(user_catalog_file()?));
+                println!("{}", plan);
+            }
+            Alias::Set {
+                tool,
+                name,
+                version,
+                ..
+            } => {
+                let catalog = session.catalog_mut()?;
+                match tool {
+                    Tool::Node => catalog.set_node_alias(&name, version.clone())?,
+                    Tool::Yarn => catalog.set_yarn_alias(&name, version.clone())?,
+                }
+                println!("{} {} -> v{}", tool.name(), name, version);
+            }
+            Alias::Remove { tool, name, .. } => {
+                let catalog = session.catalog_mut()?;
+                let version = match tool {
+                    Tool::Node => catalog.remove_node_alias(&name)?,
+                    Tool::Yarn => catalog.remove_yarn_alias(&name)?,
+                };
+                println!("Removed {} {} (was v{})", tool.name(), name, version);
+            }
+        };
+        session.add_event_end(ActivityKind::Alias, ExitCode::Success);
+        Ok(())
+    }
+}
+
+fn print_aliases(tool: Tool, aliases: &BTreeMap<String, Version>) {
+    for (name, version) in aliases {
+        println!("{} {} -> v{}", tool.name(), name, version);
+    }
+}
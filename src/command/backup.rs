@@ -0,0 +1,97 @@
+use std::path::PathBuf;
+
+use notion_core::backup::Contents;
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_file: String,
+    cmd_create: bool,
+    cmd_restore: bool,
+}
+
+pub(crate) enum Backup {
+    Help,
+    Create(PathBuf),
+    Restore(PathBuf),
+}
+
+fn print_contents(verb: &str, file: &PathBuf, contents: &Contents) {
+    println!("{} {}", verb, file.display());
+    for name in &contents.files {
+        println!("    {}", name);
+    }
+    if let Some(ref version) = contents.node {
+        println!("    default node v{}", version);
+    }
+    if let Some(ref version) = contents.yarn {
+        println!("    default yarn v{}", version);
+    }
+    for shim in &contents.shims {
+        println!("    shim {}", shim);
+    }
+}
+
+impl Command for Backup {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Back up or restore your Notion settings
+
+Usage:
+    notion backup create <file>
+    notion backup restore <file>
+    notion backup -h | --help
+
+Options:
+    -h, --help     Display this message
+
+A backup contains your catalog, config.toml, hooks.toml, and the names of your
+shims for 3rd-party executables, but not the installed Node and Yarn versions
+themselves (see `notion help bundle` for those). Restoring a backup replaces
+your current settings; default versions that are not installed are installed
+the first time they are used.
+";
+
+    fn help() -> Self {
+        Backup::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            arg_file,
+            cmd_create,
+            cmd_restore,
+        }: Args,
+    ) -> Fallible<Self> {
+        Ok(if cmd_create {
+            Backup::Create(PathBuf::from(arg_file))
+        } else if cmd_restore {
+            Backup::Restore(PathBuf::from(arg_file))
+        } else {
+            Backup::Help
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Backup);
+        match self {
+            Backup::Help => Help::Command(CommandName::Backup).run(session)?,
+            Backup::Create(file) => {
+                let contents = session.create_backup(&file)?;
+                print_contents("Backed up to", &file, &contents);
+            }
+            Backup::Restore(file) => {
+                let contents = session.restore_backup(&file)?;
+                print_contents("Restored from", &file, &contents);
+            }
+        };
+        session.add_event_end(ActivityKind::Backup, ExitCode::Success);
+        Ok(())
+    }
+}
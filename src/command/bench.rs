@@ -0,0 +1,155 @@
+use std::time::Duration;
+
+use serde_json;
+
+use notion_core::bench::{self, Latency, ShimBench};
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    flag_iterations: usize,
+    flag_json: bool,
+}
+
+/// Thrown when there is no toolchain to benchmark the shims with.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "No Node version is pinned for the current project or selected by the user")]
+#[notion_fail(code = "NoVersionMatch")]
+struct NoPlatformError;
+
+/// Thrown when the benchmark is asked to run no iterations.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "--iterations must be at least 1")]
+#[notion_fail(code = "InvalidArguments")]
+struct NoIterationsError;
+
+/// Latency percentiles in milliseconds, as printed by `--json`.
+#[derive(Serialize)]
+struct Millis {
+    p50: f64,
+    p95: f64,
+}
+
+impl Millis {
+    fn new(latency: &Latency) -> Self {
+        Millis {
+            p50: millis(latency.p50),
+            p95: millis(latency.p95),
+        }
+    }
+}
+
+/// The results of the benchmark, as printed by `--json`.
+#[derive(Serialize)]
+struct Report {
+    iterations: usize,
+    direct: Millis,
+    shim: Millis,
+    overhead: Millis,
+}
+
+pub(crate) enum Bench {
+    Help,
+    Shim { iterations: usize, json: bool },
+}
+
+impl Command for Bench {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Measure the latency shims add
+
+Usage:
+    notion bench shim [options]
+    notion bench -h | --help
+
+Options:
+    -n, --iterations=<count>  Run each command this many times [default: 50]
+    --json                    Print the results as JSON
+    -h, --help                Display this message
+
+`notion bench shim` runs `node --version` with the current toolchain, first
+directly and then through the `node` shim, and reports the 50th and 95th
+percentiles of how long each run took. The difference is the time the shim
+spends selecting the toolchain before it starts Node, which is what to watch
+for regressions.
+";
+
+    fn help() -> Self {
+        Bench::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            flag_iterations,
+            flag_json,
+        }: Args,
+    ) -> Fallible<Self> {
+        if flag_iterations == 0 {
+            throw!(NoIterationsError);
+        }
+        Ok(Bench::Shim {
+            iterations: flag_iterations,
+            json: flag_json,
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Bench);
+        match self {
+            Bench::Help => Help::Command(CommandName::Bench).run(session)?,
+            Bench::Shim { iterations, json } => {
+                let image = match session.current_platform()? {
+                    Some(image) => image,
+                    None => throw!(NoPlatformError),
+                };
+                session.prepare_image(&image)?;
+                let results = bench::bench_shim(&image, iterations)?;
+                if json {
+                    let report = Report {
+                        iterations: results.iterations,
+                        direct: Millis::new(&results.direct),
+                        shim: Millis::new(&results.shim),
+                        overhead: Millis::new(&results.overhead()),
+                    };
+                    println!("{}", serde_json::to_string_pretty(&report).unknown()?);
+                } else {
+                    print_results(&results);
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Bench, ExitCode::Success);
+        Ok(())
+    }
+}
+
+fn print_results(results: &ShimBench) {
+    println!(
+        "Ran `node {}` {} times each:",
+        bench::ARGS.join(" "),
+        results.iterations
+    );
+    println!();
+    println!("    {:<10} {:>10} {:>10}", "", "p50", "p95");
+    print_row("direct", &results.direct);
+    print_row("shim", &results.shim);
+    print_row("overhead", &results.overhead());
+}
+
+fn print_row(label: &str, latency: &Latency) {
+    println!(
+        "    {:<10} {:>8.1}ms {:>8.1}ms",
+        label,
+        millis(latency.p50),
+        millis(latency.p95)
+    );
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1000.0 + duration.subsec_nanos() as f64 / 1_000_000.0
+}
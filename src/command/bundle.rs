@@ -0,0 +1,122 @@
+use std::path::PathBuf;
+
+use semver::Version;
+
+use notion_core::bundle::Contents;
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible, ResultExt};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_file: String,
+    cmd_export: bool,
+    cmd_import: bool,
+    flag_node: Option<String>,
+    flag_yarn: Option<String>,
+}
+
+pub(crate) enum Bundle {
+    Help,
+    Export {
+        file: PathBuf,
+        node: Option<Vec<Version>>,
+        yarn: Option<Vec<Version>>,
+    },
+    Import(PathBuf),
+}
+
+/// Parses a comma-separated list of versions.
+fn parse_versions(list: Option<String>) -> Fallible<Option<Vec<Version>>> {
+    match list {
+        Some(list) => {
+            let mut versions = Vec::new();
+            for version in list.split(',').map(|v| v.trim()).filter(|v| !v.is_empty()) {
+                versions.push(Version::parse(version.trim_left_matches('v')).unknown()?);
+            }
+            Ok(Some(versions))
+        }
+        None => Ok(None),
+    }
+}
+
+fn print_contents(verb: &str, file: &PathBuf, contents: &Contents) {
+    println!("{} {}", verb, file.display());
+    for version in &contents.node {
+        println!("    node v{}", version);
+    }
+    for version in &contents.yarn {
+        println!("    yarn v{}", version);
+    }
+    for shim in &contents.shims {
+        println!("    shim {}", shim);
+    }
+}
+
+impl Command for Bundle {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Export or import an offline bundle of installed tools
+
+Usage:
+    notion bundle export <file> [options]
+    notion bundle import <file>
+    notion bundle -h | --help
+
+Options:
+    --node=<versions>  Comma-separated Node versions to export (default: all)
+    --yarn=<versions>  Comma-separated Yarn versions to export (default: all)
+    -h, --help         Display this message
+
+A bundle contains installed Node and Yarn versions, the cached Node version
+index, and shims for 3rd-party executables, so that machines without network
+access can be provisioned by importing it.
+";
+
+    fn help() -> Self {
+        Bundle::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            arg_file,
+            cmd_export,
+            cmd_import,
+            flag_node,
+            flag_yarn,
+        }: Args,
+    ) -> Fallible<Self> {
+        Ok(if cmd_export {
+            Bundle::Export {
+                file: PathBuf::from(arg_file),
+                node: parse_versions(flag_node)?,
+                yarn: parse_versions(flag_yarn)?,
+            }
+        } else if cmd_import {
+            Bundle::Import(PathBuf::from(arg_file))
+        } else {
+            Bundle::Help
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Bundle);
+        match self {
+            Bundle::Help => Help::Command(CommandName::Bundle).run(session)?,
+            Bundle::Export { file, node, yarn } => {
+                let contents = session.export_bundle(node, yarn, &file)?;
+                print_contents("Exported", &file, &contents);
+            }
+            Bundle::Import(file) => {
+                let contents = session.import_bundle(&file)?;
+                print_contents("Imported", &file, &contents);
+            }
+        };
+        session.add_event_end(ActivityKind::Bundle, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+use notion_core::session::{ActivityKind, Session};
+use notion_core::usage::{cache_sizes, format_size, CachedFile};
+use notion_fail::{ExitCode, Fallible};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    cmd_size: bool,
+}
+
+pub(crate) enum Cache {
+    Help,
+    Size,
+}
+
+fn print_files(tool: &str, files: &[CachedFile]) {
+    let total: u64 = files.iter().map(|file| file.bytes).sum();
+    println!("{}: {}", tool, format_size(total));
+    for file in files {
+        println!("    {} ({})", file.name, format_size(file.bytes));
+    }
+}
+
+impl Command for Cache {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Inspect the archive cache
+
+Usage:
+    notion cache size
+    notion cache -h | --help
+
+Options:
+    -h, --help     Display this message
+
+The cache holds the downloaded Node and Yarn archives (so that versions can be
+reinstalled without downloading them again) and the index of available Node
+versions. See `notion list --size` for the space used by installed versions.
+";
+
+    fn help() -> Self {
+        Cache::Help
+    }
+
+    fn parse(_: Notion, Args { cmd_size }: Args) -> Fallible<Self> {
+        Ok(if cmd_size { Cache::Size } else { Cache::Help })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Cache);
+        match self {
+            Cache::Help => Help::Command(CommandName::Cache).run(session)?,
+            Cache::Size => {
+                let sizes = cache_sizes()?;
+                print_files("node", &sizes.node);
+                print_files("yarn", &sizes.yarn);
+                println!("total: {}", format_size(sizes.total()));
+            }
+        };
+        session.add_event_end(ActivityKind::Cache, ExitCode::Success);
+        Ok(())
+    }
+}
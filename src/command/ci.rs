@@ -0,0 +1,95 @@
+use notion_core::ci::{github_file, GitHubSetup};
+use notion_core::plan::Action;
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    flag_github: bool,
+}
+
+/// Thrown when there is no toolchain to set up.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "No toolchain is pinned for the current project or selected by the user")]
+#[notion_fail(code = "NoVersionMatch")]
+struct NoPlatformError;
+
+pub(crate) enum Ci {
+    Help,
+    Setup { github: bool, dry_run: bool },
+}
+
+impl Command for Ci {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Set up CI jobs with the current toolchain
+
+Usage:
+    notion ci setup [--github]
+    notion ci -h | --help
+
+Options:
+    --github       Pass the toolchain on to later GitHub Actions steps
+    -h, --help     Display this message
+
+`notion ci setup` installs the current project's pinned toolchain (or else
+your own), so that no later step has to install it on demand.
+
+With --github, the Node and Yarn directories are appended to $GITHUB_PATH, so
+later steps run them directly, and the versions are written to $GITHUB_OUTPUT
+as node-version and yarn-version. For repositories pinned with Notion, this
+step replaces actions/setup-node:
+
+    - run: notion ci setup --github
+      id: toolchain
+    - run: npm test
+";
+
+    fn help() -> Self {
+        Ci::Help
+    }
+
+    fn parse(notion: Notion, Args { flag_github }: Args) -> Fallible<Self> {
+        Ok(Ci::Setup {
+            github: flag_github,
+            dry_run: notion.dry_run(),
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Ci);
+        match self {
+            Ci::Help => Help::Command(CommandName::Ci).run(session)?,
+            Ci::Setup { github, dry_run } => {
+                let image = match session.current_platform()? {
+                    Some(image) => image,
+                    None => throw!(NoPlatformError),
+                };
+
+                if dry_run {
+                    let mut plan = session.plan_prepare_image(&image)?;
+                    if github {
+                        plan.push(Action::Write(github_file("GITHUB_PATH")?));
+                        plan.push(Action::Write(github_file("GITHUB_OUTPUT")?));
+                    }
+                    println!("{}", plan);
+                } else {
+                    session.prepare_image(&image)?;
+                    if github {
+                        GitHubSetup::for_image(&image)?.write()?;
+                    }
+                    match image.yarn {
+                        Some(ref yarn) => println!("Set up Node v{}, Yarn v{}", image.node, yarn),
+                        None => println!("Set up Node v{}", image.node),
+                    }
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Ci, ExitCode::Success);
+        Ok(())
+    }
+}
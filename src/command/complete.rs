@@ -0,0 +1,180 @@
+use semver::Version;
+
+use notion_core::catalog::cached_node_versions;
+use notion_core::session::{ActivityKind, Session};
+#[cfg(feature = "notion-dev")]
+use notion_core::shim;
+use notion_fail::{ExitCode, Fallible};
+
+use Notion;
+use command::{documented_commands, Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_words: Vec<String>,
+}
+
+pub(crate) enum Complete {
+    Help,
+    Complete { words: Vec<String> },
+}
+
+impl Command for Complete {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Complete a partial Notion command line
+
+Usage:
+    notion __complete [--] [<words>...]
+    notion __complete -h | --help
+
+Options:
+    -h, --help     Display this message
+
+This is run by the scripts from `notion completions` and `notion setup` when Tab
+is pressed, rather than by hand. The words are the command line after `notion`,
+ending with the word being completed (which may be empty), and the completions
+of that word are printed one per line:
+
+    notion __complete -- shim delete ''
+
+Shims are completed for `shim delete`, installed versions for `use` and
+`repair`, and, for `fetch` and `install`, the Node versions in the cached public
+index. Nothing is downloaded, so no versions to install are completed until the
+index has been cached (by `notion list node --remote`, for instance). Aliases
+are completed wherever versions are, and for `alias set` and `alias rm`, and
+package tools for `upgrade`.
+";
+
+    fn help() -> Self {
+        Complete::Help
+    }
+
+    fn parse(_: Notion, Args { arg_words }: Args) -> Fallible<Self> {
+        Ok(Complete::Complete { words: arg_words })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Complete);
+        match self {
+            Complete::Help => Help::Command(CommandName::Complete).run(session)?,
+            Complete::Complete { words } => {
+                // A failure to complete is not worth interrupting the command line
+                // being typed for, so it just produces no completions.
+                if let Ok(completions) = complete(session, &words) {
+                    for completion in completions {
+                        println!("{}", completion);
+                    }
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Complete, ExitCode::Success);
+        Ok(())
+    }
+}
+
+/// Produces the completions of the last of the words, given the words before it.
+/// Options are ignored, since they don't change what is completed.
+fn complete(session: &Session, words: &[String]) -> Fallible<Vec<String>> {
+    let (current, before) = match words.split_last() {
+        Some((current, before)) => (current.as_str(), before),
+        None => ("", words),
+    };
+    let before: Vec<&str> = before
+        .iter()
+        .map(|word| word.as_str())
+        .filter(|word| !word.starts_with('-'))
+        .collect();
+
+    let candidates = match before.len() {
+        0 => command_names(),
+        1 => match before[0] {
+            "help" => command_names(),
+            "fetch" | "install" | "use" => strings(&["node", "yarn"]),
+            "list" => strings(&["node", "yarn", "packages"]),
+            "repair" => installed_tool_versions(session)?,
+            #[cfg(feature = "notion-dev")]
+            "shim" => strings(&["audit", "auto", "create", "delete", "list"]),
+            "alias" => strings(&["list", "set", "rm"]),
+            "upgrade" => session.catalog()?.packages.keys().cloned().collect(),
+            _ => Vec::new(),
+        },
+        2 => match (before[0], before[1]) {
+            #[cfg(feature = "notion-dev")]
+            ("shim", "delete") => shim::names_3p()?,
+            ("fetch", "node") | ("install", "node") => {
+                with_aliases(session, "node", versions(cached_node_versions()?))?
+            }
+            ("fetch", "yarn") | ("install", "yarn") => with_aliases(session, "yarn", Vec::new())?,
+            ("use", tool) => {
+                with_aliases(session, tool, versions(installed_versions(session, tool)?))?
+            }
+            ("alias", _) => strings(&["node", "yarn"]),
+            _ => Vec::new(),
+        },
+        3 => match (before[0], before[1], before[2]) {
+            ("alias", "rm", tool) | ("alias", "set", tool) => alias_names(session, tool)?,
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    };
+
+    Ok(candidates
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(current))
+        .collect())
+}
+
+fn command_names() -> Vec<String> {
+    documented_commands()
+        .into_iter()
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+fn strings(words: &[&str]) -> Vec<String> {
+    words.iter().map(|word| word.to_string()).collect()
+}
+
+fn versions(list: Vec<Version>) -> Vec<String> {
+    list.iter().map(|version| version.to_string()).collect()
+}
+
+/// Lists the installed versions of a tool, newest first.
+fn installed_versions(session: &Session, tool: &str) -> Fallible<Vec<Version>> {
+    let catalog = session.catalog()?;
+    Ok(match tool {
+        "node" => catalog.node.versions.iter().rev().cloned().collect(),
+        "yarn" => catalog.yarn.versions.iter().rev().cloned().collect(),
+        _ => Vec::new(),
+    })
+}
+
+/// Lists the aliases defined for a tool.
+fn alias_names(session: &Session, tool: &str) -> Fallible<Vec<String>> {
+    let catalog = session.catalog()?;
+    Ok(match tool {
+        "node" => catalog.node.aliases.keys().cloned().collect(),
+        "yarn" => catalog.yarn.aliases.keys().cloned().collect(),
+        _ => Vec::new(),
+    })
+}
+
+/// Adds the aliases defined for a tool to a list of its versions, since aliases are
+/// accepted wherever versions are.
+fn with_aliases(session: &Session, tool: &str, mut versions: Vec<String>) -> Fallible<Vec<String>> {
+    versions.extend(alias_names(session, tool)?);
+    Ok(versions)
+}
+
+/// Lists every installed version in the `<tool>@<version>` form `notion repair` takes.
+fn installed_tool_versions(session: &Session) -> Fallible<Vec<String>> {
+    let mut tool_versions = Vec::new();
+    for tool in &["node", "yarn"] {
+        for version in installed_versions(session, tool)? {
+            tool_versions.push(format!("{}@{}", tool, version));
+        }
+    }
+    Ok(tool_versions)
+}
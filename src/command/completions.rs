@@ -0,0 +1,95 @@
+use notion_core::completions;
+use notion_core::path;
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use Notion;
+use command::{documented_commands, Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_shell: String,
+}
+
+/// Thrown when completions are requested for a shell they aren't available for.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "No completions for '{}': expected fish or nu", name)]
+#[notion_fail(code = "InvalidArguments")]
+struct UnsupportedCompletionsShellError {
+    name: String,
+}
+
+pub(crate) enum Completions {
+    Help,
+    Fish,
+    Nu,
+}
+
+impl Command for Completions {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Print tab completions for a shell
+
+Usage:
+    notion completions <shell>
+    notion completions -h | --help
+
+Options:
+    -h, --help     Display this message
+
+Supported shells:
+    fish           Completes commands and, by running `notion __complete`, their
+                   arguments: tools, shims, installed versions, and versions to
+                   install from the cached public index
+    nu             The same, as an external completer for `notion` that passes
+                   other commands on to any completer configured before it
+
+`notion setup --shell fish` loads these completions in every new shell. To load
+them yourself, add this to your config.fish:
+
+    notion completions fish | source
+
+Nushell can only source files, so save them and source the file from config.nu:
+
+    notion completions nu | save --force ~/.notion/completions.nu
+    source ~/.notion/completions.nu
+
+PowerShell completions are included in the module installed by `notion setup`.
+";
+
+    fn help() -> Self {
+        Completions::Help
+    }
+
+    fn parse(_: Notion, Args { arg_shell }: Args) -> Fallible<Self> {
+        match arg_shell.as_str() {
+            "fish" => Ok(Completions::Fish),
+            "nu" => Ok(Completions::Nu),
+            _ => throw!(UnsupportedCompletionsShellError {
+                name: arg_shell.clone(),
+            }),
+        }
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Completions);
+        match self {
+            Completions::Help => Help::Command(CommandName::Completions).run(session)?,
+            Completions::Fish => {
+                print!(
+                    "{}",
+                    completions::fish(&path::notion_file()?, &documented_commands())
+                );
+            }
+            Completions::Nu => {
+                print!(
+                    "{}",
+                    completions::nu(&path::notion_file()?, &documented_commands())
+                );
+            }
+        };
+        session.add_event_end(ActivityKind::Completions, ExitCode::Success);
+        Ok(())
+    }
+}
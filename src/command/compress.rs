@@ -0,0 +1,91 @@
+use notion_core::session::{ActivityKind, Session};
+use notion_core::usage::format_size;
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    flag_days: Option<u64>,
+}
+
+/// Thrown when no number of days is given and none is configured.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "no number of days given: use --days or set `compress-after` in config.toml")]
+#[notion_fail(code = "ConfigurationError")]
+struct NoCompressPolicyError;
+
+pub(crate) enum Compress {
+    Help,
+    Compress { days: Option<u64>, dry_run: bool },
+}
+
+impl Command for Compress {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Compress installed versions that have not been used recently
+
+Usage:
+    notion compress [--days=<days>]
+    notion compress -h | --help
+
+Options:
+    --days=<days>  Compress versions unused for more than this many days
+                   (defaults to `compress-after` in config.toml)
+    -h, --help     Display this message
+
+Each compressed version is packed back into an archive and its directory is
+removed. It stays installed, and the next time it is used it is unpacked again,
+which makes that first run slower. The default Node and Yarn versions are never
+compressed. With --dry-run, the versions that would be compressed are listed
+without changing anything.
+
+To compress unused versions automatically after every install, add this to
+your config.toml:
+
+    [storage]
+    compress-after = 90
+";
+
+    fn help() -> Self {
+        Compress::Help
+    }
+
+    fn parse(notion: Notion, Args { flag_days }: Args) -> Fallible<Self> {
+        Ok(Compress::Compress {
+            days: flag_days,
+            dry_run: notion.dry_run(),
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Compress);
+        match self {
+            Compress::Help => Help::Command(CommandName::Compress).run(session)?,
+            Compress::Compress { days, dry_run } => {
+                let days = match days.or(session.config()?.compress_after()) {
+                    Some(days) => days,
+                    None => throw!(NoCompressPolicyError),
+                };
+
+                let compressed = session.compress_unused(days, !dry_run)?;
+                for version in &compressed {
+                    println!(
+                        "{} {} v{} ({})",
+                        if dry_run { "would compress" } else { "compressed" },
+                        version.tool,
+                        version.key,
+                        format_size(version.bytes)
+                    );
+                }
+                if compressed.is_empty() {
+                    println!("no versions have gone unused for more than {} days", days);
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Compress, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -4,6 +4,7 @@ use std::str::FromStr;
 use docopt::Docopt;
 use serde::Deserialize;
 
+use notion_core::config;
 use notion_core::session::Session;
 use notion_fail::{FailExt, Fallible};
 
@@ -42,6 +43,11 @@ pub(crate) struct KeyValue {
 #[derive(Debug, Deserialize)]
 pub(crate) struct Nullary;
 
+#[derive(Debug, Deserialize)]
+pub(crate) struct ListArgs {
+    flag_explain: bool,
+}
+
 impl Display for SubcommandName {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         write!(
@@ -87,12 +93,7 @@ pub(crate) enum Subcommand {
         key: String,
     },
     Set {
-        // Not yet implemented.
-        #[allow(dead_code)]
         key: String,
-
-        // Not yet implemented.
-        #[allow(dead_code)]
         value: String,
     },
     Delete {
@@ -100,7 +101,9 @@ pub(crate) enum Subcommand {
         #[allow(dead_code)]
         key: String,
     },
-    List,
+    List {
+        explain: bool,
+    },
     Edit,
 }
 
@@ -138,8 +141,15 @@ Config commands:
     get <key>
     set <key> <value>
     delete <key>
-    list
+    list [--explain]
     edit
+
+Keys are dotted paths into config.toml, e.g. `notion config set
+telemetry.enabled false` to stop sending events to the events plugin.
+
+`notion config list` lists the values that are set, and with --explain, also
+explains the settings whose effect depends on more than their own values, such
+as the order download credentials are looked up in.
 ";
 
     fn help() -> Self {
@@ -167,8 +177,10 @@ Config commands:
                 Config::Subcommand(Subcommand::Delete { key: arg_key })
             }
             SubcommandName::List => {
-                let Nullary = parse_subcommand("list", "", argv)?;
-                Config::Subcommand(Subcommand::List)
+                let ListArgs { flag_explain } = parse_subcommand("list", "[--explain]", argv)?;
+                Config::Subcommand(Subcommand::List {
+                    explain: flag_explain,
+                })
             }
             SubcommandName::Edit => {
                 let Nullary = parse_subcommand("edit", "", argv)?;
@@ -182,16 +194,33 @@ Config commands:
         let result = match self {
             Config::Help => Help::Command(CommandName::Config).run(session),
             Config::Subcommand(Subcommand::Get { key: _ }) => Ok(()),
-            Config::Subcommand(Subcommand::Set { key: _, value: _ }) => {
-                throw!(CommandUnimplementedError::new("set"))
-            }
+            Config::Subcommand(Subcommand::Set { key, value }) => config::set_value(&key, &value),
             Config::Subcommand(Subcommand::Delete { key: _ }) => {
                 throw!(CommandUnimplementedError::new("delete"))
             }
-            Config::Subcommand(Subcommand::List) => throw!(CommandUnimplementedError::new("list")),
+            Config::Subcommand(Subcommand::List { explain }) => list(explain),
             Config::Subcommand(Subcommand::Edit) => throw!(CommandUnimplementedError::new("edit")),
         };
         //session.add_event_end(ActivityKind::Version, 0);
         result
     }
 }
+
+/// Prints the values set in the configuration file, and optionally the explanations of
+/// the settings whose effect depends on more than their own values.
+fn list(explain: bool) -> Fallible<()> {
+    for (key, value) in config::list_values()? {
+        println!("{} = {}", key, value);
+    }
+
+    if explain {
+        for explanation in config::EXPLANATIONS {
+            println!();
+            println!("{} (default: {})", explanation.key, explanation.default);
+            for line in explanation.text {
+                println!("    {}", line);
+            }
+        }
+    }
+    Ok(())
+}
@@ -4,12 +4,13 @@ use notion_core::session::{ActivityKind, Session};
 use notion_fail::{ExitCode, Fallible, NotionFail};
 
 use Notion;
-use command::{Command, CommandName, Help};
+use command::{Command, CommandName, Help, Porcelain};
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct Args {
     flag_project: bool,
     flag_user: bool,
+    flag_porcelain: Option<String>,
 }
 
 #[derive(Debug, Fail, NotionFail)]
@@ -19,9 +20,9 @@ struct NoVersionsFoundError;
 
 pub(crate) enum Current {
     Help,
-    Project,
-    User,
-    All,
+    Project(Option<Porcelain>),
+    User(Option<Porcelain>),
+    All(Option<Porcelain>),
 }
 
 impl Command for Current {
@@ -37,6 +38,22 @@ Options:
     -h, --help     Display this message
     -p, --project  Display the current project's Node version
     -u, --user     Display the user's Node version
+    --porcelain=<version>
+                   Print in a stable format for scripts (the only version is v1)
+
+A Node version selected by the NOTION_NODE_VERSION or NOTION_NPM_VERSION
+environment variables (see `notion help use`) takes precedence over all
+others, and is marked (from env).
+
+With --porcelain=v1, each Node version found is printed as a line of
+tab-separated fields, which later releases won't change:
+
+    <source>  <version>  <active>
+
+where <source> is `env`, `project`, `directory`, or `user`, <version> is the
+version without a leading `v`, and <active> is `active` for the version in
+effect or `-` for the others. With --project or --user, only that source's
+line is printed.
 ";
 
     fn help() -> Self {
@@ -48,14 +65,16 @@ Options:
         Args {
             flag_project,
             flag_user,
+            flag_porcelain,
         }: Args,
     ) -> Fallible<Current> {
+        let porcelain = Porcelain::parse(flag_porcelain)?;
         Ok(if !flag_project && flag_user {
-            Current::User
+            Current::User(porcelain)
         } else if flag_project && !flag_user {
-            Current::Project
+            Current::Project(porcelain)
         } else {
-            Current::All
+            Current::All(porcelain)
         })
     }
 
@@ -67,27 +86,52 @@ Options:
                 Help::Command(CommandName::Current).run(session)?;
                 true
             }
-            Current::Project => project_node_version(&session)?
+            Current::Project(Some(porcelain)) => print_porcelain(session, porcelain, "project")?,
+            Current::User(Some(porcelain)) => print_porcelain(session, porcelain, "user")?,
+            Current::All(Some(porcelain)) => print_porcelain(session, porcelain, "")?,
+            Current::Project(None) => project_node_version(&session)?
                 .map(|version| {
                     println!("v{}", version);
                 })
                 .is_some(),
-            Current::User => user_node_version(session)?
+            Current::User(None) => user_node_version(session)?
                 .map(|version| {
                     println!("v{}", version);
                 })
                 .is_some(),
-            Current::All => {
-                let (project, user) = (
+            Current::All(None) => {
+                let (shell, project, directory, user) = (
+                    shell_node_version(&session)?,
                     project_node_version(&session)?,
+                    directory_node_version(&session)?,
                     user_node_version(&session)?,
                 );
 
-                let user_active = project.is_none() && user.is_some();
-                let any = project.is_some() || user.is_some();
+                let project_active = shell.is_none() && project.is_some();
+                let directory_active = shell.is_none() && project.is_none() && directory.is_some();
+                let user_active = shell.is_none() && project.is_none() && directory.is_none()
+                    && user.is_some();
+                let any = shell.is_some() || project.is_some() || directory.is_some()
+                    || user.is_some();
+
+                for version in shell {
+                    println!("shell: v{} (from env) (active)", version);
+                }
 
                 for version in project {
-                    println!("project: v{} (active)", version);
+                    println!(
+                        "project: v{}{}",
+                        version,
+                        if project_active { " (active)" } else { "" }
+                    );
+                }
+
+                for version in directory {
+                    println!(
+                        "directory: v{}{}",
+                        version,
+                        if directory_active { " (active)" } else { "" }
+                    );
                 }
 
                 for version in user {
@@ -109,13 +153,48 @@ Options:
     }
 }
 
+/// Prints the version of each source in the porcelain format, or of only one source if
+/// `only` names it. Produces false if no versions were printed.
+fn print_porcelain(session: &Session, porcelain: Porcelain, only: &str) -> Fallible<bool> {
+    let versions = [
+        ("env", shell_node_version(session)?),
+        ("project", project_node_version(session)?),
+        ("directory", directory_node_version(session)?),
+        ("user", user_node_version(session)?),
+    ];
+
+    let mut active = true;
+    let mut any = false;
+    for &(source, ref version) in versions.iter() {
+        if let &Some(ref version) = version {
+            if only.is_empty() || only == source {
+                let marker = if active { "active" } else { "" };
+                println!("{}", porcelain.line(&[source, version, marker]));
+                any = true;
+            }
+            active = false;
+        }
+    }
+    Ok(any)
+}
+
+fn shell_node_version(session: &Session) -> Fallible<Option<String>> {
+    Ok(session.shell_versions()?.0.map(|v| v.to_string()))
+}
+
 fn project_node_version(session: &Session) -> Fallible<Option<String>> {
-    if let Some(ref image) = session.project_platform() {
+    if let Some(ref image) = session.project_platform()? {
         return Ok(Some(image.node_str.clone()));
     }
     Ok(None)
 }
 
+fn directory_node_version(session: &Session) -> Fallible<Option<String>> {
+    Ok(session
+        .directory_platform()?
+        .map(|image| image.node_str.clone()))
+}
+
 fn user_node_version(session: &Session) -> Fallible<Option<String>> {
     Ok(session.user_node()?.clone().map(|v| v.to_string()))
 }
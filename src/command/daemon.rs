@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+
+use notion_core::daemon;
+use notion_core::path;
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    flag_socket: Option<String>,
+}
+
+pub(crate) enum Daemon {
+    Help,
+    Serve(Option<PathBuf>),
+}
+
+impl Command for Daemon {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Answer toolchain queries from editors and language servers over JSON-RPC
+
+Usage:
+    notion daemon [--socket=<path>]
+    notion daemon -h | --help
+
+Options:
+    -h, --help         Display this message
+    --socket=<path>    The Unix socket, or named pipe on Windows, to listen on
+                       (defaults to daemon.sock in the Notion directory, or to
+                       \\\\.\\pipe\\notion-daemon on Windows)
+
+Listens for JSON-RPC 2.0 requests, one per line, and writes each response on a
+line of its own. The installed versions stay loaded between requests, answers
+are kept until a file they were resolved from changes, and the public version
+indexes are kept for five minutes, so that editors can query the toolchain
+without starting a process each time. The methods are:
+
+    resolve {dir}            The Node and Yarn versions in effect in a
+                             directory, and where they were selected
+    list {tool, remote}      The installed versions of node or yarn, or the
+                             available ones if remote is true
+    which {dir, tool}        The executable a shim would run in a directory
+
+As with `notion status`, the versions pinned by a project that isn't trusted
+are ignored rather than asked about.
+";
+
+    fn help() -> Self {
+        Daemon::Help
+    }
+
+    fn parse(_: Notion, Args { flag_socket }: Args) -> Fallible<Self> {
+        Ok(Daemon::Serve(flag_socket.map(PathBuf::from)))
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Daemon);
+        match self {
+            Daemon::Help => Help::Command(CommandName::Daemon).run(session)?,
+            Daemon::Serve(socket) => {
+                let socket = match socket {
+                    Some(socket) => socket,
+                    None => path::daemon_socket_file()?,
+                };
+                daemon::serve(&socket)?;
+            }
+        }
+        session.add_event_end(ActivityKind::Daemon, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+use notion_core::session::{ActivityKind, Session};
+use notion_core::usage::format_size;
+use notion_fail::{ExitCode, Fallible};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args;
+
+pub(crate) enum Dedupe {
+    Help,
+    Dedupe { dry_run: bool },
+}
+
+impl Command for Dedupe {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Share identical files between installed Node versions
+
+Usage:
+    notion dedupe
+    notion dedupe -h | --help
+
+Options:
+    -h, --help     Display this message
+
+Adjacent Node versions contain many identical files, such as much of the bundled
+npm and the C headers. This replaces every copy of such a file with a hardlink
+to a single one and reports the disk space reclaimed. With --dry-run, the space
+that would be reclaimed is reported without changing anything.
+
+To deduplicate automatically after every Node install, add this to your
+config.toml:
+
+    [storage]
+    dedupe = true
+";
+
+    fn help() -> Self {
+        Dedupe::Help
+    }
+
+    fn parse(notion: Notion, _: Args) -> Fallible<Self> {
+        Ok(Dedupe::Dedupe {
+            dry_run: notion.dry_run(),
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Dedupe);
+        match self {
+            Dedupe::Help => Help::Command(CommandName::Dedupe).run(session)?,
+            Dedupe::Dedupe { dry_run } => {
+                let summary = session.dedupe_node(!dry_run)?;
+                println!(
+                    "{} {} duplicate file(s), reclaiming {}",
+                    if dry_run { "would link" } else { "linked" },
+                    summary.linked,
+                    format_size(summary.reclaimed)
+                );
+            }
+        };
+        session.add_event_end(ActivityKind::Dedupe, ExitCode::Success);
+        Ok(())
+    }
+}
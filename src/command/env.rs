@@ -0,0 +1,113 @@
+use serde_json::{self, Map, Value};
+
+use notion_core::session::{ActivityKind, Session};
+use notion_core::shell::{CurrentShell, Postscript, Shell};
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    flag_shell: String,
+    flag_json: bool,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "No toolchain is selected for the current directory")]
+#[notion_fail(code = "NoVersionMatch")]
+struct NoPlatformError;
+
+pub(crate) enum Env {
+    Help,
+    Shell(String),
+    Json,
+}
+
+impl Command for Env {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Print the environment Notion's shims run tools in
+
+Usage:
+    notion env [options]
+    notion env -h | --help
+
+Options:
+    -s, --shell <shell>  Print commands for bash, fish, nu, or powershell [default: bash]
+    --json               Print the environment as a JSON object
+    -h, --help           Display this message
+
+This prints the PATH, the path of the Node executable (NODE), and the selected
+tool versions for the current directory, so that tools like Makefiles and
+editors can reproduce Notion's environment exactly:
+
+    eval \"$(notion env)\"
+
+or, in PowerShell:
+
+    notion env --shell powershell | Out-String | Invoke-Expression
+
+Nushell can only source saved files, so for Nushell save the output and source it:
+
+    notion env --shell nu | save --force notion-env.nu
+    source notion-env.nu
+";
+
+    fn help() -> Self {
+        Env::Help
+    }
+
+    fn parse(_: Notion, Args { flag_shell, flag_json }: Args) -> Fallible<Self> {
+        Ok(if flag_json {
+            Env::Json
+        } else {
+            Env::Shell(flag_shell)
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Env);
+
+        if let Env::Help = self {
+            Help::Command(CommandName::Env).run(session)?;
+            session.add_event_end(ActivityKind::Env, ExitCode::Success);
+            return Ok(());
+        }
+
+        let image = match session.current_platform()? {
+            Some(image) => image,
+            None => throw!(NoPlatformError),
+        };
+        session.prepare_image(&image)?;
+        let vars = image.env_vars()?;
+
+        match self {
+            Env::Help => {}
+            Env::Shell(shell_name) => {
+                let shell: CurrentShell = shell_name.parse()?;
+                for (name, value) in vars {
+                    let postscript = if name == "PATH" {
+                        Postscript::Path(value)
+                    } else {
+                        Postscript::Var { name, value }
+                    };
+                    print!("{}", shell.compile_postscript(&postscript));
+                }
+            }
+            Env::Json => {
+                let mut object = Map::new();
+                for (name, value) in vars {
+                    object.insert(name, Value::String(value));
+                }
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&Value::Object(object)).unknown()?
+                );
+            }
+        };
+        session.add_event_end(ActivityKind::Env, ExitCode::Success);
+        Ok(())
+    }
+}
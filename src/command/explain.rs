@@ -0,0 +1,110 @@
+use serde_json;
+
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_code: Option<String>,
+    flag_json: bool,
+}
+
+/// Thrown when the code to explain is not a Notion exit code or error kind.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Unknown exit code '{}' (see `notion explain --all`)", code)]
+#[notion_fail(code = "InvalidArguments")]
+struct UnknownExitCodeError {
+    code: String,
+}
+
+/// An entry of the exit code registry, as printed by `--json`.
+#[derive(Serialize)]
+struct Entry {
+    code: i32,
+    name: &'static str,
+    explanation: &'static str,
+    remedy: &'static str,
+}
+
+impl Entry {
+    fn new(code: ExitCode) -> Self {
+        Entry {
+            code: code.code(),
+            name: code.name(),
+            explanation: code.explanation(),
+            remedy: code.remedy(),
+        }
+    }
+}
+
+pub(crate) enum Explain {
+    Help,
+    Explain { codes: Vec<ExitCode>, json: bool },
+}
+
+impl Command for Explain {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Explain an exit code and how to recover from it
+
+Usage:
+    notion explain <code> [--json]
+    notion explain --all [--json]
+    notion explain -h | --help
+
+Options:
+    --all          Explain every exit code
+    --json         Print the explanations as JSON
+    -h, --help     Display this message
+
+A code can be given as the number Notion exits with (e.g. 4) or as the name of
+its error kind (e.g. NoVersionMatch). Exit codes keep their meaning across
+Notion releases, so scripts can branch on them; `notion explain --all --json`
+prints the whole registry for them to consult.
+";
+
+    fn help() -> Self {
+        Explain::Help
+    }
+
+    fn parse(_: Notion, Args { arg_code, flag_json }: Args) -> Fallible<Self> {
+        let codes = match arg_code {
+            Some(code) => match ExitCode::lookup(&code) {
+                Some(exit_code) => vec![exit_code],
+                None => throw!(UnknownExitCodeError { code }),
+            },
+            None => ExitCode::ALL.to_vec(),
+        };
+        Ok(Explain::Explain {
+            codes,
+            json: flag_json,
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Explain);
+        match self {
+            Explain::Help => Help::Command(CommandName::Explain).run(session)?,
+            Explain::Explain { codes, json: true } => {
+                let entries: Vec<Entry> = codes.into_iter().map(Entry::new).collect();
+                println!("{}", serde_json::to_string_pretty(&entries).unknown()?);
+            }
+            Explain::Explain { codes, .. } => {
+                for (i, code) in codes.into_iter().enumerate() {
+                    if i > 0 {
+                        println!();
+                    }
+                    println!("{} {}", code.code(), code.name());
+                    println!("    {}", code.explanation());
+                    println!("    {}", code.remedy());
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Explain, ExitCode::Success);
+        Ok(())
+    }
+}
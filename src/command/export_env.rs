@@ -0,0 +1,81 @@
+use notion_core::session::{ActivityKind, Session};
+use notion_core::shell::{CurrentShell, Postscript, Shell};
+use notion_fail::{ExitCode, Fallible};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    flag_shell: String,
+}
+
+pub(crate) enum ExportEnv {
+    Help,
+    Export(String),
+}
+
+impl Command for ExportEnv {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Print the current toolchain environment as shell commands
+
+Usage:
+    notion export-env [options]
+    notion export-env -h | --help
+
+Options:
+    -s, --shell <shell>  The shell to print commands for [default: bash]
+    -h, --help           Display this message
+
+This is intended for integrating Notion with tools like direnv, by adding the
+following to a project's .envrc:
+
+    eval \"$(notion export-env)\"
+";
+
+    fn help() -> Self {
+        ExportEnv::Help
+    }
+
+    fn parse(_: Notion, Args { flag_shell }: Args) -> Fallible<Self> {
+        Ok(ExportEnv::Export(flag_shell))
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::ExportEnv);
+        match self {
+            ExportEnv::Help => Help::Command(CommandName::ExportEnv).run(session)?,
+            ExportEnv::Export(shell_name) => {
+                let shell: CurrentShell = shell_name.parse()?;
+
+                if let Some(image) = session.current_platform()? {
+                    session.prepare_image(&image)?;
+
+                    let path = image.path()?.to_string_lossy().into_owned();
+                    print!("{}", shell.compile_postscript(&Postscript::Path(path)));
+                    print!(
+                        "{}",
+                        shell.compile_postscript(&Postscript::ToolVersion {
+                            tool: "node".to_string(),
+                            version: image.node.clone(),
+                        })
+                    );
+
+                    if let Some(ref yarn) = image.yarn {
+                        print!(
+                            "{}",
+                            shell.compile_postscript(&Postscript::ToolVersion {
+                                tool: "yarn".to_string(),
+                                version: yarn.clone(),
+                            })
+                        );
+                    }
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::ExportEnv, ExitCode::Success);
+        Ok(())
+    }
+}
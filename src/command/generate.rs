@@ -0,0 +1,99 @@
+use std::rc::Rc;
+
+use notion_core::generate;
+use notion_core::image::Image;
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    cmd_devcontainer: bool,
+    cmd_docker: bool,
+}
+
+/// Thrown when the current project doesn't pin a toolchain to generate from.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "No toolchain is pinned for the current project")]
+#[notion_fail(code = "NoVersionMatch")]
+struct NoPlatformError;
+
+pub(crate) enum Generate {
+    Help,
+    Docker,
+    DevContainer,
+}
+
+impl Command for Generate {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Generate container configuration for the project's toolchain
+
+Usage:
+    notion generate docker
+    notion generate devcontainer
+    notion generate -h | --help
+
+Options:
+    -h, --help     Display this message
+
+`notion generate docker` prints a Dockerfile fragment that installs the Node and
+Yarn versions the current project pins, for any Linux base image with curl.
+
+`notion generate devcontainer` prints the `features` settings of a
+devcontainer.json that install the same versions, to merge into the project's
+own devcontainer.json.
+
+Version requirements are resolved the same way the shims resolve them, so the
+container runs exactly the versions Notion runs locally. Run the command again
+after changing the project's pins to keep the two in step.
+";
+
+    fn help() -> Self {
+        Generate::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            cmd_devcontainer,
+            cmd_docker,
+        }: Args,
+    ) -> Fallible<Self> {
+        Ok(if cmd_docker {
+            Generate::Docker
+        } else if cmd_devcontainer {
+            Generate::DevContainer
+        } else {
+            Generate::Help
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Generate);
+        match self {
+            Generate::Help => Help::Command(CommandName::Generate).run(session)?,
+            Generate::Docker => {
+                let image = project_platform(session)?;
+                print!("{}", generate::dockerfile(&image));
+            }
+            Generate::DevContainer => {
+                let image = project_platform(session)?;
+                println!("{}", generate::devcontainer(&image)?);
+            }
+        };
+        session.add_event_end(ActivityKind::Generate, ExitCode::Success);
+        Ok(())
+    }
+}
+
+/// Produces the platform the current project pins.
+fn project_platform(session: &Session) -> Fallible<Rc<Image>> {
+    match session.project_platform()? {
+        Some(image) => Ok(image),
+        None => throw!(NoPlatformError),
+    }
+}
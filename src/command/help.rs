@@ -1,8 +1,10 @@
 use notion_core::session::{ActivityKind, Session};
 use notion_fail::{ExitCode, Fallible};
 
-use command::{Command, CommandName, Config, Current, Deactivate, Fetch, Install, Use,
-              Version};
+use command::{Alias, Backup, Bench, Bundle, Cache, Ci, Command, CommandName, Complete, Completions,
+              Compress, Config, Current, Daemon, Deactivate, Dedupe, Env, Explain, ExportEnv,
+              Fetch, Generate, Install, List, Lock, Outdated, Prompt, Repair, Run, Sbom, Search,
+              Setup, Status, Trust, Update, Upgrade, Use, Verify, Version, Which, Why};
 #[cfg(feature = "notion-dev")]
 use command::Shim;
 use {CliParseError, Notion};
@@ -67,6 +69,37 @@ Options:
                 Help::Command(CommandName::Install) => Install::USAGE,
                 #[cfg(feature = "notion-dev")]
                 Help::Command(CommandName::Shim) => Shim::USAGE,
+                Help::Command(CommandName::Status) => Status::USAGE,
+                Help::Command(CommandName::Daemon) => Daemon::USAGE,
+                Help::Command(CommandName::Prompt) => Prompt::USAGE,
+                Help::Command(CommandName::Lock) => Lock::USAGE,
+                Help::Command(CommandName::Trust) => Trust::USAGE,
+                Help::Command(CommandName::Sbom) => Sbom::USAGE,
+                Help::Command(CommandName::Generate) => Generate::USAGE,
+                Help::Command(CommandName::Bench) => Bench::USAGE,
+                Help::Command(CommandName::Explain) => Explain::USAGE,
+                Help::Command(CommandName::Ci) => Ci::USAGE,
+                Help::Command(CommandName::Run) => Run::USAGE,
+                Help::Command(CommandName::Outdated) => Outdated::USAGE,
+                Help::Command(CommandName::Upgrade) => Upgrade::USAGE,
+                Help::Command(CommandName::Alias) => Alias::USAGE,
+                Help::Command(CommandName::Complete) => Complete::USAGE,
+                Help::Command(CommandName::Completions) => Completions::USAGE,
+                Help::Command(CommandName::Setup) => Setup::USAGE,
+                Help::Command(CommandName::Compress) => Compress::USAGE,
+                Help::Command(CommandName::Dedupe) => Dedupe::USAGE,
+                Help::Command(CommandName::Cache) => Cache::USAGE,
+                Help::Command(CommandName::Repair) => Repair::USAGE,
+                Help::Command(CommandName::Verify) => Verify::USAGE,
+                Help::Command(CommandName::Update) => Update::USAGE,
+                Help::Command(CommandName::Search) => Search::USAGE,
+                Help::Command(CommandName::List) => List::USAGE,
+                Help::Command(CommandName::Backup) => Backup::USAGE,
+                Help::Command(CommandName::Env) => Env::USAGE,
+                Help::Command(CommandName::Which) => Which::USAGE,
+                Help::Command(CommandName::Bundle) => Bundle::USAGE,
+                Help::Command(CommandName::Why) => Why::USAGE,
+                Help::Command(CommandName::ExportEnv) => ExportEnv::USAGE,
             }
         );
         session.add_event_end(ActivityKind::Help, ExitCode::Success);
@@ -1,29 +1,91 @@
+use std::path::PathBuf;
+
+use notion_core::arch::Arch;
+use notion_core::checksum::Checksum;
+use notion_core::manifest::PinStyle;
+use notion_core::package::PackageSpec;
 use notion_core::session::{ActivityKind, Session};
 use notion_core::version::VersionSpec;
-use notion_fail::{ExitCode, Fallible};
+use notion_fail::{ExitCode, Fallible, NotionFail};
 
 use result::ResultOptionExt;
 
-use CommandUnimplementedError;
 use Notion;
 use command::{Command, CommandName, Help};
+use command::use_::pin_style;
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct Args {
     arg_tool: String,
     arg_version: Option<String>,
+    flag_archive: Option<String>,
+    flag_arch: Option<String>,
+    flag_url: Option<String>,
+    flag_checksum: Option<String>,
+    flag_insecure_no_verify: bool,
+    flag_pin: bool,
+    flag_exact: bool,
+    flag_range: bool,
+}
+
+/// Thrown when the user installs from a URL without supplying a checksum.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "installing from a URL requires --checksum=sha256:<digest> (or --insecure-no-verify)")]
+#[notion_fail(code = "InvalidArguments")]
+struct ChecksumRequiredError;
+
+/// Thrown when the user installs a package from an archive or URL.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "only node and yarn can be installed from an archive or URL")]
+#[notion_fail(code = "InvalidArguments")]
+struct PackageSourceError;
+
+fn parse_version(version: Option<String>) -> Fallible<VersionSpec> {
+    Ok(version.map(VersionSpec::parse).invert()?.unwrap_or_default())
+}
+
+/// Splits a tool named with its version, like `node@18`, into the tool and version.
+fn split_tool_version(arg: &str) -> Option<(String, String)> {
+    for tool in &["node", "yarn"] {
+        if arg.starts_with(tool) && arg[tool.len()..].starts_with('@') {
+            return Some((tool.to_string(), arg[tool.len() + 1..].to_string()));
+        }
+    }
+    None
 }
 
 pub(crate) enum Install {
     Help,
-    Node(VersionSpec),
-    Yarn(VersionSpec),
-    Other {
-        package: String,
-        // not used
-        #[allow(dead_code)]
+    Node {
+        version: VersionSpec,
+        pin: bool,
+        style: Option<PinStyle>,
+        dry_run: bool,
+    },
+    Yarn {
         version: VersionSpec,
+        pin: bool,
+        style: Option<PinStyle>,
+        dry_run: bool,
     },
+    NodeArch {
+        version: VersionSpec,
+        arch: Arch,
+        dry_run: bool,
+    },
+    NodeArchive { archive: PathBuf, dry_run: bool },
+    YarnArchive { archive: PathBuf, dry_run: bool },
+    NodeUrl {
+        url: String,
+        checksum: Option<Checksum>,
+        dry_run: bool,
+    },
+    YarnUrl {
+        url: String,
+        checksum: Option<Checksum>,
+        dry_run: bool,
+    },
+    Package { spec: PackageSpec, dry_run: bool },
 }
 
 impl Command for Install {
@@ -33,14 +95,43 @@ impl Command for Install {
 Install a tool in the user toolchain
 
 Usage:
-    notion install <tool> [<version>]
+    notion install <tool> [<version>] [--arch=<arch>] [--pin [--exact | --range]]
+    notion install <tool> --archive=<file>
+    notion install <tool> --url=<url> (--checksum=<checksum> | --insecure-no-verify)
     notion install -h | --help
 
 Options:
-    -h, --help     Display this message
+    --arch=<arch>           Install a Node build for an architecture other than this
+                            machine's (x86, x64, arm64, or armv7l)
+    --archive=<file>        Install from a local archive instead of downloading it
+    --url=<url>             Install from an archive at an arbitrary URL
+    --checksum=<checksum>   The expected digest of the archive, as sha256:<digest>
+    --insecure-no-verify    Install from a URL without verifying a checksum
+    --pin                   Also pin the installed Node or Yarn version in the
+                            current project's toolchain, in the style set by
+                            pin.style
+    --exact                 Pin the exact version installed, like 18.17.1
+    --range                 Pin a caret range of the version installed, like ^18.17
+    -h, --help              Display this message
+
+A version can also follow the tool's name, so `notion install node@18 --pin`
+installs the newest Node 18 and pins it in package.json (or the project's
+notion.toml), leaving the rest of package.json as it was.
 
 Supported Tools:
-    Currently Notion supports installing `node` and `yarn` - support for more tools is coming soon!
+    node, yarn, and npm packages with executables
+
+Any other tool is installed as a package tool: an npm package whose executables
+are shimmed, installed into its own directory with the Node version in your
+toolchain. Its executables always run with that Node version, even in projects
+that pin another, so native modules keep working. Install the package again to
+move it to your current Node version. A version or range can follow the package
+name:
+
+    notion install typescript@5
+    notion install @vue/cli 5.0.8
+
+See `notion list packages` for the installed package tools.
 ";
 
     fn help() -> Self {
@@ -48,24 +139,89 @@ Supported Tools:
     }
 
     fn parse(
-        _: Notion,
+        notion: Notion,
         Args {
             arg_tool,
             arg_version,
+            flag_archive,
+            flag_arch,
+            flag_url,
+            flag_checksum,
+            flag_insecure_no_verify,
+            flag_pin,
+            flag_exact,
+            flag_range,
         }: Args,
     ) -> Fallible<Self> {
-        let version = arg_version
-            .map(VersionSpec::parse)
-            .invert()?
-            .unwrap_or_default();
+        let dry_run = notion.dry_run();
+        let style = pin_style(flag_exact, flag_range)?;
+        let (arg_tool, arg_version) = match split_tool_version(&arg_tool) {
+            Some((tool, version)) => (tool, Some(version)),
+            None => (arg_tool, arg_version),
+        };
+
+        if let Some(url) = flag_url {
+            let checksum = flag_checksum
+                .map(|checksum| checksum.parse::<Checksum>())
+                .invert()?;
+            if checksum.is_none() && !flag_insecure_no_verify {
+                throw!(ChecksumRequiredError);
+            }
+            return Ok(match &arg_tool[..] {
+                "node" => Install::NodeUrl {
+                    url,
+                    checksum,
+                    dry_run,
+                },
+                "yarn" => Install::YarnUrl {
+                    url,
+                    checksum,
+                    dry_run,
+                },
+                _ => throw!(PackageSourceError),
+            });
+        }
+
+        if let Some(archive) = flag_archive {
+            let archive = PathBuf::from(archive);
+            return Ok(match &arg_tool[..] {
+                "node" => Install::NodeArchive { archive, dry_run },
+                "yarn" => Install::YarnArchive { archive, dry_run },
+                _ => throw!(PackageSourceError),
+            });
+        }
+
+        if let Some(arch) = flag_arch {
+            let arch = arch.parse::<Arch>()?;
+            if &arg_tool[..] == "node" && arch != Arch::native() {
+                return Ok(Install::NodeArch {
+                    version: parse_version(arg_version)?,
+                    arch,
+                    dry_run,
+                });
+            }
+        }
 
         Ok(match &arg_tool[..] {
-            "node" => Install::Node(version),
-            "yarn" => Install::Yarn(version),
-            ref package => Install::Other {
-                package: package.to_string(),
-                version: version,
+            "node" => Install::Node {
+                version: parse_version(arg_version)?,
+                pin: flag_pin,
+                style,
+                dry_run,
+            },
+            "yarn" => Install::Yarn {
+                version: parse_version(arg_version)?,
+                pin: flag_pin,
+                style,
+                dry_run,
             },
+            package => {
+                let mut spec = package.parse::<PackageSpec>()?;
+                if arg_version.is_some() {
+                    spec.version = arg_version;
+                }
+                Install::Package { spec, dry_run }
+            }
         })
     }
 
@@ -75,19 +231,124 @@ Supported Tools:
             Install::Help => {
                 Help::Command(CommandName::Install).run(session)?;
             }
-            Install::Node(requirements) => {
-                session.set_user_node(&requirements)?;
+            Install::Node {
+                version,
+                pin,
+                dry_run: true,
+                ..
+            } => {
+                let (_, mut plan) = session.plan_set_user_node(&version)?;
+                if pin {
+                    plan.extend(session.plan_pin_node_version(&version, false)?.1);
+                }
+                println!("{}", plan);
+            }
+            Install::Node {
+                version,
+                pin,
+                style,
+                ..
+            } => {
+                session.set_user_node(&version)?;
+                if pin {
+                    if let Some(installed) = session.user_node()? {
+                        let installed = VersionSpec::exact(&installed);
+                        session.pin_node_version(&installed, style, false)?;
+                    }
+                }
+            }
+            Install::NodeArch {
+                version,
+                arch,
+                dry_run: true,
+            } => {
+                let (_, plan) = session.plan_set_user_node_for_arch(&version, arch)?;
+                println!("{}", plan);
+            }
+            Install::NodeArch { version, arch, .. } => {
+                session.set_user_node_for_arch(&version, arch)?;
+            }
+            Install::Yarn {
+                version,
+                pin,
+                dry_run: true,
+                ..
+            } => {
+                let (_, mut plan) = session.plan_set_user_yarn(&version)?;
+                if pin {
+                    plan.extend(session.plan_pin_yarn_version(&version, false)?.1);
+                }
+                println!("{}", plan);
+            }
+            Install::Yarn {
+                version,
+                pin,
+                style,
+                ..
+            } => {
+                session.set_user_yarn(&version)?;
+                if pin {
+                    if let Some(installed) = session.user_yarn()? {
+                        let installed = VersionSpec::exact(&installed);
+                        session.pin_yarn_version(&installed, style, false)?;
+                    }
+                }
+            }
+            Install::NodeArchive {
+                archive,
+                dry_run: true,
+            } => {
+                let (_, plan) = session.plan_set_user_node_from_archive(&archive)?;
+                println!("{}", plan);
+            }
+            Install::NodeArchive { archive, .. } => {
+                session.set_user_node_from_archive(&archive)?;
+            }
+            Install::YarnArchive {
+                archive,
+                dry_run: true,
+            } => {
+                let (_, plan) = session.plan_set_user_yarn_from_archive(&archive)?;
+                println!("{}", plan);
+            }
+            Install::YarnArchive { archive, .. } => {
+                session.set_user_yarn_from_archive(&archive)?;
+            }
+            Install::NodeUrl {
+                url, dry_run: true, ..
+            } => {
+                let (_, plan) = session.plan_set_user_node_from_url(&url)?;
+                println!("{}", plan);
+            }
+            Install::NodeUrl { url, checksum, .. } => {
+                session.set_user_node_from_url(&url, checksum.as_ref())?;
+            }
+            Install::YarnUrl {
+                url, dry_run: true, ..
+            } => {
+                let (_, plan) = session.plan_set_user_yarn_from_url(&url)?;
+                println!("{}", plan);
+            }
+            Install::YarnUrl { url, checksum, .. } => {
+                session.set_user_yarn_from_url(&url, checksum.as_ref())?;
+            }
+            Install::Package {
+                spec,
+                dry_run: true,
+            } => {
+                let plan = session.plan_install_package(&spec)?;
+                println!("{}", plan);
             }
-            Install::Yarn(requirements) => {
-                session.set_user_yarn(&requirements)?;
+            Install::Package { spec, .. } => {
+                let tool = session.install_package(&spec)?;
+                println!(
+                    "Installed {} v{} with Node v{}: {}",
+                    spec.name,
+                    tool.version,
+                    tool.node,
+                    tool.bins.join(", ")
+                );
             }
-            Install::Other {
-                package,
-                version: _,
-            } => throw!(CommandUnimplementedError::new(&format!(
-                "notion install {}",
-                package
-            ))),
         };
         session.add_event_end(ActivityKind::Install, ExitCode::Success);
         Ok(())
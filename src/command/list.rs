@@ -0,0 +1,381 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use semver::{Version, VersionReq};
+
+use notion_core::arch::Arch;
+use notion_core::catalog::{public_node_versions, public_yarn_versions};
+use notion_core::path;
+use notion_core::provenance::Provenance;
+use notion_core::session::{ActivityKind, Session};
+use notion_core::style::format_timestamp;
+use notion_core::usage::{format_size, version_sizes};
+use notion_core::version::VersionSpec;
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use Notion;
+use command::{Command, CommandName, Help, Porcelain};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_range: Option<String>,
+    cmd_node: bool,
+    cmd_yarn: bool,
+    cmd_packages: bool,
+    flag_remote: bool,
+    flag_lts: bool,
+    flag_major: Option<u64>,
+    flag_size: bool,
+    flag_provenance: bool,
+    flag_porcelain: Option<String>,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "no versions found")]
+#[notion_fail(code = "NoVersionMatch")]
+struct NoVersionsFoundError;
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "--lts can only be used to list Node versions")]
+#[notion_fail(code = "InvalidArguments")]
+struct LtsNotSupportedError;
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "--porcelain can't be combined with --size or --provenance")]
+#[notion_fail(code = "InvalidArguments")]
+struct PorcelainConflictError;
+
+/// The filters applied to a list of versions.
+pub(crate) struct Filter {
+    remote: bool,
+    lts: bool,
+    major: Option<u64>,
+    range: Option<VersionReq>,
+    size: bool,
+    provenance: bool,
+    porcelain: Option<Porcelain>,
+}
+
+impl Filter {
+    fn matches(&self, version: &Version, lts: &Option<String>) -> bool {
+        (!self.lts || lts.is_some())
+            && self.major.map_or(true, |major| version.major == major)
+            && self.range.as_ref().map_or(true, |range| range.matches(version))
+    }
+}
+
+pub(crate) enum List {
+    Help,
+    Node(Filter),
+    Yarn(Filter),
+    Packages(Option<Porcelain>),
+}
+
+impl Command for List {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+List installed or available tool versions
+
+Usage:
+    notion list node [options] [<range>]
+    notion list yarn [options] [<range>]
+    notion list packages [--porcelain=<version>]
+    notion list -h | --help
+
+Options:
+    -r, --remote       List the versions available to install
+    --lts              Only list Node versions in an LTS line
+    --major=<major>    Only list versions with this major version
+    -s, --size         Show the disk space used by each installed version
+    --provenance       Show where each installed version was downloaded from
+    --porcelain=<version>
+                       Print in a stable format for scripts (the only version
+                       is v1)
+    -h, --help         Display this message
+
+Remote Node versions are read from the cached public index, which is refreshed
+when it expires. Versions that are already installed are marked [installed],
+and the version the current directory resolves to is marked [current].
+Versions installed system-wide by an administrator are marked [system]; they can
+be used like any other but not uninstalled or repaired.
+
+With --size, the space used by builds for other architectures is included in
+the size of their version. See `notion help cache` for the size of the archive
+cache.
+
+With --provenance, each installed version is followed by the URL its archive was
+downloaded from, when, the archive's SHA-256 digest, and how the archive was
+verified: against a checksum given when it was installed, or against the
+provenance attestation its distributor published (see the `provenance.attestations`
+setting). Versions installed before Notion recorded provenance have none.
+
+`notion list packages` lists the package tools installed by `notion install`, with
+the Node and npm versions each was installed with (and always runs with) and the
+executables it provides.
+
+With --porcelain=v1, each version is printed as a line of tab-separated fields,
+which later releases won't change:
+
+    <version>  <installed>  <current>  <system>  <lts>
+
+where <version> is the version without a leading `v`, <installed>, <current>
+and <system> are `installed`, `current` and `system` for versions marked so
+above or else `-`, and <lts> is the LTS line's codename or `-`. Each package
+tool is printed as:
+
+    <name>  <version>  <node>  <npm>  <executables>
+
+where <npm> is `-` if the npm version it was installed with isn't known, and
+<executables> is a comma-separated list.
+";
+
+    fn help() -> Self {
+        List::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            arg_range,
+            cmd_node,
+            cmd_yarn,
+            cmd_packages,
+            flag_remote,
+            flag_lts,
+            flag_major,
+            flag_size,
+            flag_provenance,
+            flag_porcelain,
+        }: Args,
+    ) -> Fallible<Self> {
+        let porcelain = Porcelain::parse(flag_porcelain)?;
+        if porcelain.is_some() && (flag_size || flag_provenance) {
+            throw!(PorcelainConflictError);
+        }
+        let range = match arg_range {
+            Some(range) => Some(VersionSpec::parse_requirements(range)?),
+            None => None,
+        };
+        let filter = Filter {
+            remote: flag_remote,
+            lts: flag_lts,
+            major: flag_major,
+            range,
+            size: flag_size,
+            provenance: flag_provenance,
+            porcelain,
+        };
+
+        Ok(if cmd_node {
+            List::Node(filter)
+        } else if cmd_yarn {
+            if flag_lts {
+                throw!(LtsNotSupportedError);
+            }
+            List::Yarn(filter)
+        } else if cmd_packages {
+            List::Packages(porcelain)
+        } else {
+            List::Help
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::List);
+        match self {
+            List::Help => Help::Command(CommandName::List).run(session)?,
+            List::Node(filter) => list_node(session, filter)?,
+            List::Yarn(filter) => list_yarn(session, filter)?,
+            List::Packages(porcelain) => list_packages(session, porcelain)?,
+        };
+        session.add_event_end(ActivityKind::List, ExitCode::Success);
+        Ok(())
+    }
+}
+
+fn list_node(session: &mut Session, filter: Filter) -> Fallible<()> {
+    let current = session.current_platform()?.map(|image| image.node.clone());
+    let installed = session.catalog()?.node.versions.clone();
+    let system = session.catalog()?.node.system.clone();
+    let sizes = if filter.size {
+        version_sizes(session.catalog()?)?.node
+    } else {
+        BTreeMap::new()
+    };
+    let mut provenance = BTreeMap::new();
+    if filter.provenance {
+        let catalog = session.catalog()?;
+        for version in installed.iter() {
+            let mut builds = vec![(None, Arch::native())];
+            if let Some(arches) = catalog.node.arches.get(version) {
+                builds.extend(arches.iter().map(|arch| (Some(*arch), *arch)));
+            }
+            let records = builds
+                .into_iter()
+                .filter_map(|(label, arch)| {
+                    let key = path::node_version_key(&version.to_string(), arch);
+                    let record = catalog.node.provenance.get(&key)?;
+                    Some((label.map(|arch| arch.to_string()), record.clone()))
+                })
+                .collect();
+            provenance.insert(version.clone(), records);
+        }
+    }
+
+    // LTS lines are only known from the public index, so it is consulted for
+    // installed versions too when filtering on them.
+    let versions: Vec<(Version, Option<String>)> = if filter.remote || filter.lts {
+        public_node_versions()?
+            .into_iter()
+            .filter(|available| filter.remote || installed.contains(&available.version))
+            .map(|available| (available.version, available.lts))
+            .collect()
+    } else {
+        installed.iter().rev().map(|version| (version.clone(), None)).collect()
+    };
+
+    let is_installed = |version: &Version| installed.contains(version);
+    print_versions(&filter, versions, is_installed, &system, current, &sizes, &provenance)
+}
+
+fn list_yarn(session: &mut Session, filter: Filter) -> Fallible<()> {
+    let current = session
+        .current_platform()?
+        .and_then(|image| image.yarn.clone());
+    let installed = session.catalog()?.yarn.versions.clone();
+    let system = session.catalog()?.yarn.system.clone();
+    let sizes = if filter.size {
+        version_sizes(session.catalog()?)?.yarn
+    } else {
+        BTreeMap::new()
+    };
+    let mut provenance = BTreeMap::new();
+    if filter.provenance {
+        for (key, record) in session.catalog()?.yarn.provenance.iter() {
+            if let Ok(version) = Version::parse(key) {
+                provenance.insert(version, vec![(None, record.clone())]);
+            }
+        }
+    }
+
+    let versions: Vec<(Version, Option<String>)> = if filter.remote {
+        public_yarn_versions()?
+            .into_iter()
+            .map(|version| (version, None))
+            .collect()
+    } else {
+        installed.iter().rev().map(|version| (version.clone(), None)).collect()
+    };
+
+    let is_installed = |version: &Version| installed.contains(version);
+    print_versions(&filter, versions, is_installed, &system, current, &sizes, &provenance)
+}
+
+fn list_packages(session: &Session, porcelain: Option<Porcelain>) -> Fallible<()> {
+    for (name, tool) in &session.catalog()?.packages {
+        if let Some(porcelain) = porcelain {
+            let version = tool.version.to_string();
+            let node = tool.node.to_string();
+            let npm = tool.npm.as_ref().map(|npm| npm.to_string()).unwrap_or_default();
+            let bins = tool.bins.join(",");
+            let fields: [&str; 5] = [name, &version, &node, &npm, &bins];
+            println!("{}", porcelain.line(&fields));
+            continue;
+        }
+        let platform = match tool.npm {
+            Some(ref npm) => format!("Node v{}, npm v{}", tool.node, npm),
+            None => format!("Node v{}", tool.node),
+        };
+        println!("{} v{} ({}): {}", name, tool.version, platform, tool.bins.join(", "));
+    }
+    Ok(())
+}
+
+fn print_versions<F>(
+    filter: &Filter,
+    versions: Vec<(Version, Option<String>)>,
+    is_installed: F,
+    system: &BTreeSet<Version>,
+    current: Option<Version>,
+    sizes: &BTreeMap<Version, u64>,
+    provenance: &BTreeMap<Version, Vec<(Option<String>, Provenance)>>,
+) -> Fallible<()>
+where
+    F: Fn(&Version) -> bool,
+{
+    let mut found = false;
+    let mut total = 0;
+
+    for (version, lts) in versions {
+        if !filter.matches(&version, &lts) {
+            continue;
+        }
+        found = true;
+
+        if let Some(porcelain) = filter.porcelain {
+            let installed = !filter.remote || is_installed(&version);
+            let version_str = version.to_string();
+            let fields = [
+                &version_str[..],
+                if installed { "installed" } else { "" },
+                if current.as_ref() == Some(&version) { "current" } else { "" },
+                if system.contains(&version) { "system" } else { "" },
+                lts.as_ref().map_or("", |codename| &codename[..]),
+            ];
+            println!("{}", porcelain.line(&fields));
+            continue;
+        }
+
+        let mut line = format!("v{}", version);
+        if let Some(codename) = lts {
+            line.push_str(&format!(" (lts: {})", codename));
+        }
+        if filter.remote && is_installed(&version) {
+            line.push_str(" [installed]");
+        }
+        if system.contains(&version) {
+            line.push_str(" [system]");
+        }
+        if current.as_ref() == Some(&version) {
+            line.push_str(" [current]");
+        }
+        if let Some(bytes) = sizes.get(&version) {
+            line.push_str(&format!(" ({})", format_size(*bytes)));
+            total += bytes;
+        }
+        println!("{}", line);
+
+        if filter.provenance && is_installed(&version) {
+            print_provenance(provenance.get(&version).map_or(&[][..], |records| &records[..]));
+        }
+    }
+
+    if !found {
+        throw!(NoVersionsFoundError);
+    }
+    if filter.size {
+        println!("total: {}", format_size(total));
+    }
+    Ok(())
+}
+
+/// Prints the provenance of an installed version's builds, labeled with their
+/// architectures if they are not native builds.
+fn print_provenance(records: &[(Option<String>, Provenance)]) {
+    if records.is_empty() {
+        println!("    provenance: not recorded");
+    }
+    for &(ref label, ref record) in records {
+        let indent = match label {
+            &Some(ref arch) => {
+                println!("    {}:", arch);
+                "        "
+            }
+            &None => "    ",
+        };
+        println!("{}url: {}", indent, record.url);
+        println!("{}fetched: {}", indent, format_timestamp(record.fetched));
+        println!("{}sha256: {}", indent, record.sha256);
+        println!("{}verification: {}", indent, record.verification);
+    }
+}
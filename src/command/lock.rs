@@ -0,0 +1,70 @@
+use notion_core::lockfile::{self, LOCK_FILE};
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args;
+
+pub(crate) enum Lock {
+    Help,
+    Lock,
+}
+
+impl Command for Lock {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Lock the project's toolchain to exact archives
+
+Usage:
+    notion lock
+    notion lock -h | --help
+
+Options:
+    -h, --help     Display this message
+
+Resolves the versions the current project pins, installs them if necessary, and
+records them in `notion.lock` next to `package.json`, along with the URL and
+SHA-256 checksum of the archive each was installed from. Commit the lockfile:
+while it matches the project's pins, Notion uses the versions it records rather
+than resolving the pins again, and installs them from the recorded archives,
+verified against their checksums. `notion verify --locked` fails if the pins or
+the toolchain in use deviate from the lockfile.
+
+Node archives are recorded for the platform `notion lock` runs on. Run it again
+on each platform the project is developed or tested on to add its archive.
+";
+
+    fn help() -> Self {
+        Lock::Help
+    }
+
+    fn parse(_: Notion, _: Args) -> Fallible<Self> {
+        Ok(Lock::Lock)
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Lock);
+        match self {
+            Lock::Help => Help::Command(CommandName::Lock).run(session)?,
+            Lock::Lock => {
+                let lockfile = session.lock_project()?;
+                let mut tools = vec![format!("node v{}", lockfile.node.version)];
+                if let Some(ref yarn) = lockfile.yarn {
+                    tools.push(format!("yarn v{}", yarn.version));
+                }
+                println!(
+                    "Locked {} for {} in {}",
+                    tools.join(", "),
+                    lockfile::platform(),
+                    LOCK_FILE
+                );
+            }
+        };
+        session.add_event_end(ActivityKind::Lock, ExitCode::Success);
+        Ok(())
+    }
+}
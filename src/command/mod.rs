@@ -1,23 +1,85 @@
+mod alias;
+mod backup;
+mod bench;
+mod bundle;
+mod cache;
+mod ci;
+mod complete;
+mod completions;
+mod compress;
 mod config;
 mod current;
+mod daemon;
 mod deactivate;
+mod dedupe;
+mod env;
+mod explain;
+mod export_env;
 mod fetch;
+mod generate;
 mod help;
 mod install;
+mod list;
+mod lock;
+mod outdated;
+mod prompt;
+mod repair;
+mod run;
+mod sbom;
+mod search;
+mod setup;
 mod shim;
+mod status;
+mod trust;
+mod update;
+mod upgrade;
 mod use_;
+mod verify;
 mod version;
+mod which;
+mod why;
 
+pub(crate) use self::alias::Alias;
+pub(crate) use self::backup::Backup;
+pub(crate) use self::bench::Bench;
+pub(crate) use self::bundle::Bundle;
+pub(crate) use self::cache::Cache;
+pub(crate) use self::ci::Ci;
+pub(crate) use self::complete::Complete;
+pub(crate) use self::completions::Completions;
+pub(crate) use self::compress::Compress;
 pub(crate) use self::config::Config;
 pub(crate) use self::current::Current;
+pub(crate) use self::daemon::Daemon;
 pub(crate) use self::deactivate::Deactivate;
+pub(crate) use self::dedupe::Dedupe;
+pub(crate) use self::env::Env;
+pub(crate) use self::explain::Explain;
+pub(crate) use self::export_env::ExportEnv;
 pub(crate) use self::fetch::Fetch;
+pub(crate) use self::generate::Generate;
 pub(crate) use self::help::Help;
 pub(crate) use self::install::Install;
+pub(crate) use self::list::List;
+pub(crate) use self::lock::Lock;
+pub(crate) use self::outdated::Outdated;
+pub(crate) use self::prompt::Prompt;
+pub(crate) use self::repair::Repair;
+pub(crate) use self::run::Run;
+pub(crate) use self::sbom::Sbom;
+pub(crate) use self::search::Search;
+pub(crate) use self::setup::Setup;
 #[cfg(feature = "notion-dev")]
 pub(crate) use self::shim::Shim;
+pub(crate) use self::status::Status;
+pub(crate) use self::trust::Trust;
+pub(crate) use self::update::Update;
+pub(crate) use self::upgrade::Upgrade;
 pub(crate) use self::use_::Use;
+pub(crate) use self::verify::Verify;
 pub(crate) use self::version::Version;
+pub(crate) use self::which::Which;
+pub(crate) use self::why::Why;
 
 use docopt::Docopt;
 use serde::de::DeserializeOwned;
@@ -25,7 +87,7 @@ use serde::de::DeserializeOwned;
 use notion_core::session::Session;
 use notion_fail::{FailExt, Fallible};
 
-use {CliParseError, DocoptExt, Notion};
+use {CliParseError, DocoptExt, Notion, UnknownPorcelainError};
 
 use std::fmt::{self, Display};
 use std::str::FromStr;
@@ -41,6 +103,40 @@ pub(crate) enum CommandName {
     Deactivate,
     #[cfg(feature = "notion-dev")]
     Shim,
+    #[serde(rename = "export-env")]
+    ExportEnv,
+    Why,
+    Bundle,
+    Which,
+    Env,
+    Backup,
+    List,
+    Search,
+    Update,
+    Verify,
+    Repair,
+    Cache,
+    Dedupe,
+    Compress,
+    Setup,
+    Completions,
+    /// Not listed in the usage, since it is only run by completion scripts.
+    #[serde(rename = "__complete")]
+    Complete,
+    Alias,
+    Upgrade,
+    Outdated,
+    Run,
+    Ci,
+    Explain,
+    Bench,
+    Generate,
+    Sbom,
+    Trust,
+    Lock,
+    Prompt,
+    Status,
+    Daemon,
     Help,
     Version,
 }
@@ -59,6 +155,37 @@ impl Display for CommandName {
                 CommandName::Current => "current",
                 #[cfg(feature = "notion-dev")]
                 CommandName::Shim => "shim",
+                CommandName::ExportEnv => "export-env",
+                CommandName::Why => "why",
+                CommandName::Bundle => "bundle",
+                CommandName::Which => "which",
+                CommandName::Env => "env",
+                CommandName::Backup => "backup",
+                CommandName::List => "list",
+                CommandName::Search => "search",
+                CommandName::Update => "update",
+                CommandName::Verify => "verify",
+                CommandName::Repair => "repair",
+                CommandName::Cache => "cache",
+                CommandName::Dedupe => "dedupe",
+                CommandName::Compress => "compress",
+                CommandName::Setup => "setup",
+                CommandName::Completions => "completions",
+                CommandName::Complete => "__complete",
+                CommandName::Alias => "alias",
+                CommandName::Upgrade => "upgrade",
+                CommandName::Outdated => "outdated",
+                CommandName::Run => "run",
+                CommandName::Ci => "ci",
+                CommandName::Explain => "explain",
+                CommandName::Bench => "bench",
+                CommandName::Generate => "generate",
+                CommandName::Sbom => "sbom",
+                CommandName::Trust => "trust",
+                CommandName::Lock => "lock",
+                CommandName::Prompt => "prompt",
+                CommandName::Status => "status",
+                CommandName::Daemon => "daemon",
                 CommandName::Help => "help",
                 CommandName::Version => "version",
             }
@@ -79,6 +206,37 @@ impl FromStr for CommandName {
             "deactivate" => CommandName::Deactivate,
             #[cfg(feature = "notion-dev")]
             "shim" => CommandName::Shim,
+            "export-env" => CommandName::ExportEnv,
+            "why" => CommandName::Why,
+            "bundle" => CommandName::Bundle,
+            "which" => CommandName::Which,
+            "env" => CommandName::Env,
+            "backup" => CommandName::Backup,
+            "list" => CommandName::List,
+            "search" => CommandName::Search,
+            "update" => CommandName::Update,
+            "verify" => CommandName::Verify,
+            "repair" => CommandName::Repair,
+            "cache" => CommandName::Cache,
+            "dedupe" => CommandName::Dedupe,
+            "compress" => CommandName::Compress,
+            "setup" => CommandName::Setup,
+            "completions" => CommandName::Completions,
+            "__complete" => CommandName::Complete,
+            "alias" => CommandName::Alias,
+            "upgrade" => CommandName::Upgrade,
+            "outdated" => CommandName::Outdated,
+            "run" => CommandName::Run,
+            "ci" => CommandName::Ci,
+            "explain" => CommandName::Explain,
+            "bench" => CommandName::Bench,
+            "generate" => CommandName::Generate,
+            "sbom" => CommandName::Sbom,
+            "trust" => CommandName::Trust,
+            "lock" => CommandName::Lock,
+            "prompt" => CommandName::Prompt,
+            "status" => CommandName::Status,
+            "daemon" => CommandName::Daemon,
             "help" => CommandName::Help,
             "version" => CommandName::Version,
             _ => {
@@ -88,6 +246,51 @@ impl FromStr for CommandName {
     }
 }
 
+/// Produces the name and description of each command listed in Notion's usage, for
+/// tab completion.
+pub(crate) fn documented_commands() -> Vec<(&'static str, &'static str)> {
+    Notion::USAGE
+        .lines()
+        .skip_while(|line| !line.starts_with("Some common notion commands are:"))
+        .skip(1)
+        .take_while(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            let line = line.trim();
+            line.find(' ').map(|end| (&line[..end], line[end..].trim()))
+        })
+        .collect()
+}
+
+/// The versions of the output of `--porcelain`, which is meant for scripts to parse.
+/// Each command documents its records, and the output of a version never changes; a
+/// different output is a new version. Records are lines of tab-separated fields, with
+/// `-` for a field with no value, except for the `tool=version source` lines of
+/// `notion status`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Porcelain {
+    V1,
+}
+
+impl Porcelain {
+    /// Parses the value of a `--porcelain=<version>` option, if it was given.
+    pub(crate) fn parse(version: Option<String>) -> Fallible<Option<Self>> {
+        match version {
+            None => Ok(None),
+            Some(ref version) if version == "v1" => Ok(Some(Porcelain::V1)),
+            Some(format) => throw!(UnknownPorcelainError { format }),
+        }
+    }
+
+    /// Formats the fields of a record as a line of output.
+    pub(crate) fn line(&self, fields: &[&str]) -> String {
+        fields
+            .iter()
+            .map(|field| if field.is_empty() { "-" } else { *field })
+            .collect::<Vec<_>>()
+            .join("\t")
+    }
+}
+
 /// A Notion command.
 pub(crate) trait Command: Sized {
     /// The intermediate type Docopt should deserialize the parsed command into.
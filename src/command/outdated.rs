@@ -0,0 +1,118 @@
+use serde_json;
+
+use notion_core::outdated::{self, Comparison};
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible, ResultExt};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    flag_all: bool,
+    flag_json: bool,
+}
+
+pub(crate) enum Outdated {
+    Help,
+    Outdated { all: bool, json: bool },
+}
+
+impl Command for Outdated {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Show tools with newer versions available
+
+Usage:
+    notion outdated [options]
+    notion outdated -h | --help
+
+Options:
+    -a, --all      Include tools that are up to date
+    --json         Print the comparison as JSON
+    -h, --help     Display this message
+
+The Node and Yarn versions pinned by the current project and selected in your
+toolchain, and the installed package tools, are compared with the newest
+releases:
+
+    current        The version in use
+    wanted         The newest release allowed by the project's range pin or the
+                   range a package tool was installed with, or for exact versions
+                   the newest release of the same major version
+    latest         The newest release; for Node versions in an LTS line, the
+                   newest LTS release
+
+See `notion update` for updating range pins, and `notion upgrade` for upgrading
+package tools.
+";
+
+    fn help() -> Self {
+        Outdated::Help
+    }
+
+    fn parse(_: Notion, Args { flag_all, flag_json }: Args) -> Fallible<Self> {
+        Ok(Outdated::Outdated {
+            all: flag_all,
+            json: flag_json,
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Outdated);
+        match self {
+            Outdated::Help => Help::Command(CommandName::Outdated).run(session)?,
+            Outdated::Outdated { all, json } => {
+                let comparisons: Vec<Comparison> = outdated::compare(session)?
+                    .into_iter()
+                    .filter(|comparison| all || comparison.is_outdated())
+                    .collect();
+
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&comparisons).unknown()?);
+                } else if comparisons.is_empty() {
+                    println!("Everything is up to date");
+                } else {
+                    print_table(&comparisons);
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Outdated, ExitCode::Success);
+        Ok(())
+    }
+}
+
+fn print_table(comparisons: &[Comparison]) {
+    let mut rows = vec![[
+        "tool".to_string(),
+        "source".to_string(),
+        "current".to_string(),
+        "wanted".to_string(),
+        "latest".to_string(),
+    ]];
+    for comparison in comparisons {
+        rows.push([
+            comparison.tool.clone(),
+            comparison.source.to_string(),
+            comparison.current.clone(),
+            comparison.wanted.clone(),
+            comparison.latest.clone(),
+        ]);
+    }
+
+    let mut widths = [0; 5];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    for row in &rows {
+        let cells: Vec<String> = row.iter()
+            .zip(widths.iter())
+            .map(|(cell, &width)| format!("{:width$}", cell, width = width))
+            .collect();
+        println!("{}", cells.join("  ").trim_right());
+    }
+}
@@ -0,0 +1,100 @@
+use std::env;
+
+use docopt::Docopt;
+
+use notion_core::prompt::{self, DEFAULT_FORMAT};
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    flag_format: Option<String>,
+}
+
+pub(crate) enum Prompt {
+    Help,
+    Status { format: String },
+}
+
+impl Prompt {
+    /// Answers `notion prompt` from the platform cache, before a session is created, which
+    /// would read the project and the configuration. Produces false if the status isn't
+    /// cached, so the command runs as usual.
+    pub(crate) fn answer_early() -> bool {
+        let argv: Vec<String> = env::args().collect();
+        if argv.get(1).map(String::as_str) != Some("prompt") {
+            return false;
+        }
+        let args: Args = match Docopt::new(Prompt::USAGE).and_then(|d| d.argv(argv).deserialize()) {
+            Ok(args) => args,
+            Err(_) => return false,
+        };
+        let status = match env::current_dir() {
+            Ok(dir) => prompt::cached_status(&dir),
+            Err(_) => return false,
+        };
+        match status {
+            Ok(Some(status)) => {
+                let format = args.flag_format.unwrap_or_else(|| DEFAULT_FORMAT.to_string());
+                println!("{}", status.render(&format));
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Command for Prompt {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Print the active Node version for a shell prompt
+
+Usage:
+    notion prompt [--format=<format>]
+    notion prompt -h | --help
+
+Options:
+    -h, --help           Display this message
+    --format=<format>    The format to print the versions in, where {node} and
+                         {yarn} are replaced with the Node and Yarn versions
+                         (defaults to '⬢ {node}')
+
+Prints nothing when no Node version is active, and never fails, so it can be
+run on every prompt render. Inside a project whose platform has been resolved
+before, the versions are read from the platform cache without loading the
+catalog, which keeps the command fast enough to run on every prompt.
+
+For example, in bash:
+
+    PS1='$(notion prompt) \\$ '
+";
+
+    fn help() -> Self {
+        Prompt::Help
+    }
+
+    fn parse(_: Notion, Args { flag_format }: Args) -> Fallible<Self> {
+        Ok(Prompt::Status {
+            format: flag_format.unwrap_or_else(|| DEFAULT_FORMAT.to_string()),
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Prompt);
+        match self {
+            Prompt::Help => Help::Command(CommandName::Prompt).run(session)?,
+            Prompt::Status { format } => {
+                // a prompt can't show errors, so any failure just leaves it empty
+                if let Ok(Some(status)) = prompt::status(session) {
+                    println!("{}", status.render(&format));
+                }
+            }
+        }
+        session.add_event_end(ActivityKind::Prompt, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use semver::Version;
+
+use notion_core::arch::Arch;
+use notion_core::session::{ActivityKind, Session};
+use notion_core::version::VersionSpec;
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_tool_version: String,
+    flag_arch: Option<String>,
+}
+
+/// Thrown when the tool to repair is not given as `<tool>@<version>`.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "expected a tool and version like `node@18.17.1`, got `{}`", given)]
+#[notion_fail(code = "InvalidArguments")]
+struct ToolVersionParseError {
+    given: String,
+}
+
+/// Thrown when the tool to repair is not one Notion installs.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "cannot repair `{}`: only node and yarn versions can be repaired", tool)]
+#[notion_fail(code = "InvalidArguments")]
+struct UnsupportedToolError {
+    tool: String,
+}
+
+pub(crate) enum Repair {
+    Help,
+    Node {
+        version: Version,
+        arch: Arch,
+        dry_run: bool,
+    },
+    Yarn { version: Version, dry_run: bool },
+}
+
+impl Command for Repair {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Reinstall a damaged tool version
+
+Usage:
+    notion repair <tool-version> [--arch=<arch>]
+    notion repair -h | --help
+
+Options:
+    --arch=<arch>   Repair the Node build for an architecture other than this
+                    machine's (x86, x64, arm64, or armv7l)
+    -h, --help      Display this message
+
+The version is given as <tool>@<version>, e.g. `node@18.17.1` or `yarn@1.22.19`.
+Its directory is replaced by a fresh copy unpacked from the cached archive (which
+is downloaded again if it is no longer cached), and the old directory is removed
+only once the new one is in place. Default versions and project pins are left as
+they are. Packages installed globally with a repaired Node version need to be
+installed again.
+
+See `notion help verify` for finding damaged versions.
+";
+
+    fn help() -> Self {
+        Repair::Help
+    }
+
+    fn parse(
+        notion: Notion,
+        Args {
+            arg_tool_version,
+            flag_arch,
+        }: Args,
+    ) -> Fallible<Self> {
+        let dry_run = notion.dry_run();
+
+        let (tool, version) = match arg_tool_version.find('@') {
+            Some(index) => (&arg_tool_version[..index], &arg_tool_version[index + 1..]),
+            None => throw!(ToolVersionParseError {
+                given: arg_tool_version.clone(),
+            }),
+        };
+        let version = VersionSpec::parse_version(version.trim_left_matches('v'))?;
+
+        let arch = match flag_arch {
+            Some(arch) => arch.parse::<Arch>()?,
+            None => Arch::native(),
+        };
+
+        Ok(match tool {
+            "node" => Repair::Node {
+                version,
+                arch,
+                dry_run,
+            },
+            "yarn" => Repair::Yarn { version, dry_run },
+            _ => throw!(UnsupportedToolError {
+                tool: tool.to_string(),
+            }),
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Repair);
+        match self {
+            Repair::Help => Help::Command(CommandName::Repair).run(session)?,
+            Repair::Node {
+                version,
+                arch,
+                dry_run: true,
+            } => {
+                let plan = session.plan_repair_node(&version, arch)?;
+                println!("{}", plan);
+            }
+            Repair::Node { version, arch, .. } => {
+                session.repair_node(&version, arch)?;
+                println!("repaired node v{}", version);
+            }
+            Repair::Yarn {
+                version,
+                dry_run: true,
+            } => {
+                let plan = session.plan_repair_yarn(&version)?;
+                println!("{}", plan);
+            }
+            Repair::Yarn { version, .. } => {
+                session.repair_yarn(&version)?;
+                println!("repaired yarn v{}", version);
+            }
+        };
+        session.add_event_end(ActivityKind::Repair, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -0,0 +1,131 @@
+use notion_core::matrix::{self, Outcome};
+use notion_core::session::{ActivityKind, Session};
+use notion_core::version::VersionSpec;
+use notion_fail::{ExitCode, Fallible};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_command: String,
+    arg_args: Vec<String>,
+    flag_node: String,
+    flag_parallel: bool,
+}
+
+pub(crate) enum Run {
+    Help,
+    Run {
+        versions: Vec<VersionSpec>,
+        command: String,
+        args: Vec<String>,
+        parallel: bool,
+        dry_run: bool,
+    },
+}
+
+impl Command for Run {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Run a command with each of several Node versions
+
+Usage:
+    notion run --node=<versions> [--parallel] [--] <command> [<args>...]
+    notion run -h | --help
+
+Options:
+    --node=<versions>   A comma-separated list of Node versions to run with
+    --parallel          Run with every version at once
+    -h, --help          Display this message
+
+Each version is resolved like `notion fetch node <version>`, and fetched if it
+isn't installed, then the command runs with it in turn, as if the project
+pinned it. For example, to run a library's tests with the newest Node 16, 18,
+and 20:
+
+    notion run --node=16,18,20 -- npm test
+
+With --parallel, the output of each run is printed once it finishes. A summary
+of which versions passed and failed is printed at the end. If any version
+fails, notion exits with the exit code the failed runs share, or with 1 if
+they failed with different exit codes.
+";
+
+    fn help() -> Self {
+        Run::Help
+    }
+
+    fn parse(
+        notion: Notion,
+        Args {
+            arg_command,
+            arg_args,
+            flag_node,
+            flag_parallel,
+        }: Args,
+    ) -> Fallible<Self> {
+        let mut versions = Vec::new();
+        for version in flag_node.split(',') {
+            versions.push(VersionSpec::parse(version.trim())?);
+        }
+
+        Ok(Run::Run {
+            versions,
+            command: arg_command,
+            args: arg_args,
+            parallel: flag_parallel,
+            dry_run: notion.dry_run(),
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Run);
+        match self {
+            Run::Help => Help::Command(CommandName::Run).run(session)?,
+            Run::Run {
+                versions,
+                command,
+                dry_run: true,
+                ..
+            } => {
+                let (nodes, plan) = matrix::plan_images(session, &versions)?;
+                println!("{}", plan);
+                for node in nodes {
+                    println!("Would run '{}' with Node v{}", command, node);
+                }
+            }
+            Run::Run {
+                versions,
+                command,
+                args,
+                parallel,
+                ..
+            } => {
+                let images = matrix::images(session, &versions)?;
+                let outcomes = if parallel {
+                    matrix::run_parallel(&images, &command, &args)?
+                } else {
+                    matrix::run_sequential(&images, &command, &args)?
+                };
+                print_summary(&outcomes);
+                matrix::check(&outcomes)?;
+            }
+        };
+        session.add_event_end(ActivityKind::Run, ExitCode::Success);
+        Ok(())
+    }
+}
+
+fn print_summary(outcomes: &[Outcome]) {
+    println!();
+    println!("Summary:");
+    for outcome in outcomes {
+        if outcome.passed() {
+            println!("    Node v{}: passed", outcome.node);
+        } else {
+            println!("    Node v{}: failed (exit code {})", outcome.node, outcome.code);
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use notion_core::sbom::{self, Format};
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    flag_project: bool,
+    flag_format: String,
+}
+
+/// Thrown when `notion sbom --project` is run outside a project that pins a toolchain.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "No toolchain is pinned for the current project")]
+#[notion_fail(code = "NoVersionMatch")]
+struct NoPlatformError;
+
+pub(crate) enum Sbom {
+    Help,
+    Sbom { project: bool, format: Format },
+}
+
+impl Command for Sbom {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Print a software bill of materials for installed tools
+
+Usage:
+    notion sbom [--project | --all] [--format=<format>]
+    notion sbom -h | --help
+
+Options:
+    -h, --help           Display this message
+    --project            List only the toolchain the current project pins
+    --all                List every installed tool (the default)
+    --format=<format>    Write the SBOM as `cyclonedx` or `spdx` [default: cyclonedx]
+
+Installed Node, npm, and Yarn versions, and the package tools installed with
+`notion install`, are listed with their versions, the URLs they were downloaded
+from, and the SHA-256 digest of the archive each Node and Yarn version was
+installed from (see `notion list --provenance`). Both formats are written as
+JSON: CycloneDX 1.5 and SPDX 2.3.
+";
+
+    fn help() -> Self {
+        Sbom::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            flag_project,
+            flag_format,
+        }: Args,
+    ) -> Fallible<Self> {
+        Ok(Sbom::Sbom {
+            project: flag_project,
+            format: flag_format.parse()?,
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Sbom);
+        match self {
+            Sbom::Help => Help::Command(CommandName::Sbom).run(session)?,
+            Sbom::Sbom { project, format } => {
+                let components = if project {
+                    let image = match session.project_platform()? {
+                        Some(image) => image,
+                        None => throw!(NoPlatformError),
+                    };
+                    sbom::image_components(session.catalog()?, &image)?
+                } else {
+                    sbom::components(session.catalog()?)?
+                };
+                println!("{}", sbom::render(format, &components)?);
+            }
+        };
+        session.add_event_end(ActivityKind::Sbom, ExitCode::Success);
+        Ok(())
+    }
+}
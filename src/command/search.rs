@@ -0,0 +1,87 @@
+use notion_core::search::search_node;
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_query: String,
+    flag_limit: usize,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "nothing matches `{}`", query)]
+#[notion_fail(code = "NoVersionMatch")]
+struct NoMatchesError {
+    query: String,
+}
+
+pub(crate) enum Search {
+    Help,
+    Query { query: String, limit: usize },
+}
+
+impl Command for Search {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Search for installable tool versions
+
+Usage:
+    notion search [options] <query>
+    notion search -h | --help
+
+Options:
+    -n, --limit=<n>  Show at most this many results [default: 20]
+    -h, --help       Display this message
+
+The query is matched against Node versions (e.g. `16.20`) and LTS codenames
+(e.g. `hydrogen`), and the best matches are listed first.
+";
+
+    fn help() -> Self {
+        Search::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            arg_query,
+            flag_limit,
+        }: Args,
+    ) -> Fallible<Self> {
+        Ok(Search::Query {
+            query: arg_query,
+            limit: flag_limit,
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Search);
+        match self {
+            Search::Help => Help::Command(CommandName::Search).run(session)?,
+            Search::Query { query, limit } => {
+                let matches = search_node(&query)?;
+                if matches.is_empty() {
+                    throw!(NoMatchesError { query });
+                }
+
+                let catalog = session.catalog()?;
+                for found in matches.into_iter().take(limit) {
+                    let mut line = format!("node v{}", found.version);
+                    if let Some(codename) = found.lts {
+                        line.push_str(&format!(" (lts: {})", codename));
+                    }
+                    if catalog.node.contains(&found.version) {
+                        line.push_str(" [installed]");
+                    }
+                    println!("{}", line);
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Search, ExitCode::Success);
+        Ok(())
+    }
+}
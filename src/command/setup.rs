@@ -0,0 +1,135 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use notion_core::session::{ActivityKind, Session};
+use notion_core::setup::{SetupShell, ShellSetup};
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use Notion;
+use command::{documented_commands, Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    flag_shell: Option<String>,
+    flag_profile: Option<String>,
+}
+
+/// Thrown when `notion setup` is asked to set up a shell it doesn't support.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Cannot set up '{}': expected powershell, fish, or nu", name)]
+#[notion_fail(code = "InvalidArguments")]
+struct UnsupportedSetupShellError {
+    name: String,
+}
+
+/// Thrown when no shell is named and the current one cannot be set up.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not tell which shell to set up: use --shell")]
+#[notion_fail(code = "InvalidArguments")]
+struct UnknownSetupShellError;
+
+pub(crate) enum Setup {
+    Help,
+    Setup {
+        shell: SetupShell,
+        profile: Option<PathBuf>,
+        dry_run: bool,
+    },
+}
+
+impl Command for Setup {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Integrate Notion with PowerShell, fish, or Nushell
+
+Usage:
+    notion setup [options]
+    notion setup -h | --help
+
+Options:
+    -s, --shell <shell>  The shell to set up: powershell, fish, or nu (defaults to
+                         the current shell)
+    --profile <file>     The profile script to set up (defaults to PowerShell's
+                         $PROFILE, fish's config.fish, or Nushell's config.nu)
+    -h, --help           Display this message
+
+This generates a script for the shell and loads it from your profile. The
+script puts Notion's shims on the PATH, lets commands like `notion use` change
+the environment of the running shell, and completes Notion's commands and tool
+versions with Tab. Running it again regenerates the script and leaves the
+profile as it is.
+
+For PowerShell, the script is a module that also defines Get-NotionPrompt,
+which produces the active Node version for use in your prompt:
+
+    function prompt { \"$(Get-NotionPrompt)PS $PWD> \" }
+
+Bash and Zsh profiles are set up by the Notion installer.
+";
+
+    fn help() -> Self {
+        Setup::Help
+    }
+
+    fn parse(
+        notion: Notion,
+        Args {
+            flag_shell,
+            flag_profile,
+        }: Args,
+    ) -> Fallible<Self> {
+        let shell = match flag_shell.or_else(current_shell) {
+            Some(name) => match name.as_str() {
+                "powershell" | "pwsh" => SetupShell::PowerShell,
+                "fish" => SetupShell::Fish,
+                "nu" => SetupShell::Nu,
+                _ => throw!(UnsupportedSetupShellError { name: name.clone() }),
+            },
+            None => throw!(UnknownSetupShellError),
+        };
+
+        Ok(Setup::Setup {
+            shell,
+            profile: flag_profile.map(PathBuf::from),
+            dry_run: notion.dry_run(),
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Setup);
+        match self {
+            Setup::Help => Help::Command(CommandName::Setup).run(session)?,
+            Setup::Setup {
+                shell,
+                profile,
+                dry_run,
+            } => {
+                let setup = ShellSetup::new(shell, profile)?;
+                if dry_run {
+                    println!("{}", setup.plan());
+                } else if setup.install(&documented_commands())? {
+                    println!("Notion is set up in {}", setup.profile.display());
+                    println!("Open a new shell to start using it.");
+                } else {
+                    println!("Regenerated {}", setup.script.display());
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Setup, ExitCode::Success);
+        Ok(())
+    }
+}
+
+/// Produces the name of the shell Notion is running from, if it can tell: the
+/// shell named by `$SHELL` on Unix, and PowerShell on Windows.
+fn current_shell() -> Option<String> {
+    if cfg!(windows) {
+        return Some("powershell".to_string());
+    }
+    env::var_os("SHELL").and_then(|shell| {
+        Path::new(&shell)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+    })
+}
@@ -3,18 +3,22 @@
 use std::ffi::OsStr;
 use std::fmt::{self, Display, Formatter};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 use console::style;
+use notion_core::plan::{Action, Plan};
 use notion_core::project::Project;
 use notion_core::session::{ActivityKind, Session};
 use notion_core::style::{display_error, display_unknown_error, ErrorContext};
-use notion_core::{path, shim};
+use notion_core::version::VersionSpec;
+use notion_core::shim::Strategy;
+use notion_core::{autoshim, config, export, path, shim};
 use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
 use semver::Version;
 
 use Notion;
-use command::{Command, CommandName, Help};
+use command::{Command, CommandName, Help, Porcelain};
 
 /// Thrown when one or more errors occurred while autoshimming.
 #[derive(Debug, Fail, NotionFail)]
@@ -31,6 +35,20 @@ struct NotAPackageError {
     path: String,
 }
 
+/// Thrown when `notion shim audit` finds shims that do not match the installed Notion.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} shim(s) do not match this version of Notion", count)]
+#[notion_fail(code = "ConfigurationError")]
+struct ShimAuditError {
+    count: usize,
+}
+
+/// Thrown when there is no platform to export.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "No Node version is pinned for the current project or selected by the user")]
+#[notion_fail(code = "NoVersionMatch")]
+struct NoPlatformError;
+
 /// Thrown when the user tries to create a shim which already exists.
 #[derive(Debug, Fail, NotionFail)]
 #[fail(display = "shim `{}` already exists", name)]
@@ -39,6 +57,24 @@ struct ShimAlreadyExistsError {
     name: String,
 }
 
+/// Thrown when the user tries to create a shim that would shadow an executable on the
+/// `PATH` without `--force`.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "shim `{}` would shadow {}\nUse --force to create it anyway", name, shadowed)]
+#[notion_fail(code = "FileSystemError")]
+struct ShimConflictError {
+    name: String,
+    shadowed: String,
+}
+
+/// Thrown when the target given for a shim is not an executable file.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "shim target {} is not a file", path)]
+#[notion_fail(code = "FileSystemError")]
+struct NoSuchTargetError {
+    path: String,
+}
+
 /// Thrown when the user tries to delete a shim which doesn't exist.
 #[derive(Debug, Fail, NotionFail)]
 #[fail(display = "shim `{}` does not exist", name)]
@@ -49,22 +85,61 @@ struct ShimDoesntExistError {
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct Args {
+    arg_dir: String,
     arg_path: Option<String>,
     arg_shimname: String,
+    arg_strategy: String,
+    cmd_audit: bool,
     cmd_auto: bool,
+    cmd_conflicts: bool,
     cmd_create: bool,
     cmd_delete: bool,
+    cmd_export: bool,
     cmd_list: bool,
+    cmd_migrate: bool,
+    flag_fix: bool,
+    flag_force: bool,
     flag_help: bool,
+    flag_interpreter: Option<String>,
+    flag_keep_orphans: bool,
+    flag_node: Option<String>,
+    flag_porcelain: Option<String>,
+    flag_stale: bool,
+    flag_target: Option<String>,
     flag_verbose: bool,
 }
 
 pub(crate) enum Shim {
     Help,
-    List(bool),
-    Create(String, bool),
-    Delete(String, bool),
-    Auto(Option<PathBuf>, bool),
+    List {
+        stale: bool,
+        verbose: bool,
+        porcelain: Option<Porcelain>,
+    },
+    Create {
+        name: String,
+        target: Option<PathBuf>,
+        node: Option<VersionSpec>,
+        interpreter: Option<Vec<String>>,
+        force: bool,
+        verbose: bool,
+        dry_run: bool,
+    },
+    Delete {
+        name: String,
+        verbose: bool,
+        dry_run: bool,
+    },
+    Auto {
+        path: Option<PathBuf>,
+        keep_orphans: bool,
+        verbose: bool,
+        dry_run: bool,
+    },
+    Audit { fix: bool, dry_run: bool },
+    Conflicts,
+    Migrate { strategy: Strategy, dry_run: bool },
+    Export { dir: PathBuf, dry_run: bool },
 }
 
 enum ShimKind {
@@ -74,6 +149,53 @@ enum ShimKind {
     NotInstalled,
     WillInstall(Version),
     Unimplemented,
+    Stale(StaleReason),
+}
+
+/// The reasons a shim can be considered stale.
+enum StaleReason {
+    /// The executable the shim delegates to no longer exists.
+    MissingTarget,
+    /// The shims were generated by another version of Notion than the one running.
+    Outdated,
+    /// No installed tool or project dependency provides an executable with this name.
+    Unprovided,
+}
+
+impl StaleReason {
+    /// The name of the reason in `--porcelain` output.
+    fn porcelain_name(&self) -> &'static str {
+        match self {
+            &StaleReason::MissingTarget => "missing-target",
+            &StaleReason::Outdated => "outdated",
+            &StaleReason::Unprovided => "unprovided",
+        }
+    }
+}
+
+impl Display for StaleReason {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(match self {
+            &StaleReason::MissingTarget => "executable was removed",
+            &StaleReason::Outdated => "generated by another version of Notion",
+            &StaleReason::Unprovided => "no installed tool provides this executable",
+        })
+    }
+}
+
+impl ShimKind {
+    /// The kind and detail fields of the shim in `--porcelain` output.
+    fn porcelain_fields(&self) -> (&'static str, String) {
+        match self {
+            &ShimKind::Project(ref path) => ("project", path.to_string_lossy().into_owned()),
+            &ShimKind::User(ref path) => ("user", path.to_string_lossy().into_owned()),
+            &ShimKind::System => ("system", String::new()),
+            &ShimKind::NotInstalled => ("not-installed", String::new()),
+            &ShimKind::WillInstall(ref version) => ("will-install", version.to_string()),
+            &ShimKind::Unimplemented => ("unimplemented", String::new()),
+            &ShimKind::Stale(ref reason) => ("stale", reason.porcelain_name().to_string()),
+        }
+    }
 }
 
 impl Display for ShimKind {
@@ -89,6 +211,9 @@ impl Display for ShimKind {
             &ShimKind::Unimplemented => {
                 format!("{}", style("[shim not implemented!]").red().bold())
             }
+            &ShimKind::Stale(ref reason) => {
+                format!("{}", style(format!("[stale: {}]", reason)).yellow().bold())
+            }
         };
         f.write_str(&s)
     }
@@ -101,15 +226,92 @@ impl Command for Shim {
 Manage Notion shims for 3rd-party executables
 
 Usage:
-    notion shim list [options]
-    notion shim create <shimname> [options]
+    notion shim list [--stale] [--porcelain=<version>] [options]
+    notion shim create <shimname> [--target=<path>] [--force] [options]
     notion shim delete <shimname> [options]
-    notion shim auto [<path>] [options]
+    notion shim auto [<path>] [--keep-orphans] [options]
+    notion shim audit [--fix] [options]
+    notion shim conflicts [options]
+    notion shim migrate <strategy> [options]
+    notion shim export <dir> [options]
 
 Options:
-    -v, --verbose  Verbose output
-    -h, --help     Display this message
-
+    -s, --stale                Only list shims that are stale
+    --porcelain=<version>      List shims in a stable format for scripts (the
+                               only version is v1)
+    --target=<path>            Run this executable from the shim
+    --node=<version>           Run the target with a Node version matching this
+    --interpreter=<command>    Run the target as a script with this command
+    --fix                      Repair shims that link to the wrong launcher
+    --force                    Create the shim even if it shadows an executable
+    --keep-orphans             Keep shims whose binaries are no longer provided
+    -v, --verbose              Verbose output
+    -h, --help                 Display this message
+
+`notion shim create` refuses to create a shim that would shadow an executable
+of the same name elsewhere on the PATH, such as `python` or `git`, unless given
+--force. `notion shim conflicts` lists the shims that currently shadow
+executables, along with what they shadow.
+
+With --target, the shim runs the given executable instead of the one a project
+or package provides, and --node chooses the Node version it runs with; without
+--node, it runs with the current toolchain. Creating a shim that already exists
+with --target retargets it:
+
+    notion shim create mytool --target /opt/mytool/bin/mytool --node 18
+
+Targets that are scripts run through an interpreter rather than their shebang
+line, so that they work on Windows too: `node` for .js, .mjs and .cjs files,
+and `node --loader tsx` for .ts, .mts and .cts files. --interpreter chooses
+another, and an empty one runs the target directly:
+
+    notion shim create gen --target ./scripts/gen.ts --interpreter \"node -r esm\"
+
+`notion shim auto` shims the binaries of the project's direct dependencies. It
+remembers which shims it created for each project, and deletes them once the
+project no longer provides their binaries, or the project itself is gone, unless
+another project or an installed package still needs them. Use --keep-orphans to
+leave them in place.
+
+Which binaries are shimmed can be limited with glob patterns, in the
+`[autoshim]` section of the project's notion.toml or the `autoshim` key of the
+`toolchain` section of its package.json:
+
+    [autoshim]
+    include = [\"tsc\", \"eslint*\"]
+    exclude = [\"*-internal\"]
+
+Binaries matching an `exclude` pattern are never shimmed, and if there are any
+`include` patterns, only binaries matching one of them are. With --verbose, the
+binaries the patterns leave out are listed. Autoshimming warns about each new
+shim that shadows an executable, but creates it regardless.
+
+`notion shim migrate` remakes every shim that is made from a shared launcher
+with the given strategy (symlink, hardlink, or copy) and sets `shim.strategy`
+in the user configuration, so that new shims are made the same way. Hardlinks
+and copies suit filesystems where symlinks are unavailable or slow.
+
+`notion shim export` copies the current toolchain's Node and Yarn versions into
+a new directory, with a bin/ directory of their executables that can be put on
+the PATH wherever the directory is copied to, such as in a container image:
+
+    COPY toolchain/ /opt/toolchain/
+    ENV PATH=/opt/toolchain/bin:$PATH
+
+`notion shim audit` checks the version and kind of executable embedded in each
+shim against this version of Notion, and that each shim links to the launcher
+it should dispatch through. Shims that link to the wrong launcher can be
+repaired with --fix; other mismatches require reinstalling Notion.
+
+With --porcelain=v1, `notion shim list` prints each shim as a line of
+tab-separated fields, which later releases won't change:
+
+    <name>  <kind>  <detail>
+
+where <kind> is `project` or `user` for shims that run an executable, whose
+path is the <detail>; `will-install`, with the version that will be installed;
+`stale`, with the reason: `missing-target`, `outdated`, or `unprovided`; or
+`system`, `not-installed`, or `unimplemented`, with a <detail> of `-`.
 ";
 
     fn help() -> Self {
@@ -117,32 +319,87 @@ Options:
     }
 
     fn parse(
-        _: Notion,
+        notion: Notion,
         Args {
+            arg_dir,
             arg_path,
             arg_shimname,
+            arg_strategy,
+            cmd_audit,
             cmd_auto,
+            cmd_conflicts,
             cmd_create,
             cmd_delete,
+            cmd_export,
             cmd_list,
+            cmd_migrate,
+            flag_fix,
+            flag_force,
             flag_help,
+            flag_interpreter,
+            flag_keep_orphans,
+            flag_node,
+            flag_porcelain,
+            flag_stale,
+            flag_target,
             flag_verbose,
         }: Args,
     ) -> Fallible<Self> {
+        let dry_run = notion.dry_run();
+
         Ok(if flag_help {
             Shim::Help
+        } else if cmd_audit {
+            Shim::Audit {
+                fix: flag_fix,
+                dry_run,
+            }
         } else if cmd_auto {
-            if let Some(path_string) = arg_path {
-                Shim::Auto(Some(PathBuf::from(path_string)), flag_verbose)
-            } else {
-                Shim::Auto(None, flag_verbose)
+            Shim::Auto {
+                path: arg_path.map(PathBuf::from),
+                keep_orphans: flag_keep_orphans,
+                verbose: flag_verbose,
+                dry_run,
             }
+        } else if cmd_conflicts {
+            Shim::Conflicts
         } else if cmd_create {
-            Shim::Create(arg_shimname, flag_verbose)
+            Shim::Create {
+                name: arg_shimname,
+                target: flag_target.map(PathBuf::from),
+                node: match flag_node {
+                    Some(node) => Some(VersionSpec::parse(&node)?),
+                    None => None,
+                },
+                interpreter: flag_interpreter.map(|command| {
+                    command.split_whitespace().map(String::from).collect()
+                }),
+                force: flag_force,
+                verbose: flag_verbose,
+                dry_run,
+            }
         } else if cmd_delete {
-            Shim::Delete(arg_shimname, flag_verbose)
+            Shim::Delete {
+                name: arg_shimname,
+                verbose: flag_verbose,
+                dry_run,
+            }
+        } else if cmd_export {
+            Shim::Export {
+                dir: PathBuf::from(arg_dir),
+                dry_run,
+            }
+        } else if cmd_migrate {
+            Shim::Migrate {
+                strategy: arg_strategy.parse()?,
+                dry_run,
+            }
         } else if cmd_list {
-            Shim::List(flag_verbose)
+            Shim::List {
+                stale: flag_stale,
+                verbose: flag_verbose,
+                porcelain: Porcelain::parse(flag_porcelain)?,
+            }
         } else {
             // Can't happen.
             Shim::Help
@@ -154,10 +411,54 @@ Options:
 
         match self {
             Shim::Help => Help::Command(CommandName::Shim).run(session)?,
-            Shim::List(verbose) => list(session, verbose)?,
-            Shim::Create(shim_name, verbose) => create(session, shim_name, verbose)?,
-            Shim::Delete(shim_name, verbose) => delete(session, shim_name, verbose)?,
-            Shim::Auto(path, verbose) => autoshim(session, path, verbose)?,
+            Shim::List {
+                stale,
+                verbose,
+                porcelain,
+            } => list(session, stale, verbose, porcelain)?,
+            Shim::Create {
+                name,
+                target,
+                dry_run: true,
+                ..
+            } => {
+                let mut plan = shim::plan_create(&name)?;
+                if target.is_some() {
+                    plan.push(Action::Write(path::user_catalog_file()?));
+                }
+                println!("{}", plan)
+            }
+            Shim::Create {
+                name,
+                target,
+                node,
+                interpreter,
+                force,
+                verbose,
+                ..
+            } => create(session, name, target, node, interpreter, force, verbose)?,
+            Shim::Delete {
+                name,
+                dry_run: true,
+                ..
+            } => println!("{}", shim::plan_delete(&name)?),
+            Shim::Delete { name, verbose, .. } => delete(session, name, verbose)?,
+            Shim::Auto {
+                path,
+                keep_orphans,
+                verbose,
+                dry_run,
+            } => autoshim(session, path, keep_orphans, verbose, dry_run)?,
+            Shim::Audit { fix, dry_run } => audit(fix, dry_run)?,
+            Shim::Conflicts => conflicts()?,
+            Shim::Migrate { dry_run: true, .. } => {
+                let mut plan = shim::plan_migrate()?;
+                plan.push(Action::Write(path::user_config_file()?));
+                println!("{}", plan)
+            }
+            Shim::Migrate { strategy, .. } => migrate(strategy)?,
+            Shim::Export { dir, dry_run: true } => println!("{}", export::plan_export(&dir)),
+            Shim::Export { dir, .. } => export_platform(session, &dir)?,
         };
         session.add_event_end(ActivityKind::Shim, ExitCode::Success);
         Ok(())
@@ -165,29 +466,113 @@ Options:
 }
 
 // ISSUE(#143): all the logic for this should be moved to notion-core
-fn list(session: &Session, verbose: bool) -> Fallible<()> {
+fn list(
+    session: &Session,
+    stale_only: bool,
+    verbose: bool,
+    porcelain: Option<Porcelain>,
+) -> Fallible<()> {
     let shim_dir = path::shim_dir()?;
     let files = fs::read_dir(shim_dir).unknown()?;
+    let outdated = shim::is_outdated(::VERSION)?;
 
     for file in files {
         let file = file.unknown()?;
-        print_file_info(file, session, verbose)?;
+        let stale = stale_reason(session, &file, outdated)?;
+        if !stale_only || stale.is_some() {
+            print_file_info(file, stale, session, verbose, porcelain)?;
+        }
     }
     Ok(())
 }
 
-fn print_file_info(file: fs::DirEntry, session: &Session, verbose: bool) -> Fallible<()> {
+fn print_file_info(
+    file: fs::DirEntry,
+    stale: Option<StaleReason>,
+    session: &Session,
+    verbose: bool,
+    porcelain: Option<Porcelain>,
+) -> Fallible<()> {
     let shim_name = file.file_name();
-    if verbose {
-        let shim_info = resolve_shim(session, &shim_name)?;
-        println!("{} -> {}", shim_name.to_string_lossy(), shim_info);
+    if verbose || porcelain.is_some() {
+        let shim_info = match stale {
+            Some(reason) => ShimKind::Stale(reason),
+            None => resolve_shim(session, &shim_name)?,
+        };
+        match porcelain {
+            Some(porcelain) => {
+                let name = shim_name.to_string_lossy();
+                let (kind, detail) = shim_info.porcelain_fields();
+                println!("{}", porcelain.line(&[&name[..], kind, &detail[..]]));
+            }
+            None => println!("{} -> {}", shim_name.to_string_lossy(), shim_info),
+        }
     } else {
         println!("{}", shim_name.to_string_lossy());
     }
     Ok(())
 }
 
-fn create(_session: &Session, shim_name: String, _verbose: bool) -> Fallible<()> {
+/// Determines whether a shim is stale, and if so, why. Every shim is outdated if the
+/// shims were generated by another version of Notion than this one, whatever kind of
+/// file it is.
+fn stale_reason(
+    session: &Session,
+    file: &fs::DirEntry,
+    outdated: bool,
+) -> Fallible<Option<StaleReason>> {
+    let shim_path = file.path();
+
+    // shims for 3rd-party executables are symlinks to the launcher
+    if !shim_path.exists() {
+        return Ok(Some(StaleReason::MissingTarget));
+    }
+    if outdated {
+        return Ok(Some(StaleReason::Outdated));
+    }
+
+    let shim_name = file.file_name();
+    Ok(match resolve_shim(session, &shim_name)? {
+        ShimKind::Project(ref bin_path) | ShimKind::User(ref bin_path) if !bin_path.exists() => {
+            Some(StaleReason::MissingTarget)
+        }
+        ShimKind::NotInstalled if shim::is_3p_shim(&shim_name.to_string_lossy()) => {
+            Some(StaleReason::Unprovided)
+        }
+        _ => None,
+    })
+}
+
+fn create(
+    session: &mut Session,
+    shim_name: String,
+    target: Option<PathBuf>,
+    node: Option<VersionSpec>,
+    interpreter: Option<Vec<String>>,
+    force: bool,
+    _verbose: bool,
+) -> Fallible<()> {
+    if !force && fs::symlink_metadata(path::shim_file(&shim_name)?).is_err() {
+        if let Some(shadowed) = shim::conflicts(&shim_name)?.into_iter().next() {
+            throw!(ShimConflictError {
+                name: shim_name,
+                shadowed: shadowed.display().to_string(),
+            });
+        }
+    }
+
+    if let Some(target) = target {
+        let file = match fs::canonicalize(&target) {
+            Ok(ref file) if file.is_file() => file.clone(),
+            _ => throw!(NoSuchTargetError {
+                path: target.display().to_string(),
+            }),
+        };
+        let interpreter = interpreter.unwrap_or_else(|| shim::default_interpreter(&file));
+        session.create_target_shim(&shim_name, file, node.as_ref(), interpreter)?;
+        return Ok(());
+    }
+
     match shim::create(&shim_name)? {
         shim::ShimResult::AlreadyExists => throw!(ShimAlreadyExistsError {
             name: shim_name,
@@ -196,8 +581,8 @@ fn create(_session: &Session, shim_name: String, _verbose: bool) -> Fallible<()>
     }
 }
 
-fn delete(_session: &Session, shim_name: String, _verbose: bool) -> Fallible<()> {
-    match shim::delete(&shim_name)? {
+fn delete(session: &mut Session, shim_name: String, _verbose: bool) -> Fallible<()> {
+    match session.delete_shim(&shim_name)? {
         shim::ShimResult::DoesntExist => throw!(ShimDoesntExistError {
             name: shim_name,
         }),
@@ -205,23 +590,117 @@ fn delete(_session: &Session, shim_name: String, _verbose: bool) -> Fallible<()>
     }
 }
 
-fn autoshim(session: &Session, maybe_path: Option<PathBuf>, _verbose: bool) -> Fallible<()> {
-    let errors = if let Some(path) = maybe_path {
+fn audit(fix: bool, dry_run: bool) -> Fallible<()> {
+    let mut plan = Plan::new();
+    let mut remaining = 0;
+
+    for audit in shim::audit_all(::VERSION)? {
+        let mismatch = match audit.mismatch {
+            Some(mismatch) => mismatch,
+            None => continue,
+        };
+
+        if fix && mismatch.is_repairable() {
+            if dry_run {
+                plan.push(Action::Write(path::shim_file(&audit.name)?));
+            } else if shim::repair(&audit.name, &mismatch)? {
+                println!("{}: fixed (was: {})", audit.name, mismatch);
+            }
+            continue;
+        }
+
+        println!("{}: {}", audit.name, style(&mismatch).yellow());
+        remaining += 1;
+    }
+
+    if dry_run && fix {
+        println!("{}", plan);
+    }
+
+    if remaining > 0 {
+        throw!(ShimAuditError { count: remaining });
+    }
+    Ok(())
+}
+
+fn conflicts() -> Fallible<()> {
+    for conflict in shim::conflicts_3p()? {
+        println!("{} shadows:", conflict.name);
+        for file in conflict.shadowed {
+            println!("    {}", file.display());
+        }
+    }
+    Ok(())
+}
+
+fn export_platform(session: &mut Session, dir: &Path) -> Fallible<()> {
+    let image = match session.current_platform()? {
+        Some(image) => image,
+        None => throw!(NoPlatformError),
+    };
+    session.prepare_image(&image)?;
+
+    let exported = export::export(&image, dir)?;
+    println!("Exported to {}", dir.display());
+    println!("    node v{}", exported.node);
+    if let Some(ref yarn) = exported.yarn {
+        println!("    yarn v{}", yarn);
+    }
+    println!("    bin/ {}", exported.bins.join(" "));
+    Ok(())
+}
+
+fn migrate(strategy: Strategy) -> Fallible<()> {
+    let remade = shim::migrate(strategy)?;
+    config::set_value("shim.strategy", &strategy.to_string())?;
+    println!("Remade {} shim(s) as {}s", remade.len(), strategy);
+    Ok(())
+}
+
+fn autoshim(
+    session: &Session,
+    maybe_path: Option<PathBuf>,
+    keep_orphans: bool,
+    verbose: bool,
+    dry_run: bool,
+) -> Fallible<()> {
+    let project = if let Some(path) = maybe_path {
         if let Some(path_project) = Project::for_dir(&path)? {
-            path_project.autoshim()
+            Rc::new(path_project)
         } else {
             throw!(NotAPackageError {
                 path: path.to_str().unwrap().to_string(),
             })
         }
     } else if let Some(session_project) = session.project() {
-        session_project.autoshim()
+        session_project
     } else {
         throw!(NotAPackageError {
             path: ".".to_string(),
         })
     };
 
+    if verbose {
+        for name in project.autoshim_excluded() {
+            println!("Not shimming `{}`: excluded by the project's autoshim settings", name);
+        }
+    }
+
+    let errors = if dry_run {
+        let (mut plan, errors) = project.plan_autoshim(keep_orphans);
+        if !keep_orphans {
+            plan.extend(autoshim::plan_clean_orphans()?);
+        }
+        println!("{}", plan);
+        errors
+    } else {
+        let mut errors = project.autoshim(keep_orphans);
+        if !keep_orphans {
+            errors.extend(autoshim::clean_orphans());
+        }
+        errors
+    };
+
     if errors.len() == 0 {
         Ok(())
     } else {
@@ -254,10 +733,10 @@ fn is_node_version_installed(version: &Version, session: &Session) -> Fallible<b
 // figure out which version of Node is installed or configured,
 // or which version will be installed if it's not pinned by the project
 fn resolve_node_shims(session: &Session, shim_name: &OsStr) -> Fallible<ShimKind> {
-    if let Some(ref image) = session.project_platform() {
+    if let Some(ref image) = session.project_platform()? {
         if is_node_version_installed(&image.node, &session)? {
             // Node is pinned by the project - this shim will use that version
-            let mut bin_path = path::node_version_bin_dir(&image.node_str).unknown()?;
+            let mut bin_path = path::node_version_bin_dir(&image.node_key()).unknown()?;
             bin_path.push(&shim_name);
             return Ok(ShimKind::User(bin_path));
         }
@@ -274,7 +753,7 @@ fn resolve_node_shims(session: &Session, shim_name: &OsStr) -> Fallible<ShimKind
 }
 
 fn resolve_yarn_shims(session: &Session, shim_name: &OsStr) -> Fallible<ShimKind> {
-    if let Some(ref image) = session.project_platform() {
+    if let Some(ref image) = session.project_platform()? {
         if let Some(ref version) = image.yarn {
             let catalog = session.catalog()?;
             if catalog.yarn.contains(version) {
@@ -304,6 +783,11 @@ fn resolve_npx_shims(_session: &Session, _shim_name: &OsStr) -> Fallible<ShimKin
 }
 
 fn resolve_3p_shims(session: &Session, shim_name: &OsStr) -> Fallible<ShimKind> {
+    // shims created with --target run their target, whatever else provides the name
+    if let Some(target) = session.catalog()?.targets.get(&*shim_name.to_string_lossy()) {
+        return Ok(ShimKind::User(target.file.clone()));
+    }
+
     if let Some(ref project) = session.project() {
         // if this is a local executable, get the path to that
         if project.has_direct_bin(shim_name)? {
@@ -0,0 +1,92 @@
+use notion_core::prompt::{self, Selection};
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible};
+
+use Notion;
+use command::{Command, CommandName, Help, Porcelain};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    flag_porcelain: Option<String>,
+}
+
+/// The formats the versions in effect can be printed in.
+pub(crate) enum Format {
+    Human,
+    /// The frozen `tool=version source` lines of `--porcelain=v1`.
+    PorcelainV1,
+}
+
+pub(crate) enum Status {
+    Help,
+    Print(Format),
+}
+
+impl Command for Status {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Print the tool versions in effect and where they were selected
+
+Usage:
+    notion status [--porcelain=<version>]
+    notion status -h | --help
+
+Options:
+    -h, --help             Display this message
+    --porcelain=<version>  Print in a stable format for prompt plugins, editors
+                           and scripts (the only version is v1)
+
+The v1 porcelain format is frozen, so later releases won't change it; a
+different format would be a new version. Each line is a tool in effect:
+
+    <tool>=<version> <source>
+
+where <tool> is `node` or `yarn`, with Node first, <version> is the full
+version without a leading `v`, and <source> is where it was selected: `env`
+(by `notion use --session`), `project`, `directory`, or `user`. Nothing is
+printed when no version is in effect. Later releases may add lines for other
+tools, so skip lines for tools you don't recognize.
+
+Like `notion prompt`, this never asks whether to trust the current project;
+the versions pinned by a project that isn't trusted are ignored.
+";
+
+    fn help() -> Self {
+        Status::Help
+    }
+
+    fn parse(_: Notion, Args { flag_porcelain }: Args) -> Fallible<Self> {
+        Ok(Status::Print(match Porcelain::parse(flag_porcelain)? {
+            None => Format::Human,
+            Some(Porcelain::V1) => Format::PorcelainV1,
+        }))
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Status);
+        match self {
+            Status::Help => Help::Command(CommandName::Status).run(session)?,
+            Status::Print(format) => {
+                for selection in prompt::selections(session)? {
+                    println!("{}", render(&selection, &format));
+                }
+            }
+        }
+        session.add_event_end(ActivityKind::Status, ExitCode::Success);
+        Ok(())
+    }
+}
+
+fn render(selection: &Selection, format: &Format) -> String {
+    match format {
+        &Format::Human => format!(
+            "{} v{} (from {})",
+            selection.tool, selection.version, selection.source
+        ),
+        &Format::PorcelainV1 => format!(
+            "{}={} {}",
+            selection.tool, selection.version, selection.source
+        ),
+    }
+}
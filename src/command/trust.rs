@@ -0,0 +1,116 @@
+use std::env;
+use std::path::PathBuf;
+
+use notion_core::session::{ActivityKind, Session};
+use notion_core::trust::{self, Decision};
+use notion_fail::{ExitCode, Fallible, ResultExt};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_dir: Option<String>,
+    flag_remove: bool,
+    flag_list: bool,
+}
+
+pub(crate) enum Trust {
+    Help,
+    Trust(Option<PathBuf>),
+    Remove(Option<PathBuf>),
+    List,
+}
+
+impl Command for Trust {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Approve projects to pin their toolchain
+
+Usage:
+    notion trust [<dir>]
+    notion trust --remove [<dir>]
+    notion trust --list
+    notion trust -h | --help
+
+Options:
+    -h, --help     Display this message
+    --remove       Forget the decision about <dir>, so that Notion asks again
+    --list         List the directories that are trusted or declined
+
+The versions a project pins, and its autoshim settings, only take effect once
+the project is trusted, since they choose the code Notion installs and runs.
+Notion asks whether to trust a project the first time it is used from an
+interactive terminal, and otherwise ignores its pins with a warning.
+
+`notion trust` trusts <dir>, or the current project if no directory is given,
+along with every directory within it. Directories can also be trusted in bulk
+with the `allow` patterns of the `[trust]` section of the configuration, or the
+check turned off with `required = false` in the same section. How far a project
+is trusted also decides how the executables in its `node_modules/.bin` are
+sandboxed, as set in the `[sandbox]` section.
+";
+
+    fn help() -> Self {
+        Trust::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            arg_dir,
+            flag_remove,
+            flag_list,
+        }: Args,
+    ) -> Fallible<Self> {
+        let dir = arg_dir.map(PathBuf::from);
+        Ok(if flag_list {
+            Trust::List
+        } else if flag_remove {
+            Trust::Remove(dir)
+        } else {
+            Trust::Trust(dir)
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Trust);
+        match self {
+            Trust::Help => Help::Command(CommandName::Trust).run(session)?,
+            Trust::Trust(dir) => {
+                let dir = trust::decide(&target(session, dir)?, Decision::Trusted)?;
+                println!("Trusted {}", dir.display());
+            }
+            Trust::Remove(dir) => {
+                let dir = target(session, dir)?;
+                if trust::forget(&dir)? {
+                    println!("Forgot {}", dir.display());
+                } else {
+                    println!("No decision is recorded for {}", dir.display());
+                }
+            }
+            Trust::List => for (dir, decision) in trust::decisions()? {
+                let decision = match decision {
+                    Decision::Trusted => "trusted",
+                    Decision::Declined => "declined",
+                };
+                println!("{:<8} {}", decision, dir.display());
+            },
+        };
+        session.add_event_end(ActivityKind::Trust, ExitCode::Success);
+        Ok(())
+    }
+}
+
+/// Produces the directory a command applies to: the one given, or else the root of
+/// the current project, or else the current directory.
+fn target(session: &Session, dir: Option<PathBuf>) -> Fallible<PathBuf> {
+    if let Some(dir) = dir {
+        return Ok(dir);
+    }
+    match session.project() {
+        Some(project) => Ok(project.root().to_path_buf()),
+        None => env::current_dir().unknown(),
+    }
+}
@@ -0,0 +1,62 @@
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args;
+
+pub(crate) enum Update {
+    Help,
+    Update,
+}
+
+impl Command for Update {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Re-resolve range pins to the newest matching versions
+
+Usage:
+    notion update
+    notion update -h | --help
+
+Options:
+    -h, --help     Display this message
+
+A project can pin a range instead of an exact version (e.g. `\"node\": \"^18\"`),
+which resolves to the newest installed version that satisfies it. This command
+fetches the newest version that satisfies each range pin in the current project
+and reports any that changed.
+";
+
+    fn help() -> Self {
+        Update::Help
+    }
+
+    fn parse(_: Notion, _: Args) -> Fallible<Self> {
+        Ok(Update::Update)
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Update);
+        match self {
+            Update::Help => Help::Command(CommandName::Update).run(session)?,
+            Update::Update => {
+                for update in session.update_range_pins()? {
+                    if update.from == update.to {
+                        println!("{} {}: v{} (up to date)", update.tool, update.range, update.to);
+                    } else {
+                        println!(
+                            "{} {}: v{} -> v{}",
+                            update.tool, update.range, update.from, update.to
+                        );
+                    }
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Update, ExitCode::Success);
+        Ok(())
+    }
+}
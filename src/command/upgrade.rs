@@ -0,0 +1,89 @@
+use notion_core::session::{ActivityKind, Session};
+use notion_core::version::VersionSpec;
+use notion_fail::{ExitCode, Fallible};
+
+use result::ResultOptionExt;
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_tool: String,
+    flag_node: Option<String>,
+}
+
+pub(crate) enum Upgrade {
+    Help,
+    Upgrade {
+        name: String,
+        node: Option<VersionSpec>,
+        dry_run: bool,
+    },
+}
+
+impl Command for Upgrade {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Upgrade a package tool to its newest matching version
+
+Usage:
+    notion upgrade <tool> [--node=<version>]
+    notion upgrade -h | --help
+
+Options:
+    --node=<version>    Reinstall the package with this Node version, which it
+                        runs with from then on
+    -h, --help          Display this message
+
+A package tool is installed with the version or range it was named with (e.g.
+`notion install typescript@5`), and upgrading it installs the newest version
+of the package that still matches. It is reinstalled with the Node version it
+was installed with unless --node chooses another.
+
+The new version replaces the old one only once it is fully installed, and shims
+are created for any executables it adds before those for executables it drops
+are removed.
+";
+
+    fn help() -> Self {
+        Upgrade::Help
+    }
+
+    fn parse(notion: Notion, Args { arg_tool, flag_node }: Args) -> Fallible<Self> {
+        Ok(Upgrade::Upgrade {
+            name: arg_tool,
+            node: flag_node.map(VersionSpec::parse).invert()?,
+            dry_run: notion.dry_run(),
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Upgrade);
+        match self {
+            Upgrade::Help => Help::Command(CommandName::Upgrade).run(session)?,
+            Upgrade::Upgrade {
+                name,
+                dry_run: true,
+                ..
+            } => {
+                let plan = session.plan_upgrade_package(&name)?;
+                println!("{}", plan);
+            }
+            Upgrade::Upgrade { name, node, .. } => {
+                let (before, after) = session.upgrade_package(&name, node.as_ref())?;
+                if before.version == after.version && before.node == after.node {
+                    println!("{} v{} is up to date", name, after.version);
+                } else {
+                    println!(
+                        "Upgraded {} v{} (Node v{}) -> v{} (Node v{})",
+                        name, before.version, before.node, after.version, after.node
+                    );
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Upgrade, ExitCode::Success);
+        Ok(())
+    }
+}
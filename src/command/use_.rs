@@ -2,7 +2,11 @@
 // With https://github.com/rust-lang/rfcs/blob/master/text/2151-raw-identifiers.md we
 // could consider something like `r#use` instead.
 
+use semver::Version;
+
+use notion_core::manifest::PinStyle;
 use notion_core::session::{ActivityKind, Session};
+use notion_core::shell::{CurrentShell, Postscript, Shell};
 use notion_core::style::{display_error, display_unknown_error, ErrorContext};
 use notion_core::version::VersionSpec;
 use notion_fail::{ExitCode, Fallible, NotionFail};
@@ -14,6 +18,10 @@ use command::{Command, CommandName, Help};
 pub(crate) struct Args {
     arg_tool: String,
     arg_version: String,
+    flag_session: bool,
+    flag_to_file: bool,
+    flag_exact: bool,
+    flag_range: bool,
 }
 
 // error message for using tools that are not node|yarn
@@ -31,10 +39,38 @@ impl NoCustomUseError {
     }
 }
 
+/// Thrown when both --exact and --range are given.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "--exact and --range cannot be used together")]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct PinStyleFlagsError;
+
+/// Produces the pin style chosen by the --exact and --range flags, if any.
+pub(crate) fn pin_style(exact: bool, range: bool) -> Fallible<Option<PinStyle>> {
+    match (exact, range) {
+        (true, true) => throw!(PinStyleFlagsError),
+        (true, false) => Ok(Some(PinStyle::Exact)),
+        (false, true) => Ok(Some(PinStyle::Caret)),
+        (false, false) => Ok(None),
+    }
+}
+
 pub(crate) enum Use {
     Help,
-    Node(VersionSpec),
-    Yarn(VersionSpec),
+    Node {
+        version: VersionSpec,
+        style: Option<PinStyle>,
+        to_file: bool,
+        dry_run: bool,
+    },
+    Yarn {
+        version: VersionSpec,
+        style: Option<PinStyle>,
+        to_file: bool,
+        dry_run: bool,
+    },
+    ShellNode { version: VersionSpec, dry_run: bool },
+    ShellYarn { version: VersionSpec, dry_run: bool },
     Other {
         name: String,
         // not currently used
@@ -50,11 +86,28 @@ impl Command for Use {
 Select a tool for the current project's toolchain
 
 Usage:
-    notion use <tool> <version>
+    notion use [options] <tool> <version>
     notion use -h | --help
 
 Options:
+    -s, --session  Only use the version in the current shell session
+    -f, --to-file  Pin the version in notion.toml instead of package.json
+    --exact        Pin the exact version resolved, like 18.17.1
+    --range        Pin a caret range of the version resolved, like ^18.17
     -h, --help     Display this message
+
+A project's toolchain can be pinned under the \"toolchain\" key of package.json,
+or in a notion.toml file next to it, which takes precedence. Once a project has
+a notion.toml toolchain, new pins are written there too.
+
+The version is resolved to a release and pinned in the style set by the
+pin.style setting (exact, caret, or major; see `notion config`), which is exact
+by default. --exact and --range override the setting for one pin.
+
+With --session, package.json is left alone and the version is used by every
+shim run from the current shell, taking precedence over the project's toolchain.
+To go back to the project's toolchain, unset NOTION_NODE_VERSION (or
+NOTION_YARN_VERSION) or open a new shell.
 ";
 
     fn help() -> Self {
@@ -62,15 +115,40 @@ Options:
     }
 
     fn parse(
-        _: Notion,
+        notion: Notion,
         Args {
             arg_tool,
             arg_version,
+            flag_session,
+            flag_to_file,
+            flag_exact,
+            flag_range,
         }: Args,
     ) -> Fallible<Self> {
+        let dry_run = notion.dry_run();
+        let style = pin_style(flag_exact, flag_range)?;
+
         Ok(match &arg_tool[..] {
-            "node" => Use::Node(VersionSpec::parse(&arg_version)?),
-            "yarn" => Use::Yarn(VersionSpec::parse(&arg_version)?),
+            "node" if flag_session => Use::ShellNode {
+                version: VersionSpec::parse(&arg_version)?,
+                dry_run,
+            },
+            "yarn" if flag_session => Use::ShellYarn {
+                version: VersionSpec::parse(&arg_version)?,
+                dry_run,
+            },
+            "node" => Use::Node {
+                version: VersionSpec::parse(&arg_version)?,
+                style,
+                to_file: flag_to_file,
+                dry_run,
+            },
+            "yarn" => Use::Yarn {
+                version: VersionSpec::parse(&arg_version)?,
+                style,
+                to_file: flag_to_file,
+                dry_run,
+            },
             ref tool => Use::Other {
                 name: tool.to_string(),
                 version: VersionSpec::parse(&arg_version)?,
@@ -82,12 +160,74 @@ Options:
         session.add_event_start(ActivityKind::Use);
         match self {
             Use::Help => Help::Command(CommandName::Use).run(session)?,
-            Use::Node(spec) => session.pin_node_version(&spec)?,
-            Use::Yarn(spec) => session.pin_yarn_version(&spec)?,
+            Use::Node {
+                version,
+                to_file,
+                dry_run: true,
+                ..
+            } => {
+                let (_, plan) = session.plan_pin_node_version(&version, to_file)?;
+                println!("{}", plan);
+                session.add_event_end(ActivityKind::Use, ExitCode::Success);
+                return Ok(());
+            }
+            Use::Node {
+                version,
+                style,
+                to_file,
+                ..
+            } => session.pin_node_version(&version, style, to_file)?,
+            Use::Yarn {
+                version,
+                to_file,
+                dry_run: true,
+                ..
+            } => {
+                let (_, plan) = session.plan_pin_yarn_version(&version, to_file)?;
+                println!("{}", plan);
+                session.add_event_end(ActivityKind::Use, ExitCode::Success);
+                return Ok(());
+            }
+            Use::Yarn {
+                version,
+                style,
+                to_file,
+                ..
+            } => session.pin_yarn_version(&version, style, to_file)?,
+            Use::ShellNode {
+                version,
+                dry_run: true,
+            } => {
+                let (_, plan) = session.plan_use_shell_node(&version)?;
+                println!("{}", plan);
+                session.add_event_end(ActivityKind::Use, ExitCode::Success);
+                return Ok(());
+            }
+            Use::ShellNode { version, .. } => {
+                let version = session.fetch_node(&version)?.into_version();
+                use_in_shell("node", version)?;
+                session.add_event_end(ActivityKind::Use, ExitCode::Success);
+                return Ok(());
+            }
+            Use::ShellYarn {
+                version,
+                dry_run: true,
+            } => {
+                let (_, plan) = session.plan_use_shell_yarn(&version)?;
+                println!("{}", plan);
+                session.add_event_end(ActivityKind::Use, ExitCode::Success);
+                return Ok(());
+            }
+            Use::ShellYarn { version, .. } => {
+                let version = session.fetch_yarn(&version)?.into_version();
+                use_in_shell("yarn", version)?;
+                session.add_event_end(ActivityKind::Use, ExitCode::Success);
+                return Ok(());
+            }
             Use::Other { name, .. } => throw!(NoCustomUseError::new(name)),
         };
-        if let Some(project) = session.project() {
-            let errors = project.autoshim();
+        if let Some(project) = session.trusted_project()? {
+            let errors = project.autoshim(false);
 
             for error in errors {
                 if error.is_user_friendly() {
@@ -101,3 +241,13 @@ Options:
         Ok(())
     }
 }
+
+/// Selects a tool version for the current shell session by having the shell export
+/// `NOTION_<TOOL>_VERSION`, which the shims honor ahead of any toolchain.
+fn use_in_shell(tool: &str, version: Version) -> Fallible<()> {
+    let shell = CurrentShell::detect()?;
+    shell.save_postscript(&Postscript::ToolVersion {
+        tool: tool.to_string(),
+        version,
+    })
+}
@@ -0,0 +1,158 @@
+use notion_core::lockfile::{self, LOCK_FILE};
+use notion_core::session::{ActivityKind, Session};
+use notion_core::verify::verify_catalog;
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    flag_locked: bool,
+}
+
+/// Thrown when `notion verify` finds corrupted installs.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} installed version(s) are corrupted", count)]
+#[notion_fail(code = "FileSystemError")]
+struct CorruptedInstallsError {
+    count: usize,
+}
+
+/// Thrown when `notion verify --locked` is run outside a project with a lockfile.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "The current project has no notion.lock
+Run `notion lock` to create one")]
+#[notion_fail(code = "ConfigurationError")]
+struct NoLockfileError;
+
+/// Thrown when `notion verify --locked` finds the toolchain deviating from the lockfile.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "The toolchain deviates from notion.lock in {} way(s)", count)]
+#[notion_fail(code = "LockfileMismatch")]
+struct LockfileMismatchError {
+    count: usize,
+}
+
+pub(crate) enum Verify {
+    Help,
+    Verify,
+    Locked,
+}
+
+impl Command for Verify {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Check installed tool versions for corruption
+
+Usage:
+    notion verify [--locked]
+    notion verify -h | --help
+
+Options:
+    -h, --help     Display this message
+    --locked       Check the toolchain against the project's notion.lock instead
+
+Every installed Node and Yarn version is checked against the manifest of file
+digests recorded when it was installed, and its executables are checked to
+exist and be executable. Files added after installing (such as global packages)
+are ignored. Corrupted versions can be reinstalled with `notion repair`.
+
+With --locked, the project's pins, the toolchain selected in the current shell,
+and the archives the locked versions were installed from are checked against
+`notion.lock` (see `notion lock`) instead. Any deviation fails with exit code 10
+(LockfileMismatch), so that a CI step can guarantee the job runs with exactly
+the locked toolchain.
+";
+
+    fn help() -> Self {
+        Verify::Help
+    }
+
+    fn parse(_: Notion, Args { flag_locked }: Args) -> Fallible<Self> {
+        Ok(if flag_locked {
+            Verify::Locked
+        } else {
+            Verify::Verify
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Verify);
+        match self {
+            Verify::Help => Help::Command(CommandName::Verify).run(session)?,
+            Verify::Verify => verify(session)?,
+            Verify::Locked => verify_locked(session)?,
+        };
+        session.add_event_end(ActivityKind::Verify, ExitCode::Success);
+        Ok(())
+    }
+}
+
+fn verify(session: &mut Session) -> Fallible<()> {
+    let reports = verify_catalog(session.catalog()?)?;
+    let mut corrupted = 0;
+
+    for report in reports {
+        if report.problems.is_empty() {
+            println!("{} v{}: ok", report.tool, report.key);
+            continue;
+        }
+
+        if report.is_corrupted() {
+            corrupted += 1;
+            println!("{} v{}: corrupted", report.tool, report.key);
+        } else {
+            println!("{} v{}: ok (unverified)", report.tool, report.key);
+        }
+        for problem in &report.problems {
+            println!("    {}", problem);
+        }
+        if report.is_corrupted() {
+            let arch = match report.arch {
+                Some(arch) => format!(" --arch={}", arch),
+                None => String::new(),
+            };
+            println!(
+                "    run `notion repair {}@{}{}` to reinstall it",
+                report.tool, report.version, arch
+            );
+        }
+    }
+
+    if corrupted > 0 {
+        throw!(CorruptedInstallsError { count: corrupted });
+    }
+    Ok(())
+}
+
+fn verify_locked(session: &mut Session) -> Fallible<()> {
+    let project = match session.project() {
+        Some(project) => project,
+        None => throw!(NoLockfileError),
+    };
+    let lockfile = match project.lockfile() {
+        Some(lockfile) => lockfile,
+        None => throw!(NoLockfileError),
+    };
+
+    let image = session.current_platform()?;
+    let deviations = lockfile::deviations(
+        lockfile,
+        project.toolchain(),
+        image.as_ref().map(|image| &**image),
+        session.catalog()?,
+    );
+
+    if deviations.is_empty() {
+        println!("The toolchain matches {}", LOCK_FILE);
+        return Ok(());
+    }
+    for deviation in &deviations {
+        println!("{}", deviation);
+    }
+    throw!(LockfileMismatchError {
+        count: deviations.len(),
+    });
+}
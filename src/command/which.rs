@@ -0,0 +1,92 @@
+use notion_core::resolve;
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use Notion;
+use command::{Command, CommandName, Help, Porcelain};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_tool: String,
+    flag_porcelain: Option<String>,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "no executable found for {}", tool)]
+#[notion_fail(code = "ExecutableNotFound")]
+struct NoExecutableFoundError {
+    tool: String,
+}
+
+pub(crate) enum Which {
+    Help,
+    Tool(String, Option<Porcelain>),
+}
+
+impl Command for Which {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Locate the executable a Notion shim would run
+
+Usage:
+    notion which <tool> [--porcelain=<version>]
+    notion which -h | --help
+
+Options:
+    -h, --help             Display this message
+    --porcelain=<version>  Print in a stable format for scripts (the only
+                           version is v1)
+
+The tool may be `node`, `yarn`, or the name of any other shimmed executable.
+
+When NOTION_BYPASS=1 is set, shims skip all toolchain resolution and run the
+next executable of the same name on the PATH; `notion which` marks the result
+with [bypassed].
+
+With --porcelain=v1, the executable is printed as a line of tab-separated
+fields, which later releases won't change:
+
+    <path>  <bypassed>
+
+where <bypassed> is `bypassed` if NOTION_BYPASS is set, or else `-`.
+";
+
+    fn help() -> Self {
+        Which::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            arg_tool,
+            flag_porcelain,
+        }: Args,
+    ) -> Fallible<Self> {
+        Ok(Which::Tool(arg_tool, Porcelain::parse(flag_porcelain)?))
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Which);
+        match self {
+            Which::Help => Help::Command(CommandName::Which).run(session)?,
+            Which::Tool(tool, porcelain) => {
+                let target = resolve::which(session, &tool)?;
+                match target.path {
+                    Some(path) => match porcelain {
+                        Some(porcelain) => {
+                            let bypassed = if target.bypassed { "bypassed" } else { "" };
+                            let path = path.display().to_string();
+                            println!("{}", porcelain.line(&[&path[..], bypassed]));
+                        }
+                        None if target.bypassed => println!("[bypassed] {}", path.display()),
+                        None => println!("{}", path.display()),
+                    },
+                    None => throw!(NoExecutableFoundError { tool }),
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Which, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+use serde_json;
+
+use notion_core::resolve;
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible, ResultExt};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_tool: String,
+    flag_json: bool,
+}
+
+pub(crate) enum Why {
+    Help,
+    Explain { tool: String, json: bool },
+}
+
+impl Command for Why {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Explain how Notion selects the version of a tool to run
+
+Usage:
+    notion why [options] <tool>
+    notion why -h | --help
+
+Options:
+    --json         Print the decision chain as JSON
+    -h, --help     Display this message
+
+The tool may be `node`, `yarn`, or the name of any other shimmed executable.
+";
+
+    fn help() -> Self {
+        Why::Help
+    }
+
+    fn parse(_: Notion, Args { arg_tool, flag_json }: Args) -> Fallible<Self> {
+        Ok(Why::Explain {
+            tool: arg_tool,
+            json: flag_json,
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Why);
+        match self {
+            Why::Help => Help::Command(CommandName::Why).run(session)?,
+            Why::Explain { tool, json } => {
+                let explanation = resolve::explain(session, &tool)?;
+
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&explanation).unknown()?);
+                } else {
+                    println!("{}:", explanation.tool);
+                    for (index, step) in explanation.steps.iter().enumerate() {
+                        println!("  {}. {}", index + 1, step);
+                    }
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Why, ExitCode::Success);
+        Ok(())
+    }
+}
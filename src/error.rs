@@ -26,6 +26,23 @@ impl CliParseError {
     }
 }
 
+/// Thrown when `--dry-run` is given for a command that can't report its changes
+/// without making them.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "`notion {}` doesn't support --dry-run yet", command)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct DryRunUnsupportedError {
+    pub(crate) command: String,
+}
+
+/// Thrown when a `--porcelain` format is requested that doesn't exist.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Unknown porcelain format '{}': expected v1", format)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct UnknownPorcelainError {
+    pub(crate) format: String,
+}
+
 pub(crate) trait DocoptExt {
     fn is_help(&self) -> bool;
     fn is_version(&self) -> bool;
@@ -1,7 +1,10 @@
+#[macro_use]
 extern crate notion_core;
 
 use notion_core::tool::{Binary, Tool};
 
+shim_metadata!("launchbin");
+
 /// The entry point for shims to third-party binary executables.
 pub fn main() {
     Binary::launch()
@@ -0,0 +1,9 @@
+extern crate notion_core;
+
+use notion_core::tool::{Node, Tool};
+
+/// The entry point for the full `node` dispatcher, which the `node` shim hands off to
+/// when it can't run the tool itself.
+pub fn main() {
+    Node::launch()
+}
@@ -1,7 +1,10 @@
+#[macro_use]
 extern crate notion_core;
 
 use notion_core::tool::{Script, Tool};
 
+shim_metadata!("launchscript");
+
 /// The entry point for shims to third-party scripts.
 pub fn main() {
     Script::launch()
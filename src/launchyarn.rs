@@ -0,0 +1,9 @@
+extern crate notion_core;
+
+use notion_core::tool::{Tool, Yarn};
+
+/// The entry point for the full `yarn` dispatcher, which the `yarn` shim hands off to
+/// when it can't run the tool itself.
+pub fn main() {
+    Yarn::launch()
+}
@@ -12,6 +12,7 @@ extern crate semver;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
 extern crate result;
 
 mod command;
@@ -21,15 +22,23 @@ use std::string::ToString;
 
 use docopt::Docopt;
 
+use notion_core::http::{self, Rate};
+use notion_core::matrix::MatrixFailedError;
+use notion_core::plan;
 use notion_core::session::{ActivityKind, Session};
-use notion_core::style::{display_error, display_unknown_error, ErrorContext};
+use notion_core::shim;
+use notion_core::style::{self, display_error, display_unknown_error, ColorChoice, ErrorContext};
+use notion_core::timing;
 use notion_fail::{ExitCode, FailExt, Fallible, NotionError};
 
-use command::{Command, CommandName, Config, Current, Deactivate, Fetch, Help, Install, Use,
-              Version};
+use command::{Alias, Backup, Bench, Bundle, Cache, Ci, Command, CommandName, Complete, Completions,
+              Compress, Config, Current, Daemon, Deactivate, Dedupe, Env, Explain, ExportEnv,
+              Fetch, Generate, Help, Install, List, Lock, Outdated, Prompt, Repair, Run, Sbom,
+              Search, Setup, Status, Trust, Update, Upgrade, Use, Verify, Version, Which, Why};
 #[cfg(feature = "notion-dev")]
 use command::Shim;
-use error::{CliParseError, CommandUnimplementedError, DocoptExt, NotionErrorExt};
+use error::{CliParseError, CommandUnimplementedError, DocoptExt, DryRunUnsupportedError,
+            NotionErrorExt, UnknownPorcelainError};
 
 pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
@@ -39,12 +48,22 @@ struct Args {
     arg_args: Vec<String>,
     flag_version: bool,
     flag_verbose: bool,
+    flag_dry_run: bool,
+    flag_ci: bool,
+    flag_color: Option<String>,
+    flag_timings: bool,
+    flag_limit_rate: Option<String>,
 }
 
 pub(crate) struct Notion {
     command: CommandName,
     args: Vec<String>,
     verbose: bool,
+    dry_run: bool,
+    ci: bool,
+    color: Option<ColorChoice>,
+    timings: bool,
+    limit_rate: Option<Rate>,
 }
 
 impl Notion {
@@ -52,22 +71,64 @@ impl Notion {
 Notion: the hassle-free Node.js manager
 
 Usage:
-    notion [-v | --verbose] [<command> <args> ...]
+    notion [-v | --verbose] [--dry-run] [--ci] [--color=<when>] [--timings] [--limit-rate=<rate>] [<command> <args> ...]
     notion -h | --help
     notion -V | --version
 
 Options:
-    -h, --help     Display this message
-    -V, --version  Print version info and exit
-    -v, --verbose  Use verbose output
+    -h, --help      Display this message
+    -V, --version   Print version info and exit
+    -v, --verbose   Use verbose output
+    --dry-run       Print the changes a command would make without making them
+                    (commands that can't are refused rather than run)
+    --ci            Print plain, timestamped progress lines, and install the
+                    current toolchain up front (on by default when CI=true)
+    --color=<when>  Use colors: auto, always, or never (auto leaves them off
+                    when NO_COLOR is set, in CI mode, or for redirected output)
+    --timings       Print how long each phase of the command took (fetching
+                    indexes, resolving, downloading, verifying, unpacking, and
+                    updating shims) when it finishes
+    --limit-rate=<rate>
+                    Download at most this many bytes per second, like 500K or
+                    2M, across all downloads (overrides network.max-rate)
 
 Some common notion commands are:
     fetch          Fetch a tool to the local machine
     install        Install a tool in the user toolchain
+    upgrade        Upgrade a package tool to its newest matching version
+    outdated       Show tools with newer versions available
     use            Select a tool for the current project's toolchain
+    run            Run a command with each of several Node versions
     config         Get or set configuration values
     current        Display the currently activated Node version
     deactivate     Remove Notion from the current shell
+    export-env     Print the current toolchain environment
+    why            Explain how Notion selects a tool version
+    bundle         Export or import an offline bundle of tools
+    which          Locate the executable a shim would run
+    env            Print the environment shims run tools in
+    backup         Back up or restore your Notion settings
+    list           List installed or available tool versions
+    search         Search for installable tool versions
+    update         Re-resolve range pins to the newest matching versions
+    verify         Check installed tool versions for corruption
+    repair         Reinstall a damaged tool version
+    cache          Inspect the archive cache
+    dedupe         Share identical files between installed Node versions
+    compress       Compress installed versions that have not been used recently
+    setup          Integrate Notion with PowerShell, fish, or Nushell
+    ci             Set up CI jobs with the current toolchain
+    completions    Print tab completions for a shell
+    alias          Manage named aliases for tool versions
+    explain        Explain an exit code and how to recover from it
+    bench          Measure the latency shims add
+    generate       Generate container configuration for the project's toolchain
+    sbom           Print a software bill of materials for installed tools
+    trust          Approve projects to pin their toolchain
+    lock           Lock the project's toolchain to exact archives
+    prompt         Print the active Node version for a shell prompt
+    status         Print the tool versions in effect and their sources
+    daemon         Answer toolchain queries from editors over JSON-RPC
     help           Display this message
     version        Print version info and exit
 
@@ -80,6 +141,11 @@ See 'notion help <command>' for more information on a specific command.
         self.verbose
     }
 
+    /// Whether mutating commands should only report the changes they would make.
+    pub(crate) fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
     pub(crate) fn full_argv(&self) -> Vec<String> {
         let mut argv = vec![String::from("notion"), self.command.to_string()];
         let mut sub_argv = self.args.clone();
@@ -88,7 +154,72 @@ See 'notion help <command>' for more information on a specific command.
     }
 
     fn go(session: &mut Session) -> Fallible<()> {
-        Self::parse()?.run(session)
+        let notion = Self::parse()?;
+        if notion.timings {
+            timing::enable_report();
+        }
+        if let Some(rate) = notion.limit_rate {
+            http::limit_rate(rate);
+        }
+        let ci = style::init_ci_mode(notion.ci);
+        style::init_colors(notion.color.or(session.configured_color()));
+
+        // A dry run leaves the disk as it is, down to the catalog and caches it reads.
+        if notion.dry_run && !notion.supports_dry_run() {
+            throw!(DryRunUnsupportedError {
+                command: notion.command.to_string(),
+            });
+        }
+        if notion.dry_run {
+            plan::enable_dry_run();
+        }
+        session.recover_interrupted_installs();
+
+        // Upgrading Notion replaces its launchers, so regenerate the shims that link
+        // to them the first time a new version runs. This is skipped for dry runs,
+        // and a failure here shouldn't stop the command the user asked for.
+        if !notion.dry_run {
+            if let Err(err) = shim::regenerate_if_outdated(VERSION) {
+                display_error_and_usage(&err);
+            }
+        }
+
+        // In CI mode the current toolchain is installed before the command runs, so
+        // that the shims it runs later never install versions in the middle of a step.
+        // Like regenerating shims, a failure here is reported without stopping the
+        // command, which may be the one to fix it.
+        if ci && !notion.dry_run && notion.prepares_toolchain() {
+            if let Err(err) = session.prepare_current_platform() {
+                display_error_and_usage(&err);
+            }
+        }
+
+        notion.run(session)
+    }
+
+    /// Whether the command honors `--dry-run`. Commands that make changes without
+    /// planning them first are refused in a dry run; the rest either plan their changes
+    /// or make none.
+    fn supports_dry_run(&self) -> bool {
+        match self.command {
+            CommandName::Fetch
+            | CommandName::Config
+            | CommandName::Bundle
+            | CommandName::Backup
+            | CommandName::Update
+            | CommandName::Trust
+            | CommandName::Lock => false,
+            _ => true,
+        }
+    }
+
+    /// Whether the current toolchain is installed before the command runs in CI mode,
+    /// which is skipped for the commands that only print help or completions.
+    fn prepares_toolchain(&self) -> bool {
+        match self.command {
+            CommandName::Help | CommandName::Version | CommandName::Complete => false,
+            _ => true,
+        }
     }
 
     fn parse() -> Fallible<Notion> {
@@ -118,17 +249,38 @@ See 'notion help <command>' for more information on a specific command.
                 command: CommandName::Help,
                 args: vec![],
                 verbose: false,
+                dry_run: false,
+                ci: false,
+                color: None,
+                timings: false,
+                limit_rate: None,
             },
 
             Ok(Args {
                 arg_command: Some(cmd),
                 arg_args,
                 flag_verbose,
+                flag_dry_run,
+                flag_ci,
+                flag_color,
+                flag_timings,
+                flag_limit_rate,
                 ..
             }) => Notion {
                 command: cmd,
                 args: arg_args,
                 verbose: flag_verbose,
+                dry_run: flag_dry_run,
+                ci: flag_ci,
+                color: match flag_color {
+                    Some(color) => Some(color.parse::<ColorChoice>()?),
+                    None => None,
+                },
+                timings: flag_timings,
+                limit_rate: match flag_limit_rate {
+                    Some(rate) => Some(rate.parse::<Rate>()?),
+                    None => None,
+                },
             },
 
             Err(err) => {
@@ -139,6 +291,11 @@ See 'notion help <command>' for more information on a specific command.
                         command: CommandName::Help,
                         args: vec![],
                         verbose: false,
+                        dry_run: false,
+                        ci: false,
+                        color: None,
+                        timings: false,
+                        limit_rate: None,
                     }
                 }
                 // Docopt models `-V` and `--version` as errors, so this
@@ -148,6 +305,11 @@ See 'notion help <command>' for more information on a specific command.
                         command: CommandName::Version,
                         args: vec![],
                         verbose: false,
+                        dry_run: false,
+                        ci: false,
+                        color: None,
+                        timings: false,
+                        limit_rate: None,
                     }
                 }
                 // The only type that gets deserialized is CommandName. If
@@ -182,6 +344,37 @@ See 'notion help <command>' for more information on a specific command.
             CommandName::Deactivate => Deactivate::go(self, session),
             #[cfg(feature = "notion-dev")]
             CommandName::Shim => Shim::go(self, session),
+            CommandName::ExportEnv => ExportEnv::go(self, session),
+            CommandName::Why => Why::go(self, session),
+            CommandName::Bundle => Bundle::go(self, session),
+            CommandName::Which => Which::go(self, session),
+            CommandName::Env => Env::go(self, session),
+            CommandName::Backup => Backup::go(self, session),
+            CommandName::List => List::go(self, session),
+            CommandName::Search => Search::go(self, session),
+            CommandName::Update => Update::go(self, session),
+            CommandName::Verify => Verify::go(self, session),
+            CommandName::Repair => Repair::go(self, session),
+            CommandName::Cache => Cache::go(self, session),
+            CommandName::Dedupe => Dedupe::go(self, session),
+            CommandName::Compress => Compress::go(self, session),
+            CommandName::Setup => Setup::go(self, session),
+            CommandName::Completions => Completions::go(self, session),
+            CommandName::Complete => Complete::go(self, session),
+            CommandName::Alias => Alias::go(self, session),
+            CommandName::Upgrade => Upgrade::go(self, session),
+            CommandName::Outdated => Outdated::go(self, session),
+            CommandName::Run => Run::go(self, session),
+            CommandName::Ci => Ci::go(self, session),
+            CommandName::Explain => Explain::go(self, session),
+            CommandName::Bench => Bench::go(self, session),
+            CommandName::Generate => Generate::go(self, session),
+            CommandName::Sbom => Sbom::go(self, session),
+            CommandName::Trust => Trust::go(self, session),
+            CommandName::Lock => Lock::go(self, session),
+            CommandName::Prompt => Prompt::go(self, session),
+            CommandName::Status => Status::go(self, session),
+            CommandName::Daemon => Daemon::go(self, session),
             CommandName::Help => Help::go(self, session),
             CommandName::Version => Version::go(self, session),
         }
@@ -203,6 +396,10 @@ fn display_error_and_usage(err: &NotionError) {
 
 /// The entry point for the `notion` CLI.
 pub fn main() {
+    if Prompt::answer_early() {
+        ExitCode::Success.exit();
+    }
+
     let mut session = match Session::new() {
         Ok(session) => session,
         Err(err) => {
@@ -210,6 +407,9 @@ pub fn main() {
             ExitCode::UnknownError.exit();
         }
     };
+    session.select_configured_locale();
+    style::init_colors(session.configured_color());
+    style::init_progress(session.configured_progress_display());
 
     session.add_event_start(ActivityKind::Notion);
 
@@ -218,6 +418,11 @@ pub fn main() {
         Err(err) => {
             display_error_and_usage(&err);
             session.add_event_error(ActivityKind::Notion, &err);
+            // A failed matrix run exits with the exit code of the command it ran.
+            if let Some(code) = err.downcast_ref::<MatrixFailedError>().map(|e| e.code) {
+                session.add_event_tool_end(ActivityKind::Notion, code);
+                session.exit_tool(code);
+            }
             err.exit_code()
         }
     };
@@ -82,6 +82,42 @@ fn use_node_latest() {
     )
 }
 
+#[test]
+fn use_node_dry_run() {
+    let s = sandbox()
+        .package_json(BASIC_PACKAGE_JSON)
+        .node_available_versions(NODE_VERSION_INFO)
+        .node_archive_mocks()
+        .build();
+
+    assert_that!(
+        s.notion("--dry-run use node 10"),
+        execs()
+            .with_status(0)
+            .with_stdout_contains("would write [..]package.json")
+    );
+
+    assert_eq!(s.read_package_json(), BASIC_PACKAGE_JSON)
+}
+
+#[test]
+fn update_dry_run_refused() {
+    let s = sandbox()
+        .package_json(&package_json_with_pinned_node("^10.0.0"))
+        .node_available_versions(NODE_VERSION_INFO)
+        .node_archive_mocks()
+        .build();
+
+    assert_that!(
+        s.notion("--dry-run update"),
+        execs()
+            .with_status(ExitCode::InvalidArguments as i32)
+            .with_stderr_contains("error: `notion update` doesn't support --dry-run yet")
+    );
+
+    assert_eq!(s.read_package_json(), package_json_with_pinned_node("^10.0.0"))
+}
+
 #[test]
 fn use_yarn_no_node() {
     let s = sandbox()
@@ -130,6 +130,7 @@ pub struct SandboxBuilder {
     files: Vec<FileBuilder>,
     caches: Vec<CacheBuilder>,
     path_dirs: Vec<PathBuf>,
+    trusted: bool,
 }
 
 impl SandboxBuilder {
@@ -149,6 +150,7 @@ impl SandboxBuilder {
             files: vec![],
             caches: vec![],
             path_dirs: vec![notion_bin_dir()],
+            trusted: false,
         }
     }
 
@@ -167,6 +169,8 @@ impl SandboxBuilder {
     pub fn package_json(mut self, contents: &str) -> Self {
         let package_file = package_json_file(self.root());
         self.files.push(FileBuilder::new(package_file, contents));
+        // the project's pins are only honored once it is trusted
+        self.trusted = true;
         self
     }
 
@@ -333,6 +337,12 @@ impl SandboxBuilder {
             file_builder.build();
         }
 
+        if self.trusted {
+            let root = ok_or_panic!{ fs::canonicalize(self.root.root()) };
+            let contents = format!("trusted {}\n", root.display());
+            FileBuilder::new(trusted_projects_file(), &contents).build();
+        }
+
         // join dirs for the path (notion bin path is already first)
         self.root.path = env::join_paths(self.path_dirs.iter()).unwrap();
 
@@ -398,6 +408,14 @@ fn local_data_root() -> PathBuf {
 fn user_catalog_file() -> PathBuf {
     local_data_root().join("catalog.toml")
 }
+#[cfg(unix)]
+fn trusted_projects_file() -> PathBuf {
+    notion_home().join("trusted-projects")
+}
+#[cfg(windows)]
+fn trusted_projects_file() -> PathBuf {
+    local_data_root().join("trusted-projects")
+}
 
 pub struct Sandbox {
     root: PathBuf,